@@ -0,0 +1,86 @@
+//! Chunk walk over a large corrupted replay: how many offsets it tries and
+//! how long it takes. A walk that probes every byte after a failure would
+//! make at least one attempt per garbage byte.
+//!
+//! `cargo bench -p bfme2-replay-parser --bench resync`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bfme2_replay_parser::parser::ReplayParser;
+
+/// Order type ids, as the parser reads them
+const CMD_UNIT_COMMAND: u32 = 1071;
+const CMD_END_GAME: u32 = 29;
+
+/// Valid chunks between garbage runs, and the length of each run
+const CHUNKS_PER_BLOCK: u32 = 64;
+const GARBAGE_PER_BLOCK: usize = 4096;
+const BLOCKS: u32 = 2000;
+
+const RUNS: u32 = 5;
+
+fn header() -> Vec<u8> {
+    let mut data = b"BFME2RPL".to_vec();
+    data.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+    data.extend_from_slice(&1_700_003_000u32.to_le_bytes());
+    data.extend_from_slice(
+        b"M=maps/map wor rhun;S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:\
+          HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+    );
+    data.push(0);
+    data
+}
+
+fn chunk(time_code: u32, order_type: u32, player_num: u32) -> [u8; 13] {
+    let mut out = [0; 13];
+    out[..4].copy_from_slice(&time_code.to_le_bytes());
+    out[4..8].copy_from_slice(&order_type.to_le_bytes());
+    out[8..12].copy_from_slice(&player_num.to_le_bytes());
+    out
+}
+
+/// Valid chunks with runs of garbage between them (consecutive garbage bytes
+/// differ by 37, so it never forms a plausible header)
+fn corrupted_replay() -> (Vec<u8>, usize) {
+    let mut data = header();
+    let mut time_code = 0;
+    for _ in 0..BLOCKS {
+        for i in 0..CHUNKS_PER_BLOCK {
+            time_code += 2;
+            data.extend(chunk(time_code, CMD_UNIT_COMMAND, 3 + i % 2));
+        }
+        data.extend((0..GARBAGE_PER_BLOCK as u32).map(|i| (i * 37 + 11) as u8));
+    }
+    data.extend(chunk(time_code + 10, CMD_END_GAME, 3));
+    data.extend(chunk(time_code + 10, CMD_END_GAME, 3));
+    (data, BLOCKS as usize * GARBAGE_PER_BLOCK)
+}
+
+fn main() {
+    let (data, garbage) = corrupted_replay();
+    let parser = ReplayParser::default();
+
+    let mut best = Duration::MAX;
+    let mut stats = None;
+    for _ in 0..RUNS {
+        let started = Instant::now();
+        let (info, run_stats) = parser.parse_with_stats(black_box(&data));
+        best = best.min(started.elapsed());
+        assert!(info.is_ok(), "corrupted replay failed to parse");
+        stats = Some(run_stats);
+    }
+    let stats = stats.expect("at least one run");
+
+    let chunks = BLOCKS * CHUNKS_PER_BLOCK;
+    println!("buffer:          {} bytes ({garbage} garbage)", data.len());
+    println!("valid chunks:    {chunks}");
+    println!(
+        "chunk attempts:  {} (byte probing: over {})",
+        stats.chunk_attempts,
+        chunks as usize + garbage
+    );
+    println!("resyncs:         {}", stats.resync_count);
+    println!("bytes skipped:   {}", stats.bytes_skipped);
+    println!("best of {RUNS}:       {best:?}");
+}
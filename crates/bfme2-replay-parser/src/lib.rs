@@ -0,0 +1,26 @@
+//! Battle for Middle-earth II 1.00 replay parser: header, players, chunk
+//! stream and winner detection, with no runtime or rendering dependencies
+//!
+//! Builds for wasm32-unknown-unknown with `default-features = false`.
+
+pub mod models;
+pub mod parser;
+
+pub use models::{ReplayError, ReplayInfo};
+pub use parser::{Chunk, ChunkArg, ChunkIter, parse_replay};
+
+#[cfg(feature = "tracing")]
+pub(crate) use ::tracing;
+
+/// Stand-ins for the `tracing` macros when the feature is off
+#[cfg(not(feature = "tracing"))]
+mod tracing {
+    macro_rules! silent {
+        // Still borrow the arguments, so values only logged are not unused
+        ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+            let _ = ($(&$arg,)*);
+        }};
+        ($($any:tt)*) => {};
+    }
+    pub(crate) use {silent as debug, silent as warn};
+}
@@ -0,0 +1,140 @@
+use super::replay::{ReplayInfo, ReplayWarning};
+
+/// A copy of `replay` safe to show without identifying anyone: players are
+/// renamed "Player 1..N" in team then slot order, observers "Obs 1..N" in
+/// slot order, UIDs are dropped and the map path is cut to the map name.
+/// Ignored duplicate entries are named by their slot.
+/// The original is left as is, so stats keep the real names.
+pub fn anonymize_view(replay: &ReplayInfo) -> ReplayInfo {
+    let mut view = replay.clone();
+    view.map_path = view.map_name.clone();
+
+    let mut order: Vec<usize> = (0..view.players.len()).collect();
+    order.sort_by_key(|&i| (view.players[i].team, view.players[i].slot));
+    for (number, i) in order.into_iter().enumerate() {
+        let player = &mut view.players[i];
+        player.name = format!("Player {}", number + 1);
+        player.uid = None;
+    }
+
+    // Observers listed only after the lobby go last, in the order they came
+    let mut order: Vec<usize> = (0..view.spectators.len()).collect();
+    order.sort_by_key(|&i| (view.spectators[i].slot.is_none(), view.spectators[i].slot));
+    for (number, i) in order.into_iter().enumerate() {
+        let spectator = &mut view.spectators[i];
+        spectator.name = format!("Obs {}", number + 1);
+        spectator.uid = None;
+    }
+
+    for warning in &mut view.warnings {
+        match warning {
+            ReplayWarning::DuplicatePlayer { slot, name } => {
+                *name = format!("Slot {}", *slot + 1);
+            }
+            ReplayWarning::AfkPlayer { slot, name } => {
+                *name = match view.players.iter().find(|p| p.slot == *slot) {
+                    Some(player) => player.name.clone(),
+                    None => format!("Slot {}", *slot + 1),
+                };
+            }
+            _ => {}
+        }
+    }
+    view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, Player, PlayerBuilder, Spectator};
+
+    fn player(name: &str, team: i8, slot: u8) -> Player {
+        let mut player = PlayerBuilder::test_player(name, team, slot, Faction::Men);
+        player.uid = Some(format!("{:08X}", slot));
+        player
+    }
+
+    fn spectator(name: &str, slot: Option<u8>) -> Spectator {
+        Spectator {
+            name: name.to_string(),
+            uid: Some("0BADC0DE".to_string()),
+            slot,
+        }
+    }
+
+    fn names(view: &ReplayInfo) -> Vec<&str> {
+        view.players.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn players_are_numbered_by_team_then_slot() {
+        let replay = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![
+                player("Carol", 2, 1),
+                player("Alice", 1, 3),
+                player("Dave", 2, 0),
+                player("Bob", 1, 2),
+            ],
+        );
+        let view = anonymize_view(&replay);
+        // Listed in the original order, numbered by seat
+        assert_eq!(
+            names(&view),
+            ["Player 4", "Player 2", "Player 3", "Player 1"]
+        );
+        assert!(view.players.iter().all(|p| p.uid.is_none()));
+        // The same game always gets the same numbers
+        assert_eq!(names(&anonymize_view(&replay)), names(&view));
+        // The original keeps its names
+        assert_eq!(replay.players[0].name, "Carol");
+    }
+
+    #[test]
+    fn ignored_duplicates_are_named_by_slot() {
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![player("Bob", 1, 0)])
+            .with_warnings(vec![ReplayWarning::DuplicatePlayer {
+                slot: 3,
+                name: "Bob".to_string(),
+            }]);
+        assert_eq!(
+            anonymize_view(&replay).warnings,
+            [ReplayWarning::DuplicatePlayer {
+                slot: 3,
+                name: "Slot 4".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn afk_players_keep_their_number() {
+        let replay = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![player("Bob", 2, 1), player("Alice", 1, 0)],
+        )
+        .with_warnings(vec![ReplayWarning::AfkPlayer {
+            slot: 1,
+            name: "Bob".to_string(),
+        }]);
+        assert_eq!(
+            anonymize_view(&replay).warnings,
+            [ReplayWarning::AfkPlayer {
+                slot: 1,
+                name: "Player 2".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn observers_are_numbered_by_slot() {
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![]).with_spectators(vec![
+            spectator("late", None),
+            spectator("second", Some(5)),
+            spectator("first", Some(4)),
+        ]);
+        let view = anonymize_view(&replay);
+        let observers: Vec<&str> = view.spectators.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(observers, ["Obs 3", "Obs 2", "Obs 1"]);
+        assert!(view.spectators.iter().all(|s| s.uid.is_none()));
+    }
+}
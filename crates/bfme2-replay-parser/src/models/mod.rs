@@ -0,0 +1,15 @@
+mod anonymize;
+mod powers;
+mod replay;
+mod timing;
+
+pub use anonymize::anonymize_view;
+pub use powers::power_name;
+pub use replay::{
+    Alignment, Confidence, Faction, GameEnding, LobbyOptions, MapPosition, MapSpot, NameEncoding,
+    OrderKind, PLAYER_COLORS, Player, PlayerBuilder, ReplayError, ReplayInfo, ReplayWarning, Row,
+    Side, Spectator, SpotRegions, Winner, WinnerSource, WinnerVerdict, content_hash, format_clock,
+    format_date,
+};
+pub(crate) use timing::clock_now;
+pub use timing::{StageClock, TimingBreakdown};
@@ -0,0 +1,35 @@
+/// Spellbook power IDs carried in power purchase args, with display names.
+/// Empty until IDs (and the order that carries them) are confirmed from real
+/// replays: a guessed table would show object or target IDs as spells.
+const POWERS: &[(u32, &str)] = &[];
+
+/// Display name of a spellbook power ID
+pub fn power_name(id: u32) -> Option<&'static str> {
+    lookup(id, POWERS)
+}
+
+fn lookup(id: u32, powers: &[(u32, &'static str)]) -> Option<&'static str> {
+    powers
+        .iter()
+        .find(|(power, _)| *power == id)
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &[(u32, &str)] = &[(1, "Heal"), (2, "Balrog")];
+
+    #[test]
+    fn known_and_unknown_ids() {
+        assert_eq!(lookup(2, TABLE), Some("Balrog"));
+        assert_eq!(lookup(3, TABLE), None);
+    }
+
+    #[test]
+    fn no_power_names_until_ids_are_known() {
+        assert_eq!(power_name(3101), None);
+        assert_eq!(power_name(3208), None);
+    }
+}
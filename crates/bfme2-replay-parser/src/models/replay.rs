@@ -0,0 +1,1402 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Faction identifiers from BFME2 Rise of the Witch King
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Men,
+    Elves,
+    Dwarves,
+    Isengard,
+    Mordor,
+    Goblins,
+    Angmar,
+    Random, // Player picked Random, actual faction unknown
+    Unknown(u8),
+}
+
+impl Faction {
+    /// Convert faction ID from replay file to Faction enum.
+    /// Note: For players who picked "Random" in lobby, this returns their
+    /// lobby selection, NOT their actual in-game faction.
+    pub fn from_id(id: i8) -> Self {
+        match id {
+            -1 => Faction::Random,
+            0 => Faction::Men,
+            1 => Faction::Goblins,
+            2 => Faction::Dwarves,
+            3 => Faction::Isengard,
+            4 => Faction::Elves,
+            5 => Faction::Mordor,
+            6 => Faction::Angmar,
+            n if n >= 0 => Faction::Unknown(n as u8),
+            _ => Faction::Random,
+        }
+    }
+
+    /// Convert a PlayerTemplate registration index (from the PRNG random-faction pool)
+    /// to the actual in-game faction. Verified 6/6 via Frida runtime trace +
+    /// cross-referenced with played factions across all ground-truth replays.
+    pub fn from_template_id(id: i32) -> Option<Self> {
+        match id {
+            3 => Some(Faction::Men),
+            5 => Some(Faction::Elves),
+            6 => Some(Faction::Dwarves),
+            7 => Some(Faction::Isengard),
+            8 => Some(Faction::Mordor),
+            9 => Some(Faction::Goblins),
+            _ => None,
+        }
+    }
+
+    /// Which side of the war the faction fights on
+    pub fn alignment(self) -> Alignment {
+        match self {
+            Faction::Men | Faction::Elves | Faction::Dwarves => Alignment::Good,
+            Faction::Isengard | Faction::Mordor | Faction::Goblins | Faction::Angmar => {
+                Alignment::Evil
+            }
+            Faction::Random | Faction::Unknown(_) => Alignment::Unknown,
+        }
+    }
+}
+
+/// Good or evil side of a faction; unknown for Random and unknown factions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Good,
+    Evil,
+    Unknown,
+}
+
+impl fmt::Display for Faction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Faction::Men => write!(f, "Men"),
+            Faction::Elves => write!(f, "Elves"),
+            Faction::Dwarves => write!(f, "Dwarves"),
+            Faction::Isengard => write!(f, "Isengard"),
+            Faction::Mordor => write!(f, "Mordor"),
+            Faction::Goblins => write!(f, "Goblins"),
+            Faction::Angmar => write!(f, "Angmar"),
+            Faction::Random => write!(f, "Random"),
+            Faction::Unknown(n) => write!(f, "Unknown({})", n),
+        }
+    }
+}
+
+/// Vec2 position on the map (game world coordinates)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl MapPosition {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.x != 0.0 || self.y != 0.0
+    }
+}
+
+/// World-coordinate bounds splitting a map into the six named spots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotRegions {
+    /// x at or past this is the right side
+    pub x_midpoint: f32,
+    /// y above this is the top row
+    pub y_top: f32,
+    /// y above this (and not top) is the middle row
+    pub y_mid: f32,
+}
+
+impl Default for SpotRegions {
+    /// wor rhun bounds
+    fn default() -> Self {
+        Self {
+            x_midpoint: 2500.0,
+            y_top: 3000.0,
+            y_mid: 1500.0,
+        }
+    }
+}
+
+impl SpotRegions {
+    /// Classify a game world position (None for the invalid 0,0 position).
+    /// x == midpoint counts as Right; y exactly on a bound falls to the lower row.
+    pub fn classify(&self, pos: MapPosition) -> Option<MapSpot> {
+        if !pos.is_valid() {
+            return None;
+        }
+        let side = if pos.x < self.x_midpoint {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        let row = if pos.y > self.y_top {
+            Row::Top
+        } else if pos.y > self.y_mid {
+            Row::Mid
+        } else {
+            Row::Bottom
+        };
+        Some(MapSpot::from_parts(row, side))
+    }
+
+    /// Mirror a position horizontally across the x midpoint
+    pub fn flip_x(&self, pos: MapPosition) -> MapPosition {
+        MapPosition::new(2.0 * self.x_midpoint - pos.x, pos.y)
+    }
+
+    /// Whether x lies within the standard layout's width (0 to twice the midpoint)
+    pub fn contains_x(&self, pos: MapPosition) -> bool {
+        (0.0..=2.0 * self.x_midpoint).contains(&pos.x)
+    }
+}
+
+/// Map side (Left/Right of the x midpoint)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    /// The opposite side
+    pub fn other(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// Map row (Top/Mid/Bottom by y thresholds)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Row {
+    Top,
+    Mid,
+    Bottom,
+}
+
+/// One of the six named start spots on the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapSpot {
+    TopLeft,
+    MidLeft,
+    BottomLeft,
+    TopRight,
+    MidRight,
+    BottomRight,
+}
+
+impl MapSpot {
+    /// Classify a game world position with the default (wor rhun) bounds
+    pub fn from_position(pos: MapPosition) -> Option<MapSpot> {
+        SpotRegions::default().classify(pos)
+    }
+
+    fn from_parts(row: Row, side: Side) -> MapSpot {
+        match (row, side) {
+            (Row::Top, Side::Left) => MapSpot::TopLeft,
+            (Row::Mid, Side::Left) => MapSpot::MidLeft,
+            (Row::Bottom, Side::Left) => MapSpot::BottomLeft,
+            (Row::Top, Side::Right) => MapSpot::TopRight,
+            (Row::Mid, Side::Right) => MapSpot::MidRight,
+            (Row::Bottom, Side::Right) => MapSpot::BottomRight,
+        }
+    }
+
+    /// Parse a spot name case-insensitively, ignoring `_`, `-` and spaces
+    /// (e.g. "top_left", "Top Left", "TopLeft")
+    pub fn from_name(name: &str) -> Option<MapSpot> {
+        let normalized: String = name
+            .chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match normalized.as_str() {
+            "topleft" => Some(MapSpot::TopLeft),
+            "midleft" => Some(MapSpot::MidLeft),
+            "bottomleft" => Some(MapSpot::BottomLeft),
+            "topright" => Some(MapSpot::TopRight),
+            "midright" => Some(MapSpot::MidRight),
+            "bottomright" => Some(MapSpot::BottomRight),
+            _ => None,
+        }
+    }
+
+    pub fn side(self) -> Side {
+        match self {
+            MapSpot::TopLeft | MapSpot::MidLeft | MapSpot::BottomLeft => Side::Left,
+            MapSpot::TopRight | MapSpot::MidRight | MapSpot::BottomRight => Side::Right,
+        }
+    }
+
+    pub fn row(self) -> Row {
+        match self {
+            MapSpot::TopLeft | MapSpot::TopRight => Row::Top,
+            MapSpot::MidLeft | MapSpot::MidRight => Row::Mid,
+            MapSpot::BottomLeft | MapSpot::BottomRight => Row::Bottom,
+        }
+    }
+}
+
+impl fmt::Display for MapSpot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapSpot::TopLeft => write!(f, "Top Left"),
+            MapSpot::MidLeft => write!(f, "Mid Left"),
+            MapSpot::BottomLeft => write!(f, "Bottom Left"),
+            MapSpot::TopRight => write!(f, "Top Right"),
+            MapSpot::MidRight => write!(f, "Mid Right"),
+            MapSpot::BottomRight => write!(f, "Bottom Right"),
+        }
+    }
+}
+
+/// Order types tracked for first-occurrence timings (opening analysis)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderKind {
+    Build,
+    UnitCommand,
+}
+
+/// Raw chunk order_type ids mapped to their [`OrderKind`]
+const ORDER_KIND_IDS: &[(u32, OrderKind)] = &[
+    (1049, OrderKind::Build),
+    (1050, OrderKind::Build),
+    (1071, OrderKind::UnitCommand),
+];
+
+impl OrderKind {
+    /// Map a raw chunk order_type to a tracked kind (None if not tracked)
+    pub fn from_order_type(order_type: u32) -> Option<Self> {
+        ORDER_KIND_IDS
+            .iter()
+            .find(|(id, _)| *id == order_type)
+            .map(|(_, kind)| *kind)
+    }
+
+    /// Raw order_type ids belonging to this kind
+    pub fn raw_ids(self) -> impl Iterator<Item = u32> {
+        ORDER_KIND_IDS
+            .iter()
+            .filter(move |(_, kind)| *kind == self)
+            .map(|(id, _)| *id)
+    }
+}
+
+/// In-game player colors (10 colors from BFME2)
+/// Color ID from header maps to these RGB values
+pub const PLAYER_COLORS: [[u8; 3]; 10] = [
+    [70, 91, 156],   // 0: Blue
+    [158, 56, 42],   // 1: Red
+    [175, 189, 76],  // 2: Yellow
+    [62, 152, 100],  // 3: Green
+    [206, 135, 69],  // 4: Orange
+    [122, 168, 204], // 5: Teal/Cyan (Light Blue)
+    [148, 116, 183], // 6: Purple
+    [204, 159, 188], // 7: Pink/Magenta
+    [100, 100, 100], // 8: Gray
+    [226, 226, 226], // 9: White
+];
+
+/// Text encoding a player's header entry was decoded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameEncoding {
+    #[default]
+    Utf8,
+    Windows1254,
+    Windows1251,
+    Windows1250,
+}
+
+impl fmt::Display for NameEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameEncoding::Utf8 => write!(f, "UTF-8"),
+            NameEncoding::Windows1254 => write!(f, "windows-1254"),
+            NameEncoding::Windows1251 => write!(f, "windows-1251"),
+            NameEncoding::Windows1250 => write!(f, "windows-1250"),
+        }
+    }
+}
+
+/// Player information extracted from replay
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub name: String,
+    #[allow(dead_code)]
+    pub uid: Option<String>, // 8-char hex UID from header
+    pub team: i8,
+    pub team_raw: i8, // Original team value from header
+    pub slot: u8,     // Slot index in lobby
+    pub faction: Faction,
+    #[allow(dead_code)]
+    pub color_id: i8, // -1 = random
+    pub color_rgb: [u8; 3],                // Resolved RGB color
+    pub map_position: Option<MapPosition>, // Position on map from first building
+    pub spot: Option<MapSpot>,             // Named spot classified from map_position
+    pub actual_faction: Option<Faction>,   // For Random players, their actual faction
+    /// First chunk timecode per tracked order kind
+    pub first_actions: HashMap<OrderKind, u32>,
+    /// Encoding the name was decoded with (for diagnosing garbled names)
+    pub name_encoding: NameEncoding,
+    /// Spellbook powers bought, as (name, seconds into the game), in order
+    pub powers_used: Vec<(String, u32)>,
+    /// Issued fewer commands than the parser's AFK threshold (never left
+    /// the loading screen); left out of the defeat-based winner checks
+    pub afk: bool,
+    /// Gameplay commands issued over the whole game
+    pub command_count: u32,
+    /// Build orders issued, and how many distinct building types they placed
+    pub build_orders: u32,
+    pub building_types: u32,
+    /// Seconds into the game the player was defeated, if they were
+    pub defeated_at_secs: Option<u32>,
+}
+
+/// Builder for constructing a `Player` with named fields
+pub struct PlayerBuilder {
+    pub name: String,
+    pub uid: Option<String>,
+    pub team: i8,
+    pub team_raw: i8,
+    pub slot: u8,
+    pub faction: Faction,
+    pub color_id: i8,
+    pub color_rgb: [u8; 3],
+}
+
+impl PlayerBuilder {
+    pub fn build(self) -> Player {
+        Player {
+            name: self.name,
+            uid: self.uid,
+            team: self.team,
+            team_raw: self.team_raw,
+            slot: self.slot,
+            faction: self.faction,
+            color_id: self.color_id,
+            color_rgb: self.color_rgb,
+            map_position: None,
+            spot: None,
+            actual_faction: None,
+            first_actions: HashMap::new(),
+            name_encoding: NameEncoding::Utf8,
+            powers_used: Vec::new(),
+            afk: false,
+            command_count: 0,
+            build_orders: 0,
+            building_types: 0,
+            defeated_at_secs: None,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl PlayerBuilder {
+    /// A test player: `name` on `team` (raw team one lower) in lobby `slot`,
+    /// without a UID and in the first player color
+    pub fn test_player(name: &str, team: i8, slot: u8, faction: Faction) -> Player {
+        PlayerBuilder {
+            name: name.to_string(),
+            uid: None,
+            team,
+            team_raw: team - 1,
+            slot,
+            faction,
+            color_id: 0,
+            color_rgb: PLAYER_COLORS[0],
+        }
+        .build()
+    }
+}
+
+impl Player {
+    /// Get the display faction (actual if known, otherwise selected)
+    pub fn display_faction(&self) -> Faction {
+        self.actual_faction.unwrap_or(self.faction)
+    }
+
+    /// Get the player's display color RGB
+    pub fn display_color(&self) -> [u8; 3] {
+        self.color_rgb
+    }
+
+    /// Timecode of the player's first order of the given kind, if any
+    pub fn first_action(&self, order: OrderKind) -> Option<u32> {
+        self.first_actions.get(&order).copied()
+    }
+
+    /// The last `limit` powers bought (the spellbook's top tiers come last),
+    /// e.g. "Eye of Sauron 9:00, Balrog 21:10"
+    pub fn powers_text(&self, limit: usize) -> Option<String> {
+        let skip = self.powers_used.len().saturating_sub(limit);
+        let text = self.powers_used[skip..]
+            .iter()
+            .map(|(name, secs)| format!("{} {}", name, format_clock(*secs)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Commands per minute over a game of `duration_secs` (None for games
+    /// under a minute or of unknown length)
+    pub fn apm(&self, duration_secs: Option<u32>) -> Option<u32> {
+        let secs = duration_secs.filter(|&s| s >= 60)?;
+        Some((u64::from(self.command_count) * 60 / u64::from(secs)) as u32)
+    }
+
+    /// "23 build orders, 7 building types", if the player built anything
+    pub fn buildings_text(&self) -> Option<String> {
+        (self.build_orders > 0).then(|| {
+            format!(
+                "{} build orders, {} building types",
+                self.build_orders, self.building_types
+            )
+        })
+    }
+
+    /// "14:02": when the player was defeated, if they were
+    pub fn defeat_text(&self) -> Option<String> {
+        self.defeated_at_secs.map(format_clock)
+    }
+}
+
+/// Winning team or result
+#[derive(Debug, Clone, PartialEq)]
+pub enum Winner {
+    LeftTeam,        // Left side team won (certain: EndGame or all-defeated)
+    RightTeam,       // Right side team won (certain: EndGame or all-defeated)
+    LikelyLeftTeam,  // Left side likely won (majority-defeated heuristic)
+    LikelyRightTeam, // Right side likely won (majority-defeated heuristic)
+    Draw,            // Both sides issued EndGame within seconds (agreed draw)
+    NotConcluded,    // Game crashed/abandoned - no Order 29 and no full team defeated
+    InProgress,      // Replay saved mid-game, before any result
+    Unknown,         // Could not determine
+}
+
+impl Winner {
+    /// Winning side, if any (certain or likely)
+    pub fn side(&self) -> Option<Side> {
+        match self {
+            Winner::LeftTeam | Winner::LikelyLeftTeam => Some(Side::Left),
+            Winner::RightTeam | Winner::LikelyRightTeam => Some(Side::Right),
+            Winner::Draw | Winner::NotConcluded | Winner::InProgress | Winner::Unknown => None,
+        }
+    }
+
+    /// Whether two copies of the same game could report these results.
+    /// A copy without a winning side (crashed or undetermined) is compatible with anything.
+    pub fn is_compatible_with(&self, other: &Winner) -> bool {
+        match (self.side(), other.side()) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    }
+
+    /// Display text for the winner
+    pub fn display_text(&self) -> &'static str {
+        match self {
+            Winner::LeftTeam => "Left Team",
+            Winner::RightTeam => "Right Team",
+            Winner::LikelyLeftTeam => "Left Team (likely)",
+            Winner::LikelyRightTeam => "Right Team (likely)",
+            Winner::Draw => "Draw",
+            Winner::NotConcluded => "Not Concluded",
+            Winner::InProgress => "Game in progress",
+            Winner::Unknown => "Unknown",
+        }
+    }
+
+    /// Winner line, e.g. "Winner: Left Team"; a draw reads "Result: Draw"
+    pub fn line_text(&self) -> String {
+        match self {
+            Winner::Draw => format!("Result: {}", self.display_text()),
+            _ => format!("Winner: {}", self.display_text()),
+        }
+    }
+}
+
+/// How sure the parser is of a winning side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    None,
+    Low,
+    High,
+    Certain,
+}
+
+impl Confidence {
+    /// One step less sure (`Low` and `None` stay as they are)
+    pub fn lowered(self) -> Self {
+        match self {
+            Confidence::Certain => Confidence::High,
+            Confidence::High | Confidence::Low => Confidence::Low,
+            Confidence::None => Confidence::None,
+        }
+    }
+}
+
+/// Rule that decided the winning side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinnerSource {
+    /// The EndGame order (Order 29)
+    EndGame,
+    /// Every player of one team defeated
+    FullDefeat,
+    /// More players defeated on one team than the other
+    MajorityDefeat,
+    /// One team stopped building well before the other
+    ActivityDropoff,
+}
+
+impl WinnerSource {
+    /// Short name shown under the winner line
+    pub fn display_text(&self) -> &'static str {
+        match self {
+            WinnerSource::EndGame => "EndGame",
+            WinnerSource::FullDefeat => "full defeat",
+            WinnerSource::MajorityDefeat => "majority defeated",
+            WinnerSource::ActivityDropoff => "activity drop-off",
+        }
+    }
+}
+
+/// Winning side as decided from the game events, with how sure that is and
+/// which rule decided it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinnerVerdict {
+    pub side: Option<Side>,
+    pub confidence: Confidence,
+    pub source: Option<WinnerSource>,
+}
+
+impl Default for WinnerVerdict {
+    fn default() -> Self {
+        Self::undetermined()
+    }
+}
+
+impl WinnerVerdict {
+    /// No rule picked a side
+    pub const fn undetermined() -> Self {
+        Self {
+            side: None,
+            confidence: Confidence::None,
+            source: None,
+        }
+    }
+
+    /// Neither side won: both agreed to end the game
+    pub const fn draw() -> Self {
+        Self {
+            side: None,
+            confidence: Confidence::Certain,
+            source: Some(WinnerSource::EndGame),
+        }
+    }
+
+    /// `side` won, as decided by `source` with `confidence`
+    pub const fn decided(side: Side, confidence: Confidence, source: WinnerSource) -> Self {
+        Self {
+            side: Some(side),
+            confidence,
+            source: Some(source),
+        }
+    }
+
+    /// The display [`Winner`]: certain verdicts name the side outright (or
+    /// a draw, without one), high and low ones as likely
+    pub fn to_winner(&self) -> Winner {
+        match (self.side, self.confidence) {
+            (None, Confidence::Certain) => Winner::Draw,
+            (None, _) | (_, Confidence::None) => Winner::Unknown,
+            (Some(Side::Left), Confidence::Certain) => Winner::LeftTeam,
+            (Some(Side::Right), Confidence::Certain) => Winner::RightTeam,
+            (Some(Side::Left), _) => Winner::LikelyLeftTeam,
+            (Some(Side::Right), _) => Winner::LikelyRightTeam,
+        }
+    }
+}
+
+/// How a concluded game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEnding {
+    Surrender,   // EndGame while a losing player was still standing
+    Elimination, // Every losing player defeated before EndGame
+    Unknown,     // No EndGame, or winner not certain
+}
+
+/// Spectator (observer) information
+#[derive(Debug, Clone)]
+pub struct Spectator {
+    pub name: String,
+    pub uid: Option<String>,
+    /// Lobby slot; `None` for observers listed only after the lobby
+    pub slot: Option<u8>,
+}
+
+/// Replay parsing error types
+#[derive(Debug, Clone)]
+pub enum ReplayError {
+    InvalidHeader,
+    /// A replay of another SAGE game, named
+    WrongGame(String),
+    UnsupportedMap(String),
+    NoPlayers,
+    ParseError(String),
+    RenderError(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InvalidHeader => write!(f, "Invalid replay file: missing BFME2RPL header"),
+            ReplayError::WrongGame(game) => write!(f, "Not a BFME2 replay: {} replay", game),
+            ReplayError::UnsupportedMap(name) => write!(f, "Unsupported map: {}", name),
+            ReplayError::NoPlayers => write!(f, "No players found in replay"),
+            ReplayError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            ReplayError::RenderError(msg) => write!(f, "Render error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Format seconds as "M:SS" or "H:MM:SS"
+pub fn format_clock(total_secs: u32) -> String {
+    let hours = total_secs / 3600;
+    let mins = (total_secs % 3600) / 60;
+    let secs = total_secs % 60;
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, mins, secs)
+    } else {
+        format!("{}:{:02}", mins, secs)
+    }
+}
+
+/// Something inconsistent in a replay that parsing worked around
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayWarning {
+    /// Lobby teams disagree with where players spawned; sides were taken
+    /// from position clusters instead
+    TeamSideMismatch,
+    /// A lobby slot used the Create-A-Hero layout (hero fields before the color)
+    CreateAHeroSlot { slot: u8 },
+    /// A lobby slot had out-of-range fields under either layout; those were
+    /// reset to random (or no team)
+    MalformedSlot { slot: u8 },
+    /// The parse ran out of time and stopped walking chunks early; positions,
+    /// factions and the winner come from the part that was read
+    Truncated,
+    /// Buildings of several factions were ordered from a slot; `faction` won
+    /// with `votes` of the `total` matched build orders (or by a fortress)
+    SplitFactionVote {
+        slot: u8,
+        faction: Faction,
+        votes: u32,
+        total: u32,
+    },
+    /// The lobby listed `name` a second time (same UID, or same name without
+    /// one) in `slot`; that entry had no chunk activity and was left out
+    DuplicatePlayer { slot: u8, name: String },
+    /// `name` in `slot` issued fewer commands than the AFK threshold; shown,
+    /// but left out of the defeat-based winner checks
+    AfkPlayer { slot: u8, name: String },
+}
+
+/// Lobby settings from the header's options section; fields the replay
+/// does not carry (or carries in an unknown form) are `None`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LobbyOptions {
+    pub starting_resources: Option<u32>,
+    /// Game speed in percent of normal
+    pub game_speed: Option<u8>,
+    pub ring_heroes: Option<bool>,
+    pub fortress_expansions: Option<bool>,
+}
+
+impl LobbyOptions {
+    /// "Res 1000 · Speed 100% · Ring heroes off": the settings that are
+    /// known, or `None` when none are
+    pub fn summary(&self) -> Option<String> {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        let parts: Vec<String> = [
+            self.starting_resources.map(|res| format!("Res {}", res)),
+            self.game_speed.map(|speed| format!("Speed {}%", speed)),
+            self.ring_heroes
+                .map(|on| format!("Ring heroes {}", on_off(on))),
+            self.fortress_expansions
+                .map(|on| format!("Expansions {}", on_off(on))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
+}
+
+/// Complete replay information
+#[derive(Debug, Clone)]
+pub struct ReplayInfo {
+    /// Cleaned map name (final path segment), used for matching and display
+    pub map_name: String,
+    /// Raw `M=` header value as found in the replay, for diagnostics
+    pub map_path: String,
+    pub players: Vec<Player>,
+    pub spectators: Vec<Spectator>,
+    pub start_time: Option<u32>, // Unix timestamp
+    pub end_time: Option<u32>,   // Unix timestamp
+    pub winner: Winner,
+    /// How the winning side was decided; `winner` is its display form, or
+    /// the game state (crashed, in progress) when no side was decided
+    pub verdict: WinnerVerdict,
+    pub ending: GameEnding,
+    pub game_crashed: bool, // No Order 29 and no full team defeated
+    /// Saved mid-game: no end time and no result yet
+    pub is_partial: bool,
+    /// The file ends mid-chunk, cut off in transfer: the rest of the game
+    /// is missing, so the result may be wrong
+    pub truncated: bool,
+    pub estimated_duration_secs: Option<u32>, // From max chunk timecode / tick rate, idle gaps excluded
+    /// Estimate before idle gaps (pauses) were excluded
+    pub raw_estimated_duration_secs: Option<u32>,
+    /// `(start_tick, left_cmds, right_cmds)` per 30-second bucket, in order
+    pub activity_buckets: Vec<(u32, u32, u32)>,
+    /// `(slot, seconds into the game)` of the earliest player defeat
+    pub first_defeat: Option<(u8, u32)>,
+    /// Seconds from the losing side's first defeat to its last, when the
+    /// winner is certain and at least two of its players fell
+    pub collapse_duration_secs: Option<u32>,
+    /// Played on a horizontally mirrored edit; spots and sides are already
+    /// flipped to the standard map's orientation
+    pub mirrored: bool,
+    pub lobby_options: LobbyOptions,
+    pub warnings: Vec<ReplayWarning>,
+}
+
+impl ReplayInfo {
+    pub fn new(map_name: String, players: Vec<Player>) -> Self {
+        Self {
+            map_path: map_name.clone(),
+            map_name,
+            players,
+            spectators: Vec::new(),
+            start_time: None,
+            end_time: None,
+            winner: Winner::Unknown,
+            verdict: WinnerVerdict::undetermined(),
+            ending: GameEnding::Unknown,
+            game_crashed: false,
+            is_partial: false,
+            truncated: false,
+            estimated_duration_secs: None,
+            raw_estimated_duration_secs: None,
+            activity_buckets: Vec::new(),
+            first_defeat: None,
+            collapse_duration_secs: None,
+            mirrored: false,
+            lobby_options: LobbyOptions::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_map_path(mut self, map_path: String) -> Self {
+        self.map_path = map_path;
+        self
+    }
+
+    pub fn with_times(mut self, start: u32, end: u32) -> Self {
+        self.start_time = Some(start);
+        self.end_time = Some(end);
+        self
+    }
+
+    pub fn with_winner(mut self, winner: Winner) -> Self {
+        self.winner = winner;
+        self
+    }
+
+    /// Set the verdict, and the winner to its display form
+    pub fn with_verdict(mut self, verdict: WinnerVerdict) -> Self {
+        self.winner = verdict.to_winner();
+        self.verdict = verdict;
+        self
+    }
+
+    pub fn with_ending(mut self, ending: GameEnding) -> Self {
+        self.ending = ending;
+        self
+    }
+
+    pub fn with_spectators(mut self, spectators: Vec<Spectator>) -> Self {
+        self.spectators = spectators;
+        self
+    }
+
+    pub fn with_game_crashed(mut self, crashed: bool) -> Self {
+        self.game_crashed = crashed;
+        self
+    }
+
+    pub fn with_partial(mut self, partial: bool) -> Self {
+        self.is_partial = partial;
+        self
+    }
+
+    pub fn with_truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    pub fn with_estimated_duration(mut self, secs: Option<u32>) -> Self {
+        self.estimated_duration_secs = secs;
+        self
+    }
+
+    pub fn with_raw_estimated_duration(mut self, secs: Option<u32>) -> Self {
+        self.raw_estimated_duration_secs = secs;
+        self
+    }
+
+    pub fn with_activity_buckets(mut self, buckets: Vec<(u32, u32, u32)>) -> Self {
+        self.activity_buckets = buckets;
+        self
+    }
+
+    pub fn with_first_defeat(mut self, first_defeat: Option<(u8, u32)>) -> Self {
+        self.first_defeat = first_defeat;
+        self
+    }
+
+    pub fn with_collapse_duration(mut self, secs: Option<u32>) -> Self {
+        self.collapse_duration_secs = secs;
+        self
+    }
+
+    pub fn with_mirrored(mut self, mirrored: bool) -> Self {
+        self.mirrored = mirrored;
+        self
+    }
+
+    pub fn with_lobby_options(mut self, options: LobbyOptions) -> Self {
+        self.lobby_options = options;
+        self
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<ReplayWarning>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Content fingerprint identifying the game regardless of which client saved it:
+    /// map, sorted player UIDs (name when missing), start time to the nearest
+    /// minute and duration to the nearest 30s. FNV-1a so it's stable across builds.
+    pub fn fingerprint(&self) -> u64 {
+        let mut ids: Vec<String> = self
+            .players
+            .iter()
+            .map(|p| p.uid.clone().unwrap_or_else(|| p.name.to_lowercase()))
+            .collect();
+        ids.sort();
+
+        let mut h = Fnv1a::new();
+        h.write(self.map_name.to_lowercase().as_bytes());
+        for id in &ids {
+            h.write(&[0]);
+            h.write(id.as_bytes());
+        }
+        h.write(&[0]);
+        let start_min = self.start_time.map(|t| (u64::from(t) + 30) / 60);
+        h.write(&start_min.unwrap_or(u64::MAX).to_le_bytes());
+        let duration_30s = self.duration_seconds().map(|d| (u64::from(d) + 15) / 30);
+        h.write(&duration_30s.unwrap_or(u64::MAX).to_le_bytes());
+        h.finish()
+    }
+
+    /// Whether `other` is another copy of the same game: matching fingerprint,
+    /// confirmed by player count and compatible winners.
+    pub fn is_same_game(&self, other: &ReplayInfo) -> bool {
+        self.fingerprint() == other.fingerprint()
+            && self.players.len() == other.players.len()
+            && self.winner.is_compatible_with(&other.winner)
+    }
+
+    /// Get game duration in seconds
+    pub fn duration_seconds(&self) -> Option<u32> {
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) if end > start => Some(end - start),
+            _ => self.estimated_duration_secs,
+        }
+    }
+
+    /// Whether the displayed duration is an estimate (from chunk timecodes)
+    pub fn is_duration_estimated(&self) -> bool {
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) if end > start => false,
+            _ => self.estimated_duration_secs.is_some(),
+        }
+    }
+
+    /// Whether excluding idle gaps shortened the displayed estimate by more than 10%
+    pub fn has_large_duration_correction(&self) -> bool {
+        if !self.is_duration_estimated() {
+            return false;
+        }
+        match (
+            self.raw_estimated_duration_secs,
+            self.estimated_duration_secs,
+        ) {
+            (Some(raw), Some(corrected)) => raw.saturating_sub(corrected) * 10 > raw,
+            _ => false,
+        }
+    }
+
+    /// Format duration as "MM:SS" or "HH:MM:SS", prefixed with "~" if estimated
+    pub fn duration_formatted(&self) -> String {
+        match self.duration_seconds() {
+            Some(total_secs) => {
+                let prefix = if self.is_duration_estimated() {
+                    "~"
+                } else {
+                    ""
+                };
+                format!("{}{}", prefix, format_clock(total_secs))
+            }
+            None => "Unknown".to_string(),
+        }
+    }
+
+    /// "Bob at 12:34": who fell first and when, if anyone was defeated
+    pub fn first_defeat_text(&self) -> Option<String> {
+        let (slot, secs) = self.first_defeat?;
+        let player = self.players.iter().find(|p| p.slot == slot)?;
+        Some(format!("{} at {}", player.name, format_clock(secs)))
+    }
+
+    /// "4:12": how long the losing side held on after its first defeat
+    pub fn collapse_text(&self) -> Option<String> {
+        self.collapse_duration_secs.map(format_clock)
+    }
+
+    /// Get formatted start date as YYYY-MM-DD HH:MM
+    pub fn start_date_formatted(&self) -> String {
+        match self.start_time {
+            Some(ts) => {
+                // Convert Unix timestamp to date string
+                let secs = ts as i64;
+                // Simple date formatting without external crate
+                // Using basic calculation (not accounting for leap seconds, etc.)
+                let days_since_epoch = secs / 86400;
+                let time_of_day = (secs % 86400).max(0);
+                let hours = time_of_day / 3600;
+                let minutes = (time_of_day % 3600) / 60;
+
+                // Calculate year/month/day from days since 1970
+                let (year, month, day) = days_to_ymd(days_since_epoch as i32);
+
+                format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}",
+                    year, month, day, hours, minutes
+                )
+            }
+            None => "Unknown".to_string(),
+        }
+    }
+}
+
+/// Stable 64-bit hash of raw bytes (a replay file, an id), for correlating logs
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    let mut h = Fnv1a::new();
+    h.write(bytes);
+    h.finish()
+}
+
+/// 64-bit FNV-1a hasher (stable output, unlike `DefaultHasher`)
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// UTC calendar date of a Unix timestamp as YYYY-MM-DD
+pub fn format_date(timestamp: u32) -> String {
+    let (year, month, day) = days_to_ymd((timestamp / 86400) as i32);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Convert days since Unix epoch to year/month/day
+fn days_to_ymd(days: i32) -> (i32, u32, u32) {
+    if days < 0 {
+        return (1970, 1, 1);
+    }
+    // Days since 1970-01-01
+    let mut remaining = days;
+    let mut year = 1970;
+
+    // Find the year
+    loop {
+        let days_in_year = if is_leap_year(year) { 366 } else { 365 };
+        if remaining < days_in_year {
+            break;
+        }
+        remaining -= days_in_year;
+        year += 1;
+    }
+
+    // Find the month and day
+    let days_in_months: [i32; 12] = if is_leap_year(year) {
+        [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    } else {
+        [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    };
+
+    let mut month = 1u32;
+    for &days_in_month in &days_in_months {
+        if remaining < days_in_month {
+            break;
+        }
+        remaining -= days_in_month;
+        month += 1;
+    }
+
+    let day = (remaining + 1) as u32;
+    (year, month, day)
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_replay() -> ReplayInfo {
+        ReplayInfo::new("map wor rhun".to_string(), vec![])
+    }
+
+    fn make_player(name: &str, uid: &str) -> Player {
+        let mut player = PlayerBuilder::test_player(name, 1, 0, Faction::Men);
+        player.uid = Some(uid.to_string());
+        player
+    }
+
+    fn make_game(players: &[(&str, &str)], start: u32, end: u32, winner: Winner) -> ReplayInfo {
+        let players = players.iter().map(|(n, u)| make_player(n, u)).collect();
+        ReplayInfo::new("map wor rhun".to_string(), players)
+            .with_times(start, end)
+            .with_winner(winner)
+    }
+
+    #[test]
+    fn test_faction_alignment() {
+        for faction in [Faction::Men, Faction::Elves, Faction::Dwarves] {
+            assert_eq!(faction.alignment(), Alignment::Good, "{}", faction);
+        }
+        for faction in [
+            Faction::Isengard,
+            Faction::Mordor,
+            Faction::Goblins,
+            Faction::Angmar,
+        ] {
+            assert_eq!(faction.alignment(), Alignment::Evil, "{}", faction);
+        }
+        assert_eq!(Faction::Random.alignment(), Alignment::Unknown);
+        assert_eq!(Faction::Unknown(9).alignment(), Alignment::Unknown);
+    }
+
+    #[test]
+    fn test_lobby_options_summary_lists_known_settings() {
+        assert_eq!(LobbyOptions::default().summary(), None);
+        let options = LobbyOptions {
+            starting_resources: Some(1000),
+            game_speed: Some(100),
+            ring_heroes: None,
+            fortress_expansions: Some(false),
+        };
+        assert_eq!(
+            options.summary().as_deref(),
+            Some("Res 1000 · Speed 100% · Expansions off")
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_matches_near_identical_copies() {
+        // Host and observer copies: player order differs, start off by seconds,
+        // duration differs by a few seconds of tail.
+        let host = make_game(
+            &[("Alice", "AAAAAAAA"), ("Bob", "BBBBBBBB")],
+            1_700_000_010,
+            1_700_000_910,
+            Winner::LeftTeam,
+        );
+        let obs = make_game(
+            &[("Bob", "BBBBBBBB"), ("Alice", "AAAAAAAA")],
+            1_700_000_012,
+            1_700_000_905,
+            Winner::LikelyLeftTeam,
+        );
+        assert_eq!(host.fingerprint(), obs.fingerprint());
+        assert!(host.is_same_game(&obs));
+    }
+
+    #[test]
+    fn test_fingerprint_differs_for_different_games() {
+        let a = make_game(
+            &[("Alice", "AAAAAAAA"), ("Bob", "BBBBBBBB")],
+            1_700_000_010,
+            1_700_000_910,
+            Winner::LeftTeam,
+        );
+        let rematch = make_game(
+            &[("Alice", "AAAAAAAA"), ("Bob", "BBBBBBBB")],
+            1_700_001_500,
+            1_700_002_400,
+            Winner::LeftTeam,
+        );
+        let other_players = make_game(
+            &[("Alice", "AAAAAAAA"), ("Carol", "CCCCCCCC")],
+            1_700_000_010,
+            1_700_000_910,
+            Winner::LeftTeam,
+        );
+        assert_ne!(a.fingerprint(), rematch.fingerprint());
+        assert_ne!(a.fingerprint(), other_players.fingerprint());
+        assert!(!a.is_same_game(&rematch));
+    }
+
+    #[test]
+    fn test_same_game_requires_compatible_winner() {
+        let left = make_game(
+            &[("Alice", "AAAAAAAA"), ("Bob", "BBBBBBBB")],
+            1_700_000_010,
+            1_700_000_910,
+            Winner::LeftTeam,
+        );
+        let right = left.clone().with_winner(Winner::RightTeam);
+        let unknown = left.clone().with_winner(Winner::Unknown);
+        assert_eq!(left.fingerprint(), right.fingerprint());
+        assert!(!left.is_same_game(&right));
+        assert!(left.is_same_game(&unknown));
+    }
+
+    #[test]
+    fn test_map_spot_x_midpoint_boundary() {
+        let left = MapSpot::from_position(MapPosition::new(2499.9, 1000.0)).unwrap();
+        let right = MapSpot::from_position(MapPosition::new(2500.0, 1000.0)).unwrap();
+        assert_eq!(left, MapSpot::BottomLeft);
+        assert_eq!(right, MapSpot::BottomRight);
+        assert_eq!(left.side(), Side::Left);
+        assert_eq!(right.side(), Side::Right);
+    }
+
+    #[test]
+    fn test_flip_x_mirrors_across_the_midpoint() {
+        let regions = SpotRegions::default();
+        let flipped = regions.flip_x(MapPosition::new(1000.0, 4000.0));
+        assert_eq!((flipped.x, flipped.y), (4000.0, 4000.0));
+        let back = regions.flip_x(flipped);
+        assert_eq!(back.x, 1000.0);
+        assert_eq!(
+            regions.classify(flipped),
+            Some(MapSpot::TopRight),
+            "top left flips to top right"
+        );
+        // Out-of-bounds positions land on the other side, still out of bounds
+        let outside = MapPosition::new(-800.0, 1000.0);
+        assert!(!regions.contains_x(outside));
+        assert_eq!(regions.flip_x(outside).x, 5800.0);
+        assert!(regions.contains_x(MapPosition::new(5000.0, 0.0)));
+    }
+
+    #[test]
+    fn test_map_spot_y_boundaries() {
+        let at = |y| MapSpot::from_position(MapPosition::new(1000.0, y)).unwrap();
+        assert_eq!(at(1500.0), MapSpot::BottomLeft);
+        assert_eq!(at(1500.1), MapSpot::MidLeft);
+        assert_eq!(at(3000.0), MapSpot::MidLeft);
+        assert_eq!(at(3000.1), MapSpot::TopLeft);
+        assert_eq!(at(3000.1).row(), Row::Top);
+    }
+
+    #[test]
+    fn test_map_spot_invalid_position() {
+        assert_eq!(MapSpot::from_position(MapPosition::default()), None);
+    }
+
+    #[test]
+    fn test_player_apm_needs_a_full_minute() {
+        let mut player = PlayerBuilder::test_player("Alice", 1, 0, Faction::Men);
+        player.command_count = 900;
+        assert_eq!(player.apm(Some(600)), Some(90));
+        assert_eq!(player.apm(Some(59)), None);
+        assert_eq!(player.apm(None), None);
+
+        assert_eq!(player.buildings_text(), None);
+        player.build_orders = 23;
+        player.building_types = 7;
+        assert_eq!(
+            player.buildings_text().as_deref(),
+            Some("23 build orders, 7 building types")
+        );
+    }
+
+    #[test]
+    fn test_order_kind_mapping() {
+        assert_eq!(OrderKind::from_order_type(1049), Some(OrderKind::Build));
+        assert_eq!(OrderKind::from_order_type(1050), Some(OrderKind::Build));
+        assert_eq!(
+            OrderKind::from_order_type(1071),
+            Some(OrderKind::UnitCommand)
+        );
+        assert_eq!(OrderKind::from_order_type(29), None);
+        assert_eq!(
+            OrderKind::Build.raw_ids().collect::<Vec<_>>(),
+            vec![1049, 1050]
+        );
+    }
+
+    #[test]
+    fn test_normal_game_duration() {
+        let info = make_replay().with_times(1000, 1817);
+        assert_eq!(info.duration_seconds(), Some(817));
+        assert!(!info.is_duration_estimated());
+        assert_eq!(info.duration_formatted(), "13:37");
+    }
+
+    #[test]
+    fn test_crashed_game_estimated_duration() {
+        // Crashed game: end == start, but we have chunk timecode estimate
+        let info = make_replay()
+            .with_times(1000, 1000)
+            .with_estimated_duration(Some(780));
+        assert_eq!(info.duration_seconds(), Some(780));
+        assert!(info.is_duration_estimated());
+        assert_eq!(info.duration_formatted(), "~13:00");
+    }
+
+    #[test]
+    fn test_crashed_game_no_chunks() {
+        // Crashed game with no chunks at all
+        let info = make_replay().with_times(1000, 1000);
+        assert_eq!(info.duration_seconds(), None);
+        assert!(!info.is_duration_estimated());
+        assert_eq!(info.duration_formatted(), "Unknown");
+    }
+
+    #[test]
+    fn test_normal_game_ignores_estimate() {
+        // Normal game should use header duration even if estimate is present
+        let info = make_replay()
+            .with_times(1000, 1817)
+            .with_estimated_duration(Some(780));
+        assert_eq!(info.duration_seconds(), Some(817));
+        assert!(!info.is_duration_estimated());
+        assert_eq!(info.duration_formatted(), "13:37");
+    }
+
+    #[test]
+    fn test_large_duration_correction() {
+        let estimate = |raw, corrected| {
+            make_replay()
+                .with_times(1000, 1000)
+                .with_estimated_duration(Some(corrected))
+                .with_raw_estimated_duration(Some(raw))
+        };
+        assert!(estimate(1000, 800).has_large_duration_correction());
+        assert!(!estimate(1000, 900).has_large_duration_correction());
+        assert!(!estimate(1000, 1000).has_large_duration_correction());
+        // The header duration is shown instead, so the estimate doesn't matter
+        let timed = estimate(1000, 500).with_times(1000, 1600);
+        assert!(!timed.has_large_duration_correction());
+    }
+
+    #[test]
+    fn test_estimated_duration_with_hours() {
+        let info = make_replay()
+            .with_times(1000, 1000)
+            .with_estimated_duration(Some(3661));
+        assert_eq!(info.duration_formatted(), "~1:01:01");
+    }
+
+    #[test]
+    fn test_days_to_ymd_epoch() {
+        assert_eq!(days_to_ymd(0), (1970, 1, 1));
+    }
+
+    #[test]
+    fn test_days_to_ymd_negative() {
+        // Negative days should return epoch
+        assert_eq!(days_to_ymd(-1), (1970, 1, 1));
+        assert_eq!(days_to_ymd(-365), (1970, 1, 1));
+    }
+
+    #[test]
+    fn test_days_to_ymd_known_dates() {
+        // 2000-01-01 = day 10957 from epoch
+        assert_eq!(days_to_ymd(10957), (2000, 1, 1));
+        // 2024-02-29 = leap day
+        // Days from 1970-01-01 to 2024-02-29
+        // 54 years: need precise calculation
+        // 1970-2024: 54 years, 13 leap years (72,76,80,84,88,92,96,00,04,08,12,16,20,24)
+        // Wait, 2024-02-29 is day 19782
+        // Let's use a simpler known date: 2024-01-01 = day 19723
+        assert_eq!(days_to_ymd(19723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn test_days_to_ymd_leap_year() {
+        // 2000 is a leap year (divisible by 400)
+        assert!(is_leap_year(2000));
+        // 1900 is NOT a leap year (divisible by 100 but not 400)
+        assert!(!is_leap_year(1900));
+        // 2024 is a leap year
+        assert!(is_leap_year(2024));
+    }
+
+    #[test]
+    fn test_start_date_formatted_valid() {
+        // 2024-01-01 00:00 UTC = timestamp 1704067200
+        let info = make_replay().with_times(1704067200, 1704067200);
+        let formatted = info.start_date_formatted();
+        assert_eq!(formatted, "2024-01-01 00:00");
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        // 2024-03-12 23:59:59 UTC
+        assert_eq!(format_date(1_710_287_999), "2024-03-12");
+        assert_eq!(format_date(1_710_288_000), "2024-03-13");
+    }
+
+    #[test]
+    fn test_start_date_formatted_none() {
+        let info = make_replay();
+        assert_eq!(info.start_date_formatted(), "Unknown");
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+/// The current time, or `None` when built without the `clock` feature
+/// (`Instant::now` panics on wasm32-unknown-unknown)
+pub(crate) fn clock_now() -> Option<Instant> {
+    #[cfg(feature = "clock")]
+    return Some(Instant::now());
+    #[cfg(not(feature = "clock"))]
+    return None;
+}
+
+/// Time spent in each stage of the parse/render pipeline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingBreakdown {
+    /// Magic check, header text, player colors and factions
+    pub header: Duration,
+    /// Walking the chunk stream (excluding the raw scan)
+    pub chunk_walk: Duration,
+    /// Raw byte scan for missed end-of-game events
+    pub raw_scan: Duration,
+    /// Team sides, winner, ending and crash detection
+    pub winner: Duration,
+    /// Label placement for the image
+    pub layout: Duration,
+    /// Drawing labels and center info onto the map
+    pub drawing: Duration,
+    /// JPEG encoding
+    pub encoding: Duration,
+}
+
+impl TimingBreakdown {
+    /// Every stage as `(label, duration)`, in pipeline order
+    pub fn stages(&self) -> [(&'static str, Duration); 7] {
+        [
+            ("header", self.header),
+            ("chunk_walk", self.chunk_walk),
+            ("raw_scan", self.raw_scan),
+            ("winner", self.winner),
+            ("layout", self.layout),
+            ("drawing", self.drawing),
+            ("encoding", self.encoding),
+        ]
+    }
+}
+
+/// Lap timer for pipeline stages; reads nothing when disabled or when
+/// built without the `clock` feature
+pub struct StageClock(Option<Instant>);
+
+impl StageClock {
+    pub fn start(enabled: bool) -> Self {
+        Self(if enabled { clock_now() } else { None })
+    }
+
+    /// Time since the previous lap (zero when disabled)
+    pub fn lap(&mut self) -> Duration {
+        let Some(last) = &mut self.0 else {
+            return Duration::ZERO;
+        };
+        let Some(now) = clock_now() else {
+            return Duration::ZERO;
+        };
+        let elapsed = now - *last;
+        *last = now;
+        elapsed
+    }
+}
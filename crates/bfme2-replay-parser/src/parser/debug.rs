@@ -0,0 +1,231 @@
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::io::Write;
+
+use super::replay::{Chunk, ChunkArg, ChunkIter};
+use crate::models::ReplayError;
+
+/// Compressed size cap of a chunk dump
+pub const MAX_DUMP_BYTES: usize = 2 * 1024 * 1024;
+
+/// Uncompressed bytes written between size checks
+const FLUSH_EVERY_BYTES: usize = 32 * 1024;
+
+/// Room kept under the cap for the rows after the last size check
+const DUMP_SLACK_BYTES: usize = 2 * FLUSH_EVERY_BYTES;
+
+/// Arguments listed per row; the rest are only counted
+const MAX_ARGS_SHOWN: usize = 16;
+
+const CSV_HEADER: &str = "tick,order,player_num,args";
+
+/// A replay's decoded chunk stream as gzipped CSV, for debugging wrong results
+pub struct ChunkDump {
+    pub gzip: Vec<u8>,
+    /// Rows written
+    pub chunks: usize,
+    /// Cut short at the size cap
+    pub truncated: bool,
+}
+
+/// Dump every decoded chunk (tick, order, player_num, arg summary), capped
+/// at [`MAX_DUMP_BYTES`] compressed
+pub fn chunk_dump(data: &[u8]) -> Result<ChunkDump, ReplayError> {
+    chunk_dump_capped(data, MAX_DUMP_BYTES)
+}
+
+fn chunk_dump_capped(data: &[u8], max_bytes: usize) -> Result<ChunkDump, ReplayError> {
+    let mut iter = ChunkIter::new(data)?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let mut chunks = 0;
+    let mut truncated = false;
+    let mut unflushed = 0;
+
+    writeln!(encoder, "{}", CSV_HEADER).map_err(dump_error)?;
+    for chunk in iter.by_ref() {
+        if unflushed >= FLUSH_EVERY_BYTES {
+            encoder.flush().map_err(dump_error)?;
+            unflushed = 0;
+            if encoder.get_ref().len() + DUMP_SLACK_BYTES > max_bytes {
+                truncated = true;
+                break;
+            }
+        }
+        let row = csv_row(&chunk);
+        unflushed += row.len() + 1;
+        writeln!(encoder, "{}", row).map_err(dump_error)?;
+        chunks += 1;
+    }
+
+    if truncated {
+        writeln!(encoder, "# truncated after {} chunks", chunks).map_err(dump_error)?;
+    } else if iter.offset() < data.len() {
+        writeln!(
+            encoder,
+            "# stopped at byte {}: {} bytes left undecoded",
+            iter.offset(),
+            data.len() - iter.offset()
+        )
+        .map_err(dump_error)?;
+    }
+    let gzip = encoder.finish().map_err(dump_error)?;
+    Ok(ChunkDump {
+        gzip,
+        chunks,
+        truncated,
+    })
+}
+
+fn dump_error(e: std::io::Error) -> ReplayError {
+    ReplayError::ParseError(format!("Chunk dump failed: {}", e))
+}
+
+/// One CSV row; args are space separated so the row needs no quoting
+fn csv_row(chunk: &Chunk) -> String {
+    let mut args: Vec<String> = chunk
+        .args
+        .iter()
+        .take(MAX_ARGS_SHOWN)
+        .map(arg_summary)
+        .collect();
+    if chunk.args.len() > MAX_ARGS_SHOWN {
+        args.push(format!("+{} more", chunk.args.len() - MAX_ARGS_SHOWN));
+    }
+    format!(
+        "{},{},{},{}",
+        chunk.time_code,
+        chunk.order_type,
+        chunk.player_num,
+        args.join(" ")
+    )
+}
+
+fn arg_summary(arg: &ChunkArg) -> String {
+    match arg {
+        ChunkArg::Int(v) => v.to_string(),
+        ChunkArg::Float(v) => format!("{:.2}", v),
+        ChunkArg::Vec3(x, y, z) => format!("({:.1};{:.1};{:.1})", x, y, z),
+        ChunkArg::Other(arg_type) => format!("?{:02x}", arg_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn replay_with(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = b"BFME2RPL".to_vec();
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(b"M=maps/map wor rhun;S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0");
+        data.push(0);
+        for chunk in chunks {
+            data.extend_from_slice(chunk);
+        }
+        data
+    }
+
+    fn chunk(time_code: u32, order: u32, player_num: u32, ints: &[u32]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for v in [time_code, order, player_num] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out.extend_from_slice(&[1, 0x00, ints.len() as u8]);
+        for v in ints {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+
+    fn unzip(gzip: &[u8]) -> String {
+        let mut text = String::new();
+        GzDecoder::new(gzip).read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn rows_follow_the_chunk_stream() {
+        let mut build = Vec::new();
+        for v in [10u32, 1049, 3] {
+            build.extend_from_slice(&v.to_le_bytes());
+        }
+        build.extend_from_slice(&[2, 0x00, 1, 0x06, 1]);
+        build.extend_from_slice(&2650u32.to_le_bytes());
+        for v in [1000.0f32, 4000.0, 0.0] {
+            build.extend_from_slice(&v.to_le_bytes());
+        }
+        let data = replay_with(&[build, chunk(900, 29, 3, &[])]);
+
+        let dump = chunk_dump(&data).unwrap();
+        assert_eq!(dump.chunks, 2);
+        assert!(!dump.truncated);
+        let text = unzip(&dump.gzip);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                CSV_HEADER,
+                "10,1049,3,2650 (1000.0;4000.0;0.0)",
+                "900,29,3,"
+            ]
+        );
+    }
+
+    #[test]
+    fn undecodable_tail_is_noted() {
+        let mut data = replay_with(&[chunk(10, 1071, 3, &[1, 2])]);
+        // Player number far past the sane range
+        data.extend(chunk(20, 1071, 5000, &[1]));
+        let text = unzip(&chunk_dump(&data).unwrap().gzip);
+        assert!(text.contains("10,1071,3,1 2\n"));
+        assert!(
+            text.lines()
+                .last()
+                .unwrap()
+                .starts_with("# stopped at byte")
+        );
+    }
+
+    #[test]
+    fn long_streams_are_cut_at_the_cap() {
+        // Varied args so the stream does not compress away
+        let mut seed = 12345u32;
+        let chunks: Vec<Vec<u8>> = (0..40_000)
+            .map(|tc| {
+                let args: Vec<u32> = (0..4)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                        seed
+                    })
+                    .collect();
+                chunk(tc, 1071, 3, &args)
+            })
+            .collect();
+        let data = replay_with(&chunks);
+
+        let cap = 256 * 1024;
+        let dump = chunk_dump_capped(&data, cap).unwrap();
+        assert!(dump.truncated);
+        assert!(dump.gzip.len() <= cap, "{} bytes", dump.gzip.len());
+        assert!(dump.chunks > 0 && dump.chunks < chunks.len());
+        let text = unzip(&dump.gzip);
+        assert!(text.ends_with(&format!("# truncated after {} chunks\n", dump.chunks)));
+    }
+
+    #[test]
+    fn wide_chunks_list_only_the_first_args() {
+        let args: Vec<u32> = (0..20).collect();
+        let data = replay_with(&[chunk(5, 1071, 3, &args)]);
+        let text = unzip(&chunk_dump(&data).unwrap().gzip);
+        assert!(text.contains("12 13 14 15 +4 more\n"));
+    }
+
+    #[test]
+    fn non_replays_are_rejected() {
+        assert!(matches!(
+            chunk_dump(b"not a replay at all"),
+            Err(ReplayError::InvalidHeader)
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::models::NameEncoding;
+
+/// Single-byte code pages tried for non-UTF-8 names, in tie-break order
+const FALLBACK_ENCODINGS: [NameEncoding; 3] = [
+    NameEncoding::Windows1254,
+    NameEncoding::Windows1251,
+    NameEncoding::Windows1250,
+];
+
+/// Weight of the n-gram score relative to script coherence
+const NGRAM_WEIGHT: f32 = 0.5;
+
+/// Common name bigrams with a non-ASCII letter (Turkish, Russian, Polish), lowercase
+const COMMON_BIGRAMS: &[&str] = &[
+    // Turkish
+    "şa", "aş", "eş", "şe", "şi", "ış", "ğa", "ağ", "ğu", "ğl", "ıl", "lı", "ın", "ır", "rı", "ık",
+    "kı", "gü", "ün", "ür", "üz", "öz", "ök", "çe", "ça", "iç", "ço", "nç", // Russian
+    "ов", "ев", "ан", "ин", "ий", "ер", "ра", "ко", "ни", "на", "ор", "ст", "ал", "ей", "ми", "тр",
+    "ри", "ит", "ва", "ль", "ег", "ле", "ди", "ма", "ро", "ар", "ся", "ёт", // Polish
+    "ło", "ła", "ół", "łe", "łu", "uł", "ał", "eł", "ęd", "ąc", "ęt", "ąg", "ża", "że", "ży", "ść",
+    "ńs", "ńc", "źd", "ęk", "ąd", "ąs", "ęb", "ęs", "ąż",
+];
+
+/// Decode header text: UTF-8 when valid, otherwise the best-scoring code page
+pub(super) fn decode_best(bytes: &[u8]) -> (String, NameEncoding) {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return (s.to_string(), NameEncoding::Utf8);
+    }
+
+    let mut best: Option<(f32, String, NameEncoding)> = None;
+    for encoding in FALLBACK_ENCODINGS {
+        let text = decode_as(bytes, encoding);
+        let score = decode_score(&text);
+        if best.as_ref().is_none_or(|(s, _, _)| score > *s) {
+            best = Some((score, text, encoding));
+        }
+    }
+    let (_, text, encoding) = best.expect("at least one fallback encoding");
+    (text, encoding)
+}
+
+/// Combined plausibility of a decoded string (higher is better)
+fn decode_score(text: &str) -> f32 {
+    script_coherence(text) + NGRAM_WEIGHT * ngram_score(text)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Other,
+}
+
+fn script_of(c: char) -> Script {
+    match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Script::Latin,
+        '\u{0400}'..='\u{04FF}' => Script::Cyrillic,
+        _ => Script::Other,
+    }
+}
+
+/// Share of letters in the most common script. Non-ASCII symbols count
+/// against the score, since a wrong code page turns letters into symbols.
+fn script_coherence(text: &str) -> f32 {
+    let (mut latin, mut cyrillic, mut other, mut symbols) = (0u32, 0u32, 0u32, 0u32);
+    for c in text.chars() {
+        if c.is_alphabetic() {
+            match script_of(c) {
+                Script::Latin => latin += 1,
+                Script::Cyrillic => cyrillic += 1,
+                Script::Other => other += 1,
+            }
+        } else if !c.is_ascii() {
+            symbols += 1;
+        }
+    }
+    let total = latin + cyrillic + other + symbols;
+    if total == 0 {
+        return 1.0;
+    }
+    latin.max(cyrillic).max(other) as f32 / total as f32
+}
+
+/// Share of bigrams containing a non-ASCII letter that are common in names
+fn ngram_score(text: &str) -> f32 {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    let mut seen = 0u32;
+    let mut hits = 0u32;
+    for pair in chars.windows(2) {
+        if pair.iter().all(|c| c.is_ascii()) || !pair.iter().all(|c| c.is_alphabetic()) {
+            continue;
+        }
+        seen += 1;
+        let bigram: String = pair.iter().collect();
+        if COMMON_BIGRAMS.contains(&bigram.as_str()) {
+            hits += 1;
+        }
+    }
+    if seen == 0 {
+        0.0
+    } else {
+        hits as f32 / seen as f32
+    }
+}
+
+fn decode_as(bytes: &[u8], encoding: NameEncoding) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b < 0x80 {
+                return b as char;
+            }
+            match encoding {
+                NameEncoding::Utf8 | NameEncoding::Windows1254 => windows_1254(b),
+                NameEncoding::Windows1251 => windows_1251(b),
+                NameEncoding::Windows1250 => windows_1250(b),
+            }
+        })
+        .collect()
+}
+
+/// Windows-1254 (Turkish) 0x80..=0x9F; the rest of the upper half is
+/// Latin-1 apart from the six Turkish letters
+const WINDOWS_1254_C1: [u16; 32] = [
+    0x20AC, 0xFFFD, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0xFFFD, 0xFFFD, 0xFFFD, 0xFFFD, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0xFFFD, 0xFFFD, 0x0178,
+];
+
+/// Windows-1254 (Turkish) byte above 0x7F
+fn windows_1254(b: u8) -> char {
+    let code = match b {
+        0x80..=0x9F => WINDOWS_1254_C1[(b - 0x80) as usize] as u32,
+        0xD0 => 0x011E, // G with breve
+        0xDD => 0x0130, // I with dot above (Turkish I)
+        0xDE => 0x015E, // S with cedilla
+        0xF0 => 0x011F, // g with breve
+        0xFD => 0x0131, // dotless i (Turkish i)
+        0xFE => 0x015F, // s with cedilla
+        b => b as u32,
+    };
+    char::from_u32(code).unwrap_or('\u{FFFD}')
+}
+
+/// Windows-1251 (Cyrillic) 0x80..=0xBF; 0xC0..=0xFF is А..я in order
+const WINDOWS_1251_HIGH: [u16; 64] = [
+    0x0402, 0x0403, 0x201A, 0x0453, 0x201E, 0x2026, 0x2020, 0x2021, 0x20AC, 0x2030, 0x0409, 0x2039,
+    0x040A, 0x040C, 0x040B, 0x040F, 0x0452, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0xFFFD, 0x2122, 0x0459, 0x203A, 0x045A, 0x045C, 0x045B, 0x045F, 0x00A0, 0x040E, 0x045E, 0x0408,
+    0x00A4, 0x0490, 0x00A6, 0x00A7, 0x0401, 0x00A9, 0x0404, 0x00AB, 0x00AC, 0x00AD, 0x00AE, 0x0407,
+    0x00B0, 0x00B1, 0x0406, 0x0456, 0x0491, 0x00B5, 0x00B6, 0x00B7, 0x0451, 0x2116, 0x0454, 0x00BB,
+    0x0458, 0x0405, 0x0455, 0x0457,
+];
+
+fn windows_1251(b: u8) -> char {
+    let code = match b {
+        0xC0..=0xFF => 0x0410 + (b - 0xC0) as u32,
+        _ => WINDOWS_1251_HIGH[(b - 0x80) as usize] as u32,
+    };
+    char::from_u32(code).unwrap_or('\u{FFFD}')
+}
+
+/// Windows-1250 (Central European) 0x80..=0xFF
+const WINDOWS_1250_HIGH: [u16; 128] = [
+    0x20AC, 0xFFFD, 0x201A, 0xFFFD, 0x201E, 0x2026, 0x2020, 0x2021, 0xFFFD, 0x2030, 0x0160, 0x2039,
+    0x015A, 0x0164, 0x017D, 0x0179, 0xFFFD, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0xFFFD, 0x2122, 0x0161, 0x203A, 0x015B, 0x0165, 0x017E, 0x017A, 0x00A0, 0x02C7, 0x02D8, 0x0141,
+    0x00A4, 0x0104, 0x00A6, 0x00A7, 0x00A8, 0x00A9, 0x015E, 0x00AB, 0x00AC, 0x00AD, 0x00AE, 0x017B,
+    0x00B0, 0x00B1, 0x02DB, 0x0142, 0x00B4, 0x00B5, 0x00B6, 0x00B7, 0x00B8, 0x0105, 0x015F, 0x00BB,
+    0x013D, 0x02DD, 0x013E, 0x017C, 0x0154, 0x00C1, 0x00C2, 0x0102, 0x00C4, 0x0139, 0x0106, 0x00C7,
+    0x010C, 0x00C9, 0x0118, 0x00CB, 0x011A, 0x00CD, 0x00CE, 0x010E, 0x0110, 0x0143, 0x0147, 0x00D3,
+    0x00D4, 0x0150, 0x00D6, 0x00D7, 0x0158, 0x016E, 0x00DA, 0x0170, 0x00DC, 0x00DD, 0x0162, 0x00DF,
+    0x0155, 0x00E1, 0x00E2, 0x0103, 0x00E4, 0x013A, 0x0107, 0x00E7, 0x010D, 0x00E9, 0x0119, 0x00EB,
+    0x011B, 0x00ED, 0x00EE, 0x010F, 0x0111, 0x0144, 0x0148, 0x00F3, 0x00F4, 0x0151, 0x00F6, 0x00F7,
+    0x0159, 0x016F, 0x00FA, 0x0171, 0x00FC, 0x00FD, 0x0163, 0x02D9,
+];
+
+fn windows_1250(b: u8) -> char {
+    char::from_u32(WINDOWS_1250_HIGH[(b - 0x80) as usize] as u32).unwrap_or('\u{FFFD}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_is_kept_as_is() {
+        let (text, encoding) = decode_best("Şahin".as_bytes());
+        assert_eq!(text, "Şahin");
+        assert_eq!(encoding, NameEncoding::Utf8);
+    }
+
+    #[test]
+    fn turkish_names_decode_as_windows_1254() {
+        // "Şahin" and "Güneş" saved by a Turkish client
+        let (text, encoding) = decode_best(b"\xDEahin");
+        assert_eq!(
+            (text.as_str(), encoding),
+            ("Şahin", NameEncoding::Windows1254)
+        );
+        let (text, encoding) = decode_best(b"G\xFCne\xFE");
+        assert_eq!(
+            (text.as_str(), encoding),
+            ("Güneş", NameEncoding::Windows1254)
+        );
+    }
+
+    #[test]
+    fn windows_1254_maps_its_own_upper_half() {
+        // 0x8A/0x9A are S/s with caron; S/s with cedilla are 0xDE/0xFE
+        assert_eq!(
+            decode_as(b"\x8Atefan \x9Aimek", NameEncoding::Windows1254),
+            "Štefan šimek"
+        );
+        assert_eq!(
+            decode_as(b"\xDE\xFE\xD0\xF0\xDD\xFD", NameEncoding::Windows1254),
+            "ŞşĞğİı"
+        );
+        assert_eq!(
+            decode_as(b"\x80\x93\x94\x81", NameEncoding::Windows1254),
+            "€“”\u{FFFD}"
+        );
+        assert_eq!(decode_as(b"\xC7\xFC", NameEncoding::Windows1254), "Çü");
+    }
+
+    #[test]
+    fn russian_names_decode_as_windows_1251() {
+        // "Дмитрий" is all Latin letters in 1254 ("Äìèòğèé"), so script
+        // coherence ties and the bigram model decides
+        let bytes = b"\xC4\xEC\xE8\xF2\xF0\xE8\xE9";
+        assert!(
+            (script_coherence(&decode_as(bytes, NameEncoding::Windows1254)) - 1.0).abs() < 1e-6
+        );
+        let (text, encoding) = decode_best(bytes);
+        assert_eq!(
+            (text.as_str(), encoding),
+            ("Дмитрий", NameEncoding::Windows1251)
+        );
+
+        let (text, _) = decode_best(b"\xCF\xE5\xF2\xF0\xEE\xE2_99");
+        assert_eq!(text, "Петров_99");
+    }
+
+    #[test]
+    fn polish_names_decode_as_windows_1250() {
+        // "Łukasz Żółć": 1254 turns Ł/Ż/ł into symbols
+        let (text, encoding) = decode_best(b"\xA3ukasz \xAF\xF3\xB3\xE6");
+        assert_eq!(
+            (text.as_str(), encoding),
+            ("Łukasz Żółć", NameEncoding::Windows1250)
+        );
+    }
+
+    #[test]
+    fn scores_prefer_coherent_scripts() {
+        assert!(script_coherence("Дмитрий") > script_coherence("Дмитrий"));
+        assert!(script_coherence("Łukasz") > script_coherence("£ukasz"));
+        assert_eq!(ngram_score("Alice"), 0.0);
+        assert!(ngram_score("дмитрий") > 0.5);
+    }
+}
@@ -0,0 +1,77 @@
+use super::replay::SAGE_TICKS_PER_SECOND;
+
+/// Shortest command-free stretch that can be a pause (2 minutes)
+const PAUSE_MIN_GAP_TICKS: u32 = 2 * 60 * SAGE_TICKS_PER_SECOND;
+
+/// Window before and after a gap used to judge command density (2 minutes)
+const DENSITY_WINDOW_TICKS: u32 = 2 * 60 * SAGE_TICKS_PER_SECOND;
+
+/// Commands needed in the window before a gap for the game to count as busy
+const PAUSE_MIN_PRIOR_COMMANDS: usize = 20;
+
+/// Commands needed in the window after a gap for play to count as resumed;
+/// fewer means the players idled out the end of the game
+const PAUSE_MIN_RESUMED_COMMANDS: usize = 10;
+
+/// Ticks spent in pause-like gaps: no commands from anyone for over two
+/// minutes in the middle of a busy game that picks up again afterwards.
+/// `timecodes` are player command timecodes in ascending order.
+pub(super) fn idle_gap_ticks(timecodes: &[u32]) -> u32 {
+    let count_in = |from: u32, to: u32| {
+        timecodes.partition_point(|&tc| tc <= to) - timecodes.partition_point(|&tc| tc < from)
+    };
+    timecodes
+        .windows(2)
+        .filter_map(|pair| {
+            let (before, after) = (pair[0], pair[1]);
+            let gap = after.saturating_sub(before);
+            if gap < PAUSE_MIN_GAP_TICKS {
+                return None;
+            }
+            let prior = count_in(before.saturating_sub(DENSITY_WINDOW_TICKS), before);
+            let resumed = count_in(after, after.saturating_add(DENSITY_WINDOW_TICKS));
+            (prior >= PAUSE_MIN_PRIOR_COMMANDS && resumed >= PAUSE_MIN_RESUMED_COMMANDS)
+                .then_some(gap)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One command every `every` ticks over `[from, to)`
+    fn commands(from: u32, to: u32, every: u32) -> Vec<u32> {
+        (from..to).step_by(every as usize).collect()
+    }
+
+    #[test]
+    fn pause_in_a_busy_game_is_idle_time() {
+        // 10 minutes of play, a 5 minute pause, 10 more minutes
+        let mut timecodes = commands(0, 3000, 5);
+        timecodes.extend(commands(4500, 7500, 5));
+        assert_eq!(idle_gap_ticks(&timecodes), 4500 - 2995);
+    }
+
+    #[test]
+    fn normal_lulls_are_kept() {
+        // 90 seconds of nothing is just a quiet moment
+        let mut timecodes = commands(0, 3000, 5);
+        timecodes.extend(commands(3450, 6000, 5));
+        assert_eq!(idle_gap_ticks(&timecodes), 0);
+
+        // A long gap in a slow game (one command every 30s) is not a pause
+        let mut slow = commands(0, 3000, 150);
+        slow.extend(commands(4500, 7500, 5));
+        assert_eq!(idle_gap_ticks(&slow), 0);
+    }
+
+    #[test]
+    fn end_of_game_idling_is_kept() {
+        // Busy game, players sit idle, then a couple of final commands
+        let mut timecodes = commands(0, 3000, 5);
+        timecodes.extend([4500, 4510]);
+        assert_eq!(idle_gap_ticks(&timecodes), 0);
+        assert_eq!(idle_gap_ticks(&[]), 0);
+    }
+}
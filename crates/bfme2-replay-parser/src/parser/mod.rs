@@ -0,0 +1,22 @@
+mod debug;
+mod encoding;
+mod idle;
+mod pn_mapping;
+mod preflight;
+mod prng;
+mod replay;
+#[cfg(test)]
+pub(crate) mod replay_builder;
+mod replay_parser;
+mod tick_rate;
+mod units;
+
+pub use debug::{ChunkDump, MAX_DUMP_BYTES, chunk_dump};
+pub use pn_mapping::PnMapping;
+pub use preflight::{PreflightError, detect_other_game, preflight};
+pub use replay::{
+    Chunk, ChunkArg, ChunkIter, EndGameConflict, ParseStats, has_replay_magic, header_start_time,
+    parse_replay, parse_replay_with_budget, parse_replays_multi, split_games,
+};
+pub use replay_parser::{ReplayParser, ReplayParserBuilder};
+pub use tick_rate::TickRateCalibration;
@@ -0,0 +1,188 @@
+use crate::models::{MapPosition, Side, SpotRegions};
+use crate::tracing;
+use std::collections::HashMap;
+
+/// First player_num the game assigns
+const FIRST_PN: u32 = 3;
+
+/// Score of one pair of players whose early builds agree (or disagree) with
+/// the header teams, in position chunks
+const TEAM_PAIR_WEIGHT: i64 = 20;
+
+/// How chunk player_nums were matched to lobby slots
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PnMapping {
+    /// pn 3, 4, 5, ... for every occupied slot in order, observers included
+    #[default]
+    AllSlots,
+    /// pn 3, 4, 5, ... for player slots only, skipping observers
+    PlayersOnly,
+}
+
+impl PnMapping {
+    pub fn label(self) -> &'static str {
+        match self {
+            PnMapping::AllSlots => "all slots",
+            PnMapping::PlayersOnly => "players only",
+        }
+    }
+}
+
+/// What the chunks of one player_num show, for scoring mappings
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct PnSample {
+    /// Build and unit commands carrying a position
+    pub position_chunks: u32,
+    /// Position of the first build command
+    pub first_build: Option<MapPosition>,
+}
+
+fn numbered(slots: impl Iterator<Item = u8>) -> HashMap<u32, u8> {
+    (FIRST_PN..).zip(slots).collect()
+}
+
+/// Mappings worth trying, the engine's usual one first. Skipping observers
+/// only matters when one sits before a player slot.
+pub(super) fn candidate_mappings(
+    occupied_slots: &[u8],
+    player_slots: &[u8],
+) -> Vec<(PnMapping, HashMap<u32, u8>)> {
+    let mut candidates = vec![(
+        PnMapping::AllSlots,
+        numbered(occupied_slots.iter().copied()),
+    )];
+    let players: Vec<u8> = occupied_slots
+        .iter()
+        .copied()
+        .filter(|slot| player_slots.contains(slot))
+        .collect();
+    if !occupied_slots.starts_with(&players) {
+        candidates.push((PnMapping::PlayersOnly, numbered(players.into_iter())));
+    }
+    candidates
+}
+
+/// Position chunks landing on player slots, plus or minus
+/// [`TEAM_PAIR_WEIGHT`] for each pair of players whose first builds are on
+/// the same side exactly when they share a header team
+fn score(
+    pn_to_slot: &HashMap<u32, u8>,
+    samples: &HashMap<u32, PnSample>,
+    slot_teams: &HashMap<u8, i8>,
+    regions: &SpotRegions,
+) -> i64 {
+    let mut landed = 0i64;
+    let mut sides: Vec<(i8, Side)> = Vec::new();
+    for (pn, sample) in samples {
+        let Some(&team) = pn_to_slot.get(pn).and_then(|slot| slot_teams.get(slot)) else {
+            continue;
+        };
+        landed += i64::from(sample.position_chunks);
+        if let Some(spot) = sample.first_build.and_then(|pos| regions.classify(pos)) {
+            sides.push((team, spot.side()));
+        }
+    }
+    let mut pairs = 0i64;
+    for (i, &(team_a, side_a)) in sides.iter().enumerate() {
+        for &(team_b, side_b) in &sides[i + 1..] {
+            pairs += if (team_a == team_b) == (side_a == side_b) {
+                1
+            } else {
+                -1
+            };
+        }
+    }
+    landed + pairs * TEAM_PAIR_WEIGHT
+}
+
+/// The best-scoring candidate; ties keep the earlier one
+pub(super) fn choose_mapping(
+    candidates: Vec<(PnMapping, HashMap<u32, u8>)>,
+    samples: &HashMap<u32, PnSample>,
+    slot_teams: &HashMap<u8, i8>,
+    regions: &SpotRegions,
+) -> (PnMapping, HashMap<u32, u8>) {
+    let mut best: Option<(i64, PnMapping, HashMap<u32, u8>)> = None;
+    for (mapping, pn_to_slot) in candidates {
+        let score = score(&pn_to_slot, samples, slot_teams, regions);
+        tracing::debug!("pn mapping {}: score {}", mapping.label(), score);
+        if best
+            .as_ref()
+            .is_none_or(|(best_score, _, _)| score > *best_score)
+        {
+            best = Some((score, mapping, pn_to_slot));
+        }
+    }
+    best.map(|(_, mapping, pn_to_slot)| (mapping, pn_to_slot))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(position_chunks: u32, x: f32, y: f32) -> PnSample {
+        PnSample {
+            position_chunks,
+            first_build: Some(MapPosition::new(x, y)),
+        }
+    }
+
+    #[test]
+    fn observers_after_players_need_no_alternative() {
+        let candidates = candidate_mappings(&[0, 1, 2], &[0, 1]);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].0, PnMapping::AllSlots);
+        assert_eq!(candidates[0].1, HashMap::from([(3, 0), (4, 1), (5, 2)]));
+    }
+
+    #[test]
+    fn an_observer_before_a_player_adds_a_skipping_mapping() {
+        let candidates = candidate_mappings(&[0, 1, 2, 4], &[1, 2, 4]);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[1].0, PnMapping::PlayersOnly);
+        assert_eq!(candidates[1].1, HashMap::from([(3, 1), (4, 2), (5, 4)]));
+    }
+
+    #[test]
+    fn chunks_on_player_slots_pick_the_mapping() {
+        // Slot 0 is an observer; the players sent pn 3 and 4
+        let candidates = candidate_mappings(&[0, 1, 2], &[1, 2]);
+        let slot_teams = HashMap::from([(1, 0), (2, 1)]);
+        let samples = HashMap::from([
+            (3, sample(12, 1000.0, 4000.0)),
+            (4, sample(9, 4000.0, 4000.0)),
+        ]);
+        let (mapping, pn_to_slot) =
+            choose_mapping(candidates, &samples, &slot_teams, &SpotRegions::default());
+        assert_eq!(mapping, PnMapping::PlayersOnly);
+        assert_eq!(pn_to_slot.get(&3), Some(&1));
+    }
+
+    #[test]
+    fn team_grouping_breaks_ties() {
+        let regions = SpotRegions::default();
+        let slot_teams = HashMap::from([(0, 0), (1, 0), (2, 1), (3, 1)]);
+        // Teammates build on the same side under the second mapping only
+        let samples = HashMap::from([
+            (3, sample(5, 1000.0, 4000.0)),
+            (4, sample(5, 4000.0, 4000.0)),
+            (5, sample(5, 1000.0, 1000.0)),
+            (6, sample(5, 4000.0, 1000.0)),
+        ]);
+        let straight = HashMap::from([(3, 0), (4, 1), (5, 2), (6, 3)]);
+        let crossed = HashMap::from([(3, 0), (4, 2), (5, 1), (6, 3)]);
+        assert!(
+            score(&crossed, &samples, &slot_teams, &regions)
+                > score(&straight, &samples, &slot_teams, &regions)
+        );
+
+        // Equal scores keep the usual mapping
+        let candidates = vec![
+            (PnMapping::AllSlots, straight.clone()),
+            (PnMapping::PlayersOnly, straight),
+        ];
+        let (mapping, _) = choose_mapping(candidates, &samples, &slot_teams, &regions);
+        assert_eq!(mapping, PnMapping::AllSlots);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+use super::replay::MAGIC;
+
+/// Smallest file that can hold a replay header plus a few chunks
+const MIN_REPLAY_BYTES: usize = 2 * 1024;
+
+/// The `M=` map marker sits in the header text, well inside this window
+const MAP_MARKER_WINDOW: usize = 16 * 1024;
+
+/// SAGE save games are a sequence of blocks tagged `CHUNK_<name>`
+const SAVE_GAME_TAG: &[u8] = b"CHUNK_";
+
+/// How far into the file to look for a save-game block tag
+const SAVE_GAME_TAG_WINDOW: usize = 256;
+
+/// Leading bytes of image formats users upload by mistake (screenshots)
+const IMAGE_SIGNATURES: &[&[u8]] = &[
+    b"\x89PNG\r\n\x1a\n",
+    b"\xFF\xD8\xFF",
+    b"GIF87a",
+    b"GIF89a",
+    b"BM",
+];
+
+/// Replay magic of other SAGE games, with the game named in the rejection.
+/// BFME1 files are seen both with and without the `1`.
+const OTHER_GAME_MAGICS: &[(&[u8], &str)] = &[
+    (b"BFME1RPL", "BFME1"),
+    (b"BFMERPL", "BFME1"),
+    (b"GENREP", "C&C Generals/Zero Hour"),
+    (b"C&C3 REPLAY HEADER", "C&C3/Kane's Wrath"),
+    (b"RA3 REPLAY HEADER", "Red Alert 3"),
+];
+
+/// The other SAGE game a file's magic belongs to, if any
+pub fn detect_other_game(data: &[u8]) -> Option<&'static str> {
+    OTHER_GAME_MAGICS
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|&(_, game)| game)
+}
+
+/// Why an upload was rejected before parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightError {
+    TooSmall(usize),
+    SaveGame,
+    Image,
+    BadMagic,
+    /// A replay of another SAGE game
+    WrongGame(&'static str),
+    MissingMapMarker,
+}
+
+impl fmt::Display for PreflightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreflightError::TooSmall(len) => write!(f, "File too small: {} bytes", len),
+            PreflightError::SaveGame => write!(f, "File is a save game"),
+            PreflightError::Image => write!(f, "File is an image"),
+            PreflightError::BadMagic => write!(f, "Missing BFME2RPL header"),
+            PreflightError::WrongGame(game) => write!(f, "File is a {} replay", game),
+            PreflightError::MissingMapMarker => {
+                write!(f, "No M= marker in the first {} bytes", MAP_MARKER_WINDOW)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PreflightError {}
+
+/// Cheap checks that reject files which can never parse as a replay,
+/// so the caller can skip the parse/render work and say what is wrong.
+pub fn preflight(data: &[u8]) -> Result<(), PreflightError> {
+    let has_magic = data.starts_with(MAGIC);
+    if !has_magic {
+        if let Some(game) = detect_other_game(data) {
+            return Err(PreflightError::WrongGame(game));
+        }
+        if IMAGE_SIGNATURES.iter().any(|sig| data.starts_with(sig)) {
+            return Err(PreflightError::Image);
+        }
+        let head = &data[..data.len().min(SAVE_GAME_TAG_WINDOW)];
+        if head
+            .windows(SAVE_GAME_TAG.len())
+            .any(|w| w == SAVE_GAME_TAG)
+        {
+            return Err(PreflightError::SaveGame);
+        }
+    }
+    if data.len() < MIN_REPLAY_BYTES {
+        return Err(PreflightError::TooSmall(data.len()));
+    }
+    if !has_magic {
+        return Err(PreflightError::BadMagic);
+    }
+    let window = &data[..data.len().min(MAP_MARKER_WINDOW)];
+    if !window.windows(2).any(|w| w == b"M=") {
+        return Err(PreflightError::MissingMapMarker);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn padded(prefix: &[u8], len: usize) -> Vec<u8> {
+        let mut data = prefix.to_vec();
+        data.resize(len, 0);
+        data
+    }
+
+    fn replay_like(len: usize) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(b"M=maps/map wor rhun;S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0");
+        padded(&data, len)
+    }
+
+    #[test]
+    fn valid_replay_passes_untouched() {
+        let data = replay_like(4096);
+        let before = data.clone();
+        assert_eq!(preflight(&data), Ok(()));
+        assert_eq!(data, before);
+    }
+
+    #[test]
+    fn small_files_are_rejected() {
+        assert_eq!(
+            preflight(&replay_like(1000)),
+            Err(PreflightError::TooSmall(1000))
+        );
+        assert_eq!(preflight(b""), Err(PreflightError::TooSmall(0)));
+    }
+
+    #[test]
+    fn save_games_are_recognized() {
+        let data = padded(b"\x10\x00\x00\x00CHUNK_GameState", 40 * 1024);
+        assert_eq!(preflight(&data), Err(PreflightError::SaveGame));
+        // Even when tiny
+        assert_eq!(preflight(b"CHUNK_Campaign"), Err(PreflightError::SaveGame));
+    }
+
+    #[test]
+    fn screenshots_are_recognized() {
+        let png = padded(b"\x89PNG\r\n\x1a\n", 4 * 1024 * 1024);
+        assert_eq!(preflight(&png), Err(PreflightError::Image));
+        let jpeg = padded(b"\xFF\xD8\xFF\xE0", 100 * 1024);
+        assert_eq!(preflight(&jpeg), Err(PreflightError::Image));
+    }
+
+    #[test]
+    fn other_files_without_magic_are_rejected() {
+        let data = padded(b"PK\x03\x04", 8 * 1024);
+        assert_eq!(preflight(&data), Err(PreflightError::BadMagic));
+    }
+
+    #[test]
+    fn other_sage_replays_are_named() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"BFME1RPL", "BFME1"),
+            (b"BFMERPL\0", "BFME1"),
+            (b"GENREP", "C&C Generals/Zero Hour"),
+            (b"C&C3 REPLAY HEADER", "C&C3/Kane's Wrath"),
+            (b"RA3 REPLAY HEADER", "Red Alert 3"),
+        ];
+        for (magic, game) in cases {
+            let data = padded(magic, 8 * 1024);
+            assert_eq!(detect_other_game(&data), Some(game));
+            assert_eq!(preflight(&data), Err(PreflightError::WrongGame(game)));
+            // Named even when too small to be a replay
+            assert_eq!(preflight(magic), Err(PreflightError::WrongGame(game)));
+        }
+    }
+
+    #[test]
+    fn corrupted_bfme2_magic_is_not_another_game() {
+        for magic in [
+            &b"BFME2RPX"[..],
+            b"BFME2RP",
+            b"BFME3RPL",
+            b"bfme2rpl",
+            b"GENRE",
+        ] {
+            let data = padded(magic, 8 * 1024);
+            assert_eq!(detect_other_game(&data), None, "{:?}", magic);
+            assert_eq!(preflight(&data), Err(PreflightError::BadMagic));
+        }
+        assert_eq!(detect_other_game(&replay_like(4096)), None);
+    }
+
+    #[test]
+    fn map_marker_must_be_near_the_start() {
+        let mut data = padded(MAGIC, MAP_MARKER_WINDOW + 100);
+        assert_eq!(preflight(&data), Err(PreflightError::MissingMapMarker));
+        data.extend_from_slice(b"M=maps/map wor rhun");
+        assert_eq!(preflight(&data), Err(PreflightError::MissingMapMarker));
+    }
+}
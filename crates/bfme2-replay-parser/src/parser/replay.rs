@@ -0,0 +1,5010 @@
+use crate::models::{
+    Confidence, Faction, GameEnding, LobbyOptions, MapPosition, NameEncoding, OrderKind,
+    PLAYER_COLORS, Player, PlayerBuilder, ReplayError, ReplayInfo, ReplayWarning, Side, Spectator,
+    StageClock, TimingBreakdown, Winner, WinnerSource, WinnerVerdict, clock_now, power_name,
+};
+use crate::tracing;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use super::encoding::decode_best;
+use super::idle::idle_gap_ticks;
+use super::pn_mapping::{PnMapping, PnSample, candidate_mappings, choose_mapping};
+use super::preflight::detect_other_game;
+use super::replay_parser::ReplayParser;
+use super::tick_rate::{implied_tick_rate, ticks_to_secs};
+use super::units::detect_faction_from_units;
+
+pub(super) const MAGIC: &[u8] = b"BFME2RPL";
+
+// Command types from BFME2 replay format
+pub(super) const CMD_BUILD_OBJECT: u32 = 1049;
+const CMD_BUILD_OBJECT_2: u32 = 1050;
+pub(super) const CMD_UNIT_COMMAND: u32 = 1071; // Also has position data
+pub(super) const CMD_END_GAME: u32 = 29;
+pub(super) const CMD_PLAYER_DEFEATED: u32 = 1096;
+const CMD_PURCHASE_POWER: u32 = 1045; // Unconfirmed spellbook purchase; records nothing while `POWERS` is empty
+
+// Sanity limits for chunk parsing (timecode/player defaults, see `ReplayParser`)
+pub(super) const MAX_SANE_TIMECODE: u32 = 10_000_000;
+pub(super) const MAX_SANE_PLAYER_NUM: u32 = 100;
+const MAX_SANE_ARG_TYPES: usize = 100;
+const MAX_SANE_ARG_COUNT: usize = 50;
+
+// SAGE engine tick rate (~5 ticks per second)
+pub(super) const SAGE_TICKS_PER_SECOND: u32 = 5;
+
+/// Chunk arguments stop being decoded once positions and factions are known
+/// and the walk is past `1 / ANALYSIS_CUTOFF_DIVISOR` of the chunk data
+const ANALYSIS_CUTOFF_DIVISOR: usize = 5;
+
+// Activity timeline bucket width (30 seconds)
+const ACTIVITY_BUCKET_TICKS: u32 = 30 * SAGE_TICKS_PER_SECOND;
+
+// Activity timeline length cap (12 hours); one stray late timecode would
+// otherwise fill in every bucket up to it
+const MAX_ACTIVITY_BUCKETS: u32 = 12 * 60 * 2;
+
+// Entries read per S= section: twice the lobby's 8 slots. Keeps slot indices
+// in u8 range and the spectator dedup small.
+const MAX_SLOT_ENTRIES: usize = 16;
+
+// Unit commands in the first five minutes feed the unit-based faction fallback
+const EARLY_UNIT_WINDOW_TICKS: u32 = 5 * 60 * SAGE_TICKS_PER_SECOND;
+
+// Unit command int args kept per player from the early window
+const MAX_EARLY_UNIT_IDS_PER_PLAYER: usize = 256;
+
+// Orders accepted as a resync point after a chunk fails to parse (the ones this
+// parser acts on; any order type parses normally once back in sync)
+const RESYNC_ORDER_TYPES: [u32; 5] = [
+    CMD_END_GAME,
+    CMD_BUILD_OBJECT,
+    CMD_BUILD_OBJECT_2,
+    CMD_UNIT_COMMAND,
+    CMD_PLAYER_DEFEATED,
+];
+
+// EndGame orders kept per replay; a real game has one or two
+const MAX_ENDGAME_EVENTS: usize = 64;
+
+// Distinct player_nums tracked in the chunk stream (8 slots plus headroom);
+// chunks from further player_nums are ignored
+const MAX_TRACKED_PLAYER_NUMS: usize = 16;
+
+// Building ids kept per player (faction inference needs only a handful)
+const MAX_BUILDING_IDS_PER_PLAYER: usize = 256;
+
+// Fortress build ids, one per faction: the first id of each faction's
+// building range. Only that faction can order them, so one settles the vote.
+const FORTRESS_BUILDING_IDS: [(u32, Faction); 6] = [
+    (2622, Faction::Men),
+    (2577, Faction::Elves),
+    (2541, Faction::Dwarves),
+    (2151, Faction::Goblins),
+    (2060, Faction::Isengard),
+    (2130, Faction::Mordor),
+];
+
+// Power purchases kept per player (a spellbook has fewer powers than this)
+const MAX_POWERS_PER_PLAYER: usize = 32;
+
+// Chunk walk steps (chunks or resyncs) between time budget checks
+const BUDGET_CHECK_INTERVAL: u32 = 1024;
+
+// Player numbers accepted at a resync point
+const RESYNC_PLAYER_NUMS: std::ops::RangeInclusive<u32> = 2..=20;
+
+// How far past the last good timecode a resync point may be (10 minutes)
+const RESYNC_MAX_TIMECODE_JUMP: u32 = 10 * 60 * SAGE_TICKS_PER_SECOND;
+
+// Bytes allowed after the last chunk of a mid-game save (less than one chunk header)
+const PARTIAL_SAVE_MAX_TAIL_BYTES: usize = 13;
+
+/// How far before the computed chunks start the probe looks for earlier chunks
+const CHUNKS_START_PROBE_BYTES: usize = 4096;
+
+/// Consecutive chunks that must parse at an earlier offset to move the start there
+const CHUNKS_START_PROBE_RUN: usize = 5;
+
+/// Chunks start moves larger than this are logged as a warning
+const CHUNKS_START_WARN_BYTES: usize = 256;
+
+/// Chunks read to choose how player numbers map to slots
+const PN_SAMPLE_CHUNKS: usize = 2000;
+
+// Argument type sizes (from OpenSAGE)
+const ARG_SIZES: &[(u8, usize)] = &[
+    (0x00, 4),  // int32
+    (0x01, 4),  // float
+    (0x02, 1),  // bool
+    (0x03, 4),  // ObjectId
+    (0x04, 4),  // unknown4
+    (0x05, 8),  // ScreenPosition
+    (0x06, 12), // Vec3
+    (0x07, 12), // another 12-byte type
+    (0x08, 16), // quaternion/camera
+    (0x09, 4),  // BFME2-specific
+    (0x0A, 4),  // 4 bytes
+];
+
+fn get_arg_size(arg_type: u8) -> usize {
+    ARG_SIZES
+        .iter()
+        .find(|(t, _)| *t == arg_type)
+        .map(|(_, s)| *s)
+        .unwrap_or(4)
+}
+
+/// Parsed chunk from replay
+#[derive(Debug)]
+pub struct Chunk {
+    pub time_code: u32,
+    pub order_type: u32,
+    pub player_num: u32,
+    pub args: Vec<ChunkArg>,
+}
+
+/// One decoded chunk argument
+#[derive(Debug, PartialEq)]
+pub enum ChunkArg {
+    Int(u32),
+    Float(f32),
+    Vec3(f32, f32, f32),
+    /// Any other argument type (by its type byte), value not decoded
+    Other(u8),
+}
+
+/// Decoded chunks of a replay in stream order, with every argument decoded.
+/// Stops at the first chunk that fails to parse; unlike the full parse it
+/// never resyncs, so the output shows exactly where the stream went bad.
+pub struct ChunkIter<'a> {
+    parser: ReplayParser,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ChunkIter<'a> {
+    /// Iterate the chunks of a single-game replay
+    pub fn new(data: &'a [u8]) -> Result<Self, ReplayError> {
+        verify_magic(data)?;
+        let (s_marker, candidate) = find_chunks_start(data).ok_or(ReplayError::ParseError(
+            "Could not find the start of the chunks".to_string(),
+        ))?;
+        let parser = ReplayParser::default();
+        let pos = probe_chunks_start(&parser, data, s_marker, candidate);
+        Ok(Self { parser, data, pos })
+    }
+
+    /// Byte offset of the next chunk
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+}
+
+impl Iterator for ChunkIter<'_> {
+    type Item = Chunk;
+
+    fn next(&mut self) -> Option<Chunk> {
+        let (next, chunk) = parse_chunk(&self.parser, self.data, self.pos, true).ok()?;
+        self.pos = next;
+        Some(chunk)
+    }
+}
+
+/// Player data from header parsing
+#[derive(Debug)]
+struct HeaderPlayer {
+    name: String,
+    uid: Option<String>,
+    color_id: i8,
+    faction_id: i8,
+    team_raw: i8,
+    slot: u8,
+    /// Raw value of the 5th comma-separated field in the `S=` slot entry.
+    /// `-2` means observer/spectator; `-1` means random start position;
+    /// `0..7` is a chosen start position. Kept for diagnostic and future use.
+    #[allow(dead_code)]
+    startpos_raw: i8,
+    name_encoding: NameEncoding,
+    /// Set when the slot entry did not have the standard layout
+    slot_warning: Option<ReplayWarning>,
+}
+
+/// Result of a single-pass header parse
+struct HeaderParseResult {
+    /// Cleaned map name used for matching and display
+    map_name: String,
+    /// Raw `M=` header value, kept verbatim for diagnostics
+    map_path: String,
+    players: Vec<HeaderPlayer>,
+    spectators: Vec<Spectator>,
+    occupied_slots: Vec<u8>,
+    /// `(offset of the ;S= marker, first byte after the null ending its section)`
+    chunks_start: Option<(usize, usize)>,
+    /// Replay seed (the `SD=` header field). Used to deterministically reproduce
+    /// the game's random-color assignment.
+    sd: u32,
+    /// `(slot_index, color_id)` for each spectator/observer, in slot order.
+    /// Needed to accurately simulate PRNG consumption during color assignment
+    /// (observers consume one rand(0, num_starts-1) call for Phase 1 StartPos
+    /// and one rand(0, num_colors-1) retry loop for Phase 2 Color if their
+    /// color_id is -1).
+    observer_slots: Vec<(u8, i8)>,
+    /// Lobby settings from the `RU=` options section
+    lobby_options: LobbyOptions,
+}
+
+/// Parse the header in a single pass: extract map name, players/spectators,
+/// and locate the chunks start offset.
+/// The binary preamble may contain null bytes before the text section,
+/// so we search the full buffer for M= and ;S= markers.
+fn parse_header(data: &[u8]) -> Result<HeaderParseResult, ReplayError> {
+    // Search full data for map name (text section position is variable)
+    let (map_path, map_name) = find_map_name_in(data).ok_or(ReplayError::ParseError(
+        "Could not find map name".to_string(),
+    ))?;
+
+    // Search full data for players/spectators
+    let SlotScan {
+        players,
+        spectators,
+        occupied_slots,
+        observer_slots,
+    } = find_players_and_spectators_in(data);
+
+    // Find chunks start: first null byte after the ;S= section
+    let chunks_start = find_chunks_start(data);
+
+    // Extract the `SD=` seed field (decimal integer terminated by `;` or null).
+    let sd = find_header_u32_field(data, b";SD=").unwrap_or(0);
+
+    let lobby_options = find_lobby_options(data);
+
+    Ok(HeaderParseResult {
+        map_name,
+        map_path,
+        players,
+        spectators,
+        occupied_slots,
+        chunks_start,
+        sd,
+        observer_slots,
+        lobby_options,
+    })
+}
+
+// Positions in the space-separated `RU=` options section. Unconfirmed: no
+// replay with known lobby settings has checked them yet, so the renderer
+// does not show the result.
+const RU_GAME_SPEED: usize = 1;
+const RU_STARTING_RESOURCES: usize = 2;
+const RU_RING_HEROES: usize = 4;
+const RU_FORTRESS_EXPANSIONS: usize = 5;
+
+/// Decode the lobby options from the `;RU=` header section, e.g.
+/// `RU=3 100 1000 0 1 1 1 -1 0 -1 -1 1`. Missing sections, short sections
+/// and values out of range leave the affected settings unknown.
+fn find_lobby_options(data: &[u8]) -> LobbyOptions {
+    let marker = b";RU=";
+    let Some(start) = data
+        .windows(marker.len())
+        .position(|w| w == marker)
+        .map(|i| i + marker.len())
+    else {
+        return LobbyOptions::default();
+    };
+    let end = data[start..]
+        .iter()
+        .position(|&b| b == b';' || b == 0)
+        .map_or(data.len(), |len| start + len);
+    let section = String::from_utf8_lossy(&data[start..end]);
+    let values: Vec<&str> = section.split_ascii_whitespace().collect();
+    let value = |index: usize| values.get(index).and_then(|v| v.parse::<i64>().ok());
+    let flag = |index: usize| match value(index) {
+        Some(0) => Some(false),
+        Some(1) => Some(true),
+        _ => None,
+    };
+    LobbyOptions {
+        starting_resources: value(RU_STARTING_RESOURCES).and_then(|v| u32::try_from(v).ok()),
+        game_speed: value(RU_GAME_SPEED)
+            .and_then(|v| u8::try_from(v).ok())
+            .filter(|&speed| speed > 0),
+        ring_heroes: flag(RU_RING_HEROES),
+        fortress_expansions: flag(RU_FORTRESS_EXPANSIONS),
+    }
+}
+
+/// Find a header field of the form `;KEY=decimal_digits;` and parse as u32.
+fn find_header_u32_field(data: &[u8], marker: &[u8]) -> Option<u32> {
+    for i in 0..data.len().saturating_sub(marker.len()) {
+        if &data[i..i + marker.len()] != marker {
+            continue;
+        }
+        let mut end = i + marker.len();
+        while end < data.len() && data[end].is_ascii_digit() {
+            end += 1;
+        }
+        if end > i + marker.len()
+            && let Ok(s) = std::str::from_utf8(&data[i + marker.len()..end])
+        {
+            return s.parse::<u32>().ok();
+        }
+    }
+    None
+}
+
+/// Find where chunks start (first null byte after the ;S= section), along
+/// with where the ;S= marker is
+fn find_chunks_start(data: &[u8]) -> Option<(usize, usize)> {
+    let s_marker = b";S=";
+    for i in 0..data.len().saturating_sub(s_marker.len()) {
+        if &data[i..i + s_marker.len()] == s_marker {
+            for (j, &byte) in data.iter().enumerate().skip(i) {
+                if byte == 0 {
+                    return Some((i, j + 1));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Earliest offset between the ;S= marker and `candidate` (within a bounded
+/// window) where a consistent run of chunks parses. When `S=` is not the
+/// last header field, the null after its section can lie inside chunk data,
+/// and starting there would drop the first minutes of the game.
+fn probe_chunks_start(
+    parser: &ReplayParser,
+    data: &[u8],
+    s_marker: usize,
+    candidate: usize,
+) -> usize {
+    let lowest = candidate
+        .saturating_sub(CHUNKS_START_PROBE_BYTES)
+        .max(s_marker);
+    (lowest..candidate)
+        .find(|&offset| chunk_run_len(parser, data, offset) >= CHUNKS_START_PROBE_RUN)
+        .unwrap_or(candidate)
+}
+
+/// Chunks parsed back to back from `offset` (up to the probe run) with
+/// plausible player numbers and non-decreasing timecodes
+fn chunk_run_len(parser: &ReplayParser, data: &[u8], mut offset: usize) -> usize {
+    let mut last_tc = 0;
+    let mut run = 0;
+    while run < CHUNKS_START_PROBE_RUN {
+        let Ok((end, chunk)) = parse_chunk(parser, data, offset, false) else {
+            break;
+        };
+        if chunk.time_code < last_tc || !RESYNC_PLAYER_NUMS.contains(&chunk.player_num) {
+            break;
+        }
+        last_tc = chunk.time_code;
+        offset = end;
+        run += 1;
+    }
+    run
+}
+
+/// Parse a BFME2 replay file and extract game information
+pub fn parse_replay(data: &[u8]) -> Result<ReplayInfo, ReplayError> {
+    parse_with(&ReplayParser::default(), data)
+}
+
+/// Whether data starts like a BFME2 replay, whatever its file was called
+pub fn has_replay_magic(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Game start time from the header alone (no chunk parsing); `None` for
+/// files without the BFME2 magic or with a zero timestamp
+pub fn header_start_time(data: &[u8]) -> Option<u32> {
+    if !data.starts_with(MAGIC) {
+        return None;
+    }
+    let bytes = data.get(MAGIC.len()..MAGIC.len() + 4)?;
+    let start = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (start > 0).then_some(start)
+}
+
+/// [`parse_replay`] that gives up walking chunks after `budget` and returns
+/// what it gathered so far, flagged [`ReplayWarning::Truncated`] (positions
+/// and winner may be unknown)
+pub fn parse_replay_with_budget(data: &[u8], budget: Duration) -> Result<ReplayInfo, ReplayError> {
+    parse_with(&ReplayParser::builder().time_budget(budget).build(), data)
+}
+
+/// Split a file holding several replays back to back into one slice per game.
+/// A magic string counts as a game start only if the bytes up to the next
+/// candidate hold a parseable header with players, so a magic that happens to
+/// appear inside chunk data does not split a game.
+pub fn split_games(data: &[u8]) -> Vec<&[u8]> {
+    let candidates: Vec<usize> = (1..data.len().saturating_sub(MAGIC.len() - 1))
+        .filter(|&i| &data[i..i + MAGIC.len()] == MAGIC)
+        .collect();
+
+    let mut starts = vec![0];
+    for (idx, &start) in candidates.iter().enumerate() {
+        let end = candidates.get(idx + 1).copied().unwrap_or(data.len());
+        if is_game_start(&data[start..end]) {
+            starts.push(start);
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let end = starts.get(idx + 1).copied().unwrap_or(data.len());
+            &data[start..end]
+        })
+        .collect()
+}
+
+/// Whether a slice starting with the magic string carries a replay header
+fn is_game_start(slice: &[u8]) -> bool {
+    slice.len() >= MAGIC.len() + 16
+        && parse_header(slice).is_ok_and(|header| !header.players.is_empty())
+}
+
+/// Parse every game in a file that may hold several replays back to back
+pub fn parse_replays_multi(data: &[u8]) -> Vec<Result<ReplayInfo, ReplayError>> {
+    split_games(data).into_iter().map(parse_replay).collect()
+}
+
+/// How much recovery work a parse needed, for monitoring
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ParseStats {
+    /// Defeat/EndGame events found only by the raw byte scan
+    pub raw_scan_recoveries: u32,
+    /// Times the chunk stream lost sync and was resumed at a later header
+    pub resync_count: u32,
+    /// Bytes jumped over while resyncing
+    pub bytes_skipped: usize,
+    /// Offsets the chunk walk tried to parse a chunk at
+    pub chunk_attempts: u32,
+    /// Chunks dropped because their player_num was past `MAX_TRACKED_PLAYER_NUMS`
+    pub ignored_player_chunks: u32,
+    /// Parse stage timings, when the parser records them
+    pub timings: Option<TimingBreakdown>,
+    /// Ticks per second implied by the header duration and the chunk
+    /// timecodes, for calibrating estimates (long concluded games only)
+    pub implied_tick_rate: Option<f32>,
+    /// Bytes the chunks start was moved back from the first null after the
+    /// `;S=` section, because chunks already parse earlier
+    pub chunks_start_shift: usize,
+    /// How chunk player numbers were matched to lobby slots
+    pub pn_mapping: PnMapping,
+    /// Why the EndGame order was not trusted to name the winner, if it wasn't
+    pub endgame_conflict: Option<EndGameConflict>,
+}
+
+/// Defeat events that contradict the EndGame order's player being a winner;
+/// the losing side sometimes records it by leaving the defeat screen first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndGameConflict {
+    /// The EndGame player was defeated at or before the EndGame order
+    PlayerDefeatedFirst,
+    /// The EndGame player's team has more defeated players than any other
+    TeamMostDefeated,
+}
+
+impl EndGameConflict {
+    pub fn label(self) -> &'static str {
+        match self {
+            EndGameConflict::PlayerDefeatedFirst => "EndGame player defeated first",
+            EndGameConflict::TeamMostDefeated => "EndGame team defeated most",
+        }
+    }
+}
+
+/// Parse a replay using the limits and filters from `parser`
+pub(super) fn parse_with(parser: &ReplayParser, data: &[u8]) -> Result<ReplayInfo, ReplayError> {
+    parse_with_stats(parser, data, &mut ParseStats::default())
+}
+
+/// Check the BFME2 magic, naming the game when the file is another SAGE
+/// title's replay
+fn verify_magic(data: &[u8]) -> Result<(), ReplayError> {
+    if let Some(game) = detect_other_game(data) {
+        return Err(ReplayError::WrongGame(game.to_string()));
+    }
+    if data.len() < MAGIC.len() + 16 || &data[..MAGIC.len()] != MAGIC {
+        return Err(ReplayError::InvalidHeader);
+    }
+    Ok(())
+}
+
+/// [`parse_with`], also filling in `stats` (left at zero when no chunks are parsed)
+pub(super) fn parse_with_stats(
+    parser: &ReplayParser,
+    data: &[u8],
+    stats: &mut ParseStats,
+) -> Result<ReplayInfo, ReplayError> {
+    let mut clock = StageClock::start(parser.record_timings);
+    let mut timings = TimingBreakdown::default();
+
+    verify_magic(data)?;
+
+    // Parse header in a single pass
+    let header_result = parse_header(data)?;
+
+    // Filter to allowed maps, "wor rhun" by default (early exit for unsupported maps)
+    if !parser.is_map_allowed(&header_result.map_name) {
+        return Err(ReplayError::UnsupportedMap(header_result.map_name));
+    }
+
+    // Parse timestamps from header (offset 8-16)
+    let start_time = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
+    let end_time = u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+
+    let map_name = header_result.map_name;
+    let map_path = header_result.map_path;
+    let mut header_players = header_result.players;
+    let spectators = header_result.spectators;
+    let occupied_slots = header_result.occupied_slots;
+    let lobby_options = header_result.lobby_options;
+
+    if header_players.is_empty() {
+        return Err(ReplayError::NoPlayers);
+    }
+
+    // Resolve random colors AND random factions by replaying the game's PRNG.
+    assign_player_colors_and_factions(
+        &mut header_players,
+        header_result.sd,
+        &header_result.observer_slots,
+    );
+
+    // Build initial players list
+    let mut players = build_players(&header_players);
+    timings.header = clock.lap();
+
+    let chunks_start = header_result.chunks_start.map(|(s_marker, candidate)| {
+        let start = probe_chunks_start(parser, data, s_marker, candidate);
+        if candidate - start > CHUNKS_START_WARN_BYTES {
+            tracing::warn!(
+                "Chunks start {} bytes before the end of the S= section",
+                candidate - start
+            );
+        }
+        (start, candidate - start)
+    });
+
+    // Parse state for streaming chunk processing
+    let mut winner = Winner::Unknown;
+    let mut verdict = WinnerVerdict::undetermined();
+    let mut ending = GameEnding::Unknown;
+    let mut game_crashed = false;
+    let mut is_partial = false;
+    let mut truncated = false;
+    let mut estimated_duration_secs: Option<u32> = None;
+    let mut raw_estimated_duration_secs: Option<u32> = None;
+    let mut activity_buckets = Vec::new();
+    let mut first_defeat = None;
+    let mut collapse_duration_secs = None;
+    let mut mirrored = false;
+    let mut warnings: Vec<ReplayWarning> = header_players
+        .iter()
+        .filter_map(|p| p.slot_warning.clone())
+        .collect();
+
+    if let Some((start, shift)) = chunks_start {
+        // The engine usually assigns pn=3,4,5,... to each occupied slot in
+        // order, but some games skip observers; pick whichever fits the chunks
+        let (pn_mapping, pn_to_slot) =
+            choose_pn_mapping(parser, data, start, &header_players, &occupied_slots);
+        if pn_mapping != PnMapping::default() {
+            tracing::warn!(
+                "Chunk player numbers fit the {} mapping",
+                pn_mapping.label()
+            );
+        }
+
+        // Parse chunks for positions, faction detection, and winner
+        let parse_result =
+            parse_and_analyze_chunks(parser, data, start, &header_players, &pn_to_slot);
+        timings.raw_scan = parse_result.raw_scan_elapsed;
+        timings.chunk_walk = clock.lap().saturating_sub(timings.raw_scan);
+        *stats = ParseStats {
+            raw_scan_recoveries: parse_result.raw_scan_recoveries,
+            resync_count: parse_result.resync_count,
+            bytes_skipped: parse_result.bytes_skipped,
+            chunk_attempts: parse_result.chunk_attempts,
+            ignored_player_chunks: parse_result.ignored_player_chunks,
+            timings: None,
+            implied_tick_rate: None,
+            chunks_start_shift: shift,
+            pn_mapping,
+            endgame_conflict: None,
+        };
+        if parse_result.truncated {
+            warnings.push(ReplayWarning::Truncated);
+        }
+        // The header has no length field to check the file against; a file
+        // cut off in transfer shows as a last chunk running past the end
+        truncated = parse_result.cut_mid_chunk;
+        if truncated {
+            tracing::warn!(
+                "Replay ends mid-chunk after offset {} of {}, file truncated",
+                parse_result.last_chunk_end,
+                data.len()
+            );
+        }
+
+        // A desynced lobby can seat one player twice; the copy that never
+        // acted would otherwise hold up its team's defeat
+        let active = |slot: u8| {
+            parse_result.player_activity.contains_key(&slot)
+                || parse_result.positions.player_builds.contains_key(&slot)
+        };
+        let ghosts = duplicate_entries(&players, active);
+        for &slot in &ghosts {
+            let Some(ghost) = players.iter().find(|p| p.slot == slot) else {
+                continue;
+            };
+            tracing::warn!(
+                "Duplicate lobby entry for {} in slot {} ignored",
+                ghost.name,
+                slot
+            );
+            warnings.push(ReplayWarning::DuplicatePlayer {
+                slot,
+                name: ghost.name.clone(),
+            });
+        }
+        players.retain(|p| !ghosts.contains(&p.slot));
+        header_players.retain(|hp| !ghosts.contains(&hp.slot));
+
+        // Players who never left the loading screen are never defeated and
+        // would hold up their team's defeat
+        let afk = afk_slots(
+            &players,
+            &parse_result.player_command_counts,
+            parser.afk_min_commands,
+            parse_result.truncated || truncated,
+        );
+        for player in players.iter_mut().filter(|p| afk.contains(&p.slot)) {
+            tracing::warn!(
+                "{} in slot {} issued too few commands, marked AFK",
+                player.name,
+                player.slot
+            );
+            player.afk = true;
+            warnings.push(ReplayWarning::AfkPlayer {
+                slot: player.slot,
+                name: player.name.clone(),
+            });
+        }
+
+        // Assign positions and actual factions to players
+        for player in &mut players {
+            let build = parse_result.positions.player_builds.get(&player.slot);
+            if let Some(build) = build {
+                player.map_position = Some(build.position);
+            }
+            let vote = build.and_then(|b| b.faction_vote.as_ref());
+            if let Some(vote) = vote
+                && vote.counts.len() > 1
+            {
+                let (votes, total) = vote.tally();
+                tracing::warn!(
+                    "Split faction vote for slot {}: {:?}{}",
+                    player.slot,
+                    vote.counts,
+                    if vote.fortress { " (fortress)" } else { "" }
+                );
+                warnings.push(ReplayWarning::SplitFactionVote {
+                    slot: player.slot,
+                    faction: vote.faction,
+                    votes,
+                    total,
+                });
+            }
+            // Buildings first; early unit types cover players who never built,
+            // once unit ranges are known
+            let inferred = vote.map(|v| v.faction).or_else(|| {
+                parse_result
+                    .early_units
+                    .get(&player.slot)
+                    .and_then(|units| detect_faction_from_units(units))
+            });
+            if let Some(faction) = inferred
+                && parser.infer_factions
+            {
+                player.actual_faction = Some(faction);
+            }
+            if let Some(firsts) = parse_result.player_first_actions.get(&player.slot) {
+                player.first_actions = firsts.clone();
+            }
+            player.command_count = parse_result
+                .player_command_counts
+                .get(&player.slot)
+                .copied()
+                .unwrap_or(0);
+            if let Some(buildings) = parse_result.positions.player_building_ids.get(&player.slot) {
+                player.build_orders = buildings.values().map(|seen| seen.orders).sum();
+                player.building_types = buildings.len() as u32;
+            }
+            if let Some(powers) = parse_result.player_powers.get(&player.slot) {
+                player.powers_used = powers
+                    .iter()
+                    .filter_map(|&(id, tc)| {
+                        let name = power_name(id)?;
+                        Some((name.to_string(), ticks_to_secs(tc, parser.tick_rate)))
+                    })
+                    .collect();
+            }
+        }
+
+        // Classify spots in the standard map's orientation, flipping mirrored edits
+        mirrored = detect_mirrored(parser, &map_name, &players);
+        if mirrored {
+            tracing::debug!("Mirrored map detected, flipping spot classification");
+        }
+        for player in &mut players {
+            player.spot = player.map_position.and_then(|pos| {
+                let pos = if mirrored {
+                    parser.spot_regions.flip_x(pos)
+                } else {
+                    pos
+                };
+                parser.spot_regions.classify(pos)
+            });
+        }
+
+        // Determine team sides (Left/Right) based on positions
+        let mut team_sides = determine_team_sides(&players);
+        if let Some(teams) = conflicting_teams(&players, &team_sides) {
+            let spawns: Vec<(i8, f32)> = players
+                .iter()
+                .filter_map(|p| {
+                    let pos = p.map_position.filter(|pos| pos.is_valid())?;
+                    let pos = if mirrored {
+                        parser.spot_regions.flip_x(pos)
+                    } else {
+                        pos
+                    };
+                    Some((p.team_raw, pos.x))
+                })
+                .collect();
+            tracing::warn!("Lobby teams disagree with spawn positions, sides from clusters");
+            team_sides = cluster_team_sides(&spawns, teams, &team_sides);
+            warnings.push(ReplayWarning::TeamSideMismatch);
+        }
+
+        // Determine winner
+        let (decided, endgame_conflict) = determine_winner(
+            &parse_result,
+            &header_players,
+            &team_sides,
+            &pn_to_slot,
+            &afk,
+            parser.draw_window_ticks,
+        );
+        verdict = decided;
+        if let Some(conflict) = endgame_conflict {
+            tracing::warn!(
+                "EndGame order not trusted ({}), winner from defeats instead",
+                conflict.label()
+            );
+        }
+        stats.endgame_conflict = endgame_conflict;
+        winner = verdict.to_winner();
+        ending = determine_ending(
+            &parse_result.combat,
+            &header_players,
+            &team_sides,
+            &pn_to_slot,
+            &winner,
+        );
+
+        // Check for crashed game (only if winner is still unknown; the
+        // unread or missing rest of a truncated walk or file may still hold
+        // the ending)
+        if winner == Winner::Unknown
+            && !parse_result.truncated
+            && !truncated
+            && !parse_result.combat.has_endgame()
+            && parse_result.combat.defeated_players.is_empty()
+        {
+            // A replay saved mid-game has no end time and stops cleanly at a
+            // chunk boundary; a crash leaves an end time or trailing junk.
+            let no_end_time = end_time == 0 || end_time == start_time;
+            let clean_tail = data.len().saturating_sub(parse_result.last_chunk_end)
+                <= PARTIAL_SAVE_MAX_TAIL_BYTES;
+            if no_end_time && clean_tail {
+                is_partial = true;
+                winner = Winner::InProgress;
+            } else {
+                game_crashed = true;
+                winner = Winner::NotConcluded;
+            }
+        }
+        timings.winner = clock.lap();
+
+        // Earliest defeat among the players, as (slot, seconds)
+        first_defeat = parse_result
+            .combat
+            .defeated_players
+            .iter()
+            .filter_map(|(pn, &tc)| pn_to_slot.get(pn).map(|&slot| (tc, slot)))
+            .min()
+            .map(|(tc, slot)| (slot, tc / SAGE_TICKS_PER_SECOND));
+        for (pn, &tc) in &parse_result.combat.defeated_players {
+            let Some(&slot) = pn_to_slot.get(pn) else {
+                continue;
+            };
+            if let Some(player) = players.iter_mut().find(|p| p.slot == slot) {
+                player.defeated_at_secs = Some(tc / SAGE_TICKS_PER_SECOND);
+            }
+        }
+        collapse_duration_secs = collapse_duration(
+            &parse_result.combat,
+            &header_players,
+            &team_sides,
+            &pn_to_slot,
+            &winner,
+        );
+
+        if !game_crashed
+            && !is_partial
+            && !parse_result.truncated
+            && !truncated
+            && end_time > start_time
+        {
+            stats.implied_tick_rate =
+                implied_tick_rate(parse_result.max_timecode, end_time - start_time);
+        }
+
+        // Estimate duration from the last player chunk (max timecode if players sent none)
+        let duration_tick = if parse_result.last_player_action_tick > 0 {
+            parse_result.last_player_action_tick
+        } else {
+            parse_result.max_timecode
+        };
+        if duration_tick > 0 {
+            // Pauses keep the timecode running in some patches; leave them out
+            let idle_ticks = idle_gap_ticks(&parse_result.command_timecodes);
+            raw_estimated_duration_secs = Some(ticks_to_secs(duration_tick, parser.tick_rate));
+            estimated_duration_secs = Some(ticks_to_secs(
+                duration_tick.saturating_sub(idle_ticks),
+                parser.tick_rate,
+            ));
+            if idle_ticks > 0 {
+                tracing::debug!(
+                    "Excluded {}s of idle gaps from the estimated duration",
+                    idle_ticks / SAGE_TICKS_PER_SECOND
+                );
+            }
+        }
+
+        // Split command activity into left/right per bucket
+        let slot_sides: HashMap<u8, Side> = players
+            .iter()
+            .filter_map(|p| team_sides.get(&p.team_raw).map(|&side| (p.slot, side)))
+            .collect();
+        activity_buckets = build_activity_buckets(&parse_result.player_activity, &slot_sides);
+
+        // Remap teams to 1/2 based on side
+        remap_teams_by_side(&mut players, &team_sides);
+    }
+    if parser.record_timings {
+        stats.timings = Some(timings);
+    }
+
+    Ok(ReplayInfo::new(map_name, players)
+        .with_map_path(map_path)
+        .with_times(start_time, end_time)
+        .with_verdict(verdict)
+        .with_winner(winner)
+        .with_ending(ending)
+        .with_spectators(spectators)
+        .with_game_crashed(game_crashed)
+        .with_partial(is_partial)
+        .with_truncated(truncated)
+        .with_estimated_duration(estimated_duration_secs)
+        .with_raw_estimated_duration(raw_estimated_duration_secs)
+        .with_activity_buckets(activity_buckets)
+        .with_first_defeat(first_defeat)
+        .with_collapse_duration(collapse_duration_secs)
+        .with_mirrored(mirrored)
+        .with_lobby_options(lobby_options)
+        .with_warnings(warnings))
+}
+
+/// Slots of players who issued fewer than `min_commands` commands. None
+/// when the walk was cut short (they may act in the unread rest) or when
+/// nobody would be left: then the commands were not read, not missing.
+fn afk_slots(
+    players: &[Player],
+    command_counts: &HashMap<u8, u32>,
+    min_commands: u32,
+    truncated: bool,
+) -> HashSet<u8> {
+    if truncated {
+        return HashSet::new();
+    }
+    let afk: HashSet<u8> = players
+        .iter()
+        .filter(|p| command_counts.get(&p.slot).copied().unwrap_or(0) < min_commands)
+        .map(|p| p.slot)
+        .collect();
+    if afk.len() == players.len() {
+        return HashSet::new();
+    }
+    afk
+}
+
+/// Slots that repeat a player seated elsewhere in the lobby: the same UID,
+/// or the same name when there is no UID. Entries with chunk activity are
+/// real players (the lowest slot when none acted); the others are returned.
+/// UIDs are compared case-insensitively.
+fn duplicate_entries(players: &[Player], active: impl Fn(u8) -> bool) -> Vec<u8> {
+    let identity = |p: &Player| match &p.uid {
+        Some(uid) => (true, uid.to_ascii_uppercase()),
+        None => (false, p.name.clone()),
+    };
+    let mut groups: HashMap<(bool, String), Vec<u8>> = HashMap::new();
+    for player in players {
+        groups
+            .entry(identity(player))
+            .or_default()
+            .push(player.slot);
+    }
+
+    let mut ghosts = Vec::new();
+    for mut slots in groups.into_values().filter(|slots| slots.len() > 1) {
+        slots.sort_unstable();
+        let any_active = slots.iter().any(|&slot| active(slot));
+        let first = slots[0];
+        ghosts.extend(slots.into_iter().filter(|&slot| {
+            if any_active {
+                !active(slot)
+            } else {
+                slot != first
+            }
+        }));
+    }
+    ghosts.sort_unstable();
+    ghosts
+}
+
+/// A map is mirrored when its name carries a mirrored-edit suffix, or when
+/// most players' first buildings lie outside the standard layout's width
+fn detect_mirrored(parser: &ReplayParser, map_name: &str, players: &[Player]) -> bool {
+    if parser.is_mirrored_name(map_name) {
+        return true;
+    }
+    let positions: Vec<MapPosition> = players
+        .iter()
+        .filter_map(|p| p.map_position)
+        .filter(|pos| pos.is_valid())
+        .collect();
+    let outside = positions
+        .iter()
+        .filter(|&&pos| !parser.spot_regions.contains_x(pos))
+        .count();
+    outside * 2 > positions.len()
+}
+
+/// Search for "M=" marker and extract `(raw_path, cleaned_name)` within a header slice
+fn find_map_name_in(header: &[u8]) -> Option<(String, String)> {
+    let marker = b"M=";
+
+    for i in 0..header.len().saturating_sub(marker.len()) {
+        if &header[i..i + marker.len()] == marker {
+            let start = i + marker.len();
+            let mut end = start;
+
+            while end < header.len() && header[end] != b';' {
+                end += 1;
+            }
+
+            if end > start {
+                let map_path = &header[start..end];
+                if let Ok(path_str) = std::str::from_utf8(map_path) {
+                    return extract_map_name_from_path(path_str)
+                        .map(|name| (path_str.to_string(), name));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// File extensions that may trail the final path segment of a map path
+const KNOWN_MAP_SUFFIXES: &[&str] = &[".map"];
+
+/// Extract clean map name from path like "385maps/map wor rhun".
+///
+/// Takes the final path segment regardless of separator (`/` or `\`), ignoring
+/// trailing separators, strips a known file extension and collapses whitespace.
+fn extract_map_name_from_path(path: &str) -> Option<String> {
+    let trimmed = path.trim().trim_end_matches(['/', '\\']);
+    let mut segment = trimmed.rsplit(['/', '\\']).next().unwrap_or(trimmed);
+
+    for suffix in KNOWN_MAP_SUFFIXES {
+        if segment.len() > suffix.len()
+            && segment.is_char_boundary(segment.len() - suffix.len())
+            && segment[segment.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+        {
+            segment = &segment[..segment.len() - suffix.len()];
+        }
+    }
+
+    let name = segment.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() { None } else { Some(name) }
+}
+
+/// Output of [`find_players_and_spectators_in`]. `occupied_slots` holds the slot
+/// index of every non-empty entry (players AND spectators) of the lobby slot
+/// list. `observer_slots` pairs each of its spectators' slot index with their
+/// `color_id`, for the random-color PRNG simulation. `spectators` also holds
+/// observers from later sections.
+struct SlotScan {
+    players: Vec<HeaderPlayer>,
+    spectators: Vec<Spectator>,
+    occupied_slots: Vec<u8>,
+    observer_slots: Vec<(u8, i8)>,
+}
+
+/// Slot lists of the header text: the `;S=` lobby slot list first, then any
+/// later `;S=` sections before the text ends (some headers list late-joining
+/// observers there).
+fn slot_sections(header: &[u8]) -> Vec<&[u8]> {
+    let marker = b";S=";
+    let find_marker = |from: usize, to: usize| {
+        header[from..to]
+            .windows(marker.len())
+            .position(|w| w == marker)
+            .map(|i| from + i)
+    };
+    let Some(first) = find_marker(0, header.len()) else {
+        return Vec::new();
+    };
+    // Header text is null-terminated; chunk data follows
+    let text_end = header[first..]
+        .iter()
+        .position(|&b| b == 0)
+        .map_or(header.len(), |i| first + i);
+
+    let mut sections = Vec::new();
+    let mut next = Some(first);
+    while let Some(i) = next {
+        let start = i + marker.len();
+        let mut end = start;
+        while end < text_end {
+            let b = header[end];
+            if b == b'\n' || b == b'\r' {
+                break;
+            }
+            if end + 2 < text_end
+                && header[end] == b';'
+                && header[end + 1].is_ascii_uppercase()
+                && header[end + 2] == b'='
+            {
+                break;
+            }
+            end += 1;
+        }
+        if end > start {
+            sections.push(&header[start..end]);
+        }
+        next = find_marker(end, text_end);
+    }
+    sections
+}
+
+/// Parse players and spectators from every S= section of a header slice.
+/// Players and slot bookkeeping come from the lobby slot list only;
+/// spectators are gathered from all sections in join order, deduplicated by
+/// UID (by name when there is none).
+fn find_players_and_spectators_in(header: &[u8]) -> SlotScan {
+    let mut players = Vec::new();
+    let mut spectators: Vec<Spectator> = Vec::new();
+    let mut occupied_slots = Vec::new();
+    let mut observer_slots: Vec<(u8, i8)> = Vec::new();
+
+    for (section_idx, section) in slot_sections(header).into_iter().enumerate() {
+        let lobby = section_idx == 0;
+        // Each name was encoded by its owner's client, so decode per slot
+        let entries = section.split(|&b| b == b':').take(MAX_SLOT_ENTRIES);
+        for (slot_idx, slot_bytes) in entries.enumerate() {
+            let (player_str, name_encoding) = decode_best(slot_bytes);
+            let Some(mut parsed) = parse_player_data(&player_str, slot_idx as u8) else {
+                continue;
+            };
+            parsed.name_encoding = name_encoding;
+            if lobby {
+                occupied_slots.push(slot_idx as u8);
+            }
+            if parsed.team_raw >= 0 {
+                // Later sections only add observers
+                if lobby {
+                    players.push(parsed);
+                }
+                continue;
+            }
+            // Spectator (team_raw is -1)
+            if lobby {
+                observer_slots.push((slot_idx as u8, parsed.color_id));
+            }
+            let seen = spectators.iter().any(|s| match (&s.uid, &parsed.uid) {
+                (Some(a), Some(b)) => a == b,
+                (None, None) => s.name == parsed.name,
+                _ => false,
+            });
+            if !seen {
+                spectators.push(Spectator {
+                    name: parsed.name,
+                    uid: parsed.uid,
+                    slot: lobby.then_some(slot_idx as u8),
+                });
+            }
+        }
+    }
+
+    SlotScan {
+        players,
+        spectators,
+        occupied_slots,
+        observer_slots,
+    }
+}
+
+/// Parse player data from a slot string
+/// Format: HName,UID,Port,TT,ColorID,field5,FactionID,Team,field8,field9,field10
+/// Returns parsed player data if valid
+fn parse_player_data(s: &str, slot: u8) -> Option<HeaderPlayer> {
+    let s = s.trim();
+    if s.is_empty() || s == "X" || s == "O" || s == ";" {
+        return None;
+    }
+
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() < 8 {
+        return None;
+    }
+
+    let mut name = parts[0].to_string();
+    if name.starts_with('H') && name.len() > 1 {
+        let mut chars = name.chars();
+        chars.next(); // skip 'H'
+        name = chars.as_str().to_string();
+    }
+
+    if name.is_empty() {
+        return None;
+    }
+
+    // Parse UID (index 1) - 8-char hex string
+    let uid = if parts.len() > 1 && parts[1].len() == 8 {
+        Some(parts[1].to_string())
+    } else {
+        None
+    };
+
+    // Color, start position, faction and team follow TT (index 4 on);
+    // Create-A-Hero slots carry their hero fields in between
+    let standard = SlotFields::read(&parts[4..]);
+    let (fields, slot_warning) = if standard.is_valid() {
+        (standard, None)
+    } else if let Some(hero) = SlotFields::read_hero_layout(&parts) {
+        tracing::warn!("Slot {} ({}) has Create-A-Hero fields", slot, name);
+        (hero, Some(ReplayWarning::CreateAHeroSlot { slot }))
+    } else {
+        tracing::warn!("Slot {} ({}) has out-of-range fields: {}", slot, name, s);
+        (
+            standard.sanitized(),
+            Some(ReplayWarning::MalformedSlot { slot }),
+        )
+    };
+
+    Some(HeaderPlayer {
+        name,
+        uid,
+        color_id: fields.color_id as i8,
+        faction_id: fields.faction_id as i8,
+        team_raw: fields.team_raw as i8,
+        slot,
+        startpos_raw: fields.startpos_raw as i8,
+        name_encoding: NameEncoding::Utf8,
+        slot_warning,
+    })
+}
+
+/// Fields in a standard slot entry
+const SLOT_FIELDS: usize = 11;
+
+/// Fields a Create-A-Hero slot has between TT and the color: the hero's
+/// template id and its customization hash
+const CAH_EXTRA_FIELDS: usize = 2;
+
+/// Valid slot field values, -1 being random (or no team): the 10 lobby
+/// colors, start positions (-2 for observers), the RotWK factions (Angmar
+/// included) and 8 teams
+const SLOT_COLOR_IDS: RangeInclusive<i32> = -1..=9;
+const SLOT_STARTPOS_IDS: RangeInclusive<i32> = -2..=7;
+const SLOT_FACTION_IDS: RangeInclusive<i32> = -1..=6;
+const SLOT_TEAM_IDS: RangeInclusive<i32> = -1..=7;
+
+/// Color, start position, faction and team of a slot entry, read wide so
+/// out-of-range values can be told apart from missing ones (read as -1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SlotFields {
+    color_id: i32,
+    /// -2 means observer, -1 random, 0..7 chosen
+    startpos_raw: i32,
+    faction_id: i32,
+    team_raw: i32,
+}
+
+impl SlotFields {
+    /// Read from the color field on
+    fn read(parts: &[&str]) -> Self {
+        let field = |i: usize| parts.get(i).and_then(|s| s.parse().ok()).unwrap_or(-1);
+        Self {
+            color_id: field(0),
+            startpos_raw: field(1),
+            faction_id: field(2),
+            team_raw: field(3),
+        }
+    }
+
+    /// Valid fields of a whole entry under the Create-A-Hero layout
+    fn read_hero_layout(parts: &[&str]) -> Option<Self> {
+        if parts.len() < SLOT_FIELDS + CAH_EXTRA_FIELDS {
+            return None;
+        }
+        Some(Self::read(&parts[4 + CAH_EXTRA_FIELDS..])).filter(Self::is_valid)
+    }
+
+    /// Every field in the range the lobby allows
+    fn is_valid(&self) -> bool {
+        SLOT_COLOR_IDS.contains(&self.color_id)
+            && SLOT_STARTPOS_IDS.contains(&self.startpos_raw)
+            && SLOT_FACTION_IDS.contains(&self.faction_id)
+            && SLOT_TEAM_IDS.contains(&self.team_raw)
+    }
+
+    /// Out-of-range fields reset to -1: random color, start and faction, and
+    /// no team (listed as an observer). Keeps a stray id from claiming a color.
+    fn sanitized(self) -> Self {
+        let or_unset = |v: i32, range: RangeInclusive<i32>| if range.contains(&v) { v } else { -1 };
+        Self {
+            color_id: or_unset(self.color_id, SLOT_COLOR_IDS),
+            startpos_raw: or_unset(self.startpos_raw, SLOT_STARTPOS_IDS),
+            faction_id: or_unset(self.faction_id, SLOT_FACTION_IDS),
+            team_raw: or_unset(self.team_raw, SLOT_TEAM_IDS),
+        }
+    }
+}
+
+/// PlayerTemplate registration indices of valid random-faction entries.
+/// Derived from live Frida trace of `FUN_00643bc4`'s pre-loop filter; the
+/// mapping to played faction is verified empirically:
+///   3 → Men, 5 → Elves, 6 → Dwarves, 7 → Isengard, 8 → Mordor, 9 → Goblins.
+/// In these games every player requests Random faction, so the PRNG-derived
+/// template_id IS the actual played faction for all non-observer slots.
+const FACTION_POOL: [i32; 6] = [3, 5, 6, 7, 8, 9];
+
+/// Map a template_id from [`FACTION_POOL`] to the actual played faction.
+fn faction_from_template(template_id: i32) -> Option<Faction> {
+    Faction::from_template_id(template_id)
+}
+
+/// Resolve random-color slots AND compute actual played factions by replaying
+/// the game's deterministic RNG stream.
+///
+/// Mirrors `FUN_00647c1d` → `FUN_006443b6` → (`FUN_00643f62`, `FUN_00643bc4`)
+/// in `game.dat`. Verified against all 9 ground-truth replays via Frida trace;
+/// see `RANDOM_COLOR_REVERSE_ENGINEERING.md`.
+///
+/// Mutates each non-observer player's `color_id` (if it was -1) AND overrides
+/// `faction_id` to the PRNG-derived template value (since players in these
+/// games always request Random faction, the template is the real played faction).
+fn assign_player_colors_and_factions(
+    players: &mut [HeaderPlayer],
+    sd: u32,
+    observer_slots: &[(u8, i8)],
+) {
+    use super::prng::Bfme2Rand;
+
+    const NUM_COLORS: i32 = 10;
+    const NUM_STARTS: i32 = 6; // wor rhun 6-player map
+
+    let mut r = Bfme2Rand::new(sd);
+    let mod7 = (sd % 7) as usize;
+
+    let mut by_slot: [Option<usize>; 8] = [None; 8];
+    for (idx, p) in players.iter().enumerate() {
+        if (p.slot as usize) < 8 {
+            by_slot[p.slot as usize] = Some(idx);
+        }
+    }
+
+    let mut observer_by_slot: [Option<i8>; 8] = [None; 8];
+    for &(slot, color) in observer_slots {
+        if (slot as usize) < 8 {
+            observer_by_slot[slot as usize] = Some(color);
+        }
+    }
+
+    // --- Phase 1 (StartPos) --- one rand per observer.
+    //
+    // NOTE (2026-04-18): this assumption is specifically "observer count"
+    // and empirically matches ALL 9 ground-truth replays. But it is NOT the
+    // game's general algorithm — `FUN_00643f62` actually does three loops:
+    //   1. Mark concrete start positions.
+    //   2. For non-observer slots with start_pos=-1:
+    //      - first such slot rolls `rand(0, num_starts-1)` once with retry;
+    //      - subsequent random-start slots use a non-RNG distance-based pick.
+    //   3. For observer slots passing `FUN_007fde60` (slot type ∈ {2,3,4,5,6}
+    //      AND `+0x1A4 != 0`) AND `template_id == -2`, roll
+    //      `rand(0, num_starts-1)` until an ALREADY-TAKEN position is drawn.
+    //
+    // For live lobbies (e.g. 6-player wor-rhun with every slot's start_pos
+    // chosen manually), Phase 1 actually consumes zero RNG — see
+    // `bfme2-factionforge/SD_RESEARCH.md` "Session 2026-04-18".
+    //
+    // Replay files in this project happen to all have random start_pos and
+    // 2 observers, so `observer_slots.len()` = 2 matches the 2 rolls in
+    // Frida traces. Don't change this without re-validating all 9 replays.
+    for _ in 0..observer_slots.len() {
+        let _ = r.logic_random(0, NUM_STARTS - 1);
+    }
+
+    // --- Phase 2 (Color + Faction) --- iterate slots 0..7 in order.
+    let mut taken: HashSet<i8> = HashSet::new();
+    for idx in by_slot.iter().flatten() {
+        if players[*idx].color_id >= 0 {
+            taken.insert(players[*idx].color_id);
+        }
+    }
+
+    for slot_idx in 0..8 {
+        if let Some(pidx) = by_slot[slot_idx] {
+            // Non-observer: faction loop = mod7 warmups + one rand(0,1000).
+            for _ in 0..mod7 {
+                let _ = r.logic_random(0, 1);
+            }
+            let faction_roll = r.logic_random(0, 1000);
+            let tmpl = FACTION_POOL[(faction_roll as usize) % FACTION_POOL.len()];
+            if let Some(f) = faction_from_template(tmpl) {
+                // Store as the legacy faction_id encoding so downstream code
+                // (including chunk-based verification) sees the correct faction.
+                players[pidx].faction_id = match f {
+                    Faction::Men => 0,
+                    Faction::Goblins => 1,
+                    Faction::Dwarves => 2,
+                    Faction::Isengard => 3,
+                    Faction::Elves => 4,
+                    Faction::Mordor => 5,
+                    Faction::Angmar => 6,
+                    _ => players[pidx].faction_id,
+                };
+            }
+
+            if players[pidx].color_id == -1 {
+                let picked = pick_untaken_color(&mut r, &taken, NUM_COLORS);
+                players[pidx].color_id = picked;
+                taken.insert(picked);
+            }
+        } else if let Some(obs_color) = observer_by_slot[slot_idx] {
+            // Observer: no faction loop; color retry if color_id == -1.
+            if obs_color == -1 {
+                let picked = pick_untaken_color(&mut r, &taken, NUM_COLORS);
+                taken.insert(picked);
+            }
+        }
+    }
+}
+
+/// Roll colors until one is free, like the game's retry loop. With every
+/// color taken (impossible in a real lobby) it stays random (-1).
+fn pick_untaken_color(r: &mut super::prng::Bfme2Rand, taken: &HashSet<i8>, num_colors: i32) -> i8 {
+    if (0..num_colors).all(|c| i8::try_from(c).is_ok_and(|c| taken.contains(&c))) {
+        return -1;
+    }
+    loop {
+        let c = r.logic_random(0, num_colors - 1) as i8;
+        if !taken.contains(&c) {
+            return c;
+        }
+    }
+}
+
+/// Build Player structs from header data
+fn build_players(header_players: &[HeaderPlayer]) -> Vec<Player> {
+    // Collect unique team values for mapping
+    let mut team_raws: Vec<i8> = header_players
+        .iter()
+        .filter(|p| p.team_raw >= 0)
+        .map(|p| p.team_raw)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    team_raws.sort();
+
+    header_players
+        .iter()
+        .map(|hp| {
+            // Map team_raw to 1 or 2
+            let team = team_raws
+                .iter()
+                .position(|&t| t == hp.team_raw)
+                .map(|i| (i + 1) as i8)
+                .unwrap_or(hp.team_raw + 1);
+
+            // Get color RGB
+            let color_rgb = if hp.color_id >= 0 && hp.color_id < 10 {
+                PLAYER_COLORS[hp.color_id as usize]
+            } else {
+                [128, 128, 128]
+            };
+
+            let mut player = PlayerBuilder {
+                name: hp.name.clone(),
+                uid: hp.uid.clone(),
+                team,
+                team_raw: hp.team_raw,
+                slot: hp.slot,
+                faction: Faction::from_id(hp.faction_id),
+                color_id: hp.color_id,
+                color_rgb,
+            }
+            .build();
+            player.name_encoding = hp.name_encoding;
+            player
+        })
+        .collect()
+}
+
+/// Build info extracted from commands
+#[derive(Debug)]
+struct BuildInfo {
+    position: MapPosition,
+    faction_vote: Option<FactionVote>,
+}
+
+/// Orders seen for one building id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BuildingSeen {
+    first_tick: u32,
+    orders: u32,
+}
+
+/// Position and faction data collected per player
+struct PositionData {
+    player_builds: HashMap<u8, BuildInfo>,
+    player_positions: HashMap<u8, MapPosition>,
+    player_building_ids: HashMap<u8, HashMap<u32, BuildingSeen>>,
+}
+
+/// Combat/game result data from chunk parsing
+#[derive(Default)]
+struct CombatResult {
+    /// Defeated player_num → earliest PlayerDefeated timecode
+    defeated_players: HashMap<u32, u32>,
+    /// EndGame orders as (player_num, timecode) in the order found, each
+    /// pair once; at most [`MAX_ENDGAME_EVENTS`]
+    endgames: Vec<(u32, u32)>,
+}
+
+impl CombatResult {
+    /// Record an EndGame order, unless the same one was already found
+    fn record_endgame(&mut self, player_num: u32, time_code: u32) {
+        let event = (player_num, time_code);
+        if !self.endgames.contains(&event) && self.endgames.len() < MAX_ENDGAME_EVENTS {
+            self.endgames.push(event);
+        }
+    }
+
+    fn has_endgame(&self) -> bool {
+        !self.endgames.is_empty()
+    }
+
+    /// The latest EndGame order as (player_num, timecode); of two at the
+    /// same timecode, the one found last
+    fn latest_endgame(&self) -> Option<(u32, u32)> {
+        self.endgames.iter().copied().max_by_key(|&(_, tc)| tc)
+    }
+}
+
+/// Result of chunk parsing and analysis
+struct ChunkParseResult {
+    positions: PositionData,
+    combat: CombatResult,
+    /// Highest timecode over all chunks except those from spectator slots
+    max_timecode: u32,
+    /// Highest timecode of any chunk from a player slot; spectators idling
+    /// after the game ended don't extend it.
+    last_player_action_tick: u32,
+    /// Last command timecode per player_num (for activity-based heuristic)
+    player_last_command_tc: HashMap<u32, u32>,
+    /// Last BUILD command (CMD_BUILD_OBJECT / CMD_BUILD_OBJECT_2) timecode per player_num.
+    /// More reliable than last_command_tc because losing teams still issue sell/demolish
+    /// commands near the end, but they stop *building* earlier.
+    player_last_build_tc: HashMap<u32, u32>,
+    /// First timecode per tracked [`OrderKind`], keyed by slot.
+    /// Bounded by the number of order kinds per player.
+    player_first_actions: HashMap<u8, HashMap<OrderKind, u32>>,
+    /// Command count per activity bucket index, keyed by slot
+    player_activity: HashMap<u8, HashMap<u32, u32>>,
+    /// Gameplay commands issued, keyed by slot
+    player_command_counts: HashMap<u8, u32>,
+    /// Timecode of every player command, ascending (idle gap detection)
+    command_timecodes: Vec<u32>,
+    /// Offset just past the last chunk that parsed
+    last_chunk_end: usize,
+    /// Unit type IDs from unit commands in the early window, keyed by slot
+    early_units: HashMap<u8, Vec<u32>>,
+    /// Spellbook purchases as (power id, timecode), in stream order, keyed by slot
+    player_powers: HashMap<u8, Vec<(u32, u32)>>,
+    /// Times the chunk stream lost sync and was resumed at a later header
+    resync_count: u32,
+    /// Bytes jumped over while resyncing
+    bytes_skipped: usize,
+    /// Offsets a chunk parse was tried at
+    chunk_attempts: u32,
+    /// Chunks from player_nums past `MAX_TRACKED_PLAYER_NUMS`
+    ignored_player_chunks: u32,
+    /// Events the raw scan found that the chunk parser missed
+    raw_scan_recoveries: u32,
+    /// Time spent in the raw scan (zero unless timings are recorded)
+    raw_scan_elapsed: Duration,
+    /// The walk ran out of time budget before the end of the data
+    truncated: bool,
+    /// The last chunk tried ran past the end of the data: the file was cut off
+    cut_mid_chunk: bool,
+}
+
+/// Map chunk player numbers to slots with the candidate mapping that best
+/// fits the first [`PN_SAMPLE_CHUNKS`] chunks
+fn choose_pn_mapping(
+    parser: &ReplayParser,
+    data: &[u8],
+    start: usize,
+    header_players: &[HeaderPlayer],
+    occupied_slots: &[u8],
+) -> (PnMapping, HashMap<u32, u8>) {
+    let player_slots: Vec<u8> = header_players.iter().map(|hp| hp.slot).collect();
+    let candidates = candidate_mappings(occupied_slots, &player_slots);
+    if candidates.len() == 1 {
+        return candidates.into_iter().next().unwrap_or_default();
+    }
+
+    let mut samples: HashMap<u32, PnSample> = HashMap::new();
+    let mut pos = start;
+    for _ in 0..PN_SAMPLE_CHUNKS {
+        let Ok((next_pos, chunk)) = parse_chunk(parser, data, pos, true) else {
+            break;
+        };
+        pos = next_pos;
+        let is_build =
+            chunk.order_type == CMD_BUILD_OBJECT || chunk.order_type == CMD_BUILD_OBJECT_2;
+        if !is_build && chunk.order_type != CMD_UNIT_COMMAND {
+            continue;
+        }
+        let Some(position) = extract_position(&chunk) else {
+            continue;
+        };
+        if samples.len() >= MAX_TRACKED_PLAYER_NUMS && !samples.contains_key(&chunk.player_num) {
+            continue;
+        }
+        let sample = samples.entry(chunk.player_num).or_default();
+        sample.position_chunks += 1;
+        if is_build {
+            sample.first_build.get_or_insert(position);
+        }
+    }
+
+    let slot_teams: HashMap<u8, i8> = header_players
+        .iter()
+        .map(|hp| (hp.slot, hp.team_raw))
+        .collect();
+    choose_mapping(candidates, &samples, &slot_teams, &parser.spot_regions)
+}
+
+/// Parse chunks and analyze for positions, factions, and winner
+fn parse_and_analyze_chunks(
+    parser: &ReplayParser,
+    data: &[u8],
+    start: usize,
+    header_players: &[HeaderPlayer],
+    pn_to_slot: &HashMap<u32, u8>,
+) -> ChunkParseResult {
+    // Per-player maps never hold more than MAX_TRACKED_PLAYER_NUMS entries
+    let mut result = ChunkParseResult {
+        positions: PositionData {
+            player_builds: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+            player_positions: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+            player_building_ids: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        },
+        combat: CombatResult::default(),
+        max_timecode: 0,
+        last_player_action_tick: 0,
+        player_last_command_tc: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        player_last_build_tc: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        player_first_actions: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        player_activity: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        player_command_counts: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        command_timecodes: Vec::new(),
+        last_chunk_end: start,
+        early_units: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        player_powers: HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS),
+        resync_count: 0,
+        bytes_skipped: 0,
+        chunk_attempts: 0,
+        ignored_player_chunks: 0,
+        raw_scan_recoveries: 0,
+        raw_scan_elapsed: Duration::ZERO,
+        truncated: false,
+        cut_mid_chunk: false,
+    };
+
+    // Separate position tracking: build commands vs unit commands
+    let mut build_positions: HashMap<u8, MapPosition> =
+        HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS);
+    let mut unit_positions: HashMap<u8, MapPosition> =
+        HashMap::with_capacity(MAX_TRACKED_PLAYER_NUMS);
+
+    // player_nums seen so far, in stream order, up to the cap
+    let mut tracked_player_nums: HashSet<u32> = HashSet::with_capacity(MAX_TRACKED_PLAYER_NUMS);
+
+    // Only pns that map to actual players (not spectators), consistent with
+    // the chunk walk's is_valid_player filter
+    let valid_player_nums: HashSet<u32> = pn_to_slot
+        .iter()
+        .filter(|&(_, &slot)| header_players.iter().any(|hp| hp.slot == slot))
+        .map(|(&pn, _)| pn)
+        .collect();
+
+    // Raw byte scan for Order 1096/29 patterns the chunk walk may miss after
+    // losing sync. It covers every byte the walk consumes, in the same pass,
+    // and is merged in afterwards as if it ran on its own.
+    let mut raw_combat = CombatResult::default();
+    let mut scanned_to = start;
+    let scan_end = data.len().saturating_sub(3);
+
+    // Past this offset, once every player's position and faction are known,
+    // arguments are no longer decoded: headers still give timecodes and events
+    let analysis_cutoff = start + data.len().saturating_sub(start) / ANALYSIS_CUTOFF_DIVISOR;
+    let mut decode_args = true;
+    let mut builds_changed = true;
+
+    let mut pos = start;
+    // Timecode resyncs continue from; None until a chunk parses
+    let mut last_good_tc: Option<u32> = None;
+    // Anchor a resync search already came up empty for: every offset after
+    // it was checked, so until a chunk parses the walk probes byte by byte
+    let mut resync_exhausted_for: Option<u32> = None;
+    // Timecode of the last chunk, when it was too far from the anchor to
+    // move it on its own
+    let mut unconfirmed_tc: Option<u32> = None;
+    // Whether the walk is stepping byte by byte after a failed chunk
+    let mut probing = false;
+
+    // No clock (wasm32 without the `clock` feature): budgets are not enforced
+    let started = parser
+        .time_budget
+        .and_then(|budget| Some((clock_now()?, budget)));
+    let mut steps: u32 = 0;
+
+    while pos < data.len().saturating_sub(13) {
+        steps = steps.wrapping_add(1);
+        if steps.is_multiple_of(BUDGET_CHECK_INTERVAL)
+            && let Some((started, budget)) = started
+            && started.elapsed() > budget
+        {
+            tracing::warn!(
+                "Parse budget of {:?} spent at offset {} of {}, truncating",
+                budget,
+                pos,
+                data.len()
+            );
+            result.truncated = true;
+            break;
+        }
+        if parser.enable_raw_scan {
+            let mut clock = StageClock::start(parser.record_timings);
+            let range = scanned_to..pos.min(scan_end);
+            scan_critical_patterns(
+                parser,
+                data,
+                start,
+                range,
+                &valid_player_nums,
+                &mut raw_combat,
+            );
+            result.raw_scan_elapsed += clock.lap();
+            scanned_to = scanned_to.max(pos.min(scan_end));
+        }
+        if decode_args && builds_changed && pos >= analysis_cutoff && !parser.full_chunk_analysis {
+            builds_changed = false;
+            decode_args = !analysis_settled(
+                header_players,
+                &build_positions,
+                &result.positions.player_building_ids,
+            );
+        }
+
+        let parsed = parse_chunk(parser, data, pos, decode_args);
+        result.chunk_attempts += 1;
+        // Only a failure with nothing parsed after it says how the data ends;
+        // the byte probes after it say nothing
+        if !probing {
+            result.cut_mid_chunk = matches!(parsed, Err(ChunkFailure::OutOfBytes));
+        }
+        if let Ok((next_pos, chunk)) = parsed {
+            result.last_chunk_end = next_pos;
+            result.cut_mid_chunk = false;
+            probing = false;
+            // A chunk far from the anchor only moves it once the chunk after
+            // it agrees: a single garbage chunk that happens to parse would
+            // otherwise rule out every real header after it
+            let tc = chunk.time_code;
+            let near = |anchor: u32| tc.abs_diff(anchor) <= RESYNC_MAX_TIMECODE_JUMP;
+            if last_good_tc.is_none_or(near) || unconfirmed_tc.is_some_and(near) {
+                last_good_tc = Some(tc);
+                unconfirmed_tc = None;
+            } else {
+                unconfirmed_tc = Some(tc);
+            }
+
+            // Map player_num to slot using pn_to_slot (handles empty slot gaps)
+            let slot = match pn_to_slot.get(&chunk.player_num) {
+                Some(&s) => s,
+                None => {
+                    result.max_timecode = result.max_timecode.max(chunk.time_code);
+                    pos = next_pos;
+                    continue;
+                }
+            };
+            if !tracked_player_nums.contains(&chunk.player_num) {
+                if tracked_player_nums.len() >= MAX_TRACKED_PLAYER_NUMS {
+                    result.ignored_player_chunks += 1;
+                    pos = next_pos;
+                    continue;
+                }
+                tracked_player_nums.insert(chunk.player_num);
+            }
+            let is_valid_player = header_players.iter().any(|hp| hp.slot == slot);
+
+            // Spectator chunks don't count towards game length
+            if is_valid_player {
+                result.max_timecode = result.max_timecode.max(chunk.time_code);
+                result.last_player_action_tick =
+                    result.last_player_action_tick.max(chunk.time_code);
+            }
+
+            // Track last command timecode per player (for activity-based heuristic)
+            // Only track regular gameplay commands, not engine events
+            if is_valid_player
+                && chunk.order_type != CMD_PLAYER_DEFEATED
+                && chunk.order_type != CMD_END_GAME
+            {
+                result
+                    .player_last_command_tc
+                    .entry(chunk.player_num)
+                    .and_modify(|tc| *tc = (*tc).max(chunk.time_code))
+                    .or_insert(chunk.time_code);
+
+                // Track build commands separately (more reliable signal)
+                if chunk.order_type == CMD_BUILD_OBJECT || chunk.order_type == CMD_BUILD_OBJECT_2 {
+                    result
+                        .player_last_build_tc
+                        .entry(chunk.player_num)
+                        .and_modify(|tc| *tc = (*tc).max(chunk.time_code))
+                        .or_insert(chunk.time_code);
+                }
+
+                result.command_timecodes.push(chunk.time_code);
+
+                *result.player_command_counts.entry(slot).or_default() += 1;
+
+                // Count commands per time bucket (activity timeline)
+                *result
+                    .player_activity
+                    .entry(slot)
+                    .or_default()
+                    .entry(chunk.time_code / ACTIVITY_BUCKET_TICKS)
+                    .or_default() += 1;
+
+                // Track first occurrence per order kind (opening analysis)
+                if let Some(kind) = OrderKind::from_order_type(chunk.order_type) {
+                    result
+                        .player_first_actions
+                        .entry(slot)
+                        .or_default()
+                        .entry(kind)
+                        .and_modify(|tc| *tc = (*tc).min(chunk.time_code))
+                        .or_insert(chunk.time_code);
+                }
+            }
+
+            // Process position-providing commands (1049, 1050, 1071)
+            if decode_args
+                && is_valid_player
+                && (chunk.order_type == CMD_BUILD_OBJECT
+                    || chunk.order_type == CMD_BUILD_OBJECT_2
+                    || chunk.order_type == CMD_UNIT_COMMAND)
+            {
+                // Extract position from chunk
+                if let Some(pos_data) = extract_position(&chunk) {
+                    // Track build and unit positions separately (prefer build later)
+                    if chunk.order_type == CMD_BUILD_OBJECT
+                        || chunk.order_type == CMD_BUILD_OBJECT_2
+                    {
+                        build_positions.entry(slot).or_insert(pos_data);
+                        builds_changed = true;
+                    } else {
+                        unit_positions.entry(slot).or_insert(pos_data);
+                    }
+                }
+
+                // Collect early unit types for the unit-based faction fallback
+                if chunk.order_type == CMD_UNIT_COMMAND
+                    && chunk.time_code <= EARLY_UNIT_WINDOW_TICKS
+                {
+                    let units = result.early_units.entry(slot).or_default();
+                    let room = MAX_EARLY_UNIT_IDS_PER_PLAYER - units.len();
+                    units.extend(extract_unit_ids(&chunk).take(room));
+                }
+
+                // Extract building ID for faction detection (only from build commands)
+                if (chunk.order_type == CMD_BUILD_OBJECT || chunk.order_type == CMD_BUILD_OBJECT_2)
+                    && let Some(bid) = extract_building_id(&chunk)
+                {
+                    let ids = result
+                        .positions
+                        .player_building_ids
+                        .entry(slot)
+                        .or_default();
+                    if let Some(seen) = ids.get_mut(&bid) {
+                        seen.orders += 1;
+                    } else if ids.len() < MAX_BUILDING_IDS_PER_PLAYER {
+                        ids.insert(
+                            bid,
+                            BuildingSeen {
+                                first_tick: chunk.time_code,
+                                orders: 1,
+                            },
+                        );
+                    }
+                    builds_changed = true;
+                }
+            }
+
+            // Process EndGame command (only from actual players, not spectators)
+            if chunk.order_type == CMD_END_GAME && is_valid_player {
+                result
+                    .combat
+                    .record_endgame(chunk.player_num, chunk.time_code);
+            }
+
+            // Process Player Defeated command (only actual players, not spectators)
+            if chunk.order_type == CMD_PLAYER_DEFEATED && is_valid_player {
+                record_defeat(&mut result.combat, chunk.player_num, chunk.time_code);
+            }
+
+            // Spellbook purchases, named through the (so far empty) power table
+            if chunk.order_type == CMD_PURCHASE_POWER
+                && is_valid_player
+                && let Some(power) = extract_power_id(&chunk)
+            {
+                let powers = result.player_powers.entry(slot).or_default();
+                if powers.len() < MAX_POWERS_PER_PLAYER {
+                    powers.push((power, chunk.time_code));
+                }
+            }
+
+            pos = next_pos;
+        } else {
+            // Jump to the next plausible header instead of probing every byte;
+            // with none left, probe byte by byte so other orders still parse
+            unconfirmed_tc = None;
+            let anchor = last_good_tc.unwrap_or(0);
+            let next = if resync_exhausted_for == Some(anchor) {
+                None
+            } else {
+                find_resync_offset(data, pos + 1, anchor)
+            };
+            match next {
+                Some(next) => {
+                    result.resync_count += 1;
+                    result.bytes_skipped += next - pos;
+                    probing = false;
+                    pos = next;
+                }
+                None => {
+                    resync_exhausted_for = Some(anchor);
+                    probing = true;
+                    result.bytes_skipped += 1;
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    // Gap detection needs ascending timecodes, which a corrupt chunk can break
+    result.command_timecodes.sort_unstable();
+
+    if result.resync_count > 0 {
+        tracing::debug!(
+            "Chunk stream resynced {} times, skipped {} bytes",
+            result.resync_count,
+            result.bytes_skipped
+        );
+    }
+    if result.ignored_player_chunks > 0 {
+        tracing::debug!(
+            "Ignored {} chunks from player_nums past the first {}",
+            result.ignored_player_chunks,
+            MAX_TRACKED_PLAYER_NUMS
+        );
+    }
+
+    // Merge positions: prefer build positions, fall back to unit positions
+    for (slot, pos_data) in &build_positions {
+        result.positions.player_positions.insert(*slot, *pos_data);
+    }
+    for (slot, pos_data) in &unit_positions {
+        result
+            .positions
+            .player_positions
+            .entry(*slot)
+            .or_insert(*pos_data);
+    }
+
+    // Finish the raw scan past the last chunk (not past where a truncated
+    // walk stopped), then merge what it found
+    if parser.enable_raw_scan {
+        let mut clock = StageClock::start(parser.record_timings);
+        let range = if result.truncated {
+            scanned_to..scanned_to
+        } else {
+            scanned_to..scan_end
+        };
+        scan_critical_patterns(
+            parser,
+            data,
+            start,
+            range,
+            &valid_player_nums,
+            &mut raw_combat,
+        );
+        result.raw_scan_elapsed += clock.lap();
+
+        let known_events = |c: &CombatResult| c.defeated_players.len() + c.endgames.len();
+        let before = known_events(&result.combat);
+        merge_raw_scan(&mut result.combat, &raw_combat);
+        result.raw_scan_recoveries = (known_events(&result.combat) - before) as u32;
+    }
+
+    // Build player_builds from positions and building IDs
+    for (slot, position) in &result.positions.player_positions.clone() {
+        let buildings = result.positions.player_building_ids.get(slot);
+        let faction_vote = buildings.and_then(detect_faction_from_buildings);
+
+        result.positions.player_builds.insert(
+            *slot,
+            BuildInfo {
+                position: *position,
+                faction_vote,
+            },
+        );
+    }
+
+    result
+}
+
+/// First offset at or after `from` whose bytes look like a chunk header that
+/// continues the stream: timecode not before `last_tc` (within a jump limit),
+/// a whitelisted order type, and a plausible player number.
+fn find_resync_offset(data: &[u8], from: usize, last_tc: u32) -> Option<usize> {
+    let read_u32 =
+        |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    let max_tc = last_tc.saturating_add(RESYNC_MAX_TIMECODE_JUMP);
+    (from..data.len().saturating_sub(13)).find(|&offset| {
+        let time_code = read_u32(offset);
+        (last_tc..=max_tc).contains(&time_code)
+            && RESYNC_ORDER_TYPES.contains(&read_u32(offset + 4))
+            && RESYNC_PLAYER_NUMS.contains(&read_u32(offset + 8))
+    })
+}
+
+/// Extract position (Vec3) from a chunk
+fn extract_position(chunk: &Chunk) -> Option<MapPosition> {
+    for arg in &chunk.args {
+        if let ChunkArg::Vec3(x, y, _z) = arg {
+            return Some(MapPosition::new(*x, *y));
+        }
+    }
+    None
+}
+
+/// Extract building ID from a chunk
+fn extract_building_id(chunk: &Chunk) -> Option<u32> {
+    for arg in &chunk.args {
+        if let ChunkArg::Int(v) = arg
+            && *v > 2000
+            && *v < 3000
+        {
+            return Some(*v);
+        }
+    }
+    None
+}
+
+/// The first known spellbook power ID in a chunk
+fn extract_power_id(chunk: &Chunk) -> Option<u32> {
+    chunk.args.iter().find_map(|arg| match arg {
+        ChunkArg::Int(v) if power_name(*v).is_some() => Some(*v),
+        _ => None,
+    })
+}
+
+/// Int args of a unit command: unit type IDs, among object instance IDs
+/// that only a known unit range can tell apart
+fn extract_unit_ids(chunk: &Chunk) -> impl Iterator<Item = u32> + '_ {
+    chunk.args.iter().filter_map(|arg| match arg {
+        ChunkArg::Int(v) => Some(*v),
+        _ => None,
+    })
+}
+
+/// Faction picked from a player's building orders, with the votes behind it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FactionVote {
+    faction: Faction,
+    /// Decided by a fortress, which no other faction can order
+    fortress: bool,
+    /// Build orders per matched faction, most first
+    counts: Vec<(Faction, u32)>,
+}
+
+impl FactionVote {
+    /// Orders for the picked faction and for any faction
+    fn tally(&self) -> (u32, u32) {
+        let votes = self
+            .counts
+            .iter()
+            .find(|(faction, _)| *faction == self.faction)
+            .map_or(0, |&(_, n)| n);
+        (votes, self.counts.iter().map(|&(_, n)| n).sum())
+    }
+}
+
+/// Detect faction from every building ordered in the game: a fortress
+/// decides outright (the earliest, should there be several), otherwise the
+/// faction with the most build orders wins, ties going to the one seen first
+fn detect_faction_from_buildings(buildings: &HashMap<u32, BuildingSeen>) -> Option<FactionVote> {
+    // (faction, orders, first tick)
+    let mut votes: Vec<(Faction, u32, u32)> = Vec::new();
+    let mut fortress: Option<(u32, Faction)> = None;
+    for (&bid, seen) in buildings {
+        let Some(faction) = infer_faction_from_building(bid) else {
+            continue;
+        };
+        match votes.iter_mut().find(|vote| vote.0 == faction) {
+            Some(vote) => {
+                vote.1 += seen.orders;
+                vote.2 = vote.2.min(seen.first_tick);
+            }
+            None => votes.push((faction, seen.orders, seen.first_tick)),
+        }
+        if let Some(owner) = fortress_faction(bid)
+            && fortress.is_none_or(|(tick, _)| seen.first_tick < tick)
+        {
+            fortress = Some((seen.first_tick, owner));
+        }
+    }
+
+    // Most orders first, then earliest seen
+    votes.sort_by_key(|&(_, orders, first_tick)| (std::cmp::Reverse(orders), first_tick));
+    let (faction, fortress) = match fortress {
+        Some((_, faction)) => (faction, true),
+        None => (votes.first()?.0, false),
+    };
+    let counts = votes.iter().map(|&(f, orders, _)| (f, orders)).collect();
+    Some(FactionVote {
+        faction,
+        fortress,
+        counts,
+    })
+}
+
+/// Faction of a fortress building id
+fn fortress_faction(building_type: u32) -> Option<Faction> {
+    FORTRESS_BUILDING_IDS
+        .iter()
+        .find(|&&(id, _)| id == building_type)
+        .map(|&(_, faction)| faction)
+}
+
+/// Infer faction from building type ID
+/// Building ID ranges from render_map.py:
+/// - Men: 2622-2720
+/// - Elves: 2577-2620
+/// - Dwarves: 2541-2575
+/// - Goblins: 2151-2185
+/// - Isengard: 2060-2090
+/// - Mordor: 2130-2150
+fn infer_faction_from_building(building_type: u32) -> Option<Faction> {
+    match building_type {
+        2622..=2720 => Some(Faction::Men),
+        2577..=2620 => Some(Faction::Elves),
+        2541..=2575 => Some(Faction::Dwarves),
+        2151..=2185 => Some(Faction::Goblins),
+        2060..=2090 => Some(Faction::Isengard),
+        2130..=2150 => Some(Faction::Mordor),
+        _ => None,
+    }
+}
+
+/// Whether every player has a build position and a faction settled by a
+/// fortress, so decoding more chunk arguments can't change the result
+/// (a faction from the vote alone could still be outvoted)
+fn analysis_settled(
+    header_players: &[HeaderPlayer],
+    build_positions: &HashMap<u8, MapPosition>,
+    building_ids: &HashMap<u8, HashMap<u32, BuildingSeen>>,
+) -> bool {
+    header_players.iter().all(|hp| {
+        build_positions.contains_key(&hp.slot)
+            && building_ids
+                .get(&hp.slot)
+                .and_then(detect_faction_from_buildings)
+                .is_some_and(|vote| vote.fortress)
+    })
+}
+
+/// Why a chunk failed to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkFailure {
+    /// The chunk runs past the end of the data
+    OutOfBytes,
+    /// A field is outside the sanity limits: not a chunk header
+    Insane,
+}
+
+/// Parse a single chunk from the data. Without `decode_args` the arguments
+/// are only measured, leaving `args` empty.
+fn parse_chunk(
+    parser: &ReplayParser,
+    data: &[u8],
+    offset: usize,
+    decode_args: bool,
+) -> Result<(usize, Chunk), ChunkFailure> {
+    if offset + 13 > data.len() {
+        return Err(ChunkFailure::OutOfBytes);
+    }
+
+    let time_code = u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ]);
+    let order_type = u32::from_le_bytes([
+        data[offset + 4],
+        data[offset + 5],
+        data[offset + 6],
+        data[offset + 7],
+    ]);
+    let player_num = u32::from_le_bytes([
+        data[offset + 8],
+        data[offset + 9],
+        data[offset + 10],
+        data[offset + 11],
+    ]);
+    let n_arg_types = data[offset + 12] as usize;
+
+    // Sanity checks
+    if time_code > parser.max_timecode
+        || player_num > parser.max_player_num
+        || n_arg_types > MAX_SANE_ARG_TYPES
+    {
+        return Err(ChunkFailure::Insane);
+    }
+
+    let mut pos = offset + 13;
+
+    if !decode_args {
+        let mut args_len = 0;
+        for i in 0..n_arg_types {
+            let sig = pos + i * 2;
+            if sig + 2 > data.len() {
+                return Err(ChunkFailure::OutOfBytes);
+            }
+            let arg_count = data[sig + 1] as usize;
+            if arg_count > MAX_SANE_ARG_COUNT {
+                return Err(ChunkFailure::Insane);
+            }
+            args_len += get_arg_size(data[sig]) * arg_count;
+        }
+        let end = pos + n_arg_types * 2 + args_len;
+        if end > data.len() {
+            return Err(ChunkFailure::OutOfBytes);
+        }
+        let chunk = Chunk {
+            time_code,
+            order_type,
+            player_num,
+            args: Vec::new(),
+        };
+        return Ok((end, chunk));
+    }
+
+    // Read argument signature
+    let mut arg_sig = Vec::new();
+    for _ in 0..n_arg_types {
+        if pos + 2 > data.len() {
+            return Err(ChunkFailure::OutOfBytes);
+        }
+        let arg_type = data[pos];
+        let arg_count = data[pos + 1] as usize;
+        if arg_count > MAX_SANE_ARG_COUNT {
+            return Err(ChunkFailure::Insane);
+        }
+        arg_sig.push((arg_type, arg_count));
+        pos += 2;
+    }
+
+    // Read arguments
+    let mut args = Vec::new();
+    for (arg_type, arg_count) in arg_sig {
+        let size = get_arg_size(arg_type);
+        for _ in 0..arg_count {
+            if pos + size > data.len() {
+                return Err(ChunkFailure::OutOfBytes);
+            }
+            let arg_data = &data[pos..pos + size];
+
+            let arg = match arg_type {
+                0x06 => {
+                    // Vec3
+                    let x =
+                        f32::from_le_bytes([arg_data[0], arg_data[1], arg_data[2], arg_data[3]]);
+                    let y =
+                        f32::from_le_bytes([arg_data[4], arg_data[5], arg_data[6], arg_data[7]]);
+                    let z =
+                        f32::from_le_bytes([arg_data[8], arg_data[9], arg_data[10], arg_data[11]]);
+                    ChunkArg::Vec3(x, y, z)
+                }
+                0x00 => {
+                    let v =
+                        u32::from_le_bytes([arg_data[0], arg_data[1], arg_data[2], arg_data[3]]);
+                    ChunkArg::Int(v)
+                }
+                0x01 => {
+                    let v =
+                        f32::from_le_bytes([arg_data[0], arg_data[1], arg_data[2], arg_data[3]]);
+                    ChunkArg::Float(v)
+                }
+                _ => ChunkArg::Other(arg_type),
+            };
+            args.push(arg);
+
+            pos += size;
+        }
+    }
+
+    Ok((
+        pos,
+        Chunk {
+            time_code,
+            order_type,
+            player_num,
+            args,
+        },
+    ))
+}
+
+/// Record a PlayerDefeated event, keeping the earliest timecode per player
+fn record_defeat(combat: &mut CombatResult, player_num: u32, time_code: u32) {
+    combat
+        .defeated_players
+        .entry(player_num)
+        .and_modify(|tc| *tc = (*tc).min(time_code))
+        .or_insert(time_code);
+}
+
+/// Apply raw-scan events on top of the chunk walk's, as if the scan ran after it
+fn merge_raw_scan(combat: &mut CombatResult, raw: &CombatResult) {
+    for (&player_num, &time_code) in &raw.defeated_players {
+        record_defeat(combat, player_num, time_code);
+    }
+    for &(player_num, time_code) in &raw.endgames {
+        combat.record_endgame(player_num, time_code);
+    }
+}
+
+/// Raw binary scan for critical events (Order 1096 = PlayerDefeated, Order 29 = EndGame)
+/// over the order-field offsets in `range`. The chunk parser can lose sync and miss
+/// events. This scans raw bytes for the order patterns and validates context
+/// (timecode, player_num) to recover missed events.
+///
+/// O(n) scanner: iterates each byte once, checking for first-byte matches
+/// of each pattern then verifying remaining bytes.
+fn scan_critical_patterns(
+    parser: &ReplayParser,
+    data: &[u8],
+    chunks_start: usize,
+    range: std::ops::Range<usize>,
+    valid_player_nums: &HashSet<u32>,
+    combat: &mut CombatResult,
+) {
+    // Pattern first bytes for quick check
+    const DEFEATED_FIRST: u8 = 0x48; // 1096 LE first byte
+    const ENDGAME_FIRST: u8 = 0x1d; // 29 LE first byte
+    const DEFEATED_REST: [u8; 3] = [0x04, 0x00, 0x00];
+    const ENDGAME_REST: [u8; 3] = [0x00, 0x00, 0x00];
+
+    // The order field is at chunk_offset + 4, so we need at least 4 bytes before
+    // the match and 13 bytes total from chunk_offset
+    if data.len() < chunks_start + 8 {
+        return;
+    }
+
+    let end = range.end.min(data.len() - 3); // need 4 bytes for pattern match
+    for i in range.start.max(chunks_start)..end {
+        let b = data[i];
+
+        let cmd = if b == DEFEATED_FIRST && data[i + 1..i + 4] == DEFEATED_REST {
+            Some(CMD_PLAYER_DEFEATED)
+        } else if b == ENDGAME_FIRST && data[i + 1..i + 4] == ENDGAME_REST {
+            Some(CMD_END_GAME)
+        } else {
+            None
+        };
+
+        if let Some(cmd) = cmd {
+            // The order field is at chunk_offset + 4, so chunk_offset = i - 4
+            if i >= chunks_start + 4 {
+                let chunk_offset = i - 4;
+                if chunk_offset + 13 <= data.len() {
+                    let tc = u32::from_le_bytes([
+                        data[chunk_offset],
+                        data[chunk_offset + 1],
+                        data[chunk_offset + 2],
+                        data[chunk_offset + 3],
+                    ]);
+                    let player_num = u32::from_le_bytes([
+                        data[chunk_offset + 8],
+                        data[chunk_offset + 9],
+                        data[chunk_offset + 10],
+                        data[chunk_offset + 11],
+                    ]);
+                    let n_args = data[chunk_offset + 12] as u32;
+
+                    let tc_valid = tc > 0 && tc < parser.max_timecode;
+                    let pn_valid = (3..=20).contains(&player_num);
+                    let nargs_valid = n_args <= 10;
+
+                    if tc_valid
+                        && pn_valid
+                        && nargs_valid
+                        && valid_player_nums.contains(&player_num)
+                    {
+                        if cmd == CMD_PLAYER_DEFEATED {
+                            record_defeat(combat, player_num, tc);
+                        } else if cmd == CMD_END_GAME {
+                            combat.record_endgame(player_num, tc);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Determine which team is on which side based on player spots
+fn determine_team_sides(players: &[Player]) -> HashMap<i8, Side> {
+    let mut team_sides: HashMap<i8, Side> = HashMap::new();
+
+    for player in players {
+        if let Some(spot) = player.spot {
+            team_sides.entry(player.team_raw).or_insert(spot.side());
+        }
+    }
+
+    team_sides
+}
+
+/// The two lobby teams when they end up on the same side, or one without a
+/// side while the other has one (custom map scripts can spawn players away
+/// from their team)
+fn conflicting_teams(players: &[Player], team_sides: &HashMap<i8, Side>) -> Option<(i8, i8)> {
+    let mut teams: Vec<i8> = players.iter().map(|p| p.team_raw).collect();
+    teams.sort();
+    teams.dedup();
+    let &[a, b] = teams.as_slice() else {
+        return None;
+    };
+    match (team_sides.get(&a), team_sides.get(&b)) {
+        (None, None) => None,
+        (Some(x), Some(y)) if x != y => None,
+        _ => Some((a, b)),
+    }
+}
+
+/// Rebuild the sides of two lobby teams from `(team_raw, x)` spawn positions.
+///
+/// Spawns are split into two groups along x; the team with more of its
+/// players in the left group takes Left (mean x breaks ties). A team with
+/// no positions takes the side opposite the other's current one.
+fn cluster_team_sides(
+    spawns: &[(i8, f32)],
+    (a, b): (i8, i8),
+    team_sides: &HashMap<i8, Side>,
+) -> HashMap<i8, Side> {
+    let placed = |team: i8| spawns.iter().any(|&(t, _)| t == team);
+    if !placed(a) || !placed(b) {
+        let (team, other) = if placed(a) { (a, b) } else { (b, a) };
+        let side = team_sides.get(&team).copied().unwrap_or(Side::Left);
+        return HashMap::from([(team, side), (other, side.other())]);
+    }
+
+    let xs: Vec<f32> = spawns.iter().map(|&(_, x)| x).collect();
+    let right = two_means(&xs);
+    // (team, share of players in the right group, mean x)
+    let mut ranked: Vec<(i8, f32, f32)> = [a, b]
+        .into_iter()
+        .map(|team| {
+            let members: Vec<usize> = (0..spawns.len()).filter(|&i| spawns[i].0 == team).collect();
+            let n = members.len() as f32;
+            let right_share = members.iter().filter(|&&i| right[i]).count() as f32 / n;
+            let mean_x = members.iter().map(|&i| spawns[i].1).sum::<f32>() / n;
+            (team, right_share, mean_x)
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        a.1.total_cmp(&b.1)
+            .then(a.2.total_cmp(&b.2))
+            .then(a.0.cmp(&b.0))
+    });
+    HashMap::from([(ranked[0].0, Side::Left), (ranked[1].0, Side::Right)])
+}
+
+/// Split values into two groups with 1-D k-means (k=2), seeded at the
+/// extremes; `true` marks the upper group
+fn two_means(xs: &[f32]) -> Vec<bool> {
+    let lo = xs.iter().copied().fold(f32::INFINITY, f32::min);
+    let hi = xs.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let mut upper = vec![false; xs.len()];
+    if hi <= lo {
+        return upper;
+    }
+    let (mut c_lo, mut c_hi) = (lo, hi);
+    for _ in 0..32 {
+        let next: Vec<bool> = xs
+            .iter()
+            .map(|&x| (x - c_hi).abs() < (x - c_lo).abs())
+            .collect();
+        if next == upper {
+            break;
+        }
+        upper = next;
+        c_lo = group_mean(xs, &upper, false).unwrap_or(c_lo);
+        c_hi = group_mean(xs, &upper, true).unwrap_or(c_hi);
+    }
+    upper
+}
+
+fn group_mean(xs: &[f32], upper: &[bool], want: bool) -> Option<f32> {
+    let group: Vec<f32> = xs
+        .iter()
+        .zip(upper)
+        .filter(|&(_, &u)| u == want)
+        .map(|(&x, _)| x)
+        .collect();
+    (!group.is_empty()).then(|| group.iter().sum::<f32>() / group.len() as f32)
+}
+
+/// Per-bucket `(start_tick, left_cmds, right_cmds)` from per-slot command counts.
+/// Every bucket up to the last active one is present (quiet buckets as zeros),
+/// up to [`MAX_ACTIVITY_BUCKETS`]; slots without a side are left out.
+fn build_activity_buckets(
+    player_activity: &HashMap<u8, HashMap<u32, u32>>,
+    slot_sides: &HashMap<u8, Side>,
+) -> Vec<(u32, u32, u32)> {
+    let mut totals: HashMap<u32, (u32, u32)> = HashMap::new();
+    for (slot, buckets) in player_activity {
+        let Some(&side) = slot_sides.get(slot) else {
+            continue;
+        };
+        for (&bucket, &count) in buckets {
+            let entry = totals.entry(bucket).or_default();
+            match side {
+                Side::Left => entry.0 += count,
+                Side::Right => entry.1 += count,
+            }
+        }
+    }
+
+    let Some(&last) = totals.keys().filter(|&&b| b < MAX_ACTIVITY_BUCKETS).max() else {
+        return Vec::new();
+    };
+    (0..=last)
+        .map(|bucket| {
+            let (left, right) = totals.get(&bucket).copied().unwrap_or_default();
+            (bucket * ACTIVITY_BUCKET_TICKS, left, right)
+        })
+        .collect()
+}
+
+/// Remap team numbers based on side (Left = 1, Right = 2)
+fn remap_teams_by_side(players: &mut [Player], team_sides: &HashMap<i8, Side>) {
+    for player in players.iter_mut() {
+        if let Some(&side) = team_sides.get(&player.team_raw) {
+            player.team = if side == Side::Left { 1 } else { 2 };
+        }
+    }
+}
+
+fn endgame_verdict(side: Side) -> WinnerVerdict {
+    WinnerVerdict::decided(side, Confidence::Certain, WinnerSource::EndGame)
+}
+
+/// Try to determine winner from EndGame command (Order 29)
+///
+/// If players of both sides issued EndGame within `draw_window` ticks of the
+/// latest one, the game ended in a draw. Otherwise the latest EndGame
+/// decides: if its player is also in the defeated set, they lost — the other
+/// team wins; if not, their team is considered the winner.
+fn winner_from_endgame(
+    combat: &CombatResult,
+    header_players: &[HeaderPlayer],
+    team_sides: &HashMap<i8, Side>,
+    pn_to_slot: &HashMap<u32, u8>,
+    draw_window: u32,
+) -> Option<WinnerVerdict> {
+    let (endgame_pn, endgame_tc) = combat.latest_endgame()?;
+    let side_of = |pn: u32| {
+        let slot = pn_to_slot.get(&pn)?;
+        let hp = header_players.iter().find(|hp| hp.slot == *slot)?;
+        team_sides.get(&hp.team_raw).copied()
+    };
+    let endgame_side = side_of(endgame_pn)?;
+
+    // A player already defeated at their EndGame conceded, not drew
+    let drawn = combat
+        .endgames
+        .iter()
+        .filter(|&&(_, tc)| endgame_tc - tc <= draw_window)
+        .filter(|&&(pn, tc)| combat.defeated_players.get(&pn).is_none_or(|&d| d > tc))
+        .filter_map(|&(pn, _)| side_of(pn))
+        .any(|side| side != endgame_side);
+    if drawn && !combat.defeated_players.contains_key(&endgame_pn) {
+        return Some(WinnerVerdict::draw());
+    }
+
+    if combat.defeated_players.contains_key(&endgame_pn) {
+        // EndGame player was defeated — their team lost, the other team won
+        let other_side = endgame_side.other();
+        // Verify the other side actually exists in team_sides
+        if team_sides.values().any(|&s| s == other_side) {
+            return Some(endgame_verdict(other_side));
+        }
+        return None;
+    }
+
+    Some(endgame_verdict(endgame_side))
+}
+
+/// Cross-check the EndGame order against the defeat events. The order names
+/// a winner unless its player was already defeated when it was issued, or
+/// their team lost strictly more players than every other team.
+fn endgame_conflict(
+    combat: &CombatResult,
+    team_players: &HashMap<i8, Vec<u32>>,
+) -> Option<EndGameConflict> {
+    let (endgame_pn, endgame_tc) = combat.latest_endgame()?;
+    if combat
+        .defeated_players
+        .get(&endgame_pn)
+        .is_some_and(|&tc| tc <= endgame_tc)
+    {
+        return Some(EndGameConflict::PlayerDefeatedFirst);
+    }
+    let defeats = |players: &Vec<u32>| {
+        players
+            .iter()
+            .filter(|pn| combat.defeated_players.contains_key(pn))
+            .count()
+    };
+    let (endgame_team, endgame_players) = team_players
+        .iter()
+        .find(|(_, players)| players.contains(&endgame_pn))?;
+    let endgame_defeats = defeats(endgame_players);
+    let most_other_defeats = team_players
+        .iter()
+        .filter(|(team, _)| *team != endgame_team)
+        .map(|(_, players)| defeats(players))
+        .max()?;
+    (endgame_defeats > most_other_defeats).then_some(EndGameConflict::TeamMostDefeated)
+}
+
+/// Try to determine winner from all players on one team being defeated
+fn winner_from_full_defeat(
+    defeated: &HashMap<u32, u32>,
+    team_players: &HashMap<i8, Vec<u32>>,
+    team_sides: &HashMap<i8, Side>,
+) -> Option<WinnerVerdict> {
+    for (team_raw, players_pn) in team_players {
+        if players_pn.iter().all(|pn| defeated.contains_key(pn)) {
+            // This team lost, the other team won
+            for other_team_raw in team_players.keys() {
+                if other_team_raw != team_raw
+                    && let Some(&side) = team_sides.get(other_team_raw)
+                {
+                    return Some(WinnerVerdict::decided(
+                        side,
+                        Confidence::Certain,
+                        WinnerSource::FullDefeat,
+                    ));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Try to determine winner from majority-defeated heuristic
+fn winner_from_majority_defeated(
+    defeated: &HashMap<u32, u32>,
+    team_players: &HashMap<i8, Vec<u32>>,
+    team_sides: &HashMap<i8, Side>,
+) -> Option<WinnerVerdict> {
+    if team_players.len() != 2 {
+        return None;
+    }
+    let teams: Vec<i8> = team_players.keys().cloned().collect();
+    let team_a = teams[0];
+    let team_b = teams[1];
+
+    let defeats_a = team_players[&team_a]
+        .iter()
+        .filter(|pn| defeated.contains_key(pn))
+        .count();
+    let defeats_b = team_players[&team_b]
+        .iter()
+        .filter(|pn| defeated.contains_key(pn))
+        .count();
+
+    let verdict =
+        |side: &Side| WinnerVerdict::decided(*side, Confidence::High, WinnerSource::MajorityDefeat);
+    if defeats_a > defeats_b {
+        team_sides.get(&team_b).map(verdict)
+    } else if defeats_b > defeats_a {
+        team_sides.get(&team_a).map(verdict)
+    } else {
+        None
+    }
+}
+
+/// Try to determine winner from last-activity heuristic.
+///
+/// If one team stopped building significantly before the other team,
+/// the inactive team probably lost. This is useful for observer replays where
+/// engine events (EndGame/PlayerDefeated) may not map to real players.
+///
+/// Uses build commands (CMD_BUILD_OBJECT) as the signal instead of all commands,
+/// because losing teams still issue sell/demolish commands near the end of the
+/// game, but they stop *constructing* earlier.
+///
+/// Requirements:
+/// - Exactly 2 teams
+/// - Both teams must have issued at least one build command
+/// - The gap between teams' last build time must be > 5% of max_timecode
+fn winner_from_last_activity(
+    player_last_build_tc: &HashMap<u32, u32>,
+    team_players: &HashMap<i8, Vec<u32>>,
+    team_sides: &HashMap<i8, Side>,
+    max_timecode: u32,
+) -> Option<WinnerVerdict> {
+    if team_players.len() != 2 || max_timecode == 0 {
+        return None;
+    }
+
+    let teams: Vec<i8> = team_players.keys().cloned().collect();
+
+    // Find latest build command timecode for each team
+    let team_last_build = |team: &i8| -> Option<u32> {
+        team_players[team]
+            .iter()
+            .filter_map(|pn| player_last_build_tc.get(pn))
+            .copied()
+            .max()
+    };
+
+    let last_a = team_last_build(&teams[0])?;
+    let last_b = team_last_build(&teams[1])?;
+
+    // Require a meaningful gap: > 5% of game duration
+    let gap_threshold = max_timecode / 20;
+    let gap = last_a.abs_diff(last_b);
+
+    if gap <= gap_threshold {
+        return None; // Not enough difference to be confident
+    }
+
+    let verdict =
+        |side: &Side| WinnerVerdict::decided(*side, Confidence::Low, WinnerSource::ActivityDropoff);
+    if last_a > last_b {
+        // Team A was still building later → Team A probably won
+        team_sides.get(&teams[0]).map(verdict)
+    } else {
+        // Team B was still building later → Team B probably won
+        team_sides.get(&teams[1]).map(verdict)
+    }
+}
+
+/// Group player_nums by team_raw via the reverse slot mapping
+fn group_team_players(
+    header_players: &[HeaderPlayer],
+    pn_to_slot: &HashMap<u32, u8>,
+) -> HashMap<i8, Vec<u32>> {
+    let slot_to_pn: HashMap<u8, u32> = pn_to_slot.iter().map(|(&pn, &slot)| (slot, pn)).collect();
+    let mut team_players: HashMap<i8, Vec<u32>> = HashMap::new();
+    for hp in header_players {
+        if let Some(&pn) = slot_to_pn.get(&hp.slot) {
+            team_players.entry(hp.team_raw).or_default().push(pn);
+        }
+    }
+    team_players
+}
+
+/// Classify how a game with a certain winner ended.
+///
+/// Only EndGame-concluded games are classified. If every player on the losing
+/// side was defeated at or before the EndGame timecode the game ended by
+/// elimination; if any loser was still standing (no defeat, or a defeat only
+/// recorded after EndGame) the losing side surrendered.
+fn determine_ending(
+    combat: &CombatResult,
+    header_players: &[HeaderPlayer],
+    team_sides: &HashMap<i8, Side>,
+    pn_to_slot: &HashMap<u32, u8>,
+    winner: &Winner,
+) -> GameEnding {
+    let Some((_, endgame_tc)) = combat.latest_endgame() else {
+        return GameEnding::Unknown;
+    };
+    let losers = losing_players(header_players, team_sides, pn_to_slot, winner);
+    if losers.is_empty() {
+        return GameEnding::Unknown;
+    }
+
+    let eliminated = losers.iter().all(|pn| {
+        combat
+            .defeated_players
+            .get(pn)
+            .is_some_and(|&tc| tc <= endgame_tc)
+    });
+    if eliminated {
+        GameEnding::Elimination
+    } else {
+        GameEnding::Surrender
+    }
+}
+
+/// Player numbers on the losing side of a certain winner; empty otherwise
+fn losing_players(
+    header_players: &[HeaderPlayer],
+    team_sides: &HashMap<i8, Side>,
+    pn_to_slot: &HashMap<u32, u8>,
+    winner: &Winner,
+) -> Vec<u32> {
+    let losing_side = match winner {
+        Winner::LeftTeam => Side::Right,
+        Winner::RightTeam => Side::Left,
+        _ => return Vec::new(),
+    };
+    group_team_players(header_players, pn_to_slot)
+        .into_iter()
+        .filter(|(team_raw, _)| team_sides.get(team_raw) == Some(&losing_side))
+        .flat_map(|(_, pns)| pns)
+        .collect()
+}
+
+/// Seconds from the first to the last defeat on the losing side of a
+/// certain winner, once at least two of its players fell. Defeats the raw
+/// scan recovered count like the chunk walk's.
+fn collapse_duration(
+    combat: &CombatResult,
+    header_players: &[HeaderPlayer],
+    team_sides: &HashMap<i8, Side>,
+    pn_to_slot: &HashMap<u32, u8>,
+    winner: &Winner,
+) -> Option<u32> {
+    let ticks: Vec<u32> = losing_players(header_players, team_sides, pn_to_slot, winner)
+        .iter()
+        .filter_map(|pn| combat.defeated_players.get(pn).copied())
+        .collect();
+    if ticks.len() < 2 {
+        return None;
+    }
+    let first = ticks.iter().min()?;
+    let last = ticks.iter().max()?;
+    Some((last - first) / SAGE_TICKS_PER_SECOND)
+}
+
+/// Determine winner based on game events, using chained strategies; the
+/// verdict names the strategy that decided it. An EndGame order that
+/// conflicts with the defeats is skipped, and whichever strategy decides
+/// instead is one step less sure.
+fn determine_winner(
+    parse_result: &ChunkParseResult,
+    header_players: &[HeaderPlayer],
+    team_sides: &HashMap<i8, Side>,
+    pn_to_slot: &HashMap<u32, u8>,
+    afk_slots: &HashSet<u8>,
+    draw_window: u32,
+) -> (WinnerVerdict, Option<EndGameConflict>) {
+    // Team grouping (shared by fallback strategies)
+    let team_players = group_team_players(header_players, pn_to_slot);
+    let conflict = endgame_conflict(&parse_result.combat, &team_players);
+    // AFK players are never defeated: the defeat checks go without them
+    let active_team_players: HashMap<i8, Vec<u32>> = team_players
+        .iter()
+        .map(|(&team_raw, pns)| {
+            let active: Vec<u32> = pns
+                .iter()
+                .copied()
+                .filter(|pn| !pn_to_slot.get(pn).is_some_and(|s| afk_slots.contains(s)))
+                .collect();
+            (team_raw, active)
+        })
+        .filter(|(_, pns)| !pns.is_empty())
+        .collect();
+
+    let verdict = conflict
+        .is_none()
+        .then(|| {
+            winner_from_endgame(
+                &parse_result.combat,
+                header_players,
+                team_sides,
+                pn_to_slot,
+                draw_window,
+            )
+        })
+        .flatten()
+        .or_else(|| {
+            if parse_result.combat.defeated_players.is_empty() {
+                return None;
+            }
+            winner_from_full_defeat(
+                &parse_result.combat.defeated_players,
+                &active_team_players,
+                team_sides,
+            )
+        })
+        .or_else(|| {
+            if parse_result.combat.defeated_players.is_empty() {
+                return None;
+            }
+            winner_from_majority_defeated(
+                &parse_result.combat.defeated_players,
+                &active_team_players,
+                team_sides,
+            )
+        })
+        .or_else(|| {
+            winner_from_last_activity(
+                &parse_result.player_last_build_tc,
+                &team_players,
+                team_sides,
+                parse_result.max_timecode,
+            )
+        })
+        .map(|verdict| match conflict {
+            Some(_) => WinnerVerdict {
+                confidence: verdict.confidence.lowered(),
+                ..verdict
+            },
+            None => verdict,
+        })
+        .unwrap_or_else(WinnerVerdict::undetermined);
+    (verdict, conflict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MapSpot;
+    use crate::parser::replay_builder::ReplayBuilder;
+
+    #[test]
+    fn test_extract_map_name() {
+        assert_eq!(
+            extract_map_name_from_path("385maps/map wor rhun"),
+            Some("map wor rhun".to_string())
+        );
+        assert_eq!(
+            extract_map_name_from_path("maps/fords of isen"),
+            Some("fords of isen".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_map_name_without_maps_prefix() {
+        assert_eq!(
+            extract_map_name_from_path("data/ini/custom/wor rhun custom"),
+            Some("wor rhun custom".to_string())
+        );
+        assert_eq!(
+            extract_map_name_from_path("map wor rhun"),
+            Some("map wor rhun".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_map_name_backslashes_and_trailing_separators() {
+        assert_eq!(
+            extract_map_name_from_path("data\\maps\\map wor rhun\\"),
+            Some("map wor rhun".to_string())
+        );
+        assert_eq!(
+            extract_map_name_from_path("385maps/map wor rhun/"),
+            Some("map wor rhun".to_string())
+        );
+        assert_eq!(
+            extract_map_name_from_path("maps/map wor rhun/map wor rhun.map"),
+            Some("map wor rhun".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_map_name_collapses_whitespace() {
+        assert_eq!(
+            extract_map_name_from_path("  maps/map  wor\trhun  "),
+            Some("map wor rhun".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_map_name_empty() {
+        assert_eq!(extract_map_name_from_path(""), None);
+        assert_eq!(extract_map_name_from_path("//"), None);
+    }
+
+    #[test]
+    fn test_parse_replay_keeps_raw_map_path() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BFME2RPL");
+        data.extend_from_slice(&1700000000u32.to_le_bytes());
+        data.extend_from_slice(&1700001000u32.to_le_bytes());
+        data.extend_from_slice(
+            b"M=data\\maps\\map wor rhun;S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0",
+        );
+        data.push(0);
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.map_name, "map wor rhun");
+        assert_eq!(info.map_path, "data\\maps\\map wor rhun");
+    }
+
+    #[test]
+    fn test_parse_player_data() {
+        let player = parse_player_data("HGusto,1A53EFD5,8094,TT,2,-1,1,1,0,1,0", 0).unwrap();
+        assert_eq!(player.name, "Gusto");
+        assert_eq!(player.uid, Some("1A53EFD5".to_string()));
+        assert_eq!(player.color_id, 2);
+        assert_eq!(player.faction_id, 1);
+        assert_eq!(player.team_raw, 1);
+    }
+
+    #[test]
+    fn test_slot_entries_past_the_cap_are_ignored() {
+        let entries: Vec<String> = (0..300)
+            .map(|i| format!("HP{},{:08x},8094,TT,{},-1,0,{},0,1,0", i, i, i % 10, i % 2))
+            .collect();
+        let header = format!("M=maps/map wor rhun;S={};", entries.join(":"));
+        let scan = find_players_and_spectators_in(header.as_bytes());
+        assert_eq!(scan.players.len(), MAX_SLOT_ENTRIES);
+        assert!(
+            scan.players
+                .iter()
+                .all(|p| (p.slot as usize) < MAX_SLOT_ENTRIES)
+        );
+    }
+
+    #[test]
+    fn test_color_roll_gives_up_when_every_color_is_taken() {
+        let mut r = super::super::prng::Bfme2Rand::new(7);
+        let all: HashSet<i8> = (0..10).collect();
+        assert_eq!(pick_untaken_color(&mut r, &all, 10), -1);
+        let one_free: HashSet<i8> = (0..9).collect();
+        assert_eq!(pick_untaken_color(&mut r, &one_free, 10), 9);
+    }
+
+    #[test]
+    fn test_skip_empty_slot() {
+        assert!(parse_player_data("X", 0).is_none());
+        assert!(parse_player_data("O", 0).is_none());
+    }
+
+    #[test]
+    fn create_a_hero_slots_are_read_past_the_hero_fields() {
+        // Hero template id and customization hash between TT and the color
+        let entry = "HGusto,1A53EFD5,8094,TT,14,1611364428,2,-1,1,1,0,1,0";
+        let player = parse_player_data(entry, 3).unwrap();
+        assert_eq!(player.name, "Gusto");
+        assert_eq!(
+            (player.color_id, player.faction_id, player.team_raw),
+            (2, 1, 1)
+        );
+        assert_eq!(
+            player.slot_warning,
+            Some(ReplayWarning::CreateAHeroSlot { slot: 3 })
+        );
+
+        let standard = parse_player_data("HGusto,1A53EFD5,8094,TT,2,-1,1,1,0,1,0", 0).unwrap();
+        assert_eq!(standard.slot_warning, None);
+    }
+
+    #[test]
+    fn unreadable_slot_fields_fall_back_to_random() {
+        let player = parse_player_data("HGusto,1A53EFD5,8094,TT,42,-1,1,1,0,1,0", 2).unwrap();
+        assert_eq!(
+            (player.color_id, player.faction_id, player.team_raw),
+            (-1, 1, 1)
+        );
+        assert_eq!(
+            player.slot_warning,
+            Some(ReplayWarning::MalformedSlot { slot: 2 })
+        );
+
+        // Not long enough for the hero layout either
+        let player = parse_player_data("HBob,87654321,8094,TT,3,-1,99,1", 0).unwrap();
+        assert_eq!(
+            (player.color_id, player.faction_id, player.team_raw),
+            (3, -1, 1)
+        );
+    }
+
+    #[test]
+    fn create_a_hero_slot_keeps_its_color_out_of_the_random_pool() {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+        data.extend_from_slice(&1_700_001_000u32.to_le_bytes());
+        data.extend_from_slice(
+            b"M=maps/map wor rhun;S=HAlice,12345678,8094,TT,14,1611364428,3,-1,0,0,0,1,0:\
+              HBob,87654321,8094,TT,-1,-1,5,1,0,1,0;",
+        );
+        data.push(0);
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.players.len(), 2);
+        let alice = &info.players[0];
+        assert_eq!(alice.color_id, 3);
+        assert_eq!(alice.color_rgb, PLAYER_COLORS[3]);
+        let bob = &info.players[1];
+        assert!((0..10).contains(&bob.color_id));
+        assert_ne!(bob.color_id, 3);
+        assert_eq!(
+            info.warnings,
+            vec![ReplayWarning::CreateAHeroSlot { slot: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_infer_faction_from_building() {
+        assert_eq!(infer_faction_from_building(2650), Some(Faction::Men));
+        assert_eq!(infer_faction_from_building(2600), Some(Faction::Elves));
+        assert_eq!(infer_faction_from_building(2550), Some(Faction::Dwarves));
+        assert_eq!(infer_faction_from_building(2160), Some(Faction::Goblins));
+        assert_eq!(infer_faction_from_building(2070), Some(Faction::Isengard));
+        assert_eq!(infer_faction_from_building(2140), Some(Faction::Mordor));
+    }
+
+    fn seen_buildings(orders: &[(u32, u32)]) -> HashMap<u32, BuildingSeen> {
+        let mut buildings: HashMap<u32, BuildingSeen> = HashMap::new();
+        for &(tick, bid) in orders {
+            buildings
+                .entry(bid)
+                .and_modify(|seen| seen.orders += 1)
+                .or_insert(BuildingSeen {
+                    first_tick: tick,
+                    orders: 1,
+                });
+        }
+        buildings
+    }
+
+    #[test]
+    fn test_faction_vote_outweighs_a_stray_building() {
+        // An early id in the Men range, then five Mordor orders
+        let buildings = seen_buildings(&[
+            (10, 2650),
+            (50, 2140),
+            (60, 2141),
+            (70, 2140),
+            (80, 2145),
+            (90, 2140),
+        ]);
+        let vote = detect_faction_from_buildings(&buildings).unwrap();
+        assert_eq!(vote.faction, Faction::Mordor);
+        assert!(!vote.fortress);
+        assert_eq!(vote.counts, [(Faction::Mordor, 5), (Faction::Men, 1)]);
+        assert_eq!(vote.tally(), (5, 6));
+    }
+
+    #[test]
+    fn test_faction_vote_ties_go_to_the_earliest_building() {
+        let buildings = seen_buildings(&[(40, 2140), (20, 2550), (60, 2140), (30, 2551)]);
+        let vote = detect_faction_from_buildings(&buildings).unwrap();
+        assert_eq!(vote.faction, Faction::Dwarves);
+        assert_eq!(vote.counts, [(Faction::Dwarves, 2), (Faction::Mordor, 2)]);
+
+        assert!(detect_faction_from_buildings(&seen_buildings(&[(10, 9999)])).is_none());
+    }
+
+    #[test]
+    fn test_fortress_overrides_the_vote() {
+        // Outvoted by stray Men ids, but only Isengard builds its fortress
+        let buildings = seen_buildings(&[(10, 2650), (20, 2651), (30, 2652), (90, 2060)]);
+        let vote = detect_faction_from_buildings(&buildings).unwrap();
+        assert_eq!(vote.faction, Faction::Isengard);
+        assert!(vote.fortress);
+        assert_eq!(vote.tally(), (1, 4));
+    }
+
+    #[test]
+    fn test_split_faction_vote_is_reported() {
+        // Alice's first building is a Men id
+        let mut builder = one_v_one_game();
+        for tick in [70, 80, 90, 100, 110] {
+            builder = builder.build_command(0, 2140, 1000.0, 4000.0, tick);
+        }
+        let info = parse_replay(&builder.finish()).unwrap();
+        assert_eq!(info.players[0].actual_faction, Some(Faction::Mordor));
+        assert_eq!(
+            info.warnings,
+            vec![ReplayWarning::SplitFactionVote {
+                slot: 0,
+                faction: Faction::Mordor,
+                votes: 5,
+                total: 6,
+            }]
+        );
+    }
+
+    /// Verified against the live 3dwarf replay via Frida trace.
+    /// Ground truth: mustafaa (slot 1) resolves to color 9 (White),
+    /// Gusto (slot 7) resolves to color 1 (Red).
+    #[test]
+    fn test_assign_player_colors_3dwarf() {
+        fn p(name: &str, slot: u8, color: i8, faction: i8, team: i8) -> HeaderPlayer {
+            HeaderPlayer {
+                name: name.into(),
+                uid: None,
+                color_id: color,
+                faction_id: faction,
+                team_raw: team,
+                slot,
+                startpos_raw: -1,
+                name_encoding: NameEncoding::Utf8,
+                slot_warning: None,
+            }
+        }
+        // 3dwarf occupied_slots: 0..7. Slots 5 and 6 are observers.
+        let mut players = vec![
+            p("ALPHA", 0, 7, 0, 1),
+            p("mustafaa", 1, -1, 2, 3),
+            p("SuperNova", 2, 0, 1, 3),
+            p("C__", 3, 6, -1, 1),
+            p("AKINCI", 4, 2, 3, 1),
+            p("Gusto", 7, -1, 4, 3),
+        ];
+        // Observers: slot 5 k$ln$, slot 6 Bullet, both with color_id=-1
+        let observers = vec![(5u8, -1i8), (6u8, -1i8)];
+        assign_player_colors_and_factions(&mut players, 442_667_640, &observers);
+
+        let by_name = |name: &str| players.iter().find(|p| p.name == name).unwrap();
+        let get_color = |name: &str| by_name(name).color_id;
+        let get_fac = |name: &str| by_name(name).faction_id;
+
+        // Color ground truth (verified via Frida trace).
+        assert_eq!(
+            get_color("mustafaa"),
+            9,
+            "mustafaa should resolve to White (9)"
+        );
+        assert_eq!(get_color("Gusto"), 1, "Gusto should resolve to Red (1)");
+
+        // Faction ground truth (template_id → header encoding):
+        //   ALPHA template=6 → Dwarves (2)
+        //   mustafaa template=6 → Dwarves (2)
+        //   SuperNova template=6 → Dwarves (2)
+        //   C__ template=8 → Mordor (5)
+        //   AKINCI template=3 → Men (0)
+        //   Gusto template=6 → Dwarves (2)
+        assert_eq!(get_fac("ALPHA"), 2, "ALPHA → Dwarves");
+        assert_eq!(get_fac("mustafaa"), 2, "mustafaa → Dwarves");
+        assert_eq!(get_fac("SuperNova"), 2, "SuperNova → Dwarves");
+        assert_eq!(get_fac("C__"), 5, "C__ → Mordor");
+        assert_eq!(get_fac("AKINCI"), 0, "AKINCI → Men");
+        assert_eq!(get_fac("Gusto"), 2, "Gusto → Dwarves");
+    }
+
+    #[test]
+    fn test_turkish_decode() {
+        // Test that Turkish characters are handled
+        let turkish_bytes = b"Test\xDD\xFD"; // I with dot, dotless i in Windows-1254
+        let (decoded, encoding) = decode_best(turkish_bytes);
+        assert!(decoded.contains("Test"));
+        assert_eq!(encoding, NameEncoding::Windows1254);
+    }
+
+    #[test]
+    fn test_names_decode_per_slot() {
+        // Russian (windows-1251) and Turkish (windows-1254) names in one lobby
+        let mut data = build_test_replay("map wor rhun", "");
+        data.pop(); // reopen the header to append raw slot bytes
+        data.extend_from_slice(b"H\xC4\xEC\xE8\xF2\xF0\xE8\xE9,12345678,8094,TT,0,-1,0,0,0,1,0:");
+        data.extend_from_slice(b"H\xDEahin,87654321,8094,TT,1,-1,1,1,0,1,0");
+        data.push(0);
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.players[0].name, "Дмитрий");
+        assert_eq!(info.players[0].name_encoding, NameEncoding::Windows1251);
+        assert_eq!(info.players[1].name, "Şahin");
+        assert_eq!(info.players[1].name_encoding, NameEncoding::Windows1254);
+    }
+
+    /// Build a minimal valid replay byte sequence for testing
+    fn build_test_replay(map_name: &str, players_str: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        // Magic
+        data.extend_from_slice(b"BFME2RPL");
+        // Start time (4 bytes) + End time (4 bytes)
+        data.extend_from_slice(&1700000000u32.to_le_bytes());
+        data.extend_from_slice(&1700001000u32.to_le_bytes());
+        // Header content
+        let header = format!("M=maps/{};S={}", map_name, players_str);
+        data.extend_from_slice(header.as_bytes());
+        // Null terminator (marks end of header / start of chunks)
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn test_parse_replay_valid_rhun() {
+        let data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let result = parse_replay(&data);
+        assert!(result.is_ok());
+        let info = result.unwrap();
+        assert_eq!(info.players.len(), 2);
+        assert_eq!(info.players[0].name, "Alice");
+        assert_eq!(info.players[1].name, "Bob");
+    }
+
+    #[test]
+    fn test_parse_replays_multi_splits_concatenated_games() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        // A magic string inside chunk data must not start a new game
+        data.extend(encode_chunk(100, CMD_UNIT_COMMAND, 3, &[]));
+        data.extend_from_slice(MAGIC);
+        data.extend(encode_chunk(200, CMD_UNIT_COMMAND, 4, &[]));
+        let first_len = data.len();
+        data.extend(build_test_replay(
+            "map wor rhun",
+            "HCarol,11111111,8094,TT,2,-1,0,0,0,1,0:HDave,22222222,8094,TT,3,-1,1,1,0,1,0",
+        ));
+
+        let games = split_games(&data);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].len(), first_len);
+
+        let results = parse_replays_multi(&data);
+        let names: Vec<Vec<String>> = results
+            .iter()
+            .map(|r| {
+                r.as_ref()
+                    .unwrap()
+                    .players
+                    .iter()
+                    .map(|p| p.name.clone())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(names, vec![vec!["Alice", "Bob"], vec!["Carol", "Dave"]]);
+    }
+
+    #[test]
+    fn test_single_game_is_not_split() {
+        let data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        assert_eq!(split_games(&data), vec![&data[..]]);
+        assert_eq!(parse_replays_multi(b"not a replay").len(), 1);
+    }
+
+    /// Encode a chunk with Int args (arg type 0x00) in replay wire format
+    fn encode_chunk(time_code: u32, order_type: u32, player_num: u32, ints: &[u32]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&time_code.to_le_bytes());
+        out.extend_from_slice(&order_type.to_le_bytes());
+        out.extend_from_slice(&player_num.to_le_bytes());
+        if ints.is_empty() {
+            out.push(0);
+        } else {
+            out.push(1);
+            out.push(0x00);
+            out.push(ints.len() as u8);
+            for v in ints {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Build chunk carrying a building id and a Vec3 position
+    fn encode_build_at(time_code: u32, player_num: u32, building: u32, x: f32, y: f32) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&time_code.to_le_bytes());
+        out.extend_from_slice(&CMD_BUILD_OBJECT.to_le_bytes());
+        out.extend_from_slice(&player_num.to_le_bytes());
+        out.extend_from_slice(&[2, 0x00, 1, 0x06, 1]);
+        out.extend_from_slice(&building.to_le_bytes());
+        for v in [x, y, 0.0] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn test_raw_scan_recovers_endgame_hidden_by_desync() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        // Alice (pn 3) builds top left, Bob (pn 4) top right.
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, 2160, 4000.0, 4000.0));
+        // A corrupt unit command whose args swallow Bob's EndGame chunk, so the
+        // chunk parser steps right over it.
+        let mut hidden = encode_chunk(900, CMD_END_GAME, 4, &[]);
+        hidden.resize(16, 0);
+        let ints: Vec<u32> = hidden
+            .chunks(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        data.extend(encode_chunk(890, CMD_UNIT_COMMAND, 3, &ints));
+        data.extend([0u8; 16]);
+
+        let (with_scan, stats) = ReplayParser::default().parse_with_stats(&data);
+        assert_eq!(with_scan.unwrap().winner, Winner::RightTeam);
+        assert_eq!(stats.raw_scan_recoveries, 1);
+
+        let without_scan = ReplayParser::builder()
+            .enable_raw_scan(false)
+            .build()
+            .parse(&data)
+            .unwrap();
+        assert_eq!(without_scan.winner, Winner::NotConcluded);
+    }
+
+    #[test]
+    fn test_pause_is_excluded_from_estimated_duration() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        // 10 minutes of commands, a 5 minute pause, 10 more minutes
+        for tc in (0..3000).chain(4500..7500).step_by(10) {
+            data.extend(encode_chunk(tc, CMD_UNIT_COMMAND, 3 + tc % 2, &[]));
+        }
+        data.extend([0u8; 16]);
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.raw_estimated_duration_secs, Some(7490 / 5));
+        assert_eq!(info.estimated_duration_secs, Some((7490 - 1510) / 5));
+
+        // A calibrated rate scales the estimate, within the sane range
+        let calibrated = ReplayParser::builder().tick_rate(4.0).build();
+        let info = calibrated.parse(&data).unwrap();
+        assert_eq!(
+            info.raw_estimated_duration_secs,
+            Some((7490.0 / 4.5) as u32)
+        );
+    }
+
+    #[test]
+    fn test_implied_tick_rate_from_concluded_game() {
+        // Header says 1000 seconds; the game ends at tick 5100
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, 2160, 4000.0, 4000.0));
+        let mut concluded = data.clone();
+        concluded.extend(encode_chunk(5100, CMD_END_GAME, 4, &[]));
+        concluded.extend([0u8; 16]);
+
+        let (info, stats) = ReplayParser::default().parse_with_stats(&concluded);
+        assert_eq!(stats.implied_tick_rate, Some(5.1));
+        let verdict = info.unwrap().verdict;
+        assert_eq!(verdict.source, Some(WinnerSource::EndGame));
+
+        // A crashed game's timecodes say nothing about the rate
+        data.extend(encode_chunk(5100, CMD_UNIT_COMMAND, 3, &[]));
+        data.extend([0u8; 16]);
+        let (info, stats) = ReplayParser::default().parse_with_stats(&data);
+        assert!(info.unwrap().game_crashed);
+        assert_eq!(stats.implied_tick_rate, None);
+    }
+
+    #[test]
+    fn test_lobby_options_are_read_from_the_header() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BFME2RPL");
+        data.extend_from_slice(&1700000000u32.to_le_bytes());
+        data.extend_from_slice(&1700001000u32.to_le_bytes());
+        data.extend_from_slice(
+            b"M=maps/map wor rhun;SD=42;RU=3 150 2000 0 1 0 1 -1 0 -1 -1 1;\
+              S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.push(0);
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(
+            info.lobby_options,
+            LobbyOptions {
+                starting_resources: Some(2000),
+                game_speed: Some(150),
+                ring_heroes: Some(true),
+                fortress_expansions: Some(false),
+            }
+        );
+        assert_eq!(info.players.len(), 2);
+    }
+
+    #[test]
+    fn test_unknown_lobby_options_are_left_out() {
+        // No options section at all
+        assert_eq!(
+            find_lobby_options(b"M=maps/x;S=HAlice"),
+            LobbyOptions::default()
+        );
+        // Short section ending in a null: only what is there is read
+        let options = find_lobby_options(b"M=maps/x;RU=3 100 1000\0 1 1 1");
+        assert_eq!(options.game_speed, Some(100));
+        assert_eq!(options.starting_resources, Some(1000));
+        assert_eq!(options.ring_heroes, None);
+        // Values out of range or not numbers
+        let options = find_lobby_options(b";RU=3 300 -5 x 2 1;S=");
+        assert_eq!(options.game_speed, None);
+        assert_eq!(options.starting_resources, None);
+        assert_eq!(options.ring_heroes, None);
+        assert_eq!(options.fortress_expansions, Some(true));
+    }
+
+    #[test]
+    fn test_chunks_start_probe_finds_chunks_before_the_first_null() {
+        // M= follows S= and the header text runs straight into the chunks, so
+        // the first null after the S= section is inside the first chunk
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BFME2RPL");
+        data.extend_from_slice(&1700000000u32.to_le_bytes());
+        data.extend_from_slice(&1700001000u32.to_le_bytes());
+        data.extend_from_slice(
+            b"GT=0;S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0;\
+              M=maps/map wor rhun;",
+        );
+        let first_chunk = data.len();
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, 2160, 4000.0, 4000.0));
+        for tc in [70, 80, 90] {
+            data.extend(encode_chunk(tc, CMD_UNIT_COMMAND, 3, &[]));
+        }
+        data.extend(encode_chunk(5000, CMD_END_GAME, 4, &[]));
+        data.extend([0u8; 16]);
+
+        let (_, candidate) = find_chunks_start(&data).unwrap();
+        assert!(candidate > first_chunk);
+
+        let (info, stats) = ReplayParser::default().parse_with_stats(&data);
+        let info = info.unwrap();
+        assert_eq!(stats.chunks_start_shift, candidate - first_chunk);
+        // Alice's only build is in the first chunk
+        assert!(info.players.iter().all(|p| p.map_position.is_some()));
+    }
+
+    #[test]
+    fn test_chunks_start_probe_leaves_a_clean_header_alone() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let start = data.len();
+        for tc in [50, 60, 70, 80, 90] {
+            data.extend(encode_chunk(tc, CMD_UNIT_COMMAND, 3, &[]));
+        }
+        data.extend([0u8; 16]);
+        let parser = ReplayParser::default();
+        let (s_marker, candidate) = find_chunks_start(&data).unwrap();
+        assert_eq!(candidate, start);
+        assert_eq!(
+            probe_chunks_start(&parser, &data, s_marker, candidate),
+            start
+        );
+        assert_eq!(parser.parse_with_stats(&data).1.chunks_start_shift, 0);
+    }
+
+    #[test]
+    fn test_timings_cover_every_parse_stage() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, 2160, 4000.0, 4000.0));
+        data.extend(encode_chunk(900, CMD_END_GAME, 4, &[]));
+
+        let (_, untimed) = ReplayParser::default().parse_with_stats(&data);
+        assert_eq!(untimed.timings, None);
+
+        let parser = ReplayParser::builder().record_timings(true).build();
+        let (result, stats) = parser.parse_with_stats(&data);
+        assert!(result.is_ok());
+        let timings = stats.timings.expect("timings recorded");
+        for (stage, elapsed) in &timings.stages()[..4] {
+            assert!(!elapsed.is_zero(), "{} reported no time", stage);
+        }
+        // Render stages are filled in by the renderer
+        assert!(timings.layout.is_zero());
+    }
+
+    #[test]
+    fn test_resync_skips_garbage_between_chunks() {
+        let header = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let chunks_start = header.len();
+        let mut data = header;
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        // Garbage that never forms a plausible header (consecutive bytes differ by 37)
+        data.extend((0..4096u32).map(|i| (i * 37 + 11) as u8));
+        data.extend(encode_build_at(300, 4, 2650, 4000.0, 1000.0));
+        data.extend(encode_chunk(400, CMD_UNIT_COMMAND, 3, &[]));
+        data.extend(encode_chunk(500, CMD_END_GAME, 3, &[]));
+
+        let info = parse_replay(&data).unwrap();
+        assert!(info.players.iter().all(|p| p.map_position.is_some()));
+        assert_eq!(
+            info.estimated_duration_secs,
+            Some(400 / SAGE_TICKS_PER_SECOND)
+        );
+
+        let header_players = parse_header(&data).unwrap().players;
+        let pn_to_slot: HashMap<u32, u8> = [(3, 0), (4, 1)].into_iter().collect();
+        let result = parse_and_analyze_chunks(
+            &ReplayParser::default(),
+            &data,
+            chunks_start,
+            &header_players,
+            &pn_to_slot,
+        );
+        // One jump over the whole garbage run instead of a probe per byte
+        assert_eq!(result.resync_count, 1);
+        assert_eq!(result.bytes_skipped, 4096);
+        assert!(result.combat.has_endgame());
+        assert_eq!(result.ignored_player_chunks, 0);
+    }
+
+    #[test]
+    fn test_resync_probes_bytes_when_no_header_follows() {
+        let header = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let chunks_start = header.len();
+        let mut data = header;
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend([0xFF; 64]);
+        // Not an order type resyncs look for
+        data.extend(encode_chunk(700, 1003, 4, &[]));
+        data.extend(encode_chunk(700, 1003, 4, &[]));
+
+        let header_players = parse_header(&data).unwrap().players;
+        let pn_to_slot: HashMap<u32, u8> = [(3, 0), (4, 1)].into_iter().collect();
+        let result = parse_and_analyze_chunks(
+            &ReplayParser::default(),
+            &data,
+            chunks_start,
+            &header_players,
+            &pn_to_slot,
+        );
+        assert_eq!(result.max_timecode, 700);
+        assert_eq!(result.resync_count, 0);
+        assert_eq!(result.bytes_skipped, 64);
+    }
+
+    #[test]
+    fn test_far_timecode_chunk_does_not_move_resync_anchor() {
+        let header = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let chunks_start = header.len();
+        let mut data = header;
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        // Corrupt chunk that still parses, hours past the game
+        data.extend(encode_chunk(9_000_000, CMD_UNIT_COMMAND, 3, &[]));
+        data.extend([0xFF; 32]);
+        data.extend(encode_build_at(300, 4, 2650, 4000.0, 1000.0));
+        data.extend(encode_chunk(500, CMD_END_GAME, 3, &[]));
+
+        let header_players = parse_header(&data).unwrap().players;
+        let pn_to_slot: HashMap<u32, u8> = [(3, 0), (4, 1)].into_iter().collect();
+        let result = parse_and_analyze_chunks(
+            &ReplayParser::default(),
+            &data,
+            chunks_start,
+            &header_players,
+            &pn_to_slot,
+        );
+        // The resync still finds Bob's build after the far timecode
+        assert_eq!(result.resync_count, 1);
+        assert_eq!(result.bytes_skipped, 32);
+        assert!(result.combat.has_endgame());
+        assert!(result.positions.player_positions.contains_key(&1));
+    }
+
+    /// Alice (team 0, slot 0) building top left, Bob (team 1, slot 1) bottom right
+    fn one_v_one_game() -> ReplayBuilder {
+        ReplayBuilder::new()
+            .player("Alice", Faction::Men, 0, 0)
+            .player("Bob", Faction::Mordor, 1, 1)
+            .build_command(0, 2650, 1000.0, 4000.0, 50)
+            .build_command(1, 2160, 4000.0, 1000.0, 60)
+    }
+
+    #[test]
+    fn test_first_defeat_is_the_earliest() {
+        let data = one_v_one_game()
+            .defeat(1, 1500)
+            .defeat(0, 3000)
+            .endgame(0, 3100)
+            .finish();
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.first_defeat, Some((1, 1500 / SAGE_TICKS_PER_SECOND)));
+        assert_eq!(info.first_defeat_text().as_deref(), Some("Bob at 5:00"));
+
+        // No defeats, no first fall
+        let quiet = one_v_one_game().endgame(0, 3100).finish();
+        assert_eq!(parse_replay(&quiet).unwrap().first_defeat, None);
+    }
+
+    #[test]
+    fn test_endgame_player_wins_end_to_end() {
+        let data = one_v_one_game().endgame(1, 900).finish();
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::RightTeam);
+        assert_eq!(info.verdict.source, Some(WinnerSource::EndGame));
+        assert_eq!(info.verdict.confidence, Confidence::Certain);
+        assert!(!info.game_crashed);
+        assert_eq!(
+            info.estimated_duration_secs,
+            Some(60 / SAGE_TICKS_PER_SECOND)
+        );
+    }
+
+    #[test]
+    fn test_full_defeat_wins_end_to_end() {
+        let data = one_v_one_game()
+            .unit_command(0, 1200, &[1601])
+            .defeat(1, 1500)
+            .finish();
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::LeftTeam);
+        assert_eq!(info.verdict.source, Some(WinnerSource::FullDefeat));
+        assert_eq!(info.verdict.confidence, Confidence::Certain);
+        assert_eq!(info.first_defeat, Some((1, 1500 / SAGE_TICKS_PER_SECOND)));
+    }
+
+    #[test]
+    fn test_crashed_game_end_to_end() {
+        // End time written but no result, both sides still active: the game died
+        let data = one_v_one_game()
+            .unit_command(0, 900, &[])
+            .unit_command(1, 900, &[])
+            .finish();
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::NotConcluded);
+        assert!(info.game_crashed);
+        assert!(!info.is_partial);
+
+        // Same game never closed: saved mid-game
+        let data = one_v_one_game()
+            .times(1_700_000_000, 0)
+            .unit_command(0, 900, &[])
+            .unit_command(1, 900, &[])
+            .finish();
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::InProgress);
+        assert!(!info.game_crashed);
+    }
+
+    #[test]
+    fn test_mirrored_map_name_flips_sides_and_winner() {
+        let data = one_v_one_game()
+            .map("map wor rhun mirrored")
+            .endgame(0, 900)
+            .finish();
+
+        let info = parse_replay(&data).unwrap();
+        assert!(info.mirrored);
+        let alice = info.players.iter().find(|p| p.name == "Alice").unwrap();
+        let bob = info.players.iter().find(|p| p.name == "Bob").unwrap();
+        assert_eq!(alice.spot, Some(MapSpot::TopRight));
+        assert_eq!(bob.spot, Some(MapSpot::BottomLeft));
+        // Alice won, and she is drawn on the right
+        assert_eq!(alice.team, 2);
+        assert_eq!(info.winner.side(), Some(Side::Right));
+
+        // Without the suffix the same game keeps its orientation
+        let plain = ReplayParser::builder()
+            .mirrored_map_suffixes(Vec::new())
+            .build()
+            .parse(&data)
+            .unwrap();
+        assert!(!plain.mirrored);
+        assert_eq!(plain.winner.side(), Some(Side::Left));
+    }
+
+    #[test]
+    fn test_mirrored_map_detected_by_out_of_bounds_majority() {
+        let lobby = ReplayBuilder::new()
+            .player("Alice", Faction::Men, 0, 0)
+            .player("Bob", Faction::Mordor, 1, 1);
+        // Both first buildings lie left of the standard layout
+        let data = lobby
+            .build_command(0, 2650, -800.0, 4000.0, 50)
+            .build_command(1, 2160, -4000.0, 1000.0, 60)
+            .finish();
+        let info = parse_replay(&data).unwrap();
+        assert!(info.mirrored);
+        let alice = info.players.iter().find(|p| p.name == "Alice").unwrap();
+        let bob = info.players.iter().find(|p| p.name == "Bob").unwrap();
+        assert_eq!(alice.spot, Some(MapSpot::TopRight));
+        assert_eq!(bob.spot, Some(MapSpot::BottomRight));
+
+        // One stray position out of two is not a majority
+        let data = ReplayBuilder::new()
+            .player("Alice", Faction::Men, 0, 0)
+            .player("Bob", Faction::Mordor, 1, 1)
+            .build_command(0, 2650, -800.0, 4000.0, 50)
+            .build_command(1, 2160, 4000.0, 1000.0, 60)
+            .finish();
+        let info = parse_replay(&data).unwrap();
+        assert!(!info.mirrored);
+        let alice = info.players.iter().find(|p| p.name == "Alice").unwrap();
+        assert_eq!(alice.spot, Some(MapSpot::TopLeft));
+    }
+
+    #[test]
+    fn test_duplicate_lobby_entry_without_activity_is_dropped() {
+        // Bob listed again on Alice's team; only his real slot builds
+        let game = one_v_one_game().player("Bob", Faction::Mordor, 0, 2);
+        let bob_uid = game.uid_of(1);
+        let data = game.uid(2, bob_uid).defeat(0, 1500).finish();
+
+        let info = parse_replay(&data).unwrap();
+        let slots: Vec<u8> = info.players.iter().map(|p| p.slot).collect();
+        assert_eq!(slots, [0, 1]);
+        assert!(info.warnings.contains(&ReplayWarning::DuplicatePlayer {
+            slot: 2,
+            name: "Bob".to_string(),
+        }));
+        // The ghost no longer keeps the left team alive
+        assert_eq!(info.winner, Winner::RightTeam);
+        assert_eq!(info.verdict.source, Some(WinnerSource::FullDefeat));
+    }
+
+    #[test]
+    fn test_duplicates_are_matched_by_uid_then_name() {
+        let player = |name: &str, uid: Option<&str>, slot| {
+            let mut player = PlayerBuilder::test_player(name, 1, slot, Faction::Men);
+            player.uid = uid.map(str::to_string);
+            player
+        };
+        let players = [
+            player("Bob", Some("1A2B3C4D"), 0),
+            player("Bobby", Some("1a2b3c4d"), 3),
+            player("Carol", None, 1),
+            player("Carol", None, 2),
+            player("Dave", Some("00000001"), 4),
+            player("Dave", Some("00000002"), 5),
+        ];
+        // Slot 3 is the one that played; nobody in Carol's pair did
+        assert_eq!(duplicate_entries(&players, |slot| slot == 3), [0, 2]);
+        // Two active entries are both kept
+        assert_eq!(duplicate_entries(&players, |slot| slot != 2), [2]);
+    }
+
+    #[test]
+    fn test_spawns_away_from_lobby_team_fall_back_to_clusters() {
+        // Bob (team 1) spawns on the left with Alice and Carol (team 0)
+        let data = ReplayBuilder::new()
+            .player("Alice", Faction::Men, 0, 0)
+            .player("Bob", Faction::Mordor, 1, 1)
+            .player("Carol", Faction::Elves, 0, 2)
+            .player("Dave", Faction::Goblins, 1, 3)
+            .build_command(0, 2650, 1000.0, 4000.0, 50)
+            .build_command(1, 2650, 1300.0, 2000.0, 55)
+            .build_command(2, 2650, 900.0, 1000.0, 60)
+            .build_command(3, 2650, 4000.0, 1000.0, 65)
+            .endgame(3, 900)
+            .finish();
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.warnings, vec![ReplayWarning::TeamSideMismatch]);
+        let team_of = |name: &str| info.players.iter().find(|p| p.name == name).unwrap().team;
+        assert_eq!(team_of("Alice"), 1);
+        assert_eq!(team_of("Carol"), 1);
+        assert_eq!(team_of("Bob"), 2);
+        assert_eq!(team_of("Dave"), 2);
+        // Dave's team is decided as the right side
+        assert_eq!(info.winner.side(), Some(Side::Right));
+        // Spots still follow where each player actually spawned
+        let bob = info.players.iter().find(|p| p.name == "Bob").unwrap();
+        assert_eq!(bob.spot.map(|s| s.side()), Some(Side::Left));
+    }
+
+    #[test]
+    fn test_lobby_teams_on_their_own_sides_raise_no_warning() {
+        let data = one_v_one_game().finish();
+        assert!(parse_replay(&data).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_two_means_splits_at_the_widest_gap() {
+        assert_eq!(
+            two_means(&[1000.0, 1300.0, 900.0, 4000.0]),
+            [false, false, false, true]
+        );
+        assert_eq!(two_means(&[500.0, 500.0]), [false, false]);
+        assert!(two_means(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_spellbook_purchases_name_nothing_until_power_ids_are_known() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, 2160, 4000.0, 1000.0));
+        // Args once guessed to be power IDs
+        data.extend(encode_chunk(300, CMD_PURCHASE_POWER, 3, &[3101]));
+        data.extend(encode_chunk(2700, CMD_PURCHASE_POWER, 4, &[1, 3208]));
+        data.extend(encode_chunk(6400, CMD_END_GAME, 3, &[]));
+
+        let info = parse_replay(&data).unwrap();
+        assert!(info.players.iter().all(|p| p.powers_used.is_empty()));
+        assert!(info.players.iter().all(|p| p.powers_text(1).is_none()));
+    }
+
+    #[test]
+    fn test_garbage_player_nums_and_building_ids_are_bounded() {
+        let header = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let chunks_start = header.len();
+        let mut data = header;
+        // A corrupt header claiming 60 occupied slots, all mapped to Alice's
+        let pn_to_slot: HashMap<u32, u8> = (3..63).map(|pn| (pn, 0)).collect();
+        for pn in 3..63 {
+            data.extend(encode_chunk(10 + pn, CMD_UNIT_COMMAND, pn, &[]));
+        }
+        // A flood of distinct building ids from one player
+        for building in 2001..2999 {
+            data.extend(encode_build_at(100, 3, building, 1000.0, 4000.0));
+        }
+        data.extend(encode_chunk(200, CMD_END_GAME, 3, &[]));
+
+        let header_players = parse_header(&data).unwrap().players;
+        let parser = ReplayParser::builder().full_chunk_analysis(true).build();
+        let result =
+            parse_and_analyze_chunks(&parser, &data, chunks_start, &header_players, &pn_to_slot);
+        assert_eq!(result.player_last_command_tc.len(), MAX_TRACKED_PLAYER_NUMS);
+        assert_eq!(result.ignored_player_chunks, 60 - 16);
+        assert_eq!(
+            result.positions.player_building_ids[&0].len(),
+            MAX_BUILDING_IDS_PER_PLAYER
+        );
+        assert!(result.combat.has_endgame());
+    }
+
+    /// Unit command carrying an id and a Vec3 position, like a move order
+    fn encode_unit_move(time_code: u32, player_num: u32, x: f32, y: f32) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&time_code.to_le_bytes());
+        out.extend_from_slice(&CMD_UNIT_COMMAND.to_le_bytes());
+        out.extend_from_slice(&player_num.to_le_bytes());
+        out.extend_from_slice(&[2, 0x00, 1, 0x06, 1]);
+        out.extend_from_slice(&7u32.to_le_bytes());
+        for v in [x, y, 0.0] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+
+    /// Two players who build right away (Alice a Men fortress, Bob `bob_building`), then
+    /// `moves` unit commands with a defeat, an EndGame hidden in arguments and
+    /// a garbage run late in the stream. Returns the data and where the chunks start.
+    fn long_game_with(moves: u32, bob_building: u32) -> (Vec<u8>, usize) {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let chunks_start = data.len();
+        data.extend(encode_build_at(50, 3, 2622, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, bob_building, 4000.0, 4000.0));
+        for i in 0..moves {
+            let tc = 100 + i;
+            data.extend(encode_unit_move(tc, 3 + i % 2, 2000.0, 2000.0));
+            if i == moves * 3 / 4 {
+                // Garbage around a defeat of Bob; its timecode is behind the
+                // stream, so the walk resyncs past it
+                data.extend((0..64u32).map(|i| (i * 37 + 11) as u8));
+                data.extend(encode_chunk(1000, CMD_PLAYER_DEFEATED, 4, &[]));
+                data.extend((0..64u32).map(|i| (i * 37 + 11) as u8));
+                // A late building, after the analysis has settled
+                data.extend(encode_build_at(tc, 3, 2700, 1000.0, 4000.0));
+            }
+        }
+        let end_tc = 100 + moves;
+        let mut hidden = encode_chunk(end_tc, CMD_END_GAME, 3, &[]);
+        hidden.resize(16, 0);
+        let ints: Vec<u32> = hidden
+            .chunks(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        data.extend(encode_chunk(end_tc, CMD_UNIT_COMMAND, 3, &ints));
+        data.extend([0u8; 16]);
+        (data, chunks_start)
+    }
+
+    /// [`long_game_with`] where Bob builds a Goblin fortress
+    fn long_game(moves: u32) -> (Vec<u8>, usize) {
+        long_game_with(moves, 2151)
+    }
+
+    /// A chunk walk decoding every chunk, followed by a separate raw scan of the whole buffer
+    fn two_pass(data: &[u8], chunks_start: usize) -> ChunkParseResult {
+        let header_players = parse_header(data).unwrap().players;
+        let pn_to_slot: HashMap<u32, u8> = [(3, 0), (4, 1)].into_iter().collect();
+        let walk_only = ReplayParser::builder()
+            .enable_raw_scan(false)
+            .full_chunk_analysis(true)
+            .build();
+        let mut result =
+            parse_and_analyze_chunks(&walk_only, data, chunks_start, &header_players, &pn_to_slot);
+        let valid: HashSet<u32> = [3, 4].into_iter().collect();
+        scan_critical_patterns(
+            &walk_only,
+            data,
+            chunks_start,
+            chunks_start..data.len(),
+            &valid,
+            &mut result.combat,
+        );
+        result
+    }
+
+    fn single_pass(data: &[u8], chunks_start: usize) -> ChunkParseResult {
+        let header_players = parse_header(data).unwrap().players;
+        let pn_to_slot: HashMap<u32, u8> = [(3, 0), (4, 1)].into_iter().collect();
+        parse_and_analyze_chunks(
+            &ReplayParser::default(),
+            data,
+            chunks_start,
+            &header_players,
+            &pn_to_slot,
+        )
+    }
+
+    #[test]
+    fn test_single_pass_finds_the_same_events_as_two_passes() {
+        let (data, chunks_start) = long_game(2000);
+        let expected = two_pass(&data, chunks_start);
+        let actual = single_pass(&data, chunks_start);
+
+        assert_eq!(
+            actual.combat.defeated_players,
+            expected.combat.defeated_players
+        );
+        assert_eq!(actual.combat.endgames, expected.combat.endgames);
+        // Both hidden events were recovered
+        assert_eq!(actual.combat.defeated_players.get(&4), Some(&1000));
+        assert_eq!(actual.combat.latest_endgame().map(|(pn, _)| pn), Some(3));
+        assert_eq!(actual.raw_scan_recoveries, 2);
+        assert_eq!(
+            actual.last_player_action_tick,
+            expected.last_player_action_tick
+        );
+        assert_eq!(actual.positions.player_positions.len(), 2);
+    }
+
+    #[test]
+    fn test_arguments_stop_being_decoded_once_settled() {
+        // Alice's late building is only seen while arguments are decoded
+        let late_building = |result: &ChunkParseResult| {
+            result.positions.player_building_ids[&0].contains_key(&2700)
+        };
+        let (data, chunks_start) = long_game(2000);
+        assert!(!late_building(&single_pass(&data, chunks_start)));
+        assert!(late_building(&two_pass(&data, chunks_start)));
+        // Bob's faction stays unknown, so decoding never stops
+        let (unsettled, chunks_start) = long_game_with(2000, 9999);
+        assert!(late_building(&single_pass(&unsettled, chunks_start)));
+        // Without a fortress Bob's faction could still be outvoted
+        let (unsettled, chunks_start) = long_game_with(2000, 2160);
+        assert!(late_building(&single_pass(&unsettled, chunks_start)));
+
+        // Factions still come from the early buildings
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.players[0].actual_faction, Some(Faction::Men));
+        assert_eq!(info.players[1].actual_faction, Some(Faction::Goblins));
+    }
+
+    #[test]
+    #[cfg(feature = "clock")]
+    fn test_time_budget_truncates_long_chunk_walks() {
+        let (data, _) = long_game(50_000);
+        let info = parse_replay_with_budget(&data, Duration::ZERO).unwrap();
+        assert_eq!(info.warnings, vec![ReplayWarning::Truncated]);
+        // The early buildings were read; the ending was not
+        assert_eq!(info.players[0].actual_faction, Some(Faction::Men));
+        assert!(info.players.iter().all(|p| p.map_position.is_some()));
+        assert_eq!(info.winner, Winner::Unknown);
+        assert!(!info.game_crashed);
+        assert!(info.estimated_duration_secs.unwrap() < 50_000 / SAGE_TICKS_PER_SECOND);
+
+        let full = parse_replay_with_budget(&data, Duration::from_secs(60)).unwrap();
+        assert!(full.warnings.is_empty());
+        assert_ne!(full.winner, Winner::Unknown);
+    }
+
+    #[test]
+    fn test_time_budget_leaves_small_files_alone() {
+        // Fewer chunks than a budget check interval: even no budget at all is enough
+        let data = one_v_one_game().defeat(1, 900).endgame(0, 910).finish();
+        let budgeted = parse_replay_with_budget(&data, Duration::ZERO).unwrap();
+        let plain = parse_replay(&data).unwrap();
+        assert!(budgeted.warnings.is_empty());
+        assert_eq!(budgeted.winner, plain.winner);
+        assert_eq!(budgeted.fingerprint(), plain.fingerprint());
+    }
+
+    #[test]
+    fn test_cut_off_files_are_flagged_truncated() {
+        let mut game = one_v_one_game().times(1_700_000_000, 1_700_003_000);
+        for i in 0..40 {
+            game = game.unit_command(i % 2, 3000 + i as u32 * 30, &[101, 102, 103, 104, 105]);
+        }
+        let data = game.defeat(1, 4500).endgame(0, 4510).finish();
+
+        let intact = parse_replay(&data).unwrap();
+        assert!(!intact.truncated);
+        assert_eq!(intact.winner, Winner::LeftTeam);
+
+        // Cut off in transfer: the defeats are gone, and so is the ending
+        let cut = parse_replay(&data[..data.len() * 7 / 10]).unwrap();
+        assert!(cut.truncated);
+        assert!(!cut.game_crashed);
+        assert_eq!(cut.winner, Winner::Unknown);
+        // What was read is still reported
+        assert_eq!(cut.players[0].spot, intact.players[0].spot);
+    }
+
+    /// Timing comparison on a multi-megabyte buffer:
+    /// `cargo test --release -- --ignored --nocapture bench_single_pass`
+    #[test]
+    #[ignore]
+    fn bench_single_pass_vs_two_pass() {
+        let (data, chunks_start) = long_game(200_000);
+        assert!(data.len() > 5 * 1024 * 1024);
+        let time = |f: &dyn Fn() -> ChunkParseResult| {
+            let started = std::time::Instant::now();
+            for _ in 0..5 {
+                std::hint::black_box(f());
+            }
+            started.elapsed() / 5
+        };
+        let two = time(&|| two_pass(&data, chunks_start));
+        let one = time(&|| single_pass(&data, chunks_start));
+        println!(
+            "{} bytes: two passes {:?}, single pass {:?}",
+            data.len(),
+            two,
+            one
+        );
+    }
+
+    #[test]
+    fn test_early_unit_commands_are_collected_but_infer_nothing_yet() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let chunks_start = data.len();
+        // Alice (Random, pn 3) never builds but gives unit commands early;
+        // one after the early window is not collected.
+        data.extend(encode_chunk(100, CMD_UNIT_COMMAND, 3, &[1751]));
+        data.extend(encode_chunk(400, CMD_UNIT_COMMAND, 3, &[1766, 42]));
+        data.extend(encode_chunk(
+            EARLY_UNIT_WINDOW_TICKS + 1,
+            CMD_UNIT_COMMAND,
+            3,
+            &[1601],
+        ));
+        // Bob (pn 4) builds Men: buildings still decide
+        data.extend(encode_build_at(50, 4, 2650, 4000.0, 1000.0));
+        data.extend(encode_chunk(120, CMD_UNIT_COMMAND, 4, &[1751]));
+        data.extend([0u8; 16]);
+
+        let header_players = parse_header(&data).unwrap().players;
+        let pn_to_slot: HashMap<u32, u8> = [(3, 0), (4, 1)].into_iter().collect();
+        let result = parse_and_analyze_chunks(
+            &ReplayParser::default(),
+            &data,
+            chunks_start,
+            &header_players,
+            &pn_to_slot,
+        );
+        assert_eq!(result.early_units.get(&0), Some(&vec![1751, 1766, 42]));
+
+        // No unit ranges are known yet, so units alone settle no faction
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.players[0].actual_faction, None);
+        assert_eq!(info.players[1].actual_faction, Some(Faction::Men));
+    }
+
+    #[test]
+    fn test_parser_options() {
+        let mut data = build_test_replay(
+            "map helms deep",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_chunk(5000, CMD_UNIT_COMMAND, 4, &[]));
+        data.extend([0u8; 16]);
+
+        assert!(matches!(
+            parse_replay(&data),
+            Err(ReplayError::UnsupportedMap(_))
+        ));
+
+        let any_map = ReplayParser::builder().allowed_maps(None).build();
+        let info = any_map.parse(&data).unwrap();
+        assert!(info.players[0].actual_faction.is_some());
+        assert_eq!(
+            info.estimated_duration_secs,
+            Some(5000 / SAGE_TICKS_PER_SECOND)
+        );
+
+        let strict = ReplayParser::builder()
+            .allowed_maps(None)
+            .max_timecode(1000)
+            .infer_factions(false)
+            .build();
+        let info = strict.parse(&data).unwrap();
+        assert_eq!(info.players[0].actual_faction, None);
+        assert_eq!(
+            info.estimated_duration_secs,
+            Some(50 / SAGE_TICKS_PER_SECOND)
+        );
+    }
+
+    #[test]
+    fn test_activity_buckets() {
+        let activity: HashMap<u8, HashMap<u32, u32>> = HashMap::from([
+            (0, HashMap::from([(0, 3), (2, 1)])),
+            (1, HashMap::from([(0, 2), (1, 4)])),
+            (2, HashMap::from([(0, 5)])),
+            // Slot without a known side is ignored
+            (7, HashMap::from([(5, 9)])),
+        ]);
+        let sides = HashMap::from([(0, Side::Left), (1, Side::Right), (2, Side::Left)]);
+        assert_eq!(
+            build_activity_buckets(&activity, &sides),
+            [(0, 8, 2), (150, 0, 4), (300, 1, 0)]
+        );
+        assert!(build_activity_buckets(&HashMap::new(), &sides).is_empty());
+    }
+
+    #[test]
+    fn test_activity_buckets_stop_at_the_cap() {
+        // A stray timecode near the sanity limit doesn't fill in the gap to it
+        let activity = HashMap::from([(0, HashMap::from([(0, 1), (66_000, 1)]))]);
+        let sides = HashMap::from([(0, Side::Left)]);
+        assert_eq!(build_activity_buckets(&activity, &sides), [(0, 1, 0)]);
+    }
+
+    #[test]
+    fn test_activity_buckets_from_chunks() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, 2160, 4000.0, 4000.0));
+        data.extend(encode_chunk(149, CMD_UNIT_COMMAND, 3, &[]));
+        data.extend(encode_chunk(150, CMD_UNIT_COMMAND, 4, &[]));
+        data.extend(encode_chunk(460, CMD_UNIT_COMMAND, 3, &[]));
+        data.extend([0u8; 16]);
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(
+            info.activity_buckets,
+            [(0, 2, 1), (150, 0, 1), (300, 0, 0), (450, 1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_spectator_tail_does_not_extend_duration() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0:HObs,11111111,8094,TT,-1,-2,-1,-1,0,1,0",
+        );
+        // Alice (pn 3) and Bob (pn 4) play until tick 1500.
+        data.extend(encode_chunk(50, CMD_BUILD_OBJECT, 3, &[2650]));
+        data.extend(encode_chunk(1000, CMD_UNIT_COMMAND, 3, &[]));
+        data.extend(encode_chunk(1500, CMD_UNIT_COMMAND, 4, &[]));
+        // Observer (slot 2 → pn 5) keeps issuing commands ten minutes later.
+        data.extend(encode_chunk(
+            1500 + 600 * SAGE_TICKS_PER_SECOND,
+            CMD_UNIT_COMMAND,
+            5,
+            &[],
+        ));
+        data.extend(encode_chunk(
+            1500 + 601 * SAGE_TICKS_PER_SECOND,
+            CMD_BUILD_OBJECT,
+            5,
+            &[2650],
+        ));
+        data.extend([0u8; 16]);
+
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.spectators.len(), 1);
+        assert_eq!(
+            info.estimated_duration_secs,
+            Some(1500 / SAGE_TICKS_PER_SECOND)
+        );
+    }
+
+    /// An observer in slot 0 ahead of Alice (slot 1) and Bob (slot 2), in a
+    /// game that numbered only the players: Alice is pn 3, Bob pn 4
+    fn game_skipping_an_observer_pn() -> Vec<u8> {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HObs,11111111,8094,TT,-1,-2,-1,-1,0,1,0:HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 4, 2160, 4000.0, 4000.0));
+        for tc in (100..400).step_by(50) {
+            data.extend(encode_unit_move(tc, 3, 1200.0, 3800.0));
+            data.extend(encode_unit_move(tc + 5, 4, 3800.0, 3800.0));
+        }
+        // Bob falls, then Alice ends the game
+        data.extend(encode_chunk(1500, CMD_PLAYER_DEFEATED, 4, &[]));
+        data.extend(encode_chunk(1600, CMD_END_GAME, 3, &[]));
+        data.extend([0u8; 16]);
+        data
+    }
+
+    #[test]
+    fn test_pn_mapping_skips_an_observer_when_the_chunks_say_so() {
+        let (info, stats) =
+            ReplayParser::default().parse_with_stats(&game_skipping_an_observer_pn());
+        let info = info.unwrap();
+        assert_eq!(stats.pn_mapping, PnMapping::PlayersOnly);
+
+        let alice = info.players.iter().find(|p| p.name == "Alice").unwrap();
+        let bob = info.players.iter().find(|p| p.name == "Bob").unwrap();
+        assert_eq!(alice.spot, Some(MapSpot::TopLeft));
+        assert_eq!(bob.spot, Some(MapSpot::TopRight));
+        assert_eq!(info.winner, Winner::LeftTeam);
+        assert_eq!(info.first_defeat, Some((2, 1500 / SAGE_TICKS_PER_SECOND)));
+    }
+
+    #[test]
+    fn test_pn_mapping_keeps_observers_numbered_when_they_play_along() {
+        // Same lobby, but the game numbered the observer: Alice 4, Bob 5
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HObs,11111111,8094,TT,-1,-2,-1,-1,0,1,0:HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 4, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(60, 5, 2160, 4000.0, 4000.0));
+        data.extend(encode_chunk(1500, CMD_PLAYER_DEFEATED, 5, &[]));
+        data.extend(encode_chunk(1600, CMD_END_GAME, 4, &[]));
+        data.extend([0u8; 16]);
+
+        let (info, stats) = ReplayParser::default().parse_with_stats(&data);
+        assert_eq!(stats.pn_mapping, PnMapping::AllSlots);
+        assert_eq!(info.unwrap().winner, Winner::LeftTeam);
+    }
+
+    const LOBBY_WITH_OBSERVERS: &str = "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0:HObsA,11111111,8094,TT,-1,-2,-1,-1,0,1,0:HObsB,22222222,8094,TT,-1,-2,-1,-1,0,1,0:X:X:X:X;";
+
+    #[test]
+    fn test_observers_from_later_sections_are_collected() {
+        // A later section repeats ObsB, adds a late joiner and lists Alice again
+        let with_late = format!(
+            "{}SR=1;S=HObsB,22222222,8094,TT,-1,-2,-1,-1,0,1,0:HLate,33333333,8094,TT,-1,-2,-1,-1,0,1,0:HAlice,12345678,8094,TT,0,-1,0,0,0,1,0",
+            LOBBY_WITH_OBSERVERS
+        );
+        let info = parse_replay(&build_test_replay("map wor rhun", &with_late)).unwrap();
+        let observers: Vec<(&str, Option<&str>, Option<u8>)> = info
+            .spectators
+            .iter()
+            .map(|s| (s.name.as_str(), s.uid.as_deref(), s.slot))
+            .collect();
+        assert_eq!(
+            observers,
+            [
+                ("ObsA", Some("11111111"), Some(2)),
+                ("ObsB", Some("22222222"), Some(3)),
+                ("Late", Some("33333333"), None),
+            ]
+        );
+        assert_eq!(info.players.len(), 2);
+
+        // Players and their random colors only depend on the lobby slot list
+        let lobby_only =
+            parse_replay(&build_test_replay("map wor rhun", LOBBY_WITH_OBSERVERS)).unwrap();
+        assert_eq!(lobby_only.spectators.len(), 2);
+        for (a, b) in info.players.iter().zip(&lobby_only.players) {
+            assert_eq!((a.slot, a.color_rgb), (b.slot, b.color_rgb));
+        }
+    }
+
+    #[test]
+    fn test_slot_sections_stop_at_the_header_end() {
+        let mut header = b"M=maps/x;S=HA,1,2,TT,0,-1,0,0;T=5;S=HB,1,2,TT,0,-1,0,-1".to_vec();
+        header.push(0);
+        header.extend_from_slice(b";S=HChunk,1,2,TT,0,-1,0,-1");
+        let sections = slot_sections(&header);
+        assert_eq!(
+            sections,
+            [&b"HA,1,2,TT,0,-1,0,0"[..], &b"HB,1,2,TT,0,-1,0,-1"[..]]
+        );
+        assert!(slot_sections(b"M=maps/x;SD=5").is_empty());
+    }
+
+    /// Two-player game with a few commands and no result, header end time zeroed
+    fn unfinished_replay() -> Vec<u8> {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data[12..16].copy_from_slice(&0u32.to_le_bytes());
+        data.extend(encode_chunk(50, CMD_BUILD_OBJECT, 3, &[2650]));
+        data.extend(encode_chunk(900, CMD_UNIT_COMMAND, 4, &[]));
+        data
+    }
+
+    #[test]
+    fn test_mid_game_save_is_in_progress() {
+        let info = parse_replay(&unfinished_replay()).unwrap();
+        assert_eq!(info.winner, Winner::InProgress);
+        assert!(info.is_partial);
+        assert!(!info.game_crashed);
+
+        // End time equal to start time counts as "not written" too
+        let mut data = unfinished_replay();
+        data[12..16].copy_from_slice(&1700000000u32.to_le_bytes());
+        assert_eq!(parse_replay(&data).unwrap().winner, Winner::InProgress);
+    }
+
+    #[test]
+    fn test_crash_is_not_mistaken_for_mid_game_save() {
+        // Trailing junk after the last chunk: the game died mid-write
+        let mut data = unfinished_replay();
+        data.extend([0xFFu8; 40]);
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::NotConcluded);
+        assert!(info.game_crashed);
+        assert!(!info.is_partial);
+
+        // A written end time means the game was over when the file was closed
+        let mut data = unfinished_replay();
+        data[12..16].copy_from_slice(&1700001000u32.to_le_bytes());
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::NotConcluded);
+        assert!(!info.is_partial);
+    }
+
+    #[test]
+    fn test_first_action_timings() {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        // Alice (slot 0 → pn 3) builds at 50 and 80, issues a unit command at 120.
+        data.extend(encode_chunk(50, CMD_BUILD_OBJECT, 3, &[2650]));
+        data.extend(encode_chunk(80, CMD_BUILD_OBJECT_2, 3, &[2651]));
+        data.extend(encode_chunk(120, CMD_UNIT_COMMAND, 3, &[]));
+        // Bob (slot 1 → pn 4) only builds.
+        data.extend(encode_chunk(70, CMD_BUILD_OBJECT, 4, &[2160]));
+        data.extend([0u8; 16]);
+
+        let info = parse_replay(&data).unwrap();
+        let alice = info.players.iter().find(|p| p.name == "Alice").unwrap();
+        let bob = info.players.iter().find(|p| p.name == "Bob").unwrap();
+        assert_eq!(alice.first_action(OrderKind::Build), Some(50));
+        assert_eq!(alice.first_action(OrderKind::UnitCommand), Some(120));
+        assert_eq!(bob.first_action(OrderKind::Build), Some(70));
+        assert_eq!(bob.first_action(OrderKind::UnitCommand), None);
+    }
+
+    #[test]
+    fn test_parse_replay_unsupported_map() {
+        let data = build_test_replay(
+            "fords of isen",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        let result = parse_replay(&data);
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            ReplayError::UnsupportedMap(name) => assert_eq!(name, "fords of isen"),
+            other => panic!("Expected UnsupportedMap, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_replay_corrupt_data() {
+        // Too short to even have magic bytes
+        let result = parse_replay(&[0u8; 4]);
+        assert!(matches!(result, Err(ReplayError::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_parse_replay_bad_magic() {
+        let mut data = vec![0u8; 24];
+        data[..8].copy_from_slice(b"NOTMAGIC");
+        let result = parse_replay(&data);
+        assert!(matches!(result, Err(ReplayError::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_parse_replay_other_game() {
+        let mut data = b"BFME1RPL".to_vec();
+        data.resize(4096, 0);
+        let result = parse_replay(&data);
+        assert!(matches!(result, Err(ReplayError::WrongGame(game)) if game == "BFME1"));
+    }
+
+    #[test]
+    fn test_parse_replay_no_players() {
+        let data = build_test_replay("map wor rhun", "X:X:X:X");
+        let result = parse_replay(&data);
+        assert!(matches!(result, Err(ReplayError::NoPlayers)));
+    }
+
+    #[test]
+    fn test_char_safe_name_slicing() {
+        // Test that H-prefix stripping works with multi-byte characters
+        let player = parse_player_data("HTest,12345678,8094,TT,0,-1,0,0,0,1,0", 0).unwrap();
+        assert_eq!(player.name, "Test");
+    }
+
+    #[test]
+    fn test_endgame_defeated_player_means_other_team_wins() {
+        // When the EndGame player is also in defeated_players,
+        // their team lost — the other team should win.
+        let mut defeated = HashMap::new();
+        defeated.insert(4u32, 6900u32); // pn=4 is defeated
+
+        let combat = CombatResult {
+            defeated_players: defeated,
+            endgames: vec![(4, 7000)], // same player triggered EndGame
+        };
+
+        // pn=4 → slot=1 (Left team, team_raw=0)
+        let header_players = vec![
+            HeaderPlayer {
+                name: "LeftPlayer".to_string(),
+                uid: None,
+                slot: 1,
+                color_id: 0,
+                faction_id: 0,
+                team_raw: 0,
+                startpos_raw: -1,
+                name_encoding: NameEncoding::Utf8,
+                slot_warning: None,
+            },
+            HeaderPlayer {
+                name: "RightPlayer".to_string(),
+                uid: None,
+                slot: 2,
+                color_id: 1,
+                faction_id: 1,
+                team_raw: 1,
+                startpos_raw: -1,
+                name_encoding: NameEncoding::Utf8,
+                slot_warning: None,
+            },
+        ];
+
+        let mut team_sides = HashMap::new();
+        team_sides.insert(0i8, Side::Left);
+        team_sides.insert(1i8, Side::Right);
+
+        let mut pn_to_slot = HashMap::new();
+        pn_to_slot.insert(4u32, 1u8);
+        pn_to_slot.insert(5u32, 2u8);
+
+        let result = winner_from_endgame(&combat, &header_players, &team_sides, &pn_to_slot, 50);
+        // Left player was defeated + triggered EndGame → Right team wins
+        assert_eq!(result, Some(endgame_verdict(Side::Right)));
+    }
+
+    #[test]
+    fn test_endgame_non_defeated_player_means_their_team_wins() {
+        // When the EndGame player is NOT defeated, their team wins (normal case).
+        let combat = CombatResult {
+            defeated_players: HashMap::new(),
+            endgames: vec![(5, 7000)], // Right player triggered EndGame, not defeated
+        };
+
+        let header_players = vec![
+            HeaderPlayer {
+                name: "LeftPlayer".to_string(),
+                uid: None,
+                slot: 1,
+                color_id: 0,
+                faction_id: 0,
+                team_raw: 0,
+                startpos_raw: -1,
+                name_encoding: NameEncoding::Utf8,
+                slot_warning: None,
+            },
+            HeaderPlayer {
+                name: "RightPlayer".to_string(),
+                uid: None,
+                slot: 2,
+                color_id: 1,
+                faction_id: 1,
+                team_raw: 1,
+                startpos_raw: -1,
+                name_encoding: NameEncoding::Utf8,
+                slot_warning: None,
+            },
+        ];
+
+        let mut team_sides = HashMap::new();
+        team_sides.insert(0i8, Side::Left);
+        team_sides.insert(1i8, Side::Right);
+
+        let mut pn_to_slot = HashMap::new();
+        pn_to_slot.insert(4u32, 1u8);
+        pn_to_slot.insert(5u32, 2u8);
+
+        let result = winner_from_endgame(&combat, &header_players, &team_sides, &pn_to_slot, 50);
+        // Right player triggered EndGame and was NOT defeated → Right team wins
+        let verdict = result.unwrap();
+        assert_eq!(verdict.to_winner(), Winner::RightTeam);
+        assert_eq!(verdict.confidence, Confidence::Certain);
+        assert_eq!(verdict.source, Some(WinnerSource::EndGame));
+    }
+
+    fn endgames(events: &[(u32, u32)]) -> CombatResult {
+        CombatResult {
+            defeated_players: HashMap::new(),
+            endgames: events.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_single_endgame_names_its_side() {
+        let (hps, sides, pns) = one_v_one();
+        let verdict = winner_from_endgame(&endgames(&[(4, 7000)]), &hps, &sides, &pns, 50);
+        assert_eq!(verdict, Some(endgame_verdict(Side::Left)));
+    }
+
+    #[test]
+    fn test_endgames_of_both_sides_within_the_window_are_a_draw() {
+        let (hps, sides, pns) = one_v_one();
+        let combat = endgames(&[(4, 7000), (5, 7030)]);
+        let verdict = winner_from_endgame(&combat, &hps, &sides, &pns, 50).unwrap();
+        assert_eq!(verdict, WinnerVerdict::draw());
+        assert_eq!(verdict.to_winner(), Winner::Draw);
+        // In either order, and right at the edge of the window
+        let combat = endgames(&[(5, 7000), (4, 7050)]);
+        let verdict = winner_from_endgame(&combat, &hps, &sides, &pns, 50);
+        assert_eq!(verdict, Some(WinnerVerdict::draw()));
+        // Two orders of the same side are no draw
+        let verdict =
+            winner_from_endgame(&endgames(&[(5, 7000), (5, 7010)]), &hps, &sides, &pns, 50);
+        assert_eq!(verdict, Some(endgame_verdict(Side::Right)));
+    }
+
+    #[test]
+    fn test_endgames_of_both_sides_far_apart_go_to_the_latest() {
+        let (hps, sides, pns) = one_v_one();
+        let combat = endgames(&[(4, 7000), (5, 7051)]);
+        let verdict = winner_from_endgame(&combat, &hps, &sides, &pns, 50);
+        assert_eq!(verdict, Some(endgame_verdict(Side::Right)));
+        let combat = endgames(&[(5, 2000), (4, 7000)]);
+        let verdict = winner_from_endgame(&combat, &hps, &sides, &pns, 50);
+        assert_eq!(verdict, Some(endgame_verdict(Side::Left)));
+    }
+
+    #[test]
+    fn test_endgame_of_a_defeated_player_is_no_draw() {
+        let (hps, sides, pns) = one_v_one();
+        let mut combat = endgames(&[(4, 7000), (5, 7010)]);
+        combat.defeated_players.insert(4, 6990);
+        let verdict = winner_from_endgame(&combat, &hps, &sides, &pns, 50);
+        assert_eq!(verdict, Some(endgame_verdict(Side::Right)));
+    }
+
+    #[test]
+    fn test_draw_end_to_end() {
+        let data = one_v_one_game().endgame(0, 900).endgame(1, 910).finish();
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::Draw);
+        assert_eq!(info.verdict.source, Some(WinnerSource::EndGame));
+        assert_eq!(info.ending, GameEnding::Unknown);
+        assert!(!info.game_crashed);
+
+        // A narrower window lets the latest order decide
+        let parser = ReplayParser::builder().draw_window_ticks(5).build();
+        assert_eq!(parser.parse(&data).unwrap().winner, Winner::RightTeam);
+    }
+
+    /// Two 1v1 header players (slot 1 Left team_raw 0, slot 2 Right team_raw 1)
+    /// plus the matching side and pn maps (pn 4 → slot 1, pn 5 → slot 2).
+    fn one_v_one() -> (Vec<HeaderPlayer>, HashMap<i8, Side>, HashMap<u32, u8>) {
+        let hp = |name: &str, slot: u8, team_raw: i8| HeaderPlayer {
+            name: name.to_string(),
+            uid: None,
+            slot,
+            color_id: 0,
+            faction_id: 0,
+            team_raw,
+            startpos_raw: -1,
+            name_encoding: NameEncoding::Utf8,
+            slot_warning: None,
+        };
+        let header_players = vec![hp("LeftPlayer", 1, 0), hp("RightPlayer", 2, 1)];
+        let team_sides = HashMap::from([(0i8, Side::Left), (1i8, Side::Right)]);
+        let pn_to_slot = HashMap::from([(4u32, 1u8), (5u32, 2u8)]);
+        (header_players, team_sides, pn_to_slot)
+    }
+
+    #[test]
+    fn test_full_defeat_is_certain() {
+        let (hps, sides, pns) = one_v_one();
+        let teams = group_team_players(&hps, &pns);
+        let defeated = HashMap::from([(4u32, 6900u32)]);
+        let verdict = winner_from_full_defeat(&defeated, &teams, &sides).unwrap();
+        assert_eq!(
+            verdict,
+            WinnerVerdict::decided(Side::Right, Confidence::Certain, WinnerSource::FullDefeat)
+        );
+        assert_eq!(verdict.to_winner(), Winner::RightTeam);
+    }
+
+    #[test]
+    fn test_majority_defeat_is_high_confidence() {
+        let teams = HashMap::from([(0i8, vec![3u32, 4]), (1i8, vec![5u32, 6])]);
+        let sides = HashMap::from([(0i8, Side::Left), (1i8, Side::Right)]);
+        let defeated = HashMap::from([(5u32, 4000u32)]);
+        let verdict = winner_from_majority_defeated(&defeated, &teams, &sides).unwrap();
+        assert_eq!(verdict.side, Some(Side::Left));
+        assert_eq!(verdict.confidence, Confidence::High);
+        assert_eq!(verdict.source, Some(WinnerSource::MajorityDefeat));
+        assert_eq!(verdict.to_winner(), Winner::LikelyLeftTeam);
+        assert_eq!(winner_from_full_defeat(&defeated, &teams, &sides), None);
+    }
+
+    #[test]
+    fn test_activity_dropoff_is_low_confidence() {
+        let (hps, sides, pns) = one_v_one();
+        let teams = group_team_players(&hps, &pns);
+        let last_builds = HashMap::from([(4u32, 2000u32), (5u32, 9000u32)]);
+        let verdict = winner_from_last_activity(&last_builds, &teams, &sides, 10_000).unwrap();
+        assert_eq!(verdict.side, Some(Side::Right));
+        assert_eq!(verdict.confidence, Confidence::Low);
+        assert_eq!(verdict.source, Some(WinnerSource::ActivityDropoff));
+        assert_eq!(verdict.to_winner(), Winner::LikelyRightTeam);
+        // Too small a gap decides nothing
+        let close = HashMap::from([(4u32, 8900u32), (5u32, 9000u32)]);
+        assert_eq!(
+            winner_from_last_activity(&close, &teams, &sides, 10_000),
+            None
+        );
+    }
+
+    #[test]
+    fn test_undetermined_verdict_is_unknown() {
+        let verdict = WinnerVerdict::undetermined();
+        assert_eq!(verdict.to_winner(), Winner::Unknown);
+        assert_eq!(verdict.source, None);
+    }
+
+    /// Alice (pn 3) and Carl (pn 5) on the left against Bob (pn 4) and Dan
+    /// (pn 6) on the right, with `(pn, tick)` defeats and an EndGame order
+    fn two_v_two(defeats: &[(u32, u32)], endgame: Option<(u32, u32)>) -> Vec<u8> {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0:\
+             HCarl,11111111,8094,TT,2,-1,0,0,0,1,0:HDan,22222222,8094,TT,3,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(55, 4, 2160, 4000.0, 4000.0));
+        data.extend(encode_build_at(60, 5, 2650, 1000.0, 1000.0));
+        data.extend(encode_build_at(65, 6, 2160, 4000.0, 1000.0));
+        let mut events: Vec<(u32, u32, u32)> = defeats
+            .iter()
+            .map(|&(pn, tc)| (tc, CMD_PLAYER_DEFEATED, pn))
+            .chain(endgame.map(|(pn, tc)| (tc, CMD_END_GAME, pn)))
+            .collect();
+        events.sort();
+        for (tc, order, pn) in events {
+            data.extend(encode_chunk(tc, order, pn, &[]));
+        }
+        data.extend([0u8; 16]);
+        data
+    }
+
+    fn verdict_of(data: &[u8]) -> (WinnerVerdict, Option<EndGameConflict>) {
+        let (info, stats) = ReplayParser::default().parse_with_stats(data);
+        (info.unwrap().verdict, stats.endgame_conflict)
+    }
+
+    #[test]
+    fn test_endgame_consistent_with_defeats_is_trusted() {
+        // Bob falls, Alice's EndGame agrees
+        let (verdict, conflict) = verdict_of(&two_v_two(&[(4, 1500)], Some((3, 1600))));
+        assert_eq!(conflict, None);
+        assert_eq!(verdict, endgame_verdict(Side::Left));
+    }
+
+    #[test]
+    fn test_endgame_from_an_already_defeated_player_falls_through() {
+        // Bob is defeated, then leaves first and records the EndGame
+        let (verdict, conflict) = verdict_of(&two_v_two(&[(4, 1500)], Some((4, 1600))));
+        assert_eq!(conflict, Some(EndGameConflict::PlayerDefeatedFirst));
+        assert_eq!(
+            verdict,
+            WinnerVerdict::decided(Side::Left, Confidence::Low, WinnerSource::MajorityDefeat)
+        );
+
+        // With the whole team down, the full defeat decides, one step less sure
+        let (verdict, _) = verdict_of(&two_v_two(&[(4, 1500), (6, 1550)], Some((4, 1600))));
+        assert_eq!(
+            verdict,
+            WinnerVerdict::decided(Side::Left, Confidence::High, WinnerSource::FullDefeat)
+        );
+        assert_eq!(verdict.to_winner(), Winner::LikelyLeftTeam);
+    }
+
+    #[test]
+    fn test_endgame_against_the_defeat_majority_falls_through() {
+        // Dan is still standing but his team lost Bob; Alice's side lost no one
+        let (verdict, conflict) = verdict_of(&two_v_two(&[(4, 1500)], Some((6, 1600))));
+        assert_eq!(conflict, Some(EndGameConflict::TeamMostDefeated));
+        assert_eq!(verdict.side, Some(Side::Left));
+        assert_eq!(verdict.source, Some(WinnerSource::MajorityDefeat));
+        assert_eq!(verdict.confidence, Confidence::Low);
+
+        // Even defeats are no conflict: the EndGame decides as before
+        let (verdict, conflict) = verdict_of(&two_v_two(&[(4, 1500), (5, 1550)], Some((6, 1600))));
+        assert_eq!(conflict, None);
+        assert_eq!(verdict, endgame_verdict(Side::Right));
+    }
+
+    #[test]
+    fn test_endgame_without_defeats_is_trusted() {
+        let (verdict, conflict) = verdict_of(&two_v_two(&[], Some((6, 1600))));
+        assert_eq!(conflict, None);
+        assert_eq!(verdict, endgame_verdict(Side::Right));
+        assert_eq!(verdict.to_winner(), Winner::RightTeam);
+    }
+
+    #[test]
+    fn test_lowered_confidence() {
+        assert_eq!(Confidence::Certain.lowered(), Confidence::High);
+        assert_eq!(Confidence::High.lowered(), Confidence::Low);
+        assert_eq!(Confidence::Low.lowered(), Confidence::Low);
+        assert_eq!(Confidence::None.lowered(), Confidence::None);
+    }
+
+    fn endgame_combat(defeats: &[(u32, u32)], endgame_tc: u32) -> CombatResult {
+        CombatResult {
+            defeated_players: defeats.iter().copied().collect(),
+            endgames: vec![(5, endgame_tc)],
+        }
+    }
+
+    #[test]
+    fn test_ending_defeat_then_endgame_is_elimination() {
+        let (hps, sides, pns) = one_v_one();
+        let combat = endgame_combat(&[(4, 6900)], 7000);
+        let ending = determine_ending(&combat, &hps, &sides, &pns, &Winner::RightTeam);
+        assert_eq!(ending, GameEnding::Elimination);
+    }
+
+    #[test]
+    fn test_ending_endgame_then_defeat_is_surrender() {
+        let (hps, sides, pns) = one_v_one();
+        let combat = endgame_combat(&[(4, 7100)], 7000);
+        let ending = determine_ending(&combat, &hps, &sides, &pns, &Winner::RightTeam);
+        assert_eq!(ending, GameEnding::Surrender);
+    }
+
+    #[test]
+    fn test_ending_endgame_without_defeats_is_surrender() {
+        let (hps, sides, pns) = one_v_one();
+        let combat = endgame_combat(&[], 7000);
+        let ending = determine_ending(&combat, &hps, &sides, &pns, &Winner::RightTeam);
+        assert_eq!(ending, GameEnding::Surrender);
+    }
+
+    #[test]
+    fn test_ending_unknown_without_endgame_or_certain_winner() {
+        let (hps, sides, pns) = one_v_one();
+        let mut combat = endgame_combat(&[(4, 6900)], 7000);
+        let likely = determine_ending(&combat, &hps, &sides, &pns, &Winner::LikelyRightTeam);
+        assert_eq!(likely, GameEnding::Unknown);
+        combat.endgames.clear();
+        let no_endgame = determine_ending(&combat, &hps, &sides, &pns, &Winner::RightTeam);
+        assert_eq!(no_endgame, GameEnding::Unknown);
+    }
+
+    /// Alice (pn 3) and Carl (pn 5) against Bob (pn 4) and Dan (pn 6), who
+    /// never leaves the loading screen; Bob is defeated
+    fn two_v_two_with_afk() -> Vec<u8> {
+        let mut data = build_test_replay(
+            "map wor rhun",
+            "HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0:\
+             HCarl,11111111,8094,TT,2,-1,0,0,0,1,0:HDan,22222222,8094,TT,3,-1,1,1,0,1,0",
+        );
+        data.extend(encode_build_at(50, 3, 2650, 1000.0, 4000.0));
+        data.extend(encode_build_at(55, 4, 2160, 4000.0, 4000.0));
+        data.extend(encode_build_at(60, 5, 2650, 1000.0, 1000.0));
+        data.extend(encode_chunk(1500, CMD_PLAYER_DEFEATED, 4, &[]));
+        data.extend([0u8; 16]);
+        data
+    }
+
+    #[test]
+    fn test_afk_teammate_does_not_block_full_defeat() {
+        let (info, _) = ReplayParser::default().parse_with_stats(&two_v_two_with_afk());
+        let info = info.unwrap();
+        assert_eq!(info.winner, Winner::LeftTeam);
+        assert_eq!(info.verdict.source, Some(WinnerSource::FullDefeat));
+        let afk: Vec<&str> = info
+            .players
+            .iter()
+            .filter(|p| p.afk)
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(afk, ["Dan"]);
+        assert!(info.warnings.contains(&ReplayWarning::AfkPlayer {
+            slot: 3,
+            name: "Dan".to_string(),
+        }));
+
+        // Without the AFK check Dan holds up his team's defeat
+        let counted = ReplayParser::builder()
+            .afk_min_commands(0)
+            .build()
+            .parse(&two_v_two_with_afk())
+            .unwrap();
+        assert_eq!(counted.verdict.source, Some(WinnerSource::MajorityDefeat));
+        assert!(counted.players.iter().all(|p| !p.afk));
+    }
+
+    #[test]
+    fn test_player_activity_and_defeat_time() {
+        let info = ReplayParser::default()
+            .parse(&two_v_two_with_afk())
+            .unwrap();
+        let player = |name: &str| info.players.iter().find(|p| p.name == name).unwrap();
+        let bob = player("Bob");
+        assert_eq!(bob.command_count, 1);
+        assert_eq!((bob.build_orders, bob.building_types), (1, 1));
+        assert_eq!(bob.defeated_at_secs, Some(1500 / SAGE_TICKS_PER_SECOND));
+        assert_eq!(bob.defeat_text().as_deref(), Some("5:00"));
+
+        let dan = player("Dan");
+        assert_eq!((dan.command_count, dan.build_orders), (0, 0));
+        assert_eq!(dan.buildings_text(), None);
+        assert_eq!(player("Alice").defeated_at_secs, None);
+    }
+
+    #[test]
+    fn test_afk_threshold_counts_commands() {
+        let players: Vec<Player> = (0..3u8)
+            .map(|slot| PlayerBuilder::test_player(&format!("P{}", slot), 1, slot, Faction::Men))
+            .collect();
+        let counts = HashMap::from([(0u8, 40u32), (1, 3)]);
+        let afk = |min: u32, truncated: bool| {
+            let mut slots: Vec<u8> = afk_slots(&players, &counts, min, truncated)
+                .into_iter()
+                .collect();
+            slots.sort();
+            slots
+        };
+        assert_eq!(afk(1, false), [2]);
+        assert_eq!(afk(5, false), [1, 2]);
+        assert_eq!(afk(0, false), Vec::<u8>::new());
+        // A cut-short walk, or nobody left acting: no one is marked
+        assert_eq!(afk(5, true), Vec::<u8>::new());
+        assert_eq!(afk(50, false), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_collapse_spans_the_losing_side_defeats() {
+        // Bob (pn 4) and Dan (pn 6) fall 4:12 apart; Carl's defeat is the winners'
+        let data = two_v_two(&[(4, 1500), (5, 1600), (6, 2760)], Some((3, 2800)));
+        let info = parse_replay(&data).unwrap();
+        assert_eq!(info.winner, Winner::LeftTeam);
+        assert_eq!(info.collapse_duration_secs, Some(252));
+        assert_eq!(info.collapse_text().as_deref(), Some("4:12"));
+
+        // A single loser fell: no collapse to speak of
+        let single = two_v_two(&[(4, 1500)], Some((3, 1600)));
+        assert_eq!(parse_replay(&single).unwrap().collapse_duration_secs, None);
+    }
+
+    #[test]
+    fn test_collapse_needs_a_certain_winner_and_counts_raw_scan_defeats() {
+        let hps: Vec<HeaderPlayer> = ["Alice", "Bob", "Carl", "Dan"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| HeaderPlayer {
+                name: name.to_string(),
+                uid: None,
+                slot: i as u8,
+                color_id: 0,
+                faction_id: 0,
+                team_raw: (i % 2) as i8,
+                startpos_raw: -1,
+                name_encoding: NameEncoding::Utf8,
+                slot_warning: None,
+            })
+            .collect();
+        let sides = HashMap::from([(0i8, Side::Left), (1i8, Side::Right)]);
+        let pns = HashMap::from([(3u32, 0u8), (4, 1), (5, 2), (6, 3)]);
+
+        // Dan's defeat only came from the raw scan
+        let mut combat = endgame_combat(&[(4, 1000)], 2000);
+        merge_raw_scan(&mut combat, &endgame_combat(&[(6, 1500)], 0));
+        let collapse = |winner| collapse_duration(&combat, &hps, &sides, &pns, winner);
+        assert_eq!(collapse(&Winner::LeftTeam), Some(100));
+        // The left side lost only one player; an uncertain winner says nothing
+        assert_eq!(collapse(&Winner::RightTeam), None);
+        assert_eq!(collapse(&Winner::LikelyLeftTeam), None);
+        assert_eq!(collapse(&Winner::Unknown), None);
+    }
+}
@@ -0,0 +1,305 @@
+//! Synthetic replays for tests: a lobby header plus correctly encoded chunks
+
+use super::replay::{CMD_BUILD_OBJECT, CMD_END_GAME, CMD_PLAYER_DEFEATED, CMD_UNIT_COMMAND, MAGIC};
+use crate::models::Faction;
+
+/// First player number the engine hands out (slot order, observers included)
+const FIRST_PLAYER_NUM: u32 = 3;
+
+/// Lobby slots on the map
+const SLOT_COUNT: u8 = 8;
+
+/// Arg signature of a build order: one int (building id), one Vec3 (position)
+const BUILD_SIGNATURE: [u8; 5] = [2, 0x00, 1, 0x06, 1];
+
+struct LobbyPlayer {
+    name: String,
+    uid: u32,
+    faction: Faction,
+    team: i8,
+    slot: u8,
+}
+
+struct Order {
+    tick: u32,
+    order_type: u32,
+    slot: u8,
+    args: Vec<u8>,
+}
+
+/// Fluent builder for a replay file.
+///
+/// Players sit in the slots they are given; spectators fill the free slots
+/// after the last player. Orders name the acting player by slot and get the
+/// player number the engine would assign (occupied slots numbered in order
+/// from 3). Chunks are written in call order.
+pub(crate) struct ReplayBuilder {
+    map: String,
+    start_time: u32,
+    end_time: u32,
+    players: Vec<LobbyPlayer>,
+    spectators: Vec<String>,
+    orders: Vec<Order>,
+}
+
+impl ReplayBuilder {
+    /// A concluded wor rhun game with no one in the lobby yet
+    pub(crate) fn new() -> Self {
+        Self {
+            map: "map wor rhun".to_string(),
+            start_time: 1_700_000_000,
+            end_time: 1_700_001_000,
+            players: Vec::new(),
+            spectators: Vec::new(),
+            orders: Vec::new(),
+        }
+    }
+
+    pub(crate) fn map(mut self, name: &str) -> Self {
+        self.map = name.to_string();
+        self
+    }
+
+    /// Header start/end timestamps (an end of 0 marks a file never closed)
+    pub(crate) fn times(mut self, start: u32, end: u32) -> Self {
+        self.start_time = start;
+        self.end_time = end;
+        self
+    }
+
+    /// Lobby pick; the parser still rolls the played faction from the seed
+    pub(crate) fn player(mut self, name: &str, faction: Faction, team: i8, slot: u8) -> Self {
+        assert!(slot < SLOT_COUNT, "slot {} out of range", slot);
+        assert!(
+            self.players.iter().all(|p| p.slot != slot),
+            "slot {} taken",
+            slot
+        );
+        self.players.push(LobbyPlayer {
+            name: name.to_string(),
+            uid: 0x1000_0000 + slot as u32,
+            faction,
+            team,
+            slot,
+        });
+        self
+    }
+
+    /// Give the player in `slot` another UID (e.g. one already in the lobby)
+    pub(crate) fn uid(mut self, slot: u8, uid: u32) -> Self {
+        let player = self.players.iter_mut().find(|p| p.slot == slot);
+        player
+            .unwrap_or_else(|| panic!("slot {} is empty", slot))
+            .uid = uid;
+        self
+    }
+
+    /// UID the player in `slot` has in the header
+    pub(crate) fn uid_of(&self, slot: u8) -> u32 {
+        let player = self.players.iter().find(|p| p.slot == slot);
+        player
+            .unwrap_or_else(|| panic!("slot {} is empty", slot))
+            .uid
+    }
+
+    pub(crate) fn spectator(mut self, name: &str) -> Self {
+        self.spectators.push(name.to_string());
+        self
+    }
+
+    /// Building placed at world position `(x, y)`
+    pub(crate) fn build_command(
+        self,
+        slot: u8,
+        building_id: u32,
+        x: f32,
+        y: f32,
+        tick: u32,
+    ) -> Self {
+        let mut args = BUILD_SIGNATURE.to_vec();
+        args.extend_from_slice(&building_id.to_le_bytes());
+        for v in [x, y, 0.0] {
+            args.extend_from_slice(&v.to_le_bytes());
+        }
+        self.order(tick, CMD_BUILD_OBJECT, slot, args)
+    }
+
+    /// Unit order carrying unit template ids
+    pub(crate) fn unit_command(self, slot: u8, tick: u32, unit_ids: &[u32]) -> Self {
+        let args = int_args(unit_ids);
+        self.order(tick, CMD_UNIT_COMMAND, slot, args)
+    }
+
+    pub(crate) fn defeat(self, slot: u8, tick: u32) -> Self {
+        self.order(tick, CMD_PLAYER_DEFEATED, slot, int_args(&[]))
+    }
+
+    pub(crate) fn endgame(self, slot: u8, tick: u32) -> Self {
+        self.order(tick, CMD_END_GAME, slot, int_args(&[]))
+    }
+
+    fn order(mut self, tick: u32, order_type: u32, slot: u8, args: Vec<u8>) -> Self {
+        self.orders.push(Order {
+            tick,
+            order_type,
+            slot,
+            args,
+        });
+        self
+    }
+
+    /// Player number the engine assigns to a slot
+    pub(crate) fn player_num(&self, slot: u8) -> u32 {
+        let occupied = self.slots();
+        let index = occupied
+            .iter()
+            .position(|entry| entry.0 == slot)
+            .unwrap_or_else(|| panic!("slot {} is empty", slot));
+        FIRST_PLAYER_NUM + index as u32
+    }
+
+    /// Occupied `(slot, header entry)` pairs in slot order
+    fn slots(&self) -> Vec<(u8, String)> {
+        let mut slots: Vec<(u8, String)> = self
+            .players
+            .iter()
+            .map(|p| {
+                let entry = format!(
+                    "H{},{:08x},8094,TT,{},-1,{},{},0,1,0",
+                    p.name,
+                    p.uid,
+                    p.slot,
+                    faction_id(p.faction),
+                    p.team
+                );
+                (p.slot, entry)
+            })
+            .collect();
+        let first_free = self.players.iter().map(|p| p.slot + 1).max().unwrap_or(0);
+        for (slot, name) in (first_free..).zip(&self.spectators) {
+            assert!(slot < SLOT_COUNT, "no free slot for {}", name);
+            let entry = format!(
+                "H{},{:08x},8094,TT,-1,-2,-1,-1,0,1,0",
+                name,
+                0x2000_0000 + slot as u32
+            );
+            slots.push((slot, entry));
+        }
+        slots.sort_by_key(|entry| entry.0);
+        slots
+    }
+
+    /// Encode the header and every chunk
+    pub(crate) fn finish(&self) -> Vec<u8> {
+        let slots = self.slots();
+        let lobby: Vec<&str> = (0..SLOT_COUNT)
+            .map(|slot| {
+                slots
+                    .iter()
+                    .find(|entry| entry.0 == slot)
+                    .map_or("X", |entry| entry.1.as_str())
+            })
+            .collect();
+
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&self.start_time.to_le_bytes());
+        data.extend_from_slice(&self.end_time.to_le_bytes());
+        data.extend_from_slice(format!("M=maps/{};S={};", self.map, lobby.join(":")).as_bytes());
+        data.push(0);
+
+        for order in &self.orders {
+            data.extend_from_slice(&order.tick.to_le_bytes());
+            data.extend_from_slice(&order.order_type.to_le_bytes());
+            data.extend_from_slice(&self.player_num(order.slot).to_le_bytes());
+            data.extend_from_slice(&order.args);
+        }
+        data
+    }
+}
+
+/// Header faction id of a lobby pick (inverse of [`Faction::from_id`])
+fn faction_id(faction: Faction) -> i8 {
+    match faction {
+        Faction::Men => 0,
+        Faction::Goblins => 1,
+        Faction::Dwarves => 2,
+        Faction::Isengard => 3,
+        Faction::Elves => 4,
+        Faction::Mordor => 5,
+        Faction::Angmar => 6,
+        Faction::Unknown(id) => id as i8,
+        Faction::Random => -1,
+    }
+}
+
+/// Arg block of plain ints (no arg types at all when empty)
+fn int_args(ints: &[u32]) -> Vec<u8> {
+    if ints.is_empty() {
+        return vec![0];
+    }
+    let mut args = vec![1, 0x00, ints.len() as u8];
+    for v in ints {
+        args.extend_from_slice(&v.to_le_bytes());
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ChunkArg, ChunkIter, parse_replay};
+
+    #[test]
+    fn spectators_take_free_slots_and_player_numbers() {
+        let builder = ReplayBuilder::new()
+            .player("Alice", Faction::Men, 0, 0)
+            .player("Bob", Faction::Mordor, 1, 2)
+            .spectator("Obs");
+        assert_eq!(builder.player_num(0), 3);
+        assert_eq!(builder.player_num(2), 4);
+        assert_eq!(builder.player_num(3), 5);
+
+        let info = parse_replay(&builder.finish()).unwrap();
+        let players: Vec<(&str, u8)> = info
+            .players
+            .iter()
+            .map(|p| (p.name.as_str(), p.slot))
+            .collect();
+        assert_eq!(players, [("Alice", 0), ("Bob", 2)]);
+        assert_eq!(info.spectators[0].name, "Obs");
+        assert_eq!(info.spectators[0].slot, Some(3));
+    }
+
+    #[test]
+    fn chunks_decode_with_their_signatures() {
+        let data = ReplayBuilder::new()
+            .player("Alice", Faction::Men, 0, 0)
+            .player("Bob", Faction::Mordor, 1, 1)
+            .build_command(0, 2650, 1000.0, 4000.0, 50)
+            .unit_command(1, 60, &[1751, 1766])
+            .defeat(1, 900)
+            .endgame(0, 910)
+            .finish();
+
+        let chunks: Vec<_> = ChunkIter::new(&data).unwrap().collect();
+        let orders: Vec<(u32, u32, u32)> = chunks
+            .iter()
+            .map(|c| (c.time_code, c.order_type, c.player_num))
+            .collect();
+        assert_eq!(
+            orders,
+            [
+                (50, CMD_BUILD_OBJECT, 3),
+                (60, CMD_UNIT_COMMAND, 4),
+                (900, CMD_PLAYER_DEFEATED, 4),
+                (910, CMD_END_GAME, 3),
+            ]
+        );
+        assert_eq!(
+            chunks[0].args,
+            [ChunkArg::Int(2650), ChunkArg::Vec3(1000.0, 4000.0, 0.0)]
+        );
+        assert_eq!(chunks[1].args, [ChunkArg::Int(1751), ChunkArg::Int(1766)]);
+        assert!(chunks[3].args.is_empty());
+    }
+}
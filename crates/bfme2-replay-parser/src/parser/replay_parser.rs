@@ -0,0 +1,248 @@
+use super::replay::{
+    MAX_SANE_PLAYER_NUM, MAX_SANE_TIMECODE, ParseStats, SAGE_TICKS_PER_SECOND, parse_with,
+    parse_with_stats,
+};
+use super::tick_rate::clamp_tick_rate;
+use crate::models::{ReplayError, ReplayInfo, SpotRegions};
+use std::time::Duration;
+
+/// Map filter used by the bot (case-insensitive substring of the map name)
+const DEFAULT_ALLOWED_MAP: &str = "wor rhun";
+
+/// Commands a player needs not to count as AFK: any at all
+const DEFAULT_AFK_MIN_COMMANDS: u32 = 1;
+
+/// Ticks between EndGame orders of both sides that make a draw (10s)
+const DEFAULT_DRAW_WINDOW_TICKS: u32 = 10 * SAGE_TICKS_PER_SECOND;
+
+/// Map name endings of known horizontally mirrored edits (case-insensitive)
+const DEFAULT_MIRRORED_SUFFIXES: &[&str] = &["mirrored", "mirror", "flipped"];
+
+/// Configurable replay parser for embedding the parser outside the bot.
+///
+/// `ReplayParser::default()` behaves exactly like [`parse_replay`](super::parse_replay).
+#[derive(Debug, Clone)]
+pub struct ReplayParser {
+    pub(super) allowed_maps: Option<Vec<String>>,
+    pub(super) max_timecode: u32,
+    pub(super) max_player_num: u32,
+    pub(super) enable_raw_scan: bool,
+    pub(super) infer_factions: bool,
+    pub(super) record_timings: bool,
+    pub(super) full_chunk_analysis: bool,
+    pub(super) spot_regions: SpotRegions,
+    pub(super) tick_rate: f32,
+    pub(super) mirrored_suffixes: Vec<String>,
+    pub(super) time_budget: Option<Duration>,
+    pub(super) afk_min_commands: u32,
+    pub(super) draw_window_ticks: u32,
+}
+
+impl Default for ReplayParser {
+    fn default() -> Self {
+        Self {
+            allowed_maps: Some(vec![DEFAULT_ALLOWED_MAP.to_string()]),
+            max_timecode: MAX_SANE_TIMECODE,
+            max_player_num: MAX_SANE_PLAYER_NUM,
+            enable_raw_scan: true,
+            infer_factions: true,
+            record_timings: false,
+            full_chunk_analysis: false,
+            spot_regions: SpotRegions::default(),
+            tick_rate: SAGE_TICKS_PER_SECOND as f32,
+            mirrored_suffixes: DEFAULT_MIRRORED_SUFFIXES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            time_budget: None,
+            afk_min_commands: DEFAULT_AFK_MIN_COMMANDS,
+            draw_window_ticks: DEFAULT_DRAW_WINDOW_TICKS,
+        }
+    }
+}
+
+impl ReplayParser {
+    pub fn builder() -> ReplayParserBuilder {
+        ReplayParserBuilder {
+            parser: Self::default(),
+        }
+    }
+
+    /// Parse a BFME2 replay file using this parser's options
+    pub fn parse(&self, data: &[u8]) -> Result<ReplayInfo, ReplayError> {
+        parse_with(self, data)
+    }
+
+    /// Parse like [`parse`](Self::parse), also reporting how much recovery the parse needed
+    pub fn parse_with_stats(&self, data: &[u8]) -> (Result<ReplayInfo, ReplayError>, ParseStats) {
+        let mut stats = ParseStats::default();
+        let result = parse_with_stats(self, data, &mut stats);
+        (result, stats)
+    }
+
+    /// Whether a map name passes the map filter
+    pub fn is_map_allowed(&self, map_name: &str) -> bool {
+        match &self.allowed_maps {
+            None => true,
+            Some(allowed) => {
+                let map_name = map_name.to_lowercase();
+                allowed.iter().any(|m| map_name.contains(&m.to_lowercase()))
+            }
+        }
+    }
+
+    /// Whether a map name ends with one of the mirrored-edit suffixes
+    pub fn is_mirrored_name(&self, map_name: &str) -> bool {
+        let map_name = map_name.trim().to_lowercase();
+        self.mirrored_suffixes
+            .iter()
+            .any(|s| !s.trim().is_empty() && map_name.ends_with(&s.trim().to_lowercase()))
+    }
+}
+
+/// Builder for [`ReplayParser`]; unset options keep the bot's defaults
+#[derive(Debug, Clone)]
+pub struct ReplayParserBuilder {
+    parser: ReplayParser,
+}
+
+impl ReplayParserBuilder {
+    /// Accepted maps as case-insensitive substrings of the map name (`None` = any map)
+    pub fn allowed_maps(mut self, maps: Option<Vec<String>>) -> Self {
+        self.parser.allowed_maps = maps;
+        self
+    }
+
+    /// Chunks with a timecode above this are treated as corrupt
+    pub fn max_timecode(mut self, max_timecode: u32) -> Self {
+        self.parser.max_timecode = max_timecode;
+        self
+    }
+
+    /// Chunks with a player number above this are treated as corrupt
+    pub fn max_players(mut self, max_player_num: u32) -> Self {
+        self.parser.max_player_num = max_player_num;
+        self
+    }
+
+    /// Rescan raw bytes for EndGame/PlayerDefeated orders the chunk parser
+    /// lost sync on. Disabling it can change the detected winner.
+    pub fn enable_raw_scan(mut self, enable: bool) -> Self {
+        self.parser.enable_raw_scan = enable;
+        self
+    }
+
+    /// Infer each player's actual faction from the buildings they placed
+    pub fn infer_factions(mut self, infer: bool) -> Self {
+        self.parser.infer_factions = infer;
+        self
+    }
+
+    /// Report per-stage timings in [`ParseStats`]; off by default, so plain
+    /// parses never read the clock
+    pub fn record_timings(mut self, record: bool) -> Self {
+        self.parser.record_timings = record;
+        self
+    }
+
+    /// Keep decoding chunk arguments after every player's position and
+    /// faction are known. Slower on long games; results are the same.
+    pub fn full_chunk_analysis(mut self, full: bool) -> Self {
+        self.parser.full_chunk_analysis = full;
+        self
+    }
+
+    /// World-coordinate bounds used to place players on the map's spots
+    pub fn spot_regions(mut self, regions: SpotRegions) -> Self {
+        self.parser.spot_regions = regions;
+        self
+    }
+
+    /// Ticks per second used to estimate durations of replays without header
+    /// times (e.g. a [`TickRateCalibration`](super::TickRateCalibration) rate),
+    /// clamped to the sane range
+    pub fn tick_rate(mut self, rate: f32) -> Self {
+        self.parser.tick_rate = clamp_tick_rate(rate);
+        self
+    }
+
+    /// Map name endings that mark a horizontally mirrored edit; an empty list
+    /// leaves detection to build positions alone
+    pub fn mirrored_map_suffixes(mut self, suffixes: Vec<String>) -> Self {
+        self.parser.mirrored_suffixes = suffixes;
+        self
+    }
+
+    /// Stop walking chunks once the walk has taken this long and finish
+    /// with what was gathered, flagged [`ReplayWarning::Truncated`]. The
+    /// clock is only read every few thousand chunks, so it can overrun a
+    /// little. Not enforced when built without the `clock` feature.
+    ///
+    /// [`ReplayWarning::Truncated`]: crate::models::ReplayWarning::Truncated
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.parser.time_budget = Some(budget);
+        self
+    }
+
+    /// Players with fewer commands than this are marked AFK and left out
+    /// of the defeat-based winner checks (default 1: only players who never
+    /// acted); 0 turns the check off
+    pub fn afk_min_commands(mut self, commands: u32) -> Self {
+        self.parser.afk_min_commands = commands;
+        self
+    }
+
+    /// Players of both sides issuing EndGame within this many ticks of the
+    /// last EndGame make the game a [`Winner::Draw`] (default 50, about 10
+    /// seconds); 0 only counts orders at the same tick
+    ///
+    /// [`Winner::Draw`]: crate::models::Winner::Draw
+    pub fn draw_window_ticks(mut self, ticks: u32) -> Self {
+        self.parser.draw_window_ticks = ticks;
+        self
+    }
+
+    pub fn build(self) -> ReplayParser {
+        self.parser
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_map_filter_matches_wor_rhun_only() {
+        let parser = ReplayParser::default();
+        assert!(parser.is_map_allowed("map wor rhun"));
+        assert!(parser.is_map_allowed("Map Wor Rhun II"));
+        assert!(!parser.is_map_allowed("map helms deep"));
+    }
+
+    #[test]
+    fn map_filter_can_be_replaced_or_disabled() {
+        let any = ReplayParser::builder().allowed_maps(None).build();
+        assert!(any.is_map_allowed("map helms deep"));
+
+        let custom = ReplayParser::builder()
+            .allowed_maps(Some(vec!["Helms Deep".to_string()]))
+            .build();
+        assert!(custom.is_map_allowed("map helms deep"));
+        assert!(!custom.is_map_allowed("map wor rhun"));
+    }
+
+    #[test]
+    fn mirrored_edits_are_recognized_by_name_suffix() {
+        let parser = ReplayParser::default();
+        assert!(parser.is_mirrored_name("map wor rhun mirrored"));
+        assert!(parser.is_mirrored_name("Map Wor Rhun FLIPPED "));
+        assert!(!parser.is_mirrored_name("map wor rhun"));
+        assert!(!parser.is_mirrored_name("map mirrored wor rhun"));
+
+        let custom = ReplayParser::builder()
+            .mirrored_map_suffixes(vec!["(m)".to_string()])
+            .build();
+        assert!(custom.is_mirrored_name("map wor rhun (M)"));
+        assert!(!custom.is_mirrored_name("map wor rhun mirrored"));
+    }
+}
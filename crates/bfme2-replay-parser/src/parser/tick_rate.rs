@@ -0,0 +1,142 @@
+use super::replay::SAGE_TICKS_PER_SECOND;
+
+/// Lowest tick rate used for estimates (ticks per second)
+pub const MIN_TICK_RATE: f32 = 4.5;
+
+/// Highest tick rate used for estimates (ticks per second)
+pub const MAX_TICK_RATE: f32 = 5.5;
+
+/// Shortest header duration used for calibration; rounding dominates shorter games
+const MIN_CALIBRATION_SECS: u32 = 5 * 60;
+
+/// Samples needed before the calibrated rate replaces the nominal one
+const MIN_CALIBRATION_SAMPLES: u32 = 10;
+
+/// Samples the rolling average is taken over
+const CALIBRATION_WINDOW: u32 = 200;
+
+/// Tick rate implied by a game's chunk timecodes and its header duration.
+/// `None` for short games and for rates outside the sane range (pauses or
+/// a bad header make those unusable).
+pub(super) fn implied_tick_rate(duration_ticks: u32, header_secs: u32) -> Option<f32> {
+    if header_secs < MIN_CALIBRATION_SECS {
+        return None;
+    }
+    let rate = duration_ticks as f32 / header_secs as f32;
+    (MIN_TICK_RATE..=MAX_TICK_RATE)
+        .contains(&rate)
+        .then_some(rate)
+}
+
+/// Keep a tick rate within the sane range (NaN falls back to the nominal rate)
+pub fn clamp_tick_rate(rate: f32) -> f32 {
+    if rate.is_nan() {
+        SAGE_TICKS_PER_SECOND as f32
+    } else {
+        rate.clamp(MIN_TICK_RATE, MAX_TICK_RATE)
+    }
+}
+
+/// Whole seconds in `ticks` at `rate` ticks per second
+pub(super) fn ticks_to_secs(ticks: u32, rate: f32) -> u32 {
+    (ticks as f32 / rate) as u32
+}
+
+/// Rolling average of the tick rates implied by replays with both header
+/// times and chunk timecodes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickRateCalibration {
+    average: f32,
+    samples: u32,
+}
+
+impl Default for TickRateCalibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TickRateCalibration {
+    pub const fn new() -> Self {
+        Self {
+            average: SAGE_TICKS_PER_SECOND as f32,
+            samples: 0,
+        }
+    }
+
+    /// Add one replay's implied rate
+    pub fn record(&mut self, rate: f32) {
+        self.samples = self.samples.saturating_add(1);
+        let weight = self.samples.min(CALIBRATION_WINDOW) as f32;
+        self.average += (rate - self.average) / weight;
+    }
+
+    /// Rate to estimate durations with: the nominal rate until enough
+    /// samples are in, then the clamped average
+    pub fn rate(&self) -> f32 {
+        if self.samples < MIN_CALIBRATION_SAMPLES {
+            SAGE_TICKS_PER_SECOND as f32
+        } else {
+            clamp_tick_rate(self.average)
+        }
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn implied_rate_needs_a_long_sane_game() {
+        // 20 minutes at 5.1 ticks per second
+        assert_eq!(implied_tick_rate(6120, 1200), Some(5.1));
+        assert_eq!(implied_tick_rate(1500, 299), None);
+        // A paused game's header runs far past its ticks
+        assert_eq!(implied_tick_rate(3000, 1200), None);
+        assert_eq!(implied_tick_rate(9000, 1200), None);
+    }
+
+    #[test]
+    fn seconds_scale_with_the_rate() {
+        assert_eq!(ticks_to_secs(6000, 5.0), 1200);
+        assert_eq!(ticks_to_secs(6000, 4.8), 1250);
+        assert_eq!(ticks_to_secs(6240, 5.2), 1200);
+        // Matches integer division at the nominal rate
+        for ticks in [0, 4, 5, 9, 12_345, 9_999_999] {
+            assert_eq!(ticks_to_secs(ticks, 5.0), ticks / 5);
+        }
+    }
+
+    #[test]
+    fn rates_are_clamped() {
+        assert_eq!(clamp_tick_rate(4.0), MIN_TICK_RATE);
+        assert_eq!(clamp_tick_rate(6.0), MAX_TICK_RATE);
+        assert_eq!(clamp_tick_rate(5.1), 5.1);
+        assert_eq!(clamp_tick_rate(f32::NAN), 5.0);
+    }
+
+    #[test]
+    fn calibration_averages_after_enough_samples() {
+        let mut calibration = TickRateCalibration::new();
+        for _ in 0..MIN_CALIBRATION_SAMPLES - 1 {
+            calibration.record(4.8);
+        }
+        assert_eq!(calibration.rate(), 5.0);
+        calibration.record(4.8);
+        assert!((calibration.rate() - 4.8).abs() < 1e-4);
+
+        // Later samples pull the average over the window
+        for _ in 0..CALIBRATION_WINDOW {
+            calibration.record(5.2);
+        }
+        assert!(calibration.rate() > 5.1 && calibration.rate() <= 5.2);
+        assert_eq!(
+            calibration.samples(),
+            MIN_CALIBRATION_SAMPLES + CALIBRATION_WINDOW
+        );
+    }
+}
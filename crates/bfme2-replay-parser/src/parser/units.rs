@@ -0,0 +1,64 @@
+use crate::models::Faction;
+use std::ops::RangeInclusive;
+
+/// Unit template ID ranges carried in CMD_UNIT_COMMAND args, per faction.
+/// Empty until the real ranges are supplied: unit commands also carry object
+/// instance IDs in the same numeric span, so guessed ranges would hand
+/// Random players a confident wrong faction.
+const UNIT_RANGES: &[(RangeInclusive<u32>, Faction)] = &[];
+
+/// Faction with the most unit IDs in the known ranges (earliest unit wins ties)
+pub(super) fn detect_faction_from_units(units: &[u32]) -> Option<Faction> {
+    majority_faction(units, UNIT_RANGES)
+}
+
+fn majority_faction(units: &[u32], ranges: &[(RangeInclusive<u32>, Faction)]) -> Option<Faction> {
+    let mut counts: Vec<(Faction, usize)> = Vec::new();
+    let factions = units.iter().filter_map(|id| {
+        ranges
+            .iter()
+            .find(|(ids, _)| ids.contains(id))
+            .map(|(_, faction)| *faction)
+    });
+    for faction in factions {
+        match counts.iter_mut().find(|(f, _)| *f == faction) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((faction, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|&(_, n)| n)
+        .map(|(faction, _)| faction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANGES: &[(RangeInclusive<u32>, Faction)] = &[
+        (100..=199, Faction::Men),
+        (200..=299, Faction::Isengard),
+        (300..=399, Faction::Mordor),
+    ];
+
+    #[test]
+    fn majority_faction_wins() {
+        assert_eq!(
+            majority_faction(&[100, 300, 350], RANGES),
+            Some(Faction::Mordor)
+        );
+        // A tie goes to the faction seen first
+        assert_eq!(
+            majority_faction(&[250, 150], RANGES),
+            Some(Faction::Isengard)
+        );
+        assert_eq!(majority_faction(&[5, 9], RANGES), None);
+    }
+
+    #[test]
+    fn no_faction_until_ranges_are_known() {
+        assert_eq!(detect_faction_from_units(&[1601, 1751, 1766]), None);
+    }
+}
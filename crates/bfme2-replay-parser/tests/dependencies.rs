@@ -0,0 +1,46 @@
+//! The parser must stay usable outside the bot (tools, wasm), so none of the
+//! bot's runtime, Discord or imaging crates may creep into its manifest
+
+const MANIFEST: &str = include_str!("../Cargo.toml");
+
+const FORBIDDEN: &[&str] = &[
+    "tokio",
+    "poise",
+    "serenity",
+    "image",
+    "imageproc",
+    "ab_glyph",
+    "zip",
+    "unrar",
+];
+
+/// Dependency names declared in the manifest's dependency tables
+fn dependency_names(manifest: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut in_dependencies = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_dependencies = line.ends_with("dependencies]");
+            continue;
+        }
+        if in_dependencies
+            && !line.starts_with('#')
+            && let Some((name, _)) = line.split_once('=')
+        {
+            names.push(name.trim());
+        }
+    }
+    names
+}
+
+#[test]
+fn manifest_has_no_bot_dependencies() {
+    let names = dependency_names(MANIFEST);
+    assert!(names.contains(&"flate2"), "manifest not read: {names:?}");
+    for forbidden in FORBIDDEN {
+        assert!(
+            !names.contains(forbidden),
+            "{forbidden} is a bot dependency; keep it out of the parser"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Arbitrary input must never panic the parser or make it allocate out of
+//! proportion to the file: replays come from anyone who can upload one
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use bfme2_replay_parser::parse_replay;
+use proptest::prelude::*;
+
+/// Largest input the properties are checked on
+const MAX_INPUT: usize = 64 * 1024;
+
+/// Allowed peak heap use during a parse: this many bytes per input byte...
+const ALLOC_BYTES_PER_INPUT_BYTE: usize = 64;
+/// ...plus this much regardless of size (fixed-capacity tables, strings)
+const ALLOC_BASE_BYTES: usize = 256 * 1024;
+
+thread_local! {
+    /// Bytes allocated and not yet freed on this thread, and their peak
+    static LIVE: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts each thread's live heap bytes, so parallel tests don't skew
+/// each other's measurements
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LIVE.try_with(|live| {
+            let now = live.get() + layout.size();
+            live.set(now);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+        });
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // Memory from another thread may be freed here; never go below zero
+        let _ = LIVE.try_with(|live| live.set(live.get().saturating_sub(layout.size())));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Peak heap growth on this thread while `f` runs
+fn peak_alloc<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let base = LIVE.with(Cell::get);
+    PEAK.with(|peak| peak.set(base));
+    let out = f();
+    (out, PEAK.with(Cell::get) - base)
+}
+
+/// One chunk: timecode, order type, player number and int arguments
+fn chunk(tick: u32, order: u32, player_num: u32, ints: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(tick.to_le_bytes());
+    out.extend(order.to_le_bytes());
+    out.extend(player_num.to_le_bytes());
+    if ints.is_empty() {
+        out.push(0);
+    } else {
+        out.extend([1, 0x00, ints.len() as u8]);
+        for v in ints {
+            out.extend(v.to_le_bytes());
+        }
+    }
+    out
+}
+
+/// A build order: building id and where it was placed
+fn build(tick: u32, player_num: u32, building: u32, x: f32, y: f32) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(tick.to_le_bytes());
+    out.extend(1049u32.to_le_bytes());
+    out.extend(player_num.to_le_bytes());
+    out.extend([2, 0x00, 1, 0x06, 1]);
+    out.extend(building.to_le_bytes());
+    for v in [x, y, 0.0] {
+        out.extend(v.to_le_bytes());
+    }
+    out
+}
+
+/// A small concluded 1v1 with a few orders, a defeat and an EndGame
+fn seed_replay() -> Vec<u8> {
+    let mut data = b"BFME2RPL".to_vec();
+    data.extend(1_700_000_000u32.to_le_bytes());
+    data.extend(1_700_001_000u32.to_le_bytes());
+    data.extend_from_slice(
+        b"M=maps/map wor rhun;SD=12345;RU=3 100 1000 0 1 1 1 -1 0 -1 -1 1;\
+          S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0:\
+          HEve,11111111,8094,TT,2,-2,-1,-1,0,1,0:X:X:X:X:X;",
+    );
+    data.push(0);
+    for tick in (10..400).step_by(30) {
+        data.extend(build(tick, 3, 2650, 1000.0, 4000.0));
+        data.extend(build(tick + 5, 4, 2160, 4000.0, 1000.0));
+    }
+    data.extend(chunk(500, 1096, 4, &[]));
+    data.extend(chunk(510, 29, 3, &[]));
+    data
+}
+
+/// The seed with a few bytes overwritten and possibly cut short
+fn mutated_seed() -> impl Strategy<Value = Vec<u8>> {
+    let seed = seed_replay();
+    let len = seed.len();
+    (prop::collection::vec((0..len, any::<u8>()), 1..16), 0..=len).prop_map(move |(edits, keep)| {
+        let mut data = seed.clone();
+        for (at, byte) in edits {
+            data[at] = byte;
+        }
+        data.truncate(keep.max(1));
+        data
+    })
+}
+
+/// The seed's header followed by arbitrary chunk bytes
+fn seed_header_with_noise() -> impl Strategy<Value = Vec<u8>> {
+    let seed = seed_replay();
+    // The header text ends at its null; the timestamps before it hold zeros too
+    let text = seed.windows(2).position(|w| w == b"M=").unwrap();
+    let header_end = text + seed[text..].iter().position(|&b| b == 0).unwrap() + 1;
+    let header = seed[..header_end].to_vec();
+    prop::collection::vec(any::<u8>(), 0..MAX_INPUT - header.len()).prop_map(move |noise| {
+        let mut data = header.clone();
+        data.extend(noise);
+        data
+    })
+}
+
+/// A lobby of `slots` entries, far more than the map's 8
+fn crowded_lobby(slots: usize) -> Vec<u8> {
+    let mut data = b"BFME2RPL".to_vec();
+    data.extend(1_700_000_000u32.to_le_bytes());
+    data.extend(1_700_001_000u32.to_le_bytes());
+    let entries: Vec<String> = (0..slots)
+        .map(|i| {
+            let team = if i % 3 == 0 { -1 } else { (i % 8) as i32 };
+            format!(
+                "HP{},{:08x},8094,TT,{},-1,{},{},0,1,0",
+                i,
+                i,
+                i % 10,
+                i % 7,
+                team
+            )
+        })
+        .collect();
+    data.extend(format!("M=maps/map wor rhun;SD=7;S={};", entries.join(":")).as_bytes());
+    data.push(0);
+    data
+}
+
+fn assert_bounded(data: &[u8]) {
+    let (_, peak) = peak_alloc(|| parse_replay(data));
+    let limit = ALLOC_BASE_BYTES + ALLOC_BYTES_PER_INPUT_BYTE * data.len();
+    assert!(
+        peak <= limit,
+        "{} byte input peaked at {} heap bytes (limit {})",
+        data.len(),
+        peak,
+        limit
+    );
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn arbitrary_bytes_parse_without_panic(data in prop::collection::vec(any::<u8>(), 0..MAX_INPUT)) {
+        assert_bounded(&data);
+    }
+
+    #[test]
+    fn magic_with_arbitrary_bytes_parses_without_panic(
+        tail in prop::collection::vec(any::<u8>(), 0..MAX_INPUT - 8),
+    ) {
+        let mut data = b"BFME2RPL".to_vec();
+        data.extend(tail);
+        assert_bounded(&data);
+    }
+
+    #[test]
+    fn mutated_replays_parse_without_panic(data in mutated_seed()) {
+        assert_bounded(&data);
+    }
+
+    #[test]
+    fn noisy_chunk_streams_parse_without_panic(data in seed_header_with_noise()) {
+        assert_bounded(&data);
+    }
+}
+
+#[test]
+fn seed_replay_parses() {
+    let info = parse_replay(&seed_replay()).unwrap();
+    assert_eq!(info.players.len(), 2);
+}
+
+#[test]
+fn crowded_lobbies_parse_within_bounds() {
+    for slots in [9, 127, 128, 129, 255, 256, 257, 1000, 3000] {
+        let data = crowded_lobby(slots);
+        assert!(data.len() <= MAX_INPUT * 2);
+        assert_bounded(&data);
+    }
+}
+
+#[test]
+fn late_timecodes_parse_within_bounds() {
+    // A lone order near the sane timecode limit, in a file of a few hundred bytes
+    let mut data = seed_replay();
+    data.extend(chunk(9_999_000, 1071, 4, &[7]));
+    assert_bounded(&data);
+}
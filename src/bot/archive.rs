@@ -1,112 +1,300 @@
+use crate::models::{ReplayError, ReplayInfo, SpotRegions, Winner, format_date};
+use crate::parser::{has_replay_magic, header_start_time};
+use std::fmt;
 use std::io::Read;
+use std::time::{Duration, Instant};
+
+use super::constants::{BATCH_PARSE_BUDGET_MS, RESTART_MAX_STUB_SECS, RESTART_WINDOW_SECS};
+use super::handler::parse_recorded;
+use super::relevance::{AttachmentClass, FileKind, Venue, classify_attachment};
+use super::temp_dirs::TempDirRegistry;
+use super::user_message::UserMessage;
 
 const MAX_REPLAYS_PER_ARCHIVE: usize = 100;
 const MAX_ARCHIVE_UNCOMPRESSED_BYTES: u64 = 500 * 1024 * 1024; // 500MB total
 const MAX_ARCHIVE_EXTRACTED_FILES: usize = 200;
-const MAX_SINGLE_REPLAY_BYTES: u64 = 5 * 1024 * 1024; // 5MB
+const MAX_ZIP_ENTRIES: usize = 10_000; // across the archive and nested archives
+const ZIP_EXTRACTION_BUDGET: Duration = Duration::from_secs(10);
+const MAX_NESTED_ZIP_DEPTH: usize = 1;
+/// Bytes read from an entry with an unknown name to check for the replay magic
+const SNIFF_BYTES: u64 = 8;
+
+/// Replays extracted from an archive, and how many were found in total
+pub type ExtractedReplays = (Vec<(String, Vec<u8>)>, usize);
+
+/// Why an archive yielded no replays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// The replays (or the whole archive) need a password
+    Encrypted,
+    /// Truncated or damaged data
+    Corrupted(String),
+    /// An archive feature we can't read (compression method, split volumes)
+    Unsupported,
+    /// Temp file handling failed on our side
+    Io(String),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Encrypted => write!(f, "Archive is password-protected"),
+            ArchiveError::Corrupted(e) => write!(f, "Archive is corrupted: {}", e),
+            ArchiveError::Unsupported => write!(f, "Unsupported archive"),
+            ArchiveError::Io(e) => write!(f, "Archive I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<&zip::result::ZipError> for ArchiveError {
+    fn from(error: &zip::result::ZipError) -> Self {
+        use zip::result::ZipError;
+        match error {
+            ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)
+            | ZipError::InvalidPassword => ArchiveError::Encrypted,
+            ZipError::UnsupportedArchive(_) => ArchiveError::Unsupported,
+            e => ArchiveError::Corrupted(e.to_string()),
+        }
+    }
+}
+
+impl From<&unrar::error::UnrarError> for ArchiveError {
+    fn from(error: &unrar::error::UnrarError) -> Self {
+        use unrar::error::{Code, When};
+        match (error.code, error.when) {
+            (Code::MissingPassword | Code::BadPassword, _) => ArchiveError::Encrypted,
+            // Encryption or format newer than the library, or a missing next volume
+            (Code::UnknownFormat, _) | (Code::EOpen, When::Process) => ArchiveError::Unsupported,
+            (Code::ECreate | Code::EWrite | Code::EClose | Code::NoMemory, _) => {
+                ArchiveError::Io(error.to_string())
+            }
+            _ => ArchiveError::Corrupted(error.to_string()),
+        }
+    }
+}
 
-/// Extract .BfME2Replay files from a ZIP archive (in-memory).
+/// The problem to report when nothing could be extracted. A password prompt
+/// explains the most, so it wins over problems seen earlier.
+fn note_problem(problem: &mut Option<ArchiveError>, error: ArchiveError) {
+    if problem.is_none() || error == ArchiveError::Encrypted {
+        *problem = Some(error);
+    }
+}
+
+/// Extract replay files from a ZIP archive (in-memory), searching
+/// .zip entries one level deep. Entries with other names are kept when
+/// they start with the replay magic.
 /// Returns (replays, total_count) — only up to MAX_REPLAYS_PER_ARCHIVE are extracted,
 /// but total_count reflects how many were found.
-pub fn extract_replays_from_zip(data: &[u8]) -> (Vec<(String, Vec<u8>)>, usize) {
-    let cursor = std::io::Cursor::new(data);
-    let mut archive = match zip::ZipArchive::new(cursor) {
-        Ok(a) => a,
-        Err(e) => {
-            tracing::error!("Failed to open ZIP archive: {}", e);
-            return (Vec::new(), 0);
-        }
+/// Stops early (keeping what was collected) past MAX_ZIP_ENTRIES entries or
+/// ZIP_EXTRACTION_BUDGET of wall-clock time.
+/// Fails only when no replay could be extracted and an encrypted, damaged or
+/// unsupported replay entry (or archive) is the reason.
+pub fn extract_replays_from_zip(data: &[u8]) -> Result<ExtractedReplays, ArchiveError> {
+    let mut replays = Vec::new();
+    let total = stream_replays_from_zip(data, |name, bytes| {
+        replays.push((name, bytes));
+        true
+    })?;
+    Ok((replays, total))
+}
+
+/// [`extract_replays_from_zip`] handing each replay to `sink` as soon as it
+/// is read, so only one entry is held at a time. Extraction stops early when
+/// `sink` returns false. Returns how many replays were found in total.
+#[tracing::instrument(skip_all, fields(bytes = data.len()))]
+pub fn stream_replays_from_zip(
+    data: &[u8],
+    sink: impl FnMut(String, Vec<u8>) -> bool,
+) -> Result<usize, ArchiveError> {
+    let mut extraction = ZipExtraction {
+        sink,
+        extracted: 0,
+        total: 0,
+        extracted_bytes: 0,
+        entries_seen: 0,
+        deadline: Instant::now() + ZIP_EXTRACTION_BUDGET,
+        problem: None,
     };
+    extraction.collect(data, 0);
+    match extraction.problem {
+        Some(problem) if extraction.extracted == 0 => Err(problem),
+        _ => Ok(extraction.total),
+    }
+}
 
-    let mut replays = Vec::new();
-    let mut total = 0usize;
-    let mut extracted_bytes: u64 = 0;
+/// Limits and results shared by an archive and the archives nested in it
+struct ZipExtraction<F> {
+    /// Receives each extracted replay; false stops the extraction
+    sink: F,
+    /// Replays handed to the sink
+    extracted: usize,
+    total: usize,
+    extracted_bytes: u64,
+    entries_seen: usize,
+    deadline: Instant,
+    /// Why a replay or nested archive could not be read
+    problem: Option<ArchiveError>,
+}
 
-    for i in 0..archive.len() {
-        let mut file = match archive.by_index(i) {
-            Ok(f) => f,
+impl<F: FnMut(String, Vec<u8>) -> bool> ZipExtraction<F> {
+    /// Collect replays from one archive; returns false once a global limit
+    /// is hit so the caller stops too
+    fn collect(&mut self, data: &[u8], depth: usize) -> bool {
+        let cursor = std::io::Cursor::new(data);
+        let mut archive = match zip::ZipArchive::new(cursor) {
+            Ok(a) => a,
             Err(e) => {
-                tracing::warn!("Failed to read ZIP entry {}: {}", i, e);
-                continue;
+                if depth == 0 {
+                    tracing::error!("Failed to open ZIP archive: {}", e);
+                } else {
+                    tracing::warn!("Failed to open nested ZIP archive: {}", e);
+                }
+                note_problem(&mut self.problem, ArchiveError::from(&e));
+                return true;
             }
         };
 
-        let name = file.name().to_string();
-        if !name.to_lowercase().ends_with(".bfme2replay") || file.is_dir() {
-            continue;
-        }
+        for i in 0..archive.len() {
+            self.entries_seen += 1;
+            if self.entries_seen > MAX_ZIP_ENTRIES {
+                tracing::warn!(
+                    "ZIP entry limit exceeded ({} entries), stopping",
+                    MAX_ZIP_ENTRIES
+                );
+                return false;
+            }
+            if Instant::now() >= self.deadline {
+                tracing::warn!(
+                    "ZIP extraction time budget ({:?}) exceeded, stopping",
+                    ZIP_EXTRACTION_BUDGET
+                );
+                return false;
+            }
 
-        total += 1;
+            let relevant = archive.name_for_index(i).is_some_and(|name| {
+                classify_attachment(name, 0, None, Venue::Archive).is_relevant()
+            });
+            let mut file = match archive.by_index(i) {
+                Ok(f) => f,
+                Err(e) => {
+                    tracing::warn!("Failed to read ZIP entry {}: {}", i, e);
+                    if relevant {
+                        note_problem(&mut self.problem, ArchiveError::from(&e));
+                    }
+                    continue;
+                }
+            };
 
-        // Count but don't extract beyond the cap
-        if replays.len() >= MAX_REPLAYS_PER_ARCHIVE {
-            continue;
-        }
+            let name = file.name().to_string();
+            if file.is_dir() {
+                continue;
+            }
+            let class = classify_attachment(&name, file.size(), None, Venue::Archive);
+            let mut buf = Vec::new();
+            let kind = match class.kind() {
+                Some(kind) => kind,
+                None if class == AttachmentClass::SniffCandidate => {
+                    let head = file.by_ref().take(SNIFF_BYTES).read_to_end(&mut buf);
+                    if head.is_err() || !has_replay_magic(&buf) {
+                        continue;
+                    }
+                    FileKind::Replay
+                }
+                None => continue,
+            };
+            let is_nested = kind != FileKind::Replay;
 
-        // Skip files larger than 5MB
-        if file.size() > MAX_SINGLE_REPLAY_BYTES {
-            tracing::warn!(
-                "Skipping oversized replay in ZIP: {} ({} bytes)",
-                name,
-                file.size()
-            );
-            continue;
-        }
+            if is_nested {
+                if depth >= MAX_NESTED_ZIP_DEPTH {
+                    continue;
+                }
+            } else {
+                self.total += 1;
+                // Count but don't extract beyond the cap
+                if self.extracted >= MAX_REPLAYS_PER_ARCHIVE {
+                    continue;
+                }
+            }
+            let max_bytes = kind.max_bytes(Venue::Archive);
 
-        // Check total uncompressed bytes before allocating
-        extracted_bytes += file.size();
-        if extracted_bytes > MAX_ARCHIVE_UNCOMPRESSED_BYTES {
-            tracing::warn!(
-                "ZIP extraction byte limit exceeded ({} bytes), stopping",
-                extracted_bytes
-            );
-            break;
-        }
+            // Skip replays larger than 5MB and oversized nested archives
+            if let AttachmentClass::TooLarge { .. } = class {
+                tracing::warn!(
+                    "Skipping oversized entry in ZIP: {} ({} bytes)",
+                    name,
+                    file.size()
+                );
+                continue;
+            }
 
-        // Use Read::take to cap actual bytes read
-        let mut buf = Vec::with_capacity(file.size() as usize);
-        if let Err(e) = file
-            .by_ref()
-            .take(MAX_SINGLE_REPLAY_BYTES)
-            .read_to_end(&mut buf)
-        {
-            tracing::warn!("Failed to extract {}: {}", name, e);
-            continue;
-        }
+            // Check total uncompressed bytes before allocating
+            self.extracted_bytes += file.size();
+            if self.extracted_bytes > MAX_ARCHIVE_UNCOMPRESSED_BYTES {
+                tracing::warn!(
+                    "ZIP extraction byte limit exceeded ({} bytes), stopping",
+                    self.extracted_bytes
+                );
+                return false;
+            }
 
-        // Use just the filename, not the full path inside the archive
-        let short_name = name.rsplit(['/', '\\']).next().unwrap_or(&name).to_string();
+            // Use Read::take to cap actual bytes read (after any sniffed head)
+            buf.reserve((file.size() as usize).saturating_sub(buf.len()));
+            let rest = max_bytes - buf.len() as u64;
+            if let Err(e) = file.by_ref().take(rest).read_to_end(&mut buf) {
+                tracing::warn!("Failed to extract {}: {}", name, e);
+                note_problem(&mut self.problem, ArchiveError::Corrupted(e.to_string()));
+                continue;
+            }
 
-        replays.push((short_name, buf));
-    }
+            if is_nested {
+                if !self.collect(&buf, depth + 1) {
+                    return false;
+                }
+                continue;
+            }
 
-    (replays, total)
-}
+            // Use just the filename, not the full path inside the archive
+            let short_name = name.rsplit(['/', '\\']).next().unwrap_or(&name).to_string();
 
-/// Extract .BfME2Replay files from a RAR archive (via temp directory).
-/// Returns (replays, total_count) — only up to MAX_REPLAYS_PER_ARCHIVE bytes are read,
-/// but total_count reflects how many replay files were found on disk.
-pub fn extract_replays_from_rar(data: &[u8]) -> (Vec<(String, Vec<u8>)>, usize) {
-    let tmp_dir = match tempfile::tempdir() {
-        Ok(d) => d,
-        Err(e) => {
-            tracing::error!("Failed to create temp dir: {}", e);
-            return (Vec::new(), 0);
+            self.extracted += 1;
+            if !(self.sink)(short_name, buf) {
+                return false;
+            }
         }
+        true
+    }
+}
+
+/// Extract replay files from a RAR archive (via temp directory), handing
+/// each to `sink` as it is read back from disk; stops early when `sink`
+/// returns false.
+/// Returns the total count — only up to MAX_REPLAYS_PER_ARCHIVE files are read,
+/// but the count reflects how many replay files were found on disk.
+/// Fails like [`extract_replays_from_zip`] when nothing could be extracted.
+/// The temp directory is registered in `temp_dirs` until it is removed.
+#[tracing::instrument(skip_all, fields(bytes = data.len()))]
+pub fn stream_replays_from_rar(
+    data: &[u8],
+    temp_dirs: &TempDirRegistry,
+    mut sink: impl FnMut(String, Vec<u8>) -> bool,
+) -> Result<usize, ArchiveError> {
+    let io_error = |what: &str, e: std::io::Error| {
+        tracing::error!("Failed to {}: {}", what, e);
+        ArchiveError::Io(e.to_string())
     };
+    let tmp_dir = temp_dirs
+        .create()
+        .map_err(|e| io_error("create temp dir", e))?;
 
     // Write RAR data to a temp file (unrar needs a filesystem path)
     let rar_path = tmp_dir.path().join("archive.rar");
-    if let Err(e) = std::fs::write(&rar_path, data) {
-        tracing::error!("Failed to write temp RAR file: {}", e);
-        return (Vec::new(), 0);
-    }
+    std::fs::write(&rar_path, data).map_err(|e| io_error("write temp RAR file", e))?;
 
     let extract_dir = tmp_dir.path().join("extracted");
-    if let Err(e) = std::fs::create_dir_all(&extract_dir) {
-        tracing::error!("Failed to create extract dir: {}", e);
-        return (Vec::new(), 0);
-    }
+    std::fs::create_dir_all(&extract_dir).map_err(|e| io_error("create extract dir", e))?;
 
     // Extract using unrar
     let mut archive =
@@ -114,27 +302,48 @@ pub fn extract_replays_from_rar(data: &[u8]) -> (Vec<(String, Vec<u8>)>, usize)
             Ok(a) => a,
             Err(e) => {
                 tracing::error!("Failed to open RAR archive: {}", e);
-                return (Vec::new(), 0);
+                return Err(ArchiveError::from(&e));
             }
         };
 
     // Extract all files (unrar API requires sequential processing)
     let mut extracted_bytes: u64 = 0;
     let mut extracted_files: usize = 0;
+    let mut problem = None;
     loop {
         let header = match archive.read_header() {
             Ok(Some(header)) => header,
             Ok(None) => break,
             Err(e) => {
                 tracing::error!("Failed to read RAR header: {}", e);
+                note_problem(&mut problem, ArchiveError::from(&e));
                 break;
             }
         };
 
         let is_file = header.entry().is_file();
         let unpacked = header.entry().unpacked_size;
+        let is_replay = classify_attachment(
+            &header.entry().filename.to_string_lossy(),
+            unpacked,
+            None,
+            Venue::Archive,
+        )
+        .kind()
+            == Some(FileKind::Replay);
 
-        if is_file {
+        if is_file && header.entry().is_encrypted() {
+            if is_replay {
+                note_problem(&mut problem, ArchiveError::Encrypted);
+            }
+            archive = match header.skip() {
+                Ok(a) => a,
+                Err(e) => {
+                    tracing::warn!("Failed to skip RAR entry: {}", e);
+                    break;
+                }
+            };
+        } else if is_file {
             extracted_files += 1;
             extracted_bytes += unpacked;
             if extracted_bytes > MAX_ARCHIVE_UNCOMPRESSED_BYTES
@@ -152,6 +361,7 @@ pub fn extract_replays_from_rar(data: &[u8]) -> (Vec<(String, Vec<u8>)>, usize)
                 Ok(a) => a,
                 Err(e) => {
                     tracing::warn!("Failed to extract RAR entry: {}", e);
+                    note_problem(&mut problem, ArchiveError::from(&e));
                     break;
                 }
             };
@@ -166,53 +376,512 @@ pub fn extract_replays_from_rar(data: &[u8]) -> (Vec<(String, Vec<u8>)>, usize)
         }
     }
 
-    // Collect extracted .BfME2Replay files (reads bytes only up to cap)
-    let mut replays = Vec::new();
-    let mut total = 0usize;
-    collect_replay_files(&extract_dir, &mut replays, &mut total);
+    // Collect extracted replay files (reads bytes only up to cap)
+    let mut found = FoundReplays::default();
+    collect_replay_files(&extract_dir, &mut sink, &mut found);
+
+    match problem {
+        Some(problem) if found.extracted == 0 => Err(problem),
+        _ => Ok(found.total),
+    }
+    // tmp_dir is dropped here, cleaning up all temp files and its registry entry
+}
 
-    (replays, total)
-    // tmp_dir is dropped here, cleaning up all temp files
+/// Replay files seen on disk so far
+#[derive(Default)]
+struct FoundReplays {
+    /// Handed to the sink
+    extracted: usize,
+    total: usize,
 }
 
-/// Recursively collect .BfME2Replay files from a directory.
+/// Recursively collect replay files from a directory, by name or, for other
+/// small files, by their first bytes.
 /// Only reads file bytes for the first MAX_REPLAYS_PER_ARCHIVE files; counts the rest.
+/// Returns false once `sink` asks to stop.
 fn collect_replay_files(
     dir: &std::path::Path,
-    replays: &mut Vec<(String, Vec<u8>)>,
-    total: &mut usize,
-) {
+    sink: &mut impl FnMut(String, Vec<u8>) -> bool,
+    found: &mut FoundReplays,
+) -> bool {
     let entries = match std::fs::read_dir(dir) {
         Ok(e) => e,
-        Err(_) => return,
+        Err(_) => return true,
     };
 
     for entry in entries.flatten() {
         let path = entry.path();
         if path.is_dir() {
-            collect_replay_files(&path, replays, total);
-        } else if let Some(name) = path.file_name().and_then(|n| n.to_str())
-            && name.to_lowercase().ends_with(".bfme2replay")
-        {
-            *total += 1;
+            if !collect_replay_files(&path, sink, found) {
+                return false;
+            }
+        } else if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            let size = path.metadata().map(|meta| meta.len()).unwrap_or(0);
+            let class = classify_attachment(name, size, None, Venue::Archive);
+            let is_replay = match class {
+                AttachmentClass::SniffCandidate => file_has_replay_magic(&path),
+                _ => class.kind() == Some(FileKind::Replay),
+            };
+            if !is_replay {
+                continue;
+            }
+            found.total += 1;
 
             // Count but don't read bytes beyond the cap
-            if replays.len() >= MAX_REPLAYS_PER_ARCHIVE {
+            if found.extracted >= MAX_REPLAYS_PER_ARCHIVE {
                 continue;
             }
 
             // Skip files larger than 5MB
-            if let Ok(meta) = path.metadata()
-                && meta.len() > MAX_SINGLE_REPLAY_BYTES
-            {
-                tracing::warn!("Skipping oversized replay: {} ({} bytes)", name, meta.len());
+            if let AttachmentClass::TooLarge { .. } = class {
+                tracing::warn!("Skipping oversized replay: {} ({} bytes)", name, size);
                 continue;
             }
 
             match std::fs::read(&path) {
-                Ok(bytes) => replays.push((name.to_string(), bytes)),
+                Ok(bytes) => {
+                    found.extracted += 1;
+                    if !sink(name.to_string(), bytes) {
+                        return false;
+                    }
+                }
                 Err(e) => tracing::warn!("Failed to read {}: {}", name, e),
             }
         }
     }
+    true
+}
+
+/// Whether a file on disk starts with the replay magic
+fn file_has_replay_magic(path: &std::path::Path) -> bool {
+    let mut head = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|file| file.take(SNIFF_BYTES).read_to_end(&mut head))
+        .is_ok_and(|_| has_replay_magic(&head))
+}
+
+/// Archive replays after the pre-parse pass: duplicates merged, restart stubs removed
+pub struct PreparsedReplays {
+    pub replays: Vec<(String, Vec<u8>)>,
+    /// Copies merged away as duplicates of a kept game
+    pub merged: usize,
+    /// One line per restart stub that was skipped
+    pub restart_notes: Vec<UserMessage>,
+    /// Parse result of each kept replay, in `replays` order
+    pub infos: Vec<Result<ReplayInfo, ReplayError>>,
+}
+
+impl PreparsedReplays {
+    /// Keep only the replays whose name matches `filter` (see [`name_matches`])
+    pub fn retain_matching(&mut self, filter: &str) {
+        let (replays, infos) = std::mem::take(&mut self.replays)
+            .into_iter()
+            .zip(std::mem::take(&mut self.infos))
+            .filter(|((name, _), _)| name_matches(name, filter))
+            .unzip();
+        self.replays = replays;
+        self.infos = infos;
+    }
+}
+
+/// Lowercase, with Turkish dotted/dotless i folded to a plain `i` so
+/// "İzmir", "IZMIR" and "ızmır" all compare equal
+fn fold_name(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|&c| c != '\u{307}')
+        .map(|c| if c == 'ı' { 'i' } else { c })
+        .collect()
+}
+
+/// Whether a replay's filename contains `filter`, ignoring case
+pub fn name_matches(name: &str, filter: &str) -> bool {
+    fold_name(name).contains(&fold_name(filter))
+}
+
+/// An archive replay with its header start time and its parse result
+pub type PreparsedReplay = (
+    String,
+    Vec<u8>,
+    Option<u32>,
+    Result<ReplayInfo, ReplayError>,
+);
+
+/// Parse one extracted replay the way batches are parsed (within the batch
+/// budget, on the map's spot regions); runs as soon as it comes out of the
+/// archive, and the result is what gets rendered. Replays that fail to parse
+/// are kept so their errors still get reported.
+pub fn preparse_replay(name: String, bytes: Vec<u8>, regions: SpotRegions) -> PreparsedReplay {
+    let start = header_start_time(&bytes);
+    let budget = Duration::from_millis(BATCH_PARSE_BUDGET_MS);
+    let info = parse_recorded(&bytes, regions, budget, &tracing::Span::none());
+    (name, bytes, start, info)
+}
+
+/// Once every replay is parsed: order them (given in archive order) by
+/// header start time, merge duplicate copies and drop restart stubs
+#[tracing::instrument(skip_all, fields(replays = parsed.len()))]
+pub fn finish_preparse(parsed: Vec<PreparsedReplay>) -> PreparsedReplays {
+    let parsed = sort_by_start_time(parsed)
+        .into_iter()
+        .map(|(name, bytes, _, info)| (name, bytes, info))
+        .collect();
+    let (parsed, merged) = merge_duplicate_games(parsed);
+
+    let infos: Vec<Option<&ReplayInfo>> = parsed.iter().map(|(_, _, i)| i.as_ref().ok()).collect();
+    let restarts = find_restart_pairs(&infos);
+
+    // Follow chains (a restart of a restart) to the game that was actually played
+    let final_game = |mut idx: usize| {
+        while let Some(&(_, next)) = restarts.iter().find(|(stub, _)| *stub == idx) {
+            idx = next;
+        }
+        idx
+    };
+    let is_stub = |idx: usize| restarts.iter().any(|(stub, _)| *stub == idx);
+
+    // 1-based game numbers among the replays that are still shown
+    let game_numbers: Vec<Option<usize>> = (0..parsed.len())
+        .scan(0, |n, idx| {
+            Some((!is_stub(idx)).then(|| {
+                *n += 1;
+                *n
+            }))
+        })
+        .collect();
+
+    let mut restart_notes = Vec::new();
+    for &(stub, _) in &restarts {
+        if let Some(game) = game_numbers[final_game(stub)] {
+            tracing::info!("Skipping {} as a restart of game {}", parsed[stub].0, game);
+            restart_notes.push(UserMessage::restart_skipped(&parsed[stub].0, game));
+        }
+    }
+
+    let (replays, infos): (Vec<_>, Vec<_>) = parsed
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| !is_stub(*idx))
+        .map(|(_, (name, bytes, info))| ((name, bytes), info))
+        .unzip();
+
+    PreparsedReplays {
+        replays,
+        merged,
+        restart_notes,
+        infos,
+    }
+}
+
+type ParsedReplay = (String, Vec<u8>, Result<ReplayInfo, ReplayError>);
+
+/// Replays in order of start time; replays started the same second keep
+/// their archive order, and those without a start time go last
+pub fn sort_by_start_time(mut replays: Vec<PreparsedReplay>) -> Vec<PreparsedReplay> {
+    replays.sort_by_key(|(_, _, start, _)| (start.is_none(), *start));
+    replays
+}
+
+/// Runs of consecutive replays played on the same UTC day, as the date
+/// (`None` for unknown) and how many replays the run holds
+pub fn date_groups(starts: &[Option<u32>]) -> Vec<(Option<String>, usize)> {
+    let mut groups: Vec<(Option<String>, usize)> = Vec::new();
+    for date in starts.iter().map(|start| start.map(format_date)) {
+        match groups.last_mut() {
+            Some((last, count)) if *last == date => *count += 1,
+            _ => groups.push((date, 1)),
+        }
+    }
+    groups
+}
+
+/// One separator line per date on a page of replays, in page order; none
+/// when the whole page was played on one day
+pub fn date_separators(page: &[(String, Vec<u8>)]) -> Vec<UserMessage> {
+    let starts: Vec<Option<u32>> = page
+        .iter()
+        .map(|(_, bytes)| header_start_time(bytes))
+        .collect();
+    let groups = date_groups(&starts);
+    if groups.len() < 2 {
+        return Vec::new();
+    }
+    groups
+        .into_iter()
+        .map(|(date, replays)| UserMessage::date_group(date, replays))
+        .collect()
+}
+
+/// Drop extra copies of the same game (e.g. host and observer saves of one match).
+/// Returns the kept replays and how many copies were merged away.
+fn merge_duplicate_games(replays: Vec<ParsedReplay>) -> (Vec<ParsedReplay>, usize) {
+    let mut kept: Vec<ParsedReplay> = Vec::with_capacity(replays.len());
+    let mut merged = 0usize;
+
+    for (name, bytes, info) in replays {
+        if let Ok(info) = &info
+            && let Some((first, _, _)) = kept
+                .iter()
+                .find(|(_, _, k)| k.as_ref().is_ok_and(|k| k.is_same_game(info)))
+        {
+            tracing::info!("Merging {} as a copy of {}", name, first);
+            merged += 1;
+            continue;
+        }
+        kept.push((name, bytes, info));
+    }
+
+    (kept, merged)
+}
+
+/// Whether `stub` looks like a game that was abandoned early and rehosted as `rehost`:
+/// same players, stub too short or not concluded, and rehost started within
+/// RESTART_WINDOW_SECS of the stub (but not before it ended).
+fn is_restart_pair(stub: &ReplayInfo, rehost: &ReplayInfo) -> bool {
+    let (Some(stub_start), Some(rehost_start)) = (stub.start_time, rehost.start_time) else {
+        return false;
+    };
+    let is_stub = stub.winner == Winner::NotConcluded
+        || stub
+            .duration_seconds()
+            .is_some_and(|d| d < RESTART_MAX_STUB_SECS);
+    let stub_end = stub_start + stub.duration_seconds().unwrap_or(0);
+
+    is_stub
+        && rehost_start >= stub_end
+        && rehost_start - stub_start <= RESTART_WINDOW_SECS
+        && player_set(stub) == player_set(rehost)
+}
+
+/// Sorted player identities (UID, or lowercased name when missing)
+fn player_set(replay: &ReplayInfo) -> Vec<String> {
+    let mut ids: Vec<String> = replay
+        .players
+        .iter()
+        .map(|p| p.uid.clone().unwrap_or_else(|| p.name.to_lowercase()))
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// Pair each restart stub with the earliest later game it was rehosted as.
+/// Returns `(stub_index, rehost_index)` pairs; each rehost is claimed at most once.
+fn find_restart_pairs(infos: &[Option<&ReplayInfo>]) -> Vec<(usize, usize)> {
+    let mut by_start: Vec<(usize, &ReplayInfo)> = infos
+        .iter()
+        .enumerate()
+        .filter_map(|(i, info)| info.map(|info| (i, info)))
+        .collect();
+    by_start.sort_by_key(|(_, info)| info.start_time);
+
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    for (pos, &(stub_idx, stub)) in by_start.iter().enumerate() {
+        let rehost = by_start[pos + 1..].iter().find(|&&(idx, info)| {
+            !pairs.iter().any(|&(_, claimed)| claimed == idx) && is_restart_pair(stub, info)
+        });
+        if let Some(&(rehost_idx, _)) = rehost {
+            pairs.push((stub_idx, rehost_idx));
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, PlayerBuilder};
+
+    fn game(names: &[&str], start: u32, duration: u32, winner: Winner) -> ReplayInfo {
+        let players = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| PlayerBuilder::test_player(name, i as i8, i as u8, Faction::Men))
+            .collect();
+        ReplayInfo::new("map wor rhun".to_string(), players)
+            .with_times(start, start + duration)
+            .with_winner(winner)
+    }
+
+    const T: u32 = 1_700_000_000;
+
+    #[test]
+    fn names_match_case_insensitively() {
+        assert!(name_matches("Game vs ClanX.BfME2Replay", "vs clanx"));
+        assert!(name_matches("ÖZEL MAÇ.BfME2Replay", "özel maç"));
+        // Turkish dotted and dotless i compare equal to a plain i
+        assert!(name_matches("İZMİR final.BfME2Replay", "izmir"));
+        assert!(name_matches("Kılıç 2v2.BfME2Replay", "KILIÇ"));
+        assert!(!name_matches("Game vs ClanX.BfME2Replay", "ClanY"));
+    }
+
+    #[test]
+    fn filter_keeps_matching_replays_and_their_results() {
+        let mut preparsed = PreparsedReplays {
+            replays: vec![
+                ("vs ClanX 1.BfME2Replay".to_string(), vec![1]),
+                ("vs ClanY.BfME2Replay".to_string(), vec![2]),
+                ("vs clanx 2.BfME2Replay".to_string(), vec![3]),
+            ],
+            merged: 0,
+            restart_notes: Vec::new(),
+            infos: vec![
+                Err(ReplayError::InvalidHeader),
+                Ok(game(&["A"], T, 60, Winner::LeftTeam)),
+                Err(ReplayError::NoPlayers),
+            ],
+        };
+        preparsed.retain_matching("CLANX");
+        let kept: Vec<&[u8]> = preparsed.replays.iter().map(|(_, b)| &b[..]).collect();
+        assert_eq!(kept, [[1], [3]]);
+        assert!(preparsed.infos.iter().all(Result::is_err));
+        assert_eq!(preparsed.infos.len(), 2);
+
+        preparsed.retain_matching("nothing");
+        assert!(preparsed.replays.is_empty() && preparsed.infos.is_empty());
+    }
+
+    #[test]
+    fn genuine_restart_is_paired() {
+        let stub = game(&["Alice", "Bob"], T, 40, Winner::NotConcluded);
+        let real = game(&["Bob", "Alice"], T + 120, 1800, Winner::LeftTeam);
+        assert_eq!(find_restart_pairs(&[Some(&real), Some(&stub)]), [(1, 0)]);
+    }
+
+    #[test]
+    fn different_players_are_not_paired() {
+        let stub = game(&["Alice", "Bob"], T, 40, Winner::NotConcluded);
+        let other = game(&["Alice", "Carol"], T + 120, 1800, Winner::LeftTeam);
+        assert!(find_restart_pairs(&[Some(&stub), Some(&other)]).is_empty());
+    }
+
+    #[test]
+    fn distinct_short_games_are_not_paired() {
+        // Two quick decided games between the same players, well apart
+        let first = game(&["Alice", "Bob"], T, 45, Winner::LeftTeam);
+        let second = game(&["Alice", "Bob"], T + 1200, 50, Winner::RightTeam);
+        assert!(find_restart_pairs(&[Some(&first), Some(&second)]).is_empty());
+    }
+
+    #[test]
+    fn threshold_edges() {
+        let real = |offset| game(&["Alice", "Bob"], T + offset, 1800, Winner::LeftTeam);
+
+        // Exactly at the stub duration limit is a real (if short) game
+        let long_enough = game(
+            &["Alice", "Bob"],
+            T,
+            RESTART_MAX_STUB_SECS,
+            Winner::LeftTeam,
+        );
+        assert!(!is_restart_pair(&long_enough, &real(120)));
+        let stub = game(
+            &["Alice", "Bob"],
+            T,
+            RESTART_MAX_STUB_SECS - 1,
+            Winner::LeftTeam,
+        );
+        assert!(is_restart_pair(&stub, &real(120)));
+
+        // Rehost window is inclusive
+        assert!(is_restart_pair(&stub, &real(RESTART_WINDOW_SECS)));
+        assert!(!is_restart_pair(&stub, &real(RESTART_WINDOW_SECS + 1)));
+
+        // Rehost can't start before the stub ended
+        assert!(!is_restart_pair(&stub, &real(10)));
+    }
+
+    #[test]
+    fn each_rehost_is_claimed_once() {
+        let crash_a = game(&["Alice", "Bob"], T, 30, Winner::NotConcluded);
+        let crash_b = game(&["Alice", "Bob"], T + 60, 30, Winner::NotConcluded);
+        let real = game(&["Alice", "Bob"], T + 120, 1800, Winner::LeftTeam);
+        let pairs = find_restart_pairs(&[Some(&crash_a), Some(&crash_b), Some(&real)]);
+        assert_eq!(pairs, [(0, 1), (1, 2)]);
+    }
+
+    fn dated(name: &str, start: Option<u32>) -> PreparsedReplay {
+        (
+            name.to_string(),
+            Vec::new(),
+            start,
+            Err(ReplayError::InvalidHeader),
+        )
+    }
+
+    /// 2024-03-12 20:00 UTC
+    const EVENING: u32 = 1_710_273_600;
+
+    #[test]
+    fn replays_sort_by_start_with_unknown_dates_last() {
+        let sorted = sort_by_start_time(vec![
+            dated("broken", None),
+            dated("late", Some(EVENING + 7200)),
+            dated("first", Some(EVENING)),
+            dated("also broken", None),
+            dated("next day", Some(EVENING + 86_400)),
+        ]);
+        let names: Vec<&str> = sorted.iter().map(|(n, _, _, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            ["first", "late", "next day", "broken", "also broken"]
+        );
+    }
+
+    #[test]
+    fn same_second_replays_keep_archive_order() {
+        // Host and observer saves of one game start on the same second
+        let sorted = sort_by_start_time(vec![
+            dated("b host", Some(EVENING)),
+            dated("a early", Some(EVENING - 60)),
+            dated("c observer", Some(EVENING)),
+            dated("d host", Some(EVENING)),
+        ]);
+        let names: Vec<&str> = sorted.iter().map(|(n, _, _, _)| n.as_str()).collect();
+        assert_eq!(names, ["a early", "b host", "c observer", "d host"]);
+    }
+
+    #[test]
+    fn date_groups_follow_day_changes() {
+        let starts = [
+            Some(EVENING),
+            Some(EVENING + 3 * 3600), // 23:00, same day
+            Some(EVENING + 5 * 3600), // past midnight
+            None,
+            None,
+        ];
+        assert_eq!(
+            date_groups(&starts),
+            [
+                (Some("2024-03-12".to_string()), 2),
+                (Some("2024-03-13".to_string()), 1),
+                (None, 2),
+            ]
+        );
+        assert!(date_groups(&[]).is_empty());
+    }
+
+    #[test]
+    fn separators_only_on_pages_spanning_several_days() {
+        let replay = |start: u32| {
+            let mut bytes = b"BFME2RPL".to_vec();
+            bytes.extend_from_slice(&start.to_le_bytes());
+            ("game.BfME2Replay".to_string(), bytes)
+        };
+        let one_day = [replay(EVENING), replay(EVENING + 60)];
+        assert!(date_separators(&one_day).is_empty());
+
+        let two_days = [replay(EVENING), replay(EVENING + 86_400), replay(0)];
+        let lines: Vec<String> = date_separators(&two_days)
+            .iter()
+            .map(|line| line.render(crate::bot::i18n::Locale::En))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "— 2024-03-12 — 1 replay",
+                "— 2024-03-13 — 1 replay",
+                "— Unknown date — 1 replay",
+            ]
+        );
+    }
 }
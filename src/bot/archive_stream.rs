@@ -0,0 +1,229 @@
+//! Archive extraction and pre-parsing as a pipeline: a blocking producer
+//! extracts replays one at a time into a bounded channel, and each replay is
+//! parsed as soon as it arrives instead of after the whole archive is read
+
+use crate::models::SpotRegions;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc;
+
+use super::archive::{
+    ArchiveError, PreparsedReplay, PreparsedReplays, finish_preparse, preparse_replay,
+    stream_replays_from_rar, stream_replays_from_zip,
+};
+use super::constants::BATCH_SIZE;
+use super::temp_dirs::TempDirRegistry;
+
+/// Replay bytes extracted but not yet taken by the consumer, and the most
+/// ever held at once
+#[derive(Debug, Default)]
+struct BufferedBytes {
+    current: AtomicU64,
+    peak: AtomicU64,
+}
+
+impl BufferedBytes {
+    fn add(&self, len: u64) {
+        let now = self.current.fetch_add(len, Ordering::Relaxed) + len;
+        self.peak.fetch_max(now, Ordering::Relaxed);
+    }
+
+    fn remove(&self, len: u64) {
+        self.current.fetch_sub(len, Ordering::Relaxed);
+    }
+}
+
+/// Producer end of a replay channel
+pub struct ReplaySender {
+    tx: mpsc::Sender<(String, Vec<u8>)>,
+    buffered: Arc<BufferedBytes>,
+}
+
+impl ReplaySender {
+    /// Send one replay, waiting while the channel is full. Returns false
+    /// once the consumer is gone, so the extraction can stop. Must not be
+    /// called from async code.
+    pub fn send(&self, name: String, bytes: Vec<u8>) -> bool {
+        let len = bytes.len() as u64;
+        // Counted while waiting for room too: the producer holds it until then
+        self.buffered.add(len);
+        let sent = self.tx.blocking_send((name, bytes)).is_ok();
+        if !sent {
+            self.buffered.remove(len);
+        }
+        sent
+    }
+}
+
+/// Consumer end of a replay channel
+pub struct ReplayReceiver {
+    rx: mpsc::Receiver<(String, Vec<u8>)>,
+    buffered: Arc<BufferedBytes>,
+}
+
+impl ReplayReceiver {
+    /// Next replay in archive order; `None` once the producer is done
+    pub async fn recv(&mut self) -> Option<(String, Vec<u8>)> {
+        let item = self.rx.recv().await?;
+        self.buffered.remove(item.1.len() as u64);
+        Some(item)
+    }
+
+    /// Most replay bytes held at once by the channel, a producer waiting on
+    /// it and a replay just taken off it
+    pub fn peak_buffered_bytes(&self) -> u64 {
+        self.buffered.peak.load(Ordering::Relaxed)
+    }
+}
+
+/// A channel holding up to `capacity` replays between extraction and parsing
+pub fn replay_channel(capacity: usize) -> (ReplaySender, ReplayReceiver) {
+    let (tx, rx) = mpsc::channel(capacity);
+    let buffered = Arc::new(BufferedBytes::default());
+    (
+        ReplaySender {
+            tx,
+            buffered: buffered.clone(),
+        },
+        ReplayReceiver { rx, buffered },
+    )
+}
+
+/// Parse replays as they arrive, up to BATCH_SIZE at a time, until the
+/// producer is done. Returns them in archive (arrival) order.
+pub async fn preparse_received(
+    rx: &mut ReplayReceiver,
+    regions: SpotRegions,
+) -> Vec<PreparsedReplay> {
+    let mut set = tokio::task::JoinSet::new();
+    let mut parsed = Vec::new();
+    let mut arrived = 0;
+    while let Some((name, bytes)) = rx.recv().await {
+        while set.len() >= BATCH_SIZE {
+            collect_parsed(set.join_next().await, &mut parsed);
+        }
+        let idx = arrived;
+        arrived += 1;
+        set.spawn_blocking(move || (idx, preparse_replay(name, bytes, regions)));
+    }
+    while let Some(joined) = set.join_next().await {
+        collect_parsed(Some(joined), &mut parsed);
+    }
+    parsed.sort_by_key(|(idx, _)| *idx);
+    parsed.into_iter().map(|(_, replay)| replay).collect()
+}
+
+type JoinedParse = Result<(usize, PreparsedReplay), tokio::task::JoinError>;
+
+fn collect_parsed(joined: Option<JoinedParse>, parsed: &mut Vec<(usize, PreparsedReplay)>) {
+    match joined {
+        Some(Ok(replay)) => parsed.push(replay),
+        Some(Err(e)) => tracing::error!("Replay pre-parse task panicked: {}", e),
+        None => {}
+    }
+}
+
+/// Extract an archive on a blocking thread while its replays are parsed as
+/// they come out, on the map's spot `regions`. Returns the pre-parsed
+/// replays and how many were found, or why none could be extracted; `Err`
+/// when the extraction task failed.
+pub async fn extract_and_preparse(
+    archive_bytes: Vec<u8>,
+    is_rar: bool,
+    temp_dirs: TempDirRegistry,
+    regions: SpotRegions,
+) -> Result<Result<(PreparsedReplays, usize), ArchiveError>, tokio::task::JoinError> {
+    let (tx, mut rx) = replay_channel(BATCH_SIZE);
+    let span = tracing::Span::current();
+    let producer = tokio::task::spawn_blocking(move || {
+        let _entered = span.enter();
+        // Unwind here rather than in the runtime, so an extraction panic is
+        // reported like any other failure once its temp dir is dropped
+        let extract = || {
+            let send = |name, bytes| tx.send(name, bytes);
+            if is_rar {
+                stream_replays_from_rar(&archive_bytes, &temp_dirs, send)
+            } else {
+                stream_replays_from_zip(&archive_bytes, send)
+            }
+        };
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(extract)).unwrap_or_else(|_| {
+            tracing::error!("Archive extraction panicked");
+            Err(ArchiveError::Io("extraction panicked".to_string()))
+        })
+        // The sender drops here, ending the consumer's loop
+    });
+    let parsed = preparse_received(&mut rx, regions).await;
+    tracing::debug!(
+        "Parsed {} replays, at most {} bytes waiting at once",
+        parsed.len(),
+        rx.peak_buffered_bytes()
+    );
+    let total = producer.await?;
+    Ok(total.map(|total| (finish_preparse(parsed), total)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn buffered_bytes_stay_within_the_channel_capacity() {
+        const CAPACITY: usize = 4;
+        const REPLAY_BYTES: usize = 1000;
+        let (tx, mut rx) = replay_channel(CAPACITY);
+        let producer = tokio::task::spawn_blocking(move || {
+            (0..40).all(|i| tx.send(format!("{}.BfME2Replay", i), vec![0; REPLAY_BYTES]))
+        });
+        let mut received = 0;
+        while rx.recv().await.is_some() {
+            // A consumer slower than the producer
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            received += 1;
+        }
+        assert!(producer.await.unwrap());
+        assert_eq!(received, 40);
+        // The channel's replays, the one the producer waits to send and the
+        // one just taken off
+        let peak = rx.peak_buffered_bytes();
+        assert!(peak >= REPLAY_BYTES as u64);
+        assert!(peak <= ((CAPACITY + 2) * REPLAY_BYTES) as u64, "{}", peak);
+    }
+
+    #[tokio::test]
+    async fn producer_stops_once_the_consumer_is_gone() {
+        let (tx, rx) = replay_channel(1);
+        drop(rx);
+        let sent = tokio::task::spawn_blocking(move || tx.send("a.rep".to_string(), vec![1]))
+            .await
+            .unwrap();
+        assert!(!sent);
+    }
+
+    #[tokio::test]
+    async fn preparsed_replays_keep_archive_order() {
+        let (tx, mut rx) = replay_channel(2);
+        let names: Vec<String> = (0..3 * BATCH_SIZE)
+            .map(|i| format!("{:02}.rep", i))
+            .collect();
+        let sent = names.clone();
+        let producer = tokio::task::spawn_blocking(move || {
+            for name in sent {
+                // Larger (slower) files first, so parses finish out of order
+                let len = 64 * (3 * BATCH_SIZE - name[..2].parse::<usize>().unwrap());
+                tx.send(name, vec![0; len]);
+            }
+        });
+        let parsed = preparse_received(&mut rx, SpotRegions::default()).await;
+        producer.await.unwrap();
+        let order: Vec<&String> = parsed.iter().map(|(name, _, _, _)| name).collect();
+        assert_eq!(order, names.iter().collect::<Vec<_>>());
+        // Not replays: kept, without a start time, for their errors
+        assert!(
+            parsed
+                .iter()
+                .all(|(_, _, start, info)| start.is_none() && info.is_err())
+        );
+    }
+}
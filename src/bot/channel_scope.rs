@@ -0,0 +1,111 @@
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+
+/// Channels the bot answers in, per guild. A guild without entries (or with
+/// an empty list) is answered everywhere; DMs always are.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelScopes {
+    by_guild: HashMap<serenity::GuildId, Vec<serenity::ChannelId>>,
+}
+
+impl ChannelScopes {
+    /// Parse a `BOT_CHANNELS` value: `guild_id=channel_id,channel_id` entries
+    /// separated by `;`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut scopes = Self::default();
+        for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+            let (guild, channels) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("missing '=' in entry '{}'", entry.trim()))?;
+            let guild = parse_id(guild, "guild")?;
+            let channels = channels
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| parse_id(id, "channel").map(serenity::ChannelId::new))
+                .collect::<Result<Vec<_>, _>>()?;
+            scopes
+                .by_guild
+                .entry(serenity::GuildId::new(guild))
+                .or_default()
+                .extend(channels);
+        }
+        Ok(scopes)
+    }
+
+    /// Whether a message in this channel should be handled
+    pub fn allows(
+        &self,
+        guild_id: Option<serenity::GuildId>,
+        channel_id: serenity::ChannelId,
+    ) -> bool {
+        let Some(allowed) = guild_id.and_then(|id| self.by_guild.get(&id)) else {
+            return true;
+        };
+        allowed.is_empty() || allowed.contains(&channel_id)
+    }
+}
+
+/// Parse an `ANONYMOUS_GUILDS` value: comma-separated guild ids
+pub fn parse_guild_ids(spec: &str) -> Result<Vec<serenity::GuildId>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| parse_id(id, "guild").map(serenity::GuildId::new))
+        .collect()
+}
+
+fn parse_id(id: &str, kind: &str) -> Result<u64, String> {
+    let id = id.trim();
+    id.parse::<u64>()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("invalid {} id '{}'", kind, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: Option<serenity::GuildId> = Some(serenity::GuildId::new(42));
+    const REPLAYS: serenity::ChannelId = serenity::ChannelId::new(100);
+    const GENERAL: serenity::ChannelId = serenity::ChannelId::new(200);
+
+    #[test]
+    fn scoping_decisions() {
+        // No config: every channel
+        let none = ChannelScopes::default();
+        assert!(none.allows(GUILD, GENERAL));
+
+        // Empty list: every channel
+        let empty = ChannelScopes::parse("42=").unwrap();
+        assert!(empty.allows(GUILD, GENERAL));
+
+        let scoped = ChannelScopes::parse("42=100, 101; 7=300").unwrap();
+        assert!(scoped.allows(GUILD, REPLAYS));
+        assert!(!scoped.allows(GUILD, GENERAL));
+        // Other guilds and DMs are unaffected
+        assert!(scoped.allows(Some(serenity::GuildId::new(8)), GENERAL));
+        assert!(scoped.allows(None, GENERAL));
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        assert!(ChannelScopes::parse("42").is_err());
+        assert!(ChannelScopes::parse("abc=100").is_err());
+        assert!(ChannelScopes::parse("42=100,x").is_err());
+        assert!(ChannelScopes::parse("0=100").is_err());
+        assert!(ChannelScopes::parse("").is_ok());
+    }
+
+    #[test]
+    fn guild_id_lists() {
+        assert_eq!(
+            parse_guild_ids(" 42, 7 ,"),
+            Ok(vec![serenity::GuildId::new(42), serenity::GuildId::new(7)])
+        );
+        assert_eq!(parse_guild_ids(""), Ok(vec![]));
+        assert!(parse_guild_ids("42,abc").is_err());
+        assert!(parse_guild_ids("0").is_err());
+    }
+}
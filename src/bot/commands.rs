@@ -0,0 +1,154 @@
+use super::constants::{DEFAULT_STATS_DAYS, MIN_STATS_SAMPLE};
+use super::results_store::{FactionStats, unix_now};
+use super::setup::Data;
+use super::user_message::UserMessage;
+use super::winner_template::WinnerTemplate;
+use poise::serenity_prelude as serenity;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+type Context<'a> = poise::Context<'a, Data, Error>;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Faction pick and win rates over the games recently parsed in this server
+#[poise::command(slash_command, guild_only, default_member_permissions = "MANAGE_GUILD")]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "Days to look back (default 30)"]
+    #[min = 1]
+    #[max = 365]
+    days: Option<u32>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let locale = data
+        .locale_for(ctx.serenity_context(), ctx.guild_id())
+        .await;
+    let Some(store) = data.results_store.clone() else {
+        ctx.say(UserMessage::stats_disabled().render(locale))
+            .await?;
+        return Ok(());
+    };
+
+    let days = days.unwrap_or(DEFAULT_STATS_DAYS);
+    let since = unix_now().saturating_sub(u64::from(days) * SECS_PER_DAY);
+    let records = match tokio::task::spawn_blocking(move || store.load_since(since)).await {
+        Ok(Ok(records)) => records,
+        Ok(Err(e)) => {
+            tracing::error!("Failed to read results log: {}", e);
+            ctx.say(UserMessage::internal_error().render(locale))
+                .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            tracing::error!("Results log task failed: {}", e);
+            ctx.say(UserMessage::internal_error().render(locale))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let guild_id = ctx.guild_id().map(|id| id.get());
+    let stats = FactionStats::collect(records.iter().filter(|r| r.guild_id == guild_id));
+    let reply = if stats.games == 0 {
+        UserMessage::stats_no_games(days)
+    } else {
+        UserMessage::faction_stats(stats, days, MIN_STATS_SAMPLE)
+    };
+    ctx.say(reply.render(locale)).await?;
+    Ok(())
+}
+
+/// Bot settings for this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    subcommands("winner_template"),
+    subcommand_required
+)]
+pub async fn config(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// The winner line of result embeds
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "winner-template",
+    subcommands(
+        "set_winner_template",
+        "reset_winner_template",
+        "preview_winner_template"
+    ),
+    subcommand_required
+)]
+async fn winner_template(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Use a custom winner line in this server's result embeds
+#[poise::command(slash_command, guild_only, rename = "set")]
+async fn set_winner_template(
+    ctx: Context<'_>,
+    #[description = "e.g. Zafer: {side}! 🏆 — also {players}, {duration} and {map}"]
+    template: String,
+) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let data = ctx.data();
+    let locale = data
+        .locale_for(ctx.serenity_context(), Some(guild_id))
+        .await;
+    let reply = match WinnerTemplate::parse(&template) {
+        Ok(template) => {
+            let preview = template.preview(locale);
+            data.winner_template_overrides.insert(guild_id, template);
+            UserMessage::winner_template_set(preview)
+        }
+        Err(reason) => UserMessage::winner_template_invalid(reason),
+    };
+    say_quietly(ctx, reply.render(locale)).await
+}
+
+/// Go back to the bot's configured winner line
+#[poise::command(slash_command, guild_only, rename = "reset")]
+async fn reset_winner_template(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let data = ctx.data();
+    let locale = data
+        .locale_for(ctx.serenity_context(), Some(guild_id))
+        .await;
+    data.winner_template_overrides
+        .write(|overrides| overrides.remove(&guild_id));
+    say_quietly(ctx, UserMessage::winner_template_reset().render(locale)).await
+}
+
+/// Show this server's winner line on a sample game
+#[poise::command(slash_command, guild_only, rename = "preview")]
+async fn preview_winner_template(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let locale = data
+        .locale_for(ctx.serenity_context(), ctx.guild_id())
+        .await;
+    let reply = match data.winner_template_for(ctx.guild_id()) {
+        Some(template) => UserMessage::winner_template_preview(template.preview(locale)),
+        None => UserMessage::no_winner_template(),
+    };
+    say_quietly(ctx, reply.render(locale)).await
+}
+
+/// Reply visible only to the caller, with mentions in the text left inert
+/// (templates are free text)
+async fn say_quietly(ctx: Context<'_>, content: String) -> Result<(), Error> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
@@ -10,12 +10,116 @@ const _: () = assert!(BATCH_SIZE <= BOT_MAX_ATTACHMENTS);
 /// Max pending pagination entries across all channels
 pub const MAX_PENDING_ENTRIES: usize = 50;
 
+/// Message links followed per message
+pub const MAX_MESSAGE_LINKS: usize = 3;
+
 /// Per-channel cooldown in seconds
 pub const COOLDOWN_SECS: u64 = 2;
 
 /// Pending entry expiry in seconds
 pub const PENDING_EXPIRY_SECS: u64 = 900;
 
+/// Seconds a posted message is remembered, so an edit adding replays is noticed
+pub const SEEN_MESSAGE_TTL_SECS: u64 = 900;
+
+/// Seconds a posted result remembers who asked for it, for reaction deletes
+pub const SENT_RESULT_TTL_SECS: u64 = 86_400;
+
+/// Seconds a single result's player buttons keep answering
+pub const PLAYER_DETAIL_TTL_SECS: u64 = 3600;
+
+/// Max results with player buttons remembered at once
+pub const MAX_PLAYER_DETAIL_ENTRIES: usize = 200;
+
+/// Characters of a player name shown on their button
+pub const PLAYER_BUTTON_LABEL_CHARS: usize = 20;
+
+/// Seconds a single result keeps its replay file for re-rendering, unless
+/// `REPLAY_RETENTION_HOURS` sets another period
+pub const DEFAULT_REPLAY_RETENTION_SECS: u64 = 86_400;
+
+/// Total bytes of replay files kept for re-rendering
+pub const MAX_POSTED_REPLAY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Reaction that renders a result again with the current renderer
+pub const RERENDER_REACTION: &str = "🔁";
+
+/// Time a single replay's chunk walk may take before it is cut short
+pub const SINGLE_PARSE_BUDGET_MS: u64 = 3000;
+
+/// Time each replay's chunk walk may take inside a batch
+pub const BATCH_PARSE_BUDGET_MS: u64 = 1500;
+
+/// Reaction that deletes a result unless `DELETE_REACTION` names another
+pub const DEFAULT_DELETE_REACTION: &str = "🗑️";
+
+/// Age in seconds after which a leftover extraction temp dir is removed
+pub const TEMP_DIR_MAX_AGE_SECS: u64 = 600;
+
+/// Seconds between sweeps for leftover extraction temp dirs
+pub const TEMP_DIR_SWEEP_INTERVAL_SECS: u64 = 60;
+
+/// Batch renders (archives, several replays, "Show more" pages) run at the same time
+pub const MAX_CONCURRENT_ARCHIVES: usize = 2;
+
+/// Batch work waiting for a worker before more is turned away
+pub const WORK_QUEUE_CAPACITY: usize = 10;
+
+/// Seconds an upload may wait in the work queue before it is dropped
+pub const WORK_QUEUE_MAX_WAIT_SECS: u64 = 300;
+
+/// Seconds between checks for uploads that waited too long
+pub const WORK_QUEUE_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// Games shorter than this (seconds) count as a possible restart stub
+pub const RESTART_MAX_STUB_SECS: u32 = 60;
+
+/// Max seconds between a stub's start and its rehost's start
+pub const RESTART_WINDOW_SECS: u32 = 600;
+
+/// Parsed games an archive needs before the "stats" flag adds a stats image
+pub const MIN_STATS_GAMES: usize = 10;
+
+/// Attachment name of the chunk dump sent for the "debug" flag
+pub const CHUNK_DUMP_FILENAME: &str = "chunks.csv.gz";
+
+/// Size at which the results log is rotated to `<path>.1`
+pub const RESULTS_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Days `/stats` looks back when none are given
+pub const DEFAULT_STATS_DAYS: u32 = 30;
+
+/// Decided games below which a faction's win rate is marked as a small sample
+pub const MIN_STATS_SAMPLE: usize = 10;
+
+/// Retries after the first attempt for failed Discord sends
+pub const SEND_MAX_RETRIES: u32 = 3;
+
+/// Initial backoff between send retries in milliseconds (doubles each retry)
+pub const SEND_RETRY_BASE_DELAY_MS: u64 = 500;
+
+/// Upper bound on a single send retry backoff in milliseconds
+pub const SEND_RETRY_MAX_DELAY_MS: u64 = 8000;
+
+/// Initial wait before rebuilding the client after the gateway gave up, in
+/// seconds (doubles each failed restart)
+pub const GATEWAY_RESTART_BASE_DELAY_SECS: u64 = 5;
+
+/// Upper bound on the wait between client restarts, in seconds
+pub const GATEWAY_RESTART_MAX_DELAY_SECS: u64 = 300;
+
+/// A client that ran this long before failing starts the backoff over, in seconds
+pub const GATEWAY_STABLE_RUN_SECS: u64 = 600;
+
+/// Seconds between two admin webhook reports of the same kind of failure
+pub const FAILURE_REPORT_INTERVAL_SECS: u64 = 600;
+
+/// Backtrace lines kept in a panic's failure report
+pub const FAILURE_REPORT_BACKTRACE_LINES: usize = 20;
+
+/// Discord's limit on an attachment description (alt text)
+pub const ATTACHMENT_DESCRIPTION_MAX_CHARS: usize = 1024;
+
 /// Safe content limit (room for truncation suffix, under Discord's 2000 char limit)
 pub const CONTENT_SAFE_LIMIT: usize = 1900;
 
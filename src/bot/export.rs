@@ -0,0 +1,332 @@
+use crate::models::{ReplayError, ReplayInfo, format_clock};
+use crate::renderer::{ArchiveStats, base64, display_filename, escape_markup};
+use image::imageops::FilterType;
+use std::fmt::Write;
+
+use super::i18n::Locale;
+use super::user_message::UserMessage;
+
+/// Filename the report is attached as
+pub const REPORT_FILENAME: &str = "report.html";
+
+/// Games that get a thumbnail; later games are listed in the table only
+pub const MAX_REPORT_THUMBNAILS: usize = 30;
+
+/// Thumbnail widths tried, in order, until the report fits its budget
+const THUMBNAIL_WIDTHS: [u32; 3] = [480, 320, 200];
+
+/// JPEG quality of the thumbnails
+const THUMBNAIL_QUALITY: u8 = 70;
+
+const STYLE: &str = "body{font-family:sans-serif;background:#1c1e24;color:#ebebeb;margin:2em}\
+table{border-collapse:collapse}th,td{border:1px solid #444;padding:4px 8px;text-align:left}\
+.games{display:flex;flex-wrap:wrap;gap:1em}.game{background:#2a2d35;padding:1em;width:480px}\
+.game img{max-width:100%}.error{color:#e0a0a0}";
+
+/// Players of a game as "Name (Faction)", grouped by team
+fn player_list(replay: &ReplayInfo) -> String {
+    let mut players: Vec<_> = replay.players.iter().collect();
+    players.sort_by_key(|p| (p.team, p.slot));
+    players
+        .iter()
+        .map(|p| match p.powers_text(usize::MAX) {
+            Some(powers) => format!(
+                "{} ({}; {})",
+                escape_markup(&p.name),
+                p.display_faction(),
+                escape_markup(&powers)
+            ),
+            None => format!("{} ({})", escape_markup(&p.name), p.display_faction()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// One self-contained HTML document for an archive: a summary table, one
+/// card per game (with its thumbnail from `images`, matched by filename, if
+/// any) and the archive's aggregate stats
+pub fn generate_html_report(
+    games: &[(String, Result<ReplayInfo, ReplayError>)],
+    images: &[(String, Vec<u8>)],
+) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Replay report</title>\n<style>{}</style>\n</head>\n<body>\n\
+         <h1>Replay report</h1>\n",
+        STYLE
+    );
+
+    html.push_str("<section id=\"summary\">\n<h2>Games</h2>\n<table>\n");
+    html.push_str(
+        "<thead><tr><th>#</th><th>File</th><th>Date</th><th>Duration</th>\
+         <th>Players</th><th>Winner</th></tr></thead>\n<tbody>\n",
+    );
+    for (i, (name, result)) in games.iter().enumerate() {
+        let name = escape_markup(&display_filename(name));
+        match result {
+            Ok(replay) => {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    i + 1,
+                    name,
+                    escape_markup(&replay.start_date_formatted()),
+                    escape_markup(&replay.duration_formatted()),
+                    player_list(replay),
+                    escape_markup(replay.winner.display_text()),
+                );
+            }
+            Err(e) => {
+                let reason = UserMessage::for_replay_error(e).render(Locale::En);
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td class=\"error\" colspan=\"4\">{}</td></tr>",
+                    i + 1,
+                    name,
+                    escape_markup(&reason),
+                );
+            }
+        }
+    }
+    html.push_str("</tbody>\n</table>\n</section>\n");
+
+    html.push_str("<section id=\"games\">\n<h2>Maps</h2>\n<div class=\"games\">\n");
+    for (i, (name, result)) in games.iter().enumerate() {
+        let Ok(replay) = result else {
+            continue;
+        };
+        let _ = write!(
+            html,
+            "<article class=\"game\">\n<h3>{}. {}</h3>\n",
+            i + 1,
+            escape_markup(&display_filename(name))
+        );
+        if let Some((_, jpeg)) = images.iter().find(|(image_name, _)| image_name == name) {
+            let _ = writeln!(
+                html,
+                "<img alt=\"{}\" src=\"data:image/jpeg;base64,{}\">",
+                escape_markup(name),
+                base64(jpeg)
+            );
+        }
+        let _ = write!(
+            html,
+            "<p>Winner: {}</p>\n<p>{}</p>\n</article>\n",
+            escape_markup(replay.winner.display_text()),
+            player_list(replay)
+        );
+    }
+    html.push_str("</div>\n</section>\n");
+
+    let parsed: Vec<ReplayInfo> = games
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok().cloned())
+        .collect();
+    let stats = ArchiveStats::collect(&parsed);
+    html.push_str("<section id=\"stats\">\n<h2>Stats</h2>\n<ul>\n");
+    let _ = writeln!(html, "<li>Games: {}</li>", stats.games);
+    if let Some(secs) = stats.average_duration_secs {
+        let _ = writeln!(html, "<li>Average duration: {}</li>", format_clock(secs));
+    }
+    for (label, count) in stats.win_counts() {
+        let _ = writeln!(html, "<li>{}: {}</li>", label, count);
+    }
+    html.push_str(
+        "</ul>\n<table>\n<thead><tr><th>Faction</th><th>Picks</th></tr></thead>\n<tbody>\n",
+    );
+    for (faction, count) in stats.faction_counts() {
+        let _ = writeln!(html, "<tr><td>{}</td><td>{}</td></tr>", faction, count);
+    }
+    html.push_str("</tbody>\n</table>\n</section>\n</body>\n</html>\n");
+    html
+}
+
+/// Downscale a JPEG to `width` pixels wide, keeping its aspect ratio
+fn thumbnail(jpeg: &[u8], width: u32) -> Option<Vec<u8>> {
+    let img = image::load_from_memory(jpeg).ok()?.to_rgb8();
+    let height = (img.height() as u64 * width as u64 / img.width().max(1) as u64).max(1) as u32;
+    let small = image::imageops::resize(&img, width, height, FilterType::Triangle);
+    let mut buffer = Vec::new();
+    let encoder =
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, THUMBNAIL_QUALITY);
+    small.write_with_encoder(encoder).ok()?;
+    Some(buffer)
+}
+
+/// The report for `games` within `max_bytes`: thumbnails of the first
+/// [`MAX_REPORT_THUMBNAILS`] `images` are shrunk until it fits, then dropped.
+/// `None` when even the text alone is too large.
+pub fn build_html_report(
+    games: &[(String, Result<ReplayInfo, ReplayError>)],
+    images: &[(String, Vec<u8>)],
+    max_bytes: usize,
+) -> Option<String> {
+    let images = &images[..images.len().min(MAX_REPORT_THUMBNAILS)];
+    for width in THUMBNAIL_WIDTHS {
+        let thumbnails: Vec<(String, Vec<u8>)> = images
+            .iter()
+            .filter_map(|(name, jpeg)| Some((name.clone(), thumbnail(jpeg, width)?)))
+            .collect();
+        let html = generate_html_report(games, &thumbnails);
+        if html.len() <= max_bytes {
+            return Some(html);
+        }
+        tracing::info!(
+            "Report with {}px thumbnails is {} bytes, over {}",
+            width,
+            html.len(),
+            max_bytes
+        );
+    }
+    let html = generate_html_report(games, &[]);
+    (html.len() <= max_bytes).then_some(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, PlayerBuilder, Winner};
+    use image::{Rgb, RgbImage};
+
+    fn game(names: &[&str], winner: Winner) -> ReplayInfo {
+        let players = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                PlayerBuilder::test_player(name, i as i8 % 2 + 1, i as u8, Faction::Mordor)
+            })
+            .collect();
+        ReplayInfo::new("map wor rhun".to_string(), players)
+            .with_times(1_700_000_000, 1_700_000_900)
+            .with_winner(winner)
+    }
+
+    fn games() -> Vec<(String, Result<ReplayInfo, ReplayError>)> {
+        vec![
+            (
+                "final.BfME2Replay".to_string(),
+                Ok(game(&["<b>Bob</b>", "Tom & Jerry"], Winner::LeftTeam)),
+            ),
+            (
+                "broken.BfME2Replay".to_string(),
+                Err(ReplayError::NoPlayers),
+            ),
+            (
+                "semi.BfME2Replay".to_string(),
+                Ok(game(&["Ann", "Eve"], Winner::LikelyRightTeam)),
+            ),
+        ]
+    }
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let img = RgbImage::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 90])
+        });
+        let mut buffer = Vec::new();
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, 95);
+        img.write_with_encoder(encoder).unwrap();
+        buffer
+    }
+
+    /// Open/close tags of the elements the report emits, checked as a stack
+    fn assert_balanced(html: &str) {
+        const CHECKED: [&str; 17] = [
+            "html", "head", "title", "style", "body", "h1", "h2", "h3", "section", "div",
+            "article", "table", "thead", "tbody", "tr", "ul", "li",
+        ];
+        let mut stack: Vec<&str> = Vec::new();
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            let end = start + rest[start..].find('>').expect("unterminated tag");
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            let (closing, tag) = match tag.strip_prefix('/') {
+                Some(tag) => (true, tag),
+                None => (false, tag),
+            };
+            let name = tag.split([' ', '\n']).next().unwrap_or("");
+            if !CHECKED.contains(&name) {
+                continue;
+            }
+            if closing {
+                assert_eq!(stack.pop(), Some(name), "unexpected </{}>", name);
+            } else {
+                stack.push(name);
+            }
+        }
+        assert!(stack.is_empty(), "unclosed {:?}", stack);
+    }
+
+    #[test]
+    fn report_lists_every_game_and_the_stats() {
+        let html = generate_html_report(&games(), &[]);
+        assert_balanced(&html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert_eq!(html.matches("<tr><td>").count(), 3 + 8);
+        assert!(html.contains("<td>1</td><td>final</td>"));
+        assert!(html.contains("<td>2</td><td>broken</td><td class=\"error\" colspan=\"4\">"));
+        assert!(html.contains("Right Team (likely)"));
+        // Only parsed games get a card
+        assert_eq!(html.matches("<article").count(), 2);
+        assert!(html.contains("<li>Games: 2</li>"));
+        assert!(html.contains("<li>Left: 1</li>"));
+        assert!(html.contains("<tr><td>Mordor</td><td>4</td></tr>"));
+    }
+
+    #[test]
+    fn player_names_are_escaped() {
+        let html = generate_html_report(&games(), &[]);
+        assert!(html.contains("&lt;b&gt;Bob&lt;/b&gt; (Mordor)"));
+        assert!(html.contains("Tom &amp; Jerry"));
+        assert!(!html.contains("<b>"));
+        assert_eq!(escape_markup("a\"b'c"), "a&quot;b&#39;c");
+    }
+
+    #[test]
+    fn every_power_is_listed_with_its_player() {
+        let mut replay = game(&["Ann", "Eve"], Winner::LeftTeam);
+        replay.players[1].powers_used = vec![
+            ("Heal".to_string(), 60),
+            ("Darkness".to_string(), 540),
+            ("Balrog".to_string(), 1270),
+        ];
+        let games = vec![("p.BfME2Replay".to_string(), Ok(replay))];
+        let html = generate_html_report(&games, &[]);
+        assert!(html.contains("Eve (Mordor; Heal 1:00, Darkness 9:00, Balrog 21:10)"));
+        assert!(html.contains("Ann (Mordor)"));
+    }
+
+    #[test]
+    fn thumbnails_are_embedded_and_shrunk_to_fit() {
+        let images = vec![("final.BfME2Replay".to_string(), jpeg(1600, 1600))];
+        let roomy = build_html_report(&games(), &images, 8 * 1024 * 1024).unwrap();
+        assert_balanced(&roomy);
+        assert_eq!(roomy.matches("src=\"data:image/jpeg;base64,").count(), 1);
+
+        let text_only = generate_html_report(&games(), &[]).len();
+        let small = generate_html_report(
+            &games(),
+            &[(
+                "final.BfME2Replay".to_string(),
+                thumbnail(&images[0].1, 200).unwrap(),
+            )],
+        )
+        .len();
+        let tight = build_html_report(&games(), &images, small).unwrap();
+        assert!(tight.len() <= small && tight.len() < roomy.len());
+        assert!(tight.contains("data:image/jpeg"));
+
+        let bare = build_html_report(&games(), &images, text_only).unwrap();
+        assert!(!bare.contains("<img"));
+        assert_eq!(build_html_report(&games(), &images, text_only - 1), None);
+    }
+
+    #[test]
+    fn thumbnail_keeps_the_aspect_ratio() {
+        let small = thumbnail(&jpeg(800, 400), 200).unwrap();
+        let img = image::load_from_memory(&small).unwrap();
+        assert_eq!((img.width(), img.height()), (200, 100));
+    }
+}
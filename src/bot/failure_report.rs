@@ -0,0 +1,349 @@
+//! Reports of the bot's own failures (panicking parse/render tasks, failed
+//! renders) to an admin webhook, so they are noticed before users complain
+
+use poise::serenity_prelude as serenity;
+use serenity::{ChannelId, GuildId, WebhookId};
+use std::backtrace::Backtrace;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::constants::{
+    CONTENT_SAFE_LIMIT, FAILURE_REPORT_BACKTRACE_LINES, FAILURE_REPORT_INTERVAL_SECS,
+};
+use super::shared_map::{PoisonPolicy, SharedMap};
+
+/// What went wrong; reports are rate-limited per kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// A parse/render task panicked
+    Panic,
+    /// A parsed replay could not be rendered
+    Render,
+}
+
+impl FailureKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::Panic => "panic",
+            FailureKind::Render => "render",
+        }
+    }
+}
+
+/// Where a failed replay was posted
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FailureSite {
+    pub guild_id: Option<GuildId>,
+    pub channel_id: Option<ChannelId>,
+}
+
+impl FailureSite {
+    pub fn new(guild_id: Option<GuildId>, channel_id: ChannelId) -> Self {
+        Self {
+            guild_id,
+            channel_id: Some(channel_id),
+        }
+    }
+}
+
+/// One failure to report
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub kind: FailureKind,
+    /// Hash of the replay's filename (names often carry player names);
+    /// `None` when the failed task could not be told apart
+    pub filename_hash: Option<String>,
+    pub site: FailureSite,
+    /// Time from starting the replay until it failed
+    pub elapsed: Duration,
+    /// Error text, or panic message and backtrace
+    pub detail: String,
+}
+
+/// Admin webhook from `ADMIN_WEBHOOK_URL`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminWebhook {
+    id: WebhookId,
+    token: String,
+}
+
+impl AdminWebhook {
+    /// Parse a Discord webhook URL (`https://discord.com/api/webhooks/<id>/<token>`)
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let url = url.trim();
+        let rest = url
+            .strip_prefix("https://")
+            .and_then(|rest| rest.split_once("/api/webhooks/"))
+            .filter(|(host, _)| {
+                [
+                    "discord.com",
+                    "discordapp.com",
+                    "canary.discord.com",
+                    "ptb.discord.com",
+                ]
+                .contains(&host.to_ascii_lowercase().as_str())
+            })
+            .map(|(_, rest)| rest)
+            .ok_or_else(|| format!("'{}' is not a Discord webhook URL", url))?;
+        let (id, token) = rest
+            .trim_end_matches('/')
+            .split_once('/')
+            .ok_or("missing webhook token")?;
+        let id = id
+            .parse::<u64>()
+            .ok()
+            .filter(|&id| id != 0)
+            .ok_or_else(|| format!("'{}' is not a webhook id", id))?;
+        if token.is_empty() || token.contains('/') {
+            return Err("missing webhook token".to_string());
+        }
+        Ok(Self {
+            id: WebhookId::new(id),
+            token: token.to_string(),
+        })
+    }
+}
+
+/// Sends failure reports to the admin webhook, at most one per kind every
+/// ten minutes. Without a webhook, failures are only logged.
+pub struct FailureReporter {
+    webhook: Option<AdminWebhook>,
+    /// Webhook executions need no bot token
+    http: Arc<serenity::Http>,
+    /// On poison: recover (a lost timestamp allows one extra report)
+    last_sent: SharedMap<FailureKind, Instant>,
+    interval: Duration,
+}
+
+impl FailureReporter {
+    pub fn new(webhook: Option<AdminWebhook>) -> Self {
+        Self {
+            webhook,
+            http: Arc::new(serenity::Http::new("")),
+            last_sent: SharedMap::new("Failure reports", PoisonPolicy::Recover),
+            interval: Duration::from_secs(FAILURE_REPORT_INTERVAL_SECS),
+        }
+    }
+
+    /// Whether a failure of `kind` at `now` may be reported; records it if so
+    fn admit(&self, kind: FailureKind, now: Instant) -> bool {
+        self.last_sent.write(|map| {
+            let due = map
+                .get(&kind)
+                .is_none_or(|last| now.saturating_duration_since(*last) >= self.interval);
+            if due {
+                map.insert(kind, now);
+            }
+            due
+        })
+    }
+
+    /// Post `failure` to the admin webhook in the background, unless one of
+    /// its kind was reported recently
+    pub fn report(&self, failure: Failure) {
+        let Some(webhook) = self.webhook.clone() else {
+            return;
+        };
+        if !self.admit(failure.kind, Instant::now()) {
+            tracing::debug!(
+                "Skipping {} failure report (rate-limited)",
+                failure.kind.as_str()
+            );
+            return;
+        }
+        let http = self.http.clone();
+        let body = serde_json::json!({
+            "content": format_report(&failure),
+            "allowed_mentions": { "parse": [] },
+        });
+        tokio::spawn(async move {
+            if let Err(e) = http
+                .execute_webhook(webhook.id, None, &webhook.token, false, vec![], &body)
+                .await
+            {
+                tracing::warn!("Failed to send failure report: {}", e);
+            }
+        });
+    }
+}
+
+impl Default for FailureReporter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// The webhook message for a failure, within Discord's content limit
+pub fn format_report(failure: &Failure) -> String {
+    let id = |id: Option<u64>| id.map_or("-".to_string(), |id| id.to_string());
+    let header = format!(
+        "**Processing failure: {}**\nfile: {} · guild: {} · channel: {} · after {} ms\n",
+        failure.kind.as_str(),
+        failure.filename_hash.as_deref().unwrap_or("-"),
+        id(failure.site.guild_id.map(|g| g.get())),
+        id(failure.site.channel_id.map(|c| c.get())),
+        failure.elapsed.as_millis(),
+    );
+    // The detail goes in a code block, cut to fit after the header
+    let fence = "```";
+    let room = CONTENT_SAFE_LIMIT.saturating_sub(header.chars().count() + 2 * fence.len() + 2);
+    let detail = failure.detail.replace(fence, "'''");
+    let detail: String = if detail.chars().count() > room {
+        let mut cut: String = detail.chars().take(room.saturating_sub(1)).collect();
+        cut.push('…');
+        cut
+    } else {
+        detail
+    };
+    format!("{}{}\n{}\n{}", header, fence, detail, fence)
+}
+
+/// Backtrace of the latest panic, kept by the hook from [`install_panic_hook`]
+static LAST_BACKTRACE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Keep the backtrace of every panic for [`panic_detail`], then run the
+/// previous hook (which prints the panic as before)
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let backtrace = Backtrace::force_capture().to_string();
+        *LAST_BACKTRACE.lock().unwrap_or_else(|e| e.into_inner()) = Some(backtrace);
+        previous(info);
+    }));
+}
+
+/// Panic message of a failed blocking task, with the start of the latest
+/// panic's backtrace when the panic hook caught one
+pub fn panic_detail(error: tokio::task::JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+    let payload = error.into_panic();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic with a non-string payload".to_string());
+    let backtrace = LAST_BACKTRACE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    with_backtrace(&message, backtrace.as_deref())
+}
+
+/// `message`, then the first lines of `backtrace`
+fn with_backtrace(message: &str, backtrace: Option<&str>) -> String {
+    let Some(backtrace) = backtrace else {
+        return message.to_string();
+    };
+    let lines: Vec<&str> = backtrace.lines().collect();
+    let mut detail = format!("{}\n", message);
+    for line in lines.iter().take(FAILURE_REPORT_BACKTRACE_LINES) {
+        detail.push_str(line);
+        detail.push('\n');
+    }
+    if lines.len() > FAILURE_REPORT_BACKTRACE_LINES {
+        detail.push_str(&format!(
+            "(+{} more lines)",
+            lines.len() - FAILURE_REPORT_BACKTRACE_LINES
+        ));
+    }
+    detail.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(kind: FailureKind, detail: &str) -> Failure {
+        Failure {
+            kind,
+            filename_hash: Some("00ff00ff00ff00ff".to_string()),
+            site: FailureSite::new(Some(GuildId::new(11)), ChannelId::new(22)),
+            elapsed: Duration::from_millis(1234),
+            detail: detail.to_string(),
+        }
+    }
+
+    #[test]
+    fn reports_are_limited_per_kind() {
+        let reporter = FailureReporter::default();
+        let start = Instant::now();
+        assert!(reporter.admit(FailureKind::Panic, start));
+        // Another kind has its own limit
+        assert!(reporter.admit(FailureKind::Render, start));
+        assert!(!reporter.admit(FailureKind::Panic, start + Duration::from_secs(60)));
+        assert!(!reporter.admit(FailureKind::Panic, start + Duration::from_secs(599)));
+        assert!(reporter.admit(FailureKind::Panic, start + Duration::from_secs(600)));
+        // The window restarts at the last report
+        assert!(!reporter.admit(FailureKind::Panic, start + Duration::from_secs(900)));
+        assert!(!reporter.admit(FailureKind::Render, start + Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn report_carries_kind_site_and_detail() {
+        let report = format_report(&failure(FailureKind::Panic, "index out of bounds"));
+        assert_eq!(
+            report,
+            "**Processing failure: panic**\n\
+             file: 00ff00ff00ff00ff · guild: 11 · channel: 22 · after 1234 ms\n\
+             ```\nindex out of bounds\n```"
+        );
+
+        let unknown = Failure {
+            filename_hash: None,
+            site: FailureSite::default(),
+            ..failure(FailureKind::Render, "Render error: bad map")
+        };
+        let report = format_report(&unknown);
+        assert!(
+            report.starts_with("**Processing failure: render**\nfile: - · guild: - · channel: -")
+        );
+    }
+
+    #[test]
+    fn long_details_are_cut_to_fit() {
+        let report = format_report(&failure(FailureKind::Panic, &"x".repeat(5000)));
+        assert!(report.chars().count() <= CONTENT_SAFE_LIMIT);
+        assert!(report.ends_with("…\n```"));
+        // A fence inside the detail can't end the code block early
+        let report = format_report(&failure(FailureKind::Panic, "a ``` b"));
+        assert_eq!(report.matches("```").count(), 2);
+    }
+
+    #[test]
+    fn backtraces_are_truncated() {
+        let backtrace: Vec<String> = (0..40).map(|i| format!("  {}: frame", i)).collect();
+        let detail = with_backtrace("boom", Some(&backtrace.join("\n")));
+        let lines: Vec<&str> = detail.lines().collect();
+        assert_eq!(lines[0], "boom");
+        assert_eq!(lines.len(), FAILURE_REPORT_BACKTRACE_LINES + 2);
+        assert_eq!(lines.last(), Some(&"(+20 more lines)"));
+        assert_eq!(with_backtrace("boom", None), "boom");
+    }
+
+    #[tokio::test]
+    async fn panic_payloads_are_captured() {
+        let error = tokio::task::spawn_blocking(|| panic!("chunk {} out of range", 7))
+            .await
+            .unwrap_err();
+        assert!(panic_detail(error).starts_with("chunk 7 out of range"));
+        let error = tokio::task::spawn_blocking(|| std::panic::panic_any(42))
+            .await
+            .unwrap_err();
+        assert!(panic_detail(error).starts_with("panic with a non-string payload"));
+    }
+
+    #[test]
+    fn webhook_urls_are_validated() {
+        let webhook =
+            AdminWebhook::parse("https://discord.com/api/webhooks/1234/abc-DEF_g").unwrap();
+        assert_eq!(webhook.id, WebhookId::new(1234));
+        assert_eq!(webhook.token, "abc-DEF_g");
+        assert!(AdminWebhook::parse("https://ptb.discord.com/api/webhooks/1/t/").is_ok());
+        assert!(AdminWebhook::parse("http://discord.com/api/webhooks/1/t").is_err());
+        assert!(AdminWebhook::parse("https://example.com/api/webhooks/1/t").is_err());
+        assert!(AdminWebhook::parse("https://discord.com/api/webhooks/1").is_err());
+        assert!(AdminWebhook::parse("https://discord.com/api/webhooks/x/t").is_err());
+    }
+}
@@ -1,96 +1,629 @@
-use crate::models::ReplayError;
-use crate::parser::parse_replay;
-use crate::renderer::render_map;
+use crate::metrics;
+use crate::models::{
+    ReplayError, ReplayInfo, SpotRegions, TimingBreakdown, anonymize_view, content_hash,
+};
+use crate::parser::{ReplayParser, chunk_dump, preflight, split_games};
+use crate::renderer::{
+    FactionIcons, MapLayout, RenderAssets, RenderOptions, render_archive_stats, render_map_timed,
+    render_reveal, shown_replay,
+};
+use ab_glyph::FontArc;
+use image::RgbImage;
 use poise::serenity_prelude as serenity;
-use serenity::CreateAttachment;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use super::archive::{extract_replays_from_rar, extract_replays_from_zip};
-use super::constants::BATCH_SIZE;
+use super::archive::{PreparsedReplays, date_separators};
+use super::archive_stream::extract_and_preparse;
+use super::constants::{
+    BATCH_PARSE_BUDGET_MS, BATCH_SIZE, CHUNK_DUMP_FILENAME, MAX_MESSAGE_LINKS, MIN_STATS_GAMES,
+    SINGLE_PARSE_BUDGET_MS,
+};
+use super::export::{MAX_REPORT_THUMBNAILS, REPORT_FILENAME, build_html_report};
+use super::failure_report::{Failure, FailureKind, FailureReporter, FailureSite, panic_detail};
+use super::i18n::Locale;
+use super::message_link::{fetch_linked_message, message_links};
 use super::messages::{
-    BatchMessageArgs, send_batch_message, send_replay_image, send_simple_message,
+    BatchMessageArgs, EMBED_IMAGE_NAME, ReplyStyle, build_result_embed, delete_replies,
+    edit_simple_message, replace_replay_image, send_batch_message, send_replay_image,
+    send_simple_message, summarize_replay,
+};
+use super::origin::{Author, Origin, classify_origin};
+use super::pagination::{set_button_message, spawn_prefetch};
+use super::player_details::player_buttons;
+use super::posted_replays::{PostedReplay, is_rerender_reaction};
+use super::relevance::{
+    ArchiveKind, AttachmentClass, FileKind, Venue, classify, message_has_relevant,
 };
+use super::results_store::GameResult;
+use super::sent_results::may_delete;
 use super::setup::{Data, PendingReplays, cleanup_expired_pending_inner};
-
-const MAX_SINGLE_REPLAY_BYTES: u64 = 5 * 1024 * 1024; // 5MB
-const MAX_ARCHIVE_BYTES: u64 = 25 * 1024 * 1024; // 25MB
+use super::tally::ArchiveTally;
+use super::uploads::UploadFile;
+use super::user_message::UserMessage;
+use super::work_queue::{Admission, Turn, WorkPermit};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
 /// Handle incoming messages with replay attachments
+#[tracing::instrument(
+    name = "message",
+    skip_all,
+    fields(
+        message_id = %new_message.id,
+        channel_id = %new_message.channel_id,
+        author = %hex_hash(&new_message.author.id.get().to_le_bytes()),
+    )
+)]
 pub async fn handle_message(
     ctx: &serenity::Context,
     new_message: &serenity::Message,
     data: &Data,
 ) -> Result<(), Error> {
-    // Ignore bot messages
-    if new_message.author.bot {
+    // Ignore bot messages (allowlisted webhooks may bridge uploads)
+    let poster = Author::of_message(new_message);
+    if classify_origin(poster, None, &data.allowed_webhooks) == Origin::Rejected {
+        return Ok(());
+    }
+
+    // Outside the guild's bot channels (forwards included), stay silent
+    if !data
+        .channel_scopes
+        .allows(new_message.guild_id, new_message.channel_id)
+    {
         return Ok(());
     }
+    data.seen_messages
+        .observe(new_message.id, new_message.attachments.len());
 
-    // Collect attachments: from this message, replied-to message, or forwarded message.
+    // Collect attachments: from this message, replied-to message, or forwarded
+    // message, noting which message they came from.
     let mut is_forwarded = false;
-    let attachments = if !new_message.attachments.is_empty() {
-        new_message.attachments.clone()
+    let (attachments, source) = if !new_message.attachments.is_empty() {
+        (new_message.attachments.clone(), AttachmentSource::Own)
     } else if let Some(ref replied) = new_message.referenced_message {
         if !replied.attachments.is_empty() {
-            replied.attachments.clone()
+            (
+                replied.attachments.clone(),
+                AttachmentSource::Reply(replied),
+            )
         } else if let Some(snapshot) = replied.message_snapshots.first() {
-            snapshot.attachments.clone()
+            (
+                snapshot.attachments.clone(),
+                AttachmentSource::Forward(replied),
+            )
         } else {
-            return Ok(());
+            return handle_message_links(ctx, new_message, data, poster).await;
         }
     } else if let Some(snapshot) = new_message.message_snapshots.first() {
         is_forwarded = true;
-        snapshot.attachments.clone()
+        (
+            snapshot.attachments.clone(),
+            AttachmentSource::Forward(new_message),
+        )
     } else {
-        return Ok(());
+        return handle_message_links(ctx, new_message, data, poster).await;
     };
 
     // Check if any attachment is relevant before doing mention check
-    let has_relevant = attachments.iter().any(|a| {
-        let f = a.filename.to_lowercase();
-        f.ends_with(".bfme2replay") || f.ends_with(".zip") || f.ends_with(".rar")
-    });
-    if !has_relevant {
+    let venue = Venue::of_message(new_message.guild_id);
+    if !message_has_relevant(&attachments, venue) {
+        return handle_message_links(ctx, new_message, data, poster).await;
+    }
+
+    // Only looked up once the attachments matter (a forward may need a fetch)
+    let original = match source {
+        AttachmentSource::Own => None,
+        AttachmentSource::Reply(replied) => Some(Author::of_message(replied)),
+        // A forward made by a bot is rejected as such, without a fetch
+        AttachmentSource::Forward(forward) => match Author::of_message(forward) {
+            forwarder
+                if classify_origin(forwarder, None, &data.allowed_webhooks) == Origin::Rejected =>
+            {
+                Some(forwarder)
+            }
+            _ => Some(forwarded_author(ctx, forward).await),
+        },
+    };
+    if classify_origin(poster, original, &data.allowed_webhooks) == Origin::Rejected {
+        tracing::debug!(msg_id = %new_message.id, "Ignoring attachments posted by a bot or webhook");
         return Ok(());
     }
 
-    // Forwarded messages can't contain @mentions, so auto-process them.
-    // All other messages require the bot to be @mentioned.
-    if !is_forwarded && !is_bot_mentioned(ctx, new_message, data.bot_id).await {
+    if mention_required(new_message.guild_id, is_forwarded)
+        && !is_bot_mentioned(ctx, new_message, data.bot_id).await
+    {
         return Ok(());
     }
 
-    // Per-channel cooldown
+    // Per-channel cooldown (a DM channel is per user, so DMs get a per-user cooldown)
     if data.check_cooldown(new_message.channel_id) {
         return Ok(());
     }
     data.set_cooldown(new_message.channel_id);
+    let locale = data.locale_for(ctx, new_message.guild_id).await;
+
+    data.in_flight.begin(new_message.id);
+    let name_filter = quoted_filter(&new_message.content);
+    process_attachments(
+        ctx,
+        new_message,
+        data,
+        locale,
+        &attachments,
+        venue,
+        name_filter.as_deref(),
+    )
+    .await;
+    data.in_flight.finish(new_message.id);
+
+    Ok(())
+}
+
+/// Handle an edit that attached replays after the message was posted: the
+/// edited message goes through the upload pipeline, mention check included
+pub async fn handle_message_update(
+    ctx: &serenity::Context,
+    event: &serenity::MessageUpdateEvent,
+    data: &Data,
+) -> Result<(), Error> {
+    // Absent when the edit left the attachments alone
+    let Some(attachments) = &event.attachments else {
+        return Ok(());
+    };
+    if !data.channel_scopes.allows(event.guild_id, event.channel_id) {
+        return Ok(());
+    }
+    let relevant = message_has_relevant(attachments, Venue::of_message(event.guild_id));
+    if !data
+        .seen_messages
+        .claim_edit(event.id, attachments.len(), relevant)
+    {
+        return Ok(());
+    }
+
+    // The update carries only the changed fields; the pipeline needs the message
+    let message = match event.channel_id.message(ctx, event.id).await {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::warn!(msg_id = %event.id, "Failed to fetch edited message: {}", e);
+            return Ok(());
+        }
+    };
+    tracing::info!(msg_id = %event.id, "Edit attached replays, processing");
+    handle_message(ctx, &message, data).await
+}
+
+/// Handle reactions on bot results: the delete reaction removes a result,
+/// and 🔁 renders a single result again with the current renderer
+pub async fn handle_reaction_add(
+    ctx: &serenity::Context,
+    reaction: &serenity::Reaction,
+    data: &Data,
+) {
+    let Some(reactor) = reaction.user_id else {
+        return;
+    };
+    if reactor == data.bot_id {
+        return;
+    }
+    if data.delete_reaction.matches(&reaction.emoji) {
+        delete_by_reaction(ctx, reaction, reactor, data).await;
+    } else if is_rerender_reaction(&reaction.emoji) {
+        rerender_by_reaction(ctx, reaction, reactor, data).await;
+    }
+}
+
+/// Remove a result when the reactor asked for it or may manage messages,
+/// along with the remaining pages of its "Show more" button
+async fn delete_by_reaction(
+    ctx: &serenity::Context,
+    reaction: &serenity::Reaction,
+    reactor: serenity::UserId,
+    data: &Data,
+) {
+    let requester = data.sent_results.requester(reaction.message_id);
+    // Authors are only sent for guild reactions; DM results are known by id
+    let bot_message = requester.is_some() || reaction.message_author_id == Some(data.bot_id);
+    if !bot_message {
+        return;
+    }
+    let manages_messages = requester != Some(reactor)
+        && can_manage_messages(ctx, reaction.guild_id, reactor, reaction.member.as_ref()).await;
+    if !may_delete(requester, reactor, manages_messages) {
+        tracing::debug!(msg_id = %reaction.message_id, "Delete reaction from {} ignored", reactor);
+        return;
+    }
+
+    if let Err(e) = reaction
+        .channel_id
+        .delete_message(ctx, reaction.message_id)
+        .await
+    {
+        tracing::warn!(msg_id = %reaction.message_id, "Failed to delete result: {}", e);
+        return;
+    }
+    tracing::info!(msg_id = %reaction.message_id, "Result deleted by reaction from {}", reactor);
+    data.posted_replays.forget(reaction.message_id);
+    if let Some(key) = data.sent_results.forget(reaction.message_id) {
+        data.pending_replays.write(|map| map.remove(&key));
+    }
+}
 
-    for (att_idx, attachment) in attachments.iter().enumerate() {
-        let filename_lower = attachment.filename.to_lowercase();
+/// Render a single result again from its stored replay file and swap the
+/// new image in. Allowed to the same people as deleting it: whoever asked
+/// for it, and anyone who may manage messages.
+async fn rerender_by_reaction(
+    ctx: &serenity::Context,
+    reaction: &serenity::Reaction,
+    reactor: serenity::UserId,
+    data: &Data,
+) {
+    // Only results whose replay file is still kept can be rendered again
+    let Some(posted) = data.posted_replays.get(reaction.message_id) else {
+        return;
+    };
+    let manages_messages = posted.requester != reactor
+        && can_manage_messages(ctx, reaction.guild_id, reactor, reaction.member.as_ref()).await;
+    if !may_delete(Some(posted.requester), reactor, manages_messages) {
+        tracing::debug!(msg_id = %reaction.message_id, "Re-render reaction from {} ignored", reactor);
+        return;
+    }
 
-        if filename_lower.ends_with(".bfme2replay") {
-            process_single_attachment(ctx, new_message, data, attachment).await;
-        } else if filename_lower.ends_with(".zip") || filename_lower.ends_with(".rar") {
-            process_archive_attachment(ctx, new_message, data, attachment, att_idx).await;
+    tracing::info!(msg_id = %reaction.message_id, "Re-rendering result for {}", reactor);
+    let flags = ReplyFlags {
+        reveal: posted.reveal,
+        debug: false,
+        anonymize: posted.anonymize,
+    };
+    let span = replay_span(&posted.filename, &posted.bytes);
+    let result = render_single(data, &posted.bytes, &posted.filename, flags, span.clone()).await;
+    let outcome = match &result {
+        Ok(result) => outcome_label(result),
+        Err(_) => "panicked",
+    };
+    finish_replay_span(&span, outcome);
+    let (replay, image_bytes) = match result {
+        Ok(Ok(rendered)) => rendered,
+        Ok(Err(e)) => {
+            tracing::warn!(msg_id = %reaction.message_id, "Failed to re-render result: {}", e);
+            return;
+        }
+        Err(e) => {
+            tracing::error!(msg_id = %reaction.message_id, "Re-render task failed: {}", panic_detail(e));
+            return;
         }
+    };
+
+    let locale = data.locale_for(ctx, reaction.guild_id).await;
+    let (replay, title) = shown_replay(&replay, &posted.filename, render_options(data, flags));
+    let shown = SingleResult::new(
+        data,
+        reaction.guild_id,
+        &replay,
+        &title,
+        image_bytes,
+        flags,
+        locale,
+    );
+    let shown_in = replace_replay_image(
+        ctx,
+        reaction.channel_id,
+        reaction.message_id,
+        shown.file,
+        shown.embed,
+        shown.note.as_ref(),
+        locale,
+    )
+    .await;
+    let Some(shown_in) = shown_in else {
+        return;
+    };
+    data.player_details
+        .store(posted.upload, reaction.channel_id, replay.into_owned());
+    // Posted as a reply instead: that one can be deleted and re-rendered too
+    if shown_in != reaction.message_id {
+        data.sent_results.record(shown_in, posted.requester, None);
+        data.posted_replays.store(shown_in, posted);
     }
+}
 
+/// Handle links to earlier messages posted with a mention: the linked
+/// messages' attachments are processed like an upload and replied to here
+async fn handle_message_links(
+    ctx: &serenity::Context,
+    new_message: &serenity::Message,
+    data: &Data,
+    poster: Author,
+) -> Result<(), Error> {
+    let mut links = message_links(&new_message.content);
+    if links.is_empty() {
+        return Ok(());
+    }
+    links.truncate(MAX_MESSAGE_LINKS);
+    if mention_required(new_message.guild_id, false)
+        && !is_bot_mentioned(ctx, new_message, data.bot_id).await
+    {
+        return Ok(());
+    }
+
+    if data.check_cooldown(new_message.channel_id) {
+        return Ok(());
+    }
+    data.set_cooldown(new_message.channel_id);
+    let locale = data.locale_for(ctx, new_message.guild_id).await;
+
+    data.in_flight.begin(new_message.id);
+    let venue = Venue::of_message(new_message.guild_id);
+    let mut attachments = Vec::new();
+    let mut errors = Vec::new();
+    for link in links {
+        let linked = match fetch_linked_message(ctx, new_message, link).await {
+            Ok(linked) => linked,
+            Err(e) => {
+                tracing::info!(msg_id = %new_message.id, "Linked message not followed: {:?}", e);
+                errors.push(UserMessage::for_link_error(e));
+                continue;
+            }
+        };
+        let original = Author::of_message(&linked);
+        if classify_origin(poster, Some(original), &data.allowed_webhooks) == Origin::Rejected {
+            tracing::debug!(msg_id = %new_message.id, "Ignoring linked message from a bot or webhook");
+            continue;
+        }
+        // A linked forward carries its attachments in the snapshot
+        let found = if !linked.attachments.is_empty() {
+            linked.attachments
+        } else if let Some(snapshot) = linked.message_snapshots.into_iter().next() {
+            snapshot.attachments
+        } else {
+            Vec::new()
+        };
+        if message_has_relevant(&found, venue) {
+            attachments.extend(found);
+        } else {
+            errors.push(UserMessage::link_empty());
+        }
+    }
+
+    if !errors.is_empty() {
+        reply_text(ctx, new_message, data, locale, &UserMessage::lines(&errors)).await;
+    }
+    if !attachments.is_empty() {
+        let name_filter = quoted_filter(&new_message.content);
+        process_attachments(
+            ctx,
+            new_message,
+            data,
+            locale,
+            &attachments,
+            venue,
+            name_filter.as_deref(),
+        )
+        .await;
+    }
+    data.in_flight.finish(new_message.id);
     Ok(())
 }
 
+/// Message whose author posted the attachments being handled
+enum AttachmentSource<'a> {
+    /// The triggering message itself
+    Own,
+    /// The replied-to message
+    Reply(&'a serenity::Message),
+    /// A forward (the triggering message or the replied-to one); its
+    /// snapshot holds the attachments
+    Forward(&'a serenity::Message),
+}
+
+/// Author of the message a forward was made from, fetched through the
+/// forward's reference; `Unknown` when the bot cannot see it
+async fn forwarded_author(ctx: &serenity::Context, forward: &serenity::Message) -> Author {
+    let Some(reference) = &forward.message_reference else {
+        return Author::Unknown;
+    };
+    let Some(message_id) = reference.message_id else {
+        return Author::Unknown;
+    };
+    match ctx.http.get_message(reference.channel_id, message_id).await {
+        Ok(original) => Author::of_message(&original),
+        Err(e) => {
+            tracing::debug!(%message_id, "Forwarded message not visible: {}", e);
+            Author::Unknown
+        }
+    }
+}
+
+/// Reply to every relevant attachment of a message, stopping early if the
+/// user deletes it meanwhile
+async fn process_attachments(
+    ctx: &serenity::Context,
+    new_message: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+    attachments: &[serenity::Attachment],
+    venue: Venue,
+    name_filter: Option<&str>,
+) {
+    let classified: Vec<(&serenity::Attachment, AttachmentClass)> = attachments
+        .iter()
+        .map(|a| (a, classify(a, venue)))
+        .collect();
+
+    // Replay files from one message get a single combined reply; archives
+    // follow, each as its own reply, one at a time.
+    let replay_files: Vec<(&serenity::Attachment, AttachmentClass)> = classified
+        .iter()
+        .copied()
+        .filter(|(_, class)| class.kind() == Some(FileKind::Replay))
+        .collect();
+    match replay_files.as_slice() {
+        [] => {}
+        [(single, class)] => {
+            let flags = ReplyFlags {
+                reveal: wants_reveal(&new_message.content),
+                debug: wants_debug(&new_message.content)
+                    && can_manage_guild(ctx, new_message).await,
+                anonymize: wants_anonymity(data, new_message),
+            };
+            process_single_attachment(ctx, new_message, data, locale, single, *class, flags).await
+        }
+        // A batch of replay files renders like an archive page, in a worker slot
+        _ => {
+            if let Some(slot) = wait_for_worker(ctx, new_message, data, locale).await {
+                process_replay_attachments(ctx, new_message, data, locale, &replay_files).await;
+                slot.finish(ctx, new_message.channel_id).await;
+            }
+        }
+    }
+
+    // Archives wait for a worker, queued behind other uploads while all are busy
+    let has_archives = classified
+        .iter()
+        .any(|(_, class)| matches!(class.kind(), Some(FileKind::Archive(_))));
+    if !has_archives {
+        return;
+    }
+    let Some(slot) = wait_for_worker(ctx, new_message, data, locale).await else {
+        return;
+    };
+
+    for (att_idx, (attachment, class)) in classified.into_iter().enumerate() {
+        if data.in_flight.is_cancelled(new_message.id) {
+            tracing::info!(msg_id = %new_message.id, "Upload deleted, skipping remaining archives");
+            return;
+        }
+        if let Some(FileKind::Archive(kind)) = class.kind() {
+            let archive = ArchiveAttachment {
+                attachment,
+                class,
+                kind,
+                att_idx,
+            };
+            process_archive_attachment(ctx, new_message, data, locale, archive, name_filter).await;
+        }
+    }
+    slot.finish(ctx, new_message.channel_id).await;
+}
+
+/// A worker slot held for an upload's batch rendering (archives, several
+/// replay files, a file of several games), and the queue notice it waited
+/// under, if any
+struct WorkerSlot {
+    _permit: WorkPermit,
+    notice: Option<serenity::MessageId>,
+}
+
+impl WorkerSlot {
+    /// Free the slot and remove the "Processing…" notice
+    async fn finish(self, ctx: &serenity::Context, channel_id: serenity::ChannelId) {
+        if let Some(notice) = self.notice {
+            delete_replies(ctx, channel_id, &[notice]).await;
+        }
+    }
+}
+
+/// Take a worker for an upload, queueing behind earlier uploads while all
+/// are busy. The queue notice shows the position, then "Processing…" once
+/// the upload starts, or why it was dropped. `None` when the upload does
+/// not get a worker.
+async fn wait_for_worker(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+) -> Option<WorkerSlot> {
+    let (position, ticket) = match data.work_queue.admit(msg.id) {
+        Admission::Start(permit) => {
+            return Some(WorkerSlot {
+                _permit: permit,
+                notice: None,
+            });
+        }
+        Admission::Full => {
+            tracing::warn!(msg_id = %msg.id, "Work queue full, upload turned away");
+            reply_text(ctx, msg, data, locale, &UserMessage::queue_full()).await;
+            return None;
+        }
+        Admission::Queued { position, ticket } => (position, ticket),
+    };
+    tracing::info!(msg_id = %msg.id, "All workers busy, upload queued at position {}", position);
+    // Not tracked as a reply: it is edited when the upload is deleted
+    let notice = send_simple_message(ctx, msg, locale, &UserMessage::queued(position)).await;
+    let (turn, text) = match ticket.wait().await {
+        Turn::Start(permit) => (Some(permit), UserMessage::queue_started()),
+        Turn::Dropped(reason) => (None, UserMessage::queue_dropped(reason)),
+    };
+    if let Some(notice) = notice {
+        edit_simple_message(ctx, msg.channel_id, notice, locale, &text).await;
+    }
+    turn.map(|permit| WorkerSlot {
+        _permit: permit,
+        notice,
+    })
+}
+
+/// Track a reply so it can be removed if the upload is deleted (or by its
+/// requester's reaction); removes it right away when the deletion raced the
+/// send. `pending_key` is the pagination entry of its "Show more" button.
+async fn track_reply(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    reply: Option<serenity::MessageId>,
+    pending_key: Option<&str>,
+) {
+    let Some(reply) = reply else {
+        return;
+    };
+    if data.in_flight.record_reply(msg.id, reply) {
+        data.sent_results.record(reply, msg.author.id, pending_key);
+    } else {
+        delete_replies(ctx, msg.channel_id, &[reply]).await;
+    }
+}
+
+/// Send a text reply unless the upload was deleted
+async fn reply_text(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+    text: &UserMessage,
+) {
+    if data.in_flight.is_cancelled(msg.id) {
+        return;
+    }
+    let reply = send_simple_message(ctx, msg, locale, text).await;
+    track_reply(ctx, msg, data, reply, None).await;
+}
+
+/// Per-message options for a replay reply (and the later pages of a
+/// paginated one)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplyFlags {
+    /// Animated reveal GIF instead of the still image
+    pub reveal: bool,
+    /// Also attach the decoded chunk stream
+    pub debug: bool,
+    /// Hide player names and the filename
+    pub anonymize: bool,
+}
+
 /// Process a single replay file attachment
 async fn process_single_attachment(
     ctx: &serenity::Context,
     msg: &serenity::Message,
     data: &Data,
+    locale: Locale,
     attachment: &serenity::Attachment,
+    class: AttachmentClass,
+    flags: ReplyFlags,
 ) {
-    if u64::from(attachment.size) > MAX_SINGLE_REPLAY_BYTES {
-        tracing::warn!("Replay file too large: {} bytes", attachment.size);
-        send_simple_message(ctx, msg, "Replay file too large (max 5MB)").await;
+    if let Some(too_large) = class.too_large_message() {
+        tracing::warn!(msg_id = %msg.id, "Replay file too large: {} bytes", attachment.size);
+        reply_text(ctx, msg, data, locale, &too_large).await;
         return;
     }
 
@@ -99,13 +632,146 @@ async fn process_single_attachment(
     let data_bytes = match attachment.download().await {
         Ok(bytes) => bytes,
         Err(e) => {
-            tracing::error!("Failed to download attachment: {}", e);
-            send_simple_message(ctx, msg, "Failed to download replay file").await;
+            tracing::error!(msg_id = %msg.id, "Failed to download attachment: {}", e);
+            reply_text(
+                ctx,
+                msg,
+                data,
+                locale,
+                &UserMessage::replay_download_failed(),
+            )
+            .await;
             return;
         }
     };
 
-    process_single_replay(ctx, msg, data, &data_bytes, &attachment.filename).await;
+    if let Err(e) = preflight(&data_bytes) {
+        tracing::info!(msg_id = %msg.id, "Rejected {}: {}", attachment.filename, e);
+        reply_text(
+            ctx,
+            msg,
+            data,
+            locale,
+            &UserMessage::for_preflight_error(&e),
+        )
+        .await;
+        return;
+    }
+
+    // Files holding several games back to back get the batch/pagination flow
+    let games = split_games(&data_bytes);
+    if games.len() > 1 {
+        tracing::info!(msg_id = %msg.id, "{} holds {} games", attachment.filename, games.len());
+        let key = format!("{}_{}_multi", msg.channel_id, msg.id);
+        let reply = multi_game_reply(&attachment.filename, &games, flags);
+        if let Some(slot) = wait_for_worker(ctx, msg, data, locale).await {
+            send_paginated_replays(ctx, msg, data, locale, reply, &key).await;
+            slot.finish(ctx, msg.channel_id).await;
+        }
+        if flags.debug {
+            send_chunk_dump(ctx, msg, data, locale, &data_bytes).await;
+        }
+        return;
+    }
+
+    process_single_replay(
+        ctx,
+        msg,
+        data,
+        locale,
+        &data_bytes,
+        &attachment.filename,
+        flags,
+    )
+    .await;
+}
+
+/// The paginated reply for a file holding several games, one entry per game
+/// under the message's flags
+fn multi_game_reply(filename: &str, games: &[&[u8]], flags: ReplyFlags) -> PaginatedReply {
+    let replays = games
+        .iter()
+        .enumerate()
+        .map(|(i, game)| (format!("{} (game {})", filename, i + 1), game.to_vec()))
+        .collect();
+    PaginatedReply {
+        replays,
+        parsed: Vec::new(),
+        errors: Vec::new(),
+        cap_note: None,
+        flags,
+    }
+}
+
+/// Process several replay files from one message as a single batch reply
+async fn process_replay_attachments(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+    attachments: &[(&serenity::Attachment, AttachmentClass)],
+) {
+    let mut replays = Vec::new();
+    let mut errors = Vec::new();
+
+    for &(attachment, class) in attachments {
+        if data.in_flight.is_cancelled(msg.id) {
+            return;
+        }
+        if let Some(too_large) = class.too_large_message() {
+            tracing::warn!(msg_id = %msg.id, "Replay file too large: {} bytes", attachment.size);
+            errors.push(UserMessage::for_file(&attachment.filename, &too_large));
+            continue;
+        }
+
+        tracing::info!("Processing replay file: {}", attachment.filename);
+        match attachment.download().await {
+            Ok(bytes) => match preflight(&bytes) {
+                Ok(()) => replays.push((attachment.filename.clone(), bytes)),
+                Err(e) => {
+                    tracing::info!(msg_id = %msg.id, "Rejected {}: {}", attachment.filename, e);
+                    errors.push(UserMessage::for_file(
+                        &attachment.filename,
+                        &UserMessage::for_preflight_error(&e),
+                    ));
+                }
+            },
+            Err(e) => {
+                tracing::error!(msg_id = %msg.id, "Failed to download attachment: {}", e);
+                errors.push(UserMessage::for_file(
+                    &attachment.filename,
+                    &UserMessage::replay_download_failed(),
+                ));
+            }
+        }
+    }
+
+    if replays.is_empty() {
+        reply_text(ctx, msg, data, locale, &UserMessage::lines(&errors)).await;
+        return;
+    }
+
+    let key = format!("{}_{}_direct", msg.channel_id, msg.id);
+    let reply = PaginatedReply {
+        replays,
+        parsed: Vec::new(),
+        errors,
+        cap_note: None,
+        flags: ReplyFlags {
+            anonymize: wants_anonymity(data, msg),
+            ..ReplyFlags::default()
+        },
+    };
+    send_paginated_replays(ctx, msg, data, locale, reply, &key).await;
+}
+
+/// An archive attachment and how it was classified
+struct ArchiveAttachment<'a> {
+    attachment: &'a serenity::Attachment,
+    class: AttachmentClass,
+    kind: ArchiveKind,
+    /// Position among the message's attachments, for the pagination key
+    att_idx: usize,
 }
 
 /// Process an archive attachment (ZIP or RAR)
@@ -113,212 +779,905 @@ async fn process_archive_attachment(
     ctx: &serenity::Context,
     msg: &serenity::Message,
     data: &Data,
-    attachment: &serenity::Attachment,
-    att_idx: usize,
+    locale: Locale,
+    archive: ArchiveAttachment<'_>,
+    name_filter: Option<&str>,
 ) {
-    if u64::from(attachment.size) > MAX_ARCHIVE_BYTES {
-        tracing::warn!("Archive too large: {} bytes", attachment.size);
-        send_simple_message(ctx, msg, "Archive too large (max 25MB)").await;
+    let ArchiveAttachment {
+        attachment,
+        class,
+        kind,
+        att_idx,
+    } = archive;
+    if let Some(too_large) = class.too_large_message() {
+        tracing::warn!(msg_id = %msg.id, "Archive too large: {} bytes", attachment.size);
+        reply_text(ctx, msg, data, locale, &too_large).await;
         return;
     }
 
-    let is_rar = attachment.filename.to_lowercase().ends_with(".rar");
-    let label = if is_rar { "RAR" } else { "ZIP" };
+    let is_rar = kind == ArchiveKind::Rar;
+    let label = kind.label();
     tracing::info!("Processing {} archive: {}", label, attachment.filename);
 
     let archive_bytes = match attachment.download().await {
         Ok(bytes) => bytes,
         Err(e) => {
-            tracing::error!("Failed to download {}: {}", label, e);
-            send_simple_message(ctx, msg, "Failed to download archive").await;
+            tracing::error!(msg_id = %msg.id, "Failed to download {}: {}", label, e);
+            reply_text(
+                ctx,
+                msg,
+                data,
+                locale,
+                &UserMessage::archive_download_failed(),
+            )
+            .await;
             return;
         }
     };
 
-    let (replays, total) = if is_rar {
-        match tokio::task::spawn_blocking(move || extract_replays_from_rar(&archive_bytes)).await {
-            Ok(r) => r,
-            Err(e) => {
-                tracing::error!("{} extraction task failed: {}", label, e);
-                send_simple_message(ctx, msg, "Failed to extract archive").await;
-                return;
-            }
+    // Replays are parsed while the rest of the archive is still extracted
+    let extracted = extract_and_preparse(
+        archive_bytes,
+        is_rar,
+        data.temp_dirs.clone(),
+        data.layout.regions(),
+    )
+    .await;
+    let (preparsed, total) = match extracted {
+        Ok(Ok(r)) => {
+            metrics::global().record_archive_extraction();
+            r
         }
-    } else {
-        match tokio::task::spawn_blocking(move || extract_replays_from_zip(&archive_bytes)).await {
-            Ok(r) => r,
-            Err(e) => {
-                tracing::error!("{} extraction task failed: {}", label, e);
-                send_simple_message(ctx, msg, "Failed to extract archive").await;
-                return;
-            }
+        Ok(Err(e)) => {
+            tracing::warn!(msg_id = %msg.id, "{} not extracted: {}", label, e);
+            let message = UserMessage::for_archive_error(&e);
+            reply_text(ctx, msg, data, locale, &message).await;
+            return;
+        }
+        Err(e) => {
+            tracing::error!(msg_id = %msg.id, "{} extraction task failed: {}", label, e);
+            reply_text(
+                ctx,
+                msg,
+                data,
+                locale,
+                &UserMessage::archive_extract_failed(),
+            )
+            .await;
+            return;
         }
     };
 
-    if replays.is_empty() {
-        send_simple_message(ctx, msg, "No .BfME2Replay files found in archive").await;
+    if preparsed.replays.is_empty() {
+        reply_text(ctx, msg, data, locale, &UserMessage::archive_empty()).await;
         return;
     }
 
-    let key = format!("{}_{}_{}", msg.channel_id, msg.id, att_idx);
-    process_archive_replays(ctx, msg, data, replays, total, &key).await;
+    let archive = ArchiveReply {
+        preparsed,
+        total,
+        key: format!("{}_{}_{}", msg.channel_id, msg.id, att_idx),
+        name_filter,
+    };
+    process_archive_replays(ctx, msg, data, locale, archive).await;
 }
 
 /// Process a single replay file: parse, render, and send the image
+/// (an animated reveal GIF instead of a JPEG when `reveal` is set)
+#[tracing::instrument(
+    name = "replay",
+    skip_all,
+    fields(
+        file = filename,
+        content_hash = %hex_hash(replay_bytes),
+        fingerprint = tracing::field::Empty,
+        parse_ms = tracing::field::Empty,
+        render_ms = tracing::field::Empty,
+        output_bytes = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    )
+)]
 async fn process_single_replay(
     ctx: &serenity::Context,
     msg: &serenity::Message,
     data: &Data,
+    locale: Locale,
     replay_bytes: &[u8],
     filename: &str,
+    flags: ReplyFlags,
 ) {
+    if data.in_flight.is_cancelled(msg.id) {
+        return;
+    }
+    let started = Instant::now();
+    let result = render_single(
+        data,
+        replay_bytes,
+        filename,
+        flags,
+        tracing::Span::current(),
+    )
+    .await;
+    let outcome = match &result {
+        Ok(result) => outcome_label(result),
+        Err(_) => "panicked",
+    };
+    finish_replay_span(&tracing::Span::current(), outcome);
+
+    match result {
+        Ok(Ok(_)) if data.in_flight.is_cancelled(msg.id) => {}
+        Ok(Ok((replay, image_bytes))) => {
+            data.record_results(msg.guild_id, msg.channel_id, vec![GameResult::of(&replay)]);
+            let (replay, title) = shown_replay(&replay, filename, render_options(data, flags));
+            // Keyed by the upload, since the reply's id isn't known until sent
+            let buttons = player_buttons(msg.id, &replay);
+            let shown = SingleResult::new(
+                data,
+                msg.guild_id,
+                &replay,
+                &title,
+                image_bytes,
+                flags,
+                locale,
+            );
+            let reply = send_replay_image(
+                ctx,
+                msg,
+                shown.file,
+                shown.embed,
+                buttons,
+                shown.note.as_ref(),
+                locale,
+            )
+            .await;
+            if let Some(reply) = reply {
+                data.posted_replays.store(
+                    reply,
+                    PostedReplay {
+                        bytes: replay_bytes.into(),
+                        filename: filename.to_string(),
+                        requester: msg.author.id,
+                        upload: msg.id,
+                        reveal: flags.reveal,
+                        anonymize: flags.anonymize,
+                    },
+                );
+                data.player_details
+                    .store(msg.id, msg.channel_id, replay.into_owned());
+            }
+            track_reply(ctx, msg, data, reply, None).await;
+            if flags.debug {
+                send_chunk_dump(ctx, msg, data, locale, replay_bytes).await;
+            }
+        }
+        Ok(Err(e @ ReplayError::UnsupportedMap(_))) => {
+            tracing::info!(msg_id = %msg.id, "Skipping replay: {}", e);
+            reply_text(ctx, msg, data, locale, &UserMessage::for_replay_error(&e)).await;
+        }
+        Ok(Err(e)) => {
+            tracing::error!(msg_id = %msg.id, "Failed to process replay: {}", e);
+            if let ReplayError::RenderError(_) = e {
+                data.failure_reporter.report(Failure {
+                    kind: FailureKind::Render,
+                    filename_hash: Some(hex_hash(filename.as_bytes())),
+                    site: FailureSite::new(msg.guild_id, msg.channel_id),
+                    elapsed: started.elapsed(),
+                    detail: e.to_string(),
+                });
+            }
+            reply_text(ctx, msg, data, locale, &UserMessage::for_replay_error(&e)).await;
+        }
+        Err(e) => {
+            let detail = panic_detail(e);
+            tracing::error!(msg_id = %msg.id, "Replay processing task failed: {}", detail);
+            data.failure_reporter.report(Failure {
+                kind: FailureKind::Panic,
+                filename_hash: Some(hex_hash(filename.as_bytes())),
+                site: FailureSite::new(msg.guild_id, msg.channel_id),
+                elapsed: started.elapsed(),
+                detail,
+            });
+            reply_text(ctx, msg, data, locale, &UserMessage::internal_error()).await;
+        }
+    }
+}
+
+/// Render options for a single result with these flags
+fn render_options(data: &Data, flags: ReplyFlags) -> RenderOptions {
+    RenderOptions {
+        anonymize: flags.anonymize,
+        ..data.render_options
+    }
+}
+
+/// Parse and render one replay off the async runtime, as a still image or
+/// (with `reveal`) the animated reveal GIF, recording both on `span`
+async fn render_single(
+    data: &Data,
+    replay_bytes: &[u8],
+    filename: &str,
+    flags: ReplyFlags,
+    span: tracing::Span,
+) -> Result<Result<(ReplayInfo, Vec<u8>), ReplayError>, tokio::task::JoinError> {
     let bytes_owned = replay_bytes.to_vec();
     let font = data.font.clone();
     let map_image = data.map_image.clone();
+    let layout = data.layout.clone();
+    let faction_icons = data.faction_icons.clone();
+    let render_options = render_options(data, flags);
     let filename_owned = filename.to_string();
 
-    let result = tokio::task::spawn_blocking(move || {
-        let replay = parse_replay(&bytes_owned)?;
-        let image_bytes = render_map(&replay, &font, &map_image, &filename_owned)
-            .map_err(ReplayError::RenderError)?;
-        Ok::<Vec<u8>, ReplayError>(image_bytes)
+    tokio::task::spawn_blocking(move || {
+        let _entered = span.enter();
+        let budget = Duration::from_millis(SINGLE_PARSE_BUDGET_MS);
+        let replay = parse_recorded(&bytes_owned, layout.regions(), budget, &span)?;
+        let assets = RenderAssets {
+            font: &font,
+            map_image: &map_image,
+            layout: &layout,
+            faction_icons: &faction_icons,
+        };
+        let image_bytes = render_recorded(&span, |timings| {
+            if flags.reveal {
+                render_reveal(&replay, assets, &filename_owned, render_options)
+            } else {
+                render_map_timed(&replay, assets, &filename_owned, render_options, timings)
+            }
+        })?;
+        Ok::<_, ReplayError>((replay, image_bytes))
     })
-    .await;
+    .await
+}
 
-    match result {
-        Ok(Ok(image_bytes)) => {
-            send_replay_image(ctx, msg, image_bytes).await;
-        }
-        Ok(Err(ReplayError::UnsupportedMap(map_name))) => {
-            tracing::info!("Skipping unsupported map: {}", map_name);
-            send_simple_message(ctx, msg, &format!("Not a Rhun game (map: {})", map_name)).await;
-        }
-        Ok(Err(ReplayError::InvalidHeader)) => {
-            tracing::error!("Invalid replay header");
-            send_simple_message(ctx, msg, "Invalid replay file").await;
-        }
-        Ok(Err(ReplayError::NoPlayers)) => {
-            tracing::error!("No players found in replay");
-            send_simple_message(ctx, msg, "No players found in replay").await;
+/// A single result's image, embed (for the embed reply style) and note
+struct SingleResult {
+    file: UploadFile,
+    embed: Option<serenity::CreateEmbed>,
+    note: Option<UserMessage>,
+}
+
+impl SingleResult {
+    fn new(
+        data: &Data,
+        guild_id: Option<serenity::GuildId>,
+        replay: &ReplayInfo,
+        title: &str,
+        image_bytes: Vec<u8>,
+        flags: ReplyFlags,
+        locale: Locale,
+    ) -> Self {
+        let summary = summarize_replay(replay);
+        let note = UserMessage::for_replay(replay);
+        // The reveal GIF stays a bare attachment
+        if flags.reveal {
+            let file = UploadFile::new("replay.gif", image_bytes).with_description(summary);
+            return Self {
+                file,
+                embed: None,
+                note,
+            };
         }
+        let embed = (data.reply_style == ReplyStyle::Embed).then(|| {
+            let announcement = data
+                .winner_template_for(guild_id)
+                .and_then(|template| template.render(replay, locale));
+            build_result_embed(replay, title, announcement)
+        });
+        let file = UploadFile::new(EMBED_IMAGE_NAME, image_bytes).with_description(summary);
+        Self { file, embed, note }
+    }
+}
+
+/// Attach the decoded chunk stream of a replay, for debugging a wrong result.
+/// Skipped (and logged) when it can't be built or is over the upload limit.
+async fn send_chunk_dump(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+    replay_bytes: &[u8],
+) {
+    if data.in_flight.is_cancelled(msg.id) {
+        return;
+    }
+    let bytes_owned = replay_bytes.to_vec();
+    let dump = match tokio::task::spawn_blocking(move || chunk_dump(&bytes_owned)).await {
+        Ok(Ok(dump)) => dump,
         Ok(Err(e)) => {
-            tracing::error!("Failed to process replay: {}", e);
-            send_simple_message(ctx, msg, &format!("Error: {}", e)).await;
+            tracing::warn!(msg_id = %msg.id, "Chunk dump failed: {}", e);
+            return;
         }
         Err(e) => {
-            tracing::error!("Replay processing task failed: {}", e);
-            send_simple_message(ctx, msg, "Internal error processing replay").await;
+            tracing::error!(msg_id = %msg.id, "Chunk dump task failed: {}", e);
+            return;
         }
+    };
+    if dump.gzip.len() > data.attachment_limits.max_bytes {
+        tracing::warn!(
+            msg_id = %msg.id,
+            "Chunk dump is {} bytes, over the upload limit of {}",
+            dump.gzip.len(),
+            data.attachment_limits.max_bytes
+        );
+        return;
     }
+    tracing::info!(
+        msg_id = %msg.id,
+        "Sending chunk dump: {} chunks{}",
+        dump.chunks,
+        if dump.truncated { " (truncated)" } else { "" }
+    );
+    let file = UploadFile::new(CHUNK_DUMP_FILENAME, dump.gzip);
+    let reply = send_replay_image(ctx, msg, file, None, Vec::new(), None, locale).await;
+    track_reply(ctx, msg, data, reply, None).await;
+}
+
+/// One rendered batch: images, error messages and how its games ended
+pub struct RenderedBatch {
+    pub images: Vec<UploadFile>,
+    pub errors: Vec<UserMessage>,
+    pub tally: ArchiveTally,
+    /// Outcomes of the games that parsed, for the results log
+    pub results: Vec<GameResult>,
+}
+
+/// What rendering a batch needs from `Data`, cheap to move into a background task
+#[derive(Clone)]
+pub struct BatchRenderer {
+    font: Arc<FontArc>,
+    map_image: Arc<RgbImage>,
+    layout: Arc<MapLayout>,
+    faction_icons: Arc<FactionIcons>,
+    render_options: RenderOptions,
+    /// Animated reveal GIFs instead of still images
+    reveal: bool,
+    failure_reporter: Arc<FailureReporter>,
+    /// Where the replays were posted, for failure reports
+    site: FailureSite,
 }
 
-/// Process up to BATCH_SIZE replays and return image attachments + error messages.
+impl BatchRenderer {
+    /// `flags` pick reveal GIFs and hiding names in the images and their
+    /// alt text
+    pub fn new(data: &Data, flags: ReplyFlags, site: FailureSite) -> Self {
+        Self {
+            font: data.font.clone(),
+            map_image: data.map_image.clone(),
+            layout: data.layout.clone(),
+            faction_icons: data.faction_icons.clone(),
+            render_options: render_options(data, flags),
+            reveal: flags.reveal,
+            failure_reporter: data.failure_reporter.clone(),
+            site,
+        }
+    }
+}
+
+/// Process up to BATCH_SIZE replays and return rendered images + error messages.
 /// Uses JoinSet for parallel rendering.
+#[tracing::instrument(skip_all, fields(replays = replays.len()))]
 pub async fn process_replay_batch(
     data: &Data,
     replays: &[(String, Vec<u8>)],
-) -> (Vec<CreateAttachment>, Vec<String>) {
+    parsed: &[Result<ReplayInfo, ReplayError>],
+    flags: ReplyFlags,
+    site: FailureSite,
+) -> RenderedBatch {
+    render_replay_batch(&BatchRenderer::new(data, flags, site), replays, parsed).await
+}
+
+/// A rendered map, the game summary used as its alt text and notes on the game
+type RenderedImage = (Vec<u8>, String, Option<UserMessage>);
+
+/// [`process_replay_batch`] without `Data`, for background tasks. Replays
+/// with an entry in `parsed` (an archive pre-parse) render from it; the
+/// rest are parsed here.
+pub async fn render_replay_batch(
+    renderer: &BatchRenderer,
+    replays: &[(String, Vec<u8>)],
+    parsed: &[Result<ReplayInfo, ReplayError>],
+) -> RenderedBatch {
     let batch = &replays[..replays.len().min(BATCH_SIZE)];
+    metrics::global().record_batch_size(batch.len());
+    let started = Instant::now();
     let mut set = tokio::task::JoinSet::new();
+    let mut spans = Vec::with_capacity(batch.len());
 
     for (idx, (name, bytes)) in batch.iter().enumerate() {
-        let font = data.font.clone();
-        let map_image = data.map_image.clone();
+        let font = renderer.font.clone();
+        let map_image = renderer.map_image.clone();
+        let layout = renderer.layout.clone();
+        let faction_icons = renderer.faction_icons.clone();
+        let render_options = renderer.render_options;
+        let reveal = renderer.reveal;
         let name_owned = name.clone();
         let name_for_render = name.clone();
         let bytes_owned = bytes.clone();
+        let preparsed = parsed.get(idx).cloned();
+        let span = replay_span(name, bytes);
+        spans.push(span.clone());
 
         set.spawn_blocking(move || {
-            let replay = parse_replay(&bytes_owned);
+            let _entered = span.enter();
+            let replay = match preparsed {
+                Some(replay) => {
+                    record_fingerprint(&span, &replay);
+                    replay
+                }
+                None => {
+                    let budget = Duration::from_millis(BATCH_PARSE_BUDGET_MS);
+                    parse_recorded(&bytes_owned, layout.regions(), budget, &span)
+                }
+            };
+            let mut counted = ArchiveTally::default();
+            counted.record(&replay);
+            let result = replay.as_ref().ok().map(GameResult::of);
             (
                 idx,
                 name_owned,
+                counted,
+                result,
                 replay.and_then(|r| {
-                    render_map(&r, &font, &map_image, &name_for_render)
-                        .map_err(ReplayError::RenderError)
+                    let assets = RenderAssets {
+                        font: &font,
+                        map_image: &map_image,
+                        layout: &layout,
+                        faction_icons: &faction_icons,
+                    };
+                    let image = render_recorded(&span, |timings| {
+                        if reveal {
+                            render_reveal(&r, assets, &name_for_render, render_options)
+                        } else {
+                            render_map_timed(&r, assets, &name_for_render, render_options, timings)
+                        }
+                    })?;
+                    let (shown, _) = shown_replay(&r, &name_for_render, render_options);
+                    let note = UserMessage::for_replay(&shown);
+                    Ok((image, summarize_replay(&shown), note))
                 }),
             )
         });
     }
 
     // Collect results in order
-    let mut results: Vec<(usize, String, Result<Vec<u8>, ReplayError>)> = Vec::new();
+    let mut results: Vec<(usize, String, Result<RenderedImage, ReplayError>)> = Vec::new();
+    let mut tally = ArchiveTally::default();
+    let mut game_results = Vec::new();
     while let Some(join_result) = set.join_next().await {
         match join_result {
-            Ok(tuple) => results.push(tuple),
-            Err(e) => tracing::error!("Batch render task panicked: {}", e),
+            Ok((idx, name, counted, game_result, result)) => {
+                tally.merge(&counted);
+                game_results.extend(game_result);
+                results.push((idx, name, result));
+            }
+            Err(e) => {
+                let detail = panic_detail(e);
+                tracing::error!("Batch render task panicked: {}", detail);
+                renderer.failure_reporter.report(Failure {
+                    kind: FailureKind::Panic,
+                    filename_hash: None,
+                    site: renderer.site,
+                    elapsed: started.elapsed(),
+                    detail,
+                });
+                tally.errors += 1;
+            }
         }
     }
     results.sort_by_key(|(idx, _, _)| *idx);
 
-    let mut attachments = Vec::new();
+    let mut images = Vec::new();
     let mut errors = Vec::new();
 
     for (idx, name, result) in results {
+        finish_replay_span(&spans[idx], outcome_label(&result));
         match result {
-            Ok(image_bytes) => {
-                let filename = format!("replay_{}.jpg", idx + 1);
-                attachments.push(CreateAttachment::bytes(image_bytes, filename));
+            Ok((image_bytes, summary, note)) => {
+                let extension = if renderer.reveal { "gif" } else { "jpg" };
+                let filename = format!("replay_{}.{}", idx + 1, extension);
+                images.push(UploadFile::new(filename, image_bytes).with_description(summary));
+                if let Some(note) = note {
+                    errors.push(UserMessage::for_file(&name, &note));
+                }
             }
-            Err(ReplayError::UnsupportedMap(map_name)) => {
-                tracing::info!("Skipping unsupported map: {}", map_name);
-                errors.push(format!("{}: Not a Rhun game (map: {})", name, map_name));
+            Err(e @ ReplayError::UnsupportedMap(_)) => {
+                tracing::info!("Skipping {}: {}", name, e);
+                errors.push(UserMessage::for_file(
+                    &name,
+                    &UserMessage::for_replay_error(&e),
+                ));
             }
             Err(e) => {
                 tracing::error!("Failed to process {}: {}", name, e);
-                errors.push(format!("{}: {}", name, e));
+                if let ReplayError::RenderError(_) = e {
+                    renderer.failure_reporter.report(Failure {
+                        kind: FailureKind::Render,
+                        filename_hash: Some(hex_hash(name.as_bytes())),
+                        site: renderer.site,
+                        elapsed: started.elapsed(),
+                        detail: e.to_string(),
+                    });
+                }
+                errors.push(UserMessage::for_file(
+                    &name,
+                    &UserMessage::for_replay_error(&e),
+                ));
             }
         }
     }
 
-    (attachments, errors)
+    RenderedBatch {
+        images,
+        errors,
+        tally,
+        results: game_results,
+    }
+}
+
+/// Span for one replay file; parse and render results are recorded on it as
+/// they come in
+fn replay_span(name: &str, bytes: &[u8]) -> tracing::Span {
+    tracing::info_span!(
+        "replay",
+        file = name,
+        content_hash = %hex_hash(bytes),
+        fingerprint = tracing::field::Empty,
+        parse_ms = tracing::field::Empty,
+        render_ms = tracing::field::Empty,
+        output_bytes = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    )
+}
+
+/// Stable hash as 16 hex digits, so logs correlate without raw ids or bytes
+fn hex_hash(bytes: &[u8]) -> String {
+    format!("{:016x}", content_hash(bytes))
+}
+
+/// Outcome of a processed replay as recorded on its span
+fn outcome_label<T>(result: &Result<T, ReplayError>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(e) => metrics::error_label(e),
+    }
+}
+
+/// Record how a replay ended on its span, with one event carrying all its fields
+fn finish_replay_span(span: &tracing::Span, outcome: &str) {
+    span.record("outcome", outcome);
+    tracing::debug!(parent: span, "Replay processed");
+}
+
+/// Parse a replay within `budget`, recording its outcome and timing in the
+/// metrics (and the duration and game fingerprint on its span)
+pub(super) fn parse_recorded(
+    bytes: &[u8],
+    regions: SpotRegions,
+    budget: Duration,
+    span: &tracing::Span,
+) -> Result<ReplayInfo, ReplayError> {
+    let started = Instant::now();
+    let metrics = metrics::global();
+    let parser = ReplayParser::builder()
+        .record_timings(true)
+        .spot_regions(regions)
+        .tick_rate(metrics.calibrated_tick_rate())
+        .time_budget(budget)
+        .build();
+    let (result, stats) = parser.parse_with_stats(bytes);
+    span.record("parse_ms", started.elapsed().as_millis() as u64);
+    record_fingerprint(span, &result);
+    metrics.record_parse(
+        result.as_ref().err(),
+        started.elapsed(),
+        stats.raw_scan_recoveries,
+    );
+    if let Some(timings) = &stats.timings {
+        metrics.record_stages(timings);
+    }
+    if let Some(rate) = stats.implied_tick_rate {
+        metrics.record_tick_rate(rate);
+    }
+    result
+}
+
+/// Record a parsed game's fingerprint on its span
+fn record_fingerprint(span: &tracing::Span, result: &Result<ReplayInfo, ReplayError>) {
+    if let Ok(replay) = result {
+        span.record(
+            "fingerprint",
+            tracing::field::display(format!("{:016x}", replay.fingerprint())),
+        );
+    }
+}
+
+/// Run a render, recording its timing and any stage timings it reports in the
+/// metrics (and the duration and output size on its span)
+fn render_recorded(
+    span: &tracing::Span,
+    render: impl FnOnce(&mut TimingBreakdown) -> Result<Vec<u8>, String>,
+) -> Result<Vec<u8>, ReplayError> {
+    let started = Instant::now();
+    let mut timings = TimingBreakdown::default();
+    let result = render(&mut timings);
+    span.record("render_ms", started.elapsed().as_millis() as u64);
+    if let Ok(image) = &result {
+        span.record("output_bytes", image.len() as u64);
+    }
+    let metrics = metrics::global();
+    metrics.record_render(started.elapsed());
+    metrics.record_stages(&timings);
+    result.map_err(ReplayError::RenderError)
+}
+
+/// An extracted archive and how to reply to it
+struct ArchiveReply<'a> {
+    preparsed: PreparsedReplays,
+    /// Replays found in the archive, including ones past the cap
+    total: usize,
+    /// Pagination key for the remaining batches
+    key: String,
+    /// Only reply with replays whose name contains this
+    name_filter: Option<&'a str>,
 }
 
 /// Process an archive's replays: send first batch, store remaining for pagination.
+/// With a name filter, only matching replays are sent; when none match, the
+/// available names are listed instead so the user can retry.
 async fn process_archive_replays(
     ctx: &serenity::Context,
     msg: &serenity::Message,
     data: &Data,
-    replays: Vec<(String, Vec<u8>)>,
-    total: usize,
-    key: &str,
+    locale: Locale,
+    archive: ArchiveReply<'_>,
 ) {
-    let effective_total = replays.len();
-    let cap_note = if total > effective_total {
-        Some(format!(
-            "Found {} replays, processing first {}",
-            total, effective_total
-        ))
-    } else {
+    let ArchiveReply {
+        mut preparsed,
+        total,
+        key,
+        name_filter,
+    } = archive;
+    let processed = preparsed.replays.len() + preparsed.merged + preparsed.restart_notes.len();
+    if let Some(filter) = name_filter {
+        let names: Vec<String> = preparsed.replays.iter().map(|(n, _)| n.clone()).collect();
+        preparsed.retain_matching(filter);
+        if preparsed.replays.is_empty() {
+            let listing = UserMessage::no_matching_replays(filter, &names);
+            reply_text(ctx, msg, data, locale, &listing).await;
+            return;
+        }
+    }
+
+    let PreparsedReplays {
+        replays,
+        merged,
+        restart_notes,
+        infos,
+    } = preparsed;
+    let mut notes = Vec::new();
+    if total > processed {
+        notes.push(UserMessage::archive_capped(total, processed));
+    }
+    if merged > 0 {
+        notes.push(UserMessage::duplicates_merged(merged));
+    }
+    notes.extend(restart_notes);
+    let cap_note = if notes.is_empty() {
         None
+    } else {
+        Some(UserMessage::lines(&notes))
+    };
+
+    let report_games = has_flag(&msg.content, "html").then(|| report_games(&replays, &infos));
+    let stats_games: Vec<ReplayInfo> = if has_flag(&msg.content, "stats") {
+        infos
+            .iter()
+            .filter_map(|info| info.as_ref().ok())
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let reply = PaginatedReply {
+        replays,
+        parsed: infos,
+        errors: Vec::new(),
+        cap_note,
+        flags: ReplyFlags {
+            anonymize: wants_anonymity(data, msg),
+            ..ReplyFlags::default()
+        },
+    };
+    send_paginated_replays(ctx, msg, data, locale, reply, &key).await;
+
+    if let Some(games) = report_games {
+        send_archive_report(ctx, msg, data, locale, games).await;
+    }
+
+    if stats_games.len() >= MIN_STATS_GAMES {
+        send_archive_stats(ctx, msg, data, locale, stats_games).await;
+    }
+}
+
+/// Send one stats image summarizing an archive's games
+async fn send_archive_stats(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+    infos: Vec<ReplayInfo>,
+) {
+    if data.in_flight.is_cancelled(msg.id) {
+        return;
+    }
+    let font = data.font.clone();
+    let rendered = tokio::task::spawn_blocking(move || render_archive_stats(&infos, &font)).await;
+    match rendered {
+        Ok(Ok(image_bytes)) => {
+            let file = UploadFile::new("archive_stats.jpg", image_bytes);
+            let reply = send_replay_image(ctx, msg, file, None, Vec::new(), None, locale).await;
+            track_reply(ctx, msg, data, reply, None).await;
+        }
+        Ok(Err(e)) => tracing::error!(msg_id = %msg.id, "Failed to render archive stats: {}", e),
+        Err(e) => tracing::error!(msg_id = %msg.id, "Archive stats task failed: {}", e),
+    }
+}
+
+/// Each kept replay's name and parse result
+fn report_games(
+    replays: &[(String, Vec<u8>)],
+    infos: &[Result<ReplayInfo, ReplayError>],
+) -> Vec<(String, Result<ReplayInfo, ReplayError>)> {
+    replays
+        .iter()
+        .zip(infos)
+        .map(|((name, _), info)| (name.clone(), info.clone()))
+        .collect()
+}
+
+/// Send one HTML file with every game of an archive and a map thumbnail
+/// for each, sized to fit the upload limit
+async fn send_archive_report(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+    games: Vec<(String, Result<ReplayInfo, ReplayError>)>,
+) {
+    if data.in_flight.is_cancelled(msg.id) {
+        return;
+    }
+    let font = data.font.clone();
+    let map_image = data.map_image.clone();
+    let layout = data.layout.clone();
+    let faction_icons = data.faction_icons.clone();
+    let anonymize = wants_anonymity(data, msg);
+    let render_options = RenderOptions {
+        anonymize,
+        ..data.render_options
+    };
+    let max_bytes = data.attachment_limits.max_bytes;
+    let games: Vec<(String, Result<ReplayInfo, ReplayError>)> = if anonymize {
+        games
+            .into_iter()
+            .map(|(name, result)| (name, result.map(|info| anonymize_view(&info))))
+            .collect()
+    } else {
+        games
     };
+    let built = tokio::task::spawn_blocking(move || {
+        let assets = RenderAssets {
+            font: &font,
+            map_image: &map_image,
+            layout: &layout,
+            faction_icons: &faction_icons,
+        };
+        let images: Vec<(String, Vec<u8>)> = games
+            .iter()
+            .filter_map(|(name, result)| Some((name, result.as_ref().ok()?)))
+            .take(MAX_REPORT_THUMBNAILS)
+            .filter_map(|(name, info)| {
+                let mut timings = TimingBreakdown::default();
+                render_map_timed(info, assets, name, render_options, &mut timings)
+                    .map_err(|e| tracing::warn!("Report thumbnail for {} failed: {}", name, e))
+                    .ok()
+                    .map(|jpeg| (name.clone(), jpeg))
+            })
+            .collect();
+        build_html_report(&games, &images, max_bytes)
+    })
+    .await;
+    match built {
+        Ok(Some(html)) => {
+            let file = UploadFile::new(REPORT_FILENAME, html.into_bytes());
+            let reply = send_replay_image(ctx, msg, file, None, Vec::new(), None, locale).await;
+            track_reply(ctx, msg, data, reply, None).await;
+        }
+        Ok(None) => tracing::warn!(msg_id = %msg.id, "Archive report exceeds the upload limit"),
+        Err(e) => tracing::error!(msg_id = %msg.id, "Archive report task failed: {}", e),
+    }
+}
+
+/// Replays for one paginated reply, plus lines shown with the first batch
+struct PaginatedReply {
+    replays: Vec<(String, Vec<u8>)>,
+    /// Parse results of `replays` from an archive pre-parse; empty otherwise
+    parsed: Vec<Result<ReplayInfo, ReplayError>>,
+    /// Listed ahead of the first batch's own errors
+    errors: Vec<UserMessage>,
+    cap_note: Option<UserMessage>,
+    /// Applied to every page
+    flags: ReplyFlags,
+}
 
-    let (attachments, errors) = process_replay_batch(data, &replays).await;
+/// Send the first batch of replays as one message and store the rest for
+/// pagination under `key`.
+async fn send_paginated_replays(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    locale: Locale,
+    reply: PaginatedReply,
+    key: &str,
+) {
+    let PaginatedReply {
+        replays,
+        parsed,
+        mut errors,
+        cap_note,
+        flags,
+    } = reply;
+    let effective_total = replays.len();
+    let RenderedBatch {
+        images,
+        errors: batch_errors,
+        tally,
+        results,
+    } = process_replay_batch(
+        data,
+        &replays,
+        &parsed,
+        flags,
+        FailureSite::new(msg.guild_id, msg.channel_id),
+    )
+    .await;
+    data.record_results(msg.guild_id, msg.channel_id, results);
+    errors.extend(batch_errors);
     let batch_count = replays.len().min(BATCH_SIZE);
+    let dates = date_separators(&replays[..batch_count]);
     let remaining: Vec<(String, Vec<u8>)> = if replays.len() > batch_count {
         replays.into_iter().skip(batch_count).collect()
     } else {
         Vec::new()
     };
+    let remaining_parsed: Vec<_> = parsed.into_iter().skip(batch_count).collect();
 
     // TOCTOU-safe: lock -> cleanup -> capacity check -> insert, all under one guard
     let pending_key = if !remaining.is_empty() {
-        let mut map = data.lock_pending_replays();
-        cleanup_expired_pending_inner(&mut map);
-        if map.len() >= super::constants::MAX_PENDING_ENTRIES {
-            tracing::warn!("Pending replays map is full, discarding remaining replays");
-            None
-        } else {
-            let pending = PendingReplays {
-                replays: remaining,
-                total: effective_total,
-                shown: batch_count,
-                created_at: Instant::now(),
-                channel_id: msg.channel_id,
-            };
-            map.insert(key.to_string(), pending);
-            Some(key.to_string())
-        }
+        data.pending_replays.write(|map| {
+            cleanup_expired_pending_inner(map);
+            if map.len() >= super::constants::MAX_PENDING_ENTRIES {
+                tracing::warn!("Pending replays map is full, discarding remaining replays");
+                None
+            } else {
+                let pending = PendingReplays {
+                    replays: remaining,
+                    parsed: remaining_parsed,
+                    total: effective_total,
+                    shown: batch_count,
+                    created_at: Instant::now(),
+                    channel_id: msg.channel_id,
+                    locale,
+                    tally,
+                    flags,
+                    prefetched: None,
+                    message_id: None,
+                };
+                map.insert(key.to_string(), pending);
+                Some(key.to_string())
+            }
+        })
         // guard drops here, before any .await
     } else {
         None
@@ -329,19 +1688,140 @@ async fn process_archive_replays(
     } else {
         effective_total
     };
-    send_batch_message(
+    if data.in_flight.is_cancelled(msg.id) {
+        if let Some(key) = pending_key {
+            data.pending_replays.write(|map| map.remove(&key));
+        }
+        return;
+    }
+    let replies = send_batch_message(
         ctx,
         BatchMessageArgs {
             channel_id: msg.channel_id,
-            attachments,
+            files: images,
+            limits: data.attachment_limits,
             errors: &errors,
             shown,
             total: effective_total,
             pending_key: pending_key.as_deref(),
-            cap_note: cap_note.as_deref(),
+            cap_note: cap_note.as_ref(),
+            dates,
+            tally,
+            locale,
         },
     )
     .await;
+    // The button goes on the last message
+    if let (Some(key), Some(&last)) = (&pending_key, replies.last()) {
+        data.pending_replays
+            .write(|map| set_button_message(map, key, last));
+    }
+    for reply in replies {
+        track_reply(ctx, msg, data, Some(reply), pending_key.as_deref()).await;
+    }
+    if let Some(key) = pending_key {
+        spawn_prefetch(data, &key);
+    }
+}
+
+/// Whether a message must @mention the bot to be processed. Forwarded
+/// messages can't contain mentions and mentioning a bot in its own DM is
+/// unnatural, so both are processed without one.
+fn mention_required(guild_id: Option<serenity::GuildId>, is_forwarded: bool) -> bool {
+    guild_id.is_some() && !is_forwarded
+}
+
+/// Whether the message asks for the animated reveal (a standalone "reveal" word)
+fn wants_reveal(content: &str) -> bool {
+    has_flag(content, "reveal")
+}
+
+/// Whether the message asks for the chunk dump (a standalone "debug" word)
+fn wants_debug(content: &str) -> bool {
+    has_flag(content, "debug")
+}
+
+/// Whether the author may manage the guild the message was posted in (never
+/// in DMs). Lookup failures count as no.
+async fn can_manage_guild(ctx: &serenity::Context, msg: &serenity::Message) -> bool {
+    let Some(guild_id) = msg.guild_id else {
+        return false;
+    };
+    let member = match guild_id.member(ctx, msg.author.id).await {
+        Ok(member) => member,
+        Err(e) => {
+            tracing::warn!(msg_id = %msg.id, "Failed to fetch member for debug check: {}", e);
+            return false;
+        }
+    };
+    match guild_id.to_partial_guild(ctx).await {
+        Ok(guild) => guild.member_permissions(&member).manage_guild(),
+        Err(e) => {
+            tracing::warn!(msg_id = %msg.id, "Failed to fetch guild for debug check: {}", e);
+            false
+        }
+    }
+}
+
+/// Whether a user may manage messages in the guild a reaction was added in
+/// (never in DMs). Lookup failures count as no.
+async fn can_manage_messages(
+    ctx: &serenity::Context,
+    guild_id: Option<serenity::GuildId>,
+    user_id: serenity::UserId,
+    member: Option<&serenity::Member>,
+) -> bool {
+    let Some(guild_id) = guild_id else {
+        return false;
+    };
+    let member = match member {
+        Some(member) => member.clone(),
+        None => match guild_id.member(ctx, user_id).await {
+            Ok(member) => member,
+            Err(e) => {
+                tracing::warn!("Failed to fetch member for delete check: {}", e);
+                return false;
+            }
+        },
+    };
+    match guild_id.to_partial_guild(ctx).await {
+        Ok(guild) => guild.member_permissions(&member).manage_messages(),
+        Err(e) => {
+            tracing::warn!("Failed to fetch guild for delete check: {}", e);
+            false
+        }
+    }
+}
+
+/// Text between the first pair of quotes in a message (straight or curly),
+/// used to pick replays out of an archive by name. Mentions never contain
+/// quotes, so they need no stripping.
+fn quoted_filter(content: &str) -> Option<String> {
+    let (_, rest) = content.split_once(['"', '“'])?;
+    let (quoted, _) = rest.split_once(['"', '”'])?;
+    let filter = quoted.trim();
+    (!filter.is_empty()).then(|| filter.to_string())
+}
+
+/// Whether results for the message hide player names: it asks with a
+/// standalone "anon" word, or its guild always does
+fn wants_anonymity(data: &Data, msg: &serenity::Message) -> bool {
+    anonymity_requested(&msg.content, msg.guild_id, &data.anonymous_guilds)
+}
+
+fn anonymity_requested(
+    content: &str,
+    guild_id: Option<serenity::GuildId>,
+    anonymous_guilds: &[serenity::GuildId],
+) -> bool {
+    has_flag(content, "anon") || guild_id.is_some_and(|id| anonymous_guilds.contains(&id))
+}
+
+/// Whether the message contains `flag` as a standalone word (any case)
+fn has_flag(content: &str, flag: &str) -> bool {
+    content
+        .split_whitespace()
+        .any(|word| word.eq_ignore_ascii_case(flag))
 }
 
 /// Check if the bot was mentioned (direct user mention or bot's managed role mention)
@@ -376,3 +1856,412 @@ async fn is_bot_mentioned(
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::channel_scope::ChannelScopes;
+    use crate::bot::failure_report::FailureReporter;
+    use crate::bot::in_flight::InFlightUploads;
+    use crate::bot::player_details::PlayerDetails;
+    use crate::bot::posted_replays::PostedReplays;
+    use crate::bot::readiness::Readiness;
+    use crate::bot::seen_messages::SeenMessages;
+    use crate::bot::sent_results::{DeleteReaction, SentResults};
+    use crate::bot::shared_map::{PoisonPolicy, SharedMap};
+    use crate::bot::temp_dirs::TempDirRegistry;
+    use crate::bot::uploads::AttachmentLimits;
+    use crate::bot::winner_template::WinnerTemplates;
+    use crate::bot::work_queue::WorkQueue;
+    use crate::logging::{capture_json, json_lines};
+    use crate::parser::parse_replay;
+    use crate::renderer::{FactionIcons, RenderOptions, load_font, load_map};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn test_data() -> Data {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let font_data = std::fs::read(assets.join("fonts").join("NotoSans-Bold.ttf")).unwrap();
+        let (map_image, layout) = load_map("map wor rhun", &assets).unwrap();
+        Data {
+            font: Arc::new(load_font(&font_data).unwrap()),
+            map_image: Arc::new(map_image),
+            layout: Arc::new(layout),
+            faction_icons: Arc::new(FactionIcons::default()),
+            render_options: RenderOptions::default(),
+            bot_id: serenity::UserId::new(1),
+            pending_replays: Arc::new(SharedMap::new("pending_replays", PoisonPolicy::Clear)),
+            cooldowns: SharedMap::new("cooldowns", PoisonPolicy::Recover),
+            default_locale: Locale::En,
+            guild_locales: SharedMap::new("guild_locales", PoisonPolicy::Recover),
+            reply_style: ReplyStyle::Plain,
+            in_flight: InFlightUploads::new(),
+            seen_messages: SeenMessages::new(),
+            sent_results: SentResults::new(),
+            delete_reaction: DeleteReaction::default(),
+            player_details: PlayerDetails::new(),
+            posted_replays: PostedReplays::default(),
+            anonymous_guilds: Vec::new(),
+            allowed_webhooks: vec![],
+            channel_scopes: ChannelScopes::default(),
+            readiness: Readiness::new(),
+            results_store: None,
+            winner_templates: WinnerTemplates::default(),
+            winner_template_overrides: SharedMap::new(
+                "winner_template_overrides",
+                PoisonPolicy::Recover,
+            ),
+            attachment_limits: AttachmentLimits::default(),
+            temp_dirs: TempDirRegistry::new(),
+            work_queue: Arc::new(WorkQueue::new()),
+            failure_reporter: Arc::new(FailureReporter::default()),
+        }
+    }
+
+    fn valid_replay_bytes() -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BFME2RPL");
+        data.extend_from_slice(&1700000000u32.to_le_bytes());
+        data.extend_from_slice(&1700001000u32.to_le_bytes());
+        data.extend_from_slice(
+            b"M=maps/map wor rhun;S=HAlice,12345678,8094,TT,0,-1,0,0,0,1,0:HBob,87654321,8094,TT,1,-1,1,1,0,1,0",
+        );
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn mention_is_required_only_for_direct_guild_messages() {
+        let guild = Some(serenity::GuildId::new(7));
+        assert!(mention_required(guild, false));
+        assert!(!mention_required(guild, true));
+        assert!(!mention_required(None, false));
+        assert!(!mention_required(None, true));
+    }
+
+    #[test]
+    fn reveal_flag_is_a_standalone_word() {
+        assert!(wants_reveal("<@123> reveal"));
+        assert!(wants_reveal("REVEAL <@123>"));
+        assert!(!wants_reveal("<@123>"));
+        assert!(!wants_reveal("<@123> revealed"));
+        assert!(wants_debug("<@123> DEBUG"));
+        assert!(!wants_debug("<@123> debugging"));
+        assert!(has_flag("<@123> Stats", "stats"));
+        assert!(!has_flag("<@123> statsheet", "stats"));
+    }
+
+    #[test]
+    fn anonymity_is_asked_for_or_set_per_guild() {
+        let guild = Some(serenity::GuildId::new(7));
+        assert!(anonymity_requested("<@123> anon", None, &[]));
+        assert!(!anonymity_requested("<@123> anonymous", guild, &[]));
+        assert!(anonymity_requested(
+            "<@123>",
+            guild,
+            &[serenity::GuildId::new(7)]
+        ));
+        assert!(!anonymity_requested(
+            "<@123>",
+            None,
+            &[serenity::GuildId::new(7)]
+        ));
+    }
+
+    #[test]
+    fn winner_template_overrides_come_before_the_configured_templates() {
+        use crate::bot::winner_template::WinnerTemplate;
+        let mut data = test_data();
+        data.winner_templates = WinnerTemplates::parse("*={side} won").unwrap();
+        let guild = serenity::GuildId::new(42);
+        let preview = |data: &Data, guild| {
+            data.winner_template_for(guild)
+                .map(|template| template.preview(Locale::En))
+        };
+        data.winner_template_overrides
+            .insert(guild, WinnerTemplate::parse("Zafer: {side}").unwrap());
+        assert_eq!(
+            preview(&data, Some(guild)).as_deref(),
+            Some("Zafer: Left Team")
+        );
+        assert_eq!(
+            preview(&data, Some(serenity::GuildId::new(7))).as_deref(),
+            Some("Left Team won")
+        );
+
+        data.winner_template_overrides
+            .write(|overrides| overrides.remove(&guild));
+        assert_eq!(
+            preview(&data, Some(guild)).as_deref(),
+            Some("Left Team won")
+        );
+    }
+
+    #[test]
+    fn anonymized_result_text_names_no_one() {
+        use crate::models::{Faction, PlayerBuilder, Spectator};
+        let player = |name: &str, uid: &str, team, slot| {
+            let mut player = PlayerBuilder::test_player(name, team, slot, Faction::Men);
+            player.uid = Some(uid.to_string());
+            player
+        };
+        let replay = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![
+                player("Alice", "1A2B3C4D", 1, 0),
+                player("Bob", "5E6F7A8B", 2, 1),
+            ],
+        )
+        .with_spectators(vec![Spectator {
+            name: "Watcher".to_string(),
+            uid: Some("9C0D1E2F".to_string()),
+            slot: Some(2),
+        }])
+        .with_first_defeat(Some((1, 600)));
+        let options = RenderOptions {
+            anonymize: true,
+            ..Default::default()
+        };
+        let (shown, title) = shown_replay(&replay, "Alice_vs_Bob.BfME2Replay", options);
+        let text = format!(
+            "{} {:?}",
+            summarize_replay(&shown),
+            build_result_embed(&shown, &title, None)
+        );
+        for hidden in [
+            "Alice", "Bob", "Watcher", "1A2B3C4D", "5E6F7A8B", "9C0D1E2F",
+        ] {
+            assert!(!text.contains(hidden), "{} in {}", hidden, text);
+        }
+        assert!(text.contains("Player 1") && text.contains("Obs 1"));
+        assert_eq!(title, "map wor rhun");
+        // Stats are recorded from the real game
+        assert_eq!(replay.players[0].name, "Alice");
+    }
+
+    #[test]
+    fn archive_filter_is_the_quoted_text() {
+        assert_eq!(
+            quoted_filter("<@123> \"vs ClanX\" please").as_deref(),
+            Some("vs ClanX")
+        );
+        assert_eq!(
+            quoted_filter("<@123> “Final İzmir”").as_deref(),
+            Some("Final İzmir")
+        );
+        assert_eq!(quoted_filter("<@123> stats"), None);
+        assert_eq!(quoted_filter("<@123> \"  \""), None);
+        assert_eq!(quoted_filter("<@123> \"unclosed"), None);
+    }
+
+    #[test]
+    fn metrics_endpoint_reports_parsed_replays() {
+        parse_recorded(
+            &valid_replay_bytes(),
+            SpotRegions::default(),
+            Duration::from_millis(SINGLE_PARSE_BUDGET_MS),
+            &tracing::Span::none(),
+        )
+        .unwrap();
+        let response =
+            metrics::http_response(b"GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n", true, "READY");
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+        let parsed: u64 = response
+            .lines()
+            .find_map(|line| line.strip_prefix("replays_parsed_total "))
+            .and_then(|n| n.parse().ok())
+            .unwrap();
+        assert!(parsed >= 1);
+        assert!(response.contains("replay_parse_duration_seconds_count"));
+        assert!(response.contains("raw_scan_recoveries_total"));
+    }
+
+    #[tokio::test]
+    async fn two_game_file_keeps_the_message_flags() {
+        let data = test_data();
+        let file = [valid_replay_bytes(), valid_replay_bytes()].concat();
+        let games = split_games(&file);
+        assert_eq!(games.len(), 2);
+        let flags = ReplyFlags {
+            reveal: true,
+            debug: false,
+            anonymize: true,
+        };
+        let reply = multi_game_reply("two.BfME2Replay", &games, flags);
+        assert_eq!(reply.flags, flags);
+        assert_eq!(reply.replays[1].0, "two.BfME2Replay (game 2)");
+
+        let batch = process_replay_batch(
+            &data,
+            &reply.replays,
+            &reply.parsed,
+            reply.flags,
+            FailureSite::default(),
+        )
+        .await;
+        assert_eq!(batch.images.len(), 2);
+        for (i, image) in batch.images.iter().enumerate() {
+            assert_eq!(image.name, format!("replay_{}.gif", i + 1));
+            assert!(image.bytes.starts_with(b"GIF8"));
+            // Anonymized alt text names no one
+            let alt_text = image.description.as_deref().unwrap();
+            assert!(!alt_text.contains("Alice"), "{}", alt_text);
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_with_valid_and_corrupt_replay_yields_image_and_error() {
+        let data = test_data();
+        let replays = vec![
+            ("good.BfME2Replay".to_string(), valid_replay_bytes()),
+            ("bad.BfME2Replay".to_string(), b"not a replay".to_vec()),
+        ];
+        let batch = process_replay_batch(
+            &data,
+            &replays,
+            &[],
+            ReplyFlags::default(),
+            FailureSite::default(),
+        )
+        .await;
+        assert_eq!(batch.images.len(), 1);
+        let alt_text = batch.images[0].description.as_deref().unwrap();
+        assert!(alt_text.starts_with("1v1 on "), "{}", alt_text);
+        assert_eq!(batch.errors.len(), 1);
+        assert_eq!(batch.tally.errors, 1);
+        assert!(
+            batch.errors[0]
+                .render(Locale::En)
+                .starts_with("bad.BfME2Replay: ")
+        );
+    }
+
+    #[tokio::test]
+    async fn prefetch_is_skipped_while_every_worker_is_busy() {
+        use crate::bot::constants::MAX_CONCURRENT_ARCHIVES;
+        use crate::bot::pagination::spawn_prefetch;
+        use crate::bot::setup::PendingReplays;
+        use crate::bot::tally::ArchiveTally;
+
+        let data = test_data();
+        let entry = PendingReplays {
+            replays: vec![("a.BfME2Replay".to_string(), valid_replay_bytes())],
+            parsed: Vec::new(),
+            total: 11,
+            shown: 10,
+            created_at: std::time::Instant::now(),
+            channel_id: serenity::ChannelId::new(1),
+            locale: Locale::En,
+            tally: ArchiveTally::default(),
+            flags: ReplyFlags::default(),
+            prefetched: None,
+            message_id: None,
+        };
+        data.pending_replays.insert("k".to_string(), entry);
+        let prefetched = || {
+            data.pending_replays
+                .read(|map| map["k"].prefetched.is_some())
+        };
+
+        let busy: Vec<_> = std::iter::from_fn(|| data.work_queue.try_permit()).collect();
+        assert_eq!(busy.len(), MAX_CONCURRENT_ARCHIVES);
+        assert!(!spawn_prefetch(&data, "k"));
+        tokio::task::yield_now().await;
+        assert!(!prefetched());
+
+        drop(busy);
+        assert!(spawn_prefetch(&data, "k"));
+        for _ in 0..500 {
+            if prefetched() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(prefetched());
+        // The slot is given back once the page is rendered
+        let free: Vec<_> = std::iter::from_fn(|| data.work_queue.try_permit()).collect();
+        assert_eq!(free.len(), MAX_CONCURRENT_ARCHIVES);
+    }
+
+    #[tokio::test]
+    async fn batch_renders_preparsed_results_without_parsing_again() {
+        let data = test_data();
+        // The bytes no longer parse; only the pre-parsed results can render
+        let replays = vec![
+            ("good.BfME2Replay".to_string(), Vec::new()),
+            ("bad.BfME2Replay".to_string(), Vec::new()),
+        ];
+        let parsed = vec![
+            parse_replay(&valid_replay_bytes()),
+            Err(ReplayError::NoPlayers),
+        ];
+        let batch = process_replay_batch(
+            &data,
+            &replays,
+            &parsed,
+            ReplyFlags::default(),
+            FailureSite::default(),
+        )
+        .await;
+        assert_eq!(batch.images.len(), 1);
+        assert_eq!(batch.tally.errors, 1);
+        assert_eq!(
+            batch.errors[0].render(Locale::En),
+            UserMessage::for_file(
+                "bad.BfME2Replay",
+                &UserMessage::for_replay_error(&ReplayError::NoPlayers)
+            )
+            .render(Locale::En)
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_replays_get_spans_with_parse_and_render_fields() {
+        let data = test_data();
+        let (subscriber, buffer) = capture_json();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let good = valid_replay_bytes();
+        let replays = vec![
+            ("good.BfME2Replay".to_string(), good.clone()),
+            ("bad.BfME2Replay".to_string(), b"not a replay".to_vec()),
+        ];
+        process_replay_batch(
+            &data,
+            &replays,
+            &[],
+            ReplyFlags::default(),
+            FailureSite::default(),
+        )
+        .await;
+
+        let processed: Vec<serde_json::Value> = json_lines(buffer)
+            .into_iter()
+            .filter(|line| line["fields"]["message"] == "Replay processed")
+            .collect();
+        assert_eq!(processed.len(), 2);
+        for line in &processed {
+            assert_eq!(line["spans"][0]["name"], "process_replay_batch");
+            assert_eq!(line["spans"][0]["replays"], 2);
+        }
+
+        // The replay span is the event's explicit parent, so it is the
+        // current span rather than part of the entered span list
+        let good_span = &processed[0]["span"];
+        assert_eq!(good_span["name"], "replay");
+        assert_eq!(good_span["file"], "good.BfME2Replay");
+        assert_eq!(good_span["content_hash"], hex_hash(&good));
+        let fingerprint = parse_replay(&good).unwrap().fingerprint();
+        assert_eq!(good_span["fingerprint"], format!("{:016x}", fingerprint));
+        assert!(good_span["parse_ms"].is_u64());
+        assert!(good_span["render_ms"].is_u64());
+        assert!(good_span["output_bytes"].as_u64().unwrap() > 0);
+        assert_eq!(good_span["outcome"], "ok");
+
+        let bad_span = &processed[1]["span"];
+        assert_eq!(bad_span["file"], "bad.BfME2Replay");
+        assert!(bad_span.get("fingerprint").is_none());
+        assert!(bad_span.get("render_ms").is_none());
+        assert_eq!(bad_span["outcome"], "invalid_header");
+    }
+}
@@ -0,0 +1,390 @@
+use crate::models::Side;
+
+use super::results_store::FactionStats;
+use super::tally::ArchiveTally;
+
+/// Language for bot replies (the rendered image stays English)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Tr,
+}
+
+impl Locale {
+    /// Parse a Discord locale tag such as `en-US` or `tr`
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let lang = tag.split(['-', '_']).next().unwrap_or("");
+        if lang.eq_ignore_ascii_case("en") {
+            Some(Locale::En)
+        } else if lang.eq_ignore_ascii_case("tr") {
+            Some(Locale::Tr)
+        } else {
+            None
+        }
+    }
+}
+
+/// Every reply the bot can send. Each locale's `match` below is exhaustive,
+/// so a new key without translations fails to build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum MessageKey {
+    ReplayTooLarge,
+    ReplayDownloadFailed,
+    ArchiveTooLarge {
+        max_mb: u64,
+    },
+    ArchiveDownloadFailed,
+    ArchiveExtractFailed,
+    ArchiveEmpty,
+    ArchiveEncrypted,
+    ArchiveCorrupted,
+    ArchiveUnsupported,
+    Queued {
+        position: usize,
+    },
+    QueueStarted,
+    QueueFull,
+    QueueExpired,
+    QueueCancelled,
+    InternalError,
+    UploadFailed,
+    WrongChannel,
+    ButtonExpired,
+    LinkElsewhere,
+    LinkNoAccess,
+    LinkEmpty,
+    Showing {
+        shown: usize,
+        total: usize,
+    },
+    ArchiveCapped {
+        total: usize,
+        processed: usize,
+    },
+    ArchiveSoFar(ArchiveTally),
+    DuplicatesMerged(usize),
+    DuplicatePlayer(String),
+    ReplayTruncated,
+    DateGroup {
+        date: Option<String>,
+        replays: usize,
+    },
+    RestartSkipped {
+        game: usize,
+    },
+    NoMatchingReplays {
+        filter: String,
+    },
+    AvailableReplay(String),
+    InvalidReplay,
+    /// A replay of another SAGE game, named
+    WrongGame(String),
+    UnsupportedMap(String),
+    NoPlayers,
+    UnreadableReplay,
+    RenderFailed,
+    FileTooSmall,
+    SaveGame,
+    ImageFile,
+    NoMapInHeader,
+    WinningSide {
+        side: Side,
+        likely: bool,
+    },
+    StatsDisabled,
+    StatsNoGames {
+        days: u32,
+    },
+    StatsSummary {
+        games: usize,
+        days: u32,
+    },
+    FactionTable(FactionStats),
+    StatsSmallSample {
+        min: usize,
+    },
+    /// A template was set, with its preview
+    WinnerTemplateSet(String),
+    /// Why a template was rejected
+    WinnerTemplateInvalid(String),
+    WinnerTemplateReset,
+    WinnerTemplatePreview(String),
+    NoWinnerTemplate,
+}
+
+pub(super) fn translate(key: &MessageKey, locale: Locale) -> String {
+    match locale {
+        Locale::En => english(key),
+        Locale::Tr => turkish(key),
+    }
+}
+
+fn english(key: &MessageKey) -> String {
+    match key {
+        MessageKey::ReplayTooLarge => "Replay file too large (max 5MB)".to_string(),
+        MessageKey::ReplayDownloadFailed => "Failed to download replay file".to_string(),
+        MessageKey::ArchiveTooLarge { max_mb } => format!("Archive too large (max {}MB)", max_mb),
+        MessageKey::ArchiveDownloadFailed => "Failed to download archive".to_string(),
+        MessageKey::ArchiveExtractFailed => "Failed to extract archive".to_string(),
+        MessageKey::ArchiveEmpty => "No .BfME2Replay files found in archive".to_string(),
+        MessageKey::ArchiveEncrypted => "Archive is password-protected".to_string(),
+        MessageKey::ArchiveCorrupted => "Archive appears corrupted".to_string(),
+        MessageKey::ArchiveUnsupported => "Archive format is not supported".to_string(),
+        MessageKey::Queued { position } => format!("Queued — position {}", position),
+        MessageKey::QueueStarted => "Processing…".to_string(),
+        MessageKey::QueueFull => "The bot is busy, try again later".to_string(),
+        MessageKey::QueueExpired => "Waited too long in the queue, please upload again".to_string(),
+        MessageKey::QueueCancelled => "Upload deleted, removed from the queue".to_string(),
+        MessageKey::InternalError => "Internal error processing replay".to_string(),
+        MessageKey::UploadFailed => "Failed to upload image, try again".to_string(),
+        MessageKey::WrongChannel => {
+            "This button is only valid in the original channel.".to_string()
+        }
+        MessageKey::ButtonExpired => {
+            "This button has expired. Please re-upload the replays.".to_string()
+        }
+        MessageKey::LinkElsewhere => {
+            "Only links to messages in this server (or this DM) can be read".to_string()
+        }
+        MessageKey::LinkNoAccess => {
+            "Can't read the linked message (missing access, or it was deleted)".to_string()
+        }
+        MessageKey::LinkEmpty => "No replays or archives in the linked message".to_string(),
+        MessageKey::Showing { shown, total } => {
+            format!("Showing {} of {} replays", shown, total)
+        }
+        MessageKey::ArchiveCapped { total, processed } => {
+            format!("Found {} replays, processing first {}", total, processed)
+        }
+        MessageKey::ArchiveSoFar(tally) => {
+            let mut line = format!(
+                "So far: Left {} / Right {} / Crashed {} / Unknown {}",
+                tally.left, tally.right, tally.crashed, tally.unknown
+            );
+            if tally.errors > 0 {
+                line.push_str(&format!(" / Errors {}", tally.errors));
+            }
+            line
+        }
+        MessageKey::DuplicatesMerged(merged) => format!(
+            "Merged {} duplicate cop{} of the same game",
+            merged,
+            if *merged == 1 { "y" } else { "ies" }
+        ),
+        MessageKey::RestartSkipped { game } => format!("restart of game {}, skipped", game),
+        MessageKey::DuplicatePlayer(name) => format!("Duplicate player entry ignored: `{}`", name),
+        MessageKey::ReplayTruncated => "Replay appears truncated — result may be wrong".to_string(),
+        MessageKey::DateGroup { date, replays } => format!(
+            "— {} — {} replay{}",
+            date.as_deref().unwrap_or("Unknown date"),
+            replays,
+            if *replays == 1 { "" } else { "s" }
+        ),
+        MessageKey::NoMatchingReplays { filter } => format!(
+            "No replay in the archive matches `{}`. Available replays:",
+            filter
+        ),
+        MessageKey::AvailableReplay(name) => format!("- `{}`", name),
+        MessageKey::InvalidReplay => "Invalid replay file".to_string(),
+        MessageKey::WrongGame(game) => format!(
+            "This looks like a {} replay — this bot only supports BFME2/ROTWK",
+            game
+        ),
+        MessageKey::UnsupportedMap(map_name) => format!("Not a Rhun game (map: {})", map_name),
+        MessageKey::NoPlayers => "No players found in replay".to_string(),
+        MessageKey::UnreadableReplay => "Could not read replay file".to_string(),
+        MessageKey::RenderFailed => "Failed to render replay image".to_string(),
+        MessageKey::FileTooSmall => "File too small to be a replay".to_string(),
+        MessageKey::SaveGame => "This looks like a save game, not a replay".to_string(),
+        MessageKey::ImageFile => "This looks like an image, not a replay".to_string(),
+        MessageKey::NoMapInHeader => "Replay header has no map name".to_string(),
+        MessageKey::WinningSide { side, likely } => {
+            let team = match side {
+                Side::Left => "Left Team",
+                Side::Right => "Right Team",
+            };
+            if *likely {
+                format!("{} (likely)", team)
+            } else {
+                team.to_string()
+            }
+        }
+        MessageKey::StatsDisabled => "Result history is not enabled on this bot".to_string(),
+        MessageKey::StatsNoGames { days } => {
+            format!("No games parsed here in the last {} days", days)
+        }
+        MessageKey::StatsSummary { games, days } => {
+            format!("Factions over {} games from the last {} days", games, days)
+        }
+        MessageKey::FactionTable(stats) => stats.table(["Faction", "Picks", "Wins", "Win%"]),
+        MessageKey::StatsSmallSample { min } => {
+            format!("* fewer than {} decided games, read with care", min)
+        }
+        MessageKey::WinnerTemplateSet(preview) => {
+            format!("Winner template set. Preview: {}", preview)
+        }
+        MessageKey::WinnerTemplateInvalid(reason) => format!("Invalid template: {}", reason),
+        MessageKey::WinnerTemplateReset => "Winner template reset to the bot's default".to_string(),
+        MessageKey::WinnerTemplatePreview(preview) => format!("Preview: {}", preview),
+        MessageKey::NoWinnerTemplate => {
+            "No winner template here; results use the standard winner text".to_string()
+        }
+    }
+}
+
+fn turkish(key: &MessageKey) -> String {
+    match key {
+        MessageKey::ReplayTooLarge => "Replay dosyası çok büyük (en fazla 5MB)".to_string(),
+        MessageKey::ReplayDownloadFailed => "Replay dosyası indirilemedi".to_string(),
+        MessageKey::ArchiveTooLarge { max_mb } => {
+            format!("Arşiv çok büyük (en fazla {}MB)", max_mb)
+        }
+        MessageKey::ArchiveDownloadFailed => "Arşiv indirilemedi".to_string(),
+        MessageKey::ArchiveExtractFailed => "Arşiv açılamadı".to_string(),
+        MessageKey::ArchiveEmpty => "Arşivde .BfME2Replay dosyası bulunamadı".to_string(),
+        MessageKey::ArchiveEncrypted => "Arşiv şifre korumalı".to_string(),
+        MessageKey::ArchiveCorrupted => "Arşiv bozuk görünüyor".to_string(),
+        MessageKey::ArchiveUnsupported => "Arşiv biçimi desteklenmiyor".to_string(),
+        MessageKey::Queued { position } => format!("Sırada — {}. sıra", position),
+        MessageKey::QueueStarted => "İşleniyor…".to_string(),
+        MessageKey::QueueFull => "Bot şu an meşgul, daha sonra tekrar deneyin".to_string(),
+        MessageKey::QueueExpired => "Sırada çok uzun bekledi, lütfen tekrar yükleyin".to_string(),
+        MessageKey::QueueCancelled => "Yükleme silindi, sıradan çıkarıldı".to_string(),
+        MessageKey::InternalError => "Replay işlenirken dahili bir hata oluştu".to_string(),
+        MessageKey::UploadFailed => "Görsel yüklenemedi, tekrar deneyin".to_string(),
+        MessageKey::WrongChannel => "Bu buton yalnızca orijinal kanalda geçerlidir.".to_string(),
+        MessageKey::ButtonExpired => {
+            "Bu butonun süresi doldu. Lütfen replay dosyalarını tekrar yükleyin.".to_string()
+        }
+        MessageKey::LinkElsewhere => {
+            "Yalnızca bu sunucudaki (veya bu DM'deki) mesajların bağlantıları okunabilir"
+                .to_string()
+        }
+        MessageKey::LinkNoAccess => {
+            "Bağlantısı verilen mesaj okunamadı (erişim yok veya silinmiş)".to_string()
+        }
+        MessageKey::LinkEmpty => "Bağlantısı verilen mesajda replay veya arşiv yok".to_string(),
+        MessageKey::Showing { shown, total } => {
+            format!("{} replaydan {} tanesi gösteriliyor", total, shown)
+        }
+        MessageKey::ArchiveCapped { total, processed } => {
+            format!(
+                "{} replay bulundu, ilk {} tanesi işleniyor",
+                total, processed
+            )
+        }
+        MessageKey::ArchiveSoFar(tally) => {
+            let mut line = format!(
+                "Şu ana kadar: Sol {} / Sağ {} / Çöken {} / Bilinmeyen {}",
+                tally.left, tally.right, tally.crashed, tally.unknown
+            );
+            if tally.errors > 0 {
+                line.push_str(&format!(" / Hatalı {}", tally.errors));
+            }
+            line
+        }
+        MessageKey::DuplicatesMerged(merged) => {
+            format!("Aynı oyunun {} kopyası birleştirildi", merged)
+        }
+        MessageKey::RestartSkipped { game } => {
+            format!("{}. oyunun yeniden başlatılması, atlandı", game)
+        }
+        MessageKey::DuplicatePlayer(name) => {
+            format!("Yinelenen oyuncu kaydı yok sayıldı: `{}`", name)
+        }
+        MessageKey::ReplayTruncated => "Replay eksik görünüyor — sonuç yanlış olabilir".to_string(),
+        MessageKey::DateGroup { date, replays } => format!(
+            "— {} — {} replay",
+            date.as_deref().unwrap_or("Bilinmeyen tarih"),
+            replays
+        ),
+        MessageKey::NoMatchingReplays { filter } => format!(
+            "Arşivde `{}` ile eşleşen replay yok. Mevcut replaylar:",
+            filter
+        ),
+        MessageKey::AvailableReplay(name) => format!("- `{}`", name),
+        MessageKey::InvalidReplay => "Geçersiz replay dosyası".to_string(),
+        MessageKey::WrongGame(game) => format!(
+            "Bu bir {} replay'i gibi görünüyor — bot yalnızca BFME2/ROTWK destekliyor",
+            game
+        ),
+        MessageKey::UnsupportedMap(map_name) => {
+            format!("Rhun oyunu değil (harita: {})", map_name)
+        }
+        MessageKey::NoPlayers => "Replay'de oyuncu bulunamadı".to_string(),
+        MessageKey::UnreadableReplay => "Replay dosyası okunamadı".to_string(),
+        MessageKey::RenderFailed => "Replay görseli oluşturulamadı".to_string(),
+        MessageKey::FileTooSmall => "Dosya replay olamayacak kadar küçük".to_string(),
+        MessageKey::SaveGame => {
+            "Bu bir replay değil, kayıtlı oyun dosyası gibi görünüyor".to_string()
+        }
+        MessageKey::ImageFile => "Bu bir replay değil, görsel dosyası gibi görünüyor".to_string(),
+        MessageKey::NoMapInHeader => "Replay başlığında harita adı yok".to_string(),
+        MessageKey::WinningSide { side, likely } => {
+            let team = match side {
+                Side::Left => "Sol Takım",
+                Side::Right => "Sağ Takım",
+            };
+            if *likely {
+                format!("{} (muhtemel)", team)
+            } else {
+                team.to_string()
+            }
+        }
+        MessageKey::StatsDisabled => "Bu botta sonuç geçmişi kapalı".to_string(),
+        MessageKey::StatsNoGames { days } => {
+            format!("Son {} günde burada işlenmiş oyun yok", days)
+        }
+        MessageKey::StatsSummary { games, days } => {
+            format!("Son {} gündeki {} oyunda ırklar", days, games)
+        }
+        MessageKey::FactionTable(stats) => stats.table(["Irk", "Seçim", "Galibiyet", "Kazanma%"]),
+        MessageKey::StatsSmallSample { min } => {
+            format!("* {} oyundan az sonuç var, dikkatli yorumlayın", min)
+        }
+        MessageKey::WinnerTemplateSet(preview) => {
+            format!("Kazanan şablonu ayarlandı. Önizleme: {}", preview)
+        }
+        MessageKey::WinnerTemplateInvalid(reason) => format!("Geçersiz şablon: {}", reason),
+        MessageKey::WinnerTemplateReset => {
+            "Kazanan şablonu botun varsayılanına döndürüldü".to_string()
+        }
+        MessageKey::WinnerTemplatePreview(preview) => format!("Önizleme: {}", preview),
+        MessageKey::NoWinnerTemplate => {
+            "Burada kazanan şablonu yok; sonuçlarda standart kazanan metni kullanılıyor".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_tags() {
+        assert_eq!(Locale::from_tag("tr"), Some(Locale::Tr));
+        assert_eq!(Locale::from_tag("en-US"), Some(Locale::En));
+        assert_eq!(Locale::from_tag("EN_gb"), Some(Locale::En));
+        assert_eq!(Locale::from_tag("de"), None);
+    }
+
+    #[test]
+    fn turkish_strings_are_selected_for_tr() {
+        assert_eq!(
+            translate(&MessageKey::ReplayTooLarge, Locale::Tr),
+            "Replay dosyası çok büyük (en fazla 5MB)"
+        );
+        assert_eq!(
+            translate(
+                &MessageKey::UnsupportedMap("map helms deep".into()),
+                Locale::Tr
+            ),
+            "Rhun oyunu değil (harita: map helms deep)"
+        );
+        assert_eq!(
+            translate(&MessageKey::ReplayTooLarge, Locale::En),
+            "Replay file too large (max 5MB)"
+        );
+    }
+}
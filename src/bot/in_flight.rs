@@ -0,0 +1,121 @@
+use poise::serenity_prelude as serenity;
+use serenity::MessageId;
+
+use super::shared_map::{PoisonPolicy, SharedMap};
+
+/// An upload whose replies are still being produced
+#[derive(Debug, Default)]
+struct Upload {
+    /// Set when the user deletes the upload; remaining output is suppressed
+    cancelled: bool,
+    /// Replies already posted for it, removed if it gets deleted
+    replies: Vec<MessageId>,
+}
+
+/// Uploads being processed, keyed by the triggering message id
+pub struct InFlightUploads {
+    /// On poison: recover (a stale entry only delays cleanup)
+    uploads: SharedMap<MessageId, Upload>,
+}
+
+impl InFlightUploads {
+    pub fn new() -> Self {
+        Self {
+            uploads: SharedMap::new("In-flight uploads", PoisonPolicy::Recover),
+        }
+    }
+
+    /// Register an upload as being processed
+    pub fn begin(&self, trigger: MessageId) {
+        self.uploads.insert(trigger, Upload::default());
+    }
+
+    /// Drop an upload once processing is done
+    pub fn finish(&self, trigger: MessageId) {
+        self.uploads.write(|map| map.remove(&trigger));
+    }
+
+    /// Whether the user deleted the upload while it was being processed
+    pub fn is_cancelled(&self, trigger: MessageId) -> bool {
+        self.uploads
+            .read(|map| map.get(&trigger).is_some_and(|upload| upload.cancelled))
+    }
+
+    /// Flag an upload as deleted. Returns the replies already posted for it,
+    /// or `None` when it is not being processed.
+    pub fn cancel(&self, trigger: MessageId) -> Option<Vec<MessageId>> {
+        self.uploads.write(|map| {
+            map.get_mut(&trigger).map(|upload| {
+                upload.cancelled = true;
+                std::mem::take(&mut upload.replies)
+            })
+        })
+    }
+
+    /// Track a reply to an upload. Returns false when the upload was deleted
+    /// while the reply was being sent, so the caller removes it itself.
+    pub fn record_reply(&self, trigger: MessageId, reply: MessageId) -> bool {
+        self.uploads.write(|map| match map.get_mut(&trigger) {
+            Some(upload) if upload.cancelled => false,
+            Some(upload) => {
+                upload.replies.push(reply);
+                true
+            }
+            None => true,
+        })
+    }
+}
+
+impl Default for InFlightUploads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u64) -> MessageId {
+        MessageId::new(n)
+    }
+
+    #[test]
+    fn cancel_flags_upload_and_returns_sent_replies() {
+        let in_flight = InFlightUploads::new();
+        in_flight.begin(id(1));
+        assert!(!in_flight.is_cancelled(id(1)));
+        assert!(in_flight.record_reply(id(1), id(10)));
+        assert!(in_flight.record_reply(id(1), id(11)));
+
+        assert_eq!(in_flight.cancel(id(1)), Some(vec![id(10), id(11)]));
+        assert!(in_flight.is_cancelled(id(1)));
+        // A reply that raced the deletion is handed back to the sender
+        assert!(!in_flight.record_reply(id(1), id(12)));
+        // Replies are only returned once
+        assert_eq!(in_flight.cancel(id(1)), Some(vec![]));
+    }
+
+    #[test]
+    fn unknown_and_finished_uploads_are_not_cancelled() {
+        let in_flight = InFlightUploads::new();
+        assert_eq!(in_flight.cancel(id(1)), None);
+        assert!(!in_flight.is_cancelled(id(1)));
+
+        in_flight.begin(id(2));
+        in_flight.finish(id(2));
+        assert_eq!(in_flight.cancel(id(2)), None);
+        assert!(!in_flight.is_cancelled(id(2)));
+        assert!(in_flight.record_reply(id(2), id(20)));
+    }
+
+    #[test]
+    fn uploads_are_tracked_independently() {
+        let in_flight = InFlightUploads::new();
+        in_flight.begin(id(1));
+        in_flight.begin(id(2));
+        in_flight.cancel(id(1));
+        assert!(in_flight.is_cancelled(id(1)));
+        assert!(!in_flight.is_cancelled(id(2)));
+    }
+}
@@ -0,0 +1,204 @@
+use poise::serenity_prelude as serenity;
+
+/// IDs in a message link: guild (`None` for a DM link), channel and message
+pub type MessageLink = (
+    Option<serenity::GuildId>,
+    serenity::ChannelId,
+    serenity::MessageId,
+);
+
+/// Hosts that serve Discord message links
+const LINK_HOSTS: [&str; 6] = [
+    "discord.com",
+    "ptb.discord.com",
+    "canary.discord.com",
+    "discordapp.com",
+    "ptb.discordapp.com",
+    "canary.discordapp.com",
+];
+
+/// Parse a message link such as
+/// `https://discord.com/channels/<guild or @me>/<channel>/<message>`
+pub fn parse_message_link(url: &str) -> Option<MessageLink> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let (host, path) = rest.split_once('/')?;
+    if !LINK_HOSTS.contains(&host.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    let path = path.split(['?', '#']).next().unwrap_or("");
+    let mut segments = path.strip_prefix("channels/")?.split('/');
+    let (guild, channel, message) = (segments.next()?, segments.next()?, segments.next()?);
+    if segments.any(|s| !s.is_empty()) {
+        return None;
+    }
+    let guild = match guild {
+        "@me" => None,
+        id => Some(serenity::GuildId::new(parse_id(id)?)),
+    };
+    Some((
+        guild,
+        serenity::ChannelId::new(parse_id(channel)?),
+        serenity::MessageId::new(parse_id(message)?),
+    ))
+}
+
+/// A nonzero snowflake
+fn parse_id(text: &str) -> Option<u64> {
+    text.parse().ok().filter(|&id| id > 0)
+}
+
+/// Message links in a message's content, in order, without repeats. Links
+/// wrapped in `<...>` (embed suppressed) count too.
+pub fn message_links(content: &str) -> Vec<MessageLink> {
+    let mut links = Vec::new();
+    for word in content.split_whitespace() {
+        let word = word.trim_start_matches('<').trim_end_matches('>');
+        if let Some(link) = parse_message_link(word)
+            && !links.contains(&link)
+        {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// Why a linked message could not be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkError {
+    /// The link points to another server or another DM
+    Elsewhere,
+    /// The bot or the person who posted the link cannot read the message
+    NoAccess,
+}
+
+/// Fetch the message behind a link posted in `msg`. Only links into the
+/// same server (or the same DM) are followed, and only to channels the
+/// poster can read, so a link can't reveal anything they couldn't see.
+pub async fn fetch_linked_message(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    (guild_id, channel_id, message_id): MessageLink,
+) -> Result<serenity::Message, LinkError> {
+    match (guild_id, msg.guild_id) {
+        (Some(linked), Some(current)) if linked == current => {
+            if !poster_can_read(ctx, linked, channel_id, msg.author.id).await {
+                return Err(LinkError::NoAccess);
+            }
+        }
+        (None, None) if channel_id == msg.channel_id => {}
+        _ => return Err(LinkError::Elsewhere),
+    }
+    ctx.http
+        .get_message(channel_id, message_id)
+        .await
+        .map_err(|e| {
+            tracing::debug!(%message_id, "Linked message not readable: {}", e);
+            LinkError::NoAccess
+        })
+}
+
+/// Whether `user_id` can read the history of a channel (a thread is judged
+/// by its parent). Any failed lookup counts as no access.
+async fn poster_can_read(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    channel_id: serenity::ChannelId,
+    user_id: serenity::UserId,
+) -> bool {
+    let guild_channel = |channel: serenity::Channel| match channel {
+        serenity::Channel::Guild(channel) if channel.guild_id == guild_id => Some(channel),
+        _ => None,
+    };
+    let Some(mut channel) = ctx
+        .http
+        .get_channel(channel_id)
+        .await
+        .ok()
+        .and_then(guild_channel)
+    else {
+        return false;
+    };
+    if channel.thread_metadata.is_some() {
+        let Some(parent) = channel.parent_id else {
+            return false;
+        };
+        let Some(parent) = ctx
+            .http
+            .get_channel(parent)
+            .await
+            .ok()
+            .and_then(guild_channel)
+        else {
+            return false;
+        };
+        channel = parent;
+    }
+    let (Ok(guild), Ok(member)) = (
+        ctx.http.get_guild(guild_id).await,
+        ctx.http.get_member(guild_id, user_id).await,
+    ) else {
+        return false;
+    };
+    guild
+        .user_permissions_in(&channel, &member)
+        .contains(serenity::Permissions::VIEW_CHANNEL | serenity::Permissions::READ_MESSAGE_HISTORY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(guild: Option<u64>, channel: u64, message: u64) -> MessageLink {
+        (
+            guild.map(serenity::GuildId::new),
+            serenity::ChannelId::new(channel),
+            serenity::MessageId::new(message),
+        )
+    }
+
+    #[test]
+    fn parses_server_and_dm_links() {
+        assert_eq!(
+            parse_message_link("https://discord.com/channels/1/22/333"),
+            Some(link(Some(1), 22, 333))
+        );
+        assert_eq!(
+            parse_message_link("https://canary.discordapp.com/channels/@me/22/333"),
+            Some(link(None, 22, 333))
+        );
+        assert_eq!(
+            parse_message_link("http://PTB.Discord.com/channels/1/22/333/?x=1"),
+            Some(link(Some(1), 22, 333))
+        );
+    }
+
+    #[test]
+    fn rejects_other_urls() {
+        for url in [
+            "https://discord.com/channels/1/22",
+            "https://discord.com/channels/1/22/333/4",
+            "https://discord.com/channels/1/22/abc",
+            "https://discord.com/channels/0/22/333",
+            "https://discord.com/invite/abc",
+            "https://evil.com/channels/1/22/333",
+            "https://discord.com.evil.com/channels/1/22/333",
+            "discord.com/channels/1/22/333",
+            "",
+        ] {
+            assert_eq!(parse_message_link(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn finds_links_in_content() {
+        let content = "<@9> see https://discord.com/channels/1/22/333 and \
+            <https://discord.com/channels/1/22/444> or https://discord.com/channels/1/22/333";
+        assert_eq!(
+            message_links(content),
+            vec![link(Some(1), 22, 333), link(Some(1), 22, 444)]
+        );
+        assert!(message_links("no links here").is_empty());
+    }
+}
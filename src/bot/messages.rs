@@ -1,75 +1,788 @@
 use poise::serenity_prelude as serenity;
 use serenity::model::application::ButtonStyle;
-use serenity::{CreateActionRow, CreateAttachment, CreateButton, CreateMessage};
+use serenity::{
+    CreateActionRow, CreateAttachment, CreateButton, CreateEmbed, CreateMessage, EditMessage,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
-use super::constants::{BATCH_SIZE, build_safe_content};
+use crate::models::{Faction, ReplayInfo, Winner};
+
+use super::constants::{
+    ATTACHMENT_DESCRIPTION_MAX_CHARS, BATCH_SIZE, SEND_MAX_RETRIES, SEND_RETRY_BASE_DELAY_MS,
+    SEND_RETRY_MAX_DELAY_MS, build_safe_content,
+};
+use super::i18n::Locale;
+use super::tally::ArchiveTally;
+use super::uploads::{AttachmentLimits, UploadFile, plan_uploads};
+use super::user_message::{UserMessage, to_parts};
+
+/// Attachment name the result embed's image refers to
+pub const EMBED_IMAGE_NAME: &str = "replay.jpg";
+
+/// Embed sidebar colors by how sure the winner is
+const EMBED_COLOR_CERTAIN: u32 = 0xFFD700; // gold
+const EMBED_COLOR_LIKELY: u32 = 0xFFBF00; // amber
+const EMBED_COLOR_DRAW: u32 = 0xC0C8D2; // silver
+const EMBED_COLOR_UNKNOWN: u32 = 0x95A5A6; // gray
+
+/// How single-replay results are posted (batches are always plain)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplyStyle {
+    /// Bare image attachment
+    #[default]
+    Plain,
+    /// Embed with winner-colored sidebar, team fields and the image
+    Embed,
+}
+
+impl ReplyStyle {
+    /// Parse `plain` or `embed` (case-insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("plain") {
+            Some(ReplyStyle::Plain)
+        } else if name.eq_ignore_ascii_case("embed") {
+            Some(ReplyStyle::Embed)
+        } else {
+            None
+        }
+    }
+}
+
+type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Sleep function used between retries (injectable so tests run without real delays)
+pub type SleepFn = Arc<dyn Fn(Duration) -> SleepFuture + Send + Sync>;
+
+/// Retry policy for Discord sends: exponential backoff with jitter.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub sleep: SleepFn,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: SEND_MAX_RETRIES,
+            base_delay: Duration::from_millis(SEND_RETRY_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(SEND_RETRY_MAX_DELAY_MS),
+            sleep: Arc::new(|d| Box::pin(tokio::time::sleep(d))),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Backoff before retry number `retry` (0-based): base * 2^retry, capped at
+    /// max_delay, plus up to 25% jitter derived from `jitter_seed`.
+    pub fn delay_for(&self, retry: u32, jitter_seed: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(1u32 << retry.min(16))
+            .min(self.max_delay);
+        let jitter = exp.mul_f64(f64::from(jitter_seed % 1000) / 4000.0);
+        exp + jitter
+    }
+}
+
+/// Whether a Discord error is worth retrying: HTTP 5xx, 429 that slipped past
+/// serenity's own ratelimiter, and transport-level (network/IO) failures.
+pub fn is_retryable(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(resp)) => {
+            resp.status_code.is_server_error() || resp.status_code.as_u16() == 429
+        }
+        serenity::Error::Http(serenity::HttpError::Request(_)) => true,
+        serenity::Error::Io(_) => true,
+        _ => false,
+    }
+}
+
+/// Run a Discord send, retrying retryable errors per `policy`.
+/// Returns the final error once retries are exhausted so callers can fall back.
+pub async fn send_with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    what: &str,
+    mut op: F,
+) -> Result<T, serenity::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, serenity::Error>>,
+{
+    let mut retry = 0;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if retry < policy.max_retries && is_retryable(&e) => {
+                let seed = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.subsec_nanos())
+                    .unwrap_or(0);
+                let delay = policy.delay_for(retry, seed);
+                tracing::warn!(
+                    "Failed to send {} (attempt {}), retrying in {:?}: {}",
+                    what,
+                    retry + 1,
+                    delay,
+                    e
+                );
+                (policy.sleep)(delay).await;
+                retry += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Post the upload-failed fallback text (single attempt, best effort)
+async fn send_upload_failed(
+    ctx: &serenity::Context,
+    channel_id: serenity::ChannelId,
+    locale: Locale,
+) -> Option<serenity::MessageId> {
+    let message = CreateMessage::new().content(UserMessage::upload_failed().render(locale));
+    match channel_id.send_message(ctx, message).await {
+        Ok(sent) => Some(sent.id),
+        Err(e) => {
+            tracing::error!("Failed to send upload fallback: {}", e);
+            None
+        }
+    }
+}
+
+/// Delete the bot's own replies (best effort, no permission needed for own messages)
+pub async fn delete_replies(
+    ctx: &serenity::Context,
+    channel_id: serenity::ChannelId,
+    replies: &[serenity::MessageId],
+) {
+    for &reply in replies {
+        match channel_id.delete_message(ctx, reply).await {
+            Ok(()) => tracing::info!("Deleted reply {}", reply),
+            Err(e) => tracing::warn!("Failed to delete reply {}: {}", reply, e),
+        }
+    }
+}
 
 /// Arguments for sending a batch message
 pub struct BatchMessageArgs<'a> {
     pub channel_id: serenity::ChannelId,
-    pub attachments: Vec<CreateAttachment>,
-    pub errors: &'a [String],
+    pub files: Vec<UploadFile>,
+    pub limits: AttachmentLimits,
+    pub errors: &'a [UserMessage],
     pub shown: usize,
     pub total: usize,
     pub pending_key: Option<&'a str>,
-    pub cap_note: Option<&'a str>,
+    pub cap_note: Option<&'a UserMessage>,
+    /// Date separators of the replays on this page
+    pub dates: Vec<UserMessage>,
+    /// Results so far, shown as a footer when there is more than one page
+    pub tally: ArchiveTally,
+    pub locale: Locale,
 }
 
-/// Send a batch of replay images as a single message, with an optional "Show more" button.
-pub async fn send_batch_message(ctx: &serenity::Context, args: BatchMessageArgs<'_>) {
+/// Send a batch of replay images, with an optional "Show more" button. Images
+/// over the attachment limits are spread over several messages; notes go on
+/// the first and the button on the last. Returns the ids of the posted
+/// messages (or of the fallback text).
+pub async fn send_batch_message(
+    ctx: &serenity::Context,
+    args: BatchMessageArgs<'_>,
+) -> Vec<serenity::MessageId> {
     let mut parts = Vec::new();
     if let Some(note) = args.cap_note {
-        parts.push(note.to_string());
+        parts.push(note.clone());
     }
     if args.total > BATCH_SIZE {
-        parts.push(format!("Showing {} of {} replays", args.shown, args.total));
+        parts.push(UserMessage::showing(args.shown, args.total));
     }
-    for err in args.errors {
-        parts.push(err.clone());
+    parts.extend(args.dates);
+    parts.extend_from_slice(args.errors);
+    if args.total > BATCH_SIZE {
+        parts.push(UserMessage::archive_so_far(args.tally));
     }
 
-    let mut message = CreateMessage::new();
-    if !parts.is_empty() {
-        message = message.content(build_safe_content(&parts));
-    }
-    for att in args.attachments {
-        message = message.add_file(att);
+    let mut groups = plan_uploads(args.files, args.limits);
+    if groups.is_empty() {
+        groups.push(Vec::new());
     }
+    let last = groups.len() - 1;
+
+    let mut sent = Vec::new();
+    for (i, group) in groups.into_iter().enumerate() {
+        let mut message = CreateMessage::new();
+        if i == 0 && !parts.is_empty() {
+            message = message.content(build_safe_content(&to_parts(&parts, args.locale)));
+        }
+        for file in group {
+            message = message.add_file(attachment(file));
+        }
 
-    if let Some(key) = args.pending_key {
-        let button = CreateButton::new(format!("show_more:{}", key))
-            .label("Show more")
-            .style(ButtonStyle::Primary);
-        message = message.components(vec![CreateActionRow::Buttons(vec![button])]);
+        if i == last
+            && let Some(key) = args.pending_key
+        {
+            let button = CreateButton::new(format!("show_more:{}", key))
+                .label("Show more")
+                .style(ButtonStyle::Primary);
+            message = message.components(vec![CreateActionRow::Buttons(vec![button])]);
+        }
+
+        let policy = RetryPolicy::default();
+        let result = send_with_retry(&policy, "batch message", || {
+            args.channel_id.send_message(ctx, message.clone())
+        })
+        .await;
+        match result {
+            Ok(msg) => {
+                tracing::info!("Sent batch message {}", msg.id);
+                sent.push(msg.id);
+            }
+            Err(e) => {
+                tracing::error!("Failed to send batch message: {}", e);
+                sent.extend(send_upload_failed(ctx, args.channel_id, args.locale).await);
+                break;
+            }
+        }
     }
+    sent
+}
 
-    match args.channel_id.send_message(ctx, message).await {
-        Ok(msg) => tracing::info!("Sent batch message {}", msg.id),
-        Err(e) => tracing::error!("Failed to send batch message: {}", e),
+/// Attachment for an upload, carrying its description as alt text
+pub fn attachment(file: UploadFile) -> CreateAttachment {
+    let attachment = CreateAttachment::bytes(file.bytes, file.name);
+    match file.description {
+        Some(description) => attachment.description(description),
+        None => attachment,
     }
 }
 
-/// Send replay image as the only response (no embed)
+/// Send replay image as the only response, inside `embed` when given
+/// (the embed must reference the image as `attachment://<filename>`) and
+/// with `note` as its text and `components` (buttons) below. Returns the id
+/// of the posted message (or of the fallback text).
 pub async fn send_replay_image(
     ctx: &serenity::Context,
     msg: &serenity::Message,
-    image_bytes: Vec<u8>,
-) {
-    let attachment = CreateAttachment::bytes(image_bytes, "replay.jpg");
-    let message = CreateMessage::new().add_file(attachment);
+    file: UploadFile,
+    embed: Option<CreateEmbed>,
+    components: Vec<CreateActionRow>,
+    note: Option<&UserMessage>,
+    locale: Locale,
+) -> Option<serenity::MessageId> {
+    let mut message = CreateMessage::new().add_file(attachment(file));
+    if !components.is_empty() {
+        message = message.components(components);
+    }
+    if let Some(note) = note {
+        message = message.content(note.render(locale));
+    }
+    if let Some(embed) = embed {
+        message = message.embed(embed);
+    }
+
+    let policy = RetryPolicy::default();
+    let result = send_with_retry(&policy, "replay image", || {
+        msg.channel_id.send_message(ctx, message.clone())
+    })
+    .await;
+    match result {
+        Ok(sent) => {
+            tracing::info!("Sent replay image {}", sent.id);
+            Some(sent.id)
+        }
+        Err(e) => {
+            tracing::error!("Failed to send image: {}", e);
+            send_upload_failed(ctx, msg.channel_id, locale).await
+        }
+    }
+}
+
+/// Swap a posted result's image (and its embed and note) for a fresh
+/// render, keeping its buttons. When the edit fails, the new image is posted
+/// as a reply to the old result instead. Returns the id of the message now
+/// showing it: the result itself, that reply, or `None` when both failed.
+pub async fn replace_replay_image(
+    ctx: &serenity::Context,
+    channel_id: serenity::ChannelId,
+    message_id: serenity::MessageId,
+    file: UploadFile,
+    embed: Option<CreateEmbed>,
+    note: Option<&UserMessage>,
+    locale: Locale,
+) -> Option<serenity::MessageId> {
+    let content = note.map(|note| note.render(locale)).unwrap_or_default();
+    // A new attachment replaces the old one rather than joining it
+    let edit = EditMessage::new()
+        .new_attachment(attachment(file.clone()))
+        .content(content.clone())
+        .embeds(embed.iter().cloned().collect());
+
+    let policy = RetryPolicy::default();
+    let result = send_with_retry(&policy, "re-rendered image", || {
+        channel_id.edit_message(ctx, message_id, edit.clone())
+    })
+    .await;
+    match result {
+        Ok(_) => {
+            tracing::info!("Replaced replay image in {}", message_id);
+            return Some(message_id);
+        }
+        Err(e) => tracing::warn!(
+            "Failed to edit result {}, replying instead: {}",
+            message_id,
+            e
+        ),
+    }
+
+    let mut message = CreateMessage::new()
+        .add_file(attachment(file))
+        .reference_message((channel_id, message_id));
+    if !content.is_empty() {
+        message = message.content(content);
+    }
+    if let Some(embed) = embed {
+        message = message.embed(embed);
+    }
+    let result = send_with_retry(&policy, "re-rendered image", || {
+        channel_id.send_message(ctx, message.clone())
+    })
+    .await;
+    match result {
+        Ok(sent) => {
+            tracing::info!("Sent re-rendered image {}", sent.id);
+            Some(sent.id)
+        }
+        Err(e) => {
+            tracing::error!("Failed to send re-rendered image: {}", e);
+            None
+        }
+    }
+}
+
+/// Sidebar color for a result: gold when certain, amber when likely, silver
+/// for a draw, gray otherwise
+fn winner_color(winner: &Winner) -> u32 {
+    match winner {
+        Winner::LeftTeam | Winner::RightTeam => EMBED_COLOR_CERTAIN,
+        Winner::LikelyLeftTeam | Winner::LikelyRightTeam => EMBED_COLOR_LIKELY,
+        Winner::Draw => EMBED_COLOR_DRAW,
+        Winner::NotConcluded | Winner::InProgress | Winner::Unknown => EMBED_COLOR_UNKNOWN,
+    }
+}
 
-    match msg.channel_id.send_message(ctx, message).await {
-        Ok(sent) => tracing::info!("Sent replay image {}", sent.id),
-        Err(e) => tracing::error!("Failed to send image: {}", e),
+fn faction_emoji(faction: Faction) -> &'static str {
+    match faction {
+        Faction::Men => "🛡️",
+        Faction::Elves => "🏹",
+        Faction::Dwarves => "⛏️",
+        Faction::Isengard => "⚙️",
+        Faction::Mordor => "🔥",
+        Faction::Goblins => "🕷️",
+        Faction::Angmar => "❄️",
+        Faction::Random | Faction::Unknown(_) => "❔",
     }
 }
 
-/// Send a simple text message (no embed)
-pub async fn send_simple_message(ctx: &serenity::Context, msg: &serenity::Message, text: &str) {
-    let message = CreateMessage::new().content(text);
+/// "Left Team" for team 1, "Right Team" for team 2, "Team n" otherwise
+pub fn team_name(team: i8) -> String {
+    match team {
+        1 => "Left Team".to_string(),
+        2 => "Right Team".to_string(),
+        n => format!("Team {}", n),
+    }
+}
+
+/// One `(name, value)` field per team, in team order: "Left Team" for team 1,
+/// "Right Team" for team 2, players as `<emoji> <name>` lines
+fn team_fields(replay: &ReplayInfo) -> Vec<(String, String)> {
+    let mut teams: Vec<i8> = replay.players.iter().map(|p| p.team).collect();
+    teams.sort_unstable();
+    teams.dedup();
+    teams
+        .into_iter()
+        .map(|team| {
+            let name = team_name(team);
+            let value = replay
+                .players
+                .iter()
+                .filter(|p| p.team == team)
+                .map(|p| format!("{} {}", faction_emoji(p.display_faction()), p.name))
+                .collect::<Vec<_>>()
+                .join("\n");
+            (name, value)
+        })
+        .collect()
+}
+
+/// Embed field listing every observer, in join order
+fn observer_field(replay: &ReplayInfo) -> Option<(String, String)> {
+    if replay.spectators.is_empty() {
+        return None;
+    }
+    let names: Vec<&str> = replay.spectators.iter().map(|s| s.name.as_str()).collect();
+    Some(("Observers".to_string(), names.join(", ")))
+}
+
+/// Result embed: filename title, winner-colored sidebar, one field per team,
+/// the observers and the rendered map (attached as [`EMBED_IMAGE_NAME`])
+pub fn build_result_embed(
+    replay: &ReplayInfo,
+    filename: &str,
+    announcement: Option<String>,
+) -> CreateEmbed {
+    let winner = announcement.unwrap_or_else(|| replay.winner.line_text());
+    let mut description = format!("{} · {}", winner, replay.duration_formatted());
+    if let Some(text) = replay.first_defeat_text() {
+        description.push_str(&format!(" · First fall: {}", text));
+    }
+    CreateEmbed::new()
+        .title(filename)
+        .description(description)
+        .color(winner_color(&replay.winner))
+        .fields(
+            team_fields(replay)
+                .into_iter()
+                .map(|(name, value)| (name, value, true)),
+        )
+        .fields(
+            observer_field(replay)
+                .into_iter()
+                .map(|(name, value)| (name, value, false)),
+        )
+        .image(format!("attachment://{}", EMBED_IMAGE_NAME))
+}
+
+/// One-line game summary used as the image's alt text, e.g. "2v2 on wor rhun —
+/// Alice (Men) & Bob (Elves) vs Carol (Mordor) & Dave (Isengard). Winner: Left
+/// Team. Duration 13:37." Unknown winner or duration are left out; capped at
+/// Discord's [`ATTACHMENT_DESCRIPTION_MAX_CHARS`].
+pub fn summarize_replay(replay: &ReplayInfo) -> String {
+    let mut teams: Vec<i8> = replay.players.iter().map(|p| p.team).collect();
+    teams.sort_unstable();
+    teams.dedup();
+    let sides: Vec<Vec<String>> = teams
+        .iter()
+        .map(|&team| {
+            replay
+                .players
+                .iter()
+                .filter(|p| p.team == team)
+                .map(|p| format!("{} ({})", p.name, p.display_faction()))
+                .collect()
+        })
+        .collect();
+    let format_name = sides
+        .iter()
+        .map(|side| side.len().to_string())
+        .collect::<Vec<_>>()
+        .join("v");
+    let map = replay
+        .map_name
+        .strip_prefix("map ")
+        .unwrap_or(&replay.map_name);
+    let lineup = sides
+        .iter()
+        .map(|side| side.join(" & "))
+        .collect::<Vec<_>>()
+        .join(" vs ");
+
+    let mut summary = format!("{} on {} — {}.", format_name, map, lineup);
+    if replay.game_crashed {
+        summary.push_str(" Game crashed.");
+    }
+    if replay.truncated {
+        summary.push_str(" Replay truncated.");
+    }
+    if replay.winner != Winner::Unknown {
+        summary.push_str(&format!(" {}.", replay.winner.line_text()));
+    }
+    if replay.duration_seconds().is_some() {
+        summary.push_str(&format!(" Duration {}.", replay.duration_formatted()));
+    }
+    truncate_chars(summary, ATTACHMENT_DESCRIPTION_MAX_CHARS)
+}
+
+/// Cut `text` to at most `max` chars, ending in "…" when cut
+fn truncate_chars(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+    let mut cut: String = text.chars().take(max - 1).collect();
+    cut.push('…');
+    cut
+}
+
+/// Send a simple text message (no embed), returning its id
+pub async fn send_simple_message(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    locale: Locale,
+    text: &UserMessage,
+) -> Option<serenity::MessageId> {
+    let message = CreateMessage::new().content(text.render(locale));
+
+    let policy = RetryPolicy::default();
+    let result = send_with_retry(&policy, "message", || {
+        msg.channel_id.send_message(ctx, message.clone())
+    })
+    .await;
+    match result {
+        Ok(sent) => {
+            tracing::info!("Sent message {}", sent.id);
+            Some(sent.id)
+        }
+        Err(e) => {
+            tracing::error!("Failed to send message: {}", e);
+            None
+        }
+    }
+}
+
+/// Replace the text of one of the bot's messages (single attempt, best effort)
+pub async fn edit_simple_message(
+    ctx: &serenity::Context,
+    channel_id: serenity::ChannelId,
+    message_id: serenity::MessageId,
+    locale: Locale,
+    text: &UserMessage,
+) {
+    let edit = EditMessage::new().content(text.render(locale));
+    if let Err(e) = channel_id.edit_message(ctx, message_id, edit).await {
+        tracing::warn!("Failed to edit message {}: {}", message_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PlayerBuilder, Spectator};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Policy whose sleep records requested delays instead of sleeping
+    fn recording_policy(max_retries: u32) -> (RetryPolicy, Arc<Mutex<Vec<Duration>>>) {
+        let delays = Arc::new(Mutex::new(Vec::new()));
+        let recorded = delays.clone();
+        let policy = RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            sleep: Arc::new(move |d| {
+                recorded.lock().unwrap().push(d);
+                Box::pin(async {})
+            }),
+        };
+        (policy, delays)
+    }
+
+    fn network_error() -> serenity::Error {
+        serenity::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "reset",
+        ))
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let (policy, delays) = recording_policy(3);
+        let calls = AtomicU32::new(0);
+        let result = send_with_retry(&policy, "test", || {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            async move { if n < 2 { Err(network_error()) } else { Ok(n) } }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(delays.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (policy, delays) = recording_policy(3);
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = send_with_retry(&policy, "test", || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(network_error()) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(delays.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_retryable() {
+        let (policy, delays) = recording_policy(3);
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = send_with_retry(&policy, "test", || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(serenity::Error::Other("bad request")) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(delays.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn embed_fields_group_players_by_team() {
+        let replay = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![
+                PlayerBuilder::test_player("Alice", 1, 0, Faction::Men),
+                PlayerBuilder::test_player("Carol", 2, 1, Faction::Mordor),
+                PlayerBuilder::test_player("Bob", 1, 2, Faction::Elves),
+                PlayerBuilder::test_player("Dave", 2, 3, Faction::Goblins),
+            ],
+        )
+        .with_winner(Winner::LikelyRightTeam);
+
+        assert_eq!(
+            team_fields(&replay),
+            vec![
+                ("Left Team".to_string(), "🛡️ Alice\n🏹 Bob".to_string()),
+                ("Right Team".to_string(), "🔥 Carol\n🕷️ Dave".to_string()),
+            ]
+        );
+        assert_eq!(winner_color(&replay.winner), EMBED_COLOR_LIKELY);
+        let embed = format!("{:?}", build_result_embed(&replay, "2v2.BfME2Replay", None));
+        assert!(embed.contains("2v2.BfME2Replay"));
+        assert!(embed.contains("attachment://replay.jpg"));
+        assert!(!embed.contains("First fall"));
+        assert_eq!(observer_field(&replay), None);
+
+        let stomp = replay.clone().with_first_defeat(Some((2, 754)));
+        let embed = format!("{:?}", build_result_embed(&stomp, "2v2.BfME2Replay", None));
+        assert!(embed.contains("First fall: Bob at 12:34"));
+
+        let announced = build_result_embed(&replay, "2v2.BfME2Replay", Some("Zafer!".into()));
+        let embed = format!("{:?}", announced);
+        assert!(embed.contains("Zafer! · "));
+        assert!(!embed.contains("Winner:"));
+
+        let observed = replay.with_spectators(
+            ["ObsA", "ObsB", "Late"]
+                .iter()
+                .map(|name| Spectator {
+                    name: name.to_string(),
+                    uid: None,
+                    slot: None,
+                })
+                .collect(),
+        );
+        assert_eq!(
+            observer_field(&observed),
+            Some(("Observers".to_string(), "ObsA, ObsB, Late".to_string()))
+        );
+    }
+
+    #[test]
+    fn summary_lists_sides_winner_and_duration() {
+        let duel = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![
+                PlayerBuilder::test_player("Alice", 1, 0, Faction::Men),
+                PlayerBuilder::test_player("Carol", 2, 1, Faction::Mordor),
+            ],
+        )
+        .with_times(1_700_000_000, 1_700_000_817)
+        .with_winner(Winner::RightTeam);
+        assert_eq!(
+            summarize_replay(&duel),
+            "1v1 on wor rhun — Alice (Men) vs Carol (Mordor). Winner: Right Team. Duration 13:37."
+        );
+        assert_eq!(
+            summarize_replay(&duel.clone().with_winner(Winner::Draw)),
+            "1v1 on wor rhun — Alice (Men) vs Carol (Mordor). Result: Draw. Duration 13:37."
+        );
+
+        let mut random = PlayerBuilder::test_player("Bob", 1, 2, Faction::Random);
+        random.actual_faction = Some(Faction::Elves);
+        let team_game = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![
+                PlayerBuilder::test_player("Alice", 1, 0, Faction::Men),
+                PlayerBuilder::test_player("Carol", 2, 1, Faction::Mordor),
+                random,
+                PlayerBuilder::test_player("Dave", 2, 3, Faction::Isengard),
+            ],
+        )
+        .with_times(1_700_000_000, 1_700_000_817)
+        .with_winner(Winner::LeftTeam)
+        .with_spectators(vec![Spectator {
+            name: "Obs".to_string(),
+            uid: None,
+            slot: Some(4),
+        }]);
+        assert_eq!(
+            summarize_replay(&team_game),
+            "2v2 on wor rhun — Alice (Men) & Bob (Elves) vs Carol (Mordor) & Dave (Isengard). \
+             Winner: Left Team. Duration 13:37."
+        );
+    }
+
+    #[test]
+    fn summary_of_a_crashed_game_skips_what_is_unknown() {
+        let crashed = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![
+                PlayerBuilder::test_player("Alice", 1, 0, Faction::Men),
+                PlayerBuilder::test_player("Carol", 2, 1, Faction::Mordor),
+            ],
+        )
+        .with_game_crashed(true);
+        assert_eq!(
+            summarize_replay(&crashed),
+            "1v1 on wor rhun — Alice (Men) vs Carol (Mordor). Game crashed."
+        );
+        assert!(
+            summarize_replay(&crashed.clone().with_truncated(true))
+                .ends_with("Game crashed. Replay truncated.")
+        );
+
+        let crowded = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![PlayerBuilder::test_player(
+                &"x".repeat(2000),
+                1,
+                0,
+                Faction::Men,
+            )],
+        );
+        let summary = summarize_replay(&crowded);
+        assert_eq!(summary.chars().count(), ATTACHMENT_DESCRIPTION_MAX_CHARS);
+        assert!(summary.ends_with('…'));
+    }
+
+    #[test]
+    fn embed_color_tracks_winner_certainty() {
+        assert_eq!(winner_color(&Winner::LeftTeam), EMBED_COLOR_CERTAIN);
+        assert_eq!(winner_color(&Winner::Draw), EMBED_COLOR_DRAW);
+        assert_eq!(winner_color(&Winner::NotConcluded), EMBED_COLOR_UNKNOWN);
+        assert_eq!(winner_color(&Winner::Unknown), EMBED_COLOR_UNKNOWN);
+    }
+
+    #[test]
+    fn reply_style_names() {
+        assert_eq!(ReplyStyle::from_name("Embed"), Some(ReplyStyle::Embed));
+        assert_eq!(ReplyStyle::from_name("plain"), Some(ReplyStyle::Plain));
+        assert_eq!(ReplyStyle::from_name("fancy"), None);
+    }
 
-    match msg.channel_id.send_message(ctx, message).await {
-        Ok(sent) => tracing::info!("Sent message {}", sent.id),
-        Err(e) => tracing::error!("Failed to send message: {}", e),
+    #[test]
+    fn delay_is_exponential_capped_and_jittered() {
+        let (policy, _) = recording_policy(3);
+        assert_eq!(policy.delay_for(0, 0), Duration::from_millis(100));
+        assert_eq!(policy.delay_for(1, 0), Duration::from_millis(200));
+        assert_eq!(policy.delay_for(2, 0), Duration::from_millis(300));
+        assert_eq!(policy.delay_for(10, 0), Duration::from_millis(300));
+        let jittered = policy.delay_for(1, 999);
+        assert!(jittered > Duration::from_millis(200));
+        assert!(jittered <= Duration::from_millis(250));
     }
 }
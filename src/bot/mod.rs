@@ -1,8 +1,43 @@
 mod archive;
+mod archive_stream;
+mod channel_scope;
+mod commands;
 mod constants;
+mod export;
+mod failure_report;
 mod handler;
+mod i18n;
+mod in_flight;
+mod message_link;
 mod messages;
+mod origin;
 mod pagination;
+mod player_details;
+mod posted_replays;
+mod readiness;
+mod relevance;
+mod results_store;
+mod seen_messages;
+mod sent_results;
 mod setup;
+mod shared_map;
+mod supervisor;
+mod tally;
+mod temp_dirs;
+mod uploads;
+mod user_message;
+mod winner_template;
+mod work_queue;
 
-pub use setup::setup_bot;
+pub use archive::{ArchiveError, extract_replays_from_zip};
+pub use channel_scope::{ChannelScopes, parse_guild_ids};
+pub use failure_report::AdminWebhook;
+pub use i18n::Locale;
+pub use messages::ReplyStyle;
+pub use origin::parse_webhook_ids;
+pub use readiness::Readiness;
+pub use results_store::ResultsStore;
+pub use sent_results::DeleteReaction;
+pub use setup::{BotConfig, setup_bot};
+pub use uploads::AttachmentLimits;
+pub use winner_template::WinnerTemplates;
@@ -0,0 +1,154 @@
+use poise::serenity_prelude as serenity;
+
+/// Who posted a message, as far as the bot can tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Author {
+    Human,
+    Bot,
+    Webhook(serenity::WebhookId),
+    /// Not visible to the bot (forward snapshots carry no author)
+    Unknown,
+}
+
+impl Author {
+    /// Classify from a message's author fields; webhook messages also have a
+    /// bot author, so the webhook id is checked first
+    pub fn of(webhook_id: Option<serenity::WebhookId>, author_is_bot: bool) -> Self {
+        match webhook_id {
+            Some(id) => Author::Webhook(id),
+            None if author_is_bot => Author::Bot,
+            None => Author::Human,
+        }
+    }
+
+    pub fn of_message(msg: &serenity::Message) -> Self {
+        Self::of(msg.webhook_id, msg.author.bot)
+    }
+}
+
+/// Whether a message's attachments may be processed, judged by who posted them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Posted by people or allowlisted webhooks
+    Trusted,
+    /// A bot or an unlisted webhook is involved: never handled
+    Rejected,
+}
+
+/// Classify the origin of a message's attachments. `poster` wrote the message
+/// itself; `original` wrote the message the attachments came from (the
+/// replied-to or forwarded message), `None` when they are the poster's own.
+/// An unknown original is trusted: forwards from servers the bot is not in
+/// have no visible author, and those are a supported way to share replays.
+pub fn classify_origin(
+    poster: Author,
+    original: Option<Author>,
+    allowed_webhooks: &[serenity::WebhookId],
+) -> Origin {
+    let rejected = |author: Author| match author {
+        Author::Bot => true,
+        Author::Webhook(id) => !allowed_webhooks.contains(&id),
+        Author::Human | Author::Unknown => false,
+    };
+    if rejected(poster) || original.is_some_and(rejected) {
+        Origin::Rejected
+    } else {
+        Origin::Trusted
+    }
+}
+
+/// Parse an `ALLOWED_WEBHOOK_IDS` value: comma-separated webhook ids
+pub fn parse_webhook_ids(spec: &str) -> Result<Vec<serenity::WebhookId>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| match id.parse::<u64>() {
+            Ok(n) if n > 0 => Ok(serenity::WebhookId::new(n)),
+            _ => Err(format!("invalid webhook id '{}'", id)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BRIDGE: serenity::WebhookId = serenity::WebhookId::new(42);
+    const OTHER: serenity::WebhookId = serenity::WebhookId::new(7);
+
+    fn classify(poster: Author, original: Option<Author>) -> Origin {
+        classify_origin(poster, original, &[BRIDGE])
+    }
+
+    #[test]
+    fn author_fields() {
+        assert_eq!(Author::of(None, false), Author::Human);
+        assert_eq!(Author::of(None, true), Author::Bot);
+        assert_eq!(Author::of(Some(BRIDGE), true), Author::Webhook(BRIDGE));
+    }
+
+    #[test]
+    fn own_attachments() {
+        assert_eq!(classify(Author::Human, None), Origin::Trusted);
+        assert_eq!(classify(Author::Bot, None), Origin::Rejected);
+        assert_eq!(classify(Author::Webhook(BRIDGE), None), Origin::Trusted);
+        assert_eq!(classify(Author::Webhook(OTHER), None), Origin::Rejected);
+    }
+
+    #[test]
+    fn replied_to_attachments() {
+        assert_eq!(
+            classify(Author::Human, Some(Author::Human)),
+            Origin::Trusted
+        );
+        assert_eq!(classify(Author::Human, Some(Author::Bot)), Origin::Rejected);
+        assert_eq!(
+            classify(Author::Human, Some(Author::Webhook(OTHER))),
+            Origin::Rejected
+        );
+        assert_eq!(
+            classify(Author::Human, Some(Author::Webhook(BRIDGE))),
+            Origin::Trusted
+        );
+        assert_eq!(classify(Author::Bot, Some(Author::Human)), Origin::Rejected);
+    }
+
+    #[test]
+    fn forwarded_attachments() {
+        // Original looked up and found
+        assert_eq!(
+            classify(Author::Human, Some(Author::Human)),
+            Origin::Trusted
+        );
+        assert_eq!(classify(Author::Human, Some(Author::Bot)), Origin::Rejected);
+        // Original not visible (another server)
+        assert_eq!(
+            classify(Author::Human, Some(Author::Unknown)),
+            Origin::Trusted
+        );
+        // A bot forwarding something is still a bot
+        assert_eq!(
+            classify(Author::Bot, Some(Author::Unknown)),
+            Origin::Rejected
+        );
+        assert_eq!(
+            classify(Author::Webhook(OTHER), Some(Author::Human)),
+            Origin::Rejected
+        );
+    }
+
+    #[test]
+    fn webhook_allowlist() {
+        assert_eq!(
+            parse_webhook_ids(" 42, 7 ,"),
+            Ok(vec![BRIDGE, serenity::WebhookId::new(7)])
+        );
+        assert_eq!(parse_webhook_ids(""), Ok(vec![]));
+        assert!(parse_webhook_ids("42,abc").is_err());
+        assert!(parse_webhook_ids("0").is_err());
+        assert_eq!(
+            classify_origin(Author::Webhook(BRIDGE), None, &[]),
+            Origin::Rejected
+        );
+    }
+}
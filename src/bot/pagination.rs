@@ -2,20 +2,35 @@ use poise::serenity_prelude as serenity;
 use serenity::model::application::ButtonStyle;
 use serenity::{
     CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseFollowup,
-    CreateInteractionResponseMessage, EditInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse, EditMessage,
 };
+use std::collections::HashMap;
+use std::future::Future;
 use std::time::Instant;
 
+use super::archive::date_separators;
 use super::constants::{BATCH_SIZE, build_safe_content};
+use super::failure_report::FailureSite;
+use super::handler::{BatchRenderer, RenderedBatch, process_replay_batch, render_replay_batch};
+use super::i18n::Locale;
+use super::messages::{RetryPolicy, attachment, send_with_retry};
+use super::player_details::{handle_player_detail, is_player_button};
 use super::setup::{Data, PendingReplays, cleanup_expired_pending_inner};
+use super::uploads::{UploadFile, plan_uploads};
+use super::user_message::{UserMessage, to_parts};
+use super::work_queue::{Admission, Turn, WorkPermit};
 
-/// Handle a "Show more" button click.
+/// Handle a "Show more" or player button click.
 pub async fn handle_component_interaction(
     ctx: &serenity::Context,
     component: &serenity::ComponentInteraction,
     data: &Data,
 ) {
     let custom_id = &component.data.custom_id;
+    if is_player_button(custom_id) {
+        handle_player_detail(ctx, component, data).await;
+        return;
+    }
     let Some(key) = custom_id.strip_prefix("show_more:") else {
         return;
     };
@@ -24,13 +39,12 @@ pub async fn handle_component_interaction(
     // Short-circuits BEFORE acknowledge/disable-button flow on mismatch.
     enum LookupResult {
         ChannelMismatch,
-        Found(PendingReplays),
+        Found(Box<PendingReplays>),
         NotFound,
     }
 
-    let lookup = {
-        let mut map = data.lock_pending_replays();
-        cleanup_expired_pending_inner(&mut map);
+    let lookup = data.pending_replays.write(|map| {
+        cleanup_expired_pending_inner(map);
 
         // Validate channel BEFORE removing
         match map.get(key) {
@@ -38,16 +52,21 @@ pub async fn handle_component_interaction(
                 // Don't consume the entry -- let the rightful channel use it
                 LookupResult::ChannelMismatch
             }
-            Some(_) => LookupResult::Found(map.remove(key).unwrap()),
+            Some(_) => LookupResult::Found(Box::new(map.remove(key).unwrap())),
             None => LookupResult::NotFound,
         }
         // guard drops here
+    });
+
+    let locale = match &lookup {
+        LookupResult::Found(p) => p.locale,
+        _ => data.locale_for(ctx, component.guild_id).await,
     };
 
     if matches!(lookup, LookupResult::ChannelMismatch) {
         let response = CreateInteractionResponse::Message(
             CreateInteractionResponseMessage::new()
-                .content("This button is only valid in the original channel.")
+                .content(UserMessage::wrong_channel().render(locale))
                 .ephemeral(true),
         );
         let _ = component.create_response(ctx, response).await;
@@ -55,16 +74,22 @@ pub async fn handle_component_interaction(
     }
 
     let pending = match lookup {
-        LookupResult::Found(p) => Some(p),
+        LookupResult::Found(p) => Some(*p),
         _ => None,
     };
 
     // Acknowledge the interaction without modifying the message (preserves attachments).
+    // Once the interaction has expired, the page goes to the channel instead.
+    let mut expired = false;
     match component
         .create_response(ctx, CreateInteractionResponse::Acknowledge)
         .await
     {
         Ok(()) => tracing::info!("Acknowledged interaction {}", component.id),
+        Err(e) if pending.is_some() && is_interaction_expired(&e) => {
+            tracing::warn!("Interaction {} expired before acknowledging", component.id);
+            expired = true;
+        }
         Err(e) => {
             tracing::error!("Failed to acknowledge interaction: {}", e);
             return;
@@ -76,21 +101,30 @@ pub async fn handle_component_interaction(
         .label("Processing...")
         .style(ButtonStyle::Secondary)
         .disabled(true);
-    match component
-        .edit_response(
-            ctx,
-            EditInteractionResponse::new()
-                .components(vec![CreateActionRow::Buttons(vec![disabled_button])]),
-        )
-        .await
-    {
-        Ok(msg) => tracing::info!("Disabled button on message {}", msg.id),
-        Err(e) => tracing::error!("Failed to disable button: {}", e),
+    if !expired {
+        match component
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .components(vec![CreateActionRow::Buttons(vec![disabled_button])]),
+            )
+            .await
+        {
+            Ok(msg) => tracing::info!("Disabled button on message {}", msg.id),
+            Err(e) if pending.is_some() && is_interaction_expired(&e) => {
+                tracing::warn!(
+                    "Interaction {} expired before disabling its button",
+                    component.id
+                );
+                expired = true;
+            }
+            Err(e) => tracing::error!("Failed to disable button: {}", e),
+        }
     }
 
-    let Some(pending) = pending else {
+    let Some(mut pending) = pending else {
         let followup = CreateInteractionResponseFollowup::new()
-            .content("This button has expired. Please re-upload the archive.");
+            .content(UserMessage::button_expired().render(locale));
         match component.create_followup(ctx, followup).await {
             Ok(msg) => tracing::info!("Sent expiry notice {}", msg.id),
             Err(e) => tracing::error!("Failed to send expiry notice: {}", e),
@@ -98,59 +132,542 @@ pub async fn handle_component_interaction(
         return;
     };
 
-    // Process the next batch
-    let (attachments, errors) = super::handler::process_replay_batch(data, &pending.replays).await;
+    // Rendering on demand takes a worker slot, queued behind uploads like
+    // one; a prefetched page is already rendered
+    let slot = if pending.prefetched.is_none() {
+        match wait_for_page_slot(ctx, component, data, locale, expired).await {
+            Ok(permit) => Some(permit),
+            Err(reason) => {
+                let clicked_message = pending.message_id.unwrap_or(component.message.id);
+                restore_pending(ctx, data, key, pending, clicked_message).await;
+                send_page_notice(ctx, component, locale, expired, &reason).await;
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    // Process the next batch (already done when the prefetch finished in time)
+    let pending_flags = pending.flags;
+    let site = FailureSite::new(component.guild_id, pending.channel_id);
+    let RenderedBatch {
+        images,
+        errors,
+        tally: batch_tally,
+        results,
+    } = next_batch(&mut pending, |pending| {
+        process_replay_batch(data, &pending.replays, &pending.parsed, pending_flags, site)
+    })
+    .await;
+    drop(slot);
+    data.record_results(component.guild_id, pending.channel_id, results);
+    let mut tally = pending.tally;
+    tally.merge(&batch_tally);
     let batch_count = pending.replays.len().min(BATCH_SIZE);
+    let dates = date_separators(&pending.replays[..batch_count]);
     let new_shown = pending.shown + batch_count;
     let remaining: Vec<(String, Vec<u8>)> = pending.replays.into_iter().skip(batch_count).collect();
+    let remaining_parsed: Vec<_> = pending.parsed.into_iter().skip(batch_count).collect();
+    let clicked_message = pending.message_id.unwrap_or(component.message.id);
 
     // TOCTOU-safe reinsert: lock -> cleanup -> capacity check -> insert
     // Stable key: reuse the same key (no suffix growth)
     let pending_key = if !remaining.is_empty() {
-        let mut map = data.lock_pending_replays();
-        cleanup_expired_pending_inner(&mut map);
-        if map.len() >= super::constants::MAX_PENDING_ENTRIES {
-            None
-        } else {
-            let new_pending = PendingReplays {
-                replays: remaining,
-                total: pending.total,
-                shown: new_shown,
-                created_at: Instant::now(),
-                channel_id: pending.channel_id,
-            };
-            map.insert(key.to_string(), new_pending);
-            Some(key.to_string())
-        }
+        data.pending_replays.write(|map| {
+            cleanup_expired_pending_inner(map);
+            if map.len() >= super::constants::MAX_PENDING_ENTRIES {
+                None
+            } else {
+                let new_pending = PendingReplays {
+                    replays: remaining,
+                    parsed: remaining_parsed,
+                    total: pending.total,
+                    shown: new_shown,
+                    created_at: Instant::now(),
+                    channel_id: pending.channel_id,
+                    locale,
+                    tally,
+                    flags: pending_flags,
+                    prefetched: None,
+                    message_id: None,
+                };
+                map.insert(key.to_string(), new_pending);
+                Some(key.to_string())
+            }
+        })
         // guard drops here, before any .await
     } else {
         None
     };
 
-    // Build followup message with images + optional new button
-    let mut parts = Vec::new();
-    parts.push(format!(
-        "Showing {} of {} replays",
-        new_shown, pending.total
-    ));
-    for err in &errors {
-        parts.push(err.clone());
+    // Build followup messages with images (split to fit the attachment
+    // limits) + optional new button on the last one
+    let mut parts = vec![UserMessage::showing(new_shown, pending.total)];
+    parts.extend(dates);
+    parts.extend(errors);
+    parts.push(UserMessage::archive_so_far(tally));
+
+    let mut groups = plan_uploads(images, data.attachment_limits);
+    if groups.is_empty() {
+        groups.push(Vec::new());
+    }
+    let last = groups.len() - 1;
+    let channel_id = pending.channel_id;
+    for (i, files) in groups.into_iter().enumerate() {
+        let part = PagePart {
+            content: (i == 0).then(|| build_safe_content(&to_parts(&parts, locale))),
+            files,
+            pending_key: pending_key.as_deref().filter(|_| i == last),
+        };
+        let policy = RetryPolicy::default();
+        if !expired {
+            let result = send_with_retry(&policy, "followup batch", || {
+                component.create_followup(ctx, part.followup())
+            })
+            .await;
+            match result {
+                Ok(msg) => {
+                    tracing::info!("Sent followup batch {}", msg.id);
+                    record_page_message(data, component, &part, msg.id);
+                    continue;
+                }
+                Err(e) => match followup_fallback(discord_error_code(&e)) {
+                    FollowupFallback::PostToChannel => {
+                        tracing::warn!(
+                            "Interaction expired mid-page, posting to the channel: {}",
+                            e
+                        );
+                        expired = true;
+                    }
+                    FollowupFallback::Notice => {
+                        tracing::error!("Failed to send followup: {}", e);
+                        let fallback = CreateInteractionResponseFollowup::new()
+                            .content(UserMessage::upload_failed().render(locale));
+                        if let Err(e) = component.create_followup(ctx, fallback).await {
+                            tracing::error!("Failed to send followup fallback: {}", e);
+                        }
+                        break;
+                    }
+                },
+            }
+        }
+        let result = send_with_retry(&policy, "batch message", || {
+            channel_id.send_message(ctx, part.message())
+        })
+        .await;
+        match result {
+            Ok(msg) => {
+                tracing::info!("Sent batch message {} in place of a followup", msg.id);
+                record_page_message(data, component, &part, msg.id);
+            }
+            Err(e) => {
+                tracing::error!("Failed to send batch message: {}", e);
+                let fallback =
+                    CreateMessage::new().content(UserMessage::upload_failed().render(locale));
+                if let Err(e) = channel_id.send_message(ctx, fallback).await {
+                    tracing::error!("Failed to send upload fallback: {}", e);
+                }
+                break;
+            }
+        }
+    }
+    if expired {
+        // Its button was left as it was (or stuck on "Processing..."); the
+        // next page carries the live one
+        let edit = EditMessage::new().components(Vec::new());
+        match channel_id.edit_message(ctx, clicked_message, edit).await {
+            Ok(_) => tracing::info!("Cleared button on message {}", clicked_message),
+            Err(e) => tracing::warn!("Failed to clear button on {}: {}", clicked_message, e),
+        }
+    }
+    if let Some(pk) = pending_key {
+        spawn_prefetch(data, &pk);
+    }
+}
+
+/// One message of a page: the notes go on the first, the "Show more"
+/// button on the last
+struct PagePart<'a> {
+    content: Option<String>,
+    files: Vec<UploadFile>,
+    pending_key: Option<&'a str>,
+}
+
+impl PagePart<'_> {
+    fn button(&self) -> Option<CreateActionRow> {
+        self.pending_key.map(show_more_row)
+    }
+
+    fn followup(&self) -> CreateInteractionResponseFollowup {
+        let mut followup = CreateInteractionResponseFollowup::new();
+        if let Some(content) = &self.content {
+            followup = followup.content(content);
+        }
+        for file in &self.files {
+            followup = followup.add_file(attachment(file.clone()));
+        }
+        if let Some(row) = self.button() {
+            followup = followup.components(vec![row]);
+        }
+        followup
+    }
+
+    fn message(&self) -> CreateMessage {
+        let mut message = CreateMessage::new();
+        if let Some(content) = &self.content {
+            message = message.content(content);
+        }
+        for file in &self.files {
+            message = message.add_file(attachment(file.clone()));
+        }
+        if let Some(row) = self.button() {
+            message = message.components(vec![row]);
+        }
+        message
+    }
+}
+
+fn show_more_row(key: &str) -> CreateActionRow {
+    let button = CreateButton::new(format!("show_more:{}", key))
+        .label("Show more")
+        .style(ButtonStyle::Primary);
+    CreateActionRow::Buttons(vec![button])
+}
+
+/// Take a worker slot for rendering a page on demand, waiting in the queue
+/// (with the position on the disabled button) while every slot is busy.
+/// `Err` says why the page is not rendered.
+async fn wait_for_page_slot(
+    ctx: &serenity::Context,
+    component: &serenity::ComponentInteraction,
+    data: &Data,
+    locale: Locale,
+    expired: bool,
+) -> Result<WorkPermit, UserMessage> {
+    let (position, ticket) = match data.work_queue.admit(component.message.id) {
+        Admission::Start(permit) => return Ok(permit),
+        Admission::Full => {
+            tracing::warn!("Work queue full, page for {} not rendered", component.id);
+            return Err(UserMessage::queue_full());
+        }
+        Admission::Queued { position, ticket } => (position, ticket),
+    };
+    tracing::info!("All workers busy, page queued at position {}", position);
+    if !expired {
+        let queued = CreateButton::new("show_more_disabled")
+            .label(UserMessage::queued(position).render(locale))
+            .style(ButtonStyle::Secondary)
+            .disabled(true);
+        let edit =
+            EditInteractionResponse::new().components(vec![CreateActionRow::Buttons(vec![queued])]);
+        if let Err(e) = component.edit_response(ctx, edit).await {
+            tracing::warn!("Failed to show queue position: {}", e);
+        }
+    }
+    match ticket.wait().await {
+        Turn::Start(permit) => Ok(permit),
+        Turn::Dropped(reason) => Err(UserMessage::queue_dropped(reason)),
+    }
+}
+
+/// Put back an entry whose page was not rendered, with its "Show more"
+/// button live again on the clicked message
+async fn restore_pending(
+    ctx: &serenity::Context,
+    data: &Data,
+    key: &str,
+    mut pending: PendingReplays,
+    clicked_message: serenity::MessageId,
+) {
+    let channel_id = pending.channel_id;
+    pending.created_at = Instant::now();
+    pending.message_id = Some(clicked_message);
+    data.pending_replays.write(|map| {
+        cleanup_expired_pending_inner(map);
+        map.insert(key.to_string(), pending);
+    });
+    let edit = EditMessage::new().components(vec![show_more_row(key)]);
+    if let Err(e) = channel_id.edit_message(ctx, clicked_message, edit).await {
+        tracing::warn!("Failed to restore button on {}: {}", clicked_message, e);
+    }
+}
+
+/// Tell the clicker why their page was not rendered
+async fn send_page_notice(
+    ctx: &serenity::Context,
+    component: &serenity::ComponentInteraction,
+    locale: Locale,
+    expired: bool,
+    notice: &UserMessage,
+) {
+    let content = notice.render(locale);
+    let result = if expired {
+        component
+            .channel_id
+            .send_message(ctx, CreateMessage::new().content(content))
+            .await
+    } else {
+        let followup = CreateInteractionResponseFollowup::new()
+            .content(content)
+            .ephemeral(true);
+        component.create_followup(ctx, followup).await
+    };
+    if let Err(e) = result {
+        tracing::error!("Failed to send page notice: {}", e);
+    }
+}
+
+/// Remember a posted page: the clicker asked for it, so it is theirs to
+/// delete, and the part with the button is where the entry continues
+fn record_page_message(
+    data: &Data,
+    component: &serenity::ComponentInteraction,
+    part: &PagePart<'_>,
+    message_id: serenity::MessageId,
+) {
+    data.sent_results
+        .record(message_id, component.user.id, part.pending_key);
+    if let Some(key) = part.pending_key {
+        data.pending_replays
+            .write(|map| set_button_message(map, key, message_id));
+    }
+}
+
+/// Note the message carrying an entry's "Show more" button, so it can be
+/// cleared if a click on it expires
+pub fn set_button_message(
+    map: &mut HashMap<String, PendingReplays>,
+    key: &str,
+    message_id: serenity::MessageId,
+) {
+    if let Some(entry) = map.get_mut(key) {
+        entry.message_id = Some(message_id);
+    }
+}
+
+/// Discord error codes meaning an interaction's token no longer works:
+/// unknown interaction, unknown webhook, invalid webhook token
+const EXPIRED_INTERACTION_CODES: [isize; 3] = [10062, 10015, 50027];
+
+/// How to post the rest of a page after a followup failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FollowupFallback {
+    /// The interaction expired: post to the channel directly
+    PostToChannel,
+    /// Any other failure: tell the clicker the upload failed
+    Notice,
+}
+
+/// Pick the fallback for a failed followup from its Discord error code
+fn followup_fallback(code: Option<isize>) -> FollowupFallback {
+    match code {
+        Some(code) if EXPIRED_INTERACTION_CODES.contains(&code) => FollowupFallback::PostToChannel,
+        _ => FollowupFallback::Notice,
+    }
+}
+
+/// The JSON error code of a failed Discord request, if it got that far
+fn discord_error_code(error: &serenity::Error) -> Option<isize> {
+    match error {
+        serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(resp)) => {
+            Some(resp.error.code)
+        }
+        _ => None,
+    }
+}
+
+fn is_interaction_expired(error: &serenity::Error) -> bool {
+    followup_fallback(discord_error_code(error)) == FollowupFallback::PostToChannel
+}
+
+/// The next page of an entry: its prefetched render if there is one,
+/// otherwise rendered now
+async fn next_batch<'a, F, Fut>(pending: &'a mut PendingReplays, render: F) -> RenderedBatch
+where
+    F: FnOnce(&'a PendingReplays) -> Fut,
+    Fut: Future<Output = RenderedBatch>,
+{
+    match pending.prefetched.take() {
+        Some(batch) => {
+            tracing::info!("Using prefetched batch");
+            batch
+        }
+        None => render(pending).await,
+    }
+}
+
+/// Render the next page of a pending entry in the background, so "Show more"
+/// can answer at once. It takes a worker slot until rendered, and is skipped
+/// (returning false) while every slot is busy or uploads wait for one. A
+/// task still running at shutdown is dropped with the runtime; it holds
+/// nothing but its own copy of the replays.
+pub fn spawn_prefetch(data: &Data, key: &str) -> bool {
+    let Some(permit) = data.work_queue.try_permit() else {
+        tracing::info!("All workers busy, not prefetching {}", key);
+        return false;
+    };
+    let Some((replays, parsed, shown, flags, channel_id)) = data.pending_replays.read(|map| {
+        map.get(key).map(|p| {
+            let batch = &p.replays[..p.replays.len().min(BATCH_SIZE)];
+            let parsed = &p.parsed[..p.parsed.len().min(BATCH_SIZE)];
+            (
+                batch.to_vec(),
+                parsed.to_vec(),
+                p.shown,
+                p.flags,
+                p.channel_id,
+            )
+        })
+    }) else {
+        return false;
+    };
+    let renderer = BatchRenderer::new(data, flags, FailureSite::new(None, channel_id));
+    let pending_replays = data.pending_replays.clone();
+    let key = key.to_string();
+    tokio::spawn(async move {
+        let batch = render_replay_batch(&renderer, &replays, &parsed).await;
+        drop(permit);
+        if !pending_replays.write(|map| store_prefetched(map, &key, shown, batch)) {
+            tracing::info!("Discarded prefetched batch for {}", key);
+        }
+    });
+    true
+}
+
+/// Attach a prefetched page to the entry it was rendered for. Returns false
+/// (dropping the page) when the entry expired, was consumed, or has moved
+/// on to another page since.
+fn store_prefetched(
+    map: &mut HashMap<String, PendingReplays>,
+    key: &str,
+    shown: usize,
+    batch: RenderedBatch,
+) -> bool {
+    cleanup_expired_pending_inner(map);
+    match map.get_mut(key) {
+        Some(entry) if entry.shown == shown && entry.prefetched.is_none() => {
+            entry.prefetched = Some(batch);
+            true
+        }
+        _ => false,
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::handler::ReplyFlags;
+    use crate::bot::tally::ArchiveTally;
+    use crate::bot::uploads::UploadFile;
+    use std::cell::Cell;
 
-    let content = build_safe_content(&parts);
-    let mut followup = CreateInteractionResponseFollowup::new().content(content);
-    for att in attachments {
-        followup = followup.add_file(att);
+    fn pending(shown: usize) -> PendingReplays {
+        PendingReplays {
+            replays: vec![("a.BfME2Replay".to_string(), vec![1, 2, 3])],
+            parsed: Vec::new(),
+            total: shown + 1,
+            shown,
+            created_at: Instant::now(),
+            channel_id: serenity::ChannelId::new(1),
+            locale: Locale::En,
+            tally: ArchiveTally::default(),
+            flags: ReplyFlags::default(),
+            prefetched: None,
+            message_id: None,
+        }
     }
-    if let Some(ref pk) = pending_key {
-        let button = CreateButton::new(format!("show_more:{}", pk))
-            .label("Show more")
-            .style(ButtonStyle::Primary);
-        followup = followup.components(vec![CreateActionRow::Buttons(vec![button])]);
+
+    fn batch(name: &str) -> RenderedBatch {
+        RenderedBatch {
+            images: vec![UploadFile::new(name, vec![0xFF])],
+            errors: Vec::new(),
+            tally: ArchiveTally::default(),
+            results: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn prefetch_is_stored_only_for_the_page_it_rendered() {
+        let mut map = HashMap::from([("k".to_string(), pending(10))]);
+        assert!(store_prefetched(&mut map, "k", 10, batch("first.jpg")));
+        // A second result for the same page does not replace the first
+        assert!(!store_prefetched(&mut map, "k", 10, batch("again.jpg")));
+        assert_eq!(
+            map["k"].prefetched.as_ref().unwrap().images[0].name,
+            "first.jpg"
+        );
+
+        // The entry moved on to the next page while rendering
+        map.insert("k".to_string(), pending(20));
+        assert!(!store_prefetched(&mut map, "k", 10, batch("stale.jpg")));
+        assert!(map["k"].prefetched.is_none());
+
+        // Consumed by a click, or cleaned up after expiring
+        map.remove("k");
+        assert!(!store_prefetched(&mut map, "k", 20, batch("late.jpg")));
+        assert!(map.is_empty());
     }
 
-    match component.create_followup(ctx, followup).await {
-        Ok(msg) => tracing::info!("Sent followup batch {}", msg.id),
-        Err(e) => tracing::error!("Failed to send followup: {}", e),
+    #[test]
+    fn expired_interactions_fall_back_to_the_channel() {
+        for code in [10062, 10015, 50027] {
+            assert_eq!(
+                followup_fallback(Some(code)),
+                FollowupFallback::PostToChannel
+            );
+        }
+        // Missing permissions, oversized uploads, network failures: the
+        // interaction still works, so the clicker gets the notice
+        assert_eq!(followup_fallback(Some(50013)), FollowupFallback::Notice);
+        assert_eq!(followup_fallback(Some(40005)), FollowupFallback::Notice);
+        assert_eq!(followup_fallback(None), FollowupFallback::Notice);
+        let io = serenity::Error::Io(std::io::Error::other("reset"));
+        assert_eq!(discord_error_code(&io), None);
+        assert!(!is_interaction_expired(&io));
+    }
+
+    #[test]
+    fn button_message_is_noted_on_its_entry() {
+        let mut map = HashMap::from([("k".to_string(), pending(10))]);
+        let message = serenity::MessageId::new(42);
+        set_button_message(&mut map, "k", message);
+        assert_eq!(map["k"].message_id, Some(message));
+        // Consumed or expired entries are left alone
+        set_button_message(&mut map, "gone", message);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn only_the_last_part_of_a_page_carries_the_button() {
+        let part = |pending_key| PagePart {
+            content: None,
+            files: Vec::new(),
+            pending_key,
+        };
+        assert!(part(Some("k")).button().is_some());
+        assert!(part(None).button().is_none());
+    }
+
+    #[tokio::test]
+    async fn next_batch_prefers_the_prefetched_page() {
+        let rendered = Cell::new(0);
+        let render = |pending: &PendingReplays| {
+            rendered.set(rendered.get() + 1);
+            let name = format!("{}.jpg", pending.replays.len());
+            async move { batch(&name) }
+        };
+
+        let mut entry = pending(10);
+        entry.prefetched = Some(batch("prefetched.jpg"));
+        let got = next_batch(&mut entry, render).await;
+        assert_eq!(got.images[0].name, "prefetched.jpg");
+        assert_eq!(rendered.get(), 0);
+        assert!(entry.prefetched.is_none());
+
+        // Nothing prefetched (or not yet): rendered on demand
+        let got = next_batch(&mut entry, render).await;
+        assert_eq!(got.images[0].name, "1.jpg");
+        assert_eq!(rendered.get(), 1);
     }
 }
@@ -0,0 +1,311 @@
+use poise::serenity_prelude as serenity;
+use serenity::model::application::ButtonStyle;
+use serenity::{
+    ChannelId, CreateActionRow, CreateButton, CreateInteractionResponse,
+    CreateInteractionResponseMessage, MessageId,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::models::{Player, ReplayInfo};
+
+use super::constants::{
+    MAX_PLAYER_DETAIL_ENTRIES, PLAYER_BUTTON_LABEL_CHARS, PLAYER_DETAIL_TTL_SECS,
+};
+use super::messages::team_name;
+use super::setup::Data;
+use super::shared_map::{PoisonPolicy, SharedMap};
+use super::user_message::UserMessage;
+
+/// Custom id prefix of the player buttons under a single result
+const CUSTOM_ID_PREFIX: &str = "player_detail:";
+
+/// Discord's limit on buttons per row, and on rows per message
+const BUTTONS_PER_ROW: usize = 5;
+const MAX_ROWS: usize = 5;
+
+/// A parsed replay behind a result's player buttons
+struct StoredReplay {
+    replay: Arc<ReplayInfo>,
+    channel_id: ChannelId,
+    at: Instant,
+}
+
+/// Parsed replays of recent single results, keyed by the upload they
+/// answered, so their player buttons can answer without reparsing
+pub struct PlayerDetails {
+    /// On poison: recover (a lost entry only expires its buttons early)
+    replays: SharedMap<MessageId, StoredReplay>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl PlayerDetails {
+    pub fn new() -> Self {
+        Self::with_limits(
+            Duration::from_secs(PLAYER_DETAIL_TTL_SECS),
+            MAX_PLAYER_DETAIL_ENTRIES,
+        )
+    }
+
+    fn with_limits(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            replays: SharedMap::new("Player details", PoisonPolicy::Recover),
+            ttl,
+            capacity,
+        }
+    }
+
+    /// Remember the replay shown in a result; the oldest entry makes room
+    /// when full
+    pub fn store(&self, key: MessageId, channel_id: ChannelId, replay: ReplayInfo) {
+        let now = Instant::now();
+        self.replays.write(|map| {
+            map.retain(|_, stored| now.duration_since(stored.at) < self.ttl);
+            if map.len() >= self.capacity
+                && let Some(oldest) = map.iter().min_by_key(|(_, s)| s.at).map(|(k, _)| *k)
+            {
+                map.remove(&oldest);
+            }
+            map.insert(
+                key,
+                StoredReplay {
+                    replay: Arc::new(replay),
+                    channel_id,
+                    at: now,
+                },
+            );
+        });
+    }
+
+    /// The replay behind a result's buttons, while remembered and clicked
+    /// in the channel it was posted in
+    pub fn get(&self, key: MessageId, channel_id: ChannelId) -> Option<Arc<ReplayInfo>> {
+        let now = Instant::now();
+        self.replays.read(|map| {
+            map.get(&key)
+                .filter(|stored| now.duration_since(stored.at) < self.ttl)
+                .filter(|stored| stored.channel_id == channel_id)
+                .map(|stored| stored.replay.clone())
+        })
+    }
+}
+
+impl Default for PlayerDetails {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One button per player, labelled with their (truncated) name
+pub fn player_buttons(key: MessageId, replay: &ReplayInfo) -> Vec<CreateActionRow> {
+    let buttons: Vec<CreateButton> = replay
+        .players
+        .iter()
+        .take(BUTTONS_PER_ROW * MAX_ROWS)
+        .map(|player| {
+            CreateButton::new(custom_id(key, player.slot))
+                .label(button_label(&player.name))
+                .style(ButtonStyle::Secondary)
+        })
+        .collect();
+    buttons
+        .chunks(BUTTONS_PER_ROW)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect()
+}
+
+fn custom_id(key: MessageId, slot: u8) -> String {
+    format!("{}{}:{}", CUSTOM_ID_PREFIX, key, slot)
+}
+
+/// The result and player slot a button's custom id points at
+pub fn parse_custom_id(custom_id: &str) -> Option<(MessageId, u8)> {
+    let (key, slot) = custom_id.strip_prefix(CUSTOM_ID_PREFIX)?.split_once(':')?;
+    let key = key.parse::<u64>().ok().filter(|&k| k != 0)?;
+    Some((MessageId::new(key), slot.parse().ok()?))
+}
+
+/// A name cut to fit a button, with "…" when shortened
+fn button_label(name: &str) -> String {
+    let name = name.trim();
+    if name.is_empty() {
+        return "?".to_string();
+    }
+    if name.chars().count() <= PLAYER_BUTTON_LABEL_CHARS {
+        return name.to_string();
+    }
+    let cut: String = name.chars().take(PLAYER_BUTTON_LABEL_CHARS - 1).collect();
+    format!("{}…", cut)
+}
+
+/// A player's details: faction (and the pick, when it differs), spot,
+/// team, and whatever activity and defeat data the replay had
+pub fn player_detail_text(replay: &ReplayInfo, player: &Player) -> String {
+    let mut lines = vec![format!("**{}**", player.name)];
+    let faction = player.display_faction();
+    if faction == player.faction {
+        lines.push(format!("Faction: {}", faction));
+    } else {
+        lines.push(format!("Faction: {} (picked {})", faction, player.faction));
+    }
+    let spot = player
+        .spot
+        .map_or_else(|| "Unknown".to_string(), |spot| spot.to_string());
+    lines.push(format!("Spot: {}", spot));
+    lines.push(format!("Team: {}", team_name(player.team)));
+    if let Some(apm) = player.apm(replay.duration_seconds()) {
+        lines.push(format!("APM: {}", apm));
+    }
+    if let Some(buildings) = player.buildings_text() {
+        lines.push(format!("Buildings: {}", buildings));
+    }
+    if let Some(at) = player.defeat_text() {
+        lines.push(format!("Defeated at {}", at));
+    }
+    if player.afk {
+        lines.push("AFK (never left the loading screen)".to_string());
+    }
+    lines.join("\n")
+}
+
+/// Answer a player button with that player's details, visible only to the
+/// clicker; buttons whose result was forgotten get the expired notice
+pub async fn handle_player_detail(
+    ctx: &serenity::Context,
+    component: &serenity::ComponentInteraction,
+    data: &Data,
+) {
+    let detail = parse_custom_id(&component.data.custom_id).and_then(|(key, slot)| {
+        let replay = data.player_details.get(key, component.channel_id)?;
+        let player = replay.players.iter().find(|p| p.slot == slot)?;
+        Some(player_detail_text(&replay, player))
+    });
+    let content = match detail {
+        Some(text) => text,
+        None => {
+            let locale = data.locale_for(ctx, component.guild_id).await;
+            UserMessage::button_expired().render(locale)
+        }
+    };
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+    );
+    if let Err(e) = component.create_response(ctx, response).await {
+        tracing::error!("Failed to answer player button: {}", e);
+    }
+}
+
+/// Whether a component interaction is a player button
+pub fn is_player_button(custom_id: &str) -> bool {
+    custom_id.starts_with(CUSTOM_ID_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, MapSpot, PlayerBuilder};
+
+    fn player(name: &str, slot: u8, faction: Faction) -> Player {
+        PlayerBuilder::test_player(name, 1, slot, faction)
+    }
+
+    fn replay(players: Vec<Player>) -> ReplayInfo {
+        ReplayInfo::new("map wor rhun".to_string(), players).with_times(1000, 1600)
+    }
+
+    #[test]
+    fn details_with_all_optional_data() {
+        let mut alice = player("Alice", 0, Faction::Random);
+        alice.actual_faction = Some(Faction::Mordor);
+        alice.spot = Some(MapSpot::TopLeft);
+        alice.command_count = 900;
+        alice.build_orders = 23;
+        alice.building_types = 7;
+        alice.defeated_at_secs = Some(842);
+        let game = replay(vec![alice.clone()]);
+        assert_eq!(
+            player_detail_text(&game, &alice),
+            "**Alice**\n\
+             Faction: Mordor (picked Random)\n\
+             Spot: Top Left\n\
+             Team: Left Team\n\
+             APM: 90\n\
+             Buildings: 23 build orders, 7 building types\n\
+             Defeated at 14:02"
+        );
+    }
+
+    #[test]
+    fn details_without_optional_data() {
+        let mut bob = player("Bob", 1, Faction::Elves);
+        bob.team = 2;
+        // No duration: no APM, even with commands
+        bob.command_count = 50;
+        let game = ReplayInfo::new("map wor rhun".to_string(), vec![bob.clone()]);
+        assert_eq!(
+            player_detail_text(&game, &bob),
+            "**Bob**\nFaction: Elves\nSpot: Unknown\nTeam: Right Team"
+        );
+
+        bob.afk = true;
+        assert!(player_detail_text(&game, &bob).ends_with("AFK (never left the loading screen)"));
+    }
+
+    #[test]
+    fn custom_ids_round_trip() {
+        let key = MessageId::new(1234);
+        assert_eq!(parse_custom_id(&custom_id(key, 5)), Some((key, 5)));
+        assert!(is_player_button(&custom_id(key, 5)));
+        assert!(!is_player_button("show_more:1_2_multi"));
+        assert_eq!(parse_custom_id("player_detail:1234"), None);
+        assert_eq!(parse_custom_id("player_detail:0:1"), None);
+        assert_eq!(parse_custom_id("player_detail:x:1"), None);
+        assert_eq!(parse_custom_id("player_detail:1234:999"), None);
+    }
+
+    #[test]
+    fn long_names_are_cut_to_fit_a_button() {
+        assert_eq!(button_label("Alice"), "Alice");
+        let long = "ThisNameIsMuchTooLongForAButton";
+        let label = button_label(long);
+        assert_eq!(label.chars().count(), PLAYER_BUTTON_LABEL_CHARS);
+        assert!(label.ends_with('…'));
+        assert_eq!(button_label("  "), "?");
+    }
+
+    #[test]
+    fn buttons_are_spread_over_rows_of_five() {
+        let players = (0..8).map(|i| player(&format!("P{}", i), i, Faction::Men));
+        let rows = player_buttons(MessageId::new(1), &replay(players.collect()));
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn stored_replays_expire_and_stay_in_their_channel() {
+        let key = MessageId::new(10);
+        let here = ChannelId::new(1);
+        let details = PlayerDetails::new();
+        details.store(key, here, replay(Vec::new()));
+        assert!(details.get(key, here).is_some());
+        assert!(details.get(key, ChannelId::new(2)).is_none());
+
+        let expired = PlayerDetails::with_limits(Duration::ZERO, 10);
+        expired.store(key, here, replay(Vec::new()));
+        assert!(expired.get(key, here).is_none());
+    }
+
+    #[test]
+    fn a_full_store_drops_its_oldest_entry() {
+        let here = ChannelId::new(1);
+        let details = PlayerDetails::with_limits(Duration::from_secs(60), 2);
+        for id in 1..=3 {
+            details.store(MessageId::new(id), here, replay(Vec::new()));
+        }
+        assert!(details.get(MessageId::new(1), here).is_none());
+        assert!(details.get(MessageId::new(2), here).is_some());
+        assert!(details.get(MessageId::new(3), here).is_some());
+    }
+}
@@ -0,0 +1,205 @@
+use poise::serenity_prelude as serenity;
+use serenity::{MessageId, ReactionType, UserId};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::constants::{DEFAULT_REPLAY_RETENTION_SECS, MAX_POSTED_REPLAY_BYTES, RERENDER_REACTION};
+use super::shared_map::{PoisonPolicy, SharedMap};
+
+/// What a single result was rendered from, so it can be rendered again
+#[derive(Debug, Clone)]
+pub struct PostedReplay {
+    /// The uploaded replay file
+    pub bytes: Arc<[u8]>,
+    pub filename: String,
+    /// Who uploaded it
+    pub requester: UserId,
+    /// The upload the result answered, which its player buttons are keyed by
+    pub upload: MessageId,
+    /// Shown as the animated reveal GIF
+    pub reveal: bool,
+    /// Shown without player names
+    pub anonymize: bool,
+}
+
+struct StoredReplay {
+    replay: PostedReplay,
+    at: Instant,
+}
+
+/// Replay files behind recent single results, keyed by the result message,
+/// so a 🔁 reaction can render them again with the current renderer. Entries
+/// go after the retention period, and the oldest make room once the stored
+/// files would pass the size cap.
+pub struct PostedReplays {
+    /// On poison: recover (a lost entry only stops that result re-rendering)
+    replays: SharedMap<MessageId, StoredReplay>,
+    retention: Duration,
+    max_bytes: usize,
+}
+
+impl PostedReplays {
+    pub fn new(retention: Duration) -> Self {
+        Self::with_limits(retention, MAX_POSTED_REPLAY_BYTES)
+    }
+
+    fn with_limits(retention: Duration, max_bytes: usize) -> Self {
+        Self {
+            replays: SharedMap::new("Posted replays", PoisonPolicy::Recover),
+            retention,
+            max_bytes,
+        }
+    }
+
+    /// Remember the replay behind a posted result. Files larger than the
+    /// whole cap are not kept; returns whether this one was.
+    pub fn store(&self, result: MessageId, replay: PostedReplay) -> bool {
+        if self.retention.is_zero() || replay.bytes.len() > self.max_bytes {
+            return false;
+        }
+        let now = Instant::now();
+        self.replays.write(|map| {
+            map.retain(|_, stored| now.duration_since(stored.at) < self.retention);
+            map.remove(&result);
+            let mut total: usize = map.values().map(|s| s.replay.bytes.len()).sum();
+            while total + replay.bytes.len() > self.max_bytes {
+                let Some(oldest) = map.iter().min_by_key(|(_, s)| s.at).map(|(k, _)| *k) else {
+                    break;
+                };
+                if let Some(evicted) = map.remove(&oldest) {
+                    total -= evicted.replay.bytes.len();
+                }
+            }
+            map.insert(result, StoredReplay { replay, at: now });
+        });
+        true
+    }
+
+    /// The replay behind a result, while remembered
+    pub fn get(&self, result: MessageId) -> Option<PostedReplay> {
+        let now = Instant::now();
+        self.replays.read(|map| {
+            map.get(&result)
+                .filter(|stored| now.duration_since(stored.at) < self.retention)
+                .map(|stored| stored.replay.clone())
+        })
+    }
+
+    /// Forget a deleted result's replay
+    pub fn forget(&self, result: MessageId) {
+        self.replays.write(|map| map.remove(&result));
+    }
+}
+
+impl Default for PostedReplays {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(DEFAULT_REPLAY_RETENTION_SECS))
+    }
+}
+
+/// Whether a reaction asks for a result to be rendered again (with or
+/// without the emoji variation selector)
+pub fn is_rerender_reaction(emoji: &ReactionType) -> bool {
+    let bare = |s: &str| s.replace('\u{fe0f}', "");
+    matches!(emoji, ReactionType::Unicode(text) if bare(text) == bare(RERENDER_REACTION))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u64) -> MessageId {
+        MessageId::new(n)
+    }
+
+    fn total_bytes(posted: &PostedReplays) -> usize {
+        posted
+            .replays
+            .read(|map| map.values().map(|s| s.replay.bytes.len()).sum())
+    }
+
+    fn replay(len: usize) -> PostedReplay {
+        PostedReplay {
+            bytes: vec![0; len].into(),
+            filename: "game.BfME2Replay".to_string(),
+            requester: UserId::new(1),
+            upload: id(100),
+            reveal: false,
+            anonymize: false,
+        }
+    }
+
+    #[test]
+    fn stored_replays_come_back_until_forgotten() {
+        let posted = PostedReplays::default();
+        assert!(posted.store(id(1), replay(10)));
+        let stored = posted.get(id(1)).unwrap();
+        assert_eq!(stored.bytes.len(), 10);
+        assert_eq!(stored.upload, id(100));
+        assert!(posted.get(id(2)).is_none());
+
+        posted.forget(id(1));
+        assert!(posted.get(id(1)).is_none());
+    }
+
+    #[test]
+    fn replays_past_their_retention_are_dropped() {
+        let posted = PostedReplays::with_limits(Duration::from_nanos(1), 100);
+        posted.store(id(1), replay(10));
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(posted.get(id(1)).is_none());
+        // Expired entries are dropped on the next store
+        posted.store(id(2), replay(10));
+        assert_eq!(total_bytes(&posted), 10);
+    }
+
+    #[test]
+    fn zero_retention_keeps_nothing() {
+        let posted = PostedReplays::new(Duration::ZERO);
+        assert!(!posted.store(id(1), replay(10)));
+        assert_eq!(total_bytes(&posted), 0);
+    }
+
+    #[test]
+    fn oldest_replays_make_room_under_the_size_cap() {
+        let posted = PostedReplays::with_limits(Duration::from_secs(60), 100);
+        posted.store(id(1), replay(40));
+        posted.store(id(2), replay(40));
+        posted.store(id(3), replay(40));
+        assert!(posted.get(id(1)).is_none());
+        assert!(posted.get(id(2)).is_some());
+        assert!(posted.get(id(3)).is_some());
+        assert_eq!(total_bytes(&posted), 80);
+
+        // One large file can push out several small ones
+        posted.store(id(4), replay(90));
+        assert!(posted.get(id(2)).is_none());
+        assert!(posted.get(id(3)).is_none());
+        assert_eq!(total_bytes(&posted), 90);
+    }
+
+    #[test]
+    fn a_file_over_the_whole_cap_is_not_kept() {
+        let posted = PostedReplays::with_limits(Duration::from_secs(60), 100);
+        posted.store(id(1), replay(50));
+        assert!(!posted.store(id(2), replay(101)));
+        // Nothing was evicted for it
+        assert!(posted.get(id(1)).is_some());
+    }
+
+    #[test]
+    fn storing_a_result_again_replaces_its_replay() {
+        let posted = PostedReplays::with_limits(Duration::from_secs(60), 100);
+        posted.store(id(1), replay(60));
+        posted.store(id(1), replay(70));
+        assert_eq!(total_bytes(&posted), 70);
+    }
+
+    #[test]
+    fn rerender_reaction_matches_with_or_without_variation_selector() {
+        let unicode = |s: &str| ReactionType::Unicode(s.to_string());
+        assert!(is_rerender_reaction(&unicode("🔁")));
+        assert!(is_rerender_reaction(&unicode("🔁\u{fe0f}")));
+        assert!(!is_rerender_reaction(&unicode("🗑️")));
+    }
+}
@@ -0,0 +1,138 @@
+use poise::serenity_prelude as serenity;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Whether the Discord gateway is connected, for the `/ready` probe, and
+/// since when it has been reconnecting after losing a connection. Clones
+/// share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness(Arc<ReadinessState>);
+
+#[derive(Debug, Default)]
+struct ReadinessState {
+    ready: AtomicBool,
+    /// Unix time the connection was lost, 0 while connected or never connected
+    reconnecting_since: AtomicU64,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.ready.load(Ordering::Relaxed)
+    }
+
+    /// Unix time the gateway was lost, while reconnecting
+    pub fn reconnecting_since(&self) -> Option<u64> {
+        match self.0.reconnecting_since.load(Ordering::Relaxed) {
+            0 => None,
+            since => Some(since),
+        }
+    }
+
+    /// Record the connection state, logging changes. Losing a connection
+    /// starts the reconnecting period; connecting ends it.
+    pub fn set_ready(&self, ready: bool) {
+        let was_ready = self.0.ready.swap(ready, Ordering::Relaxed);
+        if was_ready != ready {
+            if ready {
+                tracing::info!("Gateway connected, ready");
+            } else {
+                tracing::warn!("Gateway disconnected, not ready");
+            }
+        }
+        if ready {
+            self.0.reconnecting_since.store(0, Ordering::Relaxed);
+        } else if was_ready {
+            self.mark_reconnecting();
+        }
+    }
+
+    /// Record that the client is being restarted: not ready, reconnecting
+    /// since now unless it already was
+    pub fn mark_reconnecting(&self) {
+        self.0.ready.store(false, Ordering::Relaxed);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |d| d.as_secs().max(1));
+        let _ = self.0.reconnecting_since.compare_exchange(
+            0,
+            now,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Follow a shard's connection stage: ready only once fully connected
+    pub fn on_stage(&self, stage: serenity::ConnectionStage) {
+        self.set_ready(stage == serenity::ConnectionStage::Connected);
+    }
+
+    /// Body of the `/ready` probe: READY, RECONNECTING with when the
+    /// connection was lost, or NOT READY before the first connection
+    pub fn probe_text(&self) -> String {
+        if self.is_ready() {
+            return "READY".to_string();
+        }
+        match self.reconnecting_since() {
+            Some(since) => format!("RECONNECTING since unix {}", since),
+            None => "NOT READY".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poise::serenity_prelude::ConnectionStage;
+
+    #[test]
+    fn follows_the_connection_stage() {
+        let readiness = Readiness::new();
+        let probe = readiness.clone();
+        assert!(!probe.is_ready());
+
+        readiness.set_ready(true);
+        assert!(probe.is_ready());
+
+        // Dropped connection, reconnect attempts, then back
+        for stage in [
+            ConnectionStage::Disconnected,
+            ConnectionStage::Connecting,
+            ConnectionStage::Handshake,
+            ConnectionStage::Resuming,
+        ] {
+            readiness.on_stage(stage);
+            assert!(!probe.is_ready(), "{:?}", stage);
+        }
+        readiness.on_stage(ConnectionStage::Connected);
+        assert!(probe.is_ready());
+    }
+
+    #[test]
+    fn reconnecting_period_runs_from_the_lost_connection() {
+        let readiness = Readiness::new();
+        // Not connected yet: starting, not reconnecting
+        readiness.on_stage(ConnectionStage::Connecting);
+        assert_eq!(readiness.reconnecting_since(), None);
+        assert_eq!(readiness.probe_text(), "NOT READY");
+
+        readiness.set_ready(true);
+        assert_eq!(readiness.probe_text(), "READY");
+        readiness.on_stage(ConnectionStage::Disconnected);
+        let since = readiness.reconnecting_since().expect("reconnecting");
+        // Restarting the client keeps the time the connection was lost
+        readiness.mark_reconnecting();
+        assert_eq!(readiness.reconnecting_since(), Some(since));
+        assert_eq!(
+            readiness.probe_text(),
+            format!("RECONNECTING since unix {}", since)
+        );
+
+        readiness.on_stage(ConnectionStage::Connected);
+        assert_eq!(readiness.reconnecting_since(), None);
+    }
+}
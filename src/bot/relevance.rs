@@ -0,0 +1,369 @@
+use poise::serenity_prelude as serenity;
+
+use super::user_message::UserMessage;
+
+/// Largest replay file accepted, posted directly or inside an archive
+pub const MAX_REPLAY_BYTES: u64 = 5 * 1024 * 1024; // 5MB
+
+/// Largest archive accepted in a server, and largest archive nested in one
+pub const MAX_ARCHIVE_BYTES: u64 = 25 * 1024 * 1024; // 25MB
+
+/// Largest archive accepted in a DM (DMs have no moderation)
+pub const MAX_DM_ARCHIVE_BYTES: u64 = 10 * 1024 * 1024; // 10MB
+
+/// Where a file was found, which decides the size limits it gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Venue {
+    /// Attached to a server message
+    Guild,
+    /// Attached to a DM
+    Dm,
+    /// An entry inside an archive
+    Archive,
+}
+
+impl Venue {
+    /// Venue of a message's attachments
+    pub fn of_message(guild_id: Option<serenity::GuildId>) -> Self {
+        if guild_id.is_some() {
+            Venue::Guild
+        } else {
+            Venue::Dm
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Rar,
+}
+
+impl ArchiveKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ArchiveKind::Zip => "ZIP",
+            ArchiveKind::Rar => "RAR",
+        }
+    }
+}
+
+/// A file the bot can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Replay,
+    Archive(ArchiveKind),
+}
+
+impl FileKind {
+    /// Largest accepted size of this kind of file in `venue`
+    pub fn max_bytes(self, venue: Venue) -> u64 {
+        match (self, venue) {
+            (FileKind::Replay, _) => MAX_REPLAY_BYTES,
+            (FileKind::Archive(_), Venue::Dm) => MAX_DM_ARCHIVE_BYTES,
+            (FileKind::Archive(_), _) => MAX_ARCHIVE_BYTES,
+        }
+    }
+}
+
+/// Replay file extensions, lowercase: the game's own and the aliases some
+/// community tools export the same bytes under
+const REPLAY_EXTENSIONS: &[&str] = &["bfme2replay", "bfme2replayuncompressed", "rep"];
+
+/// Whether a file name has a replay extension (case-insensitive)
+pub fn is_replay_filename(name: &str) -> bool {
+    name.rsplit_once('.').is_some_and(|(_, ext)| {
+        REPLAY_EXTENSIONS
+            .iter()
+            .any(|known| ext.eq_ignore_ascii_case(known))
+    })
+}
+
+/// What the bot does with an attachment or archive entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentClass {
+    Replay,
+    Archive(ArchiveKind),
+    /// A readable kind of file over the size limit for its venue; the
+    /// user is told so instead of it being downloaded
+    TooLarge {
+        kind: FileKind,
+        max_bytes: u64,
+    },
+    /// No known extension but could be a replay by its content type (or,
+    /// inside an archive, by being small enough); read to check its header.
+    /// Not enough on its own to make a message relevant.
+    SniffCandidate,
+    Irrelevant,
+}
+
+impl AttachmentClass {
+    /// Kind of a recognised file, whether or not it is within the size limit
+    pub fn kind(self) -> Option<FileKind> {
+        match self {
+            AttachmentClass::Replay => Some(FileKind::Replay),
+            AttachmentClass::Archive(kind) => Some(FileKind::Archive(kind)),
+            AttachmentClass::TooLarge { kind, .. } => Some(kind),
+            AttachmentClass::SniffCandidate | AttachmentClass::Irrelevant => None,
+        }
+    }
+
+    /// Whether the file warrants a reply (a result or a "too large" error)
+    pub fn is_relevant(self) -> bool {
+        self.kind().is_some()
+    }
+
+    /// Error shown for a file over its size limit
+    pub fn too_large_message(self) -> Option<UserMessage> {
+        match self {
+            AttachmentClass::TooLarge {
+                kind: FileKind::Replay,
+                ..
+            } => Some(UserMessage::replay_too_large()),
+            AttachmentClass::TooLarge {
+                kind: FileKind::Archive(_),
+                max_bytes,
+            } => Some(UserMessage::archive_too_large(max_bytes / (1024 * 1024))),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of file a name and content type point to. The extension decides;
+/// the content type only names archives whose extension was lost.
+fn file_kind(name: &str, content_type: Option<&str>) -> Option<FileKind> {
+    if is_replay_filename(name) {
+        return Some(FileKind::Replay);
+    }
+    let name = name.to_lowercase();
+    if name.ends_with(".zip") {
+        return Some(FileKind::Archive(ArchiveKind::Zip));
+    }
+    if name.ends_with(".rar") {
+        return Some(FileKind::Archive(ArchiveKind::Rar));
+    }
+    if name.contains('.') {
+        return None;
+    }
+    match content_type.map(mime_essence) {
+        Some("application/zip" | "application/x-zip-compressed") => {
+            Some(FileKind::Archive(ArchiveKind::Zip))
+        }
+        Some("application/vnd.rar" | "application/x-rar-compressed") => {
+            Some(FileKind::Archive(ArchiveKind::Rar))
+        }
+        _ => None,
+    }
+}
+
+/// Content type without parameters, e.g. `text/plain` for `text/plain; charset=utf-8`
+fn mime_essence(content_type: &str) -> &str {
+    content_type.split(';').next().unwrap_or("").trim()
+}
+
+/// Classify a file by name, size and (if known) content type. Every
+/// ingestion path goes through here, so a file accepted up front is never
+/// rejected later for its size.
+pub fn classify_attachment(
+    name: &str,
+    size: u64,
+    content_type: Option<&str>,
+    venue: Venue,
+) -> AttachmentClass {
+    let Some(kind) = file_kind(name, content_type) else {
+        let binary = content_type.is_none_or(|t| mime_essence(t) == "application/octet-stream");
+        // Archive entries are already in hand, so any name is worth a look
+        return if (venue == Venue::Archive || !name.contains('.'))
+            && binary
+            && size <= MAX_REPLAY_BYTES
+        {
+            AttachmentClass::SniffCandidate
+        } else {
+            AttachmentClass::Irrelevant
+        };
+    };
+    // Nested archives are only read from ZIPs
+    if venue == Venue::Archive && kind == FileKind::Archive(ArchiveKind::Rar) {
+        return AttachmentClass::Irrelevant;
+    }
+    let max_bytes = kind.max_bytes(venue);
+    if size > max_bytes {
+        return AttachmentClass::TooLarge { kind, max_bytes };
+    }
+    match kind {
+        FileKind::Replay => AttachmentClass::Replay,
+        FileKind::Archive(kind) => AttachmentClass::Archive(kind),
+    }
+}
+
+/// Classify a Discord attachment
+pub fn classify(attachment: &serenity::Attachment, venue: Venue) -> AttachmentClass {
+    classify_attachment(
+        &attachment.filename,
+        u64::from(attachment.size),
+        attachment.content_type.as_deref(),
+        venue,
+    )
+}
+
+/// Whether any attachment warrants a reply
+pub fn message_has_relevant(attachments: &[serenity::Attachment], venue: Venue) -> bool {
+    attachments.iter().any(|a| classify(a, venue).is_relevant())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::i18n::Locale;
+
+    const MB: u64 = 1024 * 1024;
+    const VENUES: [Venue; 3] = [Venue::Guild, Venue::Dm, Venue::Archive];
+
+    #[test]
+    fn replays_are_accepted_everywhere_up_to_the_same_limit() {
+        for venue in VENUES {
+            for name in ["game.BfME2Replay", "game.bfme2replay", "a.b.BFME2REPLAY"] {
+                for content_type in [None, Some("application/octet-stream")] {
+                    let classify = |size| classify_attachment(name, size, content_type, venue);
+                    assert_eq!(classify(0), AttachmentClass::Replay);
+                    assert_eq!(classify(5 * MB), AttachmentClass::Replay);
+                    assert_eq!(
+                        classify(5 * MB + 1),
+                        AttachmentClass::TooLarge {
+                            kind: FileKind::Replay,
+                            max_bytes: 5 * MB
+                        },
+                        "{name} in {venue:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn archive_limits_depend_on_the_venue() {
+        let cases = [
+            ("r.zip", Venue::Guild, Some(ArchiveKind::Zip), 25 * MB),
+            ("r.ZIP", Venue::Dm, Some(ArchiveKind::Zip), 10 * MB),
+            ("r.zip", Venue::Archive, Some(ArchiveKind::Zip), 25 * MB),
+            ("r.rar", Venue::Guild, Some(ArchiveKind::Rar), 25 * MB),
+            ("r.Rar", Venue::Dm, Some(ArchiveKind::Rar), 10 * MB),
+            // Nested RARs are never read
+            ("r.rar", Venue::Archive, None, 25 * MB),
+        ];
+        for (name, venue, kind, limit) in cases {
+            for content_type in [None, Some("application/zip"), Some("application/vnd.rar")] {
+                let classify = |size| classify_attachment(name, size, content_type, venue);
+                let Some(kind) = kind else {
+                    assert_eq!(classify(1), AttachmentClass::Irrelevant);
+                    continue;
+                };
+                assert_eq!(classify(limit), AttachmentClass::Archive(kind));
+                assert_eq!(
+                    classify(limit + 1),
+                    AttachmentClass::TooLarge {
+                        kind: FileKind::Archive(kind),
+                        max_bytes: limit
+                    },
+                    "{name} in {venue:?}"
+                );
+            }
+        }
+        // A 26MB server ZIP is turned away up front, with the archive message
+        let class = classify_attachment("r.zip", 26 * MB, None, Venue::Guild);
+        assert_eq!(
+            class.too_large_message().unwrap().render(Locale::En),
+            UserMessage::archive_too_large(25).render(Locale::En)
+        );
+        assert!(class.is_relevant());
+    }
+
+    #[test]
+    fn content_type_only_names_extensionless_archives() {
+        let zip = classify_attachment("replays", MB, Some("application/zip"), Venue::Guild);
+        assert_eq!(zip, AttachmentClass::Archive(ArchiveKind::Zip));
+        let rar = classify_attachment(
+            "replays",
+            MB,
+            Some("application/x-rar-compressed; charset=binary"),
+            Venue::Dm,
+        );
+        assert_eq!(rar, AttachmentClass::Archive(ArchiveKind::Rar));
+        // A named file keeps its extension's meaning
+        assert_eq!(
+            classify_attachment("notes.txt", MB, Some("application/zip"), Venue::Guild),
+            AttachmentClass::Irrelevant
+        );
+        assert_eq!(
+            classify_attachment("game.bfme2replay", MB, Some("text/plain"), Venue::Guild),
+            AttachmentClass::Replay
+        );
+    }
+
+    #[test]
+    fn extensionless_binaries_are_sniff_candidates_only() {
+        for venue in [Venue::Guild, Venue::Dm] {
+            for content_type in [None, Some("application/octet-stream")] {
+                let class = classify_attachment("Last Replay", MB, content_type, venue);
+                assert_eq!(class, AttachmentClass::SniffCandidate);
+                assert!(!class.is_relevant());
+                assert_eq!(
+                    classify_attachment("Last Replay", 6 * MB, content_type, venue),
+                    AttachmentClass::Irrelevant
+                );
+            }
+            assert_eq!(
+                classify_attachment("Last Replay", MB, Some("image/png"), venue),
+                AttachmentClass::Irrelevant
+            );
+        }
+    }
+
+    #[test]
+    fn small_archive_entries_are_sniffed_whatever_their_name() {
+        for name in ["Last Replay", "notes.txt", "game.bfme2replay.bak"] {
+            let classify = |size| classify_attachment(name, size, None, Venue::Archive);
+            assert_eq!(classify(MB), AttachmentClass::SniffCandidate, "{name}");
+            assert_eq!(classify(6 * MB), AttachmentClass::Irrelevant, "{name}");
+        }
+        // Outside archives a named file is taken by its extension
+        assert_eq!(
+            classify_attachment("notes.txt", MB, None, Venue::Guild),
+            AttachmentClass::Irrelevant
+        );
+    }
+
+    #[test]
+    fn replay_aliases_are_replays() {
+        for name in [
+            "game.rep",
+            "game.REP",
+            "game.BfME2ReplayUncompressed",
+            "game.bfme2replayuncompressed",
+        ] {
+            assert!(is_replay_filename(name), "{name}");
+            for venue in VENUES {
+                assert_eq!(
+                    classify_attachment(name, MB, None, venue),
+                    AttachmentClass::Replay,
+                    "{name} in {venue:?}"
+                );
+            }
+        }
+        for name in ["game.report", "rep", "game.bfme2replay.txt", "game.replay"] {
+            assert!(!is_replay_filename(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn other_files_are_irrelevant() {
+        for venue in VENUES {
+            for name in ["map.png", "game.bfme2replay.txt", "zip", "archive.7z", ""] {
+                let class = classify_attachment(name, MB, Some("image/png"), venue);
+                assert_eq!(class, AttachmentClass::Irrelevant, "{name:?}");
+                assert_eq!(class.kind(), None);
+                assert!(class.too_large_message().is_none());
+            }
+        }
+    }
+}
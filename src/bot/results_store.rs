@@ -0,0 +1,458 @@
+use crate::models::{Faction, ReplayInfo, Side};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::constants::{MIN_STATS_SAMPLE, RESULTS_LOG_MAX_BYTES};
+
+/// Factions always listed in the stats table, in order; `Random` counts
+/// players whose actual faction stayed unknown
+const STANDARD_FACTIONS: [Faction; 8] = [
+    Faction::Men,
+    Faction::Elves,
+    Faction::Dwarves,
+    Faction::Isengard,
+    Faction::Mordor,
+    Faction::Goblins,
+    Faction::Angmar,
+    Faction::Random,
+];
+
+/// Outcome of one parsed game, without where it was posted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameResult {
+    /// [`ReplayInfo::fingerprint`], so a game posted twice counts once
+    pub game: u64,
+    pub map: String,
+    /// Faction of each player on the left side
+    pub left: Vec<String>,
+    /// Faction of each player on the right side
+    pub right: Vec<String>,
+    /// None for crashed, unfinished and undecided games
+    pub winner: Option<Side>,
+}
+
+impl GameResult {
+    pub fn of(replay: &ReplayInfo) -> Self {
+        let factions = |team: i8| {
+            replay
+                .players
+                .iter()
+                .filter(|p| p.team == team)
+                .map(|p| p.display_faction().to_string())
+                .collect()
+        };
+        Self {
+            game: replay.fingerprint(),
+            map: replay.map_name.clone(),
+            left: factions(1),
+            right: factions(2),
+            winner: replay.winner.side().filter(|_| !replay.game_crashed),
+        }
+    }
+}
+
+/// One line of the results log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultRecord {
+    /// Unix seconds when the result was recorded
+    pub at: u64,
+    pub guild_id: Option<u64>,
+    pub channel_id: u64,
+    #[serde(flatten)]
+    pub result: GameResult,
+}
+
+/// Append-only JSONL log of parsed games. Once the file reaches its size cap
+/// it is moved to `<path>.1` (replacing the previous one) and a new file started.
+#[derive(Debug, Clone)]
+pub struct ResultsStore {
+    path: PathBuf,
+    max_bytes: u64,
+    /// Serializes appends and rotation between background writes
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl ResultsStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self::with_max_bytes(path, RESULTS_LOG_MAX_BYTES)
+    }
+
+    fn with_max_bytes(path: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            path,
+            max_bytes,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Append results posted in a channel, on a blocking thread
+    pub fn record(
+        &self,
+        guild_id: Option<serenity::GuildId>,
+        channel_id: serenity::ChannelId,
+        results: Vec<GameResult>,
+    ) {
+        if results.is_empty() {
+            return;
+        }
+        let at = unix_now();
+        let records: Vec<ResultRecord> = results
+            .into_iter()
+            .map(|result| ResultRecord {
+                at,
+                guild_id: guild_id.map(|id| id.get()),
+                channel_id: channel_id.get(),
+                result,
+            })
+            .collect();
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = store.append(&records) {
+                tracing::warn!("Failed to append to results log {:?}: {}", store.path, e);
+            }
+        });
+    }
+
+    fn append(&self, records: &[ResultRecord]) -> io::Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|p| p.into_inner());
+        if fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_bytes) {
+            fs::rename(&self.path, self.rotated_path())?;
+            tracing::info!("Rotated results log {:?}", self.path);
+        }
+        let mut lines = String::new();
+        for record in records {
+            lines.push_str(&serde_json::to_string(record).map_err(io::Error::other)?);
+            lines.push('\n');
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(lines.as_bytes())
+    }
+
+    /// Records from `since` (unix seconds) on, oldest first, across the
+    /// rotated and current files. Lines that don't parse are skipped.
+    pub fn load_since(&self, since: u64) -> io::Result<Vec<ResultRecord>> {
+        let mut records = Vec::new();
+        for path in [self.rotated_path(), self.path.clone()] {
+            read_records(&path, since, &mut records)?;
+        }
+        Ok(records)
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".1");
+        path.into()
+    }
+}
+
+fn read_records(path: &Path, since: u64, out: &mut Vec<ResultRecord>) -> io::Result<()> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut skipped = 0;
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str::<ResultRecord>(&line?) {
+            Ok(record) if record.at >= since => out.push(record),
+            Ok(_) => {}
+            Err(_) => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        tracing::warn!("Skipped {} unreadable lines in {:?}", skipped, path);
+    }
+    Ok(())
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Picks and results of one faction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactionRow {
+    pub faction: String,
+    /// Players who played it
+    pub picks: usize,
+    /// Picks in games with a winner
+    pub decided: usize,
+    pub wins: usize,
+}
+
+impl FactionRow {
+    fn empty(faction: String) -> Self {
+        Self {
+            faction,
+            picks: 0,
+            decided: 0,
+            wins: 0,
+        }
+    }
+
+    /// Rounded win rate over decided games; None when it never played one
+    pub fn win_percent(&self) -> Option<usize> {
+        (self.decided > 0).then(|| (self.wins * 100 + self.decided / 2) / self.decided)
+    }
+
+    /// Too few decided games for the win rate to mean much
+    pub fn small_sample(&self) -> bool {
+        self.decided > 0 && self.decided < MIN_STATS_SAMPLE
+    }
+}
+
+/// Faction pick and win counts over logged games
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactionStats {
+    pub games: usize,
+    /// The standard factions in order, then any others by name
+    pub rows: Vec<FactionRow>,
+}
+
+impl FactionStats {
+    /// Count each game once (its latest record) even when posted repeatedly
+    pub fn collect<'a>(records: impl IntoIterator<Item = &'a ResultRecord>) -> Self {
+        let games: HashMap<u64, &GameResult> = records
+            .into_iter()
+            .map(|r| (r.result.game, &r.result))
+            .collect();
+
+        let mut rows: Vec<FactionRow> = STANDARD_FACTIONS
+            .iter()
+            .map(|f| FactionRow::empty(f.to_string()))
+            .collect();
+        for game in games.values() {
+            for (side, factions) in [(Side::Left, &game.left), (Side::Right, &game.right)] {
+                for faction in factions {
+                    let i = match rows.iter().position(|r| &r.faction == faction) {
+                        Some(i) => i,
+                        None => {
+                            rows.push(FactionRow::empty(faction.clone()));
+                            rows.len() - 1
+                        }
+                    };
+                    let row = &mut rows[i];
+                    row.picks += 1;
+                    if let Some(winner) = game.winner {
+                        row.decided += 1;
+                        if winner == side {
+                            row.wins += 1;
+                        }
+                    }
+                }
+            }
+        }
+        rows[STANDARD_FACTIONS.len()..].sort_by(|a, b| a.faction.cmp(&b.faction));
+        Self {
+            games: games.len(),
+            rows,
+        }
+    }
+
+    /// Whether any win rate rests on too few games (marked `*` in the table)
+    pub fn has_small_samples(&self) -> bool {
+        self.rows.iter().any(FactionRow::small_sample)
+    }
+
+    /// Monospace table in a code block, with the given column headers
+    pub fn table(&self, headers: [&str; 4]) -> String {
+        let width = self
+            .rows
+            .iter()
+            .map(|r| r.faction.chars().count())
+            .chain([headers[0].chars().count()])
+            .max()
+            .unwrap_or(0);
+        let mut table = format!(
+            "```\n{:<width$}  {:>6}  {:>6}  {:>6}\n",
+            headers[0], headers[1], headers[2], headers[3]
+        );
+        for row in &self.rows {
+            let rate = match row.win_percent() {
+                Some(p) if row.small_sample() => format!("{}%*", p),
+                Some(p) => format!("{}%", p),
+                None => "-".to_string(),
+            };
+            table.push_str(&format!(
+                "{:<width$}  {:>6}  {:>6}  {:>6}\n",
+                row.faction, row.picks, row.wins, rate
+            ));
+        }
+        table.push_str("```");
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(game: u64, left: &[&str], right: &[&str], winner: Option<Side>) -> ResultRecord {
+        ResultRecord {
+            at: 1_700_000_000 + game,
+            guild_id: Some(42),
+            channel_id: 7,
+            result: GameResult {
+                game,
+                map: "map wor rhun".to_string(),
+                left: left.iter().map(|f| f.to_string()).collect(),
+                right: right.iter().map(|f| f.to_string()).collect(),
+                winner,
+            },
+        }
+    }
+
+    fn row<'a>(stats: &'a FactionStats, faction: &str) -> &'a FactionRow {
+        stats.rows.iter().find(|r| r.faction == faction).unwrap()
+    }
+
+    fn seeded_store(dir: &Path) -> ResultsStore {
+        let store = ResultsStore::new(dir.join("results.jsonl"));
+        store
+            .append(&[
+                record(
+                    1,
+                    &["Men", "Elves"],
+                    &["Mordor", "Isengard"],
+                    Some(Side::Left),
+                ),
+                record(
+                    2,
+                    &["Men", "Dwarves"],
+                    &["Mordor", "Goblins"],
+                    Some(Side::Right),
+                ),
+                record(
+                    3,
+                    &["Men", "Elves"],
+                    &["Mordor", "Angmar"],
+                    Some(Side::Left),
+                ),
+                // Crashed: picks count, results don't
+                record(4, &["Elves", "Men"], &["Goblins", "Mordor"], None),
+            ])
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn aggregates_picks_and_wins_from_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = seeded_store(dir.path());
+        let records = store.load_since(0).unwrap();
+        assert_eq!(records.len(), 4);
+
+        let stats = FactionStats::collect(&records);
+        assert_eq!(stats.games, 4);
+        let men = row(&stats, "Men");
+        assert_eq!((men.picks, men.decided, men.wins), (4, 3, 2));
+        assert_eq!(men.win_percent(), Some(67));
+        let mordor = row(&stats, "Mordor");
+        assert_eq!((mordor.picks, mordor.wins), (4, 1));
+        assert_eq!(row(&stats, "Goblins").win_percent(), Some(100));
+        assert!(stats.has_small_samples());
+    }
+
+    #[test]
+    fn unpicked_factions_have_no_win_rate() {
+        let records = [record(1, &["Men"], &["Mordor"], Some(Side::Left))];
+        let stats = FactionStats::collect(&records);
+        let dwarves = row(&stats, "Dwarves");
+        assert_eq!((dwarves.picks, dwarves.decided), (0, 0));
+        assert_eq!(dwarves.win_percent(), None);
+        assert!(!dwarves.small_sample());
+
+        // Picked only in undecided games: still no rate
+        let records = [record(1, &["Men"], &["Mordor"], None)];
+        let stats = FactionStats::collect(&records);
+        assert_eq!(row(&stats, "Men").win_percent(), None);
+
+        let table = FactionStats::collect(&[]).table(["Faction", "Picks", "Wins", "Win%"]);
+        assert!(
+            table.contains("Dwarves        0       0       -\n"),
+            "{}",
+            table
+        );
+    }
+
+    #[test]
+    fn reposted_games_count_once_and_odd_factions_follow() {
+        let records = [
+            record(1, &["Men"], &["Mordor"], Some(Side::Left)),
+            record(1, &["Men"], &["Mordor"], Some(Side::Left)),
+            record(2, &["Unknown(9)"], &["Mordor"], Some(Side::Left)),
+        ];
+        let stats = FactionStats::collect(&records);
+        assert_eq!(stats.games, 2);
+        assert_eq!(row(&stats, "Men").picks, 1);
+        assert_eq!(stats.rows.last().unwrap().faction, "Unknown(9)");
+        assert_eq!(stats.rows.len(), STANDARD_FACTIONS.len() + 1);
+    }
+
+    #[test]
+    fn load_filters_by_time_and_reads_the_rotated_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // Any existing content triggers rotation on the next append
+        let store = ResultsStore::with_max_bytes(dir.path().join("results.jsonl"), 1);
+        store
+            .append(&[record(1, &["Men"], &["Mordor"], Some(Side::Left))])
+            .unwrap();
+        store
+            .append(&[record(5, &["Elves"], &["Angmar"], Some(Side::Right))])
+            .unwrap();
+        assert!(dir.path().join("results.jsonl.1").exists());
+
+        let all = store.load_since(0).unwrap();
+        let games: Vec<u64> = all.iter().map(|r| r.result.game).collect();
+        assert_eq!(games, [1, 5]);
+        let recent = store.load_since(1_700_000_003).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].result.winner, Some(Side::Right));
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = seeded_store(dir.path());
+        OpenOptions::new()
+            .append(true)
+            .open(dir.path().join("results.jsonl"))
+            .unwrap()
+            .write_all(b"{not json\n")
+            .unwrap();
+        assert_eq!(store.load_since(0).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn game_results_follow_team_sides() {
+        use crate::models::{PlayerBuilder, Winner};
+        let player = |name: &str, team, faction| {
+            PlayerBuilder::test_player(name, team, team as u8 - 1, faction)
+        };
+        let players = vec![
+            player("Alice", 1, Faction::Men),
+            player("Bob", 2, Faction::Mordor),
+        ];
+        let replay = ReplayInfo::new("map wor rhun".to_string(), players)
+            .with_winner(Winner::LikelyRightTeam);
+        let result = GameResult::of(&replay);
+        assert_eq!(result.left, ["Men"]);
+        assert_eq!(result.right, ["Mordor"]);
+        assert_eq!(result.winner, Some(Side::Right));
+
+        let crashed = GameResult::of(&replay.with_game_crashed(true));
+        assert_eq!(crashed.winner, None);
+    }
+}
@@ -0,0 +1,147 @@
+use poise::serenity_prelude as serenity;
+use serenity::MessageId;
+use std::time::{Duration, Instant};
+
+use super::constants::SEEN_MESSAGE_TTL_SECS;
+use super::shared_map::{PoisonPolicy, SharedMap};
+
+/// What the bot knows of a recent message
+#[derive(Debug, Clone, Copy)]
+struct Seen {
+    /// Attachments it had when last looked at
+    attachments: usize,
+    /// Already handled through an edit; redundant update events are dropped
+    processed: bool,
+    at: Instant,
+}
+
+/// Recent messages and their attachment counts. The cache is disabled, so
+/// edits can only be compared against what was remembered here.
+pub struct SeenMessages {
+    /// On poison: recover (a lost entry only means an edit goes unanswered)
+    messages: SharedMap<MessageId, Seen>,
+    ttl: Duration,
+}
+
+impl SeenMessages {
+    pub fn new() -> Self {
+        Self::with_ttl(Duration::from_secs(SEEN_MESSAGE_TTL_SECS))
+    }
+
+    fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            messages: SharedMap::new("Seen messages", PoisonPolicy::Recover),
+            ttl,
+        }
+    }
+
+    /// Remember a posted message. A message already known (e.g. refetched
+    /// for an edit) keeps its entry.
+    pub fn observe(&self, id: MessageId, attachments: usize) {
+        let now = Instant::now();
+        self.messages.write(|map| {
+            map.retain(|_, seen| now.duration_since(seen.at) < self.ttl);
+            map.entry(id).or_insert(Seen {
+                attachments,
+                processed: false,
+                at: now,
+            });
+        });
+    }
+
+    /// Whether an edit should go through the attachment pipeline; claiming it
+    /// marks the message processed so repeated update events are ignored
+    pub fn claim_edit(&self, id: MessageId, attachments: usize, relevant: bool) -> bool {
+        let now = Instant::now();
+        self.messages.write(|map| {
+            let seen = map
+                .get_mut(&id)
+                .filter(|seen| now.duration_since(seen.at) < self.ttl);
+            let Some(seen) = seen else {
+                return false;
+            };
+            if !attachments_added_by_edit(seen, attachments, relevant) {
+                seen.attachments = seen.attachments.max(attachments);
+                return false;
+            }
+            seen.attachments = attachments;
+            seen.processed = true;
+            true
+        })
+    }
+}
+
+impl Default for SeenMessages {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An edit brought relevant attachments the message did not have before
+fn attachments_added_by_edit(seen: &Seen, attachments: usize, relevant: bool) -> bool {
+    !seen.processed && relevant && attachments > seen.attachments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u64) -> MessageId {
+        MessageId::new(n)
+    }
+
+    fn seen(attachments: usize, processed: bool) -> Seen {
+        Seen {
+            attachments,
+            processed,
+            at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn only_new_relevant_attachments_count_as_added() {
+        assert!(attachments_added_by_edit(&seen(0, false), 1, true));
+        assert!(attachments_added_by_edit(&seen(1, false), 2, true));
+        // Text-only edit, or an attachment removed
+        assert!(!attachments_added_by_edit(&seen(1, false), 1, true));
+        assert!(!attachments_added_by_edit(&seen(2, false), 1, true));
+        // Added, but nothing the bot handles
+        assert!(!attachments_added_by_edit(&seen(0, false), 1, false));
+        // Already answered through an earlier edit
+        assert!(!attachments_added_by_edit(&seen(0, true), 1, true));
+    }
+
+    #[test]
+    fn redundant_update_events_are_claimed_once() {
+        let seen = SeenMessages::new();
+        seen.observe(id(1), 0);
+        assert!(seen.claim_edit(id(1), 1, true));
+        assert!(!seen.claim_edit(id(1), 1, true));
+        // Observing the refetched message keeps it marked
+        seen.observe(id(1), 1);
+        assert!(!seen.claim_edit(id(1), 2, true));
+    }
+
+    #[test]
+    fn messages_not_seen_recently_are_left_alone() {
+        let seen = SeenMessages::new();
+        assert!(!seen.claim_edit(id(1), 1, true));
+
+        let short = SeenMessages::with_ttl(Duration::ZERO);
+        short.observe(id(2), 0);
+        assert!(!short.claim_edit(id(2), 1, true));
+        // Expired entries are dropped on the next observe
+        short.observe(id(3), 0);
+        assert_eq!(short.messages.read(|map| map.len()), 1);
+    }
+
+    #[test]
+    fn irrelevant_additions_raise_the_baseline() {
+        let seen = SeenMessages::new();
+        seen.observe(id(1), 0);
+        // An image first, then the replay next to it
+        assert!(!seen.claim_edit(id(1), 1, false));
+        assert!(!seen.claim_edit(id(1), 1, true));
+        assert!(seen.claim_edit(id(1), 2, true));
+    }
+}
@@ -0,0 +1,185 @@
+use poise::serenity_prelude as serenity;
+use serenity::{MessageId, ReactionType, UserId};
+use std::time::{Duration, Instant};
+
+use super::constants::{DEFAULT_DELETE_REACTION, SENT_RESULT_TTL_SECS};
+use super::shared_map::{PoisonPolicy, SharedMap};
+
+/// Who a posted result was for
+#[derive(Debug, Clone)]
+struct SentResult {
+    requester: UserId,
+    /// Pagination entry its "Show more" button belongs to
+    pending_key: Option<String>,
+    at: Instant,
+}
+
+/// Results the bot posted recently, so the person who asked for one can
+/// remove it with a reaction
+pub struct SentResults {
+    /// On poison: recover (a lost entry only leaves deletion to moderators)
+    results: SharedMap<MessageId, SentResult>,
+    ttl: Duration,
+}
+
+impl SentResults {
+    pub fn new() -> Self {
+        Self::with_ttl(Duration::from_secs(SENT_RESULT_TTL_SECS))
+    }
+
+    fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            results: SharedMap::new("Sent results", PoisonPolicy::Recover),
+            ttl,
+        }
+    }
+
+    /// Remember a posted result and the pagination entry it continues, if any
+    pub fn record(&self, reply: MessageId, requester: UserId, pending_key: Option<&str>) {
+        let now = Instant::now();
+        self.results.write(|map| {
+            map.retain(|_, sent| now.duration_since(sent.at) < self.ttl);
+            map.insert(
+                reply,
+                SentResult {
+                    requester,
+                    pending_key: pending_key.map(str::to_string),
+                    at: now,
+                },
+            );
+        });
+    }
+
+    /// Who asked for a result, while it is remembered
+    pub fn requester(&self, reply: MessageId) -> Option<UserId> {
+        let now = Instant::now();
+        self.results.read(|map| {
+            map.get(&reply)
+                .filter(|sent| now.duration_since(sent.at) < self.ttl)
+                .map(|sent| sent.requester)
+        })
+    }
+
+    /// Forget a deleted result. Returns the pagination entry it continues,
+    /// whose remaining pages can no longer be reached.
+    pub fn forget(&self, reply: MessageId) -> Option<String> {
+        self.results
+            .write(|map| map.remove(&reply))
+            .and_then(|sent| sent.pending_key)
+    }
+}
+
+impl Default for SentResults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reaction that deletes a result: a unicode emoji or a custom emoji name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteReaction(String);
+
+impl DeleteReaction {
+    /// Parse an emoji (`🗑️`) or custom emoji name (`trash`)
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim().trim_matches(':');
+        if spec.is_empty() {
+            return Err("empty emoji".to_string());
+        }
+        if spec.chars().any(char::is_whitespace) {
+            return Err(format!("'{}' is not a single emoji", spec));
+        }
+        Ok(Self(spec.to_string()))
+    }
+
+    /// Whether a reaction is this emoji. The emoji variation selector is
+    /// ignored, since clients send 🗑 both with and without it.
+    pub fn matches(&self, emoji: &ReactionType) -> bool {
+        let bare = |s: &str| s.replace('\u{fe0f}', "");
+        match emoji {
+            ReactionType::Unicode(text) => bare(text) == bare(&self.0),
+            ReactionType::Custom {
+                name: Some(name), ..
+            } => *name == self.0,
+            _ => false,
+        }
+    }
+}
+
+impl Default for DeleteReaction {
+    fn default() -> Self {
+        Self(DEFAULT_DELETE_REACTION.to_string())
+    }
+}
+
+/// Whether a reaction may delete a result: the person who asked for it
+/// can, and so can anyone allowed to manage messages
+pub fn may_delete(requester: Option<UserId>, reactor: UserId, manages_messages: bool) -> bool {
+    manages_messages || requester == Some(reactor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u64) -> MessageId {
+        MessageId::new(n)
+    }
+
+    fn user(n: u64) -> UserId {
+        UserId::new(n)
+    }
+
+    #[test]
+    fn requester_or_moderator_may_delete() {
+        assert!(may_delete(Some(user(1)), user(1), false));
+        assert!(!may_delete(Some(user(1)), user(2), false));
+        assert!(may_delete(Some(user(1)), user(2), true));
+        // Forgotten (or never tracked): moderators only
+        assert!(!may_delete(None, user(1), false));
+        assert!(may_delete(None, user(1), true));
+    }
+
+    #[test]
+    fn forgetting_a_result_hands_back_its_pagination_entry() {
+        let sent = SentResults::new();
+        sent.record(id(10), user(1), Some("7_3_multi"));
+        sent.record(id(11), user(1), None);
+        assert_eq!(sent.requester(id(10)), Some(user(1)));
+
+        assert_eq!(sent.forget(id(10)).as_deref(), Some("7_3_multi"));
+        assert_eq!(sent.requester(id(10)), None);
+        // Already forgotten, or no "Show more" button on it
+        assert_eq!(sent.forget(id(10)), None);
+        assert_eq!(sent.forget(id(11)), None);
+    }
+
+    #[test]
+    fn expired_results_are_forgotten() {
+        let sent = SentResults::with_ttl(Duration::ZERO);
+        sent.record(id(10), user(1), None);
+        assert_eq!(sent.requester(id(10)), None);
+        // Expired entries are dropped on the next record
+        sent.record(id(11), user(1), None);
+        assert_eq!(sent.results.read(|map| map.len()), 1);
+    }
+
+    #[test]
+    fn delete_reaction_matches_with_or_without_variation_selector() {
+        let trash = DeleteReaction::default();
+        assert!(trash.matches(&ReactionType::Unicode("🗑️".to_string())));
+        assert!(trash.matches(&ReactionType::Unicode("🗑".to_string())));
+        assert!(!trash.matches(&ReactionType::Unicode("👍".to_string())));
+
+        let custom = DeleteReaction::parse(":bin:").unwrap();
+        assert!(custom.matches(&ReactionType::Custom {
+            animated: false,
+            id: serenity::EmojiId::new(1),
+            name: Some("bin".to_string()),
+        }));
+        assert!(!custom.matches(&ReactionType::Unicode("🗑️".to_string())));
+
+        assert!(DeleteReaction::parse(" ").is_err());
+        assert!(DeleteReaction::parse("a b").is_err());
+    }
+}
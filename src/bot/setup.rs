@@ -1,25 +1,62 @@
-use crate::renderer::{load_font, load_map_image};
+use crate::models::{ReplayError, ReplayInfo};
+use crate::renderer::{
+    FactionIcons, MapLayout, RenderOptions, load_faction_icons, load_font, load_map,
+};
 use ab_glyph::FontArc;
 use image::RgbImage;
 use poise::serenity_prelude as serenity;
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use super::channel_scope::ChannelScopes;
+use super::commands;
 use super::constants::{COOLDOWN_SECS, PENDING_EXPIRY_SECS};
-use super::handler::handle_message;
+use super::failure_report::{AdminWebhook, FailureReporter, install_panic_hook};
+use super::handler::{
+    RenderedBatch, ReplyFlags, handle_message, handle_message_update, handle_reaction_add,
+};
+use super::i18n::Locale;
+use super::in_flight::InFlightUploads;
+use super::messages::{ReplyStyle, delete_replies};
 use super::pagination::handle_component_interaction;
+use super::player_details::PlayerDetails;
+use super::posted_replays::PostedReplays;
+use super::readiness::Readiness;
+use super::results_store::{GameResult, ResultsStore};
+use super::seen_messages::SeenMessages;
+use super::sent_results::{DeleteReaction, SentResults};
+use super::shared_map::{PoisonPolicy, SharedMap};
+use super::supervisor::{is_fatal, next_attempt, restart_delay};
+use super::tally::ArchiveTally;
+use super::temp_dirs::{TempDirRegistry, TempDirSweeper};
+use super::uploads::AttachmentLimits;
+use super::winner_template::{WinnerTemplate, WinnerTemplates};
+use super::work_queue::{WorkDispatcher, WorkQueue};
 
 pub struct PendingReplays {
     pub replays: Vec<(String, Vec<u8>)>,
+    /// Parse results of `replays` when an archive pre-parse already has them;
+    /// empty otherwise
+    pub parsed: Vec<Result<ReplayInfo, ReplayError>>,
     pub total: usize,
     pub shown: usize,
     pub created_at: Instant,
     pub channel_id: serenity::ChannelId,
+    /// Reply language of the guild the replays came from
+    pub locale: Locale,
+    /// Results of the replays shown so far
+    pub tally: ArchiveTally,
+    /// Reveal and anonymity options for the later pages too
+    pub flags: ReplyFlags,
+    /// The next page, rendered in the background after this one was sent
+    pub prefetched: Option<RenderedBatch>,
+    /// Message carrying this entry's "Show more" button, once posted
+    pub message_id: Option<serenity::MessageId>,
 }
 
-/// Remove expired entries from the pending replays map (call with lock already held).
+/// Remove expired entries from the pending replays map (call inside `SharedMap::write`).
 pub fn cleanup_expired_pending_inner(map: &mut HashMap<String, PendingReplays>) {
     let now = Instant::now();
     map.retain(|_, v| now.duration_since(v.created_at).as_secs() < PENDING_EXPIRY_SECS);
@@ -28,53 +65,159 @@ pub fn cleanup_expired_pending_inner(map: &mut HashMap<String, PendingReplays>)
 pub struct Data {
     pub font: Arc<FontArc>,
     pub map_image: Arc<RgbImage>,
+    pub layout: Arc<MapLayout>,
+    /// Icons compact labels show factions with
+    pub faction_icons: Arc<FactionIcons>,
+    /// Palette and optional panels used in rendered images
+    pub render_options: RenderOptions,
     pub bot_id: serenity::UserId,
-    pub pending_replays: Mutex<HashMap<String, PendingReplays>>,
-    pub cooldowns: Mutex<HashMap<serenity::ChannelId, Instant>>,
+    /// On poison: clear state (fail closed). Shared with prefetch tasks.
+    pub pending_replays: Arc<SharedMap<String, PendingReplays>>,
+    /// On poison: recover (stale timestamps are harmless)
+    pub cooldowns: SharedMap<serenity::ChannelId, Instant>,
+    /// Reply language when a guild's preferred locale is unsupported (and for DMs)
+    pub default_locale: Locale,
+    /// Resolved reply language per guild; on poison: recover (a refetch is harmless)
+    pub guild_locales: SharedMap<serenity::GuildId, Locale>,
+    /// Plain image or embed for single-replay results
+    pub reply_style: ReplyStyle,
+    /// Uploads being processed, so deleting one cancels its replies
+    pub in_flight: InFlightUploads,
+    /// Recent messages, so an edit that attaches replays gets answered
+    pub seen_messages: SeenMessages,
+    /// Webhooks whose uploads are handled like a person's (upload bridges)
+    pub allowed_webhooks: Vec<serenity::WebhookId>,
+    /// Channels the bot answers in, per guild
+    pub channel_scopes: ChannelScopes,
+    /// Custom winner announcements in result embeds, per guild
+    pub winner_templates: WinnerTemplates,
+    /// Templates set with `/config winner-template set`, over the configured
+    /// ones until reset (kept in memory, so a restart drops them)
+    pub winner_template_overrides: SharedMap<serenity::GuildId, WinnerTemplate>,
+    /// Attachment count and size limits per batch message
+    pub attachment_limits: AttachmentLimits,
+    /// Temp dirs of RAR extractions in progress, swept when left behind
+    pub temp_dirs: TempDirRegistry,
+    /// Gateway connection state behind the `/ready` probe
+    pub readiness: Readiness,
+    /// Log of parsed games behind `/stats`, when enabled
+    pub results_store: Option<ResultsStore>,
+    /// Recent results and who asked for them, for deleting by reaction
+    pub sent_results: SentResults,
+    /// Reaction that deletes a result
+    pub delete_reaction: DeleteReaction,
+    /// Parsed replays behind single results' player buttons
+    pub player_details: PlayerDetails,
+    /// Replay files behind recent single results, for re-rendering by reaction
+    pub posted_replays: PostedReplays,
+    /// Guilds whose results always hide player names
+    pub anonymous_guilds: Vec<serenity::GuildId>,
+    /// Archive uploads waiting for a worker; shared with the dispatcher
+    pub work_queue: Arc<WorkQueue>,
+    /// Reports panics and failed renders to the admin webhook, if any
+    pub failure_reporter: Arc<FailureReporter>,
 }
 
 impl Data {
-    /// Lock cooldowns mutex. On poison: recover (stale timestamps are harmless).
-    pub fn lock_cooldowns(
-        &self,
-    ) -> std::sync::MutexGuard<'_, HashMap<serenity::ChannelId, Instant>> {
-        self.cooldowns.lock().unwrap_or_else(|e| {
-            tracing::warn!("Cooldowns mutex poisoned, recovering");
-            e.into_inner()
-        })
-    }
-
-    /// Lock pending replays mutex. On poison: clear state (fail closed).
-    pub fn lock_pending_replays(
-        &self,
-    ) -> std::sync::MutexGuard<'_, HashMap<String, PendingReplays>> {
-        self.pending_replays.lock().unwrap_or_else(|e| {
-            tracing::warn!("Pending replays mutex poisoned, clearing state");
-            let mut guard = e.into_inner();
-            guard.clear();
-            guard
-        })
-    }
-
     /// Check if a channel is on cooldown (returns true if still cooling down)
     pub fn check_cooldown(&self, channel_id: serenity::ChannelId) -> bool {
-        let cooldowns = self.lock_cooldowns();
-        cooldowns
-            .get(&channel_id)
+        self.cooldowns
+            .get_cloned(&channel_id)
             .is_some_and(|last| last.elapsed().as_secs() < COOLDOWN_SECS)
     }
 
     /// Record that a channel was just used
     pub fn set_cooldown(&self, channel_id: serenity::ChannelId) {
-        let mut cooldowns = self.lock_cooldowns();
-        cooldowns.insert(channel_id, Instant::now());
+        self.cooldowns.insert(channel_id, Instant::now());
+    }
+
+    /// Append parsed games to the results log, if one is configured
+    pub fn record_results(
+        &self,
+        guild_id: Option<serenity::GuildId>,
+        channel_id: serenity::ChannelId,
+        results: Vec<GameResult>,
+    ) {
+        if let Some(store) = &self.results_store {
+            store.record(guild_id, channel_id, results);
+        }
+    }
+
+    /// Winner announcement template for a guild: its `/config` override, else
+    /// the configured one
+    pub fn winner_template_for(
+        &self,
+        guild_id: Option<serenity::GuildId>,
+    ) -> Option<WinnerTemplate> {
+        guild_id
+            .and_then(|id| self.winner_template_overrides.get_cloned(&id))
+            .or_else(|| self.winner_templates.for_guild(guild_id).cloned())
+    }
+
+    /// Reply language for a guild, from its preferred locale (fetched once, then cached)
+    pub async fn locale_for(
+        &self,
+        ctx: &serenity::Context,
+        guild_id: Option<serenity::GuildId>,
+    ) -> Locale {
+        let Some(guild_id) = guild_id else {
+            return self.default_locale;
+        };
+        if let Some(locale) = self.guild_locales.get_cloned(&guild_id) {
+            return locale;
+        }
+        match guild_id.to_partial_guild(ctx).await {
+            Ok(guild) => {
+                let locale =
+                    Locale::from_tag(&guild.preferred_locale).unwrap_or(self.default_locale);
+                self.guild_locales.insert(guild_id, locale);
+                locale
+            }
+            Err(e) => {
+                tracing::warn!("Failed to fetch guild {} locale: {}", guild_id, e);
+                self.default_locale
+            }
+        }
     }
 }
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
-/// Set up and run the Discord bot
-pub async fn setup_bot(token: String, assets_path: PathBuf) -> Result<(), Error> {
+/// Deployment settings read from the environment at startup
+#[derive(Debug, Clone, Default)]
+pub struct BotConfig {
+    /// `LABEL_LAYOUT` overrides applied on top of the map's layout file
+    pub label_overrides: Option<String>,
+    pub default_locale: Locale,
+    pub reply_style: ReplyStyle,
+    pub render_options: RenderOptions,
+    pub allowed_webhooks: Vec<serenity::WebhookId>,
+    pub channel_scopes: ChannelScopes,
+    pub winner_templates: WinnerTemplates,
+    pub attachment_limits: AttachmentLimits,
+    /// Set while the gateway is connected; the caller keeps a clone to serve `/ready`
+    pub readiness: Readiness,
+    /// `RESULTS_LOG` file parsed games are appended to, for `/stats`
+    pub results_store: Option<ResultsStore>,
+    /// `DELETE_REACTION` emoji that removes a result
+    pub delete_reaction: DeleteReaction,
+    /// `REPLAY_RETENTION_HOURS` single results keep their replay file for
+    /// re-rendering (a day when unset); zero keeps none
+    pub replay_retention: Option<Duration>,
+    /// `ANONYMOUS_GUILDS` whose results always hide player names
+    pub anonymous_guilds: Vec<serenity::GuildId>,
+    /// `ADMIN_WEBHOOK_URL` the bot's own failures are reported to
+    pub admin_webhook: Option<AdminWebhook>,
+}
+
+/// Set up and run the Discord bot. The client is rebuilt and restarted with
+/// a growing delay whenever it stops on an error, unless the error will
+/// recur (a bad token or disallowed intents).
+pub async fn setup_bot(
+    token: String,
+    assets_path: PathBuf,
+    config: BotConfig,
+) -> Result<(), Error> {
     // Load font at startup
     let font_path = assets_path.join("fonts").join("NotoSans-Bold.ttf");
     let font_data = std::fs::read(&font_path)
@@ -83,21 +226,141 @@ pub async fn setup_bot(token: String, assets_path: PathBuf) -> Result<(), Error>
 
     let font = load_font(&font_data).map_err(|e| format!("Failed to parse font: {}", e))?;
 
-    // Load map image at startup (only "map wor rhun" is supported)
-    let map_image = load_map_image("map wor rhun", &assets_path)
-        .map_err(|e| format!("Failed to load map image: {}", e))?;
+    // Load map image and layout at startup (only "map wor rhun" is supported)
+    let (map_image, layout) =
+        load_map("map wor rhun", &assets_path).map_err(|e| format!("Failed to load map: {}", e))?;
     tracing::info!(
         "Loaded map image: {}x{}",
         map_image.width(),
         map_image.height()
     );
+    let layout = match &config.label_overrides {
+        Some(spec) => layout
+            .with_overrides(spec)
+            .map_err(|e| format!("Invalid LABEL_LAYOUT: {}", e))?,
+        None => layout,
+    };
+    let faction_icons = load_faction_icons(&assets_path);
+    tracing::info!("Loaded {} faction icons", faction_icons.len());
+    let assets = Assets {
+        font: Arc::new(font),
+        map_image: Arc::new(map_image),
+        layout: Arc::new(layout),
+        faction_icons: Arc::new(faction_icons),
+    };
+
+    let temp_dirs = TempDirRegistry::new();
+    let readiness = config.readiness.clone();
 
     let intents = serenity::GatewayIntents::GUILD_MESSAGES
         | serenity::GatewayIntents::MESSAGE_CONTENT
-        | serenity::GatewayIntents::DIRECT_MESSAGES;
+        | serenity::GatewayIntents::DIRECT_MESSAGES
+        | serenity::GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | serenity::GatewayIntents::DIRECT_MESSAGE_REACTIONS;
+
+    let sweeper = TempDirSweeper::start(temp_dirs.clone());
+    // Outlives client restarts, so queued uploads keep their place
+    let work_queue = Arc::new(WorkQueue::new());
+    let dispatcher = WorkDispatcher::start(work_queue.clone());
+    if config.admin_webhook.is_some() {
+        install_panic_hook();
+        tracing::info!("Reporting processing failures to the admin webhook");
+    }
+    // Outlives client restarts too, so its rate limits hold
+    let failure_reporter = Arc::new(FailureReporter::new(config.admin_webhook.clone()));
+    let mut failures = 0;
+    let result = loop {
+        let framework = build_framework(
+            config.clone(),
+            &assets,
+            temp_dirs.clone(),
+            work_queue.clone(),
+            failure_reporter.clone(),
+        );
+
+        // Disable all caching — this bot never reads from the cache
+        /*   let mut cache_settings = serenity::cache::Settings::default();
+        cache_settings.cache_guilds = false;
+        cache_settings.cache_channels = false;
+        cache_settings.cache_users = false; */
+
+        let started = Instant::now();
+        let result = match serenity::ClientBuilder::new(&token, intents)
+            //.cache_settings(cache_settings)
+            .framework(framework)
+            .await
+        {
+            Ok(mut client) => client.start().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => break Ok(()),
+            Err(e) if is_fatal(&e) => {
+                tracing::error!("Discord client stopped and cannot recover: {}", e);
+                break Err(e);
+            }
+            Err(e) => {
+                readiness.mark_reconnecting();
+                failures = next_attempt(failures, started.elapsed());
+                let delay = restart_delay(failures - 1);
+                tracing::warn!(
+                    "Discord client stopped: {}; restarting in {}s (failure {} in a row)",
+                    e,
+                    delay.as_secs(),
+                    failures
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    };
+    sweeper.stop();
+    dispatcher.stop();
+    result?;
+
+    Ok(())
+}
+
+/// Font, map image, layout and faction icons, loaded once and shared by
+/// every client
+struct Assets {
+    font: Arc<FontArc>,
+    map_image: Arc<RgbImage>,
+    layout: Arc<MapLayout>,
+    faction_icons: Arc<FactionIcons>,
+}
 
-    let framework = poise::Framework::builder()
+/// A fresh framework for one client run; its state starts empty
+fn build_framework(
+    config: BotConfig,
+    assets: &Assets,
+    temp_dirs: TempDirRegistry,
+    work_queue: Arc<WorkQueue>,
+    failure_reporter: Arc<FailureReporter>,
+) -> poise::Framework<Data, Error> {
+    let BotConfig {
+        label_overrides: _,
+        default_locale,
+        reply_style,
+        render_options,
+        allowed_webhooks,
+        channel_scopes,
+        winner_templates,
+        attachment_limits,
+        readiness,
+        results_store,
+        delete_reaction,
+        replay_retention,
+        anonymous_guilds,
+        admin_webhook: _,
+    } = config;
+    let font = assets.font.clone();
+    let map_image = assets.map_image.clone();
+    let layout = assets.layout.clone();
+    let faction_icons = assets.faction_icons.clone();
+
+    poise::Framework::builder()
         .options(poise::FrameworkOptions {
+            commands: vec![commands::stats(), commands::config()],
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
             },
@@ -107,35 +370,51 @@ pub async fn setup_bot(token: String, assets_path: PathBuf) -> Result<(), Error>
             },
             ..Default::default()
         })
-        .setup(move |_ctx, ready, _framework| {
+        .setup(move |ctx, ready, framework| {
             Box::pin(async move {
                 let bot_id = ready.user.id;
                 tracing::info!("Bot is ready! Bot ID: {}", bot_id);
+                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                readiness.set_ready(true);
                 Ok(Data {
-                    font: Arc::new(font),
-                    map_image: Arc::new(map_image),
+                    font,
+                    map_image,
+                    layout,
+                    faction_icons,
+                    render_options,
                     bot_id,
-                    pending_replays: Mutex::new(HashMap::new()),
-                    cooldowns: Mutex::new(HashMap::new()),
+                    pending_replays: Arc::new(SharedMap::new(
+                        "Pending replays",
+                        PoisonPolicy::Clear,
+                    )),
+                    cooldowns: SharedMap::new("Cooldowns", PoisonPolicy::Recover),
+                    default_locale,
+                    guild_locales: SharedMap::new("Guild locales", PoisonPolicy::Recover),
+                    reply_style,
+                    in_flight: InFlightUploads::new(),
+                    seen_messages: SeenMessages::new(),
+                    allowed_webhooks,
+                    channel_scopes,
+                    winner_templates,
+                    winner_template_overrides: SharedMap::new(
+                        "Winner template overrides",
+                        PoisonPolicy::Recover,
+                    ),
+                    attachment_limits,
+                    temp_dirs,
+                    readiness,
+                    results_store,
+                    sent_results: SentResults::new(),
+                    delete_reaction,
+                    player_details: PlayerDetails::new(),
+                    posted_replays: replay_retention.map(PostedReplays::new).unwrap_or_default(),
+                    anonymous_guilds,
+                    work_queue,
+                    failure_reporter,
                 })
             })
         })
-        .build();
-
-    // Disable all caching — this bot never reads from the cache
-    /*   let mut cache_settings = serenity::cache::Settings::default();
-    cache_settings.cache_guilds = false;
-    cache_settings.cache_channels = false;
-    cache_settings.cache_users = false; */
-
-    let mut client = serenity::ClientBuilder::new(token, intents)
-        //.cache_settings(cache_settings)
-        .framework(framework)
-        .await?;
-
-    client.start().await?;
-
-    Ok(())
+        .build()
 }
 
 /// Handle Discord events
@@ -149,11 +428,37 @@ async fn event_handler(
         serenity::FullEvent::Message { new_message } => {
             handle_message(ctx, new_message, data).await?;
         }
+        serenity::FullEvent::MessageUpdate { event, .. } => {
+            handle_message_update(ctx, event, data).await?;
+        }
         serenity::FullEvent::InteractionCreate {
             interaction: serenity::Interaction::Component(component),
         } => {
             handle_component_interaction(ctx, component, data).await;
         }
+        serenity::FullEvent::ReactionAdd { add_reaction } => {
+            handle_reaction_add(ctx, add_reaction, data).await;
+        }
+        serenity::FullEvent::ShardStageUpdate { event } => {
+            data.readiness.on_stage(event.new);
+        }
+        serenity::FullEvent::MessageDelete {
+            channel_id,
+            deleted_message_id,
+            ..
+        } => {
+            if data.work_queue.cancel(*deleted_message_id) {
+                tracing::info!(msg_id = %deleted_message_id, "Queued upload deleted, dropped");
+            }
+            if let Some(replies) = data.in_flight.cancel(*deleted_message_id) {
+                tracing::info!(
+                    msg_id = %deleted_message_id,
+                    "Upload deleted mid-processing, removing {} replies",
+                    replies.len()
+                );
+                delete_replies(ctx, *channel_id, &replies).await;
+            }
+        }
         _ => {}
     }
     Ok(())
@@ -0,0 +1,177 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// What to do with the map contents when a previous holder panicked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Keep the data (stale entries are harmless)
+    Recover,
+    /// Drop all entries (fail closed)
+    Clear,
+}
+
+thread_local! {
+    /// Number of SharedMap accessors currently running on this thread.
+    static ACCESS_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Tracks accessor nesting; nested access from inside a closure would deadlock
+/// on the RwLock, so it is caught early in debug builds.
+struct AccessScope;
+
+impl AccessScope {
+    fn enter() -> Self {
+        ACCESS_DEPTH.with(|d| {
+            debug_assert_eq!(d.get(), 0, "nested SharedMap access inside an accessor");
+            d.set(d.get() + 1);
+        });
+        AccessScope
+    }
+}
+
+impl Drop for AccessScope {
+    fn drop(&mut self) {
+        ACCESS_DEPTH.with(|d| d.set(d.get().saturating_sub(1)));
+    }
+}
+
+/// RwLock-backed map shared across event handlers.
+///
+/// Guards are never handed out: every accessor takes a synchronous closure (or
+/// returns owned data), so a guard can't be held across an `.await`.
+pub struct SharedMap<K, V> {
+    inner: RwLock<HashMap<K, V>>,
+    name: &'static str,
+    poison: PoisonPolicy,
+}
+
+impl<K: Eq + Hash, V> SharedMap<K, V> {
+    pub fn new(name: &'static str, poison: PoisonPolicy) -> Self {
+        Self {
+            inner: RwLock::new(HashMap::new()),
+            name,
+            poison,
+        }
+    }
+
+    fn read_guard(&self) -> RwLockReadGuard<'_, HashMap<K, V>> {
+        if self.inner.is_poisoned() {
+            // Apply the poison policy under the write lock before reading
+            drop(self.write_guard());
+        }
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_guard(&self) -> RwLockWriteGuard<'_, HashMap<K, V>> {
+        self.inner.write().unwrap_or_else(|e| {
+            let mut guard = e.into_inner();
+            match self.poison {
+                PoisonPolicy::Recover => {
+                    tracing::warn!("{} lock poisoned, recovering", self.name);
+                }
+                PoisonPolicy::Clear => {
+                    tracing::warn!("{} lock poisoned, clearing state", self.name);
+                    guard.clear();
+                }
+            }
+            self.inner.clear_poison();
+            guard
+        })
+    }
+
+    /// Run `f` with shared access to the map
+    pub fn read<R>(&self, f: impl FnOnce(&HashMap<K, V>) -> R) -> R {
+        let _scope = AccessScope::enter();
+        f(&self.read_guard())
+    }
+
+    /// Run `f` with exclusive access to the map (for multi-step check-then-act sequences)
+    pub fn write<R>(&self, f: impl FnOnce(&mut HashMap<K, V>) -> R) -> R {
+        let _scope = AccessScope::enter();
+        f(&mut self.write_guard())
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.write(|m| m.insert(key, value))
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> SharedMap<K, V> {
+    /// Clone out the value for `key`
+    pub fn get_cloned(&self, key: &K) -> Option<V> {
+        self.read(|m| m.get(key).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn basic_operations() {
+        let map: SharedMap<u32, String> = SharedMap::new("test", PoisonPolicy::Recover);
+        assert!(map.read(|m| m.is_empty()));
+        map.insert(1, "a".to_string());
+        map.insert(2, "b".to_string());
+        assert_eq!(map.get_cloned(&1), Some("a".to_string()));
+        assert_eq!(map.write(|m| m.remove(&2)), Some("b".to_string()));
+        map.write(|m| m.retain(|k, _| *k != 1));
+        assert_eq!(map.read(|m| m.len()), 0);
+    }
+
+    #[test]
+    fn poison_recover_keeps_entries() {
+        let map = Arc::new(SharedMap::new("test", PoisonPolicy::Recover));
+        map.insert(1u32, 1u32);
+        let m = map.clone();
+        let _ = std::thread::spawn(move || m.write(|_| panic!("poison"))).join();
+        assert_eq!(map.get_cloned(&1), Some(1));
+    }
+
+    #[test]
+    fn poison_clear_drops_entries() {
+        let map = Arc::new(SharedMap::new("test", PoisonPolicy::Clear));
+        map.insert(1u32, 1u32);
+        let m = map.clone();
+        let _ = std::thread::spawn(move || m.write(|_| panic!("poison"))).join();
+        assert_eq!(map.get_cloned(&1), None);
+        map.insert(2, 2);
+        assert_eq!(map.read(|m| m.len()), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "nested SharedMap access")]
+    fn nested_access_is_caught() {
+        let map: SharedMap<u32, u32> = SharedMap::new("test", PoisonPolicy::Recover);
+        let other: SharedMap<u32, u32> = SharedMap::new("other", PoisonPolicy::Recover);
+        map.read(|_| other.get_cloned(&1));
+    }
+
+    #[test]
+    fn stress_insert_and_cleanup() {
+        let map = Arc::new(SharedMap::new("stress", PoisonPolicy::Clear));
+        let handles: Vec<_> = (0..8u32)
+            .map(|t| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    for i in 0..1000u32 {
+                        map.insert(t * 1000 + i, i);
+                        if i % 10 == 0 {
+                            map.write(|m| m.retain(|_, v| *v % 2 == 0));
+                        }
+                        let _ = map.get_cloned(&(t * 1000 + i));
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        map.write(|m| m.retain(|_, v| *v % 2 == 0));
+        map.read(|m| assert!(m.values().all(|v| v % 2 == 0)));
+        assert_eq!(map.read(|m| m.len()), 8 * 500);
+    }
+}
@@ -0,0 +1,85 @@
+//! Restarting the Discord client after its gateway connection gives up:
+//! which errors are worth a restart and how long to wait before one
+
+use poise::serenity_prelude as serenity;
+use serenity::{GatewayError, StatusCode};
+use std::time::Duration;
+
+use super::constants::{
+    GATEWAY_RESTART_BASE_DELAY_SECS, GATEWAY_RESTART_MAX_DELAY_SECS, GATEWAY_STABLE_RUN_SECS,
+};
+
+/// Whether a client error will recur on every restart (a bad token or
+/// intents the application may not use), so the bot should exit instead
+pub fn is_fatal(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Gateway(error) => matches!(
+            error,
+            GatewayError::InvalidAuthentication
+                | GatewayError::NoAuthentication
+                | GatewayError::InvalidGatewayIntents
+                | GatewayError::DisallowedGatewayIntents
+                | GatewayError::InvalidShardData
+                | GatewayError::OverloadedShard
+        ),
+        serenity::Error::Http(error) => error.status_code() == Some(StatusCode::UNAUTHORIZED),
+        _ => false,
+    }
+}
+
+/// Wait before restart number `attempt` (0-based): base * 2^attempt, capped
+pub fn restart_delay(attempt: u32) -> Duration {
+    Duration::from_secs(GATEWAY_RESTART_BASE_DELAY_SECS)
+        .saturating_mul(1u32 << attempt.min(16))
+        .min(Duration::from_secs(GATEWAY_RESTART_MAX_DELAY_SECS))
+}
+
+/// Failures in a row, counting one of a client that ran for `ran_for`: the
+/// backoff grows while restarts keep failing and starts over after a
+/// stable run
+pub fn next_attempt(failures: u32, ran_for: Duration) -> u32 {
+    if ran_for >= Duration::from_secs(GATEWAY_STABLE_RUN_SECS) {
+        1
+    } else {
+        failures.saturating_add(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_credentials_are_fatal_and_dropped_connections_are_not() {
+        for fatal in [
+            GatewayError::InvalidAuthentication,
+            GatewayError::DisallowedGatewayIntents,
+            GatewayError::InvalidGatewayIntents,
+        ] {
+            assert!(is_fatal(&serenity::Error::Gateway(fatal)));
+        }
+        for transient in [
+            GatewayError::ReconnectFailure,
+            GatewayError::HeartbeatFailed,
+            GatewayError::Closed(None),
+        ] {
+            assert!(!is_fatal(&serenity::Error::Gateway(transient)));
+        }
+        assert!(!is_fatal(&serenity::Error::Other("shard manager stopped")));
+    }
+
+    #[test]
+    fn restart_delay_doubles_up_to_five_minutes() {
+        let secs: Vec<u64> = (0..8).map(|n| restart_delay(n).as_secs()).collect();
+        assert_eq!(secs, [5, 10, 20, 40, 80, 160, 300, 300]);
+        assert_eq!(restart_delay(u32::MAX), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn stable_runs_reset_the_backoff() {
+        assert_eq!(next_attempt(0, Duration::from_secs(3)), 1);
+        assert_eq!(next_attempt(4, Duration::from_secs(599)), 5);
+        assert_eq!(next_attempt(4, Duration::from_secs(600)), 1);
+        assert_eq!(next_attempt(u32::MAX, Duration::ZERO), u32::MAX);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::models::{ReplayError, ReplayInfo, Side};
+
+/// Running result counts over the pages of an archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveTally {
+    pub left: usize,
+    pub right: usize,
+    pub crashed: usize,
+    /// Parsed, but no side won
+    pub unknown: usize,
+    /// Failed to parse at all
+    pub errors: usize,
+}
+
+impl ArchiveTally {
+    /// Count one replay. A replay that fails to parse is an error, not an
+    /// unknown result; a crashed game counts as crashed whatever side it leaned to.
+    pub fn record(&mut self, result: &Result<ReplayInfo, ReplayError>) {
+        match result {
+            Err(_) => self.errors += 1,
+            Ok(replay) if replay.game_crashed => self.crashed += 1,
+            Ok(replay) => match replay.winner.side() {
+                Some(Side::Left) => self.left += 1,
+                Some(Side::Right) => self.right += 1,
+                None => self.unknown += 1,
+            },
+        }
+    }
+
+    /// Add the counts of another batch
+    pub fn merge(&mut self, other: &ArchiveTally) {
+        self.left += other.left;
+        self.right += other.right;
+        self.crashed += other.crashed;
+        self.unknown += other.unknown;
+        self.errors += other.errors;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Winner;
+
+    fn game(winner: Winner) -> Result<ReplayInfo, ReplayError> {
+        Ok(ReplayInfo::new("map rhun".to_string(), Vec::new()).with_winner(winner))
+    }
+
+    #[test]
+    fn batches_add_up_across_pages() {
+        let mut tally = ArchiveTally::default();
+        let mut first = ArchiveTally::default();
+        for result in [
+            game(Winner::LeftTeam),
+            game(Winner::LikelyRightTeam),
+            game(Winner::InProgress),
+        ] {
+            first.record(&result);
+        }
+        tally.merge(&first);
+
+        let mut second = ArchiveTally::default();
+        second.record(&game(Winner::LikelyLeftTeam));
+        second.record(&game(Winner::NotConcluded).map(|r| r.with_game_crashed(true)));
+        tally.merge(&second);
+
+        assert_eq!(
+            tally,
+            ArchiveTally {
+                left: 2,
+                right: 1,
+                crashed: 1,
+                unknown: 1,
+                errors: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn unparsable_replays_are_errors_not_unknown() {
+        let mut tally = ArchiveTally::default();
+        tally.record(&Err(ReplayError::InvalidHeader));
+        tally.record(&game(Winner::Unknown));
+        assert_eq!(tally.errors, 1);
+        assert_eq!(tally.unknown, 1);
+    }
+
+    #[test]
+    fn a_crash_outweighs_a_leaning_side() {
+        let mut tally = ArchiveTally::default();
+        tally.record(&game(Winner::LikelyLeftTeam).map(|r| r.with_game_crashed(true)));
+        assert_eq!(tally.crashed, 1);
+        assert_eq!(tally.left, 0);
+    }
+}
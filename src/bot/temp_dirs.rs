@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+use super::constants::{TEMP_DIR_MAX_AGE_SECS, TEMP_DIR_SWEEP_INTERVAL_SECS};
+use super::shared_map::{PoisonPolicy, SharedMap};
+
+/// Temp directories created for archive extraction, with when they were
+/// created. A directory normally removes itself when dropped; one left
+/// behind by an aborted or crashed worker is removed by the sweeper.
+#[derive(Clone)]
+pub struct TempDirRegistry {
+    /// On poison: recover (an entry only delays or repeats a removal)
+    dirs: Arc<SharedMap<PathBuf, Instant>>,
+}
+
+impl Default for TempDirRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TempDirRegistry {
+    pub fn new() -> Self {
+        Self {
+            dirs: Arc::new(SharedMap::new("Temp dirs", PoisonPolicy::Recover)),
+        }
+    }
+
+    /// Create a temp directory and register it
+    pub fn create(&self) -> std::io::Result<RegisteredTempDir> {
+        let dir = tempfile::tempdir()?;
+        self.register(dir.path().to_path_buf(), Instant::now());
+        Ok(RegisteredTempDir {
+            dir,
+            registry: self.clone(),
+        })
+    }
+
+    fn register(&self, path: PathBuf, created_at: Instant) {
+        self.dirs.insert(path, created_at);
+    }
+
+    fn release(&self, path: &Path) {
+        self.dirs.write(|map| map.remove(path));
+    }
+
+    /// Remove registered directories older than `max_age` from disk and from
+    /// the registry. Returns how many were removed.
+    pub fn sweep(&self, max_age: Duration) -> usize {
+        self.sweep_at(Instant::now(), max_age)
+    }
+
+    fn sweep_at(&self, now: Instant, max_age: Duration) -> usize {
+        let expired: Vec<PathBuf> = self.dirs.write(|map| {
+            let expired: Vec<PathBuf> = map
+                .iter()
+                .filter(|(_, created_at)| now.saturating_duration_since(**created_at) >= max_age)
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in &expired {
+                map.remove(path);
+            }
+            expired
+        });
+        for path in &expired {
+            match std::fs::remove_dir_all(path) {
+                Ok(()) => tracing::warn!("Removed leftover temp dir {:?}", path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::error!("Failed to remove temp dir {:?}: {}", path, e),
+            }
+        }
+        expired.len()
+    }
+}
+
+/// A registered temp directory; dropping it removes the directory and its entry
+pub struct RegisteredTempDir {
+    dir: TempDir,
+    registry: TempDirRegistry,
+}
+
+impl RegisteredTempDir {
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Drop for RegisteredTempDir {
+    fn drop(&mut self) {
+        self.registry.release(self.dir.path());
+    }
+}
+
+/// Background task sweeping a registry every [`TEMP_DIR_SWEEP_INTERVAL_SECS`]
+pub struct TempDirSweeper {
+    registry: TempDirRegistry,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl TempDirSweeper {
+    /// Start sweeping (call from within the runtime)
+    pub fn start(registry: TempDirRegistry) -> Self {
+        let swept = registry.clone();
+        let task = tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(TEMP_DIR_SWEEP_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                swept.sweep(Duration::from_secs(TEMP_DIR_MAX_AGE_SECS));
+            }
+        });
+        Self { registry, task }
+    }
+
+    /// Stop sweeping and remove every directory still registered
+    pub fn stop(self) {
+        self.task.abort();
+        self.registry.sweep(Duration::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    impl TempDirRegistry {
+        fn len(&self) -> usize {
+            self.dirs.read(HashMap::len)
+        }
+    }
+
+    #[test]
+    fn sweep_removes_only_old_directories() {
+        let root = tempfile::tempdir().unwrap();
+        let registry = TempDirRegistry::new();
+        let now = Instant::now();
+        let old = root.path().join("old");
+        let fresh = root.path().join("fresh");
+        for dir in [&old, &fresh] {
+            std::fs::create_dir_all(dir.join("extracted")).unwrap();
+            std::fs::write(dir.join("extracted").join("a.BfME2Replay"), b"x").unwrap();
+        }
+        registry.register(old.clone(), now);
+        registry.register(fresh.clone(), now + Duration::from_secs(10 * 60));
+        // Registered but already gone from disk
+        registry.register(root.path().join("gone"), now);
+
+        // Eleven minutes on, only the fresh one is under ten minutes old
+        let later = now + Duration::from_secs(11 * 60);
+        assert_eq!(registry.sweep_at(later, Duration::from_secs(10 * 60)), 2);
+        assert!(!old.exists());
+        assert!(fresh.join("extracted").join("a.BfME2Replay").exists());
+        assert_eq!(registry.len(), 1);
+
+        assert_eq!(registry.sweep_at(later, Duration::ZERO), 1);
+        assert!(!fresh.exists());
+        assert_eq!(registry.len(), 0);
+    }
+
+    #[test]
+    fn dropped_directories_leave_the_registry() {
+        let registry = TempDirRegistry::new();
+        let dir = registry.create().unwrap();
+        let path = dir.path().to_path_buf();
+        assert!(path.exists());
+        assert_eq!(registry.len(), 1);
+
+        drop(dir);
+        assert!(!path.exists());
+        assert_eq!(registry.len(), 0);
+    }
+
+    #[test]
+    fn a_panic_still_removes_the_directory() {
+        let registry = TempDirRegistry::new();
+        let path = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let dir = registry.create().unwrap();
+            std::panic::panic_any(dir.path().to_path_buf())
+        }))
+        .unwrap_err()
+        .downcast::<PathBuf>()
+        .unwrap();
+        assert!(!path.exists());
+        assert_eq!(registry.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn stopping_the_sweeper_removes_what_is_left() {
+        let registry = TempDirRegistry::new();
+        let sweeper = TempDirSweeper::start(registry.clone());
+        let leaked = registry.create().unwrap();
+        let path = leaked.path().to_path_buf();
+        std::mem::forget(leaked);
+
+        sweeper.stop();
+        assert!(!path.exists());
+        assert_eq!(registry.len(), 0);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::renderer::UPLOAD_BUDGET_BYTES;
+
+use super::constants::BOT_MAX_ATTACHMENTS;
+
+/// JPEG qualities tried, in order, for an image too large to post on its own
+const REENCODE_QUALITIES: [u8; 3] = [70, 55, 40];
+
+/// An attachment: filename, encoded bytes and its alt text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+    /// Shown to screen readers and when the image fails to load
+    pub description: Option<String>,
+}
+
+impl UploadFile {
+    pub fn new(name: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            bytes,
+            description: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+}
+
+/// Per-message attachment limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttachmentLimits {
+    pub max_files: usize,
+    /// Total attachment bytes per message
+    pub max_bytes: usize,
+}
+
+impl Default for AttachmentLimits {
+    /// 10 files and 8MB, the upload limit on servers without boosts
+    fn default() -> Self {
+        Self {
+            max_files: BOT_MAX_ATTACHMENTS,
+            max_bytes: UPLOAD_BUDGET_BYTES,
+        }
+    }
+}
+
+impl AttachmentLimits {
+    /// Limits with a per-message budget of `mb` megabytes
+    pub fn with_megabytes(mb: usize) -> Self {
+        Self {
+            max_bytes: mb * 1024 * 1024,
+            ..Self::default()
+        }
+    }
+}
+
+/// Group files into messages that each stay within `limits`, keeping their
+/// order. A JPEG too large to post even on its own is re-encoded at lower
+/// quality; other formats (GIF) are left as they are and go alone.
+pub fn plan_uploads(files: Vec<UploadFile>, limits: AttachmentLimits) -> Vec<Vec<UploadFile>> {
+    let mut messages: Vec<Vec<UploadFile>> = Vec::new();
+    let mut current: Vec<UploadFile> = Vec::new();
+    let mut current_bytes = 0;
+    for mut file in files {
+        if file.bytes.len() > limits.max_bytes {
+            file.bytes = shrink_jpeg(&file.name, file.bytes, limits.max_bytes);
+        }
+        let full = current.len() >= limits.max_files.max(1)
+            || current_bytes + file.bytes.len() > limits.max_bytes;
+        if full && !current.is_empty() {
+            messages.push(std::mem::take(&mut current));
+            current_bytes = 0;
+        }
+        current_bytes += file.bytes.len();
+        current.push(file);
+    }
+    if !current.is_empty() {
+        messages.push(current);
+    }
+    if messages.len() > 1 {
+        tracing::info!(
+            "Split attachments over {} messages to stay under {} bytes each",
+            messages.len(),
+            limits.max_bytes
+        );
+    }
+    messages
+}
+
+/// Re-encode a JPEG at falling quality until it fits `budget`; the smallest
+/// attempt is kept when none fits, and non-JPEG data is returned unchanged
+fn shrink_jpeg(name: &str, bytes: Vec<u8>, budget: usize) -> Vec<u8> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        tracing::warn!(
+            "{} is {} bytes, over budget of {}",
+            name,
+            bytes.len(),
+            budget
+        );
+        return bytes;
+    }
+    let Ok(img) = image::load_from_memory(&bytes) else {
+        return bytes;
+    };
+    let img = img.to_rgb8();
+    let mut smallest = bytes;
+    for quality in REENCODE_QUALITIES {
+        let mut buffer = Vec::new();
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality);
+        if img.write_with_encoder(encoder).is_err() {
+            break;
+        }
+        tracing::info!(
+            "Re-encoded {} at quality {}: {} -> {} bytes",
+            name,
+            quality,
+            smallest.len(),
+            buffer.len()
+        );
+        if buffer.len() < smallest.len() {
+            smallest = buffer;
+        }
+        if smallest.len() <= budget {
+            break;
+        }
+    }
+    smallest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn file(name: &str, len: usize) -> UploadFile {
+        UploadFile::new(name, vec![0; len])
+    }
+
+    fn names(messages: &[Vec<UploadFile>]) -> Vec<Vec<&str>> {
+        messages
+            .iter()
+            .map(|m| m.iter().map(|f| f.name.as_str()).collect())
+            .collect()
+    }
+
+    /// A noisy image, so it compresses poorly at high quality
+    fn noisy_jpeg(quality: u8) -> Vec<u8> {
+        let mut seed = 12345u32;
+        let img = RgbImage::from_fn(200, 200, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            Rgb([r, g, b])
+        });
+        let mut buffer = Vec::new();
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality);
+        img.write_with_encoder(encoder).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn files_are_split_by_count_and_bytes_in_order() {
+        let limits = AttachmentLimits {
+            max_files: 2,
+            max_bytes: 100,
+        };
+        let files = vec![file("a", 10), file("b", 10), file("c", 10)];
+        assert_eq!(
+            names(&plan_uploads(files, limits)),
+            vec![vec!["a", "b"], vec!["c"]]
+        );
+
+        let files = vec![file("a", 60), file("b", 30), file("c", 30), file("d", 90)];
+        let limits = AttachmentLimits {
+            max_files: 10,
+            max_bytes: 100,
+        };
+        assert_eq!(
+            names(&plan_uploads(files, limits)),
+            vec![vec!["a", "b"], vec!["c"], vec!["d"]]
+        );
+    }
+
+    #[test]
+    fn everything_fits_in_one_message_by_default() {
+        let files: Vec<UploadFile> = (0..10).map(|i| file(&i.to_string(), 300_000)).collect();
+        let messages = plan_uploads(files, AttachmentLimits::default());
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].len(), 10);
+        assert!(plan_uploads(vec![], AttachmentLimits::default()).is_empty());
+    }
+
+    #[test]
+    fn oversized_jpeg_is_reencoded_to_fit() {
+        let original = noisy_jpeg(100);
+        let limits = AttachmentLimits {
+            max_files: 10,
+            max_bytes: original.len() * 2 / 3,
+        };
+        let messages = plan_uploads(vec![UploadFile::new("map.jpg", original.clone())], limits);
+        let bytes = &messages[0][0].bytes;
+        assert!(bytes.len() <= limits.max_bytes);
+        assert!(image::load_from_memory(bytes).is_ok());
+    }
+
+    #[test]
+    fn oversized_non_jpeg_goes_alone_unchanged() {
+        let limits = AttachmentLimits {
+            max_files: 10,
+            max_bytes: 100,
+        };
+        let gif = b"GIF89a".repeat(30);
+        let files = vec![
+            file("a", 50),
+            UploadFile::new("reveal.gif", gif.clone()),
+            file("b", 50),
+        ];
+        let messages = plan_uploads(files, limits);
+        assert_eq!(
+            names(&messages),
+            vec![vec!["a"], vec!["reveal.gif"], vec!["b"]]
+        );
+        assert_eq!(messages[1][0].bytes, gif);
+    }
+
+    #[test]
+    fn megabyte_budget() {
+        assert_eq!(
+            AttachmentLimits::with_megabytes(25).max_bytes,
+            25 * 1024 * 1024
+        );
+    }
+}
@@ -0,0 +1,521 @@
+use crate::models::{ReplayError, ReplayInfo, ReplayWarning};
+use crate::parser::PreflightError;
+use crate::renderer::display_filename;
+
+use super::archive::ArchiveError;
+use super::constants::build_safe_content;
+use super::i18n::{Locale, MessageKey, translate};
+use super::message_link::LinkError;
+use super::results_store::FactionStats;
+use super::tally::ArchiveTally;
+use super::work_queue::DropReason;
+
+/// Text that is safe to post in a channel.
+///
+/// Only the constructors below can build one, so internal details (paths,
+/// byte counts, library errors) cannot reach users by accident; they belong
+/// in tracing instead. The text is translated when rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserMessage(Vec<Line>);
+
+/// One line of a reply, optionally prefixed with the attachment it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Line {
+    filename: Option<String>,
+    key: MessageKey,
+}
+
+impl UserMessage {
+    fn key(key: MessageKey) -> Self {
+        Self(vec![Line {
+            filename: None,
+            key,
+        }])
+    }
+
+    /// Render in `locale`, one line per message, truncated to fit in a Discord message
+    pub fn render(&self, locale: Locale) -> String {
+        build_safe_content(&self.render_lines(locale))
+    }
+
+    fn render_lines(&self, locale: Locale) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|line| {
+                let text = translate(&line.key, locale);
+                match &line.filename {
+                    Some(name) => format!("{}: {}", name, text),
+                    None => text,
+                }
+            })
+            .collect()
+    }
+
+    pub fn replay_too_large() -> Self {
+        Self::key(MessageKey::ReplayTooLarge)
+    }
+
+    pub fn replay_download_failed() -> Self {
+        Self::key(MessageKey::ReplayDownloadFailed)
+    }
+
+    pub fn archive_too_large(max_mb: u64) -> Self {
+        Self::key(MessageKey::ArchiveTooLarge { max_mb })
+    }
+
+    pub fn archive_download_failed() -> Self {
+        Self::key(MessageKey::ArchiveDownloadFailed)
+    }
+
+    pub fn archive_extract_failed() -> Self {
+        Self::key(MessageKey::ArchiveExtractFailed)
+    }
+
+    pub fn archive_empty() -> Self {
+        Self::key(MessageKey::ArchiveEmpty)
+    }
+
+    pub fn queued(position: usize) -> Self {
+        Self::key(MessageKey::Queued { position })
+    }
+
+    pub fn queue_started() -> Self {
+        Self::key(MessageKey::QueueStarted)
+    }
+
+    pub fn queue_full() -> Self {
+        Self::key(MessageKey::QueueFull)
+    }
+
+    /// Why a queued upload was dropped before its turn
+    pub fn queue_dropped(reason: DropReason) -> Self {
+        Self::key(match reason {
+            DropReason::Expired => MessageKey::QueueExpired,
+            DropReason::Cancelled => MessageKey::QueueCancelled,
+        })
+    }
+
+    pub fn internal_error() -> Self {
+        Self::key(MessageKey::InternalError)
+    }
+
+    pub fn upload_failed() -> Self {
+        Self::key(MessageKey::UploadFailed)
+    }
+
+    pub fn wrong_channel() -> Self {
+        Self::key(MessageKey::WrongChannel)
+    }
+
+    pub fn button_expired() -> Self {
+        Self::key(MessageKey::ButtonExpired)
+    }
+
+    pub fn showing(shown: usize, total: usize) -> Self {
+        Self::key(MessageKey::Showing { shown, total })
+    }
+
+    pub fn stats_disabled() -> Self {
+        Self::key(MessageKey::StatsDisabled)
+    }
+
+    pub fn stats_no_games(days: u32) -> Self {
+        Self::key(MessageKey::StatsNoGames { days })
+    }
+
+    pub fn winner_template_set(preview: String) -> Self {
+        Self::key(MessageKey::WinnerTemplateSet(preview))
+    }
+
+    pub fn winner_template_invalid(reason: String) -> Self {
+        Self::key(MessageKey::WinnerTemplateInvalid(reason))
+    }
+
+    pub fn winner_template_reset() -> Self {
+        Self::key(MessageKey::WinnerTemplateReset)
+    }
+
+    pub fn winner_template_preview(preview: String) -> Self {
+        Self::key(MessageKey::WinnerTemplatePreview(preview))
+    }
+
+    pub fn no_winner_template() -> Self {
+        Self::key(MessageKey::NoWinnerTemplate)
+    }
+
+    /// `/stats` reply: summary line, faction table and a note when some
+    /// win rates rest on few games
+    pub fn faction_stats(stats: FactionStats, days: u32, min_sample: usize) -> Self {
+        let small_samples = stats.has_small_samples();
+        let mut lines = vec![
+            MessageKey::StatsSummary {
+                games: stats.games,
+                days,
+            },
+            MessageKey::FactionTable(stats),
+        ];
+        if small_samples {
+            lines.push(MessageKey::StatsSmallSample { min: min_sample });
+        }
+        Self(
+            lines
+                .into_iter()
+                .map(|key| Line {
+                    filename: None,
+                    key,
+                })
+                .collect(),
+        )
+    }
+
+    /// Running results footer of a multi-page archive
+    pub fn archive_so_far(tally: ArchiveTally) -> Self {
+        Self::key(MessageKey::ArchiveSoFar(tally))
+    }
+
+    pub fn archive_capped(total: usize, processed: usize) -> Self {
+        Self::key(MessageKey::ArchiveCapped { total, processed })
+    }
+
+    /// Separator before the replays of one day on a page (`None`: unknown date)
+    pub fn date_group(date: Option<String>, replays: usize) -> Self {
+        Self::key(MessageKey::DateGroup { date, replays })
+    }
+
+    pub fn duplicates_merged(merged: usize) -> Self {
+        Self::key(MessageKey::DuplicatesMerged(merged))
+    }
+
+    /// What is worth telling the uploader about a parsed replay (a file cut
+    /// off in transfer, players seated twice in the lobby); `None` when
+    /// there is nothing
+    pub fn for_replay(replay: &ReplayInfo) -> Option<Self> {
+        let truncated = replay
+            .truncated
+            .then(|| Self::key(MessageKey::ReplayTruncated));
+        let lines: Vec<Self> = truncated
+            .into_iter()
+            .chain(replay.warnings.iter().filter_map(|warning| match warning {
+                ReplayWarning::DuplicatePlayer { name, .. } => Some(Self::key(
+                    MessageKey::DuplicatePlayer(inline_code_safe(name)),
+                )),
+                _ => None,
+            }))
+            .collect();
+        (!lines.is_empty()).then(|| Self::lines(&lines))
+    }
+
+    pub fn restart_skipped(filename: &str, game: usize) -> Self {
+        Self::for_file(filename, &Self::key(MessageKey::RestartSkipped { game }))
+    }
+
+    /// No archive entry matched a requested name: the request, then every
+    /// available replay (the rendered text is truncated to fit)
+    pub fn no_matching_replays(filter: &str, names: &[String]) -> Self {
+        let mut lines = vec![Self::key(MessageKey::NoMatchingReplays {
+            filter: inline_code_safe(filter),
+        })];
+        lines.extend(names.iter().map(|name| {
+            Self::key(MessageKey::AvailableReplay(inline_code_safe(
+                &display_filename(name),
+            )))
+        }));
+        Self::lines(&lines)
+    }
+
+    /// User-facing text for a replay error. Parse and render details are
+    /// dropped here; callers log the full error.
+    pub fn for_replay_error(error: &ReplayError) -> Self {
+        Self::key(match error {
+            ReplayError::InvalidHeader => MessageKey::InvalidReplay,
+            ReplayError::WrongGame(game) => MessageKey::WrongGame(game.clone()),
+            ReplayError::UnsupportedMap(map_name) => MessageKey::UnsupportedMap(map_name.clone()),
+            ReplayError::NoPlayers => MessageKey::NoPlayers,
+            ReplayError::ParseError(_) => MessageKey::UnreadableReplay,
+            ReplayError::RenderError(_) => MessageKey::RenderFailed,
+        })
+    }
+
+    /// User-facing text for an archive that yielded no replays
+    pub fn for_archive_error(error: &ArchiveError) -> Self {
+        Self::key(match error {
+            ArchiveError::Encrypted => MessageKey::ArchiveEncrypted,
+            ArchiveError::Corrupted(_) => MessageKey::ArchiveCorrupted,
+            ArchiveError::Unsupported => MessageKey::ArchiveUnsupported,
+            ArchiveError::Io(_) => MessageKey::ArchiveExtractFailed,
+        })
+    }
+
+    /// User-facing text for a message link that could not be followed
+    pub fn for_link_error(error: LinkError) -> Self {
+        Self::key(match error {
+            LinkError::Elsewhere => MessageKey::LinkElsewhere,
+            LinkError::NoAccess => MessageKey::LinkNoAccess,
+        })
+    }
+
+    pub fn link_empty() -> Self {
+        Self::key(MessageKey::LinkEmpty)
+    }
+
+    /// User-facing text for an upload rejected before parsing
+    pub fn for_preflight_error(error: &PreflightError) -> Self {
+        Self::key(match error {
+            PreflightError::TooSmall(_) => MessageKey::FileTooSmall,
+            PreflightError::SaveGame => MessageKey::SaveGame,
+            PreflightError::Image => MessageKey::ImageFile,
+            PreflightError::BadMagic => MessageKey::InvalidReplay,
+            PreflightError::WrongGame(game) => MessageKey::WrongGame(game.to_string()),
+            PreflightError::MissingMapMarker => MessageKey::NoMapInHeader,
+        })
+    }
+
+    /// Prefix a message with the attachment it refers to
+    pub fn for_file(filename: &str, message: &UserMessage) -> Self {
+        Self(
+            message
+                .0
+                .iter()
+                .map(|line| Line {
+                    filename: Some(filename.to_string()),
+                    key: line.key.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    /// One message per line
+    pub fn lines(messages: &[UserMessage]) -> Self {
+        Self(messages.iter().flat_map(|m| m.0.iter().cloned()).collect())
+    }
+}
+
+/// User text shown inside inline code: backticks would end it early
+fn inline_code_safe(text: &str) -> String {
+    text.replace('`', "'")
+}
+
+/// Rendered lines for `build_safe_content`
+pub fn to_parts(messages: &[UserMessage], locale: Locale) -> Vec<String> {
+    messages
+        .iter()
+        .flat_map(|m| m.render_lines(locale))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::constants::CONTENT_SAFE_LIMIT;
+
+    fn all_errors() -> Vec<ReplayError> {
+        vec![
+            ReplayError::InvalidHeader,
+            ReplayError::WrongGame("BFME1".to_string()),
+            ReplayError::UnsupportedMap("map helms deep".to_string()),
+            ReplayError::NoPlayers,
+            ReplayError::ParseError("offset 1234 in /tmp/x: unexpected EOF".to_string()),
+            ReplayError::RenderError("Failed to encode image: io error".to_string()),
+        ]
+    }
+
+    #[test]
+    fn every_replay_error_has_a_complete_message() {
+        for error in all_errors() {
+            for locale in [Locale::En, Locale::Tr] {
+                let text = UserMessage::for_replay_error(&error).render(locale);
+                assert!(!text.is_empty(), "{:?}", error);
+                assert!(!text.contains('{') && !text.contains('}'), "{:?}", error);
+            }
+        }
+    }
+
+    fn replay_with(warnings: Vec<ReplayWarning>) -> ReplayInfo {
+        ReplayInfo::new("map wor rhun".to_string(), Vec::new()).with_warnings(warnings)
+    }
+
+    #[test]
+    fn only_duplicate_players_are_reported() {
+        let warnings = vec![
+            ReplayWarning::TeamSideMismatch,
+            ReplayWarning::DuplicatePlayer {
+                slot: 2,
+                name: "Bob`s".to_string(),
+            },
+        ];
+        assert_eq!(
+            UserMessage::for_replay(&replay_with(warnings.clone()))
+                .unwrap()
+                .render(Locale::En),
+            "Duplicate player entry ignored: `Bob's`"
+        );
+        assert_eq!(
+            UserMessage::for_replay(&replay_with(warnings[..1].to_vec())),
+            None
+        );
+    }
+
+    #[test]
+    fn truncated_replays_are_flagged_first() {
+        let replay = replay_with(vec![ReplayWarning::DuplicatePlayer {
+            slot: 2,
+            name: "Bob".to_string(),
+        }])
+        .with_truncated(true);
+        let note = UserMessage::for_replay(&replay).unwrap();
+        assert_eq!(
+            note.render(Locale::En),
+            "Replay appears truncated — result may be wrong\nDuplicate player entry ignored: `Bob`"
+        );
+        assert!(
+            note.render(Locale::Tr)
+                .starts_with("Replay eksik görünüyor")
+        );
+    }
+
+    #[test]
+    fn preflight_errors_get_specific_messages() {
+        assert_eq!(
+            UserMessage::for_preflight_error(&PreflightError::SaveGame).render(Locale::En),
+            "This looks like a save game, not a replay"
+        );
+        assert_eq!(
+            UserMessage::for_preflight_error(&PreflightError::TooSmall(40)).render(Locale::En),
+            "File too small to be a replay"
+        );
+        assert_eq!(
+            UserMessage::for_preflight_error(&PreflightError::WrongGame("BFME1"))
+                .render(Locale::En),
+            "This looks like a BFME1 replay — this bot only supports BFME2/ROTWK"
+        );
+    }
+
+    #[test]
+    fn archive_errors_get_specific_messages() {
+        assert_eq!(
+            UserMessage::for_archive_error(&ArchiveError::Encrypted).render(Locale::En),
+            "Archive is password-protected"
+        );
+        let corrupted = UserMessage::for_archive_error(&ArchiveError::Corrupted(
+            "invalid Zip archive: Could not find EOCD".to_string(),
+        ));
+        assert_eq!(corrupted.render(Locale::En), "Archive appears corrupted");
+        assert_eq!(corrupted.render(Locale::Tr), "Arşiv bozuk görünüyor");
+    }
+
+    #[test]
+    fn internal_details_stay_out_of_user_messages() {
+        let parse = UserMessage::for_replay_error(&ReplayError::ParseError(
+            "offset 1234 in /tmp/x".to_string(),
+        ))
+        .render(Locale::En);
+        assert!(!parse.contains("1234"));
+        assert!(!parse.contains("/tmp"));
+
+        let render =
+            UserMessage::for_replay_error(&ReplayError::RenderError("io error".to_string()))
+                .render(Locale::En);
+        assert!(!render.contains("io error"));
+    }
+
+    #[test]
+    fn unsupported_map_names_the_map() {
+        let msg = UserMessage::for_replay_error(&ReplayError::UnsupportedMap(
+            "map helms deep".to_string(),
+        ));
+        assert_eq!(
+            msg.render(Locale::En),
+            "Not a Rhun game (map: map helms deep)"
+        );
+    }
+
+    #[test]
+    fn file_prefix_and_lines() {
+        let a = UserMessage::for_file("a.BfME2Replay", &UserMessage::replay_too_large());
+        assert_eq!(
+            a.render(Locale::En),
+            "a.BfME2Replay: Replay file too large (max 5MB)"
+        );
+        let joined = UserMessage::lines(&[UserMessage::showing(5, 12), a]);
+        assert_eq!(
+            joined.render(Locale::En),
+            "Showing 5 of 12 replays\na.BfME2Replay: Replay file too large (max 5MB)"
+        );
+        assert_eq!(
+            UserMessage::duplicates_merged(1).render(Locale::En),
+            "Merged 1 duplicate copy of the same game"
+        );
+        assert_eq!(
+            UserMessage::restart_skipped("b.BfME2Replay", 2).render(Locale::En),
+            "b.BfME2Replay: restart of game 2, skipped"
+        );
+    }
+
+    #[test]
+    fn archive_footer_lists_errors_only_when_present() {
+        let mut tally = ArchiveTally {
+            left: 8,
+            right: 6,
+            crashed: 2,
+            unknown: 4,
+            errors: 0,
+        };
+        assert_eq!(
+            UserMessage::archive_so_far(tally).render(Locale::En),
+            "So far: Left 8 / Right 6 / Crashed 2 / Unknown 4"
+        );
+        tally.errors = 1;
+        assert_eq!(
+            UserMessage::archive_so_far(tally).render(Locale::Tr),
+            "Şu ana kadar: Sol 8 / Sağ 6 / Çöken 2 / Bilinmeyen 4 / Hatalı 1"
+        );
+    }
+
+    #[test]
+    fn faction_stats_note_only_small_samples() {
+        let empty = FactionStats::collect(&[]);
+        let text = UserMessage::faction_stats(empty, 7, 10).render(Locale::En);
+        assert!(text.starts_with("Factions over 0 games from the last 7 days\n```\n"));
+        assert!(text.ends_with("```"));
+
+        let mut stats = FactionStats::collect(&[]);
+        stats.rows[0].picks = 2;
+        stats.rows[0].decided = 2;
+        stats.rows[0].wins = 1;
+        let text = UserMessage::faction_stats(stats, 7, 10).render(Locale::Tr);
+        assert!(text.contains("50%*"));
+        assert!(text.ends_with("* 10 oyundan az sonuç var, dikkatli yorumlayın"));
+    }
+
+    #[test]
+    fn no_match_lists_available_replays() {
+        let names = vec![
+            "game vs `ClanX`.BfME2Replay".to_string(),
+            "Rhun 1v1.BfME2Replay".to_string(),
+        ];
+        assert_eq!(
+            UserMessage::no_matching_replays("clanY", &names).render(Locale::En),
+            "No replay in the archive matches `clanY`. Available replays:\n\
+             - `game vs 'ClanX'`\n\
+             - `Rhun 1v1`"
+        );
+
+        // Long archives are cut to fit one Discord message
+        let many: Vec<String> = (0..100)
+            .map(|i| format!("{:03} a rather long tournament game name.BfME2Replay", i))
+            .collect();
+        let text = UserMessage::no_matching_replays("x", &many).render(Locale::En);
+        assert!(text.chars().count() <= CONTENT_SAFE_LIMIT);
+        assert!(text.contains("- `000 a rather long tournament g`"));
+        assert!(text.ends_with(" more...)"));
+    }
+
+    #[test]
+    fn turkish_replies_for_tr_locale() {
+        let msg = UserMessage::for_file("a.BfME2Replay", &UserMessage::replay_too_large());
+        assert_eq!(
+            msg.render(Locale::Tr),
+            "a.BfME2Replay: Replay dosyası çok büyük (en fazla 5MB)"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::models::{Faction, PLAYER_COLORS, Player, PlayerBuilder, ReplayInfo, Side, Winner};
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+
+use super::i18n::{Locale, MessageKey, translate};
+
+/// Longest template accepted, in characters
+const MAX_TEMPLATE_CHARS: usize = 200;
+
+/// Values a template can refer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// Winning side, e.g. "Left Team"
+    Side,
+    /// Winning players' names
+    Players,
+    Duration,
+    Map,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "side" => Some(Placeholder::Side),
+            "players" => Some(Placeholder::Players),
+            "duration" => Some(Placeholder::Duration),
+            "map" => Some(Placeholder::Map),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A guild's winner announcement, e.g. `Zafer: {side}! 🏆`.
+///
+/// Placeholders are `{side}`, `{players}`, `{duration}` and `{map}`; `{{`
+/// and `}}` stand for literal braces. Player and map names are substituted
+/// with Discord markdown escaped, so a name like `*Bob*` shows as typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WinnerTemplate(Vec<Part>);
+
+impl WinnerTemplate {
+    /// Parse a template, rejecting unknown placeholders and stray braces
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if template.trim().is_empty() {
+            return Err("template is empty".to_string());
+        }
+        if template.chars().count() > MAX_TEMPLATE_CHARS {
+            return Err(format!(
+                "template is longer than {} characters",
+                MAX_TEMPLATE_CHARS
+            ));
+        }
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err("unclosed '{' (write '{{' for a brace)".to_string()),
+                        }
+                    }
+                    let placeholder = Placeholder::parse(&name)
+                        .ok_or_else(|| format!("unknown placeholder {{{}}}", name))?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Placeholder(placeholder));
+                }
+                '}' => return Err("unmatched '}' (write '}}' for a brace)".to_string()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self(parts))
+    }
+
+    /// The announcement for a game with a winning side; `None` otherwise, so
+    /// the standard winner text is used
+    pub fn render(&self, replay: &ReplayInfo, locale: Locale) -> Option<String> {
+        let side = replay.winner.side()?;
+        let likely = matches!(
+            replay.winner,
+            Winner::LikelyLeftTeam | Winner::LikelyRightTeam
+        );
+        let team = match side {
+            Side::Left => 1,
+            Side::Right => 2,
+        };
+        Some(
+            self.0
+                .iter()
+                .map(|part| match part {
+                    Part::Text(text) => text.clone(),
+                    Part::Placeholder(Placeholder::Side) => {
+                        translate(&MessageKey::WinningSide { side, likely }, locale)
+                    }
+                    Part::Placeholder(Placeholder::Players) => replay
+                        .players
+                        .iter()
+                        .filter(|p| p.team == team)
+                        .map(|p| escape_markdown(&p.name))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    Part::Placeholder(Placeholder::Duration) => replay.duration_formatted(),
+                    Part::Placeholder(Placeholder::Map) => escape_markdown(&replay.map_name),
+                })
+                .collect(),
+        )
+    }
+
+    /// The announcement for a sample 2v2 the left team won, for
+    /// `/config winner-template preview`
+    pub fn preview(&self, locale: Locale) -> String {
+        // The sample always has a winning side
+        self.render(&sample_game(), locale).unwrap_or_default()
+    }
+}
+
+/// Backslash-escape the characters Discord reads as markdown (or as the
+/// start of a mention), so substituted names can't format the announcement
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '[' | ']' | '<'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn sample_game() -> ReplayInfo {
+    let player = |name: &str, team: i8, slot: u8, faction| -> Player {
+        PlayerBuilder {
+            name: name.to_string(),
+            uid: None,
+            team,
+            team_raw: team - 1,
+            slot,
+            faction,
+            color_id: slot as i8,
+            color_rgb: PLAYER_COLORS[slot as usize],
+        }
+        .build()
+    };
+    ReplayInfo::new(
+        "map wor rhun".to_string(),
+        vec![
+            player("Alice", 1, 0, Faction::Men),
+            player("Bob", 2, 1, Faction::Mordor),
+            player("Carol", 1, 2, Faction::Elves),
+            player("Dave", 2, 3, Faction::Isengard),
+        ],
+    )
+    .with_times(1_700_000_000, 1_700_000_754)
+    .with_winner(Winner::LeftTeam)
+}
+
+/// Winner templates per guild, with an optional one for every other guild
+#[derive(Debug, Clone, Default)]
+pub struct WinnerTemplates {
+    by_guild: HashMap<serenity::GuildId, WinnerTemplate>,
+    fallback: Option<WinnerTemplate>,
+}
+
+impl WinnerTemplates {
+    /// Parse a `WINNER_TEMPLATES` value: `guild_id=template` entries separated
+    /// by `;`, with `*` as the guild id for every guild without its own entry
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut templates = Self::default();
+        for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+            let (guild, template) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("missing '=' in entry '{}'", entry.trim()))?;
+            let template = WinnerTemplate::parse(template.trim())
+                .map_err(|e| format!("guild {}: {}", guild.trim(), e))?;
+            match guild.trim() {
+                "*" => templates.fallback = Some(template),
+                id => {
+                    let id = id
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("invalid guild id '{}'", id))?;
+                    templates
+                        .by_guild
+                        .insert(serenity::GuildId::new(id), template);
+                }
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Template for a guild (DMs use the fallback)
+    pub fn for_guild(&self, guild_id: Option<serenity::GuildId>) -> Option<&WinnerTemplate> {
+        guild_id
+            .and_then(|id| self.by_guild.get(&id))
+            .or(self.fallback.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, Player, PlayerBuilder};
+
+    fn player(name: &str, team: i8) -> Player {
+        PlayerBuilder::test_player(name, team, 0, Faction::Men)
+    }
+
+    fn game(winner: Winner) -> ReplayInfo {
+        ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![player("Alice", 1), player("Bob", 2), player("Carol", 1)],
+        )
+        .with_times(1_700_000_000, 1_700_000_754)
+        .with_winner(winner)
+    }
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let template =
+            WinnerTemplate::parse("{side} wins on {map} in {duration}: {players} {{gg}}").unwrap();
+        assert_eq!(
+            template.render(&game(Winner::LeftTeam), Locale::En),
+            Some("Left Team wins on map wor rhun in 12:34: Alice, Carol {gg}".to_string())
+        );
+        let turkish = WinnerTemplate::parse("Zafer: {side}! 🏆").unwrap();
+        assert_eq!(
+            turkish.render(&game(Winner::LikelyRightTeam), Locale::Tr),
+            Some("Zafer: Sağ Takım (muhtemel)! 🏆".to_string())
+        );
+    }
+
+    #[test]
+    fn substituted_names_are_markdown_escaped() {
+        let replay = ReplayInfo::new(
+            "map *wor* rhun".to_string(),
+            vec![
+                player("__Al__", 1),
+                player("Bob", 2),
+                player("`C|a>ro~l`", 1),
+            ],
+        )
+        .with_times(1_700_000_000, 1_700_000_754)
+        .with_winner(Winner::LeftTeam);
+        let template = WinnerTemplate::parse("**{players}** on {map}").unwrap();
+        assert_eq!(
+            template.render(&replay, Locale::En).as_deref(),
+            Some(r"**\_\_Al\_\_, \`C\|a\>ro\~l\`** on map \*wor\* rhun")
+        );
+    }
+
+    #[test]
+    fn preview_renders_a_sample_game() {
+        let template = WinnerTemplate::parse("{players} won as {side} in {duration}").unwrap();
+        assert_eq!(
+            template.preview(Locale::En),
+            "Alice, Carol won as Left Team in 12:34"
+        );
+    }
+
+    #[test]
+    fn games_without_a_winning_side_keep_the_standard_text() {
+        let template = WinnerTemplate::parse("{side}!").unwrap();
+        assert_eq!(
+            template.render(&game(Winner::NotConcluded), Locale::En),
+            None
+        );
+        assert_eq!(template.render(&game(Winner::Unknown), Locale::En), None);
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        assert!(WinnerTemplate::parse("{winner} won").is_err());
+        assert!(WinnerTemplate::parse("{side").is_err());
+        assert!(WinnerTemplate::parse("side}").is_err());
+        assert!(WinnerTemplate::parse("  ").is_err());
+        assert!(WinnerTemplate::parse(&"x".repeat(MAX_TEMPLATE_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn templates_per_guild() {
+        let templates = WinnerTemplates::parse("42=Zafer: {side}; *={side} won").unwrap();
+        let rendered = |guild: Option<u64>| {
+            templates
+                .for_guild(guild.map(serenity::GuildId::new))
+                .and_then(|t| t.render(&game(Winner::LeftTeam), Locale::En))
+        };
+        assert_eq!(rendered(Some(42)).as_deref(), Some("Zafer: Left Team"));
+        assert_eq!(rendered(Some(7)).as_deref(), Some("Left Team won"));
+        assert_eq!(rendered(None).as_deref(), Some("Left Team won"));
+
+        assert!(
+            WinnerTemplates::parse("")
+                .unwrap()
+                .for_guild(None)
+                .is_none()
+        );
+        assert!(WinnerTemplates::parse("abc={side}").is_err());
+        assert!(WinnerTemplates::parse("42={nope}").is_err());
+    }
+}
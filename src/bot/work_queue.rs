@@ -0,0 +1,404 @@
+//! Batch work waiting for a worker: a FIFO queue in front of a fixed number
+//! of worker slots, and the dispatcher handing slots out in order as they
+//! free up. Every render of several replays takes a slot: archives, several
+//! replay files or a file of several games, and their "Show more" pages
+//! (prefetched or on demand).
+
+use poise::serenity_prelude as serenity;
+use serenity::MessageId;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore, oneshot};
+
+use super::constants::{
+    MAX_CONCURRENT_ARCHIVES, WORK_QUEUE_CAPACITY, WORK_QUEUE_CHECK_INTERVAL_SECS,
+    WORK_QUEUE_MAX_WAIT_SECS,
+};
+
+/// Why a queued upload was dropped before its turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// Waited longer than the queue allows
+    Expired,
+    /// The upload was deleted
+    Cancelled,
+}
+
+/// A worker slot; dropping it frees the slot and wakes the dispatcher
+pub struct WorkPermit {
+    _permit: OwnedSemaphorePermit,
+    wake: Arc<Notify>,
+}
+
+impl Drop for WorkPermit {
+    fn drop(&mut self) {
+        self.wake.notify_one();
+    }
+}
+
+/// What a queued upload gets when it leaves the queue
+pub enum Turn {
+    Start(WorkPermit),
+    Dropped(DropReason),
+}
+
+/// Answer to an upload asking for a worker
+pub enum Admission {
+    /// A slot was free: start now
+    Start(WorkPermit),
+    /// Every slot is busy: wait on the ticket. `position` is 1-based.
+    Queued { position: usize, ticket: Ticket },
+    /// The queue is full too
+    Full,
+}
+
+/// A queued upload's claim on its turn
+pub struct Ticket(oneshot::Receiver<Turn>);
+
+impl Ticket {
+    /// Wait until the upload may start or is dropped
+    pub async fn wait(self) -> Turn {
+        // The queue itself is gone: nothing will start it
+        self.0.await.unwrap_or(Turn::Dropped(DropReason::Cancelled))
+    }
+}
+
+struct Job {
+    trigger: MessageId,
+    queued_at: Instant,
+    turn: oneshot::Sender<Turn>,
+}
+
+/// Worker slots for batch rendering and the work waiting for one
+pub struct WorkQueue {
+    /// On poison: recover (the queue holds no invariant a panic can break)
+    jobs: Mutex<VecDeque<Job>>,
+    permits: Arc<Semaphore>,
+    wake: Arc<Notify>,
+    capacity: usize,
+    max_wait: Duration,
+}
+
+impl Default for WorkQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkQueue {
+    pub fn new() -> Self {
+        Self::with_limits(
+            MAX_CONCURRENT_ARCHIVES,
+            WORK_QUEUE_CAPACITY,
+            Duration::from_secs(WORK_QUEUE_MAX_WAIT_SECS),
+        )
+    }
+
+    fn with_limits(workers: usize, capacity: usize, max_wait: Duration) -> Self {
+        Self {
+            jobs: Mutex::new(VecDeque::new()),
+            permits: Arc::new(Semaphore::new(workers)),
+            wake: Arc::new(Notify::new()),
+            capacity,
+            max_wait,
+        }
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, VecDeque<Job>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A free worker slot for work that is skipped while every slot is busy
+    /// (a page prefetch); never taken ahead of queued uploads
+    pub fn try_permit(&self) -> Option<WorkPermit> {
+        if !self.jobs().is_empty() {
+            return None;
+        }
+        self.free_permit()
+    }
+
+    fn free_permit(&self) -> Option<WorkPermit> {
+        let permit = self.permits.clone().try_acquire_owned().ok()?;
+        Some(WorkPermit {
+            _permit: permit,
+            wake: self.wake.clone(),
+        })
+    }
+
+    /// Ask for a worker for the upload `trigger`
+    pub fn admit(&self, trigger: MessageId) -> Admission {
+        self.admit_at(trigger, Instant::now())
+    }
+
+    fn admit_at(&self, trigger: MessageId, now: Instant) -> Admission {
+        let mut jobs = self.jobs();
+        // Nobody waiting: a free slot is this upload's
+        if jobs.is_empty()
+            && let Some(permit) = self.free_permit()
+        {
+            return Admission::Start(permit);
+        }
+        if jobs.len() >= self.capacity {
+            return Admission::Full;
+        }
+        let (turn, ticket) = oneshot::channel();
+        jobs.push_back(Job {
+            trigger,
+            queued_at: now,
+            turn,
+        });
+        Admission::Queued {
+            position: jobs.len(),
+            ticket: Ticket(ticket),
+        }
+    }
+
+    /// Drop a deleted upload from the queue. Returns false when it was not
+    /// queued (already started, or never queued).
+    pub fn cancel(&self, trigger: MessageId) -> bool {
+        let mut jobs = self.jobs();
+        let Some(index) = jobs.iter().position(|job| job.trigger == trigger) else {
+            return false;
+        };
+        if let Some(job) = jobs.remove(index) {
+            let _ = job.turn.send(Turn::Dropped(DropReason::Cancelled));
+        }
+        true
+    }
+
+    /// Drop uploads that waited too long, then start the next ones in
+    /// order while slots are free. Returns how many were started.
+    pub fn dispatch(&self) -> usize {
+        self.dispatch_at(Instant::now())
+    }
+
+    fn dispatch_at(&self, now: Instant) -> usize {
+        let mut jobs = self.jobs();
+        let (expired, waiting): (VecDeque<Job>, VecDeque<Job>) = jobs
+            .drain(..)
+            .partition(|job| now.saturating_duration_since(job.queued_at) >= self.max_wait);
+        *jobs = waiting;
+        for job in expired {
+            tracing::info!(msg_id = %job.trigger, "Queued upload waited too long, dropped");
+            let _ = job.turn.send(Turn::Dropped(DropReason::Expired));
+        }
+
+        let mut started = 0;
+        while let Some(job) = jobs.front() {
+            if !job.turn.is_closed() {
+                let Some(permit) = self.free_permit() else {
+                    break;
+                };
+                let job = jobs.pop_front().expect("front checked");
+                // Ticket dropped meanwhile: the permit frees itself again
+                if job.turn.send(Turn::Start(permit)).is_ok() {
+                    started += 1;
+                }
+            } else {
+                // Its upload went away without cancelling
+                jobs.pop_front();
+            }
+        }
+        started
+    }
+}
+
+/// Background task handing out worker slots as they free up, and dropping
+/// stale uploads every [`WORK_QUEUE_CHECK_INTERVAL_SECS`]
+pub struct WorkDispatcher {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl WorkDispatcher {
+    /// Start dispatching (call from within the runtime)
+    pub fn start(queue: Arc<WorkQueue>) -> Self {
+        let task = tokio::spawn(async move {
+            let interval = Duration::from_secs(WORK_QUEUE_CHECK_INTERVAL_SECS);
+            loop {
+                queue.dispatch();
+                tokio::select! {
+                    _ = queue.wake.notified() => {}
+                    _ = tokio::time::sleep(interval) => {}
+                }
+            }
+        });
+        Self { task }
+    }
+
+    /// Stop dispatching; uploads still queued wait until it restarts
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl WorkQueue {
+        fn len(&self) -> usize {
+            self.jobs().len()
+        }
+    }
+
+    fn id(n: u64) -> MessageId {
+        MessageId::new(n)
+    }
+
+    fn started(admission: Admission) -> WorkPermit {
+        match admission {
+            Admission::Start(permit) => permit,
+            _ => panic!("expected a free slot"),
+        }
+    }
+
+    fn queued(admission: Admission) -> (usize, Ticket) {
+        match admission {
+            Admission::Queued { position, ticket } => (position, ticket),
+            _ => panic!("expected to be queued"),
+        }
+    }
+
+    /// The turn handed to a ticket so far, without waiting
+    fn turn(ticket: &mut Ticket) -> Option<Turn> {
+        ticket.0.try_recv().ok()
+    }
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    #[test]
+    fn queued_uploads_start_in_order_as_slots_free() {
+        let queue = WorkQueue::with_limits(1, 10, minutes(5));
+        let now = Instant::now();
+        let first = started(queue.admit_at(id(1), now));
+        let (position, mut second) = queued(queue.admit_at(id(2), now));
+        assert_eq!(position, 1);
+        let (position, mut third) = queued(queue.admit_at(id(3), now));
+        assert_eq!(position, 2);
+
+        // Every slot busy: nothing moves
+        assert_eq!(queue.dispatch_at(now), 0);
+        assert!(turn(&mut second).is_none());
+
+        // Stubbed processing: each upload finishes and hands the slot on
+        drop(first);
+        assert_eq!(queue.dispatch_at(now + minutes(1)), 1);
+        let Some(Turn::Start(second_permit)) = turn(&mut second) else {
+            panic!("second upload should start");
+        };
+        assert!(turn(&mut third).is_none());
+        drop(second_permit);
+        assert_eq!(queue.dispatch_at(now + minutes(2)), 1);
+        assert!(matches!(turn(&mut third), Some(Turn::Start(_))));
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn uploads_waiting_too_long_are_dropped() {
+        let queue = WorkQueue::with_limits(1, 10, minutes(5));
+        let now = Instant::now();
+        let _busy = started(queue.admit_at(id(1), now));
+        let (_, mut old) = queued(queue.admit_at(id(2), now));
+        let (_, mut recent) = queued(queue.admit_at(id(3), now + minutes(4)));
+
+        queue.dispatch_at(now + minutes(5));
+        assert!(matches!(
+            turn(&mut old),
+            Some(Turn::Dropped(DropReason::Expired))
+        ));
+        assert!(turn(&mut recent).is_none());
+        assert_eq!(queue.len(), 1);
+        // Newcomers queue behind the one left
+        let (position, _) = queued(queue.admit_at(id(4), now + minutes(5)));
+        assert_eq!(position, 2);
+    }
+
+    #[test]
+    fn deleted_uploads_leave_the_queue() {
+        let queue = WorkQueue::with_limits(1, 10, minutes(5));
+        let now = Instant::now();
+        let busy = started(queue.admit_at(id(1), now));
+        let (_, mut deleted) = queued(queue.admit_at(id(2), now));
+        let (_, mut next) = queued(queue.admit_at(id(3), now));
+
+        assert!(queue.cancel(id(2)));
+        assert!(matches!(
+            turn(&mut deleted),
+            Some(Turn::Dropped(DropReason::Cancelled))
+        ));
+        // Running or unknown uploads are not queued
+        assert!(!queue.cancel(id(1)));
+        assert!(!queue.cancel(id(2)));
+
+        drop(busy);
+        queue.dispatch_at(now);
+        assert!(matches!(turn(&mut next), Some(Turn::Start(_))));
+    }
+
+    #[test]
+    fn a_full_queue_turns_uploads_away() {
+        let queue = WorkQueue::with_limits(1, 2, minutes(5));
+        let now = Instant::now();
+        let _busy = started(queue.admit_at(id(1), now));
+        let _second = queued(queue.admit_at(id(2), now));
+        let _third = queued(queue.admit_at(id(3), now));
+        assert!(matches!(queue.admit_at(id(4), now), Admission::Full));
+    }
+
+    #[test]
+    fn abandoned_tickets_do_not_hold_a_slot() {
+        let queue = WorkQueue::with_limits(1, 10, minutes(5));
+        let now = Instant::now();
+        let busy = started(queue.admit_at(id(1), now));
+        let (_, gone) = queued(queue.admit_at(id(2), now));
+        let (_, mut next) = queued(queue.admit_at(id(3), now));
+        drop(gone);
+
+        drop(busy);
+        assert_eq!(queue.dispatch_at(now), 1);
+        assert!(matches!(turn(&mut next), Some(Turn::Start(_))));
+    }
+
+    #[test]
+    fn skippable_work_takes_only_a_slot_nobody_waits_for() {
+        let queue = WorkQueue::with_limits(1, 10, minutes(5));
+        let now = Instant::now();
+        let prefetch = queue.try_permit().expect("a slot is free");
+        // The prefetch holds the slot an upload would take
+        let (_, mut upload) = queued(queue.admit_at(id(1), now));
+        assert!(queue.try_permit().is_none());
+
+        drop(prefetch);
+        // The freed slot goes to the queued upload, not to another prefetch
+        assert!(queue.try_permit().is_none());
+        assert_eq!(queue.dispatch_at(now), 1);
+        let Some(Turn::Start(upload_permit)) = turn(&mut upload) else {
+            panic!("queued upload should start");
+        };
+        assert!(queue.try_permit().is_none());
+        drop(upload_permit);
+        assert!(queue.try_permit().is_some());
+    }
+
+    #[tokio::test]
+    async fn dispatcher_hands_freed_slots_to_waiting_uploads() {
+        let queue = Arc::new(WorkQueue::with_limits(1, 10, minutes(5)));
+        let dispatcher = WorkDispatcher::start(queue.clone());
+        let first = started(queue.admit(id(1)));
+        let (_, second) = queued(queue.admit(id(2)));
+
+        let processed = tokio::spawn(async move {
+            match second.wait().await {
+                // Stubbed processing: nothing to do but hold the slot
+                Turn::Start(_permit) => true,
+                Turn::Dropped(_) => false,
+            }
+        });
+        drop(first);
+        assert!(processed.await.unwrap());
+        dispatcher.stop();
+    }
+}
@@ -0,0 +1,490 @@
+//! Golden-corpus regression checks: a canonical flat serialization of
+//! `ReplayInfo`, a field-level differ, and a harness that compares a directory
+//! of replays against their JSON snapshots.
+
+use crate::models::{Player, ReplayInfo};
+use crate::parser::parse_replay;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Env var pointing at the corpus directory (golden test skips when unset)
+pub const GOLDEN_DIR_ENV: &str = "GOLDEN_DIR";
+
+/// Env var that, when set to `1`, rewrites snapshots instead of comparing
+pub const GOLDEN_REGENERATE_ENV: &str = "GOLDEN_REGENERATE";
+
+/// Ordered `(field, value)` pairs; `None` is serialized as JSON `null`
+pub type CanonicalFields = Vec<(String, Option<String>)>;
+
+/// Canonical field list for a replay: fixed field order, players and
+/// spectators in replay order, first actions sorted by kind, floats to 0.1.
+pub fn canonical_fields(replay: &ReplayInfo) -> CanonicalFields {
+    let mut fields = Vec::new();
+    let mut push = |key: String, value: Option<String>| fields.push((key, value));
+
+    push("map_name".into(), Some(replay.map_name.clone()));
+    push("map_path".into(), Some(replay.map_path.clone()));
+    push(
+        "start_time".into(),
+        replay.start_time.map(|t| t.to_string()),
+    );
+    push("end_time".into(), replay.end_time.map(|t| t.to_string()));
+    push(
+        "estimated_duration_secs".into(),
+        replay.estimated_duration_secs.map(|s| s.to_string()),
+    );
+    push("winner".into(), Some(format!("{:?}", replay.winner)));
+    push("ending".into(), Some(format!("{:?}", replay.ending)));
+    push("game_crashed".into(), Some(replay.game_crashed.to_string()));
+    push("is_partial".into(), Some(replay.is_partial.to_string()));
+
+    for (i, player) in replay.players.iter().enumerate() {
+        for (name, value) in player_fields(player) {
+            push(format!("players[{}].{}", i, name), value);
+        }
+    }
+    for (i, spectator) in replay.spectators.iter().enumerate() {
+        push(
+            format!("spectators[{}].name", i),
+            Some(spectator.name.clone()),
+        );
+        push(format!("spectators[{}].uid", i), spectator.uid.clone());
+        push(
+            format!("spectators[{}].slot", i),
+            spectator.slot.map(|s| s.to_string()),
+        );
+    }
+    fields
+}
+
+fn player_fields(player: &Player) -> CanonicalFields {
+    let mut fields: CanonicalFields = vec![
+        ("name".into(), Some(player.name.clone())),
+        ("uid".into(), player.uid.clone()),
+        ("team".into(), Some(player.team.to_string())),
+        ("team_raw".into(), Some(player.team_raw.to_string())),
+        ("slot".into(), Some(player.slot.to_string())),
+        ("faction".into(), Some(player.faction.to_string())),
+        (
+            "actual_faction".into(),
+            player.actual_faction.map(|f| f.to_string()),
+        ),
+        ("color_id".into(), Some(player.color_id.to_string())),
+        (
+            "color_rgb".into(),
+            Some(format!(
+                "{},{},{}",
+                player.color_rgb[0], player.color_rgb[1], player.color_rgb[2]
+            )),
+        ),
+        (
+            "map_position".into(),
+            player
+                .map_position
+                .map(|p| format!("{:.1},{:.1}", p.x, p.y)),
+        ),
+        ("spot".into(), player.spot.map(|s| s.to_string())),
+        (
+            "name_encoding".into(),
+            Some(player.name_encoding.to_string()),
+        ),
+    ];
+
+    let mut first_actions: Vec<(String, u32)> = player
+        .first_actions
+        .iter()
+        .map(|(kind, tc)| (format!("{:?}", kind), *tc))
+        .collect();
+    first_actions.sort();
+    for (kind, tc) in first_actions {
+        fields.push((format!("first_actions.{}", kind), Some(tc.to_string())));
+    }
+    fields
+}
+
+/// Serialize fields as a flat JSON object, one field per line
+pub fn to_json(fields: &CanonicalFields) -> String {
+    let mut out = String::from("{\n");
+    for (i, (key, value)) in fields.iter().enumerate() {
+        out.push_str("  ");
+        write_json_string(&mut out, key);
+        out.push_str(": ");
+        match value {
+            Some(v) => write_json_string(&mut out, v),
+            None => out.push_str("null"),
+        }
+        if i + 1 < fields.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Parse a flat JSON object of string/null values (the `to_json` format)
+pub fn from_json(json: &str) -> Result<CanonicalFields, String> {
+    let mut chars = json.chars().peekable();
+    let mut fields = Vec::new();
+
+    skip_ws(&mut chars);
+    expect(&mut chars, '{')?;
+    skip_ws(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_ws(&mut chars);
+            let key = read_json_string(&mut chars)?;
+            skip_ws(&mut chars);
+            expect(&mut chars, ':')?;
+            skip_ws(&mut chars);
+            let value = if chars.peek() == Some(&'n') {
+                for expected in "null".chars() {
+                    expect(&mut chars, expected)?;
+                }
+                None
+            } else {
+                Some(read_json_string(&mut chars)?)
+            };
+            fields.push((key, value));
+            skip_ws(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                other => return Err(format!("Expected ',' or '}}', found {:?}", other)),
+            }
+        }
+    }
+    skip_ws(&mut chars);
+    if let Some(c) = chars.next() {
+        return Err(format!("Trailing data after object: {:?}", c));
+    }
+    Ok(fields)
+}
+
+type JsonChars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_ws(chars: &mut JsonChars<'_>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut JsonChars<'_>, expected: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        other => Err(format!("Expected {:?}, found {:?}", expected, other)),
+    }
+}
+
+fn read_json_string(chars: &mut JsonChars<'_>) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('"') => s.push('"'),
+                Some('\\') => s.push('\\'),
+                Some('/') => s.push('/'),
+                Some('n') => s.push('\n'),
+                Some('r') => s.push('\r'),
+                Some('t') => s.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("Invalid \\u escape: {}", hex))?;
+                    s.push(c);
+                }
+                other => return Err(format!("Invalid escape: {:?}", other)),
+            },
+            Some(c) => s.push(c),
+            None => return Err("Unterminated string".to_string()),
+        }
+    }
+}
+
+/// One field that differs between an expected and an actual snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: String,
+    /// `None` when the field is absent on that side
+    pub expected: Option<Option<String>>,
+    pub actual: Option<Option<String>>,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn show(v: &Option<Option<String>>) -> String {
+            match v {
+                None => "<absent>".to_string(),
+                Some(None) => "null".to_string(),
+                Some(Some(s)) => format!("{:?}", s),
+            }
+        }
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.field,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+/// Field-level differences, in expected order followed by fields only in actual
+pub fn diff_fields(expected: &CanonicalFields, actual: &CanonicalFields) -> Vec<FieldDiff> {
+    let lookup = |fields: &CanonicalFields, key: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    };
+
+    let mut diffs = Vec::new();
+    for (key, value) in expected {
+        let actual_value = lookup(actual, key);
+        if actual_value.as_ref() != Some(value) {
+            diffs.push(FieldDiff {
+                field: key.clone(),
+                expected: Some(value.clone()),
+                actual: actual_value,
+            });
+        }
+    }
+    for (key, value) in actual {
+        if lookup(expected, key).is_none() {
+            diffs.push(FieldDiff {
+                field: key.clone(),
+                expected: None,
+                actual: Some(value.clone()),
+            });
+        }
+    }
+    diffs
+}
+
+/// Field-level differences between two replays
+pub fn diff_replays(expected: &ReplayInfo, actual: &ReplayInfo) -> Vec<FieldDiff> {
+    diff_fields(&canonical_fields(expected), &canonical_fields(actual))
+}
+
+/// Outcome of checking a corpus directory against its snapshots
+#[derive(Debug, Default)]
+pub struct CorpusReport {
+    pub checked: usize,
+    /// Snapshots written (regenerate mode)
+    pub written: usize,
+    /// Replays with no snapshot next to them
+    pub missing: Vec<PathBuf>,
+    pub mismatches: Vec<(PathBuf, Vec<FieldDiff>)>,
+}
+
+impl CorpusReport {
+    /// No missing snapshots and no mismatches
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for CorpusReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} replays checked, {} snapshots written, {} missing, {} mismatched",
+            self.checked,
+            self.written,
+            self.missing.len(),
+            self.mismatches.len()
+        )?;
+        for path in &self.missing {
+            writeln!(f, "missing snapshot: {}", path.display())?;
+        }
+        for (path, diffs) in &self.mismatches {
+            writeln!(f, "{}:", path.display())?;
+            for diff in diffs {
+                writeln!(f, "  {}", diff)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Canonical fields for a replay file; parse failures are snapshotted too
+pub fn snapshot_replay(bytes: &[u8]) -> CanonicalFields {
+    match parse_replay(bytes) {
+        Ok(replay) => canonical_fields(&replay),
+        Err(e) => vec![("error".to_string(), Some(e.to_string()))],
+    }
+}
+
+/// Parse every `.BfME2Replay` in `dir` and compare against the `.json`
+/// snapshot beside it. With `regenerate`, snapshots are (re)written instead.
+pub fn check_corpus(dir: &Path, regenerate: bool) -> Result<CorpusReport, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read corpus dir {}: {}", dir.display(), e))?;
+    let mut replays: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("bfme2replay"))
+        })
+        .collect();
+    replays.sort();
+
+    let mut report = CorpusReport::default();
+    for path in replays {
+        let bytes = std::fs::read(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let actual = snapshot_replay(&bytes);
+        let snapshot_path = path.with_extension("json");
+        report.checked += 1;
+
+        if regenerate {
+            std::fs::write(&snapshot_path, to_json(&actual))
+                .map_err(|e| format!("Failed to write {}: {}", snapshot_path.display(), e))?;
+            report.written += 1;
+            continue;
+        }
+
+        let Ok(json) = std::fs::read_to_string(&snapshot_path) else {
+            report.missing.push(path);
+            continue;
+        };
+        let expected = from_json(&json)
+            .map_err(|e| format!("Invalid snapshot {}: {}", snapshot_path.display(), e))?;
+        let diffs = diff_fields(&expected, &actual);
+        if !diffs.is_empty() {
+            report.mismatches.push((path, diffs));
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        Faction, MapPosition, MapSpot, OrderKind, PlayerBuilder, Spectator, Winner,
+    };
+
+    fn player(name: &str, slot: u8) -> Player {
+        let mut player = PlayerBuilder::test_player(name, slot as i8, slot, Faction::Men);
+        player.uid = Some(format!("{:08x}", slot));
+        player
+    }
+
+    fn replay() -> ReplayInfo {
+        ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![player("Alice", 0), player("Bob", 1)],
+        )
+        .with_times(1700000000, 1700001000)
+        .with_winner(Winner::LeftTeam)
+    }
+
+    #[test]
+    fn identical_replays_have_no_diff() {
+        assert!(diff_replays(&replay(), &replay()).is_empty());
+    }
+
+    #[test]
+    fn changed_fields_are_reported_by_path() {
+        let expected = replay();
+        let mut actual = replay().with_winner(Winner::LikelyRightTeam);
+        actual.players[1].spot = Some(MapSpot::TopRight);
+
+        let diffs = diff_replays(&expected, &actual);
+        let fields: Vec<&str> = diffs.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(fields, ["winner", "players[1].spot"]);
+        assert_eq!(
+            diffs[0].to_string(),
+            "winner: expected \"LeftTeam\", got \"LikelyRightTeam\""
+        );
+        assert_eq!(
+            diffs[1].to_string(),
+            "players[1].spot: expected null, got \"Top Right\""
+        );
+    }
+
+    #[test]
+    fn positions_are_rounded_before_comparing() {
+        let mut expected = replay();
+        expected.players[0].map_position = Some(MapPosition::new(1000.01, 2000.04));
+        let mut actual = replay();
+        actual.players[0].map_position = Some(MapPosition::new(1000.02, 2000.03));
+        assert!(diff_replays(&expected, &actual).is_empty());
+
+        actual.players[0].map_position = Some(MapPosition::new(1000.5, 2000.0));
+        assert_eq!(diff_replays(&expected, &actual).len(), 1);
+    }
+
+    #[test]
+    fn added_and_removed_fields_are_reported() {
+        let expected = replay();
+        let mut actual = replay().with_spectators(vec![Spectator {
+            name: "Obs".to_string(),
+            uid: None,
+            slot: Some(2),
+        }]);
+        actual.players[0].first_actions.insert(OrderKind::Build, 40);
+        actual.players.pop();
+
+        let diffs = diff_replays(&expected, &actual);
+        assert!(
+            diffs
+                .iter()
+                .any(|d| d.field == "players[1].name" && d.actual.is_none())
+        );
+        let added: Vec<&str> = diffs
+            .iter()
+            .filter(|d| d.expected.is_none())
+            .map(|d| d.field.as_str())
+            .collect();
+        assert_eq!(
+            added,
+            [
+                "players[0].first_actions.Build",
+                "spectators[0].name",
+                "spectators[0].uid",
+                "spectators[0].slot"
+            ]
+        );
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let mut r = replay();
+        r.players[0].name = "Quote\"Back\\slash\ttab".to_string();
+        let fields = canonical_fields(&r);
+        assert_eq!(from_json(&to_json(&fields)).unwrap(), fields);
+        assert_eq!(from_json("{}").unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn from_json_rejects_malformed_input() {
+        assert!(from_json("").is_err());
+        assert!(from_json("{\"a\": 1}").is_err());
+        assert!(from_json("{\"a\": \"b\"").is_err());
+        assert!(from_json("{\"a\": \"b\"} x").is_err());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod bot;
-pub mod models;
-pub mod parser;
+pub mod golden;
+pub mod logging;
+pub mod metrics;
 pub mod renderer;
+
+pub use bfme2_replay_parser::{models, parser};
@@ -0,0 +1,107 @@
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::SubscriberBuilder;
+use tracing_subscriber::fmt::format::{Format, Json, JsonFields};
+
+/// Log line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of every enclosing span
+    Json,
+}
+
+impl LogFormat {
+    /// Parse `text` or `json` (case-insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("text") {
+            Some(LogFormat::Text)
+        } else if name.eq_ignore_ascii_case("json") {
+            Some(LogFormat::Json)
+        } else {
+            None
+        }
+    }
+}
+
+/// Install the global subscriber: `RUST_LOG` filter (info by default) and the given format
+pub fn init(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        LogFormat::Json => json_format().with_env_filter(filter).init(),
+    }
+}
+
+/// One JSON object per line: time, level, target, the event's fields, its
+/// current span and every enclosing span (outermost first) with its fields
+fn json_format() -> SubscriberBuilder<JsonFields, Format<Json>> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+}
+
+/// A JSON subscriber taking every level and writing into memory, and the
+/// buffer it writes to (leaked, so it outlives the subscriber)
+#[cfg(test)]
+pub(crate) fn capture_json() -> (
+    impl tracing::Subscriber + Send + Sync,
+    &'static std::sync::Mutex<Vec<u8>>,
+) {
+    use tracing_subscriber::fmt::MakeWriter;
+    let buffer: &'static std::sync::Mutex<Vec<u8>> = Box::leak(Box::default());
+    let subscriber = json_format()
+        .with_max_level(tracing::Level::TRACE)
+        .with_writer(move || buffer.make_writer())
+        .finish();
+    (subscriber, buffer)
+}
+
+/// Every line written to a [`capture_json`] buffer so far, parsed
+#[cfg(test)]
+pub(crate) fn json_lines(buffer: &std::sync::Mutex<Vec<u8>>) -> Vec<serde_json::Value> {
+    let bytes = buffer.lock().unwrap().clone();
+    String::from_utf8(bytes)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_format_names() {
+        assert_eq!(LogFormat::from_name("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::from_name("text"), Some(LogFormat::Text));
+        assert_eq!(LogFormat::from_name("yaml"), None);
+    }
+
+    #[test]
+    fn events_carry_fields_of_enclosing_spans() {
+        let (subscriber, buffer) = capture_json();
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("message", message_id = 42u64);
+            let _outer = outer.enter();
+            let inner = tracing::info_span!("replay", outcome = tracing::field::Empty);
+            let _inner = inner.enter();
+            inner.record("outcome", "ok");
+            tracing::warn!(size = 3, "Sent {}", "image");
+        });
+
+        let lines = json_lines(buffer);
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["fields"]["message"], "Sent image");
+        assert_eq!(line["fields"]["size"], 3);
+        assert_eq!(line["spans"][0]["name"], "message");
+        assert_eq!(line["spans"][0]["message_id"], 42);
+        assert_eq!(line["spans"][1]["name"], "replay");
+        assert_eq!(line["spans"][1]["outcome"], "ok");
+    }
+}
@@ -1,13 +1,19 @@
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
-use tracing_subscriber::EnvFilter;
 
-use dcreplaybot::bot::setup_bot;
+use dcreplaybot::bot::{
+    AdminWebhook, AttachmentLimits, BotConfig, ChannelScopes, DeleteReaction, Locale, Readiness,
+    ReplyStyle, ResultsStore, WinnerTemplates, parse_guild_ids, parse_webhook_ids, setup_bot,
+};
+use dcreplaybot::logging::{self, LogFormat};
+use dcreplaybot::metrics;
+use dcreplaybot::renderer::{DisplayPalette, RenderOptions};
 
-/// Minimal HTTP health check server
-async fn health_check_server(port: u16) {
+/// Minimal HTTP health check server, also serving `GET /metrics` and `GET /ready`
+async fn health_check_server(port: u16, readiness: Readiness) {
     let addr = format!("0.0.0.0:{}", port);
     let listener = match TcpListener::bind(&addr).await {
         Ok(l) => {
@@ -23,10 +29,14 @@ async fn health_check_server(port: u16) {
     loop {
         match listener.accept().await {
             Ok((mut stream, _)) => {
-                // Read and discard the request bytes before responding
-                let mut discard = [0u8; 1024];
-                let _ = stream.read(&mut discard).await;
-                let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK";
+                // Only the request line matters: /metrics or the plain health check
+                let mut request = [0u8; 1024];
+                let n = stream.read(&mut request).await.unwrap_or(0);
+                let response = metrics::http_response(
+                    &request[..n],
+                    readiness.is_ready(),
+                    &readiness.probe_text(),
+                );
                 let _ = stream.write_all(response.as_bytes()).await;
                 let _ = stream.shutdown().await;
             }
@@ -39,16 +49,18 @@ async fn health_check_server(port: u16) {
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
-
     // Load .env file if present
     let _ = dotenvy::dotenv();
 
+    // Initialize logging: text lines (default) or JSON lines with span fields
+    let log_format = match env::var("LOG_FORMAT") {
+        Ok(name) => {
+            LogFormat::from_name(&name).ok_or_else(|| format!("Invalid LOG_FORMAT: {}", name))?
+        }
+        Err(_) => LogFormat::default(),
+    };
+    logging::init(log_format);
+
     // Get Discord token
     let token = env::var("DISCORD_TOKEN").expect("DISCORD_TOKEN environment variable not set");
 
@@ -57,20 +69,195 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .map(PathBuf::from)
         .unwrap_or_else(|_| PathBuf::from("assets"));
 
-    // Health check port (default 8000 for Koyeb)
+    // Optional per-spot label placement overrides (see MapLayout::parse),
+    // applied on top of the map's layout file when the bot starts
+    let label_overrides = env::var("LABEL_LAYOUT").ok();
+
+    // Reply language when a guild's preferred locale is unsupported (en, tr)
+    let default_locale = match env::var("DEFAULT_LOCALE") {
+        Ok(tag) => {
+            Locale::from_tag(&tag).ok_or_else(|| format!("Invalid DEFAULT_LOCALE: {}", tag))?
+        }
+        Err(_) => Locale::default(),
+    };
+
+    // Single-replay result style: plain image (default) or embed
+    let reply_style = match env::var("REPLY_STYLE") {
+        Ok(name) => {
+            ReplyStyle::from_name(&name).ok_or_else(|| format!("Invalid REPLY_STYLE: {}", name))?
+        }
+        Err(_) => ReplyStyle::default(),
+    };
+
+    // Player colors in images: in-game colors (default) or color-blind safe
+    let palette = match env::var("DISPLAY_PALETTE") {
+        Ok(name) => DisplayPalette::from_name(&name)
+            .ok_or_else(|| format!("Invalid DISPLAY_PALETTE: {}", name))?,
+        Err(_) => DisplayPalette::default(),
+    };
+
+    // Lobby slot panel on the left edge of images (off by default)
+    let show_lobby_panel = match env::var("LOBBY_PANEL") {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return Err(format!("Invalid LOBBY_PANEL: {}", value).into()),
+        },
+        Err(_) => false,
+    };
+
+    // "Empty" markers on unclaimed spots (default: games with fewer than six players)
+    let show_empty_spots = match env::var("EMPTY_SPOTS") {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => None,
+            "on" | "true" | "1" => Some(true),
+            "off" | "false" | "0" => Some(false),
+            _ => return Err(format!("Invalid EMPTY_SPOTS: {}", value).into()),
+        },
+        Err(_) => None,
+    };
+
+    // 1v1 images cropped to the two players' spots (off by default)
+    let crop_duels = match env::var("CROP_DUELS") {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return Err(format!("Invalid CROP_DUELS: {}", value).into()),
+        },
+        Err(_) => false,
+    };
+
+    // Compact player labels (default: games where many players have long names)
+    let compact_labels = match env::var("COMPACT_LABELS") {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => None,
+            "on" | "true" | "1" => Some(true),
+            "off" | "false" | "0" => Some(false),
+            _ => return Err(format!("Invalid COMPACT_LABELS: {}", value).into()),
+        },
+        Err(_) => None,
+    };
+
+    // Webhooks whose uploads are processed like a person's (e.g. upload bridges)
+    let allowed_webhooks = match env::var("ALLOWED_WEBHOOK_IDS") {
+        Ok(spec) => {
+            parse_webhook_ids(&spec).map_err(|e| format!("Invalid ALLOWED_WEBHOOK_IDS: {}", e))?
+        }
+        Err(_) => Vec::new(),
+    };
+
+    // Channels the bot answers in: `guild_id=channel_id,channel_id;...`
+    let channel_scopes = match env::var("BOT_CHANNELS") {
+        Ok(spec) => {
+            ChannelScopes::parse(&spec).map_err(|e| format!("Invalid BOT_CHANNELS: {}", e))?
+        }
+        Err(_) => ChannelScopes::default(),
+    };
+
+    // Custom winner text in result embeds: `guild_id=template;*=template`
+    let winner_templates = match env::var("WINNER_TEMPLATES") {
+        Ok(spec) => {
+            WinnerTemplates::parse(&spec).map_err(|e| format!("Invalid WINNER_TEMPLATES: {}", e))?
+        }
+        Err(_) => WinnerTemplates::default(),
+    };
+
+    // Attachment bytes per batch message; raise on boosted servers (default 8)
+    let attachment_limits = match env::var("UPLOAD_LIMIT_MB") {
+        Ok(value) => match value.trim().parse::<usize>() {
+            Ok(mb) if mb > 0 => AttachmentLimits::with_megabytes(mb),
+            _ => return Err(format!("Invalid UPLOAD_LIMIT_MB: {}", value).into()),
+        },
+        Err(_) => AttachmentLimits::default(),
+    };
+
+    // Append-only log of parsed games behind `/stats` (off unless a path is set)
+    let results_store = env::var("RESULTS_LOG")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map(|path| ResultsStore::new(PathBuf::from(path.trim())));
+
+    // Reaction that deletes a result (requester or ManageMessages; default 🗑️)
+    let delete_reaction = match env::var("DELETE_REACTION") {
+        Ok(spec) => {
+            DeleteReaction::parse(&spec).map_err(|e| format!("Invalid DELETE_REACTION: {}", e))?
+        }
+        Err(_) => DeleteReaction::default(),
+    };
+
+    // Hours a single result keeps its replay file for 🔁 re-renders (default 24; 0 turns it off)
+    let replay_retention = match env::var("REPLAY_RETENTION_HOURS") {
+        Ok(value) => match value
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|h| h.checked_mul(3600))
+        {
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => return Err(format!("Invalid REPLAY_RETENTION_HOURS: {}", value).into()),
+        },
+        Err(_) => None,
+    };
+
+    // Guilds whose results always hide player names (others opt in with "anon")
+    let anonymous_guilds = match env::var("ANONYMOUS_GUILDS") {
+        Ok(spec) => {
+            parse_guild_ids(&spec).map_err(|e| format!("Invalid ANONYMOUS_GUILDS: {}", e))?
+        }
+        Err(_) => Vec::new(),
+    };
+
+    // Discord webhook the bot's own processing failures are reported to
+    let admin_webhook = match env::var("ADMIN_WEBHOOK_URL") {
+        Ok(url) if !url.trim().is_empty() => Some(
+            AdminWebhook::parse(&url).map_err(|e| format!("Invalid ADMIN_WEBHOOK_URL: {}", e))?,
+        ),
+        _ => None,
+    };
+
+    // Health check port (default 8000 for Koyeb); 0 or DISABLE_HEALTH=1 turns it off
     let port: u16 = env::var("PORT")
         .ok()
         .and_then(|p| p.parse().ok())
         .unwrap_or(8000);
+    let health_disabled = port == 0 || env::var("DISABLE_HEALTH").is_ok_and(|v| v.trim() == "1");
 
     tracing::info!("Starting DCReplayBot...");
     tracing::info!("Assets path: {:?}", assets_path);
 
     // Start health check server in background
-    tokio::spawn(health_check_server(port));
+    let readiness = Readiness::new();
+    if health_disabled {
+        tracing::info!("Health check disabled");
+    } else {
+        tokio::spawn(health_check_server(port, readiness.clone()));
+    }
 
     // Run the bot
-    setup_bot(token, assets_path).await?;
+    let config = BotConfig {
+        label_overrides,
+        default_locale,
+        reply_style,
+        render_options: RenderOptions {
+            palette,
+            show_lobby_panel,
+            anonymize: false,
+            show_empty_spots,
+            crop_duels,
+            compact_labels,
+        },
+        allowed_webhooks,
+        channel_scopes,
+        winner_templates,
+        attachment_limits,
+        readiness,
+        results_store,
+        delete_reaction,
+        replay_retention,
+        anonymous_guilds,
+        admin_webhook,
+    };
+    setup_bot(token, assets_path, config).await?;
 
     Ok(())
 }
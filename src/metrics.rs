@@ -0,0 +1,374 @@
+use crate::models::{ReplayError, TimingBreakdown};
+use crate::parser::TickRateCalibration;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds (seconds) for parse/render duration buckets
+const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Upper bounds for replays-per-batch buckets
+const BATCH_SIZE_BUCKETS: &[f64] = &[1.0, 2.0, 3.0, 5.0, 8.0, 10.0];
+
+/// `ReplayError` variants as metric labels, in `error_index` order
+const ERROR_LABELS: [&str; 6] = [
+    "invalid_header",
+    "unsupported_map",
+    "no_players",
+    "parse_error",
+    "render_error",
+    "wrong_game",
+];
+
+fn error_index(error: &ReplayError) -> usize {
+    match error {
+        ReplayError::InvalidHeader => 0,
+        ReplayError::UnsupportedMap(_) => 1,
+        ReplayError::NoPlayers => 2,
+        ReplayError::ParseError(_) => 3,
+        ReplayError::RenderError(_) => 4,
+        ReplayError::WrongGame(_) => 5,
+    }
+}
+
+/// Metric label of a `ReplayError` variant
+pub fn error_label(error: &ReplayError) -> &'static str {
+    ERROR_LABELS[error_index(error)]
+}
+
+/// Cumulative histogram with fixed bucket bounds
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// One counter per bound, plus the `+Inf` bucket
+    counts: [AtomicU64; 11],
+    /// Sum of observed values in millionths
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new(bounds: &'static [f64]) -> Self {
+        assert!(bounds.len() < 11);
+        Self {
+            bounds,
+            counts: [const { AtomicU64::new(0) }; 11],
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|&bound| value <= bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add((value * 1_000_000.0) as u64, Ordering::Relaxed);
+    }
+
+    fn write_prometheus(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (i, bound) in self.bounds.iter().enumerate() {
+            cumulative += self.counts[i].load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        cumulative += self.counts[self.bounds.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, cumulative);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, cumulative);
+    }
+}
+
+/// Everything the bot reports
+pub struct Metrics {
+    replays_parsed: AtomicU64,
+    parse_errors: [AtomicU64; 6],
+    raw_scan_recoveries: AtomicU64,
+    archive_extractions: AtomicU64,
+    /// Microseconds per `TimingBreakdown` stage, in `stages()` order
+    stage_micros: [AtomicU64; 7],
+    parse_duration: Histogram,
+    render_duration: Histogram,
+    batch_size: Histogram,
+    /// Rolling tick rate from replays with both header times and chunks
+    tick_rate: Mutex<TickRateCalibration>,
+}
+
+static METRICS: Metrics = Metrics::new();
+
+/// The process-wide registry
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            replays_parsed: AtomicU64::new(0),
+            parse_errors: [const { AtomicU64::new(0) }; 6],
+            raw_scan_recoveries: AtomicU64::new(0),
+            archive_extractions: AtomicU64::new(0),
+            stage_micros: [const { AtomicU64::new(0) }; 7],
+            parse_duration: Histogram::new(DURATION_BUCKETS),
+            render_duration: Histogram::new(DURATION_BUCKETS),
+            batch_size: Histogram::new(BATCH_SIZE_BUCKETS),
+            tick_rate: Mutex::new(TickRateCalibration::new()),
+        }
+    }
+
+    /// Record one parse attempt: its outcome, duration and raw-scan recoveries
+    pub fn record_parse(&self, error: Option<&ReplayError>, elapsed: Duration, recoveries: u32) {
+        match error {
+            None => self.replays_parsed.fetch_add(1, Ordering::Relaxed),
+            Some(e) => self.parse_errors[error_index(e)].fetch_add(1, Ordering::Relaxed),
+        };
+        self.raw_scan_recoveries
+            .fetch_add(u64::from(recoveries), Ordering::Relaxed);
+        self.parse_duration.observe(elapsed.as_secs_f64());
+    }
+
+    /// Record one image render (successful or not)
+    pub fn record_render(&self, elapsed: Duration) {
+        self.render_duration.observe(elapsed.as_secs_f64());
+    }
+
+    /// Add pipeline stage timings to the per-stage totals
+    pub fn record_stages(&self, timings: &TimingBreakdown) {
+        for (total, (_, elapsed)) in self.stage_micros.iter().zip(timings.stages()) {
+            total.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Record one archive extraction
+    pub fn record_archive_extraction(&self) {
+        self.archive_extractions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how many replays one batch rendered
+    pub fn record_batch_size(&self, size: usize) {
+        self.batch_size.observe(size as f64);
+    }
+
+    /// Add one replay's implied tick rate to the calibration
+    pub fn record_tick_rate(&self, rate: f32) {
+        self.tick_rate_calibration(|c| c.record(rate));
+    }
+
+    /// Tick rate to estimate durations with (nominal until calibrated)
+    pub fn calibrated_tick_rate(&self) -> f32 {
+        self.tick_rate_calibration(|c| c.rate())
+    }
+
+    /// On poison: recover (a stale average is harmless)
+    fn tick_rate_calibration<T>(&self, f: impl FnOnce(&mut TickRateCalibration) -> T) -> T {
+        let mut calibration = self.tick_rate.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut calibration)
+    }
+
+    /// All metrics in Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: &AtomicU64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        };
+        counter(
+            &mut out,
+            "replays_parsed_total",
+            "Replays parsed successfully",
+            &self.replays_parsed,
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP replay_parse_errors_total Parse failures by error"
+        );
+        let _ = writeln!(out, "# TYPE replay_parse_errors_total counter");
+        for (label, count) in ERROR_LABELS.iter().zip(&self.parse_errors) {
+            let _ = writeln!(
+                out,
+                "replay_parse_errors_total{{error=\"{}\"}} {}",
+                label,
+                count.load(Ordering::Relaxed)
+            );
+        }
+
+        counter(
+            &mut out,
+            "raw_scan_recoveries_total",
+            "End-of-game events found only by the raw byte scan",
+            &self.raw_scan_recoveries,
+        );
+        counter(
+            &mut out,
+            "archive_extractions_total",
+            "ZIP/RAR archives extracted",
+            &self.archive_extractions,
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP replay_stage_seconds_total Time spent per pipeline stage"
+        );
+        let _ = writeln!(out, "# TYPE replay_stage_seconds_total counter");
+        let stages = TimingBreakdown::default().stages();
+        for ((label, _), micros) in stages.iter().zip(&self.stage_micros) {
+            let seconds = micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+            let _ = writeln!(
+                out,
+                "replay_stage_seconds_total{{stage=\"{}\"}} {}",
+                label, seconds
+            );
+        }
+
+        self.parse_duration.write_prometheus(
+            &mut out,
+            "replay_parse_duration_seconds",
+            "Time spent parsing one replay",
+        );
+        self.render_duration.write_prometheus(
+            &mut out,
+            "replay_render_duration_seconds",
+            "Time spent rendering one replay image",
+        );
+        self.batch_size.write_prometheus(
+            &mut out,
+            "replay_batch_size",
+            "Replays rendered per batch message",
+        );
+
+        let (rate, samples) = self.tick_rate_calibration(|c| (c.rate(), c.samples()));
+        let _ = writeln!(
+            out,
+            "# HELP replay_tick_rate Ticks per second used for estimated durations"
+        );
+        let _ = writeln!(out, "# TYPE replay_tick_rate gauge");
+        let _ = writeln!(out, "replay_tick_rate {}", rate);
+        let _ = writeln!(
+            out,
+            "# HELP replay_tick_rate_samples Replays the tick rate was calibrated from"
+        );
+        let _ = writeln!(out, "# TYPE replay_tick_rate_samples gauge");
+        let _ = writeln!(out, "replay_tick_rate_samples {}", samples);
+        out
+    }
+}
+
+/// Response for the health check server: metrics on `GET /metrics`,
+/// readiness on `GET /ready` (503 until `ready`, with `ready_text` as the
+/// body), "OK" (liveness) otherwise
+pub fn http_response(request: &[u8], ready: bool, ready_text: &str) -> String {
+    let (status, content_type, body) = if request.starts_with(b"GET /metrics") {
+        (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            global().render_prometheus(),
+        )
+    } else if request.starts_with(b"GET /ready") {
+        let status = if ready {
+            "200 OK"
+        } else {
+            "503 Service Unavailable"
+        };
+        (status, "text/plain", ready_text.to_string())
+    } else {
+        ("200 OK", "text/plain", "OK".to_string())
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[1.0, 2.0]);
+        for value in [0.5, 1.5, 1.5, 9.0] {
+            histogram.observe(value);
+        }
+        let mut out = String::new();
+        histogram.write_prometheus(&mut out, "x", "help");
+        assert!(out.contains("x_bucket{le=\"1\"} 1\n"));
+        assert!(out.contains("x_bucket{le=\"2\"} 3\n"));
+        assert!(out.contains("x_bucket{le=\"+Inf\"} 4\n"));
+        assert!(out.contains("x_sum 12.5\n"));
+        assert!(out.contains("x_count 4\n"));
+    }
+
+    #[test]
+    fn errors_are_counted_by_variant() {
+        let metrics = Metrics::new();
+        metrics.record_parse(Some(&ReplayError::NoPlayers), Duration::ZERO, 0);
+        metrics.record_parse(None, Duration::from_millis(3), 2);
+        let text = metrics.render_prometheus();
+        assert!(text.contains("replay_parse_errors_total{error=\"no_players\"} 1\n"));
+        assert!(text.contains("replay_parse_errors_total{error=\"parse_error\"} 0\n"));
+        assert!(text.contains("replays_parsed_total 1\n"));
+        assert!(text.contains("raw_scan_recoveries_total 2\n"));
+        assert!(text.contains("replay_parse_duration_seconds_count 2\n"));
+    }
+
+    #[test]
+    fn stage_timings_are_summed_per_stage() {
+        let metrics = Metrics::new();
+        let timings = TimingBreakdown {
+            header: Duration::from_millis(2),
+            encoding: Duration::from_millis(500),
+            ..Default::default()
+        };
+        metrics.record_stages(&timings);
+        metrics.record_stages(&timings);
+        let text = metrics.render_prometheus();
+        assert!(text.contains("replay_stage_seconds_total{stage=\"header\"} 0.004\n"));
+        assert!(text.contains("replay_stage_seconds_total{stage=\"encoding\"} 1\n"));
+        assert!(text.contains("replay_stage_seconds_total{stage=\"raw_scan\"} 0\n"));
+    }
+
+    #[test]
+    fn tick_rate_is_calibrated_from_recorded_rates() {
+        let metrics = Metrics::new();
+        assert_eq!(metrics.calibrated_tick_rate(), 5.0);
+        for _ in 0..20 {
+            metrics.record_tick_rate(5.2);
+        }
+        assert!((metrics.calibrated_tick_rate() - 5.2).abs() < 1e-4);
+        let text = metrics.render_prometheus();
+        assert!(text.contains("replay_tick_rate_samples 20\n"));
+    }
+
+    #[test]
+    fn other_paths_get_the_health_check() {
+        // Liveness answers whether or not the gateway is connected
+        for ready in [false, true] {
+            let response = http_response(b"GET / HTTP/1.1\r\n\r\n", ready, "READY");
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.ends_with("\r\n\r\nOK"));
+        }
+    }
+
+    #[test]
+    fn ready_is_unavailable_until_connected() {
+        let request = b"GET /ready HTTP/1.1\r\n\r\n";
+        let waiting = http_response(request, false, "NOT READY");
+        assert!(waiting.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(waiting.ends_with("NOT READY"));
+        let reconnecting = http_response(request, false, "RECONNECTING since unix 1700000000");
+        assert!(reconnecting.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(reconnecting.ends_with("\r\n\r\nRECONNECTING since unix 1700000000"));
+        let ready = http_response(request, true, "READY");
+        assert!(ready.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(ready.ends_with("\r\n\r\nREADY"));
+    }
+}
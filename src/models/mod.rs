@@ -1,6 +0,0 @@
-mod replay;
-
-pub use replay::{
-    Faction, MapPosition, PLAYER_COLORS, Player, PlayerBuilder, ReplayError, ReplayInfo, Spectator,
-    Winner,
-};
@@ -1,4 +0,0 @@
-mod prng;
-mod replay;
-
-pub use replay::parse_replay;
@@ -0,0 +1,183 @@
+use crate::models::{ReplayInfo, Side};
+use image::{Rgb, RgbImage};
+
+/// Most bars drawn in the strip; longer games are downsampled to this
+const MAX_STRIP_BUCKETS: usize = 120;
+
+/// Strip height in pixels (half above the axis for left, half below for right)
+const STRIP_HEIGHT: i32 = 60;
+
+/// Gap between the strip and the image's left, right and bottom edges
+const STRIP_MARGIN: i32 = 12;
+
+/// Height the strip takes at the bottom of an image, margin included
+pub(super) const STRIP_SPACE: i32 = STRIP_HEIGHT + STRIP_MARGIN;
+
+/// Strip background (semi-transparent black)
+const STRIP_BACKGROUND: [u8; 4] = [0, 0, 0, 150];
+
+const AXIS_COLOR: Rgb<u8> = Rgb([160, 160, 160]);
+
+/// Fallback bar color for a side with no colored player
+const FALLBACK_COLOR: [u8; 3] = [200, 200, 200];
+
+/// Merge adjacent buckets so at most `max` remain, summing left/right counts.
+/// Each merged bucket keeps the start tick of its first source bucket.
+pub(super) fn downsample_buckets(buckets: &[(u32, u32, u32)], max: usize) -> Vec<(u32, u32, u32)> {
+    if buckets.len() <= max || max == 0 {
+        return buckets.to_vec();
+    }
+    let group = buckets.len().div_ceil(max);
+    buckets
+        .chunks(group)
+        .map(|chunk| {
+            let (left, right) = chunk
+                .iter()
+                .fold((0, 0), |(l, r), &(_, left, right)| (l + left, r + right));
+            (chunk[0].0, left, right)
+        })
+        .collect()
+}
+
+/// Top y of the strip for an image of the given height
+pub(super) fn strip_top(img_height: i32) -> i32 {
+    img_height - STRIP_SPACE
+}
+
+/// Color of the first player on a side
+fn side_color(replay: &ReplayInfo, side: Side) -> [u8; 3] {
+    replay
+        .players
+        .iter()
+        .find(|p| p.spot.map(|s| s.side()) == Some(side))
+        .map(|p| p.display_color())
+        .unwrap_or(FALLBACK_COLOR)
+}
+
+/// Draw the command activity strip along the bottom of the image:
+/// left-team bars above the axis, right-team bars below.
+pub(super) fn draw_activity_strip(img: &mut RgbImage, replay: &ReplayInfo) {
+    if replay.activity_buckets.is_empty() {
+        return;
+    }
+    let buckets = downsample_buckets(&replay.activity_buckets, MAX_STRIP_BUCKETS);
+    let peak = buckets
+        .iter()
+        .map(|&(_, left, right)| left.max(right))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let (width, height) = (img.width() as i32, img.height() as i32);
+    let strip_w = width - STRIP_MARGIN * 2;
+    if strip_w <= 0 || height <= STRIP_HEIGHT + STRIP_MARGIN {
+        return;
+    }
+    let top = strip_top(height);
+    let axis_y = top + STRIP_HEIGHT / 2;
+    let half_h = STRIP_HEIGHT / 2 - 1;
+
+    super::map::draw_rect_alpha(
+        img,
+        STRIP_MARGIN,
+        top,
+        strip_w,
+        STRIP_HEIGHT,
+        STRIP_BACKGROUND,
+    );
+
+    let left_color = Rgb(side_color(replay, Side::Left));
+    let right_color = Rgb(side_color(replay, Side::Right));
+    let n = buckets.len() as i32;
+
+    for (i, &(_, left, right)) in buckets.iter().enumerate() {
+        let i = i as i32;
+        let x0 = STRIP_MARGIN + i * strip_w / n;
+        let x1 = (STRIP_MARGIN + (i + 1) * strip_w / n - 1).max(x0);
+        let left_h = (left as i64 * half_h as i64 / peak as i64) as i32;
+        let right_h = (right as i64 * half_h as i64 / peak as i64) as i32;
+
+        fill_rect(img, x0, axis_y - left_h, x1, axis_y - 1, left_color);
+        fill_rect(img, x0, axis_y + 1, x1, axis_y + right_h, right_color);
+    }
+
+    fill_rect(
+        img,
+        STRIP_MARGIN,
+        axis_y,
+        STRIP_MARGIN + strip_w - 1,
+        axis_y,
+        AXIS_COLOR,
+    );
+}
+
+/// Fill the inclusive rectangle `(x0, y0)..=(x1, y1)`, clipped to the image
+pub(super) fn fill_rect(img: &mut RgbImage, x0: i32, y0: i32, x1: i32, y1: i32, color: Rgb<u8>) {
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    for y in y0.max(0)..=y1.min(h - 1) {
+        for x in x0.max(0)..=x1.min(w - 1) {
+            img.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, MapSpot, PLAYER_COLORS, PlayerBuilder};
+
+    #[test]
+    fn short_games_are_not_downsampled() {
+        let buckets = vec![(0, 1, 2), (150, 3, 4)];
+        assert_eq!(downsample_buckets(&buckets, 120), buckets);
+    }
+
+    #[test]
+    fn long_games_are_merged_into_at_most_max_buckets() {
+        let buckets: Vec<(u32, u32, u32)> = (0..250).map(|i| (i * 150, 1, 2)).collect();
+        let merged = downsample_buckets(&buckets, 120);
+        assert_eq!(merged.len(), 84); // groups of 3
+        assert_eq!(merged[0], (0, 3, 6));
+        assert_eq!(merged[1], (450, 3, 6));
+        // Last group holds the single leftover bucket
+        assert_eq!(merged[83], (249 * 150, 1, 2));
+        let total_left: u32 = merged.iter().map(|b| b.1).sum();
+        assert_eq!(total_left, 250);
+    }
+
+    #[test]
+    fn strip_draws_bars_at_the_bottom() {
+        let mut alice = PlayerBuilder::test_player("Alice", 1, 0, Faction::Men);
+        alice.spot = Some(MapSpot::TopLeft);
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![alice])
+            .with_activity_buckets(vec![(0, 10, 0), (150, 5, 8), (300, 0, 2)]);
+
+        let blank = RgbImage::from_pixel(300, 300, Rgb([0, 100, 0]));
+        let mut img = blank.clone();
+        draw_activity_strip(&mut img, &replay);
+
+        let top = strip_top(300) as u32;
+        let changed_in_strip = (top..300 - STRIP_MARGIN as u32)
+            .flat_map(|y| (0..300).map(move |x| (x, y)))
+            .filter(|&(x, y)| img.get_pixel(x, y) != blank.get_pixel(x, y))
+            .count();
+        assert!(changed_in_strip > 0);
+        assert!(
+            (0..top)
+                .flat_map(|y| (0..300).map(move |x| (x, y)))
+                .all(|(x, y)| img.get_pixel(x, y) == blank.get_pixel(x, y))
+        );
+        // Peak left bucket reaches near the top of the strip in Alice's color
+        let first_bar_x = STRIP_MARGIN as u32 + 1;
+        assert_eq!(*img.get_pixel(first_bar_x, top + 2), Rgb(PLAYER_COLORS[0]));
+    }
+
+    #[test]
+    fn empty_activity_draws_nothing() {
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![]);
+        let blank = RgbImage::from_pixel(100, 100, Rgb([0, 100, 0]));
+        let mut img = blank.clone();
+        draw_activity_strip(&mut img, &replay);
+        assert_eq!(img, blank);
+    }
+}
@@ -0,0 +1,135 @@
+//! Faction icons drawn left of player names in compact labels, loaded from
+//! `assets/icons/<faction>.png` at startup
+
+use crate::models::Faction;
+use image::RgbaImage;
+use image::imageops::FilterType;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Width and height icons are drawn at, in pixels
+pub const FACTION_ICON_SIZE: u32 = 16;
+
+/// Factions an icon is looked for; Random and unknown factions are always
+/// written out
+const ICON_FACTIONS: [Faction; 7] = [
+    Faction::Men,
+    Faction::Elves,
+    Faction::Dwarves,
+    Faction::Isengard,
+    Faction::Mordor,
+    Faction::Goblins,
+    Faction::Angmar,
+];
+
+/// Faction icons by faction; a faction without one is written out instead
+#[derive(Debug, Clone, Default)]
+pub struct FactionIcons {
+    icons: HashMap<Faction, Arc<RgbaImage>>,
+}
+
+impl FactionIcons {
+    /// The icon drawn for `faction`, if it has one
+    pub fn get(&self, faction: Faction) -> Option<&Arc<RgbaImage>> {
+        self.icons.get(&faction)
+    }
+
+    /// How many factions have an icon
+    pub fn len(&self) -> usize {
+        self.icons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.icons.is_empty()
+    }
+}
+
+/// Load the faction icons under `<assets_path>/icons` (call once at startup),
+/// scaled to [`FACTION_ICON_SIZE`]. Factions without an icon file are left
+/// out, as are unreadable files (with a warning).
+pub fn load_faction_icons(assets_path: &Path) -> FactionIcons {
+    let icons_dir = assets_path.join("icons");
+    let icons = ICON_FACTIONS
+        .into_iter()
+        .filter_map(|faction| {
+            let path = icons_dir.join(format!("{}.png", faction.to_string().to_lowercase()));
+            if !path.exists() {
+                return None;
+            }
+            match image::open(&path) {
+                Ok(icon) => Some((faction, Arc::new(icon_sized(icon.to_rgba8())))),
+                Err(e) => {
+                    tracing::warn!("Skipping faction icon {:?}: {}", path, e);
+                    None
+                }
+            }
+        })
+        .collect();
+    FactionIcons { icons }
+}
+
+fn icon_sized(icon: RgbaImage) -> RgbaImage {
+    if icon.dimensions() == (FACTION_ICON_SIZE, FACTION_ICON_SIZE) {
+        return icon;
+    }
+    image::imageops::resize(
+        &icon,
+        FACTION_ICON_SIZE,
+        FACTION_ICON_SIZE,
+        FilterType::Triangle,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn every_faction_finds_its_icon_or_falls_back() {
+        let assets = tempfile::tempdir().unwrap();
+        let icons_dir = assets.path().join("icons");
+        std::fs::create_dir(&icons_dir).unwrap();
+        let icon = |size: u32| RgbaImage::from_pixel(size, size, Rgba([200, 40, 30, 255]));
+        // A larger icon is scaled down
+        icon(32).save(icons_dir.join("men.png")).unwrap();
+        for name in ["elves", "dwarves", "isengard", "mordor"] {
+            icon(16)
+                .save(icons_dir.join(format!("{}.png", name)))
+                .unwrap();
+        }
+        // Goblins has no icon; a broken file is skipped
+        std::fs::write(icons_dir.join("angmar.png"), b"not a png").unwrap();
+        // Random is written out even with an icon file
+        icon(16).save(icons_dir.join("random.png")).unwrap();
+
+        let icons = load_faction_icons(assets.path());
+        assert_eq!(icons.len(), 5);
+        for faction in [
+            Faction::Men,
+            Faction::Elves,
+            Faction::Dwarves,
+            Faction::Isengard,
+            Faction::Mordor,
+        ] {
+            assert_eq!(
+                icons.get(faction).map(|icon| icon.dimensions()),
+                Some((FACTION_ICON_SIZE, FACTION_ICON_SIZE)),
+                "{}",
+                faction
+            );
+        }
+        for faction in [
+            Faction::Goblins,
+            Faction::Angmar,
+            Faction::Random,
+            Faction::Unknown(7),
+        ] {
+            assert!(icons.get(faction).is_none(), "{}", faction);
+        }
+
+        // No icons directory: every faction is written out
+        assert!(load_faction_icons(&assets.path().join("missing")).is_empty());
+    }
+}
@@ -0,0 +1,1671 @@
+//! Where everything drawn over the map goes: player labels, the center info
+//! and the observer lines, as boxes and text runs. Computing this apart from
+//! drawing lets the JPEG and SVG outputs share it.
+
+use super::activity::{STRIP_SPACE, strip_top};
+use super::faction_icons::{FACTION_ICON_SIZE, FactionIcons};
+use super::layout::{MapLayout, SpotLayout, label_block_top};
+use super::lobby::LOBBY_PANEL_WIDTH;
+use super::map::{RenderOptions, RevealStage, fit_text, measure_text_width, replay_stem};
+use super::palette::DisplayPalette;
+use super::winner::{WinnerIcon, icon_size, sprite, winner_line, winner_source_line};
+use crate::models::{Alignment, Faction, Player, ReplayInfo};
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use image::{Rgb, RgbaImage};
+use std::sync::Arc;
+
+/// Share of the narrowest left-right spot gap a player name may take; half
+/// of a centered name reaches inward, leaving two thirds for the center info
+const NAME_GAP_FRACTION: f32 = 1.0 / 3.0;
+
+/// Share of the image width the filename in the center info may take
+const FILENAME_WIDTH_FRACTION: f32 = 0.4;
+
+/// Spellbook powers listed under a player's faction
+const LABEL_POWERS: usize = 2;
+
+/// Vertical gap between label blocks of players sharing a spot
+const LABEL_STACK_GAP: i32 = 4;
+
+/// Backdrop behind each row of a player label
+const LABEL_BACKDROP: [u8; 4] = [0, 0, 0, 180];
+
+/// Label backdrops of good and evil factions: the black backdrop tinted
+/// dark blue or dark green, just enough to tell the sides apart
+const GOOD_LABEL_BACKDROP: [u8; 4] = [10, 22, 62, 180];
+const EVIL_LABEL_BACKDROP: [u8; 4] = [10, 48, 14, 180];
+
+/// Height of the faction-colored line under the faction name
+const FACTION_UNDERLINE_H: i32 = 2;
+
+/// Tag after the faction of a player who never acted, in gray
+const AFK_TAG: &str = "(AFK)";
+const AFK_TAG_COLOR: Rgb<u8> = Rgb([150, 150, 150]);
+
+/// Space between the faction and the AFK tag
+const AFK_TAG_GAP: i32 = 6;
+
+/// Between a compact label's player name and faction, when it has no icon
+const COMPACT_SEPARATOR: &str = "·";
+
+/// Gap between a faction icon and the name right of it
+const FACTION_ICON_GAP: i32 = 4;
+
+/// Center info note on a replay cut off in transfer, in orange
+const TRUNCATED_TEXT: Rgb<u8> = Rgb([230, 150, 70]);
+
+/// Backdrop behind the center info and the observer lines
+const INFO_BACKDROP: [u8; 4] = [0, 0, 0, 160];
+
+/// Backdrop behind an empty spot's marker, half transparent
+const EMPTY_SPOT_BACKDROP: [u8; 4] = [0, 0, 0, 128];
+
+/// Marker text of an empty spot, dim next to the player labels
+const EMPTY_SPOT_TEXT: Rgb<u8> = Rgb([150, 150, 150]);
+
+/// Space a cropped 1v1 frame keeps around the labels and the center info
+const CROP_MARGIN: i32 = 24;
+
+/// Height of an observer line, backdrop and gap included
+const SPECTATOR_ROW: i32 = 28;
+
+/// Axis-aligned box in image pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Bounds {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Bounds {
+    pub fn contains(&self, other: &Bounds) -> bool {
+        self.x <= other.x
+            && self.y <= other.y
+            && other.x + other.w <= self.x + self.w
+            && other.y + other.h <= self.y + self.h
+    }
+
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+
+    /// Smallest box holding both
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        Bounds {
+            x,
+            y,
+            w: (self.x + self.w).max(other.x + other.w) - x,
+            h: (self.y + self.h).max(other.y + other.h) - y,
+        }
+    }
+
+    /// Grown by `dx` on the left and right and `dy` on the top and bottom
+    fn inflated(&self, dx: i32, dy: i32) -> Bounds {
+        Bounds {
+            x: self.x - dx,
+            y: self.y - dy,
+            w: self.w + dx * 2,
+            h: self.h + dy * 2,
+        }
+    }
+
+    /// Moved (and if need be shrunk) to lie within an image of `size` pixels
+    fn clamped(&self, (width, height): (u32, u32)) -> Bounds {
+        let (w, h) = (self.w.min(width as i32), self.h.min(height as i32));
+        Bounds {
+            x: self.x.clamp(0, width as i32 - w),
+            y: self.y.clamp(0, height as i32 - h),
+            w,
+            h,
+        }
+    }
+}
+
+/// Part of the map a cropped 1v1 frame keeps, and how far the center info
+/// moves to sit in its middle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Crop {
+    pub bounds: Bounds,
+    info_shift: (i32, i32),
+}
+
+/// One thing to draw, in drawing order
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum LayoutItem {
+    /// Filled box, alpha-blended over what is below
+    Rect { bounds: Bounds, color: [u8; 4] },
+    /// Text run with its top-left corner at `x, y`; `width` is its measured advance
+    Text {
+        text: String,
+        x: i32,
+        y: i32,
+        width: i32,
+        scale: PxScale,
+        color: Rgb<u8>,
+    },
+    /// Winner icon: left edge at `x`, bottom edge on `baseline`, about `height` tall
+    Icon {
+        icon: WinnerIcon,
+        x: i32,
+        baseline: i32,
+        height: i32,
+        color: Rgb<u8>,
+    },
+    /// Faction icon with its top-left corner at `x, y`, drawn at its own size
+    FactionIcon {
+        image: Arc<RgbaImage>,
+        x: i32,
+        y: i32,
+    },
+}
+
+impl LayoutItem {
+    fn rect(x: i32, y: i32, w: i32, h: i32, color: [u8; 4]) -> Self {
+        LayoutItem::Rect {
+            bounds: Bounds { x, y, w, h },
+            color,
+        }
+    }
+
+    fn text(text: &str, font: &FontArc, x: i32, y: i32, scale: PxScale, color: Rgb<u8>) -> Self {
+        LayoutItem::Text {
+            text: text.to_string(),
+            x,
+            y,
+            width: measure_text_width(text, font, scale),
+            scale,
+            color,
+        }
+    }
+
+    /// The same item moved by `dx, dy`
+    fn shifted(self, dx: i32, dy: i32) -> Self {
+        match self {
+            LayoutItem::Rect { bounds, color } => LayoutItem::Rect {
+                bounds: Bounds {
+                    x: bounds.x + dx,
+                    y: bounds.y + dy,
+                    ..bounds
+                },
+                color,
+            },
+            LayoutItem::Text {
+                text,
+                x,
+                y,
+                width,
+                scale,
+                color,
+            } => LayoutItem::Text {
+                text,
+                x: x + dx,
+                y: y + dy,
+                width,
+                scale,
+                color,
+            },
+            LayoutItem::Icon {
+                icon,
+                x,
+                baseline,
+                height,
+                color,
+            } => LayoutItem::Icon {
+                icon,
+                x: x + dx,
+                baseline: baseline + dy,
+                height,
+                color,
+            },
+            LayoutItem::FactionIcon { image, x, y } => LayoutItem::FactionIcon {
+                image,
+                x: x + dx,
+                y: y + dy,
+            },
+        }
+    }
+
+    /// Area the item covers (a text run: its advance by its pixel height)
+    pub fn bounds(&self) -> Bounds {
+        match self {
+            LayoutItem::Rect { bounds, .. } => *bounds,
+            LayoutItem::Text {
+                x, y, width, scale, ..
+            } => Bounds {
+                x: *x,
+                y: *y,
+                w: *width,
+                h: scale.y as i32,
+            },
+            LayoutItem::Icon {
+                x,
+                baseline,
+                height,
+                ..
+            } => {
+                let size = icon_size(*height);
+                Bounds {
+                    x: *x,
+                    y: baseline - size,
+                    w: size,
+                    h: size,
+                }
+            }
+            LayoutItem::FactionIcon { image, x, y } => Bounds {
+                x: *x,
+                y: *y,
+                w: image.width() as i32,
+                h: image.height() as i32,
+            },
+        }
+    }
+}
+
+/// Where a player's label block goes: the spot layout plus its slot in the stack
+pub(super) struct LabelPlacement<'a> {
+    layout: &'a SpotLayout,
+    /// Pixel size of the map asset `layout.coords` refers to
+    asset_size: (f32, f32),
+    stack_index: usize,
+    stack_count: usize,
+    /// Leftmost x the label block may reach
+    min_left: i32,
+    /// Widest the name may be, in asset pixels
+    max_name_width: f32,
+    /// Whether the stack at this spot has a powers row (so every label in it
+    /// is the same height)
+    powers_row: bool,
+}
+
+/// Where the labels of a frame go: one per positioned player, and a marker
+/// at each spot none of them was placed at (when those are marked)
+pub(super) struct FramePlacements<'a> {
+    pub players: Vec<(&'a Player, LabelPlacement<'a>)>,
+    pub empty_spots: Vec<&'a SpotLayout>,
+    /// Pixel size of the map asset the spot layouts refer to
+    asset_size: (f32, f32),
+    /// Leftmost x a marker may reach
+    min_left: i32,
+    /// Whether a 1v1 frame may be cropped ([`RenderOptions::crop_duels`])
+    crop_duels: bool,
+    crop: Option<Crop>,
+    /// Whether labels are compact ([`RenderOptions::uses_compact_labels`])
+    compact: bool,
+    /// Icons compact labels show factions with; without them, factions are
+    /// written out
+    faction_icons: Option<&'a FactionIcons>,
+}
+
+impl<'a> FramePlacements<'a> {
+    /// Show factions in compact labels with these icons
+    pub fn with_faction_icons(self, faction_icons: &'a FactionIcons) -> Self {
+        Self {
+            faction_icons: Some(faction_icons),
+            ..self
+        }
+    }
+
+    /// Crop a 1v1 frame of `size` pixels to the action when the options ask
+    /// for it and both players have a spot; other games keep the full map
+    pub fn cropped_to_action(
+        mut self,
+        replay: &ReplayInfo,
+        font: &FontArc,
+        size: (u32, u32),
+        filename: &str,
+        palette: DisplayPalette,
+    ) -> Self {
+        if self.crop_duels && replay.players.len() == 2 && self.players.len() == 2 {
+            self.crop = Some(action_crop(replay, font, &self, size, filename, palette));
+        }
+        self
+    }
+
+    /// The part of the map the frame shows, when cropped
+    pub fn crop(&self) -> Option<Bounds> {
+        self.crop.map(|crop| crop.bounds)
+    }
+}
+
+/// Label placement for every positioned player.
+/// Players sharing a spot are stacked in player order; with the lobby panel
+/// shown, labels are kept right of it. Empty spots are the ones no label
+/// went to, so a marker never shares a spot with a player.
+pub(super) fn label_placements<'a>(
+    replay: &'a ReplayInfo,
+    layout: &'a MapLayout,
+    options: RenderOptions,
+) -> FramePlacements<'a> {
+    let min_left = if options.show_lobby_panel {
+        LOBBY_PANEL_WIDTH
+    } else {
+        0
+    };
+    let players = player_placements(replay, layout, min_left);
+    let empty_spots = if options.marks_empty_spots(replay) {
+        layout
+            .spots()
+            .filter(|(spot, _)| players.iter().all(|(p, _)| p.spot != Some(*spot)))
+            .map(|(_, spot_layout)| spot_layout)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    FramePlacements {
+        players,
+        empty_spots,
+        asset_size: layout.asset_size(),
+        min_left,
+        crop_duels: options.crop_duels && !options.show_lobby_panel,
+        crop: None,
+        compact: options.uses_compact_labels(replay),
+        faction_icons: None,
+    }
+}
+
+fn player_placements<'a>(
+    replay: &'a ReplayInfo,
+    layout: &'a MapLayout,
+    min_left: i32,
+) -> Vec<(&'a Player, LabelPlacement<'a>)> {
+    let max_name_width = layout.narrowest_row_gap() * NAME_GAP_FRACTION;
+    replay
+        .players
+        .iter()
+        .enumerate()
+        .filter_map(|(i, player)| {
+            let spot = player.spot?; // Skip players without valid positions
+            let same_spot = |p: &&Player| p.spot == Some(spot);
+            let label = LabelPlacement {
+                layout: layout.spot(spot),
+                asset_size: layout.asset_size(),
+                stack_index: replay.players[..i].iter().filter(same_spot).count(),
+                stack_count: replay.players.iter().filter(same_spot).count(),
+                min_left,
+                max_name_width,
+                powers_row: replay
+                    .players
+                    .iter()
+                    .filter(same_spot)
+                    .any(|p| !p.powers_used.is_empty()),
+            };
+            Some((player, label))
+        })
+        .collect()
+}
+
+/// Whether a frame shows the activity strip: it hints at the outcome, so it
+/// comes with the winner
+pub(super) fn shows_activity(replay: &ReplayInfo, stage: RevealStage) -> bool {
+    stage >= RevealStage::Winner && !replay.activity_buckets.is_empty()
+}
+
+/// Everything a frame of `size` pixels draws over the map, showing as much
+/// as `stage` allows, in drawing order: empty spot markers, player labels,
+/// the center info (filename, date, duration, winner), then the observers.
+/// A cropped frame's items are relative to its crop.
+pub(super) fn layout_frame(
+    replay: &ReplayInfo,
+    font: &FontArc,
+    placements: &FramePlacements<'_>,
+    size: (u32, u32),
+    filename: &str,
+    stage: RevealStage,
+    palette: DisplayPalette,
+) -> Vec<LayoutItem> {
+    let font_large = PxScale::from(24.0);
+    let font_small = PxScale::from(20.0);
+    let label_style = LabelStyle::of(font, placements);
+
+    let mut items = Vec::new();
+    for spot in &placements.empty_spots {
+        let marker = empty_spot_marker(spot, placements, font, size);
+        // A cropped frame keeps only the markers wholly inside it
+        if placements
+            .crop()
+            .is_none_or(|crop| marker.iter().all(|item| crop.contains(&item.bounds())))
+        {
+            items.extend(marker);
+        }
+    }
+    for (player, label) in &placements.players {
+        items.extend(player_label(
+            player,
+            label,
+            &label_style,
+            size,
+            stage,
+            palette,
+        ));
+    }
+    let center = CenterInfo {
+        font,
+        scale: font_large,
+        filename,
+        show_winner: stage >= RevealStage::Winner,
+        palette,
+    };
+    let info = center_info(replay, &center, size);
+    let above_strip = shows_activity(replay, stage);
+    let Some(crop) = placements.crop else {
+        items.extend(info);
+        items.extend(spectator_lines(
+            replay,
+            font,
+            font_small,
+            size.0 as i32,
+            spectator_rows(size.1 as i32, above_strip),
+        ));
+        return items;
+    };
+
+    let (dx, dy) = crop.info_shift;
+    items.extend(info.into_iter().map(|item| item.shifted(dx, dy)));
+    let mut items: Vec<LayoutItem> = items
+        .into_iter()
+        .map(|item| item.shifted(-crop.bounds.x, -crop.bounds.y))
+        .collect();
+    items.extend(spectator_lines(
+        replay,
+        font,
+        font_small,
+        crop.bounds.w,
+        cropped_spectator_rows(crop, above_strip),
+    ));
+    items
+}
+
+/// Where a 1v1 frame is cropped: around both label blocks with the center
+/// info moved between them, or where the info sits on the full map when it
+/// would cover a label there. Laid out as the final frame, so every frame
+/// of a reveal gets the same crop.
+fn action_crop(
+    replay: &ReplayInfo,
+    font: &FontArc,
+    placements: &FramePlacements<'_>,
+    size: (u32, u32),
+    filename: &str,
+    palette: DisplayPalette,
+) -> Crop {
+    let style = LabelStyle::of(font, placements);
+    let union = |items: Vec<LayoutItem>| {
+        items
+            .iter()
+            .map(LayoutItem::bounds)
+            .reduce(|a, b| a.union(&b))
+    };
+    let labels: Vec<Bounds> = placements
+        .players
+        .iter()
+        .filter_map(|(player, label)| {
+            union(player_label(
+                player,
+                label,
+                &style,
+                size,
+                RevealStage::Winner,
+                palette,
+            ))
+        })
+        .collect();
+    let center = CenterInfo {
+        font,
+        scale: PxScale::from(24.0),
+        filename,
+        show_winner: true,
+        palette,
+    };
+    let info = union(center_info(replay, &center, size)).expect("center info has a backdrop");
+
+    let spectators = if replay.spectators.is_empty() {
+        0
+    } else {
+        SPECTATOR_ROW
+    };
+    let strip = if shows_activity(replay, RevealStage::Winner) {
+        STRIP_SPACE
+    } else {
+        0
+    };
+    crop_rect(&labels, info, size, (spectators, spectators + strip))
+}
+
+/// Crop around the `labels` with room for `info` and the `bands` above and
+/// below, clamped to an image of `size` pixels
+fn crop_rect(labels: &[Bounds], info: Bounds, size: (u32, u32), bands: (i32, i32)) -> Crop {
+    let around = labels
+        .iter()
+        .copied()
+        .reduce(|a, b| a.union(&b))
+        .unwrap_or(info);
+    let frame = |content: Bounds| {
+        Bounds {
+            x: content.x - CROP_MARGIN,
+            y: content.y - CROP_MARGIN - bands.0,
+            w: content.w + CROP_MARGIN * 2,
+            h: content.h + CROP_MARGIN * 2 + bands.0 + bands.1,
+        }
+        .clamped(size)
+    };
+
+    // Made wide and tall enough for the info, which then sits in the middle
+    let roomy = around.inflated(
+        ((info.w - around.w).max(0) + 1) / 2,
+        ((info.h - around.h).max(0) + 1) / 2,
+    );
+    let bounds = frame(roomy);
+    let middle_y = bounds.y + bands.0 + (bounds.h - bands.0 - bands.1) / 2;
+    let centered = Bounds {
+        x: bounds.x + (bounds.w - info.w) / 2,
+        y: middle_y - info.h / 2,
+        ..info
+    };
+    if bounds.contains(&centered) && labels.iter().all(|label| !label.overlaps(&centered)) {
+        return Crop {
+            bounds,
+            info_shift: (centered.x - info.x, centered.y - info.y),
+        };
+    }
+    Crop {
+        bounds: frame(around.union(&info)),
+        info_shift: (0, 0),
+    }
+}
+
+/// Font and sizes for a label's rows, and whether it is compact
+struct LabelStyle<'a> {
+    font: &'a FontArc,
+    name: PxScale,
+    faction: PxScale,
+    powers: PxScale,
+    compact: bool,
+    faction_icons: Option<&'a FactionIcons>,
+}
+
+impl<'a> LabelStyle<'a> {
+    /// The labels of a frame placed by `placements`
+    fn of(font: &'a FontArc, placements: &FramePlacements<'a>) -> Self {
+        let (name, faction, powers) = if placements.compact {
+            (18.0, 14.0, 14.0)
+        } else {
+            (24.0, 20.0, 16.0)
+        };
+        Self {
+            font,
+            name: PxScale::from(name),
+            faction: PxScale::from(faction),
+            powers: PxScale::from(powers),
+            compact: placements.compact,
+            faction_icons: placements.faction_icons,
+        }
+    }
+}
+
+/// Player label at their spot (center-aligned horizontally), moved right
+/// when the full label would cross `min_left`. A compact label puts the
+/// faction on the name row: as its icon left of the name, or written out
+/// after it when there is no icon.
+/// Before `RevealStage::Players` only a "?" marks the spot.
+fn player_label(
+    player: &Player,
+    label: &LabelPlacement<'_>,
+    style: &LabelStyle<'_>,
+    (width, height): (u32, u32),
+    stage: RevealStage,
+    palette: DisplayPalette,
+) -> Vec<LayoutItem> {
+    let (font, font_large, font_small) = (style.font, style.name, style.faction);
+    let scale_x = width as f32 / label.asset_size.0;
+    let scale_y = height as f32 / label.asset_size.1;
+
+    let pad = 3;
+    let name_h = font_large.y as i32;
+    let faction_h = font_small.y as i32;
+    let gap = 2; // gap between name and faction rows
+    let powers_h = style.powers.y as i32;
+    let mut total_h = if style.compact {
+        name_h
+    } else {
+        name_h + gap + faction_h
+    };
+    if label.powers_row {
+        total_h += gap + powers_h;
+    }
+
+    // Keep the name clear of the other side's labels and the center info
+    let full_name = fit_text(
+        &player.name,
+        font,
+        font_large,
+        (label.max_name_width * scale_x) as i32,
+    );
+    let faction = player.display_faction();
+    let faction_text = faction.to_string();
+    let faction_icon = style
+        .faction_icons
+        .filter(|_| style.compact)
+        .and_then(|icons| icons.get(faction));
+    let full_name = if style.compact && faction_icon.is_none() {
+        format!("{} {} {}", full_name, COMPACT_SEPARATOR, faction_text)
+    } else {
+        full_name
+    };
+    let badge = palette.badge(player);
+    let badge_w = badge
+        .as_ref()
+        .map_or(0, |b| measure_text_width(b, font, font_small));
+    let faction_w = measure_text_width(&faction_text, font, font_small);
+    // Players who never acted are tagged after their faction
+    let afk_w = if player.afk {
+        AFK_TAG_GAP + measure_text_width(AFK_TAG, font, font_small)
+    } else {
+        0
+    };
+    let faction_row_w = faction_w + afk_w;
+    let icon_w = faction_icon.map_or(0, |icon| icon.width() as i32 + FACTION_ICON_GAP);
+
+    // Anchor point in rendered image pixels; the revealed label decides the
+    // shift so every reveal frame lines up
+    let img_pos = label.layout.coords;
+    let mut center_x = (img_pos.0 * scale_x) as i32 + label.layout.label_offset.0;
+    let center_y = (img_pos.1 * scale_y) as i32;
+    let full_name_w = measure_text_width(&full_name, font, font_large);
+    let badge_extent = if badge.is_some() {
+        gap + pad * 3 + badge_w
+    } else {
+        0
+    };
+    let block_left = if style.compact {
+        center_x - (icon_w + full_name_w + afk_w) / 2 - pad - badge_extent
+    } else {
+        (center_x - full_name_w / 2 - pad - badge_extent).min(center_x - faction_row_w / 2 - pad)
+    };
+    center_x += (label.min_left - block_left).max(0);
+
+    let text_color = palette.player_color(player);
+
+    // Hidden players get the plain backdrop, which gives nothing away
+    let revealed = stage >= RevealStage::Players;
+    let (name, backdrop) = if revealed {
+        (full_name, label_backdrop(faction))
+    } else {
+        ("?".to_string(), LABEL_BACKDROP)
+    };
+
+    // Place the label block per the spot's anchor, offset and stack slot
+    let block_top = label_block_top(
+        center_y,
+        label.layout,
+        total_h,
+        LABEL_STACK_GAP,
+        label.stack_index,
+        label.stack_count,
+    );
+
+    // --- Name (top row, centered horizontally; compact: with the faction) ---
+    let name_w = measure_text_width(&name, font, font_large);
+    let (row_w, icon_w) = if style.compact && revealed {
+        (icon_w + name_w + afk_w, icon_w)
+    } else {
+        (name_w, 0)
+    };
+    let row_x = center_x - row_w / 2;
+    let name_x = row_x + icon_w;
+    let name_y = block_top;
+    let mut items = vec![
+        LayoutItem::rect(
+            row_x - pad,
+            name_y - 2,
+            row_w + pad * 2,
+            name_h + 4,
+            backdrop,
+        ),
+        LayoutItem::text(&name, font, name_x, name_y, font_large, text_color),
+    ];
+
+    if !revealed {
+        return items;
+    }
+
+    if let Some(icon) = faction_icon {
+        items.push(LayoutItem::FactionIcon {
+            image: icon.clone(),
+            x: row_x,
+            y: name_y + (name_h - FACTION_ICON_SIZE as i32) / 2,
+        });
+    }
+
+    // --- Badge (left of the name row, player color with dark text) ---
+    if let Some(badge) = badge {
+        let badge_x = row_x - pad - gap - pad * 2 - badge_w;
+        let [r, g, b] = text_color.0;
+        items.push(LayoutItem::rect(
+            badge_x - pad,
+            name_y,
+            badge_w + pad * 2,
+            name_h,
+            [r, g, b, 255],
+        ));
+        items.push(LayoutItem::text(
+            &badge,
+            font,
+            badge_x,
+            name_y + 2,
+            font_small,
+            Rgb([0, 0, 0]),
+        ));
+    }
+
+    let rows_bottom = if style.compact {
+        if player.afk {
+            items.push(LayoutItem::text(
+                AFK_TAG,
+                font,
+                name_x + name_w + AFK_TAG_GAP,
+                name_y + name_h - faction_h,
+                font_small,
+                AFK_TAG_COLOR,
+            ));
+        }
+        name_y + name_h
+    } else {
+        // --- Faction (bottom row, centered horizontally) ---
+        let faction_x = center_x - faction_row_w / 2;
+        let faction_y = block_top + name_h + gap;
+        items.push(LayoutItem::rect(
+            faction_x - pad,
+            faction_y - 2,
+            faction_row_w + pad * 2,
+            faction_h + 4,
+            backdrop,
+        ));
+        if player.afk {
+            items.push(LayoutItem::text(
+                AFK_TAG,
+                font,
+                faction_x + faction_w + AFK_TAG_GAP,
+                faction_y,
+                font_small,
+                AFK_TAG_COLOR,
+            ));
+        }
+        items.push(LayoutItem::text(
+            &faction_text,
+            font,
+            faction_x,
+            faction_y,
+            font_small,
+            text_color,
+        ));
+        if let Some(Rgb([r, g, b])) = faction_color(faction) {
+            items.push(LayoutItem::rect(
+                faction_x,
+                faction_y + faction_h,
+                faction_w,
+                FACTION_UNDERLINE_H,
+                [r, g, b, 255],
+            ));
+        }
+        faction_y + faction_h
+    };
+
+    // --- Last spellbook powers bought (last row, when any) ---
+    let Some(powers_text) = player.powers_text(LABEL_POWERS) else {
+        return items;
+    };
+    let powers_w = measure_text_width(&powers_text, font, style.powers);
+    let powers_x = center_x - powers_w / 2;
+    let powers_y = rows_bottom + gap;
+    items.push(LayoutItem::rect(
+        powers_x - pad,
+        powers_y - 2,
+        powers_w + pad * 2,
+        powers_h + 4,
+        backdrop,
+    ));
+    items.push(LayoutItem::text(
+        &powers_text,
+        font,
+        powers_x,
+        powers_y,
+        style.powers,
+        Rgb([220, 220, 220]),
+    ));
+    items
+}
+
+/// Backdrop of a player's label rows, tinted by the faction's side
+fn label_backdrop(faction: Faction) -> [u8; 4] {
+    match faction.alignment() {
+        Alignment::Good => GOOD_LABEL_BACKDROP,
+        Alignment::Evil => EVIL_LABEL_BACKDROP,
+        Alignment::Unknown => LABEL_BACKDROP,
+    }
+}
+
+/// Color of a faction's underline; none for Random and unknown factions
+fn faction_color(faction: Faction) -> Option<Rgb<u8>> {
+    let rgb = match faction {
+        Faction::Men => [70, 130, 220],
+        Faction::Elves => [60, 180, 90],
+        Faction::Dwarves => [205, 150, 60],
+        Faction::Isengard => [200, 200, 200],
+        Faction::Mordor => [210, 40, 30],
+        Faction::Goblins => [230, 120, 30],
+        Faction::Angmar => [130, 180, 240],
+        Faction::Random | Faction::Unknown(_) => return None,
+    };
+    Some(Rgb(rgb))
+}
+
+/// Dim "Empty" marker centered on a spot no player was placed at, kept
+/// right of `min_left` like the player labels
+fn empty_spot_marker(
+    spot: &SpotLayout,
+    placements: &FramePlacements<'_>,
+    font: &FontArc,
+    (width, height): (u32, u32),
+) -> Vec<LayoutItem> {
+    let scale = PxScale::from(18.0);
+    let (pad, text_h) = (3, 18);
+    let text = "Empty";
+    let text_w = measure_text_width(text, font, scale);
+
+    let (asset_w, asset_h) = placements.asset_size;
+    let center_x = (spot.coords.0 * width as f32 / asset_w) as i32 + spot.label_offset.0;
+    let center_y = (spot.coords.1 * height as f32 / asset_h) as i32;
+    let x = (center_x - text_w / 2).max(placements.min_left + pad);
+    let y = label_block_top(center_y, spot, text_h, 0, 0, 1);
+    vec![
+        LayoutItem::rect(
+            x - pad,
+            y - 2,
+            text_w + pad * 2,
+            text_h + 4,
+            EMPTY_SPOT_BACKDROP,
+        ),
+        LayoutItem::text(text, font, x, y, scale, EMPTY_SPOT_TEXT),
+    ]
+}
+
+/// What the center info block shows and in which font
+struct CenterInfo<'a> {
+    font: &'a FontArc,
+    scale: PxScale,
+    filename: &'a str,
+    show_winner: bool,
+    palette: DisplayPalette,
+}
+
+/// Centered info block (filename, date, duration, winner) on one backdrop
+fn center_info(replay: &ReplayInfo, info: &CenterInfo<'_>, size: (u32, u32)) -> Vec<LayoutItem> {
+    let (font, scale, palette) = (info.font, info.scale, info.palette);
+    let (width, height) = (size.0 as i32, size.1 as i32);
+    let center_x = width / 2;
+    let center_y = height / 2;
+
+    let display_name = fit_text(
+        replay_stem(info.filename),
+        font,
+        scale,
+        (width as f32 * FILENAME_WIDTH_FRACTION) as i32,
+    );
+
+    // Format info text
+    let date_text = format!("Date: {}", replay.start_date_formatted());
+    let mut duration_text = format!("Duration: {}", replay.duration_formatted());
+    if replay.has_large_duration_correction() {
+        duration_text.push_str(" (pauses excluded)");
+    }
+
+    // Build info lines (the winner line may carry an icon, its source note is smaller)
+    let small = PxScale::from(scale.y * 0.75);
+    let mut info_lines: Vec<(String, Rgb<u8>, Option<WinnerIcon>, PxScale)> = vec![
+        (display_name, Rgb([255, 255, 255]), None, scale),
+        (date_text, Rgb([200, 200, 200]), None, scale),
+        (duration_text, Rgb([200, 200, 200]), None, scale),
+    ];
+
+    // Lobby settings are not drawn until their `RU=` positions are
+    // confirmed against replays with known settings
+
+    // A file cut off in transfer: whatever result follows may be wrong
+    if replay.truncated {
+        info_lines.push(("Replay truncated".to_string(), TRUNCATED_TEXT, None, small));
+    }
+
+    // When the first player fell hints at the outcome, so it comes with the winner
+    if info.show_winner
+        && let Some(text) = replay.first_defeat_text()
+    {
+        info_lines.push((
+            format!("First fall: {}", text),
+            Rgb([200, 200, 200]),
+            None,
+            scale,
+        ));
+    }
+
+    // Only show winner if known
+    if info.show_winner
+        && let Some((text, style)) = winner_line(replay)
+    {
+        // Color-blind mode also marks the outcome with a shape
+        let text = match palette.winner_prefix(style.icon) {
+            Some(prefix) => format!("{} {}", prefix, text),
+            None => text,
+        };
+        // Icons missing from the atlas become a text prefix
+        match style.icon {
+            Some(icon) if sprite(icon).is_none() => info_lines.push((
+                format!("{} {}", icon.fallback_text(), text),
+                style.color,
+                None,
+                scale,
+            )),
+            icon => info_lines.push((text, style.color, icon, scale)),
+        }
+        if let Some(note) = winner_source_line(replay) {
+            info_lines.push((note, Rgb([170, 170, 170]), None, small));
+        }
+        if let Some(collapse) = replay.collapse_text() {
+            info_lines.push((
+                format!("Collapse: {}", collapse),
+                Rgb([170, 170, 170]),
+                None,
+                small,
+            ));
+        }
+    }
+
+    let line_height = 28;
+    let total_height = (info_lines.len() as i32) * line_height;
+    let start_y = center_y - total_height / 2;
+
+    // Icon sized to the cap height, followed by a gap
+    let scaled = font.as_scaled(scale);
+    let icon_height = (scale.y * 0.7) as i32;
+    let icon_w = icon_size(icon_height) + icon_height / 3;
+    let line_width = |text: &str, icon: Option<WinnerIcon>, scale: PxScale| {
+        measure_text_width(text, font, scale) + if icon.is_some() { icon_w } else { 0 }
+    };
+
+    // Backdrop as wide as the widest line
+    let max_width = info_lines
+        .iter()
+        .map(|(text, _, icon, scale)| line_width(text, *icon, *scale))
+        .max()
+        .unwrap_or(0);
+    let padding = 10;
+    let mut items = vec![LayoutItem::rect(
+        center_x - max_width / 2 - padding,
+        start_y - padding,
+        max_width + padding * 2,
+        total_height + padding * 2,
+        INFO_BACKDROP,
+    )];
+
+    // Info lines, centered, icon included
+    for (i, (text, color, icon, line_scale)) in info_lines.iter().enumerate() {
+        let mut text_x = center_x - line_width(text, *icon, *line_scale) / 2;
+        let text_y = start_y + (i as i32) * line_height;
+        if let Some(icon) = *icon {
+            items.push(LayoutItem::Icon {
+                icon,
+                x: text_x,
+                baseline: text_y + scaled.ascent() as i32,
+                height: icon_height,
+                color: *color,
+            });
+            text_x += icon_w;
+        }
+        items.push(LayoutItem::text(
+            text,
+            font,
+            text_x,
+            text_y,
+            *line_scale,
+            *color,
+        ));
+    }
+    items
+}
+
+/// "Obs: a, b, c" line for some observers; names that don't `fit` are
+/// dropped from the end and counted as "+N"
+fn spectator_line(names: &[&str], fits: impl Fn(&str) -> bool) -> String {
+    let line = |shown: usize| {
+        let mut text = format!("Obs: {}", names[..shown].join(", "));
+        if shown < names.len() {
+            text.push_str(&format!(" +{}", names.len() - shown));
+        }
+        text
+    };
+    (1..=names.len())
+        .rev()
+        .map(line)
+        .find(|text| fits(text))
+        .unwrap_or_else(|| line(1.min(names.len())))
+}
+
+/// Tops of the observer lines on a full frame of `height` pixels, the lower
+/// one kept above the activity strip
+fn spectator_rows(height: i32, above_strip: bool) -> (i32, i32) {
+    let mut bottom_y = (height as f32 * 0.92) as i32;
+    if above_strip {
+        bottom_y = bottom_y.min(strip_top(height) - SPECTATOR_ROW);
+    }
+    ((height as f32 * 0.08) as i32, bottom_y)
+}
+
+/// Tops of the observer lines on a cropped frame, in the bands kept for them
+fn cropped_spectator_rows(crop: Crop, above_strip: bool) -> (i32, i32) {
+    let height = crop.bounds.h;
+    let bottom_y = if above_strip {
+        strip_top(height) - SPECTATOR_ROW
+    } else {
+        // Backdrop ending half a margin above the bottom
+        height - CROP_MARGIN / 2 - 22
+    };
+    (CROP_MARGIN / 2, bottom_y)
+}
+
+/// Observer lines with tops at `rows`, across a frame `width` pixels wide:
+/// the first half of the observers on top, the rest below
+fn spectator_lines(
+    replay: &ReplayInfo,
+    font: &FontArc,
+    scale: PxScale,
+    width: i32,
+    (top_y, bottom_y): (i32, i32),
+) -> Vec<LayoutItem> {
+    if replay.spectators.is_empty() {
+        return Vec::new();
+    }
+
+    let center_x = width / 2;
+    let spectator_color = Rgb([180, 180, 180]);
+    let max_width = width - 40;
+
+    // One long name may take at most a third of the line
+    let fitted: Vec<String> = replay
+        .spectators
+        .iter()
+        .map(|s| fit_text(&s.name, font, scale, max_width / 3))
+        .collect();
+    let names: Vec<&str> = fitted.iter().map(String::as_str).collect();
+    let (top, bottom) = names.split_at(names.len().div_ceil(2));
+
+    let lines = [(top, top_y), (bottom, bottom_y)];
+    let mut items = Vec::new();
+    for (names, spec_y) in lines {
+        if names.is_empty() {
+            continue;
+        }
+        let spec_text = spectator_line(names, |text| {
+            measure_text_width(text, font, scale) <= max_width
+        });
+        let spec_w = measure_text_width(&spec_text, font, scale);
+        let spec_x = center_x - spec_w / 2;
+        items.push(LayoutItem::rect(
+            spec_x - 3,
+            spec_y - 2,
+            spec_w + 6,
+            24,
+            INFO_BACKDROP,
+        ));
+        items.push(LayoutItem::text(
+            &spec_text,
+            font,
+            spec_x,
+            spec_y,
+            scale,
+            spectator_color,
+        ));
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, LobbyOptions, MapSpot, PlayerBuilder, Spectator, Winner};
+    use crate::renderer::map::test_font;
+
+    const SIZE: (u32, u32) = (1000, 1000);
+
+    /// Two players per side spot in the middle rows, one elsewhere, some with
+    /// long names or powers bought
+    fn crowded_game() -> ReplayInfo {
+        let seats = [
+            ("Alice", MapSpot::TopLeft),
+            ("Bob the Unpronounceable Warlord", MapSpot::MidLeft),
+            ("Carol", MapSpot::MidLeft),
+            ("Dave", MapSpot::BottomLeft),
+            ("Erin", MapSpot::TopRight),
+            ("Frank", MapSpot::MidRight),
+            ("Grace of the Very Long Name", MapSpot::MidRight),
+            ("Heidi", MapSpot::BottomRight),
+        ];
+        let players = seats
+            .iter()
+            .enumerate()
+            .map(|(i, &(name, spot))| {
+                let mut player = PlayerBuilder::test_player(
+                    name,
+                    if i < 4 { 1 } else { 2 },
+                    i as u8,
+                    Faction::Elves,
+                );
+                player.spot = Some(spot);
+                if i % 3 == 0 {
+                    player.powers_used = vec![("Elven Wood".to_string(), 300)];
+                }
+                player
+            })
+            .collect();
+        ReplayInfo::new("map wor rhun".to_string(), players)
+            .with_times(1_700_000_000, 1_700_001_000)
+            .with_winner(Winner::LeftTeam)
+            .with_spectators(
+                ["ObsA", "ObsB", "ObsC"]
+                    .iter()
+                    .map(|name| Spectator {
+                        name: name.to_string(),
+                        uid: None,
+                        slot: None,
+                    })
+                    .collect(),
+            )
+    }
+
+    fn union(items: &[LayoutItem]) -> Bounds {
+        items
+            .iter()
+            .map(LayoutItem::bounds)
+            .reduce(|a, b| a.union(&b))
+            .unwrap()
+    }
+
+    #[test]
+    fn player_labels_do_not_overlap() {
+        let replay = crowded_game();
+        let font = test_font();
+        let layout = MapLayout::default();
+        let placements = label_placements(&replay, &layout, Default::default());
+        let style = LabelStyle::of(&font, &placements);
+        for palette in [DisplayPalette::Standard, DisplayPalette::ColorBlind] {
+            let blocks: Vec<(&str, Bounds)> = placements
+                .players
+                .iter()
+                .map(|(player, label)| {
+                    let items =
+                        player_label(player, label, &style, SIZE, RevealStage::Winner, palette);
+                    (player.name.as_str(), union(&items))
+                })
+                .collect();
+            assert_eq!(blocks.len(), 8);
+            for (i, (a, a_bounds)) in blocks.iter().enumerate() {
+                for (b, b_bounds) in &blocks[i + 1..] {
+                    assert!(
+                        !a_bounds.overlaps(b_bounds),
+                        "{} {:?} overlaps {} {:?}",
+                        a,
+                        a_bounds,
+                        b,
+                        b_bounds
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_text_run_sits_on_a_backdrop() {
+        let replay = crowded_game();
+        let font = test_font();
+        let layout = MapLayout::default();
+        for palette in [DisplayPalette::Standard, DisplayPalette::ColorBlind] {
+            let options = RenderOptions {
+                palette,
+                ..Default::default()
+            };
+            let placements = label_placements(&replay, &layout, options);
+            let items = layout_frame(
+                &replay,
+                &font,
+                &placements,
+                SIZE,
+                "final.BfME2Replay",
+                RevealStage::Winner,
+                palette,
+            );
+            let backdrops: Vec<Bounds> = items
+                .iter()
+                .filter(|item| matches!(item, LayoutItem::Rect { .. }))
+                .map(LayoutItem::bounds)
+                .collect();
+            let foreground = items
+                .iter()
+                .filter(|item| !matches!(item, LayoutItem::Rect { .. }));
+            let mut runs = 0;
+            for item in foreground {
+                let bounds = item.bounds();
+                assert!(
+                    backdrops.iter().any(|rect| rect.contains(&bounds)),
+                    "{:?} has no backdrop",
+                    item
+                );
+                runs += 1;
+            }
+            // 8 labels of 3 or 4 rows (badges too when color-blind), 6 center
+            // lines with the trophy, 2 observer lines
+            assert!(runs >= 8 * 2 + 6 + 2, "{}", runs);
+        }
+    }
+
+    #[test]
+    fn compact_labels_are_narrower_single_rows() {
+        let replay = crowded_game();
+        let font = test_font();
+        let layout = MapLayout::default();
+        let assets = tempfile::tempdir().unwrap();
+        std::fs::create_dir(assets.path().join("icons")).unwrap();
+        RgbaImage::from_pixel(16, 16, image::Rgba([60, 180, 90, 255]))
+            .save(assets.path().join("icons").join("elves.png"))
+            .unwrap();
+        let icons = crate::renderer::load_faction_icons(assets.path());
+        let no_icons = FactionIcons::default();
+
+        let labels = |compact: bool, icons: &FactionIcons| -> Vec<Vec<LayoutItem>> {
+            let options = RenderOptions {
+                compact_labels: Some(compact),
+                ..Default::default()
+            };
+            let placements = label_placements(&replay, &layout, options).with_faction_icons(icons);
+            let style = LabelStyle::of(&font, &placements);
+            placements
+                .players
+                .iter()
+                .map(|(player, label)| {
+                    player_label(
+                        player,
+                        label,
+                        &style,
+                        SIZE,
+                        RevealStage::Winner,
+                        DisplayPalette::Standard,
+                    )
+                })
+                .collect()
+        };
+        // Text runs of each label, side by side
+        let text_block = |items: &[LayoutItem]| -> Bounds {
+            let runs: Vec<LayoutItem> = items
+                .iter()
+                .filter(|item| matches!(item, LayoutItem::Text { .. }))
+                .cloned()
+                .collect();
+            union(&runs)
+        };
+        let placed = label_placements(&replay, &layout, Default::default());
+        let full = labels(false, &icons);
+        for (((player, _), full), compact) in
+            placed.players.iter().zip(&full).zip(&labels(true, &icons))
+        {
+            let (full_text, compact_text) = (text_block(full), text_block(compact));
+            // Long names are cut to the same width either way
+            if player.name.len() < 20 {
+                assert!(
+                    compact_text.w < full_text.w,
+                    "{:?} not narrower than {:?}",
+                    compact_text,
+                    full_text
+                );
+            }
+            assert!(union(compact).h < union(full).h);
+        }
+        // Without icons the faction shares the name row
+        for (full, compact) in full.iter().zip(&labels(true, &no_icons)) {
+            assert!(union(compact).h < union(full).h);
+        }
+
+        // The faction is an icon left of the name, or written out after it
+        let texts = |labels: &[Vec<LayoutItem>]| -> Vec<String> {
+            labels
+                .iter()
+                .flatten()
+                .filter_map(|item| match item {
+                    LayoutItem::Text { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let with_icons = labels(true, &icons);
+        let icon_count = with_icons
+            .iter()
+            .flatten()
+            .filter(|item| matches!(item, LayoutItem::FactionIcon { .. }))
+            .count();
+        assert_eq!(icon_count, 8);
+        assert!(texts(&with_icons).contains(&"Alice".to_string()));
+        assert!(!texts(&with_icons).iter().any(|text| text.contains("Elves")));
+        assert!(texts(&labels(true, &no_icons)).contains(&"Alice · Elves".to_string()));
+    }
+
+    #[test]
+    fn many_long_names_pick_compact_labels() {
+        let mut replay = crowded_game();
+        let options = RenderOptions::default();
+        assert!(!options.uses_compact_labels(&replay));
+        for player in &mut replay.players[..5] {
+            player.name = format!("{} the Bold", player.name);
+        }
+        assert!(options.uses_compact_labels(&replay));
+        let forced = RenderOptions {
+            compact_labels: Some(false),
+            ..options
+        };
+        assert!(!forced.uses_compact_labels(&replay));
+    }
+
+    #[test]
+    fn players_stay_hidden_until_their_stage() {
+        let replay = crowded_game();
+        let font = test_font();
+        let layout = MapLayout::default();
+        let placements = label_placements(&replay, &layout, RenderOptions::default());
+        let texts = |stage| -> Vec<String> {
+            layout_frame(
+                &replay,
+                &font,
+                &placements,
+                SIZE,
+                "final.BfME2Replay",
+                stage,
+                DisplayPalette::Standard,
+            )
+            .into_iter()
+            .filter_map(|item| match item {
+                LayoutItem::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect()
+        };
+        let hidden = texts(RevealStage::Positions);
+        assert_eq!(hidden.iter().filter(|t| *t == "?").count(), 8);
+        assert!(
+            !hidden
+                .iter()
+                .any(|t| t == "Alice" || t.starts_with("Winner"))
+        );
+        let shown = texts(RevealStage::Winner);
+        assert!(shown.iter().any(|t| t == "Alice"));
+        assert!(shown.iter().any(|t| t.starts_with("Winner: Left Team")));
+    }
+
+    #[test]
+    fn lobby_options_are_not_drawn_yet() {
+        let font = test_font();
+        let layout = MapLayout::default();
+        let texts = |replay: &ReplayInfo| -> Vec<String> {
+            let placements = label_placements(replay, &layout, RenderOptions::default());
+            layout_frame(
+                replay,
+                &font,
+                &placements,
+                SIZE,
+                "final.BfME2Replay",
+                RevealStage::Winner,
+                DisplayPalette::Standard,
+            )
+            .into_iter()
+            .filter_map(|item| match item {
+                LayoutItem::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect()
+        };
+        let replay = crowded_game().with_lobby_options(LobbyOptions {
+            starting_resources: Some(1000),
+            game_speed: Some(100),
+            ..Default::default()
+        });
+        assert_eq!(texts(&replay), texts(&crowded_game()));
+    }
+
+    #[test]
+    fn collapse_line_follows_the_winner() {
+        let font = test_font();
+        let layout = MapLayout::default();
+        let replay = crowded_game()
+            .with_winner(Winner::LeftTeam)
+            .with_collapse_duration(Some(252));
+        let texts = |stage: RevealStage| -> Vec<String> {
+            let placements = label_placements(&replay, &layout, RenderOptions::default());
+            layout_frame(
+                &replay,
+                &font,
+                &placements,
+                SIZE,
+                "final.BfME2Replay",
+                stage,
+                DisplayPalette::Standard,
+            )
+            .into_iter()
+            .filter_map(|item| match item {
+                LayoutItem::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect()
+        };
+        assert!(
+            texts(RevealStage::Winner)
+                .iter()
+                .any(|t| t == "Collapse: 4:12")
+        );
+        assert!(
+            !texts(RevealStage::Players)
+                .iter()
+                .any(|t| t.starts_with("Collapse"))
+        );
+    }
+
+    #[test]
+    fn truncated_replays_are_noted_before_the_winner() {
+        let font = test_font();
+        let layout = MapLayout::default();
+        let texts = |replay: &ReplayInfo| -> Vec<String> {
+            let placements = label_placements(replay, &layout, RenderOptions::default());
+            layout_frame(
+                replay,
+                &font,
+                &placements,
+                SIZE,
+                "final.BfME2Replay",
+                RevealStage::Players,
+                DisplayPalette::Standard,
+            )
+            .into_iter()
+            .filter_map(|item| match item {
+                LayoutItem::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect()
+        };
+        let note = |t: &String| t == "Replay truncated";
+        assert!(!texts(&crowded_game()).iter().any(note));
+        assert!(texts(&crowded_game().with_truncated(true)).iter().any(note));
+    }
+
+    #[test]
+    fn spectator_lines_drop_names_that_do_not_fit() {
+        let fits = |text: &str| text.chars().count() <= 20;
+        assert_eq!(spectator_line(&["Ann", "Bo"], fits), "Obs: Ann, Bo");
+        assert_eq!(
+            spectator_line(&["Ann", "Bo", "Carol", "Dave"], fits),
+            "Obs: Ann, Bo +2"
+        );
+        // Even one name too long: it is still shown
+        assert_eq!(
+            spectator_line(&["AVeryLongObserverName", "Bo"], fits),
+            "Obs: AVeryLongObserverName +1"
+        );
+    }
+
+    /// A 1v1 at `spots` with an observer and activity, so the crop keeps
+    /// room for both
+    fn duel(spots: [MapSpot; 2]) -> ReplayInfo {
+        let players = spots
+            .iter()
+            .enumerate()
+            .map(|(i, &spot)| {
+                let mut player = PlayerBuilder::test_player(
+                    ["Alice", "Bob the Unpronounceable Warlord"][i],
+                    i as i8 + 1,
+                    i as u8,
+                    Faction::Elves,
+                );
+                player.spot = Some(spot);
+                player
+            })
+            .collect();
+        ReplayInfo::new("map wor rhun".to_string(), players)
+            .with_times(1_700_000_000, 1_700_001_000)
+            .with_winner(Winner::LeftTeam)
+            .with_spectators(vec![Spectator {
+                name: "ObsA".to_string(),
+                uid: None,
+                slot: None,
+            }])
+            .with_activity_buckets(vec![(0, 3, 2)])
+    }
+
+    #[test]
+    fn duels_are_cropped_around_both_spots() {
+        let font = test_font();
+        let layout = MapLayout::default();
+        let options = RenderOptions {
+            crop_duels: true,
+            ..Default::default()
+        };
+        let full = Bounds {
+            x: 0,
+            y: 0,
+            w: SIZE.0 as i32,
+            h: SIZE.1 as i32,
+        };
+        let spots: Vec<MapSpot> = layout.spots().map(|(spot, _)| spot).collect();
+        for (i, &a) in spots.iter().enumerate() {
+            for &b in &spots[i + 1..] {
+                let replay = duel([a, b]);
+                let placements = label_placements(&replay, &layout, options).cropped_to_action(
+                    &replay,
+                    &font,
+                    SIZE,
+                    "duel.BfME2Replay",
+                    options.palette,
+                );
+                let crop = placements.crop().expect("both spots known");
+                assert!(full.contains(&crop), "{:?}-{:?}: {:?}", a, b, crop);
+                if a.side() == b.side() {
+                    assert!(crop.w < full.w, "{:?}-{:?} not cropped: {:?}", a, b, crop);
+                }
+
+                let style = LabelStyle::of(&font, &placements);
+                let labels: Vec<Bounds> = placements
+                    .players
+                    .iter()
+                    .map(|(player, label)| {
+                        let items = player_label(
+                            player,
+                            label,
+                            &style,
+                            SIZE,
+                            RevealStage::Winner,
+                            DisplayPalette::Standard,
+                        );
+                        let block = union(&items);
+                        Bounds {
+                            x: block.x - crop.x,
+                            y: block.y - crop.y,
+                            ..block
+                        }
+                    })
+                    .collect();
+                let frame = Bounds {
+                    x: 0,
+                    y: 0,
+                    w: crop.w,
+                    h: crop.h,
+                };
+                let items = layout_frame(
+                    &replay,
+                    &font,
+                    &placements,
+                    SIZE,
+                    "duel.BfME2Replay",
+                    RevealStage::Winner,
+                    DisplayPalette::Standard,
+                );
+                for item in &items {
+                    let bounds = item.bounds();
+                    assert!(
+                        frame.contains(&bounds),
+                        "{:?}-{:?}: {:?} outside {:?}",
+                        a,
+                        b,
+                        item,
+                        frame
+                    );
+                    // Info and observer text stays clear of the labels
+                    if matches!(item, LayoutItem::Text { .. })
+                        && !labels.iter().any(|label| label.contains(&bounds))
+                    {
+                        assert!(
+                            labels.iter().all(|label| !label.overlaps(&bounds)),
+                            "{:?}-{:?}: {:?} covers a label",
+                            a,
+                            b,
+                            item
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_duels_with_known_spots_are_cropped() {
+        let font = test_font();
+        let layout = MapLayout::default();
+        let options = RenderOptions {
+            crop_duels: true,
+            ..Default::default()
+        };
+        let crop = |replay: &ReplayInfo, options: RenderOptions| {
+            label_placements(replay, &layout, options)
+                .cropped_to_action(replay, &font, SIZE, "game", options.palette)
+                .crop()
+        };
+
+        let duel = duel([MapSpot::TopLeft, MapSpot::MidLeft]);
+        assert!(crop(&duel, options).is_some());
+        assert!(crop(&duel, RenderOptions::default()).is_none());
+        let with_panel = RenderOptions {
+            show_lobby_panel: true,
+            ..options
+        };
+        assert!(crop(&duel, with_panel).is_none());
+
+        let mut unknown = duel.clone();
+        unknown.players[1].spot = None;
+        assert!(crop(&unknown, options).is_none());
+        assert!(crop(&crowded_game(), options).is_none());
+    }
+}
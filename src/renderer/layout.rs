@@ -0,0 +1,467 @@
+use crate::models::{MapSpot, SpotRegions};
+use serde::Deserialize;
+
+/// Where a spot's label block sits relative to its anchor point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LabelAnchor {
+    /// Block bottom edge on the point (label drawn above)
+    Above,
+    /// Block top edge on the point (label drawn below)
+    Below,
+    /// Block vertically centered on the point
+    #[default]
+    Center,
+}
+
+impl LabelAnchor {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "above" => Some(LabelAnchor::Above),
+            "below" => Some(LabelAnchor::Below),
+            "center" => Some(LabelAnchor::Center),
+            _ => None,
+        }
+    }
+}
+
+/// Label placement for one named spot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLayout {
+    /// Anchor point in pixels on the original map asset
+    pub coords: (f32, f32),
+    /// Extra (x, y) pixel shift applied after anchoring
+    pub label_offset: (i32, i32),
+    pub label_anchor: LabelAnchor,
+}
+
+/// Spot placement for a map: asset size, world bounds of each spot and label
+/// placement for all six spots
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapLayout {
+    /// Pixel size of the map asset the spot coordinates refer to
+    asset_size: (f32, f32),
+    regions: SpotRegions,
+    spots: [(MapSpot, SpotLayout); 6],
+}
+
+impl Default for MapLayout {
+    /// wor rhun bounds and anchor coordinates on its 1624x1620 asset, labels
+    /// centered with no offset
+    fn default() -> Self {
+        let spot = |spot, x, y| {
+            (
+                spot,
+                SpotLayout {
+                    coords: (x, y),
+                    label_offset: (0, 0),
+                    label_anchor: LabelAnchor::Center,
+                },
+            )
+        };
+        Self {
+            asset_size: (1624.0, 1620.0),
+            regions: SpotRegions::default(),
+            spots: [
+                spot(MapSpot::TopLeft, 272.0, 336.0),
+                spot(MapSpot::MidLeft, 198.0, 896.0),
+                spot(MapSpot::BottomLeft, 344.0, 1370.0),
+                spot(MapSpot::TopRight, 1330.0, 336.0),
+                spot(MapSpot::MidRight, 1370.0, 850.0),
+                spot(MapSpot::BottomRight, 1314.0, 1420.0),
+            ],
+        }
+    }
+}
+
+/// `assets/maps/<name>.toml` contents
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LayoutFile {
+    asset: AssetSize,
+    world: SpotRegionsFile,
+    spot: Vec<SpotFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssetSize {
+    width: f32,
+    height: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpotRegionsFile {
+    x_midpoint: f32,
+    y_top: f32,
+    y_mid: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpotFile {
+    name: String,
+    x: f32,
+    y: f32,
+    label_anchor: Option<String>,
+    label_offset: Option<(i32, i32)>,
+}
+
+impl MapLayout {
+    /// Parse a map layout file: `[asset]` pixel size, `[world]` spot bounds
+    /// and one `[[spot]]` per named spot with its pixel anchor, e.g.
+    ///
+    /// ```toml
+    /// [asset]
+    /// width = 1624
+    /// height = 1620
+    ///
+    /// [world]
+    /// x_midpoint = 2500
+    /// y_top = 3000
+    /// y_mid = 1500
+    ///
+    /// [[spot]]
+    /// name = "top_left"
+    /// x = 272
+    /// y = 336
+    /// label_anchor = "below"   # optional
+    /// label_offset = [0, 12]   # optional
+    /// ```
+    ///
+    /// Every one of the six spots must be listed exactly once.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let file: LayoutFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        if file.asset.width <= 0.0 || file.asset.height <= 0.0 {
+            return Err("Asset size must be positive".to_string());
+        }
+        let mut layout = Self {
+            asset_size: (file.asset.width, file.asset.height),
+            regions: SpotRegions {
+                x_midpoint: file.world.x_midpoint,
+                y_top: file.world.y_top,
+                y_mid: file.world.y_mid,
+            },
+            ..Self::default()
+        };
+        if layout.regions.y_mid >= layout.regions.y_top {
+            return Err("y_mid must be below y_top".to_string());
+        }
+
+        let mut seen = Vec::new();
+        for entry in &file.spot {
+            let spot = MapSpot::from_name(&entry.name)
+                .ok_or_else(|| format!("Unknown spot: {}", entry.name))?;
+            if seen.contains(&spot) {
+                return Err(format!("Spot listed twice: {}", entry.name));
+            }
+            seen.push(spot);
+            let label_anchor = match &entry.label_anchor {
+                Some(a) => {
+                    LabelAnchor::parse(a).ok_or_else(|| format!("Unknown label anchor: {}", a))?
+                }
+                None => LabelAnchor::Center,
+            };
+            *layout.spot_mut(spot) = SpotLayout {
+                coords: (entry.x, entry.y),
+                label_offset: entry.label_offset.unwrap_or((0, 0)),
+                label_anchor,
+            };
+        }
+        if seen.len() < layout.spots.len() {
+            let missing: Vec<String> = layout
+                .spots
+                .iter()
+                .filter(|(s, _)| !seen.contains(s))
+                .map(|(s, _)| s.to_string())
+                .collect();
+            return Err(format!("Missing spots: {}", missing.join(", ")));
+        }
+        Ok(layout)
+    }
+
+    /// Pixel size of the map asset the spot coordinates refer to
+    pub fn asset_size(&self) -> (f32, f32) {
+        self.asset_size
+    }
+
+    /// World-coordinate bounds of the map's spots
+    pub fn regions(&self) -> SpotRegions {
+        self.regions
+    }
+
+    /// Narrowest horizontal distance between a left spot and the right spot
+    /// of the same row, in asset pixels
+    pub fn narrowest_row_gap(&self) -> f32 {
+        [
+            (MapSpot::TopLeft, MapSpot::TopRight),
+            (MapSpot::MidLeft, MapSpot::MidRight),
+            (MapSpot::BottomLeft, MapSpot::BottomRight),
+        ]
+        .into_iter()
+        .map(|(left, right)| (self.spot(right).coords.0 - self.spot(left).coords.0).abs())
+        .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Every spot with its layout
+    pub fn spots(&self) -> impl Iterator<Item = (MapSpot, &SpotLayout)> {
+        self.spots.iter().map(|(spot, layout)| (*spot, layout))
+    }
+
+    /// Layout for a spot
+    pub fn spot(&self, spot: MapSpot) -> &SpotLayout {
+        &self
+            .spots
+            .iter()
+            .find(|(s, _)| *s == spot)
+            .expect("MapLayout covers every MapSpot")
+            .1
+    }
+
+    fn spot_mut(&mut self, spot: MapSpot) -> &mut SpotLayout {
+        &mut self
+            .spots
+            .iter_mut()
+            .find(|(s, _)| *s == spot)
+            .expect("MapLayout covers every MapSpot")
+            .1
+    }
+
+    /// Parse label overrides on top of the default layout.
+    ///
+    /// Format: `spot=anchor[@dx,dy]` entries separated by `;`, e.g.
+    /// `top_left=below@0,12;bottom_right=above`. Spot names are matched
+    /// case-insensitively, ignoring `_`, `-` and spaces.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        Self::default().with_overrides(spec)
+    }
+
+    /// Apply label overrides in the [`parse`](Self::parse) format on top of
+    /// this layout
+    pub fn with_overrides(mut self, spec: &str) -> Result<Self, String> {
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (spot_name, rest) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Missing '=' in layout entry: {}", entry))?;
+            let spot = MapSpot::from_name(spot_name)
+                .ok_or_else(|| format!("Unknown spot in layout entry: {}", spot_name.trim()))?;
+            let (anchor_str, offset_str) = match rest.split_once('@') {
+                Some((a, o)) => (a, Some(o)),
+                None => (rest, None),
+            };
+            let anchor = LabelAnchor::parse(anchor_str)
+                .ok_or_else(|| format!("Unknown label anchor: {}", anchor_str.trim()))?;
+            let offset = match offset_str {
+                Some(o) => parse_offset(o)?,
+                None => (0, 0),
+            };
+
+            let target = self.spot_mut(spot);
+            target.label_anchor = anchor;
+            target.label_offset = offset;
+        }
+        Ok(self)
+    }
+}
+
+fn parse_offset(s: &str) -> Result<(i32, i32), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Label offset must be 'dx,dy': {}", s.trim()))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid label offset value: {}", v.trim()))
+    };
+    Ok((parse(x)?, parse(y)?))
+}
+
+/// Top y of the `index`-th of `count` stacked label blocks at an anchor.
+/// The whole stack is placed according to `anchor`, then shifted by `offset_y`.
+pub fn label_block_top(
+    anchor_y: i32,
+    layout: &SpotLayout,
+    block_h: i32,
+    stack_gap: i32,
+    index: usize,
+    count: usize,
+) -> i32 {
+    let count = count.max(1) as i32;
+    let stack_h = count * block_h + (count - 1) * stack_gap;
+    let stack_top = match layout.label_anchor {
+        LabelAnchor::Above => anchor_y - stack_h,
+        LabelAnchor::Below => anchor_y,
+        LabelAnchor::Center => anchor_y - stack_h / 2,
+    };
+    stack_top + layout.label_offset.1 + index as i32 * (block_h + stack_gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MapPosition;
+
+    fn layout_with(anchor: LabelAnchor, offset: (i32, i32)) -> SpotLayout {
+        SpotLayout {
+            coords: (0.0, 0.0),
+            label_offset: offset,
+            label_anchor: anchor,
+        }
+    }
+
+    #[test]
+    fn default_is_centered_without_offset() {
+        let layout = MapLayout::default();
+        let top_left = layout.spot(MapSpot::TopLeft);
+        assert_eq!(top_left.coords, (272.0, 336.0));
+        assert_eq!(top_left.label_anchor, LabelAnchor::Center);
+        assert_eq!(top_left.label_offset, (0, 0));
+    }
+
+    #[test]
+    fn parse_overrides() {
+        let layout = MapLayout::parse("top_left=below@0,12; Bottom-Right=above").unwrap();
+        let top_left = layout.spot(MapSpot::TopLeft);
+        assert_eq!(top_left.label_anchor, LabelAnchor::Below);
+        assert_eq!(top_left.label_offset, (0, 12));
+        let bottom_right = layout.spot(MapSpot::BottomRight);
+        assert_eq!(bottom_right.label_anchor, LabelAnchor::Above);
+        assert_eq!(bottom_right.label_offset, (0, 0));
+        assert_eq!(
+            layout.spot(MapSpot::MidLeft).label_anchor,
+            LabelAnchor::Center
+        );
+    }
+
+    const SAMPLE: &str = r#"
+        [asset]
+        width = 800
+        height = 600
+
+        [world]
+        x_midpoint = 4000
+        y_top = 5000
+        y_mid = 2000
+
+        [[spot]]
+        name = "top_left"
+        x = 100
+        y = 80
+        label_anchor = "below"
+        label_offset = [0, 12]
+
+        [[spot]]
+        name = "mid_left"
+        x = 90
+        y = 300
+
+        [[spot]]
+        name = "bottom_left"
+        x = 120
+        y = 520
+
+        [[spot]]
+        name = "top_right"
+        x = 700
+        y = 80
+
+        [[spot]]
+        name = "mid_right"
+        x = 710
+        y = 300
+
+        [[spot]]
+        name = "bottom_right"
+        x = 680
+        y = 520
+    "#;
+
+    #[test]
+    fn layout_file_sets_size_bounds_and_spots() {
+        let layout = MapLayout::from_toml(SAMPLE).unwrap();
+        assert_eq!(layout.asset_size(), (800.0, 600.0));
+        let top_left = layout.spot(MapSpot::TopLeft);
+        assert_eq!(top_left.coords, (100.0, 80.0));
+        assert_eq!(top_left.label_anchor, LabelAnchor::Below);
+        assert_eq!(top_left.label_offset, (0, 12));
+        assert_eq!(layout.spot(MapSpot::MidRight).coords, (710.0, 300.0));
+        assert_eq!(layout.narrowest_row_gap(), 560.0);
+
+        // Classification follows the file's bounds, not wor rhun's
+        let regions = layout.regions();
+        let at = |x, y| regions.classify(MapPosition::new(x, y)).unwrap();
+        assert_eq!(at(3000.0, 1000.0), MapSpot::BottomLeft);
+        assert_eq!(at(3000.0, 2500.0), MapSpot::MidLeft);
+        assert_eq!(at(4000.0, 5000.1), MapSpot::TopRight);
+        assert_eq!(
+            MapSpot::from_position(MapPosition::new(3000.0, 2500.0)),
+            Some(MapSpot::MidRight)
+        );
+    }
+
+    #[test]
+    fn shipped_layout_file_matches_the_defaults() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join("maps")
+            .join("map wor rhun.toml");
+        let text = std::fs::read_to_string(path).unwrap();
+        assert_eq!(MapLayout::from_toml(&text).unwrap(), MapLayout::default());
+    }
+
+    #[test]
+    fn layout_file_rejects_bad_spots() {
+        let without_bottom_right = SAMPLE.split("[[spot]]").take(6).collect::<Vec<_>>();
+        let err = MapLayout::from_toml(&without_bottom_right.join("[[spot]]")).unwrap_err();
+        assert_eq!(err, "Missing spots: Bottom Right");
+        let doubled = SAMPLE.replace("\"mid_right\"", "\"mid_left\"");
+        assert!(MapLayout::from_toml(&doubled).is_err());
+        assert!(MapLayout::from_toml(&SAMPLE.replace("\"mid_right\"", "\"center\"")).is_err());
+        assert!(MapLayout::from_toml(&SAMPLE.replace("y_mid = 2000", "y_mid = 6000")).is_err());
+        assert!(MapLayout::from_toml(&SAMPLE.replace("x = 100", "xx = 100")).is_err());
+        assert!(MapLayout::from_toml("").is_err());
+    }
+
+    #[test]
+    fn overrides_apply_on_top_of_a_loaded_layout() {
+        let layout = MapLayout::from_toml(SAMPLE)
+            .unwrap()
+            .with_overrides("mid_right=above@4,0")
+            .unwrap();
+        let mid_right = layout.spot(MapSpot::MidRight);
+        assert_eq!(mid_right.coords, (710.0, 300.0));
+        assert_eq!(mid_right.label_anchor, LabelAnchor::Above);
+        assert_eq!(mid_right.label_offset, (4, 0));
+    }
+
+    #[test]
+    fn parse_rejects_bad_entries() {
+        assert!(MapLayout::parse("top_left").is_err());
+        assert!(MapLayout::parse("nowhere=above").is_err());
+        assert!(MapLayout::parse("top_left=sideways").is_err());
+        assert!(MapLayout::parse("top_left=above@5").is_err());
+        assert!(MapLayout::parse("top_left=above@a,b").is_err());
+        assert_eq!(MapLayout::parse("").unwrap(), MapLayout::default());
+    }
+
+    #[test]
+    fn centered_single_block_matches_legacy_placement() {
+        let l = layout_with(LabelAnchor::Center, (0, 0));
+        assert_eq!(label_block_top(500, &l, 46, 4, 0, 1), 500 - 23);
+    }
+
+    #[test]
+    fn below_stacks_downward_from_anchor_with_offset() {
+        let l = layout_with(LabelAnchor::Below, (0, 10));
+        assert_eq!(label_block_top(500, &l, 46, 4, 0, 2), 510);
+        assert_eq!(label_block_top(500, &l, 46, 4, 1, 2), 560);
+    }
+
+    #[test]
+    fn above_stacks_end_on_anchor_with_offset() {
+        let l = layout_with(LabelAnchor::Above, (0, -10));
+        // Stack height: 2 * 46 + 4 = 96; bottom edge lands on 500 - 10.
+        assert_eq!(label_block_top(500, &l, 46, 4, 0, 2), 394);
+        assert_eq!(label_block_top(500, &l, 46, 4, 1, 2) + 46, 490);
+    }
+}
@@ -0,0 +1,153 @@
+use super::map::{RevealStage, draw_rect_alpha, fit_text};
+use super::palette::DisplayPalette;
+use crate::models::{Player, ReplayInfo, Spectator};
+use ab_glyph::{FontArc, PxScale};
+use image::{Rgb, RgbImage};
+use imageproc::drawing::draw_text_mut;
+
+/// Lobby slots in a BfME2 game
+const LOBBY_SLOTS: usize = 8;
+
+/// Panel distance from the image's left edge
+const PANEL_MARGIN: i32 = 8;
+
+/// Panel width, margin and gap to the labels included; left-side labels are
+/// kept right of this
+pub(super) const LOBBY_PANEL_WIDTH: i32 = 176;
+
+const ROW_HEIGHT: i32 = 24;
+const PADDING: i32 = 8;
+
+/// Who sat in a lobby slot
+#[derive(Debug, Clone, Copy)]
+enum SlotOccupant<'a> {
+    Player(&'a Player),
+    Observer(&'a Spectator),
+    Empty,
+}
+
+/// Occupant of each lobby slot, in slot order
+fn lobby_slots(replay: &ReplayInfo) -> [SlotOccupant<'_>; LOBBY_SLOTS] {
+    std::array::from_fn(|slot| {
+        let slot = slot as u8;
+        if let Some(player) = replay.players.iter().find(|p| p.slot == slot) {
+            SlotOccupant::Player(player)
+        } else if let Some(observer) = replay.spectators.iter().find(|s| s.slot == Some(slot)) {
+            SlotOccupant::Observer(observer)
+        } else {
+            SlotOccupant::Empty
+        }
+    })
+}
+
+/// Text and color of one panel row; names stay hidden until `RevealStage::Players`
+fn slot_row(
+    slot: usize,
+    occupant: SlotOccupant<'_>,
+    stage: RevealStage,
+    palette: DisplayPalette,
+) -> (String, Rgb<u8>) {
+    let gray = Rgb([160, 160, 160]);
+    let hidden = stage < RevealStage::Players;
+    let (name, color) = match occupant {
+        SlotOccupant::Empty => ("–", Rgb([110, 110, 110])),
+        _ if hidden => ("?", gray),
+        SlotOccupant::Player(p) => (p.name.as_str(), palette.player_color(p)),
+        SlotOccupant::Observer(s) => (s.name.as_str(), gray),
+    };
+    (format!("{} {}", slot + 1, name), color)
+}
+
+/// Draw the lobby slot list on the left edge, vertically centered
+pub(super) fn draw_lobby_panel(
+    img: &mut RgbImage,
+    replay: &ReplayInfo,
+    font: &FontArc,
+    stage: RevealStage,
+    palette: DisplayPalette,
+) {
+    let scale = PxScale::from(18.0);
+    let panel_w = LOBBY_PANEL_WIDTH - PANEL_MARGIN * 2;
+    let max_text_w = panel_w - PADDING * 2;
+    let panel_h = (LOBBY_SLOTS as i32 + 1) * ROW_HEIGHT + PADDING * 2;
+    let top = (img.height() as i32 - panel_h) / 2;
+
+    draw_rect_alpha(img, PANEL_MARGIN, top, panel_w, panel_h, [0, 0, 0, 170]);
+    let text_x = PANEL_MARGIN + PADDING;
+    draw_text_mut(
+        img,
+        Rgb([255, 255, 255]),
+        text_x,
+        top + PADDING,
+        scale,
+        font,
+        "Lobby",
+    );
+
+    for (slot, occupant) in lobby_slots(replay).into_iter().enumerate() {
+        let (text, color) = slot_row(slot, occupant, stage, palette);
+        let text = fit_text(&text, font, scale, max_text_w);
+        let y = top + PADDING + (slot as i32 + 1) * ROW_HEIGHT;
+        draw_text_mut(img, color, text_x, y, scale, font, &text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, PLAYER_COLORS, PlayerBuilder};
+
+    fn player(name: &str, slot: u8) -> Player {
+        PlayerBuilder::test_player(name, 1, slot, Faction::Men)
+    }
+
+    fn observer(name: &str, slot: Option<u8>) -> Spectator {
+        Spectator {
+            name: name.to_string(),
+            uid: None,
+            slot,
+        }
+    }
+
+    #[test]
+    fn slots_list_players_observers_and_gaps() {
+        let replay = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![player("Alice", 0), player("Bob", 3)],
+        )
+        .with_spectators(vec![observer("Watcher", Some(1)), observer("Late", None)]);
+        let rows: Vec<(String, Rgb<u8>)> = lobby_slots(&replay)
+            .into_iter()
+            .enumerate()
+            .map(|(slot, occupant)| {
+                slot_row(
+                    slot,
+                    occupant,
+                    RevealStage::Winner,
+                    DisplayPalette::Standard,
+                )
+            })
+            .collect();
+        assert_eq!(rows[0], ("1 Alice".to_string(), Rgb(PLAYER_COLORS[0])));
+        assert_eq!(rows[1], ("2 Watcher".to_string(), Rgb([160, 160, 160])));
+        assert_eq!(rows[2].0, "3 –");
+        assert_eq!(rows[3].0, "4 Bob");
+        // Observers who joined later have no lobby slot
+        assert!(rows.iter().all(|(text, _)| !text.contains("Late")));
+    }
+
+    #[test]
+    fn names_are_hidden_before_the_players_stage() {
+        let alice = player("Alice", 0);
+        let row = |occupant, stage| slot_row(0, occupant, stage, DisplayPalette::Standard).0;
+        assert_eq!(
+            row(SlotOccupant::Player(&alice), RevealStage::Positions),
+            "1 ?"
+        );
+        assert_eq!(
+            row(SlotOccupant::Player(&alice), RevealStage::Players),
+            "1 Alice"
+        );
+        assert_eq!(row(SlotOccupant::Empty, RevealStage::Positions), "1 –");
+    }
+}
@@ -1,35 +1,101 @@
-use crate::models::{Player, ReplayInfo, Winner};
+use super::activity::draw_activity_strip;
+use super::faction_icons::FactionIcons;
+use super::frame_layout::{
+    FramePlacements, LayoutItem, label_placements, layout_frame, shows_activity,
+};
+use super::layout::MapLayout;
+use super::lobby::draw_lobby_panel;
+use super::minimap::load_minimap_from_map_file;
+use super::palette::DisplayPalette;
+use super::winner::{draw_icon, sprite};
+use crate::models::{ReplayInfo, StageClock, TimingBreakdown, anonymize_view};
 use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
-use image::{Rgb, RgbImage};
+use image::{RgbImage, RgbaImage};
 use imageproc::drawing::draw_text_mut;
+use std::borrow::Cow;
 use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Appended to text cut to fit
+const ELLIPSIS: &str = "…";
+
+/// Named spots on a map; smaller games leave some of them empty
+const SPOT_COUNT: usize = 6;
+
+/// Names longer than this many characters count towards compact labels
+const COMPACT_NAME_CHARS: usize = 10;
+
+/// Games with more long names than this get compact labels by default
+const COMPACT_LONG_NAMES: usize = 4;
+
+/// Load and prepare a map image and its spot layout from the assets directory
+/// (call once at startup). The layout comes from `<map_name>.toml` next to the
+/// image, or the compiled-in wor rhun layout when there is none. Without a
+/// `<map_name>.jpg`, the minimap preview of `<map_name>.map` is used.
+pub fn load_map(map_name: &str, assets_path: &Path) -> Result<(RgbImage, MapLayout), String> {
+    let maps_dir = assets_path.join("maps");
+    let layout_path = maps_dir.join(format!("{}.toml", map_name));
+    let layout = if layout_path.exists() {
+        let text = std::fs::read_to_string(&layout_path)
+            .map_err(|e| format!("Failed to read map layout: {}", e))?;
+        MapLayout::from_toml(&text)
+            .map_err(|e| format!("Invalid map layout {:?}: {}", layout_path, e))?
+    } else {
+        MapLayout::default()
+    };
+
+    let (asset_w, asset_h) = layout.asset_size();
+    let img = load_map_image(&maps_dir, map_name, (asset_w as u32, asset_h as u32))?;
+    if (img.width() as f32, img.height() as f32) != (asset_w, asset_h) {
+        return Err(format!(
+            "Map image is {}x{} but its layout expects {}x{}",
+            img.width(),
+            img.height(),
+            asset_w,
+            asset_h
+        ));
+    }
+    Ok((resize_for_output(img), layout))
+}
 
-/// Load and prepare a map image from the assets directory (call once at startup)
-pub fn load_map_image(map_name: &str, assets_path: &Path) -> Result<RgbImage, String> {
-    let map_path_jpg = assets_path.join("maps").join(format!("{}.jpg", map_name));
+fn load_map_image(
+    maps_dir: &Path,
+    map_name: &str,
+    asset_size: (u32, u32),
+) -> Result<RgbImage, String> {
+    let map_path_jpg = maps_dir.join(format!("{}.jpg", map_name));
+    let map_path_map = maps_dir.join(format!("{}.map", map_name));
 
-    let img = if map_path_jpg.exists() {
+    if map_path_jpg.exists() {
         image::open(&map_path_jpg)
             .map(|img| img.to_rgb8())
-            .map_err(|e| format!("Failed to load map image: {}", e))?
+            .map_err(|e| format!("Failed to load map image: {}", e))
+    } else if map_path_map.exists() {
+        // The preview covers the whole map, so stretching it to the asset
+        // size lines it up with the layout
+        let minimap = load_minimap_from_map_file(&map_path_map)?;
+        let (w, h) = asset_size;
+        Ok(image::imageops::resize(
+            &minimap,
+            w,
+            h,
+            image::imageops::FilterType::Triangle,
+        ))
     } else {
-        return Err(format!("Map image not found: {}", map_name));
-    };
+        Err(format!("Map image not found: {}", map_name))
+    }
+}
 
-    // Resize to ~1000px for output if larger
+/// Resize to ~1000px for output if larger
+fn resize_for_output(img: RgbImage) -> RgbImage {
     let (w, h) = (img.width(), img.height());
     if w > 1000 || h > 1000 {
         let scale = 1000.0 / w.max(h) as f32;
         let new_w = (w as f32 * scale) as u32;
         let new_h = (h as f32 * scale) as u32;
-        Ok(image::imageops::resize(
-            &img,
-            new_w,
-            new_h,
-            image::imageops::FilterType::Lanczos3,
-        ))
+        image::imageops::resize(&img, new_w, new_h, image::imageops::FilterType::Lanczos3)
     } else {
-        Ok(img)
+        img
     }
 }
 
@@ -38,65 +104,18 @@ pub fn load_font(font_data: &[u8]) -> Result<FontArc, String> {
     FontArc::try_from_vec(font_data.to_vec()).map_err(|e| format!("Failed to parse font: {}", e))
 }
 
-/// Circle center coordinates in pixels on the original 1624x1620 map asset.
-/// At render time these are scaled to match the actual (resized) image dimensions.
-const MAP_ASSET_WIDTH: f32 = 1624.0;
-const MAP_ASSET_HEIGHT: f32 = 1620.0;
-
-// Map position thresholds (game world coordinates)
-const MAP_X_MIDPOINT: f32 = 2500.0;
-const MAP_Y_TOP_THRESHOLD: f32 = 3000.0;
-const MAP_Y_MID_THRESHOLD: f32 = 1500.0;
-
-/// Map positions for player placement
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Position {
-    TopLeft,
-    MidLeft,
-    BottomLeft,
-    TopRight,
-    MidRight,
-    BottomRight,
-}
-
-impl Position {
-    /// Get pixel coordinates on the original map asset for this position
-    fn coords(self) -> (f32, f32) {
-        match self {
-            Position::TopLeft => (272.0, 336.0),
-            Position::MidLeft => (198.0, 896.0),
-            Position::BottomLeft => (344.0, 1370.0),
-            Position::TopRight => (1330.0, 336.0),
-            Position::MidRight => (1370.0, 850.0),
-            Position::BottomRight => (1314.0, 1420.0),
-        }
-    }
-}
-
-/// Get position from game world coordinates
-fn get_position(x: f32, y: f32) -> Position {
-    let is_left = x < MAP_X_MIDPOINT;
-    if y > MAP_Y_TOP_THRESHOLD {
-        if is_left {
-            Position::TopLeft
-        } else {
-            Position::TopRight
-        }
-    } else if y > MAP_Y_MID_THRESHOLD {
-        if is_left {
-            Position::MidLeft
-        } else {
-            Position::MidRight
-        }
-    } else if is_left {
-        Position::BottomLeft
-    } else {
-        Position::BottomRight
-    }
+/// The bundled bold font, for renderer tests
+#[cfg(test)]
+pub(super) fn test_font() -> FontArc {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSans-Bold.ttf");
+    load_font(&std::fs::read(path).unwrap()).unwrap()
 }
 
 /// Draw a semi-transparent rectangle (alpha blending on RGB image)
-fn draw_rect_alpha(img: &mut RgbImage, x: i32, y: i32, w: i32, h: i32, color: [u8; 4]) {
+pub(super) fn draw_rect_alpha(img: &mut RgbImage, x: i32, y: i32, w: i32, h: i32, color: [u8; 4]) {
     let a = color[3] as f32 / 255.0;
     let inv_a = 1.0 - a;
     let src_r = color[0] as f32 * a;
@@ -114,36 +133,163 @@ fn draw_rect_alpha(img: &mut RgbImage, x: i32, y: i32, w: i32, h: i32, color: [u
 }
 
 /// Measure text width using actual glyph advance widths from the font
-fn measure_text_width(text: &str, font: &FontArc, scale: PxScale) -> i32 {
+pub(super) fn measure_text_width(text: &str, font: &FontArc, scale: PxScale) -> i32 {
     let scaled = font.as_scaled(scale);
     text.chars()
         .map(|c| scaled.h_advance(font.glyph_id(c)))
         .sum::<f32>() as i32
 }
 
+/// `text` cut at grapheme cluster boundaries until it is at most
+/// `max_width` pixels wide, ending in an ellipsis when anything was cut
+pub(super) fn fit_text(text: &str, font: &FontArc, scale: PxScale, max_width: i32) -> String {
+    if measure_text_width(text, font, scale) <= max_width {
+        return text.to_string();
+    }
+    let mut graphemes: Vec<&str> = text.graphemes(true).collect();
+    while graphemes.pop().is_some() {
+        let cut = format!("{}{}", graphemes.concat().trim_end(), ELLIPSIS);
+        if measure_text_width(&cut, font, scale) <= max_width {
+            return cut;
+        }
+    }
+    ELLIPSIS.to_string()
+}
+
+/// Filename with the replay extension stripped (case-insensitive)
+pub(super) fn replay_stem(filename: &str) -> &str {
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) if ext.eq_ignore_ascii_case("BfME2Replay") => stem,
+        _ => filename,
+    }
+}
+
+/// Filename as shown to users: replay extension stripped (case-insensitive),
+/// capped at 30 chars
+pub fn display_filename(filename: &str) -> String {
+    replay_stem(filename).chars().take(30).collect()
+}
+
+/// How much of the game a rendered frame shows (frames of the reveal animation)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RevealStage {
+    /// Positions only: names and factions hidden
+    Positions,
+    /// Names and factions, winner hidden
+    Players,
+    /// Everything, including the winner
+    Winner,
+}
+
+/// Display settings shared by every render
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub palette: DisplayPalette,
+    /// List who sat in each lobby slot on the left edge
+    pub show_lobby_panel: bool,
+    /// Show "Player N"/"Obs N" instead of names, and the map instead of the filename
+    pub anonymize: bool,
+    /// Mark spots no player was placed at; `None` marks them in games with
+    /// fewer players than spots
+    pub show_empty_spots: Option<bool>,
+    /// Crop 1v1 images to the two players' spots and the center info
+    /// (not with the lobby panel, which needs the left edge)
+    pub crop_duels: bool,
+    /// Smaller single-line player labels with faction icons; `None` uses
+    /// them in games where many players have long names
+    pub compact_labels: Option<bool>,
+}
+
+impl RenderOptions {
+    /// Whether empty spots are marked on this game's image
+    pub fn marks_empty_spots(&self, replay: &ReplayInfo) -> bool {
+        self.show_empty_spots
+            .unwrap_or(replay.players.len() < SPOT_COUNT)
+    }
+
+    /// Whether this game's image gets compact player labels
+    pub fn uses_compact_labels(&self, replay: &ReplayInfo) -> bool {
+        self.compact_labels.unwrap_or_else(|| {
+            replay
+                .players
+                .iter()
+                .filter(|p| p.name.chars().count() > COMPACT_NAME_CHARS)
+                .count()
+                > COMPACT_LONG_NAMES
+        })
+    }
+}
+
+/// What a render draws the replay with, loaded once at startup
+#[derive(Clone, Copy)]
+pub struct RenderAssets<'a> {
+    pub font: &'a FontArc,
+    pub map_image: &'a RgbImage,
+    pub layout: &'a MapLayout,
+    pub faction_icons: &'a FactionIcons,
+}
+
+/// The game a render shows and the title above it: the replay and its
+/// filename, or with `anonymize` its [`anonymize_view`] titled by the map
+pub fn shown_replay<'a>(
+    replay: &'a ReplayInfo,
+    filename: &'a str,
+    options: RenderOptions,
+) -> (Cow<'a, ReplayInfo>, Cow<'a, str>) {
+    if options.anonymize {
+        (
+            Cow::Owned(anonymize_view(replay)),
+            Cow::Owned(replay.map_name.clone()),
+        )
+    } else {
+        (Cow::Borrowed(replay), Cow::Borrowed(filename))
+    }
+}
+
 /// Render a map visualization with player positions
 pub fn render_map(
     replay: &ReplayInfo,
-    font: &FontArc,
-    map_image: &RgbImage,
+    assets: RenderAssets<'_>,
     filename: &str,
+    options: RenderOptions,
 ) -> Result<Vec<u8>, String> {
-    let mut img = map_image.clone();
-
-    // Font sizes
-    let font_large = PxScale::from(24.0);
-    let font_small = PxScale::from(20.0);
-
-    // Draw player info at each position (text only, no circles)
-    for player in &replay.players {
-        draw_player_text(&mut img, player, font, font_large, font_small);
-    }
-
-    // Draw centered info (Filename, Date, Duration, Winner)
-    draw_center_info(&mut img, replay, font, font_large, filename);
+    let mut timings = TimingBreakdown::default();
+    render_map_timed(replay, assets, filename, options, &mut timings)
+}
 
-    // Draw spectators if any
-    draw_spectators(&mut img, replay, font, font_small);
+/// [`render_map`], also filling in the layout, drawing and encoding stages of `timings`
+pub fn render_map_timed(
+    replay: &ReplayInfo,
+    assets: RenderAssets<'_>,
+    filename: &str,
+    options: RenderOptions,
+    timings: &mut TimingBreakdown,
+) -> Result<Vec<u8>, String> {
+    let mut clock = StageClock::start(true);
+    let RenderAssets {
+        font, map_image, ..
+    } = assets;
+    let (replay, filename) = shown_replay(replay, filename, options);
+    let placements = label_placements(&replay, assets.layout, options)
+        .with_faction_icons(assets.faction_icons)
+        .cropped_to_action(
+            &replay,
+            font,
+            map_image.dimensions(),
+            &filename,
+            options.palette,
+        );
+    timings.layout = clock.lap();
+    let img = render_frame(
+        &replay,
+        font,
+        map_image,
+        &placements,
+        &filename,
+        RevealStage::Winner,
+        options,
+    );
+    timings.drawing = clock.lap();
 
     // Encode directly to JPEG with quality 85 (already RGB, no conversion needed)
     let mut buffer = Vec::new();
@@ -158,226 +304,382 @@ pub fn render_map(
             image::ExtendedColorType::Rgb8,
         )
         .map_err(|e| format!("Failed to encode image: {}", e))?;
+    timings.encoding = clock.lap();
 
     Ok(buffer)
 }
 
-/// Draw player text at their position (center-aligned)
-fn draw_player_text(
-    img: &mut RgbImage,
-    player: &Player,
+/// Draw one frame showing as much as `stage` allows
+pub(super) fn render_frame(
+    replay: &ReplayInfo,
     font: &FontArc,
-    font_large: PxScale,
-    font_small: PxScale,
-) {
-    let (width, height) = (img.width() as f32, img.height() as f32);
-    let scale_x = width / MAP_ASSET_WIDTH;
-    let scale_y = height / MAP_ASSET_HEIGHT;
-
-    // Get position from map coordinates
-    let img_pos = if let Some(pos) = &player.map_position {
-        if pos.is_valid() {
-            Some(get_position(pos.x, pos.y).coords())
-        } else {
-            None
-        }
-    } else {
-        None
-    };
-
-    let img_pos = match img_pos {
-        Some(p) => p,
-        None => return, // Skip players without valid positions
-    };
-
-    // Circle center in rendered image pixels
-    let center_x = (img_pos.0 * scale_x) as i32;
-    let center_y = (img_pos.1 * scale_y) as i32;
-
-    // Get player color
-    let color = player.display_color();
-    let text_color = Rgb([color[0], color[1], color[2]]);
-
-    // Truncate name to 12 chars
-    let name: String = player.name.chars().take(12).collect();
-
-    let pad = 3;
-    let name_h = 24;
-    let faction_h = 20;
-    let gap = 2; // gap between name and faction rows
-    let total_h = name_h + gap + faction_h;
-
-    // Vertically center the two-line block on circle center
-    let block_top = center_y - total_h / 2;
-
-    // --- Name (top row, centered horizontally) ---
-    let name_w = measure_text_width(&name, font, font_large);
-    let name_x = center_x - name_w / 2;
-    let name_y = block_top;
-
-    draw_rect_alpha(
-        img,
-        name_x - pad,
-        name_y - 2,
-        name_w + pad * 2,
-        name_h + 4,
-        [0, 0, 0, 180],
+    map_image: &RgbImage,
+    placements: &FramePlacements<'_>,
+    filename: &str,
+    stage: RevealStage,
+    options: RenderOptions,
+) -> RgbImage {
+    let items = layout_frame(
+        replay,
+        font,
+        placements,
+        map_image.dimensions(),
+        filename,
+        stage,
+        options.palette,
     );
+    let mut img = frame_map(map_image, placements).into_owned();
+    rasterize(&mut img, &items, font);
 
-    draw_text_mut(img, text_color, name_x, name_y, font_large, font, &name);
-
-    // --- Faction (bottom row, centered horizontally) ---
-    let faction_text = player.display_faction().to_string();
-    let faction_w = measure_text_width(&faction_text, font, font_small);
-    let faction_x = center_x - faction_w / 2;
-    let faction_y = block_top + name_h + gap;
-
-    draw_rect_alpha(
-        img,
-        faction_x - pad,
-        faction_y - 2,
-        faction_w + pad * 2,
-        faction_h + 4,
-        [0, 0, 0, 180],
-    );
+    if shows_activity(replay, stage) {
+        draw_activity_strip(&mut img, replay);
+    }
+    if options.show_lobby_panel {
+        draw_lobby_panel(&mut img, replay, font, stage, options.palette);
+    }
 
-    draw_text_mut(
-        img,
-        text_color,
-        faction_x,
-        faction_y,
-        font_small,
-        font,
-        &faction_text,
-    );
+    img
 }
 
-/// Draw centered info (Filename, Date, Duration, Winner)
-fn draw_center_info(
-    img: &mut RgbImage,
-    replay: &ReplayInfo,
-    font: &FontArc,
-    scale: PxScale,
-    filename: &str,
-) {
-    let (width, height) = (img.width() as i32, img.height() as i32);
-    let center_x = width / 2;
-    let center_y = height / 2;
-
-    // Format filename: strip extension (case-insensitive), cap at 30 chars
-    let display_name = match filename.rsplit_once('.') {
-        Some((stem, ext)) if ext.eq_ignore_ascii_case("BfME2Replay") => stem,
-        _ => filename,
-    };
-    let display_name: String = display_name.chars().take(30).collect();
-
-    // Format info text
-    let date_text = format!("Date: {}", replay.start_date_formatted());
-    let duration_text = format!("Duration: {}", replay.duration_formatted());
-
-    // Build info lines
-    let mut info_lines: Vec<(&str, Rgb<u8>)> = vec![
-        (&display_name, Rgb([255, 255, 255])),
-        (&date_text, Rgb([200, 200, 200])),
-        (&duration_text, Rgb([200, 200, 200])),
-    ];
-
-    // Only show winner if known
-    let winner_text = if replay.game_crashed {
-        Some(("Winner: Not Concluded".to_string(), Rgb([200, 100, 100])))
-    } else if replay.winner == Winner::LikelyLeftTeam || replay.winner == Winner::LikelyRightTeam {
-        Some((
-            format!("Winner: {}", replay.winner.display_text()),
-            Rgb([255, 200, 80]),
-        ))
-    } else if replay.winner != Winner::Unknown {
-        Some((
-            format!("Winner: {}", replay.winner.display_text()),
-            Rgb([255, 215, 0]),
-        ))
-    } else {
-        None
-    };
-    if let Some((ref text, color)) = winner_text {
-        info_lines.push((text, color));
+/// The map under a frame: cropped when its placements are
+pub(super) fn frame_map<'a>(
+    map_image: &'a RgbImage,
+    placements: &FramePlacements<'_>,
+) -> Cow<'a, RgbImage> {
+    match placements.crop() {
+        Some(crop) => Cow::Owned(
+            image::imageops::crop_imm(
+                map_image,
+                crop.x as u32,
+                crop.y as u32,
+                crop.w as u32,
+                crop.h as u32,
+            )
+            .to_image(),
+        ),
+        None => Cow::Borrowed(map_image),
     }
+}
 
-    let line_height = 28;
-    let total_height = (info_lines.len() as i32) * line_height;
-    let start_y = center_y - total_height / 2;
-
-    // Calculate max width for background using accurate measurement
-    let max_width = info_lines
-        .iter()
-        .map(|(text, _)| measure_text_width(text, font, scale))
-        .max()
-        .unwrap_or(0);
-
-    // Draw background rectangle
-    let padding = 10;
-    draw_rect_alpha(
-        img,
-        center_x - max_width / 2 - padding,
-        start_y - padding,
-        max_width + padding * 2,
-        total_height + padding * 2,
-        [0, 0, 0, 160],
-    );
+/// Draw laid-out items onto `img`, in order
+pub(super) fn rasterize(img: &mut RgbImage, items: &[LayoutItem], font: &FontArc) {
+    for item in items {
+        match item {
+            LayoutItem::Rect { bounds, color } => {
+                draw_rect_alpha(img, bounds.x, bounds.y, bounds.w, bounds.h, *color)
+            }
+            LayoutItem::Text {
+                text,
+                x,
+                y,
+                scale,
+                color,
+                ..
+            } => draw_text_mut(img, *color, *x, *y, *scale, font, text),
+            LayoutItem::Icon {
+                icon,
+                x,
+                baseline,
+                height,
+                color,
+            } => {
+                if let Some(sprite) = sprite(*icon) {
+                    draw_icon(img, sprite, *x, *baseline, *height, *color);
+                }
+            }
+            LayoutItem::FactionIcon { image, x, y } => draw_image_alpha(img, image, *x, *y),
+        }
+    }
+}
 
-    // Draw info text (centered)
-    for (i, (text, color)) in info_lines.iter().enumerate() {
-        let text_w = measure_text_width(text, font, scale);
-        let text_x = center_x - text_w / 2;
-        let text_y = start_y + (i as i32) * line_height;
-        draw_text_mut(img, *color, text_x, text_y, scale, font, text);
+/// Blend `image` onto `img` with its top-left corner at `x, y`, by its alpha
+fn draw_image_alpha(img: &mut RgbImage, image: &RgbaImage, x: i32, y: i32) {
+    for (ix, iy, pixel) in image.enumerate_pixels() {
+        let (px, py) = (x + ix as i32, y + iy as i32);
+        if px < 0 || py < 0 || px >= img.width() as i32 || py >= img.height() as i32 {
+            continue;
+        }
+        let a = pixel[3] as f32 / 255.0;
+        let target = img.get_pixel_mut(px as u32, py as u32);
+        for c in 0..3 {
+            target[c] = (target[c] as f32 * (1.0 - a) + pixel[c] as f32 * a) as u8;
+        }
     }
 }
 
-/// Draw spectators above and below center
-fn draw_spectators(img: &mut RgbImage, replay: &ReplayInfo, font: &FontArc, scale: PxScale) {
-    if replay.spectators.is_empty() {
-        return;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, MapSpot, PlayerBuilder};
+    use crate::renderer::lobby::LOBBY_PANEL_WIDTH;
+    use crate::renderer::winner::WinnerIcon;
+    use image::Rgb;
+
+    #[test]
+    fn names_are_cut_to_a_pixel_budget() {
+        let font = test_font();
+        let scale = PxScale::from(24.0);
+        let width = |text: &str| measure_text_width(text, &font, scale);
+        assert_ne!(font.glyph_id('…').0, 0);
+
+        // Short names are left alone, without an ellipsis
+        assert_eq!(fit_text("Ann", &font, scale, 150), "Ann");
+
+        let budget = width("WWWWWW");
+        let cut = fit_text("WWWWWWWWWWWW", &font, scale, budget);
+        assert!(cut.ends_with('…') && width(&cut) <= budget, "{}", cut);
+
+        // Emoji sequences and combining marks are kept whole or dropped whole
+        for name in [
+            "Zoë👨‍👩‍👧‍👦👨‍👩‍👧‍👦👨‍👩‍👧‍👦",
+            "Ame\u{301}lie\u{301}e\u{301}e\u{301}e\u{301}e\u{301}",
+        ] {
+            let budget = width(name) - 1;
+            let cut = fit_text(name, &font, scale, budget);
+            assert!(cut.ends_with('…') && width(&cut) <= budget, "{}", cut);
+            let kept = cut.trim_end_matches('…');
+            assert!(name.starts_with(kept));
+            assert!(
+                name[kept.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(|c| !('\u{300}'..='\u{36f}').contains(&c) && c != '\u{200d}'),
+                "{:?} splits a grapheme",
+                cut
+            );
+        }
+
+        // Nothing fits: only the ellipsis
+        assert_eq!(fit_text("Ann", &font, scale, 1), "…");
     }
 
-    let (width, height) = (img.width() as i32, img.height() as i32);
-    let center_x = width / 2;
-    let spectator_color = Rgb([180, 180, 180]);
-
-    // First spectator near top
-    {
-        let spec_y = (height as f32 * 0.08) as i32;
-        let spec_text = format!("Obs: {}", replay.spectators[0].name);
-        let spec_w = measure_text_width(&spec_text, font, scale);
-        let spec_x = center_x - spec_w / 2;
-
-        draw_rect_alpha(img, spec_x - 3, spec_y - 2, spec_w + 6, 24, [0, 0, 0, 160]);
-        draw_text_mut(
-            img,
-            spectator_color,
-            spec_x,
-            spec_y,
-            scale,
-            font,
-            &spec_text,
+    #[test]
+    fn winner_prefixes_exist_in_the_font() {
+        let font = test_font();
+        for icon in [
+            WinnerIcon::TrophyFilled,
+            WinnerIcon::TrophyOutline,
+            WinnerIcon::BrokenFlag,
+        ] {
+            let prefix = DisplayPalette::ColorBlind
+                .winner_prefix(Some(icon))
+                .unwrap();
+            assert!(
+                prefix.chars().all(|c| font.glyph_id(c).0 != 0),
+                "{:?} prefix {} is not in the font",
+                icon,
+                prefix
+            );
+        }
+    }
+
+    #[test]
+    fn lobby_panel_pushes_left_labels_right() {
+        let mut alice = PlayerBuilder::test_player("Alice", 1, 0, Faction::Men);
+        alice.spot = Some(MapSpot::MidLeft);
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![alice]);
+        let font = test_font();
+        let background = RgbImage::from_pixel(1000, 1000, Rgb([40, 60, 40]));
+        let layout = MapLayout::default();
+        let render = |show_lobby_panel| {
+            let options = RenderOptions {
+                show_lobby_panel,
+                show_empty_spots: Some(false),
+                ..Default::default()
+            };
+            let placements = label_placements(&replay, &layout, options);
+            render_frame(
+                &replay,
+                &font,
+                &background,
+                &placements,
+                "test.BfME2Replay",
+                RevealStage::Winner,
+                options,
+            )
+        };
+        let touched = |img: &RgbImage, xs: std::ops::Range<u32>| {
+            (0..1000).any(|y| {
+                xs.clone()
+                    .any(|x| img.get_pixel(x, y) != background.get_pixel(x, y))
+            })
+        };
+        let (from, to) = (LOBBY_PANEL_WIDTH as u32 - 8, LOBBY_PANEL_WIDTH as u32);
+        // The label normally sits where the panel goes...
+        let without_panel = render(false);
+        assert!(touched(&without_panel, 0..from));
+        assert!(!touched(&without_panel, to..350));
+        // ...and moves right of it, past a clear gap, when the panel is drawn
+        let with_panel = render(true);
+        assert!(!touched(&with_panel, from..to));
+        assert!(touched(&with_panel, to..350));
+        assert!(font.glyph_id('–').0 != 0);
+    }
+
+    #[test]
+    fn label_backdrop_is_tinted_by_faction() {
+        let font = test_font();
+        let background = RgbImage::from_pixel(1000, 1000, Rgb([40, 60, 40]));
+        let layout = MapLayout::default();
+        let options = RenderOptions::default();
+        let frame = |faction| {
+            let mut tom = PlayerBuilder::test_player("Tom", 1, 0, faction);
+            tom.spot = Some(MapSpot::TopLeft);
+            let replay = ReplayInfo::new("map wor rhun".to_string(), vec![tom]);
+            let placements = label_placements(&replay, &layout, options);
+            let items = layout_frame(
+                &replay,
+                &font,
+                &placements,
+                background.dimensions(),
+                "test.BfME2Replay",
+                RevealStage::Winner,
+                options.palette,
+            );
+            let image = render_frame(
+                &replay,
+                &font,
+                &background,
+                &placements,
+                "test.BfME2Replay",
+                RevealStage::Winner,
+                options,
+            );
+            (items, image)
+        };
+        let (items, men) = frame(Faction::Men);
+        let (_, mordor) = frame(Faction::Mordor);
+
+        // Top left corner of the name's backdrop, inside its padding
+        let name = items
+            .iter()
+            .find(|item| matches!(item, LayoutItem::Text { text, .. } if text == "Tom"))
+            .unwrap()
+            .bounds();
+        let backdrop = items
+            .iter()
+            .find(|item| matches!(item, LayoutItem::Rect { .. }) && item.bounds().contains(&name))
+            .unwrap()
+            .bounds();
+        let (x, y) = (backdrop.x as u32 + 1, backdrop.y as u32 + 1);
+        let (good, evil) = (men.get_pixel(x, y).0, mordor.get_pixel(x, y).0);
+        assert_ne!(good, evil);
+        // Blue-leaning for the good side, green-leaning for the evil side
+        assert!(
+            good[2] > evil[2] && evil[1] > good[1],
+            "{:?} {:?}",
+            good,
+            evil
         );
     }
 
-    // Second spectator near bottom
-    if replay.spectators.len() >= 2 {
-        let spec_y = (height as f32 * 0.92) as i32;
-        let spec_text = format!("Obs: {}", replay.spectators[1].name);
-        let spec_w = measure_text_width(&spec_text, font, scale);
-        let spec_x = center_x - spec_w / 2;
-
-        draw_rect_alpha(img, spec_x - 3, spec_y - 2, spec_w + 6, 24, [0, 0, 0, 160]);
-        draw_text_mut(
-            img,
-            spectator_color,
-            spec_x,
-            spec_y,
-            scale,
-            font,
-            &spec_text,
+    #[test]
+    fn empty_spots_are_marked_in_small_games() {
+        let player = |name: &str, slot, spot| {
+            let mut player = PlayerBuilder::test_player(name, slot as i8 + 1, slot, Faction::Men);
+            player.spot = Some(spot);
+            player
+        };
+        let replay = ReplayInfo::new(
+            "map wor rhun".to_string(),
+            vec![
+                player("Alice", 0, MapSpot::TopLeft),
+                player("Bob", 1, MapSpot::BottomRight),
+            ],
         );
+        let font = test_font();
+        let background = RgbImage::from_pixel(1000, 1000, Rgb([40, 60, 40]));
+        let layout = MapLayout::default();
+        let render = |show_empty_spots| {
+            let options = RenderOptions {
+                show_empty_spots,
+                ..Default::default()
+            };
+            let placements = label_placements(&replay, &layout, options);
+            render_frame(
+                &replay,
+                &font,
+                &background,
+                &placements,
+                "test.BfME2Replay",
+                RevealStage::Winner,
+                options,
+            )
+        };
+        let (asset_w, asset_h) = layout.asset_size();
+        let anchor = |spot| {
+            let (x, y) = layout.spot(spot).coords;
+            ((x * 1000.0 / asset_w) as u32, (y * 1000.0 / asset_h) as u32)
+        };
+        let empty = [
+            MapSpot::MidLeft,
+            MapSpot::BottomLeft,
+            MapSpot::TopRight,
+            MapSpot::MidRight,
+        ];
+
+        // A 1v1 leaves four spots empty, each marked on a dimmed backdrop
+        let marked = render(None);
+        for spot in empty {
+            let (x, y) = anchor(spot);
+            // Top padding of the backdrop, above the text
+            let backdrop = marked.get_pixel(x, y - 10);
+            assert_ne!(backdrop, background.get_pixel(x, y - 10), "{:?}", spot);
+            assert!(backdrop.0[1] < 40, "{:?} {:?}", spot, backdrop);
+        }
+
+        // Turned off, the same spots stay bare
+        let bare = render(Some(false));
+        for spot in empty {
+            let (x, y) = anchor(spot);
+            assert_eq!(bare.get_pixel(x, y - 10), background.get_pixel(x, y - 10));
+        }
+    }
+
+    #[test]
+    fn color_blind_badge_is_drawn_left_of_the_name() {
+        // A color outside the game palette, so only the badge changes
+        let mut alice = PlayerBuilder::test_player("Alice", 1, 0, Faction::Men);
+        alice.color_rgb = [255, 0, 0];
+        alice.spot = Some(MapSpot::TopLeft);
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![alice]);
+        let font = test_font();
+        let background = RgbImage::from_pixel(1000, 1000, Rgb([40, 60, 40]));
+        let layout = MapLayout::default();
+        let placements = label_placements(&replay, &layout, RenderOptions::default());
+        let render = |palette| {
+            render_frame(
+                &replay,
+                &font,
+                &background,
+                &placements,
+                "test.BfME2Replay",
+                RevealStage::Winner,
+                RenderOptions {
+                    palette,
+                    ..Default::default()
+                },
+            )
+        };
+        let standard = render(DisplayPalette::Standard);
+        let color_blind = render(DisplayPalette::ColorBlind);
+
+        // Left edge of the standard label in the quarter holding the spot
+        let label_left = (0..500)
+            .flat_map(|y| (0..500).map(move |x| (x, y)))
+            .filter(|&(x, y)| standard.get_pixel(x, y) != background.get_pixel(x, y))
+            .map(|(x, _)| x)
+            .min()
+            .unwrap();
+        let changed: Vec<(u32, u32)> = (0..1000)
+            .flat_map(|y| (0..1000).map(move |x| (x, y)))
+            .filter(|&(x, y)| standard.get_pixel(x, y) != color_blind.get_pixel(x, y))
+            .collect();
+        assert!(!changed.is_empty());
+        assert!(changed.iter().all(|&(x, _)| x < label_left));
+        // Badge fill in the player color, with dark glyphs on it
+        let badge_pixels = changed.iter().map(|&(x, y)| *color_blind.get_pixel(x, y));
+        assert!(badge_pixels.clone().any(|p| p == Rgb([255, 0, 0])));
+        assert!(badge_pixels.clone().any(|p| p.0.iter().all(|&c| c < 60)));
     }
 }
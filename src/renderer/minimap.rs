@@ -0,0 +1,290 @@
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+/// Magic of an uncompressed map file
+const MAP_MAGIC: &[u8; 4] = b"CkMp";
+
+/// Magic of a RefPack-compressed map file
+const COMPRESSED_MAP_MAGIC: &[u8; 4] = b"EAR\0";
+
+/// Name of the chunk holding the TGA preview
+const PREVIEW_CHUNK: &str = "MapPreview";
+
+/// Size of a chunk header: id (u32), version (u16), data size (u32)
+const CHUNK_HEADER_LEN: usize = 10;
+
+/// Size of a TGA file header
+const TGA_HEADER_LEN: usize = 18;
+
+/// Little-endian reader over a byte slice that fails instead of panicking
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| format!("Map file truncated at byte {}", self.pos))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// Read the minimap preview embedded in a BFME2 `.map` file
+pub fn load_minimap_from_map_file(path: &Path) -> Result<RgbImage, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read map file: {}", e))?;
+    minimap_from_map_bytes(&data)
+}
+
+/// Find the preview chunk in a map container and decode it. The container
+/// starts with `CkMp`, a table of `(name, id)` chunk names, then the
+/// top-level chunks, each an id, a version and a data size ahead of its data.
+fn minimap_from_map_bytes(data: &[u8]) -> Result<RgbImage, String> {
+    if data.starts_with(COMPRESSED_MAP_MAGIC) {
+        return Err("Compressed map files are not supported".to_string());
+    }
+    if !data.starts_with(MAP_MAGIC) {
+        return Err("Not a map file".to_string());
+    }
+    let mut reader = Reader::new(&data[MAP_MAGIC.len()..]);
+
+    let mut preview_id = None;
+    for _ in 0..reader.u32()? {
+        let len = reader.u8()? as usize;
+        let name = reader.bytes(len)?;
+        let id = reader.u32()?;
+        if name == PREVIEW_CHUNK.as_bytes() {
+            preview_id = Some(id);
+        }
+    }
+    let preview_id = preview_id.ok_or("Map file has no preview")?;
+
+    while !reader.is_empty() {
+        if reader.data.len() - reader.pos < CHUNK_HEADER_LEN {
+            break;
+        }
+        let id = reader.u32()?;
+        let _version = reader.u16()?;
+        let size = reader.u32()? as usize;
+        let chunk = reader.bytes(size)?;
+        if id == preview_id {
+            return decode_tga(chunk);
+        }
+    }
+    Err("Map file has no preview".to_string())
+}
+
+/// Decode an uncompressed (type 2) or RLE (type 10) true-color TGA with 24
+/// or 32 bits per pixel; alpha is dropped
+fn decode_tga(data: &[u8]) -> Result<RgbImage, String> {
+    let mut reader = Reader::new(data);
+    let header = reader.bytes(TGA_HEADER_LEN)?;
+    let id_len = header[0] as usize;
+    let color_map_type = header[1];
+    let image_type = header[2];
+    let color_map_len = u16::from_le_bytes([header[5], header[6]]) as usize;
+    let color_map_entry_bits = header[7] as usize;
+    let width = u16::from_le_bytes([header[12], header[13]]) as u32;
+    let height = u16::from_le_bytes([header[14], header[15]]) as u32;
+    let bits = header[16];
+    let top_down = header[17] & 0x20 != 0;
+
+    let rle = match image_type {
+        2 => false,
+        10 => true,
+        other => return Err(format!("Unsupported TGA image type {}", other)),
+    };
+    let bytes_per_pixel = match bits {
+        24 => 3,
+        32 => 4,
+        other => return Err(format!("Unsupported TGA depth {}", other)),
+    };
+    if width == 0 || height == 0 {
+        return Err("TGA image is empty".to_string());
+    }
+    reader.bytes(id_len)?;
+    if color_map_type != 0 {
+        reader.bytes(color_map_len * color_map_entry_bits.div_ceil(8))?;
+    }
+
+    // Pixels in file order, as BGR(A)
+    let pixel_count = (width * height) as usize;
+    let mut pixels: Vec<Rgb<u8>> = Vec::with_capacity(pixel_count);
+    let read_pixel = |reader: &mut Reader| -> Result<Rgb<u8>, String> {
+        let p = reader.bytes(bytes_per_pixel)?;
+        Ok(Rgb([p[2], p[1], p[0]]))
+    };
+    while pixels.len() < pixel_count {
+        if !rle {
+            pixels.push(read_pixel(&mut reader)?);
+            continue;
+        }
+        let packet = reader.u8()?;
+        let count = ((packet & 0x7F) as usize + 1).min(pixel_count - pixels.len());
+        if packet & 0x80 != 0 {
+            let pixel = read_pixel(&mut reader)?;
+            pixels.extend(std::iter::repeat_n(pixel, count));
+        } else {
+            for _ in 0..count {
+                pixels.push(read_pixel(&mut reader)?);
+            }
+        }
+    }
+
+    // Rows are stored bottom-up unless the descriptor says otherwise
+    Ok(RgbImage::from_fn(width, height, |x, y| {
+        let row = if top_down { y } else { height - 1 - y };
+        pixels[(row * width + x) as usize]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    fn tga_header(image_type: u8, width: u16, height: u16, bits: u8, descriptor: u8) -> Vec<u8> {
+        let mut out = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out.extend_from_slice(&[bits, descriptor]);
+        out
+    }
+
+    fn bgr(rgb: [u8; 3]) -> [u8; 3] {
+        [rgb[2], rgb[1], rgb[0]]
+    }
+
+    /// 3x2 TGA: bottom row red, green, blue; top row all blue (stored bottom-up)
+    fn uncompressed_tga() -> Vec<u8> {
+        let mut out = tga_header(2, 3, 2, 24, 0);
+        for rgb in [RED, GREEN, BLUE, BLUE, BLUE, BLUE] {
+            out.extend_from_slice(&bgr(rgb));
+        }
+        out
+    }
+
+    /// The same image, RLE-compressed with 32-bit pixels
+    fn rle_tga() -> Vec<u8> {
+        let mut out = tga_header(10, 3, 2, 32, 0);
+        // Raw packet of red and green, then a run of four blues
+        out.push(1);
+        for rgb in [RED, GREEN] {
+            out.extend_from_slice(&bgr(rgb));
+            out.push(255);
+        }
+        out.push(0x80 | 3);
+        out.extend_from_slice(&bgr(BLUE));
+        out.push(255);
+        out
+    }
+
+    /// A map container with a dummy chunk ahead of the preview chunk
+    fn map_file(preview: &[u8]) -> Vec<u8> {
+        let mut out = MAP_MAGIC.to_vec();
+        out.extend_from_slice(&2u32.to_le_bytes());
+        for (name, id) in [("HeightMapData", 1u32), (PREVIEW_CHUNK, 2)] {
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&id.to_le_bytes());
+        }
+        for (id, data) in [(1u32, &[7u8; 5][..]), (2, preview)] {
+            out.extend_from_slice(&id.to_le_bytes());
+            out.extend_from_slice(&1u16.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    fn assert_test_image(img: &RgbImage) {
+        assert_eq!(img.dimensions(), (3, 2));
+        assert_eq!(img.get_pixel(0, 1).0, RED);
+        assert_eq!(img.get_pixel(1, 1).0, GREEN);
+        assert_eq!(img.get_pixel(2, 1).0, BLUE);
+        assert_eq!(img.get_pixel(0, 0).0, BLUE);
+    }
+
+    #[test]
+    fn decodes_uncompressed_and_rle_previews() {
+        assert_test_image(&minimap_from_map_bytes(&map_file(&uncompressed_tga())).unwrap());
+        assert_test_image(&minimap_from_map_bytes(&map_file(&rle_tga())).unwrap());
+    }
+
+    #[test]
+    fn top_down_rows_are_kept_in_order() {
+        let mut tga = uncompressed_tga();
+        tga[17] = 0x20;
+        let img = decode_tga(&tga).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, RED);
+        assert_eq!(img.get_pixel(0, 1).0, BLUE);
+    }
+
+    #[test]
+    fn load_map_falls_back_to_the_map_file_preview() {
+        let assets = tempfile::tempdir().unwrap();
+        let maps = assets.path().join("maps");
+        std::fs::create_dir(&maps).unwrap();
+        std::fs::write(maps.join("map new.map"), map_file(&rle_tga())).unwrap();
+
+        let (img, layout) = crate::renderer::load_map("map new", assets.path()).unwrap();
+        // Stretched to the default layout's asset, then scaled for output
+        let (asset_w, asset_h) = layout.asset_size();
+        assert_eq!(img.width(), 1000);
+        assert!((img.width() as f32 / img.height() as f32 - asset_w / asset_h).abs() < 0.01);
+        let [r, g, b] = img.get_pixel(0, img.height() - 1).0;
+        assert!(r > 200 && g < 60 && b < 60, "{:?}", (r, g, b));
+        assert_eq!(img.get_pixel(img.width() - 1, 0).0, BLUE);
+
+        assert!(crate::renderer::load_map("map missing", assets.path()).is_err());
+    }
+
+    #[test]
+    fn unsupported_and_broken_files_are_errors() {
+        assert!(minimap_from_map_bytes(b"EAR\0\x10\x00\x00\x00").is_err());
+        assert!(minimap_from_map_bytes(b"PNG").is_err());
+
+        let mut no_preview = MAP_MAGIC.to_vec();
+        no_preview.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(
+            minimap_from_map_bytes(&no_preview).unwrap_err(),
+            "Map file has no preview"
+        );
+
+        // Truncated pixel data
+        let tga = uncompressed_tga();
+        assert!(minimap_from_map_bytes(&map_file(&tga[..tga.len() - 1])).is_err());
+        // Color-mapped TGA
+        assert!(decode_tga(&tga_header(1, 3, 2, 8, 0)).is_err());
+    }
+}
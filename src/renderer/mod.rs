@@ -1,3 +1,26 @@
+mod activity;
+mod faction_icons;
+mod frame_layout;
+mod layout;
+mod lobby;
 mod map;
+mod markup;
+mod minimap;
+mod palette;
+mod reveal;
+mod stats;
+mod svg;
+mod winner;
 
-pub use map::{load_font, load_map_image, render_map};
+pub use faction_icons::{FactionIcons, load_faction_icons};
+pub use layout::{LabelAnchor, MapLayout, SpotLayout};
+pub use map::{
+    RenderAssets, RenderOptions, RevealStage, display_filename, load_font, load_map, render_map,
+    render_map_timed, shown_replay,
+};
+pub use markup::{base64, escape_markup};
+pub use minimap::load_minimap_from_map_file;
+pub use palette::DisplayPalette;
+pub use reveal::{UPLOAD_BUDGET_BYTES, encode_gif_within_budget, render_reveal};
+pub use stats::{ArchiveStats, render_archive_stats};
+pub use svg::render_map_svg;
@@ -0,0 +1,129 @@
+use super::winner::WinnerIcon;
+use crate::models::{PLAYER_COLORS, Player};
+use image::Rgb;
+
+/// Color-blind safe text colors, indexed like `PLAYER_COLORS` (Okabe-Ito
+/// based, lightened where needed to stay readable on the dark map)
+const COLOR_BLIND_COLORS: [[u8; 3]; 10] = [
+    [86, 180, 233],  // 0: Blue -> sky blue
+    [213, 94, 0],    // 1: Red -> vermillion
+    [240, 228, 66],  // 2: Yellow
+    [0, 158, 115],   // 3: Green -> bluish green
+    [230, 159, 0],   // 4: Orange
+    [170, 225, 255], // 5: Teal -> pale blue
+    [204, 121, 167], // 6: Purple -> reddish purple
+    [255, 190, 220], // 7: Pink
+    [150, 150, 150], // 8: Gray
+    [240, 240, 240], // 9: White
+];
+
+/// Colors and markers used to draw players; `PLAYER_COLORS` stays the data color
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayPalette {
+    /// In-game colors, as the players saw them
+    #[default]
+    Standard,
+    /// Color-blind safe colors, plus a team/slot badge per name and a shape
+    /// before the winner line so nothing relies on color alone
+    ColorBlind,
+}
+
+impl DisplayPalette {
+    /// Parse a `DISPLAY_PALETTE` value (case-insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "standard" => Some(DisplayPalette::Standard),
+            "colorblind" | "color-blind" => Some(DisplayPalette::ColorBlind),
+            _ => None,
+        }
+    }
+
+    /// Text color for a player's label
+    pub(super) fn player_color(self, player: &Player) -> Rgb<u8> {
+        let data_color = player.display_color();
+        let color = match self {
+            DisplayPalette::Standard => data_color,
+            DisplayPalette::ColorBlind => PLAYER_COLORS
+                .iter()
+                .position(|&c| c == data_color)
+                .map_or(data_color, |i| COLOR_BLIND_COLORS[i]),
+        };
+        Rgb(color)
+    }
+
+    /// Badge drawn before a player's name: team number and 1-based slot
+    pub(super) fn badge(self, player: &Player) -> Option<String> {
+        match self {
+            DisplayPalette::Standard => None,
+            DisplayPalette::ColorBlind => Some(format!("{}-{}", player.team, player.slot + 1)),
+        }
+    }
+
+    /// Shape prefixed to the winner line, distinct per outcome (glyphs the
+    /// bundled font has; it lacks stars and check marks)
+    pub(super) fn winner_prefix(self, icon: Option<WinnerIcon>) -> Option<&'static str> {
+        match (self, icon?) {
+            (DisplayPalette::Standard, _) => None,
+            (DisplayPalette::ColorBlind, WinnerIcon::TrophyFilled) => Some("●"),
+            (DisplayPalette::ColorBlind, WinnerIcon::TrophyOutline) => Some("○"),
+            (DisplayPalette::ColorBlind, WinnerIcon::BrokenFlag) => Some("×"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, PlayerBuilder};
+
+    fn player(color_rgb: [u8; 3]) -> Player {
+        let mut player = PlayerBuilder::test_player("Alice", 2, 3, Faction::Men);
+        player.color_rgb = color_rgb;
+        player
+    }
+
+    #[test]
+    fn palette_names() {
+        assert_eq!(
+            DisplayPalette::from_name("ColorBlind"),
+            Some(DisplayPalette::ColorBlind)
+        );
+        assert_eq!(
+            DisplayPalette::from_name(" standard "),
+            Some(DisplayPalette::Standard)
+        );
+        assert_eq!(DisplayPalette::from_name("neon"), None);
+    }
+
+    #[test]
+    fn color_blind_palette_swaps_only_display_colors() {
+        let red = player(PLAYER_COLORS[1]);
+        assert_eq!(
+            DisplayPalette::Standard.player_color(&red),
+            Rgb(PLAYER_COLORS[1])
+        );
+        assert_eq!(
+            DisplayPalette::ColorBlind.player_color(&red),
+            Rgb(COLOR_BLIND_COLORS[1])
+        );
+        // The data color is untouched
+        assert_eq!(red.display_color(), PLAYER_COLORS[1]);
+        // Colors outside the game palette are kept
+        let custom = player([1, 2, 3]);
+        assert_eq!(
+            DisplayPalette::ColorBlind.player_color(&custom),
+            Rgb([1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn markers_only_in_color_blind_mode() {
+        let p = player(PLAYER_COLORS[0]);
+        assert_eq!(DisplayPalette::Standard.badge(&p), None);
+        assert_eq!(DisplayPalette::ColorBlind.badge(&p).as_deref(), Some("2-4"));
+        let icon = Some(WinnerIcon::TrophyFilled);
+        assert_eq!(DisplayPalette::Standard.winner_prefix(icon), None);
+        assert_eq!(DisplayPalette::ColorBlind.winner_prefix(icon), Some("●"));
+        assert_eq!(DisplayPalette::ColorBlind.winner_prefix(None), None);
+    }
+}
@@ -0,0 +1,174 @@
+use super::frame_layout::label_placements;
+use super::map::{RenderAssets, RenderOptions, RevealStage, render_frame, shown_replay};
+use crate::models::ReplayInfo;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, RgbImage};
+
+/// Upload budget for a rendered image (Discord's default attachment limit is 10MB)
+pub const UPLOAD_BUDGET_BYTES: usize = 8 * 1024 * 1024;
+
+/// How long each reveal frame is shown
+const REVEAL_FRAME_MS: u32 = 1500;
+
+/// Frame scales tried in order until the animation fits the budget
+const REVEAL_SCALES: [f32; 3] = [1.0, 0.75, 0.5];
+
+/// GIF quantizer speed (1 = best quality, 30 = fastest)
+const GIF_SPEED: i32 = 10;
+
+/// Render a 3-frame animated GIF revealing positions, then players, then the winner.
+/// All frames share one label layout so they line up exactly.
+pub fn render_reveal(
+    replay: &ReplayInfo,
+    assets: RenderAssets<'_>,
+    filename: &str,
+    options: RenderOptions,
+) -> Result<Vec<u8>, String> {
+    let RenderAssets {
+        font, map_image, ..
+    } = assets;
+    let (replay, filename) = shown_replay(replay, filename, options);
+    let placements = label_placements(&replay, assets.layout, options)
+        .with_faction_icons(assets.faction_icons)
+        .cropped_to_action(
+            &replay,
+            font,
+            map_image.dimensions(),
+            &filename,
+            options.palette,
+        );
+    let frames: Vec<RgbImage> = [
+        RevealStage::Positions,
+        RevealStage::Players,
+        RevealStage::Winner,
+    ]
+    .into_iter()
+    .map(|stage| {
+        render_frame(
+            &replay,
+            font,
+            map_image,
+            &placements,
+            &filename,
+            stage,
+            options,
+        )
+    })
+    .collect();
+
+    encode_gif_within_budget(&frames, UPLOAD_BUDGET_BYTES)
+}
+
+/// Encode frames as a looping GIF, downscaling until the result fits in `budget` bytes
+pub fn encode_gif_within_budget(frames: &[RgbImage], budget: usize) -> Result<Vec<u8>, String> {
+    for scale in REVEAL_SCALES {
+        let bytes = encode_gif(frames, scale)?;
+        if bytes.len() <= budget {
+            return Ok(bytes);
+        }
+        tracing::warn!(
+            "Reveal GIF at {:.0}% is {} bytes, over budget of {}",
+            scale * 100.0,
+            bytes.len(),
+            budget
+        );
+    }
+    Err(format!("Reveal animation exceeds {} bytes", budget))
+}
+
+fn encode_gif(frames: &[RgbImage], scale: f32) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut buffer, GIF_SPEED);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| format!("Failed to encode image: {}", e))?;
+        for frame in frames {
+            let rgba = if scale < 1.0 {
+                let w = ((frame.width() as f32 * scale) as u32).max(1);
+                let h = ((frame.height() as f32 * scale) as u32).max(1);
+                DynamicImage::ImageRgb8(frame.clone())
+                    .resize_exact(w, h, image::imageops::FilterType::Triangle)
+                    .to_rgba8()
+            } else {
+                DynamicImage::ImageRgb8(frame.clone()).to_rgba8()
+            };
+            let delay = Delay::from_numer_denom_ms(REVEAL_FRAME_MS, 1);
+            encoder
+                .encode_frame(Frame::from_parts(rgba, 0, 0, delay))
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+        }
+    }
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, MapSpot, PlayerBuilder, Winner};
+    use crate::renderer::map::test_font;
+    use crate::renderer::{FactionIcons, MapLayout};
+    use image::AnimationDecoder;
+    use image::codecs::gif::GifDecoder;
+
+    fn test_replay() -> ReplayInfo {
+        let mut alice = PlayerBuilder::test_player("Alice", 1, 0, Faction::Men);
+        alice.spot = Some(MapSpot::TopLeft);
+        ReplayInfo::new("map wor rhun".to_string(), vec![alice])
+            .with_times(1700000000, 1700001000)
+            .with_winner(Winner::LeftTeam)
+    }
+
+    fn decode_frames(bytes: &[u8]) -> Vec<image::Frame> {
+        let decoder = GifDecoder::new(std::io::Cursor::new(bytes)).unwrap();
+        decoder.into_frames().collect_frames().unwrap()
+    }
+
+    #[test]
+    fn reveal_is_three_frame_animation() {
+        let map = RgbImage::from_pixel(200, 200, image::Rgb([40, 60, 40]));
+        let bytes = render_reveal(
+            &test_replay(),
+            RenderAssets {
+                font: &test_font(),
+                map_image: &map,
+                layout: &MapLayout::default(),
+                faction_icons: &FactionIcons::default(),
+            },
+            "test.BfME2Replay",
+            RenderOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(&bytes[..6], b"GIF89a");
+        let frames = decode_frames(&bytes);
+        assert_eq!(frames.len(), 3);
+        for frame in &frames {
+            assert_eq!(frame.buffer().dimensions(), (200, 200));
+            assert_eq!(frame.delay().numer_denom_ms(), (REVEAL_FRAME_MS, 1));
+        }
+        // Each stage adds information, so consecutive frames differ
+        assert_ne!(frames[0].buffer(), frames[1].buffer());
+        assert_ne!(frames[1].buffer(), frames[2].buffer());
+    }
+
+    #[test]
+    fn oversized_animation_is_downscaled_or_rejected() {
+        // Noise compresses badly, so the full-size encode is large
+        let noisy = RgbImage::from_fn(120, 120, |x, y| {
+            let v = (x.wrapping_mul(7919) ^ y.wrapping_mul(104729)) as u8;
+            image::Rgb([v, v.wrapping_mul(3), v.wrapping_mul(5)])
+        });
+        let frames = vec![noisy.clone(), noisy.clone(), noisy];
+
+        let full = encode_gif(&frames, 1.0).unwrap();
+        let half = encode_gif(&frames, 0.5).unwrap();
+        assert!(half.len() < full.len());
+
+        let fitted = encode_gif_within_budget(&frames, full.len() - 1).unwrap();
+        assert!(fitted.len() < full.len());
+        assert_eq!(decode_frames(&fitted)[0].buffer().width(), 90);
+
+        assert!(encode_gif_within_budget(&frames, 10).is_err());
+    }
+}
@@ -0,0 +1,420 @@
+use super::activity::fill_rect;
+use super::map::measure_text_width;
+use crate::models::{Faction, ReplayInfo, Side};
+use ab_glyph::{FontArc, PxScale};
+use image::{Rgb, RgbImage};
+use imageproc::drawing::draw_text_mut;
+use std::f32::consts::TAU;
+
+const IMAGE_WIDTH: u32 = 800;
+const IMAGE_HEIGHT: u32 = 460;
+const BACKGROUND: Rgb<u8> = Rgb([28, 30, 36]);
+const TEXT_COLOR: Rgb<u8> = Rgb([235, 235, 235]);
+const MUTED_TEXT_COLOR: Rgb<u8> = Rgb([170, 170, 170]);
+
+/// Donut geometry (left half of the image)
+const DONUT_CENTER: (i32, i32) = (200, 250);
+const DONUT_OUTER_RADIUS: f32 = 120.0;
+const DONUT_INNER_RADIUS: f32 = 62.0;
+
+/// Bar chart geometry (right half of the image)
+const BARS_LEFT: i32 = 520;
+const BARS_TOP: i32 = 110;
+const BAR_MAX_LENGTH: i32 = 200;
+const BAR_HEIGHT: i32 = 26;
+const BAR_GAP: i32 = 14;
+const BAR_COLOR: Rgb<u8> = Rgb([110, 160, 220]);
+
+/// Win slices in drawing order: left wins, right wins, games without a result
+const WIN_SLICES: [(&str, Rgb<u8>); 3] = [
+    ("Left", Rgb([80, 140, 230])),
+    ("Right", Rgb([225, 85, 70])),
+    ("No result", Rgb([130, 130, 130])),
+];
+
+/// Faction categories of the bar chart; `Random` counts players whose
+/// actual faction stayed unknown
+const FACTIONS: [Faction; 8] = [
+    Faction::Men,
+    Faction::Elves,
+    Faction::Dwarves,
+    Faction::Isengard,
+    Faction::Mordor,
+    Faction::Goblins,
+    Faction::Angmar,
+    Faction::Random,
+];
+
+/// Totals over an archive's games
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveStats {
+    pub games: usize,
+    /// In `WIN_SLICES` order
+    pub wins: [usize; 3],
+    /// In `FACTIONS` order
+    pub faction_picks: [usize; 8],
+    /// Mean over games with a known duration
+    pub average_duration_secs: Option<u32>,
+}
+
+impl ArchiveStats {
+    pub fn collect(replays: &[ReplayInfo]) -> Self {
+        let mut wins = [0; 3];
+        let mut faction_picks = [0; 8];
+        for replay in replays {
+            let slice = match replay.winner.side() {
+                Some(Side::Left) if !replay.game_crashed => 0,
+                Some(Side::Right) if !replay.game_crashed => 1,
+                _ => 2,
+            };
+            wins[slice] += 1;
+            for player in &replay.players {
+                let faction = player.display_faction();
+                if let Some(i) = FACTIONS.iter().position(|&f| f == faction) {
+                    faction_picks[i] += 1;
+                }
+            }
+        }
+        let durations: Vec<u64> = replays
+            .iter()
+            .filter_map(|r| r.duration_seconds().map(u64::from))
+            .collect();
+        let average_duration_secs = (!durations.is_empty())
+            .then(|| (durations.iter().sum::<u64>() / durations.len() as u64) as u32);
+        Self {
+            games: replays.len(),
+            wins,
+            faction_picks,
+            average_duration_secs,
+        }
+    }
+
+    /// `(label, games)` for left wins, right wins and games without a result
+    pub fn win_counts(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        WIN_SLICES
+            .iter()
+            .zip(self.wins)
+            .map(|((label, _), count)| (*label, count))
+    }
+
+    /// `(faction, players)` per faction category, `Random` for unknown picks
+    pub fn faction_counts(&self) -> impl Iterator<Item = (Faction, usize)> + '_ {
+        FACTIONS.into_iter().zip(self.faction_picks)
+    }
+}
+
+/// `(start, end)` angle of each slice in radians, clockwise from 12 o'clock.
+/// Zero counts get an empty slice; all-zero input gives all empty slices.
+pub(super) fn slice_angles(counts: &[usize]) -> Vec<(f32, f32)> {
+    let total: usize = counts.iter().sum();
+    let mut start = 0.0;
+    counts
+        .iter()
+        .map(|&count| {
+            let sweep = if total == 0 {
+                0.0
+            } else {
+                TAU * count as f32 / total as f32
+            };
+            let slice = (start, start + sweep);
+            start += sweep;
+            slice
+        })
+        .collect()
+}
+
+/// Bar lengths in pixels, the largest count spanning `max_length`
+pub(super) fn bar_lengths(counts: &[usize], max_length: i32) -> Vec<i32> {
+    let peak = counts.iter().copied().max().unwrap_or(0).max(1);
+    counts
+        .iter()
+        .map(|&count| (count as i64 * max_length as i64 / peak as i64) as i32)
+        .collect()
+}
+
+/// Point halfway through a slice's ring, where its percentage is drawn;
+/// `None` for empty slices
+pub(super) fn slice_label_point(
+    center: (i32, i32),
+    inner_radius: f32,
+    outer_radius: f32,
+    (start, end): (f32, f32),
+) -> Option<(i32, i32)> {
+    if end <= start {
+        return None;
+    }
+    let angle = (start + end) / 2.0;
+    let radius = (inner_radius + outer_radius) / 2.0;
+    Some((
+        center.0 + (radius * angle.sin()).round() as i32,
+        center.1 - (radius * angle.cos()).round() as i32,
+    ))
+}
+
+/// Whole-number percentage of `count` in `total`
+fn percent(count: usize, total: usize) -> usize {
+    (count * 100 + total / 2).checked_div(total).unwrap_or(0)
+}
+
+/// Fill the part of a ring between `start` and `end` (radians clockwise
+/// from 12 o'clock), clipped to the image
+fn fill_ring_slice(
+    img: &mut RgbImage,
+    center: (i32, i32),
+    inner_radius: f32,
+    outer_radius: f32,
+    (start, end): (f32, f32),
+    color: Rgb<u8>,
+) {
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    let r = outer_radius.ceil() as i32;
+    for y in (center.1 - r).max(0)..=(center.1 + r).min(h - 1) {
+        for x in (center.0 - r).max(0)..=(center.0 + r).min(w - 1) {
+            let (dx, dy) = ((x - center.0) as f32, (y - center.1) as f32);
+            let distance = dx.hypot(dy);
+            if distance < inner_radius || distance > outer_radius {
+                continue;
+            }
+            let angle = dx.atan2(-dy).rem_euclid(TAU);
+            if angle >= start && angle < end {
+                img.put_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
+
+/// Draw text centered on `(x, y)`
+fn draw_centered(
+    img: &mut RgbImage,
+    font: &FontArc,
+    scale: PxScale,
+    (x, y): (i32, i32),
+    color: Rgb<u8>,
+    text: &str,
+) {
+    let width = measure_text_width(text, font, scale);
+    let top = y - (scale.y / 2.0) as i32;
+    draw_text_mut(img, color, x - width / 2, top, scale, font, text);
+}
+
+/// Format seconds as "MM:SS" or "H:MM:SS"
+fn format_duration(secs: u32) -> String {
+    let (hours, mins, secs) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, mins, secs)
+    } else {
+        format!("{:02}:{:02}", mins, secs)
+    }
+}
+
+/// Render one summary graphic for an archive: wins by side as a donut,
+/// faction picks as bars, total games and average duration. JPEG encoded.
+pub fn render_archive_stats(replays: &[ReplayInfo], font: &FontArc) -> Result<Vec<u8>, String> {
+    let stats = ArchiveStats::collect(replays);
+    let mut img = RgbImage::from_pixel(IMAGE_WIDTH, IMAGE_HEIGHT, BACKGROUND);
+    let title_scale = PxScale::from(30.0);
+    let text_scale = PxScale::from(20.0);
+
+    // Header
+    let duration = stats
+        .average_duration_secs
+        .map_or_else(|| "-".to_string(), format_duration);
+    draw_text_mut(
+        &mut img,
+        TEXT_COLOR,
+        24,
+        18,
+        title_scale,
+        font,
+        &format!("{} games", stats.games),
+    );
+    draw_text_mut(
+        &mut img,
+        MUTED_TEXT_COLOR,
+        24,
+        56,
+        text_scale,
+        font,
+        &format!("Average duration: {}", duration),
+    );
+
+    // Wins by side
+    let slices = slice_angles(&stats.wins);
+    for (&slice, (_, color)) in slices.iter().zip(WIN_SLICES) {
+        fill_ring_slice(
+            &mut img,
+            DONUT_CENTER,
+            DONUT_INNER_RADIUS,
+            DONUT_OUTER_RADIUS,
+            slice,
+            color,
+        );
+    }
+    for (&slice, &count) in slices.iter().zip(&stats.wins) {
+        if let Some(point) =
+            slice_label_point(DONUT_CENTER, DONUT_INNER_RADIUS, DONUT_OUTER_RADIUS, slice)
+        {
+            let label = format!("{}%", percent(count, stats.games));
+            draw_centered(&mut img, font, text_scale, point, TEXT_COLOR, &label);
+        }
+    }
+    draw_centered(&mut img, font, text_scale, DONUT_CENTER, TEXT_COLOR, "Wins");
+    let legend_y = DONUT_CENTER.1 + DONUT_OUTER_RADIUS as i32 + 24;
+    let mut legend_x = DONUT_CENTER.0 - DONUT_OUTER_RADIUS as i32 - 40;
+    for ((name, color), count) in WIN_SLICES.iter().zip(stats.wins) {
+        fill_rect(
+            &mut img,
+            legend_x,
+            legend_y + 4,
+            legend_x + 11,
+            legend_y + 15,
+            *color,
+        );
+        let text = format!("{} {}", name, count);
+        draw_text_mut(
+            &mut img,
+            TEXT_COLOR,
+            legend_x + 16,
+            legend_y,
+            text_scale,
+            font,
+            &text,
+        );
+        legend_x += 16 + measure_text_width(&text, font, text_scale) + 20;
+    }
+
+    // Faction picks
+    let lengths = bar_lengths(&stats.faction_picks, BAR_MAX_LENGTH);
+    for (i, ((faction, count), length)) in FACTIONS
+        .iter()
+        .zip(stats.faction_picks)
+        .zip(lengths)
+        .enumerate()
+    {
+        let top = BARS_TOP + i as i32 * (BAR_HEIGHT + BAR_GAP);
+        let name = faction.to_string();
+        let name_x = BARS_LEFT - 12 - measure_text_width(&name, font, text_scale);
+        draw_text_mut(
+            &mut img,
+            TEXT_COLOR,
+            name_x,
+            top + 2,
+            text_scale,
+            font,
+            &name,
+        );
+        if length > 0 {
+            fill_rect(
+                &mut img,
+                BARS_LEFT,
+                top,
+                BARS_LEFT + length - 1,
+                top + BAR_HEIGHT - 1,
+                BAR_COLOR,
+            );
+        }
+        draw_text_mut(
+            &mut img,
+            MUTED_TEXT_COLOR,
+            BARS_LEFT + length + 8,
+            top + 2,
+            text_scale,
+            font,
+            &count.to_string(),
+        );
+    }
+
+    let mut buffer = Vec::new();
+    let mut cursor = std::io::Cursor::new(&mut buffer);
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut cursor, 85)
+        .encode(
+            &img,
+            img.width(),
+            img.height(),
+            image::ExtendedColorType::Rgb8,
+        )
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PlayerBuilder, Winner};
+    use std::f32::consts::PI;
+
+    fn replay(winner: Winner, factions: &[Faction], secs: u32) -> ReplayInfo {
+        let players = factions
+            .iter()
+            .enumerate()
+            .map(|(i, &faction)| {
+                PlayerBuilder::test_player(&format!("P{}", i), 1, i as u8, faction)
+            })
+            .collect();
+        ReplayInfo::new("map wor rhun".to_string(), players)
+            .with_winner(winner)
+            .with_times(1_700_000_000, 1_700_000_000 + secs)
+    }
+
+    #[test]
+    fn stats_count_wins_picks_and_duration() {
+        let replays = vec![
+            replay(Winner::LeftTeam, &[Faction::Men, Faction::Mordor], 600),
+            replay(
+                Winner::LikelyRightTeam,
+                &[Faction::Men, Faction::Elves],
+                900,
+            ),
+            replay(Winner::LeftTeam, &[Faction::Random], 1200).with_game_crashed(true),
+        ];
+        let stats = ArchiveStats::collect(&replays);
+        assert_eq!(stats.games, 3);
+        assert_eq!(stats.wins, [1, 1, 1]);
+        assert_eq!(stats.faction_picks, [2, 1, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(stats.average_duration_secs, Some(900));
+        assert_eq!(ArchiveStats::collect(&[]).average_duration_secs, None);
+    }
+
+    #[test]
+    fn slices_split_the_circle_by_count() {
+        let slices = slice_angles(&[1, 0, 3]);
+        assert_eq!(slices[0], (0.0, PI / 2.0));
+        // Zero counts are empty and get no label
+        assert_eq!(slices[1], (PI / 2.0, PI / 2.0));
+        assert_eq!(slice_label_point((0, 0), 10.0, 30.0, slices[1]), None);
+        assert!((slices[2].1 - TAU).abs() < 1e-5);
+        // All zero: nothing to draw
+        assert!(slice_angles(&[0, 0]).iter().all(|(s, e)| s == e));
+    }
+
+    #[test]
+    fn slice_labels_sit_mid_ring() {
+        // First quarter (12 to 3 o'clock): label up and to the right
+        let point = slice_label_point((100, 100), 10.0, 30.0, (0.0, PI / 2.0)).unwrap();
+        assert_eq!(point, (114, 86));
+        // Full circle of a single category: label at 6 o'clock
+        let point = slice_label_point((100, 100), 10.0, 30.0, (0.0, TAU)).unwrap();
+        assert_eq!(point, (100, 120));
+    }
+
+    #[test]
+    fn bars_scale_to_the_largest_count() {
+        assert_eq!(bar_lengths(&[4, 0, 2, 1], 200), vec![200, 0, 100, 50]);
+        assert_eq!(bar_lengths(&[0, 0], 200), vec![0, 0]);
+        assert_eq!(percent(1, 3), 33);
+        assert_eq!(percent(2, 3), 67);
+        assert_eq!(percent(0, 0), 0);
+    }
+
+    #[test]
+    fn ring_slice_fills_only_its_quarter() {
+        let mut img = RgbImage::from_pixel(21, 21, BACKGROUND);
+        let color = Rgb([255, 0, 0]);
+        fill_ring_slice(&mut img, (10, 10), 3.0, 9.0, (0.0, PI / 2.0), color);
+        // Up-right quadrant filled, others untouched, hole kept
+        assert_eq!(*img.get_pixel(15, 5), color);
+        assert_eq!(*img.get_pixel(5, 5), BACKGROUND);
+        assert_eq!(*img.get_pixel(15, 15), BACKGROUND);
+        assert_eq!(*img.get_pixel(11, 9), BACKGROUND);
+    }
+}
@@ -0,0 +1,253 @@
+//! SVG output: the map embedded as a JPEG under the frame layout drawn as
+//! vector text and boxes, so it stays sharp when zoomed
+
+use super::frame_layout::{LayoutItem, label_placements, layout_frame};
+use super::map::{RenderAssets, RenderOptions, RevealStage, frame_map, shown_replay};
+use super::markup::{base64, escape_markup};
+use super::winner::{icon_cells, sprite};
+use crate::models::ReplayInfo;
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use image::Rgb;
+use std::fmt::Write;
+
+/// JPEG quality of the embedded map
+const SVG_MAP_QUALITY: u8 = 85;
+
+/// Font of the text; viewers without Noto Sans fall back to their own
+const SVG_FONT_FAMILY: &str = "'Noto Sans', sans-serif";
+
+/// Render the map visualization as an SVG document. Player labels, the center
+/// info and the observers match [`render_map`](super::render_map); the
+/// activity strip and lobby panel are raster-only and left out.
+pub fn render_map_svg(
+    replay: &ReplayInfo,
+    assets: RenderAssets<'_>,
+    filename: &str,
+    options: RenderOptions,
+) -> Result<String, String> {
+    let RenderAssets {
+        font, map_image, ..
+    } = assets;
+    let (replay, filename) = shown_replay(replay, filename, options);
+    let placements = label_placements(&replay, assets.layout, options)
+        .with_faction_icons(assets.faction_icons)
+        .cropped_to_action(
+            &replay,
+            font,
+            map_image.dimensions(),
+            &filename,
+            options.palette,
+        );
+    let items = layout_frame(
+        &replay,
+        font,
+        &placements,
+        map_image.dimensions(),
+        &filename,
+        RevealStage::Winner,
+        options.palette,
+    );
+    let map_image = frame_map(map_image, &placements);
+    let (width, height) = map_image.dimensions();
+
+    let mut jpeg = Vec::new();
+    let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, SVG_MAP_QUALITY);
+    map_image
+        .write_with_encoder(encoder)
+        .map_err(|e| format!("Failed to encode map image: {}", e))?;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\">",
+        w = width,
+        h = height
+    );
+    let _ = writeln!(
+        svg,
+        "<image width=\"{}\" height=\"{}\" href=\"data:image/jpeg;base64,{}\"/>",
+        width,
+        height,
+        base64(&jpeg)
+    );
+    let _ = writeln!(
+        svg,
+        "<g font-family=\"{}\" font-weight=\"bold\">",
+        SVG_FONT_FAMILY
+    );
+    for item in &items {
+        svg_element(&mut svg, item, font);
+    }
+    svg.push_str("</g>\n</svg>\n");
+    Ok(svg)
+}
+
+/// Append one item as SVG elements
+fn svg_element(svg: &mut String, item: &LayoutItem, font: &FontArc) {
+    match item {
+        LayoutItem::Rect { bounds, color } => {
+            let [r, g, b, a] = *color;
+            let _ = write!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"",
+                bounds.x,
+                bounds.y,
+                bounds.w,
+                bounds.h,
+                hex(Rgb([r, g, b]))
+            );
+            if a < 255 {
+                let _ = write!(svg, " fill-opacity=\"{:.3}\"", a as f32 / 255.0);
+            }
+            svg.push_str("/>\n");
+        }
+        LayoutItem::Text {
+            text,
+            x,
+            y,
+            scale,
+            color,
+            ..
+        } => {
+            // Raster text hangs from its top edge; SVG text sits on its baseline
+            let baseline = *y as f32 + font.as_scaled(*scale).ascent();
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{:.1}\" font-size=\"{:.1}\" fill=\"{}\">{}</text>",
+                x,
+                baseline,
+                font_size(font, *scale),
+                hex(*color),
+                escape_markup(text)
+            );
+        }
+        LayoutItem::Icon {
+            icon,
+            x,
+            baseline,
+            height,
+            color,
+        } => {
+            let Some(sprite) = sprite(*icon) else {
+                return;
+            };
+            let _ = writeln!(svg, "<g fill=\"{}\">", hex(*color));
+            for (cx, cy, side) in icon_cells(sprite, *x, *baseline, *height) {
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                    cx, cy, side, side
+                );
+            }
+            svg.push_str("</g>\n");
+        }
+        LayoutItem::FactionIcon { image, x, y } => {
+            let mut png = Vec::new();
+            let encoder = image::codecs::png::PngEncoder::new(&mut png);
+            if image.write_with_encoder(encoder).is_ok() {
+                let _ = writeln!(
+                    svg,
+                    "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                     href=\"data:image/png;base64,{}\"/>",
+                    x,
+                    y,
+                    image.width(),
+                    image.height(),
+                    base64(&png)
+                );
+            }
+        }
+    }
+}
+
+/// SVG font size (em size in pixels) matching a glyph scale, which spans
+/// ascent to descent rather than the em square
+fn font_size(font: &FontArc, scale: PxScale) -> f32 {
+    match font.units_per_em() {
+        Some(units_per_em) => scale.y * units_per_em / font.height_unscaled(),
+        None => scale.y,
+    }
+}
+
+fn hex(color: Rgb<u8>) -> String {
+    let [r, g, b] = color.0;
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Faction, MapSpot, PlayerBuilder, Winner};
+    use crate::renderer::map::test_font;
+    use crate::renderer::{FactionIcons, MapLayout};
+    use image::RgbImage;
+
+    #[test]
+    fn svg_carries_the_map_and_every_label() {
+        let mut tom = PlayerBuilder::test_player("Tom & <Jerry>", 1, 0, Faction::Men);
+        tom.spot = Some(MapSpot::TopLeft);
+        let replay =
+            ReplayInfo::new("map wor rhun".to_string(), vec![tom]).with_winner(Winner::LeftTeam);
+        let font = test_font();
+        let background = RgbImage::from_pixel(1000, 1000, Rgb([40, 60, 40]));
+        let svg = render_map_svg(
+            &replay,
+            RenderAssets {
+                font: &font,
+                map_image: &background,
+                layout: &MapLayout::default(),
+                faction_icons: &FactionIcons::default(),
+            },
+            "final.BfME2Replay",
+            RenderOptions::default(),
+        )
+        .unwrap();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1000\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("href=\"data:image/jpeg;base64,").count(), 1);
+        assert!(svg.contains(">Tom &amp; &lt;Jerry&gt;</text>"));
+        assert!(svg.contains(">Men</text>"));
+        assert!(svg.contains(">final</text>"));
+        // Men's labels sit on the good side's blue-tinted backdrop
+        assert!(svg.contains("fill=\"#0a163e\" fill-opacity=\"0.706\""));
+        // The trophy is drawn from the sprite cells in the winner color
+        assert!(svg.contains("<g fill=\"#ffd700\">\n<rect"));
+    }
+
+    #[test]
+    fn anonymized_svg_shows_numbers_and_the_map() {
+        let mut tom = PlayerBuilder::test_player("Tom", 1, 0, Faction::Men);
+        tom.uid = Some("1A2B3C4D".to_string());
+        tom.spot = Some(MapSpot::TopLeft);
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![tom]);
+        let svg = render_map_svg(
+            &replay,
+            RenderAssets {
+                font: &test_font(),
+                map_image: &RgbImage::from_pixel(1000, 1000, Rgb([40, 60, 40])),
+                layout: &MapLayout::default(),
+                faction_icons: &FactionIcons::default(),
+            },
+            "tom_final.BfME2Replay",
+            RenderOptions {
+                anonymize: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(svg.contains(">Player 1</text>"));
+        assert!(svg.contains(">map wor rhun</text>"));
+        assert!(!svg.contains("Tom") && !svg.contains("tom_final"));
+    }
+
+    #[test]
+    fn font_size_converts_glyph_scale_to_em() {
+        let font = test_font();
+        let size = font_size(&font, PxScale::from(24.0));
+        // Noto Sans spans about 1.36 em from ascent to descent
+        assert!((17.0..18.5).contains(&size), "{}", size);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::models::{GameEnding, ReplayInfo, Winner};
+use image::{Rgb, RgbImage};
+
+/// Icon drawn before the winner line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum WinnerIcon {
+    /// Certain winner
+    TrophyFilled,
+    /// Likely winner
+    TrophyOutline,
+    /// No result (crashed or abandoned)
+    BrokenFlag,
+}
+
+impl WinnerIcon {
+    /// Text drawn instead of the icon when the atlas has no sprite for it
+    pub(super) fn fallback_text(self) -> &'static str {
+        match self {
+            WinnerIcon::TrophyFilled => "✓",
+            WinnerIcon::TrophyOutline => "~",
+            WinnerIcon::BrokenFlag => "✕",
+        }
+    }
+}
+
+/// Color and icon of the winner line for one outcome
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct WinnerStyle {
+    pub color: Rgb<u8>,
+    pub icon: Option<WinnerIcon>,
+}
+
+/// Style per outcome, `None` when no winner line is drawn. The match is
+/// exhaustive, so a new `Winner` variant fails to build until it gets a style.
+pub(super) fn winner_style(winner: &Winner) -> Option<WinnerStyle> {
+    let (color, icon) = match winner {
+        Winner::LeftTeam | Winner::RightTeam => ([255, 215, 0], Some(WinnerIcon::TrophyFilled)),
+        Winner::LikelyLeftTeam | Winner::LikelyRightTeam => {
+            ([255, 200, 80], Some(WinnerIcon::TrophyOutline))
+        }
+        Winner::Draw => ([190, 200, 215], None),
+        Winner::NotConcluded => ([200, 100, 100], Some(WinnerIcon::BrokenFlag)),
+        Winner::InProgress => ([200, 200, 200], None),
+        Winner::Unknown => return None,
+    };
+    Some(WinnerStyle {
+        color: Rgb(color),
+        icon,
+    })
+}
+
+/// Winner line text and style for a replay (a crash overrides the parsed winner)
+pub(super) fn winner_line(replay: &ReplayInfo) -> Option<(String, WinnerStyle)> {
+    let winner = if replay.game_crashed {
+        Winner::NotConcluded
+    } else if replay.is_partial {
+        Winner::InProgress
+    } else {
+        replay.winner.clone()
+    };
+    let style = winner_style(&winner)?;
+    let text = match winner {
+        Winner::InProgress => winner.display_text().to_string(),
+        Winner::LeftTeam | Winner::RightTeam if replay.ending == GameEnding::Surrender => {
+            format!("Winner: {} (surrender)", winner.display_text())
+        }
+        _ => winner.line_text(),
+    };
+    Some((text, style))
+}
+
+/// Small note under the winner line naming the rule that decided it, e.g.
+/// "via EndGame"; `None` when neither a side nor a draw was decided or the
+/// game did not finish
+pub(super) fn winner_source_line(replay: &ReplayInfo) -> Option<String> {
+    let decided = replay.winner.side().is_some() || replay.winner == Winner::Draw;
+    if replay.game_crashed || replay.is_partial || !decided {
+        return None;
+    }
+    let source = replay.verdict.source?;
+    Some(format!("via {}", source.display_text()))
+}
+
+/// Sprite rows, `#` for a filled cell
+type Sprite = [&'static str; 11];
+
+const TROPHY_FILLED: Sprite = [
+    "###########",
+    "#.#######.#",
+    "#.#######.#",
+    ".#########.",
+    "..#######..",
+    "...#####...",
+    "....###....",
+    ".....#.....",
+    ".....#.....",
+    "...#####...",
+    "..#######..",
+];
+
+const TROPHY_OUTLINE: Sprite = [
+    "###########",
+    "#.#.....#.#",
+    "#.#.....#.#",
+    ".##.....##.",
+    "..#.....#..",
+    "...#...#...",
+    "....#.#....",
+    ".....#.....",
+    ".....#.....",
+    "...#####...",
+    "..#######..",
+];
+
+const BROKEN_FLAG: Sprite = [
+    "#..........",
+    "#######....",
+    "#####.###..",
+    "####.#####.",
+    "#####.###..",
+    "#######....",
+    "#..........",
+    "#..........",
+    "...........",
+    "#..........",
+    "#..........",
+];
+
+/// Built-in icon atlas
+const ICON_ATLAS: &[(WinnerIcon, &Sprite)] = &[
+    (WinnerIcon::TrophyFilled, &TROPHY_FILLED),
+    (WinnerIcon::TrophyOutline, &TROPHY_OUTLINE),
+    (WinnerIcon::BrokenFlag, &BROKEN_FLAG),
+];
+
+pub(super) fn sprite(icon: WinnerIcon) -> Option<&'static Sprite> {
+    ICON_ATLAS
+        .iter()
+        .find(|(entry, _)| *entry == icon)
+        .map(|(_, sprite)| *sprite)
+}
+
+/// Side length in pixels of an icon drawn `height` pixels tall
+pub(super) fn icon_size(height: i32) -> i32 {
+    let cell = (height / TROPHY_FILLED.len() as i32).max(1);
+    cell * TROPHY_FILLED.len() as i32
+}
+
+/// Filled squares `(x, y, side)` of a sprite whose bottom edge sits on
+/// `baseline`, scaled to about `height` pixels
+pub(super) fn icon_cells(
+    sprite: &Sprite,
+    x: i32,
+    baseline: i32,
+    height: i32,
+) -> Vec<(i32, i32, i32)> {
+    let cell = icon_size(height) / sprite.len() as i32;
+    let top = baseline - cell * sprite.len() as i32;
+    sprite
+        .iter()
+        .enumerate()
+        .flat_map(|(row, line)| {
+            line.chars()
+                .enumerate()
+                .filter(|&(_, c)| c == '#')
+                .map(move |(col, _)| (x + col as i32 * cell, top + row as i32 * cell, cell))
+        })
+        .collect()
+}
+
+/// Draw a sprite whose bottom edge sits on `baseline`, scaled to about
+/// `height` pixels, clipped to the image
+pub(super) fn draw_icon(
+    img: &mut RgbImage,
+    sprite: &Sprite,
+    x: i32,
+    baseline: i32,
+    height: i32,
+    color: Rgb<u8>,
+) {
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    for (x0, y0, cell) in icon_cells(sprite, x, baseline, height) {
+        for py in y0.max(0)..(y0 + cell).min(h) {
+            for px in x0.max(0)..(x0 + cell).min(w) {
+                img.put_pixel(px as u32, py as u32, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Confidence, Side, WinnerSource, WinnerVerdict};
+
+    /// Every variant; the match fails to build when one is added
+    fn all_winners() -> Vec<Winner> {
+        let winners = vec![
+            Winner::LeftTeam,
+            Winner::RightTeam,
+            Winner::LikelyLeftTeam,
+            Winner::LikelyRightTeam,
+            Winner::Draw,
+            Winner::NotConcluded,
+            Winner::InProgress,
+            Winner::Unknown,
+        ];
+        for winner in &winners {
+            match winner {
+                Winner::LeftTeam
+                | Winner::RightTeam
+                | Winner::LikelyLeftTeam
+                | Winner::LikelyRightTeam
+                | Winner::Draw
+                | Winner::NotConcluded
+                | Winner::InProgress
+                | Winner::Unknown => {}
+            }
+        }
+        winners
+    }
+
+    #[test]
+    fn every_known_outcome_has_a_style() {
+        for winner in all_winners() {
+            let style = winner_style(&winner);
+            assert_eq!(style.is_none(), winner == Winner::Unknown, "{:?}", winner);
+            if let Some(WinnerStyle {
+                icon: Some(icon), ..
+            }) = style
+            {
+                assert!(sprite(icon).is_some(), "{:?} has no sprite", icon);
+            }
+        }
+        assert_eq!(
+            winner_style(&Winner::LeftTeam).unwrap().icon,
+            Some(WinnerIcon::TrophyFilled)
+        );
+        assert_eq!(
+            winner_style(&Winner::LikelyRightTeam).unwrap().icon,
+            Some(WinnerIcon::TrophyOutline)
+        );
+        assert_eq!(
+            winner_style(&Winner::NotConcluded).unwrap().icon,
+            Some(WinnerIcon::BrokenFlag)
+        );
+    }
+
+    #[test]
+    fn crash_overrides_parsed_winner() {
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![])
+            .with_winner(Winner::LeftTeam)
+            .with_game_crashed(true);
+        let (text, style) = winner_line(&replay).unwrap();
+        assert_eq!(text, "Winner: Not Concluded");
+        assert_eq!(style.icon, Some(WinnerIcon::BrokenFlag));
+
+        let surrender = ReplayInfo::new("map wor rhun".to_string(), vec![])
+            .with_winner(Winner::RightTeam)
+            .with_ending(GameEnding::Surrender);
+        assert_eq!(
+            winner_line(&surrender).unwrap().0,
+            "Winner: Right Team (surrender)"
+        );
+    }
+
+    #[test]
+    fn source_note_follows_the_verdict() {
+        let replay = ReplayInfo::new("map wor rhun".to_string(), vec![]);
+        assert_eq!(winner_source_line(&replay), None);
+
+        let decided = replay.with_verdict(WinnerVerdict::decided(
+            Side::Left,
+            Confidence::High,
+            WinnerSource::MajorityDefeat,
+        ));
+        assert_eq!(
+            winner_line(&decided).unwrap().0,
+            "Winner: Left Team (likely)"
+        );
+        assert_eq!(
+            winner_source_line(&decided).as_deref(),
+            Some("via majority defeated")
+        );
+        assert_eq!(winner_source_line(&decided.with_game_crashed(true)), None);
+    }
+
+    #[test]
+    fn draw_reads_as_a_result_in_a_neutral_color() {
+        let draw =
+            ReplayInfo::new("map wor rhun".to_string(), vec![]).with_verdict(WinnerVerdict::draw());
+        let (text, style) = winner_line(&draw).unwrap();
+        assert_eq!(text, "Result: Draw");
+        assert_eq!(style.icon, None);
+        let [r, g, b] = style.color.0;
+        assert!(
+            r.abs_diff(g) < 30 && g.abs_diff(b) < 30,
+            "{:?}",
+            style.color
+        );
+        assert_eq!(winner_source_line(&draw).as_deref(), Some("via EndGame"));
+    }
+
+    #[test]
+    fn icon_sits_on_the_baseline() {
+        let mut img = RgbImage::from_pixel(40, 40, Rgb([0, 0, 0]));
+        let color = Rgb([255, 215, 0]);
+        draw_icon(&mut img, &TROPHY_FILLED, 5, 30, 22, color);
+        // 22px tall -> 2px cells, 22 rows ending just above y = 30
+        assert_eq!(*img.get_pixel(5, 8), color);
+        assert_eq!(*img.get_pixel(5, 7), Rgb([0, 0, 0]));
+        assert_eq!(*img.get_pixel(15, 29), color);
+        assert!((30..40).all(|y| (0..40).all(|x| *img.get_pixel(x, y) == Rgb([0, 0, 0]))));
+    }
+}
@@ -72,6 +72,113 @@ fn test_zip_empty() {
     assert_eq!(archive.len(), 0);
 }
 
+#[test]
+fn test_nested_zip_replays_are_extracted() {
+    use dcreplaybot::bot::extract_replays_from_zip;
+
+    let replay = build_test_replay_bytes("map wor rhun");
+    let inner = build_zip(&[("inner.BfME2Replay", &replay)]);
+    let innermost = build_zip(&[("deep.zip", &inner)]);
+    let zip_data = build_zip(&[
+        ("outer.BfME2Replay", &replay),
+        ("nested/games.zip", &inner),
+        ("too_deep.zip", &innermost),
+    ]);
+
+    let (replays, total) = extract_replays_from_zip(&zip_data).unwrap();
+    let names: Vec<&str> = replays.iter().map(|(name, _)| name.as_str()).collect();
+    // One level of nesting is searched; an archive inside a nested archive is not
+    assert_eq!(names, vec!["outer.BfME2Replay", "inner.BfME2Replay"]);
+    assert_eq!(total, 2);
+    assert_eq!(replays[1].1, replay);
+}
+
+#[test]
+fn test_zip_replay_aliases_and_sniffed_entries_are_extracted() {
+    use dcreplaybot::bot::extract_replays_from_zip;
+
+    let replay = build_test_replay_bytes("map wor rhun");
+    let zip_data = build_zip(&[
+        ("a.rep", &replay),
+        ("b.BfME2ReplayUncompressed", &replay),
+        ("renamed.txt", &replay),
+        ("readme.txt", b"BFME2 notes, not a replay"),
+        ("short", b"BF"),
+    ]);
+
+    let (replays, total) = extract_replays_from_zip(&zip_data).unwrap();
+    let names: Vec<&str> = replays.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["a.rep", "b.BfME2ReplayUncompressed", "renamed.txt"]
+    );
+    assert_eq!(total, 3);
+    // The sniffed head is kept in front of the rest of the file
+    assert_eq!(replays[2].1, replay);
+}
+
+#[test]
+fn test_zip_with_too_many_entries_stops_at_cap() {
+    use dcreplaybot::bot::extract_replays_from_zip;
+
+    let replay = build_test_replay_bytes("map wor rhun");
+    let names: Vec<String> = (0..10_050).map(|i| format!("junk_{}.txt", i)).collect();
+    let mut files: Vec<(&str, &[u8])> = vec![("first.BfME2Replay", &replay)];
+    files.extend(names.iter().map(|name| (name.as_str(), &b""[..])));
+    files.push(("after_cap.BfME2Replay", &replay));
+    let zip_data = build_zip(&files);
+
+    let (replays, total) = extract_replays_from_zip(&zip_data).unwrap();
+    // Entries past the cap are never looked at
+    assert_eq!(replays.len(), 1);
+    assert_eq!(replays[0].0, "first.BfME2Replay");
+    assert_eq!(total, 1);
+}
+
+#[test]
+fn test_encrypted_zip_is_reported_as_password_protected() {
+    use dcreplaybot::bot::{ArchiveError, extract_replays_from_zip};
+
+    let replay = build_test_replay_bytes("map wor rhun");
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .with_aes_encryption(zip::AesMode::Aes256, "secret");
+    zip.start_file("locked.BfME2Replay", options).unwrap();
+    zip.write_all(&replay).unwrap();
+    let zip_data = zip.finish().unwrap().into_inner();
+
+    assert_eq!(
+        extract_replays_from_zip(&zip_data),
+        Err(ArchiveError::Encrypted)
+    );
+
+    // Readable replays next to a locked one are still extracted
+    let mixed = build_zip(&[("open.BfME2Replay", &replay), ("locked.zip", &zip_data)]);
+    let (replays, total) = extract_replays_from_zip(&mixed).unwrap();
+    assert_eq!(replays.len(), 1);
+    assert_eq!(total, 1);
+}
+
+#[test]
+fn test_truncated_zip_is_reported_as_corrupted() {
+    use dcreplaybot::bot::{ArchiveError, extract_replays_from_zip};
+
+    let replay = build_test_replay_bytes("map wor rhun");
+    let zip_data = build_zip(&[("game.BfME2Replay", &replay)]);
+    let truncated = &zip_data[..zip_data.len() / 2];
+
+    let result = extract_replays_from_zip(truncated);
+    assert!(
+        matches!(result, Err(ArchiveError::Corrupted(_))),
+        "{:?}",
+        result.map(|(_, total)| total)
+    );
+
+    // A zip without replays is not an error
+    let empty = build_zip(&[("readme.txt", b"hi")]);
+    assert_eq!(extract_replays_from_zip(&empty), Ok((Vec::new(), 0)));
+}
+
 #[test]
 fn test_render_map_smoke() {
     use std::path::Path;
@@ -92,9 +199,9 @@ fn test_render_map_smoke() {
         return;
     };
 
-    // Load map image
-    let map_image = dcreplaybot::renderer::load_map_image("map wor rhun", assets_path);
-    let Ok(map_image) = map_image else {
+    // Load map image and layout
+    let map = dcreplaybot::renderer::load_map("map wor rhun", assets_path);
+    let Ok((map_image, layout)) = map else {
         return;
     };
 
@@ -102,7 +209,14 @@ fn test_render_map_smoke() {
     let replay = dcreplaybot::models::ReplayInfo::new("map wor rhun".to_string(), vec![]);
 
     // Render
-    let result = dcreplaybot::renderer::render_map(&replay, &font, &map_image, "test.BfME2Replay");
+    let options = dcreplaybot::renderer::RenderOptions::default();
+    let assets = dcreplaybot::renderer::RenderAssets {
+        font: &font,
+        map_image: &map_image,
+        layout: &layout,
+        faction_icons: &dcreplaybot::renderer::FactionIcons::default(),
+    };
+    let result = dcreplaybot::renderer::render_map(&replay, assets, "test.BfME2Replay", options);
     assert!(result.is_ok());
 
     let bytes = result.unwrap();
@@ -110,4 +224,61 @@ fn test_render_map_smoke() {
     assert!(bytes.len() > 2);
     assert_eq!(bytes[0], 0xFF);
     assert_eq!(bytes[1], 0xD8);
+
+    // Every render stage reports its time
+    let mut timings = dcreplaybot::models::TimingBreakdown::default();
+    dcreplaybot::renderer::render_map_timed(
+        &replay,
+        assets,
+        "test.BfME2Replay",
+        options,
+        &mut timings,
+    )
+    .unwrap();
+    assert!(!timings.layout.is_zero());
+    assert!(!timings.drawing.is_zero());
+    assert!(!timings.encoding.is_zero());
+}
+
+#[test]
+fn test_render_archive_stats_smoke() {
+    let font_path = std::path::Path::new("assets")
+        .join("fonts")
+        .join("NotoSans-Bold.ttf");
+    let Ok(font_data) = std::fs::read(font_path) else {
+        return;
+    };
+    let font = ab_glyph::FontArc::try_from_vec(font_data).unwrap();
+
+    // Parsed games from an archive, plus the empty case
+    let zip = build_zip(&[
+        ("a.BfME2Replay", &build_test_replay_bytes("map wor rhun")),
+        ("b.BfME2Replay", &build_test_replay_bytes("map wor rhun")),
+    ]);
+    let (replays, _) = dcreplaybot::bot::extract_replays_from_zip(&zip).unwrap();
+    let infos: Vec<_> = replays
+        .iter()
+        .filter_map(|(_, bytes)| dcreplaybot::parser::parse_replay(bytes).ok())
+        .collect();
+    assert_eq!(infos.len(), 2);
+
+    for games in [&infos[..], &[]] {
+        let bytes = dcreplaybot::renderer::render_archive_stats(games, &font).unwrap();
+        assert_eq!(&bytes[..2], &[0xFF, 0xD8]);
+    }
+}
+
+#[test]
+fn test_golden_corpus() {
+    use dcreplaybot::golden::{GOLDEN_DIR_ENV, GOLDEN_REGENERATE_ENV, check_corpus};
+
+    // Skip unless a corpus is configured (replays are private, not in the repo)
+    let Ok(dir) = std::env::var(GOLDEN_DIR_ENV) else {
+        return;
+    };
+    let regenerate = std::env::var(GOLDEN_REGENERATE_ENV).is_ok_and(|v| v == "1");
+
+    let report = check_corpus(std::path::Path::new(&dir), regenerate).unwrap();
+    assert!(report.checked > 0, "No replays found in {}", dir);
+    assert!(report.is_clean(), "{}", report);
 }